@@ -1,18 +1,8 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
-use oxigraph::model::*;
 use rsp_rs::RSPEngine;
+use rsp_rs::testing::generate_quad;
 use std::time::Instant;
 
-/// Generate a quad for benchmarking
-fn generate_quad(subject_id: usize, property_id: usize, object_id: usize) -> Quad {
-    Quad::new(
-        NamedNode::new(&format!("http://example.org/sensor{}", subject_id)).unwrap(),
-        NamedNode::new(&format!("http://example.org/property{}", property_id)).unwrap(),
-        Literal::new_simple_literal(&format!("value_{}", object_id)),
-        GraphName::DefaultGraph,
-    )
-}
-
 /// Get CPU metrics using sysinfo
 fn get_cpu_metrics() -> (f32, usize) {
     let mut sys = sysinfo::System::new_all();
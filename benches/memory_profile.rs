@@ -1,19 +1,9 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
-use oxigraph::model::*;
 use rsp_rs::RSPEngine;
+use rsp_rs::testing::generate_quad;
 use std::time::Instant;
 use sysinfo::System;
 
-/// Generate a quad for benchmarking
-fn generate_quad(subject_id: usize, property_id: usize, object_id: usize) -> Quad {
-    Quad::new(
-        NamedNode::new(&format!("http://example.org/sensor{}", subject_id)).unwrap(),
-        NamedNode::new(&format!("http://example.org/property{}", property_id)).unwrap(),
-        Literal::new_simple_literal(&format!("value_{}", object_id)),
-        GraphName::DefaultGraph,
-    )
-}
-
 /// Get current memory usage in MB
 fn get_memory_usage_mb() -> f64 {
     let mut sys = System::new_all();
@@ -1,19 +1,10 @@
 use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
 use oxigraph::model::*;
+use rsp_rs::testing::generate_quad;
 use rsp_rs::{QuadContainer, R2ROperator};
 use std::collections::HashSet;
 use std::time::Instant;
 
-/// Generate a quad for benchmarking
-fn generate_quad(subject_id: usize, property_id: usize, object_id: usize) -> Quad {
-    Quad::new(
-        NamedNode::new(&format!("http://example.org/sensor{}", subject_id)).unwrap(),
-        NamedNode::new(&format!("http://example.org/property{}", property_id)).unwrap(),
-        Literal::new_simple_literal(&format!("value_{}", object_id)),
-        GraphName::DefaultGraph,
-    )
-}
-
 /// Generate static data quads
 fn generate_static_quad(subject_id: usize, property_id: usize, object_id: usize) -> Quad {
     Quad::new(
@@ -170,12 +161,25 @@ fn benchmark_r2r_complex_query(c: &mut Criterion) {
             }
         "#,
         ),
+        (
+            "three_patterns_reordered",
+            r#"
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?prop1 ?prop2 ?prop3
+            WHERE {
+                ?sensor ex:property1 ?prop1 .
+                ?sensor ex:property2 ?prop2 .
+                ?sensor ex:property3 ?prop3 .
+            }
+        "#,
+        ),
     ];
 
     for (name, query) in queries {
         group.bench_with_input(BenchmarkId::from_parameter(name), query, |b, query| {
             b.iter_custom(|iters| {
                 let mut r2r = R2ROperator::new(query.to_string());
+                r2r.set_join_reordering(name.ends_with("_reordered"));
 
                 // Add some static data
                 for i in 0..100 {
@@ -0,0 +1,336 @@
+use crate::TimestampExtractor;
+use crate::ingestion::timestamp::parse_date_time;
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const OM_OWL: &str = "http://knoesis.wright.edu/ssw/ont/sensor-observation.owl#";
+const OWL_TIME: &str = "http://www.w3.org/2006/time#";
+
+/// Quads of one event of a benchmark stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchmarkEvent {
+    pub timestamp: i64,
+    pub quads: Vec<Quad>,
+}
+
+/// Events of one stream of a benchmark dataset, ordered by timestamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchmarkStream {
+    /// IRI of the stream, as used in the benchmark queries
+    pub uri: String,
+    pub events: Vec<BenchmarkEvent>,
+}
+
+impl BenchmarkStream {
+    /// Load a CityBench sensor stream file (CSV with a header row)
+    ///
+    /// Every row becomes an observation `<{uri}/observation/{row}>` with one
+    /// `<{uri}#{column}>` property per column, numeric values typed as `xsd:integer`
+    /// or `xsd:decimal`. The event time is read from the column named `timestamp`,
+    /// whatever its case, as a date time (`2014-08-01T07:50:00` or
+    /// `2014-08-01 07:50:00`, UTC) or in milliseconds.
+    pub fn citybench_csv(uri: &str, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse_citybench_csv(uri, &text)
+    }
+
+    /// Parse the content of a CityBench sensor stream file, see `citybench_csv`
+    pub fn parse_citybench_csv(uri: &str, text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .map(csv_fields)
+            .ok_or_else(|| "Empty CityBench stream".to_string())?;
+        let timestamp_column = header
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case("timestamp"))
+            .ok_or_else(|| "CityBench stream has no timestamp column".to_string())?;
+        let predicates = header
+            .iter()
+            .map(|column| named_node(&format!("{}#{}", uri, column.trim().replace(' ', "_"))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut events = Vec::new();
+        for (row, line) in lines.enumerate() {
+            let fields = csv_fields(line);
+            let timestamp = fields
+                .get(timestamp_column)
+                .and_then(|value| parse_csv_timestamp(value))
+                .ok_or_else(|| {
+                    format!(
+                        "Row {} of the CityBench stream has no valid timestamp",
+                        row + 1
+                    )
+                })?;
+            let observation = named_node(&format!("{}/observation/{}", uri, row))?;
+            let quads = predicates
+                .iter()
+                .zip(&fields)
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(predicate, value)| {
+                    Quad::new(
+                        observation.clone(),
+                        predicate.clone(),
+                        csv_literal(value),
+                        GraphName::DefaultGraph,
+                    )
+                })
+                .collect();
+            events.push(BenchmarkEvent { timestamp, quads });
+        }
+        Ok(Self::sorted(uri, events))
+    }
+
+    /// Load an SRBench (LinkedSensorData) file in any RDF syntax oxigraph detects from
+    /// its extension, with one event per observation timed by `om-owl:samplingTime`
+    pub fn srbench(uri: &str, path: impl AsRef<Path>) -> Result<Self, String> {
+        let extractor = TimestampExtractor::from_path(vec![
+            NamedNode::new_unchecked(format!("{}samplingTime", OM_OWL)),
+            NamedNode::new_unchecked(format!("{}inXSDDateTime", OWL_TIME)),
+        ]);
+        Self::from_rdf(uri, path, &extractor)
+    }
+
+    /// Load an RDF file with one event per subject holding the first predicate of
+    /// the extractor's path
+    ///
+    /// An event holds the triples of its subject and of the nodes they lead to, two
+    /// steps deep (e.g. the sampling time instant and the measured result).
+    pub fn from_rdf(
+        uri: &str,
+        path: impl AsRef<Path>,
+        extractor: &TimestampExtractor,
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+        let format = path
+            .extension()
+            .and_then(|extension| RdfFormat::from_extension(&extension.to_string_lossy()))
+            .ok_or_else(|| format!("Unknown RDF format for {}", path.display()))?;
+        let data =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let quads = RdfParser::from_format(format)
+            .for_slice(&data)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        Self::group_events(uri, quads, extractor)
+    }
+
+    /// Group quads into events around the subjects holding the extractor's path
+    pub fn group_events(
+        uri: &str,
+        quads: Vec<Quad>,
+        extractor: &TimestampExtractor,
+    ) -> Result<Self, String> {
+        let first = extractor
+            .path()
+            .first()
+            .ok_or_else(|| "Timestamp extractor has an empty property path".to_string())?;
+        let mut by_subject: HashMap<NamedOrBlankNode, Vec<Quad>> = HashMap::new();
+        let mut roots = Vec::new();
+        let mut root_set = HashSet::new();
+        for quad in quads {
+            if quad.predicate == *first && root_set.insert(quad.subject.clone()) {
+                roots.push(quad.subject.clone());
+            }
+            by_subject
+                .entry(quad.subject.clone())
+                .or_default()
+                .push(quad);
+        }
+
+        let mut events = Vec::with_capacity(roots.len());
+        for root in roots {
+            let mut event_quads = Vec::new();
+            let mut visited = HashSet::from([root.clone()]);
+            let mut frontier = vec![root];
+            for _ in 0..3 {
+                let mut next = Vec::new();
+                for subject in frontier {
+                    for quad in by_subject.get(&subject).into_iter().flatten() {
+                        let object = match &quad.object {
+                            Term::NamedNode(node) => Some(NamedOrBlankNode::from(node.clone())),
+                            Term::BlankNode(node) => Some(NamedOrBlankNode::from(node.clone())),
+                            _ => None,
+                        };
+                        if let Some(object) = object {
+                            if by_subject.contains_key(&object) && visited.insert(object.clone()) {
+                                next.push(object);
+                            }
+                        }
+                        event_quads.push(quad.clone());
+                    }
+                }
+                frontier = next;
+            }
+            let timestamp = extractor.extract(&event_quads)?;
+            events.push(BenchmarkEvent {
+                timestamp,
+                quads: event_quads,
+            });
+        }
+        Ok(Self::sorted(uri, events))
+    }
+
+    fn sorted(uri: &str, mut events: Vec<BenchmarkEvent>) -> Self {
+        events.sort_by_key(|event| event.timestamp);
+        Self {
+            uri: uri.to_string(),
+            events,
+        }
+    }
+
+    /// Get the number of events
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether the stream has no event
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// Load the queries of a query set directory, one RSP-QL query per `.rq` file,
+/// as (file name without extension, query) pairs sorted by name
+pub fn load_query_set(directory: impl AsRef<Path>) -> Result<Vec<(String, String)>, String> {
+    let directory = directory.as_ref();
+    let entries = fs::read_dir(directory)
+        .map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?;
+    let mut queries = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?
+            .path();
+        if path.extension().is_some_and(|extension| extension == "rq") {
+            let query = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            queries.push((name, query));
+        }
+    }
+    queries.sort();
+    Ok(queries)
+}
+
+fn named_node(iri: &str) -> Result<NamedNode, String> {
+    NamedNode::new(iri).map_err(|e| format!("Invalid IRI {}: {}", iri, e))
+}
+
+/// Split a CSV line into fields, unquoting quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn parse_csv_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    value
+        .parse()
+        .ok()
+        .or_else(|| parse_date_time(&value.replacen(' ', "T", 1)))
+}
+
+fn csv_literal(value: &str) -> Literal {
+    let value = value.trim();
+    if value.parse::<i64>().is_ok() {
+        Literal::new_typed_literal(value, xsd::INTEGER)
+    } else if value.parse::<f64>().is_ok_and(f64::is_finite) && !value.contains(['e', 'E']) {
+        Literal::new_typed_literal(value, xsd::DECIMAL)
+    } else {
+        Literal::new_simple_literal(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_citybench_csv() {
+        let text = "status,avgSpeed,TIMESTAMP,REPORT_ID\n\
+                    OK,60,2014-08-01T07:55:00,182955\n\
+                    OK,52.5,2014-08-01 07:50:00,182955\n";
+        let stream =
+            BenchmarkStream::parse_citybench_csv("http://example.org/traffic", text).unwrap();
+        assert_eq!(stream.len(), 2);
+        // Events are ordered by timestamp, not by row
+        assert_eq!(stream.events[0].timestamp, 1_406_879_400_000);
+        assert_eq!(
+            stream.events[1].timestamp - stream.events[0].timestamp,
+            300_000
+        );
+
+        let speed = stream.events[0]
+            .quads
+            .iter()
+            .find(|quad| quad.predicate.as_str() == "http://example.org/traffic#avgSpeed")
+            .unwrap();
+        assert_eq!(
+            speed.subject.to_string(),
+            "<http://example.org/traffic/observation/1>"
+        );
+        assert_eq!(
+            speed.object,
+            Literal::new_typed_literal("52.5", xsd::DECIMAL).into()
+        );
+
+        let untimed = BenchmarkStream::parse_citybench_csv("http://example.org/s", "a,b\n1,2\n");
+        assert!(untimed.is_err());
+    }
+
+    #[test]
+    fn test_group_srbench_observations() {
+        let data = r#"
+            @prefix om: <http://knoesis.wright.edu/ssw/ont/sensor-observation.owl#> .
+            @prefix time: <http://www.w3.org/2006/time#> .
+            @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+            @prefix ex: <http://example.org/> .
+            ex:obs2 om:samplingTime ex:t2 ; om:result ex:r2 .
+            ex:t2 time:inXSDDateTime "2004-08-10T18:10:00Z"^^xsd:dateTime .
+            ex:r2 om:floatValue "21.5"^^xsd:float .
+            ex:obs1 om:samplingTime ex:t1 ; om:result ex:r1 .
+            ex:t1 time:inXSDDateTime "2004-08-10T18:00:00Z"^^xsd:dateTime .
+            ex:r1 om:floatValue "20.0"^^xsd:float .
+        "#;
+        let path = std::env::temp_dir().join(format!("rsp-rs-srbench-{}.ttl", std::process::id()));
+        fs::write(&path, data).unwrap();
+        let stream = BenchmarkStream::srbench("http://example.org/weather", &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(stream.len(), 2);
+        assert!(stream.events[0].timestamp < stream.events[1].timestamp);
+        // Each observation carries its time instant and result
+        assert_eq!(stream.events[0].quads.len(), 4);
+        let subjects: HashSet<String> = stream.events[0]
+            .quads
+            .iter()
+            .map(|quad| quad.subject.to_string())
+            .collect();
+        let expected = ["obs1", "t1", "r1"].map(|name| format!("<http://example.org/{}>", name));
+        assert_eq!(subjects, HashSet::from(expected));
+    }
+}
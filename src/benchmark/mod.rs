@@ -0,0 +1,28 @@
+//! Standard RSP benchmarks (CityBench, SRBench) run against `RSPEngine`
+//!
+//! Datasets are loaded into [`BenchmarkStream`]s of timestamped events, and a
+//! [`BenchmarkRunner`] replays them through an engine for each query of a query
+//! set, reporting latency, throughput and the number of results, to compare with
+//! the figures published for C-SPARQL and CQELS. Queries must be written in RSP-QL,
+//! with the stream IRIs given to the loaders.
+//!
+//! ```rust,no_run
+//! use rsp_rs::benchmark::{BenchmarkRunner, BenchmarkStream, load_query_set};
+//!
+//! let traffic = BenchmarkStream::citybench_csv(
+//!     "http://example.org/AarhusTrafficData182955",
+//!     "citybench/AarhusTrafficData182955.stream",
+//! )
+//! .unwrap();
+//! let runner = BenchmarkRunner::new(vec![traffic]);
+//! for (name, query) in load_query_set("citybench/queries").unwrap() {
+//!     let report = runner.run(&name, &query).unwrap();
+//!     println!("{}", report);
+//! }
+//! ```
+
+mod datasets;
+mod runner;
+
+pub use datasets::{BenchmarkEvent, BenchmarkStream, load_query_set};
+pub use runner::{BenchmarkReport, BenchmarkRunner};
@@ -0,0 +1,218 @@
+use super::datasets::{BenchmarkEvent, BenchmarkStream};
+use crate::{EngineConfig, RSPEngine};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Figures of one query run over the benchmark streams
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    /// Name of the query in its query set
+    pub query: String,
+    /// Events added to the streams read by the query
+    pub events: usize,
+    /// Results emitted by the query
+    pub results: usize,
+    /// Wall-clock time of the whole run
+    pub duration: Duration,
+    /// Time taken by each event (or end of stream) that produced results, from being
+    /// added to a stream until all its results were emitted
+    pub latencies: Vec<Duration>,
+}
+
+impl BenchmarkReport {
+    /// Get the number of events processed per second
+    pub fn throughput(&self) -> f64 {
+        self.events as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Get the mean latency of the events producing results
+    pub fn mean_latency(&self) -> Duration {
+        let total: Duration = self.latencies.iter().sum();
+        total
+            .checked_div(self.latencies.len() as u32)
+            .unwrap_or_default()
+    }
+
+    /// Get the latency that `percentile` percent of the events producing results
+    /// stay within
+    pub fn latency_percentile(&self, percentile: f64) -> Duration {
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * latencies.len() as f64).ceil();
+        latencies
+            .get((rank as usize).saturating_sub(1))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the fraction of the `expected` results that were emitted, e.g. with
+    /// the result counts of a reference engine; 1 when nothing is expected
+    pub fn completeness(&self, expected: usize) -> f64 {
+        if expected == 0 {
+            return 1.0;
+        }
+        (self.results as f64 / expected as f64).min(1.0)
+    }
+
+    /// Count the results of one event, keeping its latency if it produced any
+    fn record(&mut self, results: usize, latency: Duration) {
+        if results > 0 {
+            self.results += results;
+            self.latencies.push(latency);
+        }
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} events, {} results in {:.3}s ({:.0} events/s), latency mean {:?}, p95 {:?}, max {:?}",
+            self.query,
+            self.events,
+            self.results,
+            self.duration.as_secs_f64(),
+            self.throughput(),
+            self.mean_latency(),
+            self.latency_percentile(95.0),
+            self.latency_percentile(100.0)
+        )
+    }
+}
+
+/// Replays benchmark streams through an engine for each query of a query set
+///
+/// Engines run in synchronous mode, so every latency is the processing time of
+/// one event, without queueing or waiting. Events of all streams are interleaved
+/// by timestamp, and streams are closed after their last event so the last windows
+/// report.
+pub struct BenchmarkRunner {
+    streams: Vec<BenchmarkStream>,
+    config: EngineConfig,
+}
+
+impl BenchmarkRunner {
+    /// Create a runner over the given streams, with the default engine configuration
+    pub fn new(streams: Vec<BenchmarkStream>) -> Self {
+        Self {
+            streams,
+            config: EngineConfig::default(),
+        }
+    }
+
+    /// Set the configuration of the engines running the queries
+    /// Synchronous mode is always enabled
+    pub fn with_config(mut self, config: EngineConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Run one RSP-QL query over the streams
+    pub fn run(&self, name: &str, query: &str) -> Result<BenchmarkReport, String> {
+        let config = EngineConfig {
+            synchronous: true,
+            ..self.config.clone()
+        };
+        let mut engine = RSPEngine::with_config(query.to_string(), config);
+        engine.initialize()?;
+
+        // Streams the query doesn't read are skipped
+        let streams: Vec<_> = self
+            .streams
+            .iter()
+            .filter_map(|stream| Some((engine.get_stream(&stream.uri)?, stream)))
+            .collect();
+        let mut events: Vec<(&BenchmarkEvent, usize)> = streams
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (_, stream))| stream.events.iter().map(move |e| (e, index)))
+            .collect();
+        events.sort_by_key(|(event, _)| event.timestamp);
+
+        let mut report = BenchmarkReport {
+            query: name.to_string(),
+            events: events.len(),
+            results: 0,
+            duration: Duration::ZERO,
+            latencies: Vec::new(),
+        };
+        let started = Instant::now();
+        for (event, index) in events {
+            let added = Instant::now();
+            streams[index]
+                .0
+                .add_quads(event.quads.clone(), event.timestamp)?;
+            report.record(engine.poll_results().len(), added.elapsed());
+        }
+
+        // A sentinel past the widest window and its delay closes every window still open
+        let horizon = engine
+            .parsed_query()
+            .s2r
+            .iter()
+            .map(|window| window.width + window.slide + window.delay.unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        for (rdf_stream, stream) in &streams {
+            if let Some(last) = stream.events.last() {
+                let added = Instant::now();
+                engine.close_stream(&rdf_stream.name, last.timestamp + horizon + 1)?;
+                report.record(engine.poll_results().len(), added.elapsed());
+            }
+        }
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// Run every query of a query set over the streams, in order
+    pub fn run_all(&self, queries: &[(String, String)]) -> Result<Vec<BenchmarkReport>, String> {
+        queries
+            .iter()
+            .map(|(name, query)| {
+                self.run(name, query)
+                    .map_err(|e| format!("Query {} failed: {}", name, e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_citybench_query() {
+        let mut text = String::from("vehicleCount,TIMESTAMP\n");
+        for minute in 0..20 {
+            text.push_str(&format!("{},2014-08-01T08:{:02}:00\n", minute % 7, minute));
+        }
+        let stream =
+            BenchmarkStream::parse_citybench_csv("http://example.org/traffic", &text).unwrap();
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <output> AS
+            SELECT (COUNT(?count) AS ?observations)
+            FROM NAMED WINDOW ex:w ON STREAM ex:traffic [RANGE 300000 STEP 300000]
+            WHERE { WINDOW ex:w { ?obs <http://example.org/traffic#vehicleCount> ?count } }
+        "#;
+
+        let report = BenchmarkRunner::new(vec![stream]).run("q1", query).unwrap();
+        assert_eq!(report.events, 20);
+        // Four windows of five minutes
+        assert_eq!(report.results, 4);
+        assert!(!report.latencies.is_empty());
+        assert!(report.latency_percentile(100.0) >= report.mean_latency());
+        assert_eq!(report.completeness(8), 0.5);
+        assert!(report.to_string().starts_with("q1: 20 events, 4 results"));
+
+        let skipped = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <output> AS
+            SELECT *
+            FROM NAMED WINDOW ex:w ON STREAM ex:other [RANGE 1000 STEP 1000]
+            WHERE { WINDOW ex:w { ?s ?p ?o } }
+        "#;
+        let report = BenchmarkRunner::new(Vec::new()).run("q2", skipped).unwrap();
+        assert_eq!(report.events, 0);
+    }
+}
@@ -0,0 +1,314 @@
+//! C ABI for embedding the engine in non-Rust hosts, e.g. C/C++ gateways or
+//! Python through `ctypes`
+//!
+//! An engine is created from an RSP-QL query, receives its results through a C
+//! callback as JSON objects (a field per variable, then `window_start` and
+//! `window_end`) and is fed quads as N-Quads documents. Functions returning an
+//! `int` return 0 on success and -1 on failure, with the message available from
+//! `rsp_last_error`. A C header can be generated with
+//! `cbindgen --config cbindgen.toml --output rsp_rs.h`, and a shared library built
+//! with `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! ```python
+//! import ctypes
+//!
+//! lib = ctypes.CDLL("target/release/librsp_rs.so")
+//! lib.rsp_engine_new.restype = ctypes.c_void_p
+//! lib.rsp_engine_new.argtypes = [ctypes.c_char_p, ctypes.c_bool]
+//! CALLBACK = ctypes.CFUNCTYPE(None, ctypes.c_void_p, ctypes.c_char_p)
+//! lib.rsp_engine_on_result.argtypes = [ctypes.c_void_p, CALLBACK, ctypes.c_void_p]
+//! lib.rsp_engine_push_nquads.argtypes = [
+//!     ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p, ctypes.c_int64
+//! ]
+//!
+//! engine = lib.rsp_engine_new(query.encode(), True)
+//! on_result = CALLBACK(lambda _, result: print(result.decode()))
+//! lib.rsp_engine_on_result(engine, on_result, None)
+//! lib.rsp_engine_initialize(engine)
+//! lib.rsp_engine_push_nquads(engine, b"http://example.org/stream1", nquads, 1000)
+//! ```
+
+use crate::file::json_object;
+use crate::{RSPEngine, RSPEngineBuilder, parse_rdf};
+use oxigraph::io::RdfFormat;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::ptr;
+
+/// Engine created by `rsp_engine_new`, opaque to C
+pub struct RspEngine {
+    engine: RSPEngine,
+}
+
+/// Function receiving each result as a NUL-terminated JSON object, valid for the
+/// duration of the call, along with the `user_data` given at registration
+pub type RspResultCallback = extern "C" fn(user_data: *mut c_void, result: *const c_char);
+
+/// Callback and user data handed over to the threads evaluating the reports
+struct ForeignCallback {
+    callback: RspResultCallback,
+    user_data: *mut c_void,
+}
+
+// The host is responsible for making the user data usable from other threads,
+// as documented on `rsp_engine_on_result`
+unsafe impl Send for ForeignCallback {}
+unsafe impl Sync for ForeignCallback {}
+
+impl ForeignCallback {
+    fn call(&self, result: &CStr) {
+        (self.callback)(self.user_data, result.as_ptr());
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record the error of a failed call, for `rsp_last_error`
+fn set_last_error(message: impl Into<Vec<u8>>) {
+    // Messages can't contain NUL bytes in C, so they are cut at the first one
+    let mut message = message.into();
+    if let Some(end) = message.iter().position(|&byte| byte == 0) {
+        message.truncate(end);
+    }
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Turn the outcome of a call into its return code
+fn status(result: Result<(), String>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
+}
+
+/// Read a NUL-terminated UTF-8 string passed by the host
+///
+/// # Safety
+///
+/// `value` must be null or point to a NUL-terminated string
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map_err(|e| format!("{} is not valid UTF-8: {}", name, e))
+}
+
+/// Read the engine passed by the host
+///
+/// # Safety
+///
+/// `engine` must be null or returned by `rsp_engine_new` and not freed
+unsafe fn read_engine<'a>(engine: *mut RspEngine) -> Result<&'a mut RspEngine, String> {
+    unsafe { engine.as_mut() }.ok_or_else(|| "engine is null".to_string())
+}
+
+/// Get the message of the last failed call on this thread, or null
+/// The message stays valid until the next failed call on this thread
+#[no_mangle]
+pub extern "C" fn rsp_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Create an engine registering an RSP-QL query, or return null on failure
+/// Synchronous engines evaluate reports on the threads pushing quads, so results
+/// are delivered before `rsp_engine_push_nquads` returns
+///
+/// # Safety
+///
+/// `query` must point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_new(query: *const c_char, synchronous: bool) -> *mut RspEngine {
+    let query = match unsafe { read_str(query, "query") } {
+        Ok(query) => query,
+        Err(e) => {
+            set_last_error(e);
+            return ptr::null_mut();
+        }
+    };
+    let engine = RSPEngineBuilder::new(query.to_string())
+        .synchronous(synchronous)
+        .build();
+    if engine.parsed_query().s2r.is_empty() {
+        set_last_error("Query has no window");
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(RspEngine { engine }))
+}
+
+/// Call `callback` with every result of the engine's query
+/// Callbacks can be registered before or after `rsp_engine_initialize`; they run
+/// on the threads evaluating the reports, so `user_data` must be usable from them
+///
+/// # Safety
+///
+/// `engine` must be returned by `rsp_engine_new` and not freed
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_on_result(
+    engine: *mut RspEngine,
+    callback: RspResultCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let result = unsafe { read_engine(engine) }.and_then(|engine| {
+        let foreign = ForeignCallback {
+            callback,
+            user_data,
+        };
+        let query_id = engine.engine.parsed_query().r2s.name.clone();
+        engine.engine.on_result(&query_id, move |result| {
+            // Solutions can't contain NUL bytes in their JSON encoding
+            if let Ok(json) = CString::new(json_object(result)) {
+                foreign.call(&json);
+            }
+        })?;
+        Ok(())
+    });
+    status(result)
+}
+
+/// Create the windows and streams of the engine
+///
+/// # Safety
+///
+/// `engine` must be returned by `rsp_engine_new` and not freed
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_initialize(engine: *mut RspEngine) -> c_int {
+    status(unsafe { read_engine(engine) }.and_then(|engine| engine.engine.initialize()))
+}
+
+/// Add the quads of an N-Quads document as one event of a stream
+///
+/// # Safety
+///
+/// `engine` must be returned by `rsp_engine_new` and not freed, `stream` and
+/// `nquads` must point to NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_push_nquads(
+    engine: *mut RspEngine,
+    stream: *const c_char,
+    nquads: *const c_char,
+    timestamp: i64,
+) -> c_int {
+    let result = (|| {
+        let engine = unsafe { read_engine(engine) }?;
+        let stream_name = unsafe { read_str(stream, "stream") }?;
+        let document = unsafe { read_str(nquads, "nquads") }?;
+        let stream = engine
+            .engine
+            .get_stream(stream_name)
+            .ok_or_else(|| format!("Unknown stream {}", stream_name))?;
+        let quads = parse_rdf(document, RdfFormat::NQuads)?;
+        stream.add_quads(quads, timestamp)
+    })();
+    status(result)
+}
+
+/// Close a stream, reporting the windows open before `timestamp`
+///
+/// # Safety
+///
+/// `engine` must be returned by `rsp_engine_new` and not freed, `stream` must
+/// point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_close_stream(
+    engine: *mut RspEngine,
+    stream: *const c_char,
+    timestamp: i64,
+) -> c_int {
+    let result = (|| {
+        let engine = unsafe { read_engine(engine) }?;
+        let stream_name = unsafe { read_str(stream, "stream") }?;
+        engine.engine.close_stream(stream_name, timestamp)
+    })();
+    status(result)
+}
+
+/// Free an engine; null is ignored
+///
+/// # Safety
+///
+/// `engine` must be null or returned by `rsp_engine_new`, and not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn rsp_engine_free(engine: *mut RspEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    extern "C" fn collect(user_data: *mut c_void, result: *const c_char) {
+        let results = unsafe { &*(user_data as *const Mutex<Vec<String>>) };
+        let result = unsafe { CStr::from_ptr(result) }.to_str().unwrap();
+        results.lock().unwrap().push(result.to_string());
+    }
+
+    fn last_error() -> String {
+        let error = rsp_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_engine_over_c_abi() {
+        let query = CString::new(
+            "PREFIX ex: <http://example.org/>
+             REGISTER RStream <http://example.org/output> AS
+             SELECT ?s ?o
+             FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+             WHERE { WINDOW ex:w1 { ?s ex:value ?o } }",
+        )
+        .unwrap();
+        let stream = CString::new("http://example.org/stream1").unwrap();
+        let results: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        unsafe {
+            let engine = rsp_engine_new(query.as_ptr(), true);
+            assert!(!engine.is_null());
+            let user_data = &results as *const _ as *mut c_void;
+            assert_eq!(rsp_engine_on_result(engine, collect, user_data), 0);
+            assert_eq!(rsp_engine_initialize(engine), 0);
+
+            let event =
+                CString::new("<http://example.org/s1> <http://example.org/value> 21 .").unwrap();
+            assert_eq!(
+                rsp_engine_push_nquads(engine, stream.as_ptr(), event.as_ptr(), 100),
+                0
+            );
+            let invalid = CString::new("not n-quads").unwrap();
+            assert_eq!(
+                rsp_engine_push_nquads(engine, stream.as_ptr(), invalid.as_ptr(), 200),
+                -1
+            );
+            assert!(!last_error().is_empty());
+            let unknown = CString::new("http://example.org/other").unwrap();
+            assert_eq!(rsp_engine_close_stream(engine, unknown.as_ptr(), 5000), -1);
+            assert_eq!(rsp_engine_close_stream(engine, stream.as_ptr(), 5000), 0);
+            rsp_engine_free(engine);
+
+            assert!(rsp_engine_new(ptr::null(), true).is_null());
+            assert_eq!(last_error(), "query is null");
+            assert_eq!(rsp_engine_initialize(ptr::null_mut()), -1);
+            rsp_engine_free(ptr::null_mut());
+        }
+        assert_eq!(
+            results.into_inner().unwrap(),
+            vec![
+                r#"{"o":"21","s":"http://example.org/s1","window_start":0,"window_end":1000}"#
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::delta::Binding;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Condition over one result binding
+type Predicate = Arc<dyn Fn(&Binding) -> bool + Send + Sync>;
+
+/// Result of the query matching an alert rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    /// Name of the rule that matched
+    pub rule: String,
+    pub binding: Binding,
+    /// Time range of the report, as in `BindingWithTimestamp`
+    pub window: (i64, i64),
+}
+
+/// Rule raising an alert for every result binding matching a predicate
+///
+/// With a debounce interval, a rule raises at most one alert per interval. The
+/// interval is measured in event time, on the end of the reported windows, so
+/// replaying a stream raises the same alerts.
+///
+/// ```rust,no_run
+/// use rsp_rs::{AlertRule, RSPEngine};
+/// use std::time::Duration;
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let alerts = engine.start_processing_alerts(vec![
+///     AlertRule::greater_than("overheating", "avg_temp", 30.0)
+///         .with_debounce(Duration::from_secs(60)),
+/// ]);
+/// for alert in alerts {
+///     println!("{}: {:?}", alert.rule, alert.binding.get("sensor"));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AlertRule {
+    name: String,
+    predicate: Predicate,
+    debounce: Option<i64>,
+    /// End of the window of the last alert raised
+    last_alert: Option<i64>,
+}
+
+impl AlertRule {
+    /// Create a rule matching the bindings for which `predicate` holds
+    pub fn new<F>(name: &str, predicate: F) -> Self
+    where
+        F: Fn(&Binding) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            predicate: Arc::new(predicate),
+            debounce: None,
+            last_alert: None,
+        }
+    }
+
+    /// Create a rule matching the bindings whose numeric `variable` is above `threshold`
+    pub fn greater_than(name: &str, variable: &str, threshold: f64) -> Self {
+        let variable = variable.to_string();
+        Self::new(name, move |binding| {
+            binding
+                .number(&variable)
+                .is_some_and(|value| value > threshold)
+        })
+    }
+
+    /// Create a rule matching the bindings whose numeric `variable` is below `threshold`
+    pub fn less_than(name: &str, variable: &str, threshold: f64) -> Self {
+        let variable = variable.to_string();
+        Self::new(name, move |binding| {
+            binding
+                .number(&variable)
+                .is_some_and(|value| value < threshold)
+        })
+    }
+
+    /// Raise at most one alert per `interval` of event time
+    pub fn with_debounce(mut self, interval: Duration) -> Self {
+        self.debounce = Some(interval.as_millis() as i64);
+        self
+    }
+
+    /// Get the name of the rule
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check a binding of the report of `window`, returning the alert to raise if any
+    pub fn check(&mut self, binding: &Binding, window: (i64, i64)) -> Option<Alert> {
+        if !(self.predicate)(binding) {
+            return None;
+        }
+        // Reports evaluated in parallel may come out of order, so the interval
+        // applies on both sides of the last alert
+        if let (Some(debounce), Some(last_alert)) = (self.debounce, self.last_alert) {
+            if (window.1 - last_alert).abs() < debounce {
+                return None;
+            }
+        }
+        self.last_alert = Some(self.last_alert.map_or(window.1, |last| last.max(window.1)));
+        Some(Alert {
+            rule: self.name.clone(),
+            binding: binding.clone(),
+            window,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{Literal, NamedNode};
+
+    fn reading(sensor: &str, avg: f64) -> Binding {
+        Binding {
+            values: vec![
+                ("avg".to_string(), Literal::from(avg).into()),
+                (
+                    "sensor".to_string(),
+                    NamedNode::new(format!("http://example.org/{}", sensor))
+                        .unwrap()
+                        .into(),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_threshold_rules() {
+        let mut high = AlertRule::greater_than("high", "avg", 30.0);
+        let mut low = AlertRule::less_than("low", "avg", 10.0);
+        assert!(high.check(&reading("s1", 31.5), (0, 10)).is_some());
+        assert!(high.check(&reading("s1", 30.0), (0, 10)).is_none());
+        assert!(low.check(&reading("s1", 5.0), (0, 10)).is_some());
+
+        // Unbound or non-numeric variables never match
+        let mut missing = AlertRule::greater_than("missing", "sensor", 0.0);
+        assert!(missing.check(&reading("s1", 31.5), (0, 10)).is_none());
+    }
+
+    #[test]
+    fn test_debounce_uses_event_time() {
+        let mut rule =
+            AlertRule::greater_than("high", "avg", 30.0).with_debounce(Duration::from_millis(100));
+        let alert = rule.check(&reading("s1", 35.0), (0, 10)).unwrap();
+        assert_eq!(alert.rule, "high");
+        assert_eq!(alert.window, (0, 10));
+
+        assert!(rule.check(&reading("s2", 35.0), (50, 60)).is_none());
+        // Readings below the threshold don't restart the interval
+        assert!(rule.check(&reading("s1", 20.0), (100, 110)).is_none());
+        assert!(rule.check(&reading("s1", 35.0), (100, 110)).is_some());
+    }
+}
@@ -0,0 +1,912 @@
+use crate::calendar::TimeZone;
+use crate::clock::{self, Clock};
+use crate::dedup::ResultDedup;
+use crate::enrichment::StaticLookup;
+use crate::evaluation_pool::EvaluationPool;
+use crate::eviction::QuadTtl;
+use crate::parsed_query::ParsedQuery;
+use crate::pause::PausePolicy;
+use crate::quad_filter::QuadFilter;
+use crate::rate_limit::RateLimit;
+use crate::result_filter::ResultFilter;
+use crate::router::StreamRouter;
+use crate::rspql_parser::QueryDialect;
+use crate::shacl::{ShaclShapes, ValidationPolicy};
+use crate::sharing::PlanSharing;
+use crate::shedding::LoadSheddingPolicy;
+use crate::supervisor::THREAD_NAME_PREFIX;
+use crate::{RSPEngine, ReportStrategy, Tick};
+use oxigraph::model::{GraphName, Literal, NamedNode, Quad};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configuration of an RSP engine beyond its query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineConfig {
+    /// Syntax of the query
+    pub dialect: QueryDialect,
+    /// Report strategy of the windows
+    pub report_strategy: ReportStrategy,
+    /// Tick of the windows
+    pub tick: Tick,
+    /// Report strategies of individual windows by IRI, overriding the query and the default
+    pub window_report_strategies: HashMap<String, ReportStrategy>,
+    /// Ticks of individual windows by IRI, overriding the query and the default
+    pub window_ticks: HashMap<String, Tick>,
+    /// Start time of the windows; 0 aligns them to the first event
+    pub start_time: i64,
+    /// Alignment origin (t0) of the window boundaries, overriding the start time;
+    /// `None` keeps the start time alignment
+    pub origin: Option<i64>,
+    /// Alignment origins of individual windows by IRI, overriding the query and the default
+    pub window_origins: HashMap<String, i64>,
+    /// Time zones aligning individual tumbling windows by IRI on their calendar,
+    /// overriding the query
+    pub window_calendars: HashMap<String, TimeZone>,
+    /// Partition variables of individual windows by IRI, overriding the query
+    pub window_partitions: HashMap<String, String>,
+    /// Whether window instances without events report too, e.g. for `COUNT = 0` alerts;
+    /// always on for windows matched under `NOT EXISTS` or `MINUS`
+    pub emit_empty_windows: bool,
+    /// Whether empty instances of individual windows by IRI report, overriding the
+    /// query and the default
+    pub window_emit_empty_windows: HashMap<String, bool>,
+    /// Report delays (in ms) of individual windows by IRI, overriding the query
+    pub window_delays: HashMap<String, i64>,
+    /// Retentions (in ms) of the raw quads of individual landmark windows by IRI,
+    /// overriding the query
+    pub window_retentions: HashMap<String, i64>,
+    /// Filters of the quads stored by individual windows by IRI
+    pub window_filters: HashMap<String, QuadFilter>,
+    /// Static attributes attached to the events of individual windows by IRI
+    pub window_lookups: HashMap<String, StaticLookup>,
+    /// Windows by IRI keeping every event adding a quad, counting duplicates, see
+    /// `CSPARQLWindow::set_multiset`
+    pub multiset_windows: HashSet<String>,
+    /// Time-to-live of the quads of individual windows by IRI
+    pub window_ttls: HashMap<String, QuadTtl>,
+    /// How far (in ms) behind the last window report an event may be before it is
+    /// dropped; `None` keeps every event
+    pub max_lateness: Option<i64>,
+    /// Source of processing time; event time only by default
+    pub clock: Arc<dyn Clock>,
+    /// How often the windows are advanced to the time of a processing-time clock
+    pub clock_interval: Duration,
+    /// Maximum number of quads of the events sharing a timestamp that individual
+    /// streams by IRI coalesce before sending them to their window, see
+    /// `RDFStream::flush`; ignored for partitioned windows
+    pub stream_micro_batches: HashMap<String, usize>,
+    /// Maximum skew in ms between the events that several producers add concurrently
+    /// to individual streams by IRI, which are buffered and sorted by timestamp
+    /// before reaching their window
+    pub stream_max_skews: HashMap<String, i64>,
+    /// Whether dropping the last handle of a stream given by `RSPEngine::get_stream`
+    /// closes the open instances of its window
+    pub auto_close_streams: bool,
+    /// Quad of the event added by `RSPEngine::close_stream`; `None` closes the windows
+    /// with an empty event, so no sentinel quad reaches them
+    pub close_sentinel: Option<Quad>,
+    /// Wall-clock silences of individual streams by IRI after which the open instances
+    /// of their window close and report, as if `close_stream` had been called
+    pub stream_idle_timeouts: HashMap<String, Duration>,
+    /// Streams by IRI whose quads upsert the static data by subject rather than
+    /// feeding a window, see `RSPEngineBuilder::side_input_stream`
+    pub side_input_streams: HashSet<String>,
+    /// Filters of the quads of individual streams by IRI, applied before they are sent
+    pub stream_filters: HashMap<String, QuadFilter>,
+    /// Rate limits of individual streams by IRI, applied before events are sent
+    pub stream_rate_limits: HashMap<String, RateLimit>,
+    /// SHACL shapes validating the events of individual streams by IRI, with what
+    /// to do with the invalid ones
+    pub stream_shapes: HashMap<String, (ShaclShapes, ValidationPolicy)>,
+    /// Feeds by IRI whose quads are dispatched to windows by their content
+    pub stream_routes: HashMap<String, StreamRouter>,
+    /// Directory of an on-disk oxigraph store keeping the static data, instead of
+    /// indexing it in memory; `None` by default, ignored in WebAssembly builds
+    pub static_store_path: Option<PathBuf>,
+    /// Append-only log recording the events added to the streams, to replay the run
+    /// with `RSPEngine::replay`; `None` by default
+    pub input_log_path: Option<PathBuf>,
+    /// Maximum number of solutions of the static patterns of the query evaluated
+    /// once and joined with the windows on every report; `None` to evaluate the
+    /// whole query on every report, the default
+    pub static_pattern_cache: Option<usize>,
+    /// Registry of the windows and window patterns shared with the engines of other
+    /// queries; `None` by default
+    pub plan_sharing: Option<PlanSharing>,
+    /// Capacity of each stream channel; producers block when it is full.
+    /// `None` for unbounded channels
+    pub channel_capacity: Option<usize>,
+    /// What streams do with the events added while the engine is paused
+    pub pause_policy: PausePolicy,
+    /// When and how the engine sheds load, `None` to never shed
+    pub load_shedding: Option<LoadSheddingPolicy>,
+    /// Number of threads evaluating R2R queries
+    pub evaluation_threads: usize,
+    /// Prefix of the names of the threads of the engine, e.g. `rsp-window-<window IRI>`
+    pub thread_name_prefix: String,
+    /// Whether eligible aggregation queries are evaluated incrementally
+    pub incremental_aggregation: bool,
+    /// Whether the incremental aggregates of sliding windows are kept per pane, see
+    /// `CSPARQLWindow::set_pane_aggregation`
+    pub pane_aggregation: bool,
+    /// Whether window quads are reified with the timestamp of their event, see
+    /// `R2ROperator::set_annotate_timestamps`; disables incremental aggregation
+    pub annotate_timestamps: bool,
+    /// Whether window quads keep the stream of their event and are reified with it,
+    /// see `R2ROperator::set_annotate_streams`; disables incremental aggregation
+    pub annotate_streams: bool,
+    /// Whether the triple patterns of the query are reordered by their cardinalities
+    /// before each evaluation, see `R2ROperator::set_join_reordering`
+    pub join_reordering: bool,
+    /// Suppression of the results repeated by consecutive reports of a window
+    pub result_dedup: ResultDedup,
+    /// Conditions every result must meet to be emitted
+    pub result_filters: Vec<ResultFilter>,
+    /// How long, in event time, results wait for the reports of the other windows
+    /// closing earlier before delivery; `None` to deliver them as evaluated
+    pub result_merge_delay: Option<Duration>,
+    /// Memory limit of each window's buffers before spilling to disk
+    pub max_window_memory_bytes: Option<usize>,
+    /// Timeout of the requests evaluating `SERVICE` clauses and dereferencing
+    /// static graphs
+    pub service_timeout: Duration,
+    /// How long `SERVICE` results are reused; `None` queries the endpoint on every report
+    pub service_cache_ttl: Option<Duration>,
+    /// Whether `initialize` fetches the static graphs of `FROM` / `FROM NAMED` clauses
+    /// missing from the static data over HTTP
+    pub dereference_static_graphs: bool,
+    /// Whether streams apply events and evaluate reports on the calling thread,
+    /// with results collected by `RSPEngine::poll_results`
+    pub synchronous: bool,
+    /// Number of latest results retained for `RSPEngine::recent_results`
+    pub retained_results: Option<usize>,
+    /// How long (in ms of event time) results are retained for `RSPEngine::recent_results`
+    pub retained_results_ms: Option<i64>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            dialect: QueryDialect::default(),
+            report_strategy: ReportStrategy::OnWindowClose,
+            tick: Tick::TimeDriven,
+            window_report_strategies: HashMap::new(),
+            window_ticks: HashMap::new(),
+            start_time: 0,
+            origin: None,
+            window_origins: HashMap::new(),
+            window_calendars: HashMap::new(),
+            window_partitions: HashMap::new(),
+            emit_empty_windows: false,
+            window_emit_empty_windows: HashMap::new(),
+            window_delays: HashMap::new(),
+            window_retentions: HashMap::new(),
+            window_filters: HashMap::new(),
+            window_lookups: HashMap::new(),
+            multiset_windows: HashSet::new(),
+            window_ttls: HashMap::new(),
+            max_lateness: None,
+            clock: clock::event_time(),
+            clock_interval: Duration::from_millis(100),
+            stream_micro_batches: HashMap::new(),
+            stream_max_skews: HashMap::new(),
+            auto_close_streams: false,
+            close_sentinel: Some(Quad::new(
+                NamedNode::new_unchecked("urn:rsp:sentinel"),
+                NamedNode::new_unchecked("urn:rsp:type"),
+                Literal::new_simple_literal("end"),
+                GraphName::DefaultGraph,
+            )),
+            stream_idle_timeouts: HashMap::new(),
+            side_input_streams: HashSet::new(),
+            stream_filters: HashMap::new(),
+            stream_rate_limits: HashMap::new(),
+            stream_shapes: HashMap::new(),
+            stream_routes: HashMap::new(),
+            static_store_path: None,
+            input_log_path: None,
+            static_pattern_cache: None,
+            plan_sharing: None,
+            channel_capacity: None,
+            pause_policy: PausePolicy::default(),
+            load_shedding: None,
+            evaluation_threads: EvaluationPool::default_threads(),
+            thread_name_prefix: THREAD_NAME_PREFIX.to_string(),
+            incremental_aggregation: true,
+            pane_aggregation: false,
+            annotate_timestamps: false,
+            annotate_streams: false,
+            join_reordering: false,
+            result_dedup: ResultDedup::None,
+            result_filters: Vec::new(),
+            result_merge_delay: None,
+            max_window_memory_bytes: None,
+            service_timeout: Duration::from_secs(10),
+            service_cache_ttl: Some(Duration::from_secs(60)),
+            dereference_static_graphs: true,
+            synchronous: false,
+            retained_results: None,
+            retained_results_ms: None,
+        }
+    }
+}
+
+/// Builder for an `RSPEngine` with a non-default configuration
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngineBuilder, ReportStrategy};
+///
+/// let mut engine = RSPEngineBuilder::new("...".to_string())
+///     .report_strategy(ReportStrategy::NonEmptyContent)
+///     .max_lateness(5000)
+///     .channel_capacity(1024)
+///     .build();
+/// engine.initialize().unwrap();
+/// ```
+pub struct RSPEngineBuilder {
+    query: EngineQuery,
+    config: EngineConfig,
+}
+
+/// Query of the engine being built
+enum EngineQuery {
+    Text(String),
+    Parsed(ParsedQuery),
+}
+
+impl RSPEngineBuilder {
+    /// Start building an engine for an RSP-QL query
+    pub fn new(query: String) -> Self {
+        Self {
+            query: EngineQuery::Text(query),
+            config: EngineConfig::default(),
+        }
+    }
+
+    /// Start building an engine for a query built or deserialized without RSP-QL
+    /// text; the dialect setting doesn't apply to it
+    pub fn from_parsed_query(query: ParsedQuery) -> Self {
+        Self {
+            query: EngineQuery::Parsed(query),
+            config: EngineConfig::default(),
+        }
+    }
+
+    /// Read the query in C-SPARQL or CQELS syntax, translated into RSP-QL, see
+    /// `RSPQLParser::with_dialect`
+    pub fn dialect(mut self, dialect: QueryDialect) -> Self {
+        self.config.dialect = dialect;
+        self
+    }
+
+    /// Set the report strategy of the windows; `initialize` fails unless it is
+    /// `NonEmptyContent` or `OnWindowClose`, the strategies implemented so far
+    pub fn report_strategy(mut self, report_strategy: ReportStrategy) -> Self {
+        self.config.report_strategy = report_strategy;
+        self
+    }
+
+    /// Set the tick of the windows; `initialize` fails unless it is `TimeDriven`,
+    /// the only tick implemented so far
+    pub fn tick(mut self, tick: Tick) -> Self {
+        self.config.tick = tick;
+        self
+    }
+
+    /// Set the report strategy of one window, identified by its IRI
+    pub fn window_report_strategy(
+        mut self,
+        window_name: &str,
+        report_strategy: ReportStrategy,
+    ) -> Self {
+        self.config
+            .window_report_strategies
+            .insert(window_name.to_string(), report_strategy);
+        self
+    }
+
+    /// Set the tick of one window, identified by its IRI
+    pub fn window_tick(mut self, window_name: &str, tick: Tick) -> Self {
+        self.config
+            .window_ticks
+            .insert(window_name.to_string(), tick);
+        self
+    }
+
+    /// Set the start time of the windows
+    pub fn start_time(mut self, start_time: i64) -> Self {
+        self.config.start_time = start_time;
+        self
+    }
+
+    /// Align the window boundaries of all windows to `origin`, e.g. 0 or midnight
+    pub fn origin(mut self, origin: i64) -> Self {
+        self.config.origin = Some(origin);
+        self
+    }
+
+    /// Align the window boundaries of one window, identified by its IRI, to `origin`
+    pub fn window_origin(mut self, window_name: &str, origin: i64) -> Self {
+        self.config
+            .window_origins
+            .insert(window_name.to_string(), origin);
+        self
+    }
+
+    /// Align the instances of one tumbling window, identified by its IRI, on the
+    /// calendar of a time zone, e.g. a `RANGE` of a day at local midnight whatever
+    /// daylight saving time, see `CSPARQLWindow::set_calendar`
+    /// Zones only come from POSIX `TZ` strings, see `TimeZone::parse`: IANA names
+    /// such as `Europe/Paris` aren't supported, the `TZ` string at the end of the
+    /// zone's tzdata file gives its current rules, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`
+    /// `initialize` fails if the window slides, is a landmark or doesn't report on close
+    pub fn window_calendar(mut self, window_name: &str, zone: TimeZone) -> Self {
+        self.config
+            .window_calendars
+            .insert(window_name.to_string(), zone);
+        self
+    }
+
+    /// Keep the state of one window, identified by its IRI, per value of a variable
+    /// of its pattern (e.g. `"sensor"`), so every key reports and evicts independently
+    pub fn window_partition_by(mut self, window_name: &str, variable: &str) -> Self {
+        self.config.window_partitions.insert(
+            window_name.to_string(),
+            variable.trim_start_matches('?').to_string(),
+        );
+        self
+    }
+
+    /// Make every closed window instance report, also without events, so absence
+    /// and gap detection see the empty windows
+    pub fn emit_empty_windows(mut self, enabled: bool) -> Self {
+        self.config.emit_empty_windows = enabled;
+        self
+    }
+
+    /// Make every closed instance of one window, identified by its IRI, report, also
+    /// without events, or only those with events
+    pub fn window_emit_empty_windows(mut self, window_name: &str, enabled: bool) -> Self {
+        self.config
+            .window_emit_empty_windows
+            .insert(window_name.to_string(), enabled);
+        self
+    }
+
+    /// Defer the reports of one window, identified by its IRI, by `delay` ms of event
+    /// time after its instances close, so late events are still included
+    pub fn window_delay(mut self, window_name: &str, delay: i64) -> Self {
+        self.config
+            .window_delays
+            .insert(window_name.to_string(), delay);
+        self
+    }
+
+    /// Drop the raw quads of one landmark window (`RANGE UNBOUNDED`), identified by
+    /// its IRI, `retention` ms of event time after their event, while its
+    /// incrementally maintained aggregates keep covering everything since its start
+    pub fn window_retention(mut self, window_name: &str, retention: i64) -> Self {
+        self.config
+            .window_retentions
+            .insert(window_name.to_string(), retention);
+        self
+    }
+
+    /// Only store the quads of one window, identified by its IRI, passing a filter,
+    /// e.g. those with the predicates its patterns match, so unrelated quads of its
+    /// streams don't take memory in every active instance
+    pub fn filter_window(mut self, window_name: &str, filter: QuadFilter) -> Self {
+        self.config
+            .window_filters
+            .insert(window_name.to_string(), filter);
+        self
+    }
+
+    /// Attach the static attributes of the subjects of the events of one window,
+    /// identified by its IRI, from a lookup table built from the static data, so
+    /// simple enrichments don't join the window with the static data on every report
+    pub fn enrich_window(mut self, window_name: &str, lookup: StaticLookup) -> Self {
+        self.config
+            .window_lookups
+            .insert(window_name.to_string(), lookup);
+        self
+    }
+
+    /// Keep every event adding a quad to one window, identified by its IRI, so
+    /// identical observations at different times within an instance are counted
+    /// apart by `COUNT` and `AVG` instead of collapsing into one quad
+    pub fn window_multiset(mut self, window_name: &str) -> Self {
+        self.config.multiset_windows.insert(window_name.to_string());
+        self
+    }
+
+    /// Expire the quads of one window, identified by its IRI, a time after their
+    /// event depending on their predicate or stream, so stale observations leave
+    /// its instances before they close
+    pub fn window_ttl(mut self, window_name: &str, ttl: QuadTtl) -> Self {
+        self.config.window_ttls.insert(window_name.to_string(), ttl);
+        self
+    }
+
+    /// Coalesce the consecutive events of one stream, identified by its IRI, sharing a
+    /// timestamp into one container of up to `max_quads` quads, so their window is
+    /// locked and scoped once for them
+    pub fn stream_micro_batch(mut self, stream_name: &str, max_quads: usize) -> Self {
+        self.config
+            .stream_micro_batches
+            .insert(stream_name.to_string(), max_quads);
+        self
+    }
+
+    /// Sort the events of one stream, identified by its IRI, pushed by several
+    /// producers by timestamp before they reach its window, holding each until an
+    /// event `max_skew` ms later is added, so a slightly older event of another
+    /// producer doesn't take the out-of-order path
+    /// Events older than those already sent pass through; `RDFStream::flush` and
+    /// `close_stream` send the held events
+    pub fn stream_reorder(mut self, stream_name: &str, max_skew: i64) -> Self {
+        self.config
+            .stream_max_skews
+            .insert(stream_name.to_string(), max_skew.max(0));
+        self
+    }
+
+    /// Close the open instances of the window of a stream once no event with an
+    /// advancing timestamp arrived on it for `timeout`, so the final results are
+    /// emitted even if its producer died without calling `close_stream`
+    /// The window advances past its latest instance without any sentinel quad;
+    /// events arriving afterwards open new instances as usual. A window fed by
+    /// several streams only closes once all of them are idle, those without a
+    /// timeout of their own for the shortest timeout of the others
+    pub fn stream_idle_timeout(mut self, stream_name: &str, timeout: Duration) -> Self {
+        self.config
+            .stream_idle_timeouts
+            .insert(stream_name.to_string(), timeout);
+        self
+    }
+
+    /// Close the open instances of the window of a stream once the last handle of the
+    /// stream given by `RSPEngine::get_stream` is dropped, as `stream_idle_timeout`
+    /// does, so short-lived programs get their final results without `close_stream`
+    /// A window fed by several streams closes once the handles of all of them are
+    /// dropped; later handles feed the window as usual
+    pub fn auto_close_streams(mut self, enabled: bool) -> Self {
+        self.config.auto_close_streams = enabled;
+        self
+    }
+
+    /// Set the quad of the event added by `RSPEngine::close_stream`, `None` to close
+    /// the windows with an empty event, e.g. so `?s ?p ?o` patterns don't match the
+    /// default `<urn:rsp:sentinel> <urn:rsp:type> "end"`
+    pub fn close_sentinel(mut self, sentinel: Option<Quad>) -> Self {
+        self.config.close_sentinel = sentinel;
+        self
+    }
+
+    /// Make a stream a side input updating the static data, e.g. a slowly changing
+    /// dimension such as device metadata, rather than a stream feeding a window
+    /// The quads of each event replace the static quads of their subjects, in their
+    /// graph, and a subject with an `rsp:deleted` quad is removed from the static
+    /// data, see `R2ROperator::upsert_static_subjects`. Events apply in arrival
+    /// order, so window reports from then on see them; the stream must not be
+    /// declared by a window of the query
+    pub fn side_input_stream(mut self, stream_name: &str) -> Self {
+        self.config
+            .side_input_streams
+            .insert(stream_name.to_string());
+        self
+    }
+
+    /// Discard the quads of one stream, identified by its IRI, failing a filter when
+    /// they are added, on the producer's thread, before they reach any window
+    pub fn filter_stream(mut self, stream_name: &str, filter: QuadFilter) -> Self {
+        self.config
+            .stream_filters
+            .insert(stream_name.to_string(), filter);
+        self
+    }
+
+    /// Limit the quads or events added per second to one stream, identified by its
+    /// IRI, delaying, dropping or sampling the events over the rate on the
+    /// producer's thread, see `RateLimit`
+    pub fn stream_rate_limit(mut self, stream_name: &str, limit: RateLimit) -> Self {
+        self.config
+            .stream_rate_limits
+            .insert(stream_name.to_string(), limit);
+        self
+    }
+
+    /// Validate every event of one stream, identified by its IRI, against SHACL
+    /// shapes when it is added, on the producer's thread; invalid events are
+    /// rejected, quarantined or annotated according to `policy`
+    pub fn validate_stream(
+        mut self,
+        stream_name: &str,
+        shapes: ShaclShapes,
+        policy: ValidationPolicy,
+    ) -> Self {
+        self.config
+            .stream_shapes
+            .insert(stream_name.to_string(), (shapes, policy));
+        self
+    }
+
+    /// Add a stream, identified by its IRI, whose quads are dispatched to windows of
+    /// the query by their content, e.g. a single feed carrying several kinds of
+    /// events; the stream must not be declared by a window of the query
+    pub fn route_stream(mut self, stream_name: &str, router: StreamRouter) -> Self {
+        self.config
+            .stream_routes
+            .insert(stream_name.to_string(), router);
+        self
+    }
+
+    /// Keep the static data in an on-disk oxigraph store at `path`, created if
+    /// missing, so static datasets may be larger than RAM and can be inspected
+    /// after the engine stopped; the quads already in the store are static data too
+    /// The store is opened by `RSPEngine::initialize`, see `R2ROperator::open_store`
+    pub fn static_store_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.static_store_path = Some(path.into());
+        self
+    }
+
+    /// Record every event added to the streams, with its stream and timestamp, to an
+    /// append-only log at `path`, created if missing, e.g. to reproduce a bug with
+    /// `RSPEngine::replay` and `InputLog::open` on another machine
+    /// The log is opened by `RSPEngine::initialize`
+    pub fn record_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.input_log_path = Some(path.into());
+        self
+    }
+
+    /// Evaluate the patterns of the query matching only static data once, when the
+    /// engine is initialized and after the static data changed, and join only the
+    /// window data with their solutions on every report, e.g. for queries joining
+    /// windows with large static graphs
+    /// Patterns with more than `max_solutions` solutions are still evaluated on
+    /// every report, see `R2ROperator::cache_static_patterns`
+    pub fn cache_static_patterns(mut self, max_solutions: usize) -> Self {
+        self.config.static_pattern_cache = Some(max_solutions);
+        self
+    }
+
+    /// Share the windows declared identically by this query and the queries of the
+    /// other engines built with the same registry, and the solutions of the `GRAPH`
+    /// clauses of those windows matching the same basic graph pattern, evaluated once
+    /// per report; see `PlanSharing`
+    /// Queries sharing window patterns are parsed again on every report
+    pub fn share_plans(mut self, sharing: &PlanSharing) -> Self {
+        self.config.plan_sharing = Some(sharing.clone());
+        self
+    }
+
+    /// Drop events more than `max_lateness` ms older than the last window report
+    pub fn max_lateness(mut self, max_lateness: i64) -> Self {
+        self.config.max_lateness = Some(max_lateness);
+        self
+    }
+
+    /// Consult `clock` for processing time, see `Clock`
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
+    /// Advance the windows to the time of a processing-time clock every `interval`
+    pub fn clock_interval(mut self, interval: Duration) -> Self {
+        self.config.clock_interval = interval;
+        self
+    }
+
+    /// Bound each stream channel to `capacity` pending event batches
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.config.channel_capacity = Some(capacity);
+        self
+    }
+
+    /// Set what streams do with the events added while the engine is paused,
+    /// see `RSPEngine::pause`
+    pub fn pause_policy(mut self, policy: PausePolicy) -> Self {
+        self.config.pause_policy = policy;
+        self
+    }
+
+    /// Shed load when the engine is overloaded, by sampling its input or skipping
+    /// evaluations, see `LoadSheddingPolicy`
+    pub fn load_shedding(mut self, policy: LoadSheddingPolicy) -> Self {
+        self.config.load_shedding = Some(policy);
+        self
+    }
+
+    /// Set the number of threads evaluating R2R queries
+    pub fn evaluation_threads(mut self, threads: usize) -> Self {
+        self.config.evaluation_threads = threads.max(1);
+        self
+    }
+
+    /// Name the threads of the engine `<prefix>-window-<window IRI>` (ingestion),
+    /// `<prefix>-r2r-<i>` (evaluation) and so on, instead of `rsp-...`, e.g. to tell
+    /// engines apart in a profiler
+    pub fn thread_name_prefix(mut self, prefix: &str) -> Self {
+        self.config.thread_name_prefix = prefix.to_string();
+        self
+    }
+
+    /// Enable or disable incremental evaluation of eligible aggregation queries
+    pub fn incremental_aggregation(mut self, enabled: bool) -> Self {
+        self.config.incremental_aggregation = enabled;
+        self
+    }
+
+    /// Keep the incremental aggregates of sliding windows per pane, the slices
+    /// `gcd(RANGE, STEP)` ms wide their instances are made of, and combine the panes
+    /// of an instance when it reports; each event is then aggregated once instead
+    /// of once per instance containing it, for a long RANGE with a short STEP
+    pub fn pane_aggregation(mut self, enabled: bool) -> Self {
+        self.config.pane_aggregation = enabled;
+        self
+    }
+
+    /// Reify every window quad with the timestamp of its event (`rsp:timestamp`),
+    /// so queries can order events or compute the time between them
+    pub fn annotate_timestamps(mut self, enabled: bool) -> Self {
+        self.config.annotate_timestamps = enabled;
+        self
+    }
+
+    /// Reify every window quad with the stream of its event (`rsp:stream`), so
+    /// queries over windows fed by several streams can tell their sources apart
+    pub fn annotate_streams(mut self, enabled: bool) -> Self {
+        self.config.annotate_streams = enabled;
+        self
+    }
+
+    /// Reorder the triple patterns of the query by their number of matches in the
+    /// static data and the reported window before each evaluation, most selective
+    /// first, e.g. for queries joining many patterns
+    pub fn join_reordering(mut self, enabled: bool) -> Self {
+        self.config.join_reordering = enabled;
+        self
+    }
+
+    /// Suppress the results repeated by consecutive reports of a window, e.g. with
+    /// sliding windows whose overlapping instances report the same bindings
+    /// Applies to `start_processing`, `on_result` callbacks and `poll_results`;
+    /// deltas and alerts see every result
+    pub fn result_dedup(mut self, mode: ResultDedup) -> Self {
+        self.config.result_dedup = mode;
+        self
+    }
+
+    /// Only emit the results passing `filter`, and every filter added before, e.g.
+    /// the aggregates above an alerting threshold
+    /// Applies to `start_processing`, `on_result` callbacks and `poll_results`,
+    /// before deduplication; deltas and alerts see every result
+    pub fn filter_results(mut self, filter: ResultFilter) -> Self {
+        self.config.result_filters.push(filter);
+        self
+    }
+
+    /// Deliver the results of the windows of the query in the order of their close
+    /// time, rather than as their reports are evaluated
+    /// A report waits until every window reported past its close time, or until one
+    /// of them is `max_delay` (in event time) past it, so idle windows only delay
+    /// results by that much; windows stop holding results back once their streams
+    /// are closed with `close_stream`
+    pub fn merge_window_results(mut self, max_delay: Duration) -> Self {
+        self.config.result_merge_delay = Some(max_delay);
+        self
+    }
+
+    /// Limit the memory of each window's buffers, spilling to disk beyond it
+    pub fn max_window_memory_bytes(mut self, max_bytes: usize) -> Self {
+        self.config.max_window_memory_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Give up on `SERVICE` requests after `timeout`
+    pub fn service_timeout(mut self, timeout: Duration) -> Self {
+        self.config.service_timeout = timeout;
+        self
+    }
+
+    /// Reuse `SERVICE` results for `ttl`, or query the endpoint on every report with `None`
+    pub fn service_cache_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.config.service_cache_ttl = ttl;
+        self
+    }
+
+    /// Enable or disable fetching the static graphs of the query over HTTP when they
+    /// aren't in the static data at initialization
+    pub fn dereference_static_graphs(mut self, enabled: bool) -> Self {
+        self.config.dereference_static_graphs = enabled;
+        self
+    }
+
+    /// Process events synchronously: adding quads to a stream applies them to the
+    /// window and evaluates its reports before returning, without background
+    /// threads, and `RSPEngine::poll_results` returns the results so far.
+    /// Meant for deterministic tests of pipelines
+    pub fn synchronous(mut self, enabled: bool) -> Self {
+        self.config.synchronous = enabled;
+        self
+    }
+
+    /// Retain the latest `count` results for `RSPEngine::recent_results`
+    pub fn retain_results(mut self, count: usize) -> Self {
+        self.config.retained_results = Some(count);
+        self
+    }
+
+    /// Retain the results of the last `duration_ms` ms of event time for
+    /// `RSPEngine::recent_results`
+    pub fn retain_results_for(mut self, duration_ms: i64) -> Self {
+        self.config.retained_results_ms = Some(duration_ms);
+        self
+    }
+
+    /// Build the engine; call `initialize` on it before adding data
+    pub fn build(self) -> RSPEngine {
+        match self.query {
+            EngineQuery::Text(query) => RSPEngine::with_config(query, self.config),
+            EngineQuery::Parsed(query) => RSPEngine::from_parsed_query(query, self.config),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = r#"
+        REGISTER RStream <http://example.org/output> AS
+        PREFIX ex: <http://example.org/>
+        SELECT ?s ?p ?o
+        FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+        WHERE {
+            WINDOW ex:win1 { ?s ?p ?o }
+        }
+    "#;
+
+    #[test]
+    fn test_builder_configures_windows() {
+        let mut engine = RSPEngineBuilder::new(QUERY.to_string())
+            .report_strategy(ReportStrategy::NonEmptyContent)
+            .tick(Tick::TimeDriven)
+            .start_time(100)
+            .max_lateness(50)
+            .channel_capacity(8)
+            .evaluation_threads(2)
+            .build();
+        assert_eq!(engine.config().channel_capacity, Some(8));
+        assert_eq!(engine.evaluation_threads(), 2);
+        engine.initialize().unwrap();
+
+        let window = engine.get_window("http://example.org/win1").unwrap();
+        let window = window.lock().unwrap();
+        assert_eq!(window.report, ReportStrategy::NonEmptyContent);
+        assert_eq!(window.tick, Tick::TimeDriven);
+        assert_eq!(window.t0, 100);
+        assert_eq!(window.max_lateness(), Some(50));
+    }
+
+    #[test]
+    fn test_window_overrides() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <http://example.org/output> AS
+            SELECT *
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 5 REPORT NON_EMPTY_CONTENT]
+            FROM NAMED WINDOW ex:win2 ON STREAM ex:stream2 [RANGE 10 STEP 5 TICK TIME_DRIVEN]
+            FROM NAMED WINDOW ex:win3 ON STREAM ex:stream3 [RANGE 10 STEP 5 REPORT ON_WINDOW_CLOSE]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#;
+        let mut engine = RSPEngineBuilder::new(query.to_string())
+            .window_report_strategy("http://example.org/win3", ReportStrategy::NonEmptyContent)
+            .build();
+        engine.initialize().unwrap();
+
+        let settings = |name: &str| {
+            let window = engine.get_window(name).unwrap();
+            let window = window.lock().unwrap();
+            (window.report, window.tick)
+        };
+        assert_eq!(
+            settings("http://example.org/win1"),
+            (ReportStrategy::NonEmptyContent, Tick::TimeDriven)
+        );
+        assert_eq!(
+            settings("http://example.org/win2"),
+            (ReportStrategy::OnWindowClose, Tick::TimeDriven)
+        );
+        // The builder takes precedence over the query
+        assert_eq!(
+            settings("http://example.org/win3"),
+            (ReportStrategy::NonEmptyContent, Tick::TimeDriven)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_window_settings_fail() {
+        let engine = |builder: RSPEngineBuilder| builder.build().initialize();
+        let builder = || RSPEngineBuilder::new(QUERY.to_string());
+        assert!(engine(builder().tick(Tick::TupleDriven)).is_err());
+        let win1 = "http://example.org/win1";
+        assert!(engine(builder().window_report_strategy(win1, ReportStrategy::Periodic)).is_err());
+        let query = QUERY.replace("STEP 5]", "STEP 5 REPORT ON_CONTENT_CHANGE]");
+        let error = RSPEngine::new(query).initialize().unwrap_err();
+        assert!(error.contains("Unsupported report strategy"), "{}", error);
+    }
+
+    #[test]
+    fn test_window_origins() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <http://example.org/output> AS
+            SELECT *
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 5 ALIGN 0]
+            FROM NAMED WINDOW ex:win2 ON STREAM ex:stream2 [RANGE 10 STEP 5 ALIGN 3]
+            FROM NAMED WINDOW ex:win3 ON STREAM ex:stream3 [RANGE 10 STEP 5]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#;
+        let mut engine = RSPEngineBuilder::new(query.to_string())
+            .start_time(100)
+            .window_origin("http://example.org/win2", 7)
+            .build();
+        engine.initialize().unwrap();
+
+        let origin = |name: &str| {
+            let window = engine.get_window(name).unwrap();
+            let window = window.lock().unwrap();
+            (window.origin(), window.t0)
+        };
+        assert_eq!(origin("http://example.org/win1"), (Some(0), 0));
+        assert_eq!(origin("http://example.org/win2"), (Some(7), 7));
+        assert_eq!(origin("http://example.org/win3"), (None, 100));
+    }
+
+    #[test]
+    fn test_origin_makes_windows_reproducible() {
+        let mut engine = RSPEngineBuilder::new(QUERY.to_string()).origin(0).build();
+        engine.initialize().unwrap();
+
+        let window = engine.get_window("http://example.org/win1").unwrap();
+        let mut window = window.lock().unwrap();
+        window.scope(13);
+        let mut ranges = window.get_active_window_ranges();
+        ranges.sort();
+        // Aligned to 0 rather than to the first event at 13
+        assert_eq!(ranges, vec![(5, 15), (10, 20)]);
+    }
+
+    #[test]
+    fn test_window_partition_by() {
+        let mut engine = RSPEngineBuilder::new(QUERY.to_string())
+            .window_partition_by("http://example.org/win1", "?s")
+            .build();
+        engine.initialize().unwrap();
+        let window = engine.get_window("http://example.org/win1").unwrap();
+        assert_eq!(
+            window.lock().unwrap().partition_key(),
+            Some(&crate::PartitionKey::subject_of(None))
+        );
+
+        let mut engine = RSPEngineBuilder::new(QUERY.to_string())
+            .window_partition_by("http://example.org/win1", "unknown")
+            .build();
+        assert!(engine.initialize().is_err());
+    }
+
+    #[test]
+    fn test_default_config_matches_new() {
+        let engine = RSPEngineBuilder::new(QUERY.to_string()).build();
+        assert_eq!(*engine.config(), EngineConfig::default());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::file::{json_object, json_string};
+use crate::metrics::MetricsRecorder;
+use crate::parsed_query::ParsedQuery;
+use crate::timestamp::wall_clock_millis;
+use crate::{BindingWithTimestamp, CSPARQLWindow};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// Results shown by the console, the oldest are dropped first
+const RECENT_RESULTS: usize = 50;
+
+/// How long a client may take to send its request or read the answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the console checks for connections, and whether its engine is gone
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Page of the console, polling `/state` every second
+const PAGE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>rsp-rs console</title>
+<style>
+body { font-family: sans-serif; margin: 1.5em; color: #222; }
+h2 { margin-top: 1.5em; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; vertical-align: top; }
+pre { background: #f4f4f4; padding: 0.6em; overflow-x: auto; }
+canvas { border: 1px solid #ccc; }
+</style>
+</head>
+<body>
+<h1>rsp-rs console</h1>
+<h2>Query</h2>
+<div id="query"></div>
+<h2>Throughput</h2>
+<canvas id="throughput" width="720" height="180"></canvas>
+<div>Events ingested (blue) and results emitted (orange) per second, <span id="counters"></span></div>
+<h2>Windows</h2>
+<table id="windows"></table>
+<h2>Recent results</h2>
+<table id="results"></table>
+<script>
+const history = [];
+let previous = null;
+function text(value) {
+  const span = document.createElement("span");
+  span.textContent = value;
+  return span.innerHTML;
+}
+function row(cells, tag) {
+  return "<tr>" + cells.map(c => "<" + tag + ">" + c + "</" + tag + ">").join("") + "</tr>";
+}
+function chart(canvas, series) {
+  const ctx = canvas.getContext("2d");
+  ctx.clearRect(0, 0, canvas.width, canvas.height);
+  const max = Math.max(1, ...series.flatMap(s => s.values));
+  for (const s of series) {
+    ctx.strokeStyle = s.color;
+    ctx.beginPath();
+    s.values.forEach((v, i) => {
+      const x = canvas.width * i / 119;
+      const y = canvas.height - 2 - (canvas.height - 4) * v / max;
+      i === 0 ? ctx.moveTo(x, y) : ctx.lineTo(x, y);
+    });
+    ctx.stroke();
+  }
+  ctx.fillStyle = "#222";
+  ctx.fillText(max.toFixed(1) + "/s", 4, 12);
+}
+function render(state) {
+  const q = state.query;
+  document.getElementById("query").innerHTML =
+    "<div>" + text(q.operator) + " " + text(q.output) + "</div><pre>" + text(q.sparql) + "</pre>";
+  const m = state.metrics;
+  if (previous) {
+    const seconds = Math.max(0.001, (state.time - previous.time) / 1000);
+    history.push([
+      (m.events_ingested - previous.metrics.events_ingested) / seconds,
+      (m.results_emitted - previous.metrics.results_emitted) / seconds,
+    ]);
+    if (history.length > 120) history.shift();
+  }
+  previous = state;
+  chart(document.getElementById("throughput"), [
+    { color: "#1f77b4", values: history.map(h => h[0]) },
+    { color: "#ff7f0e", values: history.map(h => h[1]) },
+  ]);
+  document.getElementById("counters").textContent =
+    m.events_ingested + " events, " + m.results_emitted + " results, " +
+    m.queue_depth + " queued, " + m.active_windows + " open windows";
+  let windows = row(["Window", "Streams", "Width / slide", "Time", "Instance", "Quads", "Per stream"], "th");
+  for (const w of state.windows) {
+    const instances = w.instances.length ? w.instances : [null];
+    instances.forEach((instance, i) => {
+      const head = i === 0
+        ? [text(w.name), w.streams.map(text).join("<br>"), w.width + " / " + w.slide, w.time]
+        : ["", "", "", ""];
+      const cells = instance
+        ? ["[" + instance.open + ", " + instance.close + ")", instance.quads,
+           Object.entries(instance.streams).map(([s, n]) => text(s) + ": " + n).join("<br>")]
+        : ["none", "", ""];
+      windows += row(head.concat(cells), "td");
+    });
+  }
+  document.getElementById("windows").innerHTML = windows;
+  const names = [...new Set(state.results.flatMap(r => Object.keys(r)))];
+  let results = row(names.map(text), "th");
+  for (const r of state.results.slice().reverse()) {
+    results += row(names.map(n => text(r[n] === undefined ? "" : r[n])), "td");
+  }
+  document.getElementById("results").innerHTML = results;
+}
+async function poll() {
+  try {
+    const response = await fetch("/state");
+    render(await response.json());
+  } catch (e) {
+    console.error(e);
+  }
+  setTimeout(poll, 1000);
+}
+poll();
+</script>
+</body>
+</html>
+"##;
+
+/// State of an engine shown by the debug console, read again on every poll
+pub(crate) struct Console {
+    query: ParsedQuery,
+    windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+    metrics: Arc<MetricsRecorder>,
+    results: Mutex<VecDeque<BindingWithTimestamp>>,
+}
+
+impl Console {
+    pub(crate) fn new(
+        query: ParsedQuery,
+        windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+        metrics: Arc<MetricsRecorder>,
+    ) -> Self {
+        Self {
+            query,
+            windows,
+            metrics,
+            results: Mutex::new(VecDeque::with_capacity(RECENT_RESULTS)),
+        }
+    }
+
+    /// Keep a result among the recent ones
+    pub(crate) fn record(&self, result: &BindingWithTimestamp) {
+        let mut results = self.results.lock().unwrap();
+        if results.len() == RECENT_RESULTS {
+            results.pop_front();
+        }
+        results.push_back(result.clone());
+    }
+
+    /// Render the query, metrics, open window instances and recent results as JSON
+    fn state(&self) -> String {
+        let query = format!(
+            "{{\"output\":{},\"operator\":{},\"sparql\":{}}}",
+            json_string(&self.query.r2s.name),
+            json_string(&format!("{:?}", self.query.r2s.operator)),
+            json_string(&self.query.sparql_query)
+        );
+
+        let mut active_windows = 0;
+        let mut windows = Vec::new();
+        for definition in &self.query.s2r {
+            let Some(window) = self.windows.get(&definition.window_name) else {
+                continue;
+            };
+            let window = window.lock().unwrap();
+            active_windows += window.get_active_window_count();
+            let streams: Vec<&String> = std::iter::once(&definition.stream_name)
+                .chain(&definition.additional_stream_names)
+                .collect();
+            let instances: Vec<String> = window
+                .active_windows
+                .iter()
+                .map(|(instance, container)| {
+                    // Windows only record the stream of their quads when fed by several
+                    let mut sizes: BTreeMap<&str, usize> = BTreeMap::new();
+                    if container.streams.is_empty() {
+                        sizes.insert(&definition.stream_name, container.len());
+                    }
+                    for stream in container.streams.values() {
+                        *sizes.entry(stream).or_default() += 1;
+                    }
+                    let sizes: Vec<String> = sizes
+                        .iter()
+                        .map(|(stream, size)| format!("{}:{}", json_string(stream), size))
+                        .collect();
+                    format!(
+                        "{{\"open\":{},\"close\":{},\"quads\":{},\"streams\":{{{}}}}}",
+                        instance.open,
+                        instance.close,
+                        container.len(),
+                        sizes.join(",")
+                    )
+                })
+                .collect();
+            let streams: Vec<String> = streams.iter().map(|s| json_string(s)).collect();
+            windows.push(format!(
+                "{{\"name\":{},\"streams\":[{}],\"width\":{},\"slide\":{},\"time\":{},\"instances\":[{}]}}",
+                json_string(&window.name),
+                streams.join(","),
+                window.width,
+                window.slide,
+                window.time,
+                instances.join(",")
+            ));
+        }
+
+        let metrics = self.metrics.snapshot(active_windows);
+        let metrics = format!(
+            "{{\"events_ingested\":{},\"results_emitted\":{},\"queue_depth\":{},\"active_windows\":{}}}",
+            metrics.events_ingested,
+            metrics.results_emitted,
+            metrics.queue_depth,
+            metrics.active_windows
+        );
+        let results: Vec<String> = self
+            .results
+            .lock()
+            .unwrap()
+            .iter()
+            .map(json_object)
+            .collect();
+        format!(
+            "{{\"time\":{},\"query\":{},\"metrics\":{},\"windows\":[{}],\"results\":[{}]}}",
+            wall_clock_millis(),
+            query,
+            metrics,
+            windows.join(","),
+            results.join(",")
+        )
+    }
+}
+
+/// Serve the console page on `/` and the state it polls on `/state` from a thread
+/// named `name`, until the console is dropped with its engine
+/// The connections are answered one at a time, each given `REQUEST_TIMEOUT` so a
+/// stalled client can't block the following requests
+pub(crate) fn serve_console(
+    listener: TcpListener,
+    console: Weak<Console>,
+    name: String,
+) -> Result<thread::JoinHandle<()>, String> {
+    // Accepting without blocking lets the thread notice that the engine is gone
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to set up debug console: {}", e))?;
+    thread::Builder::new()
+        .name(name)
+        .spawn(move || {
+            loop {
+                let Some(console) = console.upgrade() else {
+                    return;
+                };
+                match listener.accept() {
+                    Ok((stream, _)) => answer(stream, &console),
+                    Err(e) => {
+                        if e.kind() != ErrorKind::WouldBlock {
+                            tracing::warn!("Failed to accept console connection: {}", e);
+                        }
+                        drop(console);
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                }
+            }
+        })
+        .map_err(|e| format!("Failed to spawn console thread: {}", e))
+}
+
+/// Answer one request to the console
+fn answer(stream: TcpStream, console: &Console) {
+    let timeouts = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_read_timeout(Some(REQUEST_TIMEOUT)))
+        .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)));
+    if let Err(e) = timeouts {
+        tracing::warn!("Failed to set the timeouts of a console request: {}", e);
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return;
+    }
+    // Skip the headers, requests have no body
+    let mut line = String::new();
+    while reader.read_line(&mut line).map(|n| n > 0).unwrap_or(false) && line != "\r\n" {
+        line.clear();
+    }
+    let path = request.split(' ').nth(1).unwrap_or("/");
+    let (status, content_type, body) = match path {
+        "/" => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+        "/state" => ("200 OK", "application/json", console.state()),
+        _ => ("404 Not Found", "text/plain", "Not found".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+        tracing::warn!("Failed to answer console request: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsed_query::Operator;
+    use crate::parsed_query::WindowDefinition;
+    use crate::{ReportStrategy, Tick};
+    use oxigraph::model::{GraphName, Literal, NamedNode, Quad};
+    use std::io::Read;
+
+    #[test]
+    fn test_state_shows_windows_and_results() {
+        let mut query = ParsedQuery::new("SELECT ?s WHERE { ?s ?p ?o }".to_string());
+        query.set_r2s(Operator::RStream, "http://example.org/out".to_string());
+        query.add_s2r_window(WindowDefinition::new(
+            "http://example.org/w1",
+            "http://example.org/stream1",
+            10,
+            2,
+        ));
+        let mut window = CSPARQLWindow::new(
+            "http://example.org/w1".to_string(),
+            10,
+            2,
+            ReportStrategy::OnWindowClose,
+            Tick::TimeDriven,
+            0,
+        );
+        let quad = Quad::new(
+            NamedNode::new_unchecked("http://example.org/sensor1"),
+            NamedNode::new_unchecked("http://example.org/value"),
+            Literal::from(1),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, 4);
+        let windows = HashMap::from([(
+            "http://example.org/w1".to_string(),
+            Arc::new(Mutex::new(window)),
+        )]);
+        let console = Console::new(query, windows, Arc::new(MetricsRecorder::default()));
+
+        let state = console.state();
+        assert!(state.contains("\"output\":\"http://example.org/out\",\"operator\":\"RStream\""));
+        assert!(state.contains(
+            "{\"open\":0,\"close\":10,\"quads\":1,\"streams\":{\"http://example.org/stream1\":1}}"
+        ));
+        assert!(state.contains("\"results\":[]"));
+    }
+
+    #[test]
+    fn test_console_stops_with_engine() {
+        let query = ParsedQuery::new("SELECT ?s WHERE { ?s ?p ?o }".to_string());
+        let metrics = Arc::new(MetricsRecorder::default());
+        let console = Arc::new(Console::new(query, HashMap::new(), metrics));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let name = "rsp-console-test".to_string();
+        let server = serve_console(listener, Arc::downgrade(&console), name).unwrap();
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"GET /state HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        drop(console);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+use crate::shacl::ShaclViolation;
+use oxigraph::model::Quad;
+use std::sync::{Arc, Mutex, mpsc};
+
+/// Why an event was dropped before reaching the windows of its stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropReason {
+    /// Event older than the allowed lateness of a window, at the time of its latest
+    /// report
+    Late { window: String, watermark: i64 },
+    /// Event violating the SHACL shapes of its stream, rejected or quarantined
+    Invalid(Vec<ShaclViolation>),
+    /// Serialized event failing to parse, with the parse error
+    Unparsable(String),
+    /// Event over the rate limit of its stream, see `RateLimitAction`
+    RateLimited,
+    /// Event shed while the engine was overloaded, see `LoadSheddingPolicy`
+    Shed,
+}
+
+impl DropReason {
+    /// Get the code of the reason: `late`, `invalid`, `unparsable`, `rate_limited`
+    /// or `shed`
+    pub fn code(&self) -> &'static str {
+        match self {
+            DropReason::Late { .. } => "late",
+            DropReason::Invalid(_) => "invalid",
+            DropReason::Unparsable(_) => "unparsable",
+            DropReason::RateLimited => "rate_limited",
+            DropReason::Shed => "shed",
+        }
+    }
+}
+
+/// Event dropped by an engine, from `RSPEngine::dead_letters`, with what is needed
+/// to audit it and add it again once repaired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadLetter {
+    /// IRI of the stream the event was added to
+    pub stream: String,
+    /// Timestamp of the event, `None` if it failed to parse before getting one
+    pub timestamp: Option<i64>,
+    /// Quads of the event, empty if it failed to parse
+    pub quads: Vec<Quad>,
+    /// Serialized event, if it failed to parse
+    pub document: Option<String>,
+    pub reason: DropReason,
+}
+
+/// Subscribers to the events dropped by the streams and windows of an engine
+#[derive(Clone, Default)]
+pub(crate) struct DeadLetters {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<DeadLetter>>>>,
+}
+
+impl DeadLetters {
+    /// Receive the events dropped from now on
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<DeadLetter> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send the dead letter built by `letter` to the subscribers, if any, so the
+    /// quads of dropped events are only copied while someone audits them
+    pub(crate) fn send(&self, letter: impl FnOnce() -> DeadLetter) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        let letter = letter();
+        subscribers.retain(|tx| tx.send(letter.clone()).is_ok());
+    }
+}
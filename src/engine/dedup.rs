@@ -0,0 +1,191 @@
+use crate::delta::Binding;
+use oxigraph::sparql::QuerySolution;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+/// Suppression of the results repeated by the reports of a window, e.g. the
+/// bindings of events covered by several overlapping instances of a sliding window
+///
+/// Results are compared per window as bindings, regardless of the time range of
+/// their report; repetitions within one report are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultDedup {
+    /// Every result is emitted
+    #[default]
+    None,
+    /// Results identical to one emitted by the previous `k` reports are suppressed
+    Recent(usize),
+    /// Only results absent from the previous report are emitted, like an IStream
+    /// of the bindings
+    NewBindings,
+}
+
+/// Report waiting for earlier ones: its solutions and time, `None` for a failed
+/// evaluation
+type PendingReport<T> = Option<(Vec<QuerySolution>, T)>;
+
+/// Filters the reports of one window in report order
+///
+/// Reports are evaluated in parallel, so each one reserves a ticket when the
+/// window emits it and is filtered once the reports before it are.
+#[derive(Debug)]
+pub(crate) struct Deduplicator<T> {
+    mode: ResultDedup,
+    next_ticket: u64,
+    next_to_apply: u64,
+    pending: BTreeMap<u64, PendingReport<T>>,
+    /// Bindings of the previous reports compared against, most recent last
+    recent: VecDeque<HashSet<Binding>>,
+}
+
+impl<T> Deduplicator<T> {
+    pub(crate) fn new(mode: ResultDedup) -> Self {
+        Self {
+            mode,
+            next_ticket: 0,
+            next_to_apply: 0,
+            pending: BTreeMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Reserve the position of a window report
+    pub(crate) fn reserve(&mut self) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        ticket
+    }
+
+    /// Record the solutions of a report and get the filtered reports that can be
+    /// delivered, in report order, with their time
+    pub(crate) fn complete(
+        &mut self,
+        ticket: u64,
+        report: PendingReport<T>,
+    ) -> Vec<(Vec<QuerySolution>, T)> {
+        self.pending.insert(ticket, report);
+
+        let mut ready = Vec::new();
+        while let Some(report) = self.pending.remove(&self.next_to_apply) {
+            self.next_to_apply += 1;
+            if let Some((solutions, timestamp)) = report {
+                ready.push((self.filter(solutions), timestamp));
+            }
+        }
+        ready
+    }
+
+    /// Drop the solutions repeating the previous reports, and remember this one
+    fn filter(&mut self, solutions: Vec<QuerySolution>) -> Vec<QuerySolution> {
+        let (history, every_result) = match self.mode {
+            ResultDedup::None => return solutions,
+            ResultDedup::Recent(reports) => (reports, false),
+            ResultDedup::NewBindings => (1, true),
+        };
+        let mut kept = Vec::new();
+        let mut remembered = HashSet::new();
+        for solution in solutions {
+            let binding = Binding::from(&solution);
+            let repeated = self.recent.iter().any(|report| report.contains(&binding));
+            if !repeated {
+                kept.push(solution);
+            }
+            // IStreams compare against every result, the others against the emitted ones
+            if every_result || !repeated {
+                remembered.insert(binding);
+            }
+        }
+        self.recent.push_back(remembered);
+        while self.recent.len() > history {
+            self.recent.pop_front();
+        }
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{Literal, Variable};
+    use std::sync::Arc;
+
+    fn report(values: &[i64]) -> Vec<QuerySolution> {
+        let variables: Arc<[Variable]> = Arc::new([Variable::new_unchecked("x")]);
+        values
+            .iter()
+            .map(|&value| (variables.clone(), vec![Some(Literal::from(value).into())]).into())
+            .collect()
+    }
+
+    fn filtered(mode: ResultDedup, reports: &[&[i64]]) -> Vec<Vec<String>> {
+        let mut deduplicator = Deduplicator::new(mode);
+        let mut values = Vec::new();
+        for (time, values_of_report) in reports.iter().enumerate() {
+            let ticket = deduplicator.reserve();
+            let solutions = Some((report(values_of_report), time as i64));
+            for (solutions, _) in deduplicator.complete(ticket, solutions) {
+                let x = |solution: &QuerySolution| solution.get("x").unwrap().to_string();
+                values.push(solutions.iter().map(x).collect());
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_repeated_results_are_suppressed() {
+        let reports: &[&[i64]] = &[&[1, 2], &[1, 2, 3], &[1, 3], &[1]];
+        let literals = |values: &[i64]| -> Vec<String> {
+            values
+                .iter()
+                .map(|v| Literal::from(*v).to_string())
+                .collect()
+        };
+        assert_eq!(
+            filtered(ResultDedup::None, reports),
+            reports.iter().map(|r| literals(r)).collect::<Vec<_>>()
+        );
+        // 1 is emitted again once a report didn't emit it
+        assert_eq!(
+            filtered(ResultDedup::Recent(1), reports),
+            vec![
+                literals(&[1, 2]),
+                literals(&[3]),
+                literals(&[1]),
+                literals(&[])
+            ]
+        );
+        assert_eq!(
+            filtered(ResultDedup::Recent(2), reports),
+            vec![
+                literals(&[1, 2]),
+                literals(&[3]),
+                literals(&[]),
+                literals(&[1])
+            ]
+        );
+        assert_eq!(
+            filtered(ResultDedup::NewBindings, reports),
+            vec![
+                literals(&[1, 2]),
+                literals(&[3]),
+                literals(&[]),
+                literals(&[])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_are_filtered_in_order() {
+        let mut deduplicator = Deduplicator::new(ResultDedup::NewBindings);
+        let first = deduplicator.reserve();
+        let second = deduplicator.reserve();
+        assert!(
+            deduplicator
+                .complete(second, Some((report(&[1]), 10)))
+                .is_empty()
+        );
+        let ready = deduplicator.complete(first, Some((report(&[1]), 0)));
+        assert_eq!(ready.len(), 2);
+        assert_eq!((ready[0].0.len(), ready[0].1), (1, 0));
+        assert_eq!((ready[1].0.len(), ready[1].1), (0, 10));
+    }
+}
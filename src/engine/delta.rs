@@ -0,0 +1,350 @@
+use oxigraph::model::Term;
+use oxigraph::sparql::QuerySolution;
+use std::collections::{BTreeMap, HashMap};
+
+/// One solution of the R2R query, as variable/value pairs sorted by variable name
+///
+/// With the `serde` feature, bindings are (de)serialized as in the SPARQL 1.1 JSON
+/// results format: an object from variable names to terms, typed as `uri`,
+/// `bnode` or `literal` with their datatype or language. Triple terms can't be
+/// serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub values: Vec<(String, Term)>,
+}
+
+impl Binding {
+    /// Get the value bound to a variable, if any
+    pub fn get(&self, variable: &str) -> Option<&Term> {
+        self.values
+            .iter()
+            .find(|(name, _)| name == variable)
+            .map(|(_, term)| term)
+    }
+
+    /// Get the value bound to a variable as a number, if it is a numeric literal
+    pub fn number(&self, variable: &str) -> Option<f64> {
+        let Term::Literal(literal) = self.get(variable)? else {
+            return None;
+        };
+        let datatype = literal
+            .datatype()
+            .as_str()
+            .strip_prefix("http://www.w3.org/2001/XMLSchema#")?;
+        match datatype {
+            "integer" | "decimal" | "float" | "double" | "long" | "int" | "short" | "byte"
+            | "nonNegativeInteger" | "nonPositiveInteger" | "negativeInteger"
+            | "positiveInteger" | "unsignedLong" | "unsignedInt" | "unsignedShort"
+            | "unsignedByte" => literal.value().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl From<&QuerySolution> for Binding {
+    fn from(solution: &QuerySolution) -> Self {
+        let mut values: Vec<(String, Term)> = solution
+            .iter()
+            .map(|(variable, term)| (variable.as_str().to_string(), term.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Self { values }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serialization {
+    use super::Binding;
+    use oxigraph::model::vocab::xsd;
+    use oxigraph::model::{BlankNode, Literal, NamedNode, Term};
+    use serde::de::Error as _;
+    use serde::ser::{Error as _, SerializeMap};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    /// Term as in the SPARQL 1.1 JSON results format
+    #[derive(Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    enum JsonTerm {
+        Uri {
+            value: String,
+        },
+        Bnode {
+            value: String,
+        },
+        Literal {
+            value: String,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            datatype: Option<String>,
+            #[serde(rename = "xml:lang", default, skip_serializing_if = "Option::is_none")]
+            language: Option<String>,
+        },
+    }
+
+    impl TryFrom<&Term> for JsonTerm {
+        type Error = String;
+
+        fn try_from(term: &Term) -> Result<Self, String> {
+            Ok(match term {
+                Term::NamedNode(node) => JsonTerm::Uri {
+                    value: node.as_str().to_string(),
+                },
+                Term::BlankNode(node) => JsonTerm::Bnode {
+                    value: node.as_str().to_string(),
+                },
+                Term::Literal(literal) => JsonTerm::Literal {
+                    value: literal.value().to_string(),
+                    // Plain strings and language-tagged strings have implicit datatypes
+                    datatype: (literal.language().is_none() && literal.datatype() != xsd::STRING)
+                        .then(|| literal.datatype().as_str().to_string()),
+                    language: literal.language().map(str::to_string),
+                },
+                #[cfg(feature = "rdf-star")]
+                Term::Triple(_) => return Err("Triple terms can't be serialized".to_string()),
+            })
+        }
+    }
+
+    impl TryFrom<JsonTerm> for Term {
+        type Error = String;
+
+        fn try_from(term: JsonTerm) -> Result<Self, String> {
+            match term {
+                JsonTerm::Uri { value } => NamedNode::new(value)
+                    .map(Term::from)
+                    .map_err(|e| e.to_string()),
+                JsonTerm::Bnode { value } => BlankNode::new(value)
+                    .map(Term::from)
+                    .map_err(|e| e.to_string()),
+                JsonTerm::Literal {
+                    value,
+                    language: Some(language),
+                    ..
+                } => Literal::new_language_tagged_literal(value, language)
+                    .map(Term::from)
+                    .map_err(|e| e.to_string()),
+                JsonTerm::Literal {
+                    value,
+                    datatype: Some(datatype),
+                    ..
+                } => {
+                    let datatype = NamedNode::new(datatype).map_err(|e| e.to_string())?;
+                    Ok(Literal::new_typed_literal(value, datatype).into())
+                }
+                JsonTerm::Literal { value, .. } => Ok(Literal::new_simple_literal(value).into()),
+            }
+        }
+    }
+
+    impl Serialize for Binding {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.values.len()))?;
+            for (variable, term) in &self.values {
+                let term = JsonTerm::try_from(term).map_err(S::Error::custom)?;
+                map.serialize_entry(variable, &term)?;
+            }
+            map.end()
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Binding {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            // Sorted by variable name, like the bindings of solutions
+            let terms = BTreeMap::<String, JsonTerm>::deserialize(deserializer)?;
+            let values = terms
+                .into_iter()
+                .map(|(variable, term)| Ok((variable, Term::try_from(term)?)))
+                .collect::<Result<_, String>>()
+                .map_err(D::Error::custom)?;
+            Ok(Binding { values })
+        }
+    }
+}
+
+/// Change of the results of one window between two consecutive reports
+///
+/// `added` is the IStream of the report and `removed` its DStream, so applying
+/// the deltas in order keeps a materialized view equal to the latest results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultDelta {
+    /// Bindings present in this report but not in the previous one
+    pub added: Vec<Binding>,
+    /// Bindings present in the previous report but not in this one
+    pub removed: Vec<Binding>,
+    /// Time range of the report, as in `BindingWithTimestamp`
+    pub window: (i64, i64),
+}
+
+/// Computes the deltas between consecutive reports of one window
+///
+/// Reports are evaluated in parallel, so each one reserves a ticket when the
+/// window emits it and results are diffed in ticket order. Bindings are
+/// compared as a bag: duplicates are added and removed one by one.
+#[derive(Debug, Default)]
+pub(crate) struct DeltaTracker {
+    next_ticket: u64,
+    next_to_apply: u64,
+    /// Finished reports waiting for earlier ones; `None` for failed evaluations
+    pending: BTreeMap<u64, Option<(Vec<Binding>, (i64, i64))>>,
+    previous: HashMap<Binding, usize>,
+}
+
+impl DeltaTracker {
+    /// Reserve the position of a window report
+    pub(crate) fn reserve(&mut self) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        ticket
+    }
+
+    /// Record the results of a report and get the deltas that can be delivered,
+    /// in report order
+    pub(crate) fn complete(
+        &mut self,
+        ticket: u64,
+        results: Option<(Vec<Binding>, (i64, i64))>,
+    ) -> Vec<ResultDelta> {
+        self.pending.insert(ticket, results);
+
+        let mut deltas = Vec::new();
+        while let Some(results) = self.pending.remove(&self.next_to_apply) {
+            self.next_to_apply += 1;
+            if let Some((bindings, window)) = results {
+                deltas.push(self.diff(bindings, window));
+            }
+        }
+        deltas
+    }
+
+    /// Diff a report against the previous one and make it the new previous one
+    fn diff(&mut self, bindings: Vec<Binding>, window: (i64, i64)) -> ResultDelta {
+        let mut current: HashMap<Binding, usize> = HashMap::new();
+        for binding in bindings {
+            *current.entry(binding).or_default() += 1;
+        }
+
+        let mut added = Vec::new();
+        for (binding, &count) in &current {
+            let before = self.previous.get(binding).copied().unwrap_or(0);
+            for _ in before..count {
+                added.push(binding.clone());
+            }
+        }
+        let mut removed = Vec::new();
+        for (binding, &count) in &self.previous {
+            let after = current.get(binding).copied().unwrap_or(0);
+            for _ in after..count {
+                removed.push(binding.clone());
+            }
+        }
+
+        self.previous = current;
+        ResultDelta {
+            added,
+            removed,
+            window,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::Literal;
+
+    fn binding(value: i64) -> Binding {
+        Binding {
+            values: vec![("x".to_string(), Literal::from(value).into())],
+        }
+    }
+
+    #[test]
+    fn test_deltas_between_reports() {
+        let mut tracker = DeltaTracker::default();
+        let first = tracker.reserve();
+        let second = tracker.reserve();
+
+        let deltas = tracker.complete(first, Some((vec![binding(1), binding(2)], (0, 10))));
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].added.len(), 2);
+        assert!(deltas[0].removed.is_empty());
+
+        let deltas = tracker.complete(second, Some((vec![binding(2), binding(3)], (5, 15))));
+        assert_eq!(deltas[0].added, vec![binding(3)]);
+        assert_eq!(deltas[0].removed, vec![binding(1)]);
+        assert_eq!(deltas[0].window, (5, 15));
+    }
+
+    #[test]
+    fn test_deltas_follow_report_order() {
+        let mut tracker = DeltaTracker::default();
+        let first = tracker.reserve();
+        let second = tracker.reserve();
+        let third = tracker.reserve();
+
+        // The second report finishes first, but waits for the first one
+        assert!(
+            tracker
+                .complete(second, Some((vec![binding(2)], (5, 15))))
+                .is_empty()
+        );
+        // A failed evaluation doesn't block the reports after it
+        assert!(tracker.complete(third, None).is_empty());
+        let deltas = tracker.complete(first, Some((vec![binding(1), binding(1)], (0, 10))));
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].added, vec![binding(1), binding(1)]);
+        assert_eq!(deltas[1].added, vec![binding(2)]);
+        assert_eq!(deltas[1].removed, vec![binding(1), binding(1)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        use crate::BindingWithTimestamp;
+        use oxigraph::model::{BlankNode, NamedNode};
+
+        let solution = Binding {
+            values: vec![
+                ("blank".to_string(), BlankNode::new_unchecked("b0").into()),
+                (
+                    "label".to_string(),
+                    Literal::new_language_tagged_literal_unchecked("hall", "en").into(),
+                ),
+                ("name".to_string(), Literal::new_simple_literal("s1").into()),
+                (
+                    "sensor".to_string(),
+                    NamedNode::new_unchecked("http://example.org/s1").into(),
+                ),
+                ("value".to_string(), Literal::from(21.5).into()),
+            ],
+        };
+        let result = BindingWithTimestamp {
+            bindings: String::new(),
+            solution: solution.clone(),
+            timestamp_from: 0,
+            timestamp_to: 1000,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        };
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            json["solution"]["sensor"],
+            serde_json::json!({"type": "uri", "value": "http://example.org/s1"})
+        );
+        assert_eq!(
+            json["solution"]["label"],
+            serde_json::json!({"type": "literal", "value": "hall", "xml:lang": "en"})
+        );
+        assert_eq!(
+            json["solution"]["value"]["datatype"],
+            "http://www.w3.org/2001/XMLSchema#double"
+        );
+        assert!(json["solution"]["name"].get("datatype").is_none());
+
+        let read: BindingWithTimestamp = serde_json::from_value(json).unwrap();
+        assert_eq!(read.solution, solution);
+        assert_eq!((read.timestamp_from, read.timestamp_to), (0, 1000));
+    }
+}
@@ -0,0 +1,170 @@
+use crate::supervisor::ErrorChannel;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Fixed-size pool of worker threads running R2R evaluations
+///
+/// Window callbacks only enqueue work here, so ingestion never waits on query
+/// execution and several window reports can be evaluated in parallel.
+/// A panicking job doesn't take its worker down, and workers shut down once every
+/// handle to the pool has been dropped.
+#[derive(Clone)]
+pub struct EvaluationPool {
+    /// `None` for an inline pool, running jobs on the calling thread
+    sender: Option<mpsc::Sender<Job>>,
+    threads: usize,
+}
+
+impl EvaluationPool {
+    /// Create a pool with the given number of worker threads (at least one)
+    pub fn new(threads: usize) -> Self {
+        Self::supervised(threads, "rsp", ErrorChannel::default())
+    }
+
+    /// Create a pool whose workers are named `<prefix>-r2r-<i>` and report the panics
+    /// of their jobs to `errors`
+    pub(crate) fn supervised(threads: usize, prefix: &str, errors: ErrorChannel) -> Self {
+        let threads = threads.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            let errors = errors.clone();
+            thread::Builder::new()
+                .name(format!("{}-r2r-{}", prefix, i))
+                .spawn(move || {
+                    loop {
+                        // Only hold the lock while waiting for the next job
+                        let job = match receiver.lock() {
+                            Ok(receiver) => receiver.recv(),
+                            Err(_) => break,
+                        };
+                        match job {
+                            Ok(job) => {
+                                let _ = errors.catch(job);
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                })
+                .expect("Failed to spawn R2R evaluation thread");
+        }
+
+        Self {
+            sender: Some(sender),
+            threads,
+        }
+    }
+
+    /// Create a pool without workers, running every job on the thread queueing it
+    pub fn inline() -> Self {
+        Self {
+            sender: None,
+            threads: 0,
+        }
+    }
+
+    /// Number of threads matching the available parallelism of the machine
+    pub fn default_threads() -> usize {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    }
+
+    /// Queue a job for execution on one of the workers, or run it right away
+    /// for an inline pool
+    pub fn execute<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        match &self.sender {
+            Some(sender) => {
+                let _ = sender.send(Box::new(job));
+            }
+            None => job(),
+        }
+    }
+
+    /// Get the number of worker threads, 0 for an inline pool
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+}
+
+impl Default for EvaluationPool {
+    fn default() -> Self {
+        Self::new(Self::default_threads())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pool_runs_all_jobs() {
+        let pool = EvaluationPool::new(3);
+        assert_eq!(pool.threads(), 3);
+
+        let (tx, rx) = mpsc::channel();
+        for i in 0..10 {
+            let tx = tx.clone();
+            pool.execute(move || {
+                tx.send(i).unwrap();
+            });
+        }
+        drop(tx);
+
+        let mut received: Vec<i32> = rx.iter().collect();
+        received.sort();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_pool_evaluates_in_parallel() {
+        let pool = EvaluationPool::new(2);
+        let (tx, rx) = mpsc::channel();
+
+        // The first job blocks until the second one has run on another worker
+        let (unblock_tx, unblock_rx) = mpsc::channel::<()>();
+        let first_tx = tx.clone();
+        pool.execute(move || {
+            unblock_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            first_tx.send("first").unwrap();
+        });
+        pool.execute(move || {
+            tx.send("second").unwrap();
+            unblock_tx.send(()).unwrap();
+        });
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("second"));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("first"));
+    }
+
+    #[test]
+    fn test_panicking_job_keeps_its_worker() {
+        let errors = ErrorChannel::default();
+        let reported = errors.subscribe();
+        let pool = EvaluationPool::supervised(1, "engine", errors);
+        pool.execute(|| panic!("evaluation failed"));
+
+        let (tx, rx) = mpsc::channel();
+        pool.execute(move || {
+            let name = thread::current().name().map(str::to_string);
+            tx.send(name).unwrap();
+        });
+        let name = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(name.as_deref(), Some("engine-r2r-0"));
+        let error = reported.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(error.message, "evaluation failed");
+    }
+
+    #[test]
+    fn test_zero_threads_uses_one_worker() {
+        assert_eq!(EvaluationPool::new(0).threads(), 1);
+    }
+}
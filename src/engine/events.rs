@@ -0,0 +1,114 @@
+use crate::supervisor::EngineError;
+use std::fmt;
+use std::sync::{Arc, Mutex, mpsc};
+use tracing::error;
+
+/// Non-result event of an engine, from `RSPEngine::subscribe_events`, e.g. for
+/// monitoring why results are missing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineEvent {
+    /// Part of the query the engine can't run as written, e.g. a SPARQL syntax error
+    /// or a query without windows, found by `initialize`
+    ParseWarning(String),
+    /// Event dropped for arriving later than the allowed lateness of its window
+    LateEventDropped {
+        window: String,
+        stream: String,
+        timestamp: i64,
+    },
+    /// Results a sink failed to deliver, e.g. a file it couldn't write or an
+    /// endpoint refusing an update, see `FileSink::with_events`
+    SinkFailed { sink: String, error: String },
+    /// Panic caught in a thread of the engine, see `RSPEngine::errors`
+    CallbackPanicked(EngineError),
+    /// Time of the latest report of a window advanced, by its events or a clock
+    WatermarkAdvanced { window: String, watermark: i64 },
+    /// Instance of a window dropped after reporting, with its bounds
+    WindowEvicted {
+        window: String,
+        open: i64,
+        close: i64,
+    },
+}
+
+/// Publisher of the events of an engine to their subscribers, shared by the threads
+/// of the engine and by the sinks reporting their failures, see
+/// `RSPEngine::event_publisher`
+///
+/// Events are only built while someone subscribes, and subscribers whose receiver
+/// was dropped are forgotten.
+#[derive(Clone, Default)]
+pub struct EventPublisher {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<EngineEvent>>>>,
+}
+
+impl EventPublisher {
+    /// Create a publisher without subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Receive the events published from now on
+    pub fn subscribe(&self) -> mpsc::Receiver<EngineEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Whether anyone receives the events
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Send the event built by `event` to the subscribers, if any
+    pub fn publish(&self, event: impl FnOnce() -> EngineEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        let event = event();
+        subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Log the failure of a sink, e.g. its file path or endpoint, and publish it
+    pub fn sink_failed(&self, sink: &str, error: &str) {
+        error!(sink, "{}", error);
+        self.publish(|| EngineEvent::SinkFailed {
+            sink: sink.to_string(),
+            error: error.to_string(),
+        });
+    }
+}
+
+impl fmt::Debug for EventPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventPublisher")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_only_built_for_subscribers() {
+        let events = EventPublisher::new();
+        assert!(!events.has_subscribers());
+        events.publish(|| unreachable!("no subscriber"));
+
+        let rx = events.subscribe();
+        drop(events.subscribe());
+        events.sink_failed("results.csv", "disk full");
+        let expected = EngineEvent::SinkFailed {
+            sink: "results.csv".to_string(),
+            error: "disk full".to_string(),
+        };
+        assert_eq!(rx.try_recv(), Ok(expected));
+        // The dropped receiver is forgotten
+        assert_eq!(events.subscribers.lock().unwrap().len(), 1);
+
+        drop(rx);
+        events.publish(|| EngineEvent::ParseWarning("ignored".to_string()));
+        assert!(!events.has_subscribers());
+    }
+}
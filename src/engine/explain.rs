@@ -0,0 +1,99 @@
+use crate::{Operator, ReportStrategy, Tick};
+use std::fmt;
+
+/// Compiled pipeline of an engine's query, from its streams to its output,
+/// see `RSPEngine::explain`
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    /// Name the results are registered under
+    pub output: String,
+    /// Stream operator producing the results
+    pub operator: Operator,
+    pub windows: Vec<WindowPlan>,
+    /// SPARQL query evaluated on every window report, rewritten from the RSP-QL query
+    pub sparql_query: String,
+    /// Static graphs merged into the default graph
+    pub default_graphs: Vec<String>,
+    /// Static graphs available to `GRAPH` patterns
+    pub named_graphs: Vec<String>,
+    /// Evaluation plan of the SPARQL query in oxigraph, over the static data only;
+    /// `None` if the query doesn't parse
+    pub evaluation_plan: Option<String>,
+}
+
+/// Window of a compiled pipeline, with the settings in effect after the engine's
+/// configuration is applied
+#[derive(Debug, Clone)]
+pub struct WindowPlan {
+    pub name: String,
+    /// Streams feeding the window
+    pub streams: Vec<String>,
+    pub width: i64,
+    pub slide: i64,
+    pub report: ReportStrategy,
+    pub tick: Tick,
+    /// Alignment origin of the window boundaries, `None` if aligned to the start time
+    pub origin: Option<i64>,
+    /// How long (in ms) closed instances wait for late events
+    pub delay: i64,
+    /// Whether instances without events report
+    pub emit_empty_windows: bool,
+    /// Variable whose value partitions the window
+    pub partition_by: Option<String>,
+    /// Whether only the latest quad of each subject and predicate is kept
+    pub latest_value: bool,
+    /// Whether the query's aggregates are maintained incrementally for this window
+    pub incremental: bool,
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:?} <{}>", self.operator, self.output)?;
+        for window in &self.windows {
+            write!(
+                f,
+                "  window <{}> [RANGE {} STEP {}] report {:?} tick {:?}",
+                window.name, window.width, window.slide, window.report, window.tick
+            )?;
+            if let Some(origin) = window.origin {
+                write!(f, " align {}", origin)?;
+            }
+            if window.delay > 0 {
+                write!(f, " delay {}", window.delay)?;
+            }
+            if let Some(variable) = &window.partition_by {
+                write!(f, " partition by ?{}", variable)?;
+            }
+            if window.latest_value {
+                write!(f, " latest")?;
+            }
+            if window.emit_empty_windows {
+                write!(f, " emitting empty instances")?;
+            }
+            if window.incremental {
+                write!(f, " incremental")?;
+            }
+            writeln!(f)?;
+            for stream in &window.streams {
+                writeln!(f, "    stream <{}>", stream)?;
+            }
+        }
+        for graph in &self.default_graphs {
+            writeln!(f, "  static graph <{}>", graph)?;
+        }
+        for graph in &self.named_graphs {
+            writeln!(f, "  static named graph <{}>", graph)?;
+        }
+        writeln!(f, "  query:")?;
+        for line in self.sparql_query.trim().lines() {
+            writeln!(f, "    {}", line.trim())?;
+        }
+        if let Some(plan) = &self.evaluation_plan {
+            writeln!(f, "  evaluation plan:")?;
+            for line in plan.lines() {
+                writeln!(f, "    {}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
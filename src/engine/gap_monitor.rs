@@ -0,0 +1,186 @@
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use std::sync::{Arc, Mutex, Weak, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a stream may go without events with advancing timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapThreshold {
+    /// Maximum jump in ms between consecutive advancing timestamps, detected when
+    /// the event ending the gap arrives
+    EventTime(i64),
+    /// Maximum wall-clock time without an advancing event, detected while the
+    /// stream is still silent
+    WallClock(Duration),
+}
+
+/// Gap detected in the events of a stream, e.g. a dead sensor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamGap {
+    /// Event time jumped from `last` to `next`, further than the threshold
+    EventTime {
+        stream: String,
+        last: i64,
+        next: i64,
+    },
+    /// No advancing event arrived for `silence` after the event at `last`, or
+    /// since the monitor started if `None`; raised once per silence
+    WallClock {
+        stream: String,
+        last: Option<i64>,
+        silence: Duration,
+    },
+}
+
+/// Events seen by a monitor
+struct GapState {
+    watermark: Option<i64>,
+    last_advance: Instant,
+    /// Whether the current wall-clock silence was already reported
+    reported: bool,
+}
+
+/// Watches the timestamps of the events added to one stream
+pub(crate) struct GapMonitor {
+    stream: String,
+    threshold: GapThreshold,
+    state: Mutex<GapState>,
+    tx: mpsc::Sender<StreamGap>,
+}
+
+impl GapMonitor {
+    /// Start monitoring a stream, checking wall-clock silences on a background
+    /// thread that stops with the monitor
+    pub(crate) fn start(
+        stream: &str,
+        threshold: GapThreshold,
+    ) -> (Arc<Self>, mpsc::Receiver<StreamGap>) {
+        let (tx, rx) = mpsc::channel();
+        (Self::with_sender(stream, threshold, tx), rx)
+    }
+
+    /// Start monitoring a stream like `start`, raising its gaps on a channel shared
+    /// with other monitors
+    pub(crate) fn with_sender(
+        stream: &str,
+        threshold: GapThreshold,
+        tx: mpsc::Sender<StreamGap>,
+    ) -> Arc<Self> {
+        let monitor = Arc::new(Self {
+            stream: stream.to_string(),
+            threshold,
+            state: Mutex::new(GapState {
+                watermark: None,
+                last_advance: Instant::now(),
+                reported: false,
+            }),
+            tx,
+        });
+        if let GapThreshold::WallClock(gap) = threshold {
+            let monitor = Arc::downgrade(&monitor);
+            let role = format!("gap-{}", stream);
+            spawn_named(THREAD_NAME_PREFIX, &role, move || Self::watch(monitor, gap));
+        }
+        monitor
+    }
+
+    /// Check whether the stream has been silent for longer than the wall-clock
+    /// threshold
+    pub(crate) fn is_silent(&self) -> bool {
+        let GapThreshold::WallClock(gap) = self.threshold else {
+            return false;
+        };
+        self.state.lock().unwrap().last_advance.elapsed() >= gap
+    }
+
+    /// Record an event added to the stream
+    pub(crate) fn observe(&self, timestamp: i64) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .watermark
+            .is_some_and(|watermark| timestamp <= watermark)
+        {
+            return;
+        }
+        if let (GapThreshold::EventTime(gap), Some(last)) = (self.threshold, state.watermark) {
+            if timestamp - last > gap {
+                let _ = self.tx.send(StreamGap::EventTime {
+                    stream: self.stream.clone(),
+                    last,
+                    next: timestamp,
+                });
+            }
+        }
+        state.watermark = Some(timestamp);
+        state.last_advance = Instant::now();
+        state.reported = false;
+    }
+
+    /// Raise a gap once the stream has been silent for `gap`, until the monitor
+    /// is dropped or nobody listens anymore
+    fn watch(monitor: Weak<Self>, gap: Duration) {
+        let interval = (gap / 4).max(Duration::from_millis(1));
+        loop {
+            thread::sleep(interval);
+            let Some(monitor) = monitor.upgrade() else {
+                return;
+            };
+            let mut state = monitor.state.lock().unwrap();
+            let silence = state.last_advance.elapsed();
+            if state.reported || silence < gap {
+                continue;
+            }
+            state.reported = true;
+            let detected = StreamGap::WallClock {
+                stream: monitor.stream.clone(),
+                last: state.watermark,
+                silence,
+            };
+            if monitor.tx.send(detected).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_time_gaps() {
+        let (monitor, rx) = GapMonitor::start("http://example.org/s", GapThreshold::EventTime(500));
+        for timestamp in [100, 400, 300, 1200, 1500] {
+            monitor.observe(timestamp);
+        }
+        // 300 doesn't advance the timestamps, so the gap runs from 400 to 1200
+        let gaps: Vec<_> = rx.try_iter().collect();
+        assert_eq!(
+            gaps,
+            vec![StreamGap::EventTime {
+                stream: "http://example.org/s".to_string(),
+                last: 400,
+                next: 1200,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wall_clock_gaps() {
+        let threshold = GapThreshold::WallClock(Duration::from_millis(20));
+        let (monitor, rx) = GapMonitor::start("http://example.org/s", threshold);
+        monitor.observe(100);
+        let gap = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        let StreamGap::WallClock { last, silence, .. } = gap else {
+            panic!("Expected a wall-clock gap, got {:?}", gap);
+        };
+        assert_eq!(last, Some(100));
+        assert!(silence >= Duration::from_millis(20));
+        // Raised once per silence
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        monitor.observe(200);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        drop(monitor);
+    }
+}
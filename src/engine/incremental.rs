@@ -0,0 +1,759 @@
+use crate::sketches::{self, APPROX_COUNT_DISTINCT, Sketch};
+use oxigraph::model::{Literal, NamedNode, Quad, Term, Variable};
+use oxigraph::sparql::QuerySolution;
+use oxsdatatypes::{Decimal, Double, Float, Integer};
+use spargebra::algebra::{AggregateExpression, AggregateFunction, Expression, GraphPattern};
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Aggregate functions that can be maintained incrementally
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateKind {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// Estimated number of distinct values, see `sketches::APPROX_COUNT_DISTINCT`
+    ApproxCountDistinct,
+    /// Estimated percentile, e.g. 95, see `sketches::APPROX_PERCENTILE_95`
+    ApproxPercentile(u8),
+}
+
+/// One aggregate of an eligible query, e.g. `(AVG(?temp) AS ?avgTemp)`
+#[derive(Debug, Clone)]
+struct AggregateSpec {
+    kind: AggregateKind,
+    /// Variable the aggregate reads, `None` for `COUNT(*)`
+    input: Option<Variable>,
+}
+
+/// One projected column of an eligible query
+#[derive(Debug, Clone)]
+enum Column {
+    Group(usize),
+    Aggregate(usize),
+}
+
+/// Execution plan for an aggregation query that can skip SPARQL re-evaluation
+///
+/// Eligible queries are `SELECT` queries over a single triple pattern inside one
+/// `GRAPH` (window) clause, projecting only `GROUP BY` variables and non-distinct
+/// COUNT/SUM/AVG/MIN/MAX or approximate (`rsp:approxCountDistinct`, percentiles)
+/// aggregates over pattern variables. Anything else (filters, joins, HAVING,
+/// ORDER BY, subqueries...) is left to oxigraph.
+#[derive(Debug, Clone)]
+pub struct AggregationPlan {
+    graph: NamedNode,
+    pattern: TriplePattern,
+    group_by: Vec<Variable>,
+    aggregates: Vec<AggregateSpec>,
+    columns: Vec<Column>,
+    variables: Arc<[Variable]>,
+}
+
+impl AggregationPlan {
+    /// Detect whether a SPARQL query can be maintained incrementally
+    pub fn detect(query: &str) -> Option<Self> {
+        let Query::Select {
+            dataset: None,
+            pattern,
+            ..
+        } = SparqlParser::new().parse_query(query).ok()?
+        else {
+            return None;
+        };
+
+        let GraphPattern::Project { inner, variables } = pattern else {
+            return None;
+        };
+
+        // Unwrap the `(AGG(?x) AS ?y)` projections down to the GROUP node
+        let mut renames: HashMap<Variable, Variable> = HashMap::new();
+        let mut current = *inner;
+        let (group_inner, group_by, aggregates) = loop {
+            match current {
+                GraphPattern::Extend {
+                    inner,
+                    variable,
+                    expression: Expression::Variable(source),
+                } => {
+                    renames.insert(variable, source);
+                    current = *inner;
+                }
+                GraphPattern::Group {
+                    inner,
+                    variables,
+                    aggregates,
+                } => break (*inner, variables, aggregates),
+                _ => return None,
+            }
+        };
+
+        let GraphPattern::Graph {
+            name: NamedNodePattern::NamedNode(graph),
+            inner,
+        } = group_inner
+        else {
+            return None;
+        };
+        let GraphPattern::Bgp { patterns } = *inner else {
+            return None;
+        };
+        let [pattern] = <[TriplePattern; 1]>::try_from(patterns).ok()?;
+        let pattern_variables = Self::pattern_variables(&pattern)?;
+
+        if group_by.iter().any(|v| !pattern_variables.contains(v)) {
+            return None;
+        }
+
+        let mut specs = Vec::new();
+        let mut aggregate_variables = Vec::new();
+        for (variable, expression) in aggregates {
+            let spec = match expression {
+                AggregateExpression::CountSolutions { distinct: false } => AggregateSpec {
+                    kind: AggregateKind::Count,
+                    input: None,
+                },
+                AggregateExpression::FunctionCall {
+                    name,
+                    expr: Expression::Variable(input),
+                    distinct: false,
+                } if pattern_variables.contains(&input) => {
+                    let kind = match name {
+                        AggregateFunction::Count => AggregateKind::Count,
+                        AggregateFunction::Sum => AggregateKind::Sum,
+                        AggregateFunction::Avg => AggregateKind::Avg,
+                        AggregateFunction::Min => AggregateKind::Min,
+                        AggregateFunction::Max => AggregateKind::Max,
+                        AggregateFunction::Custom(function)
+                            if function.as_str() == APPROX_COUNT_DISTINCT =>
+                        {
+                            AggregateKind::ApproxCountDistinct
+                        }
+                        AggregateFunction::Custom(function) => AggregateKind::ApproxPercentile(
+                            sketches::function_percentile(function.as_str())?,
+                        ),
+                        _ => return None,
+                    };
+                    AggregateSpec {
+                        kind,
+                        input: Some(input),
+                    }
+                }
+                _ => return None,
+            };
+            specs.push(spec);
+            aggregate_variables.push(variable);
+        }
+
+        let mut columns = Vec::new();
+        for variable in &variables {
+            let source = renames.get(variable).unwrap_or(variable);
+            if let Some(i) = group_by.iter().position(|v| v == source) {
+                columns.push(Column::Group(i));
+            } else if let Some(i) = aggregate_variables.iter().position(|v| v == source) {
+                columns.push(Column::Aggregate(i));
+            } else {
+                return None;
+            }
+        }
+
+        Some(Self {
+            graph,
+            pattern,
+            group_by,
+            aggregates: specs,
+            columns,
+            variables: variables.into(),
+        })
+    }
+
+    /// Collect the variables of a pattern, rejecting patterns that need more than matching
+    fn pattern_variables(pattern: &TriplePattern) -> Option<Vec<Variable>> {
+        let mut variables = Vec::new();
+        for term in [&pattern.subject, &pattern.object] {
+            match term {
+                TermPattern::Variable(v) => variables.push(v.clone()),
+                TermPattern::NamedNode(_) | TermPattern::Literal(_) => {}
+                _ => return None,
+            }
+        }
+        if let NamedNodePattern::Variable(v) = &pattern.predicate {
+            variables.push(v.clone());
+        }
+        Some(variables)
+    }
+
+    /// Get the window graph the plan reads from
+    pub fn graph(&self) -> &NamedNode {
+        &self.graph
+    }
+
+    /// Create an empty state for a new window instance
+    pub fn new_state(&self) -> AggregationState {
+        AggregationState {
+            groups: HashMap::new(),
+            fallback: false,
+        }
+    }
+
+    /// Match a quad against the pattern, returning the variable bindings
+    fn bind(&self, quad: &Quad) -> Option<HashMap<Variable, Term>> {
+        if quad.graph_name != self.graph.clone().into() {
+            return None;
+        }
+        let mut bindings = HashMap::new();
+        let positions: [(&TermPattern, Term); 2] = [
+            (&self.pattern.subject, quad.subject.clone().into()),
+            (&self.pattern.object, quad.object.clone()),
+        ];
+        let predicate = match &self.pattern.predicate {
+            NamedNodePattern::NamedNode(node) => TermPattern::NamedNode(node.clone()),
+            NamedNodePattern::Variable(v) => TermPattern::Variable(v.clone()),
+        };
+        for (pattern, term) in positions
+            .into_iter()
+            .chain([(&predicate, quad.predicate.clone().into())])
+        {
+            match pattern {
+                TermPattern::Variable(v) => match bindings.get(v) {
+                    Some(bound) if *bound != term => return None,
+                    Some(_) => {}
+                    None => {
+                        bindings.insert(v.clone(), term);
+                    }
+                },
+                TermPattern::NamedNode(node) => {
+                    if Term::from(node.clone()) != term {
+                        return None;
+                    }
+                }
+                TermPattern::Literal(literal) => {
+                    if Term::from(literal.clone()) != term {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(bindings)
+    }
+
+    /// Apply the insertion of a quad into a window to its aggregation state
+    pub fn insert(&self, state: &mut AggregationState, quad: &Quad) {
+        if state.fallback {
+            return;
+        }
+        let Some(bindings) = self.bind(quad) else {
+            return;
+        };
+
+        let key: Vec<Term> = self.group_by.iter().map(|v| bindings[v].clone()).collect();
+        let accumulators = state.groups.entry(key).or_insert_with(|| {
+            self.aggregates
+                .iter()
+                .map(|spec| Accumulator::new(spec.kind))
+                .collect()
+        });
+
+        for (spec, accumulator) in self.aggregates.iter().zip(accumulators.iter_mut()) {
+            let value = spec.input.as_ref().map(|v| &bindings[v]);
+            if !accumulator.accumulate(value) {
+                // A value the incremental path can't order (e.g. MIN over IRIs)
+                state.fallback = true;
+                return;
+            }
+        }
+    }
+
+    /// Compute the result rows for a window from its aggregation state
+    /// Returns `None` when the window must be evaluated with SPARQL instead
+    pub fn solutions(&self, state: &AggregationState) -> Option<Vec<QuerySolution>> {
+        if state.fallback {
+            return None;
+        }
+
+        let mut rows = Vec::new();
+        if state.groups.is_empty() && self.group_by.is_empty() {
+            // Without GROUP BY, an empty window still yields one row
+            let empty: Vec<Accumulator> = self
+                .aggregates
+                .iter()
+                .map(|spec| Accumulator::new(spec.kind))
+                .collect();
+            rows.push(self.row(&[], &empty));
+        }
+        for (key, accumulators) in &state.groups {
+            rows.push(self.row(key, accumulators));
+        }
+        Some(rows)
+    }
+
+    fn row(&self, key: &[Term], accumulators: &[Accumulator]) -> QuerySolution {
+        let values: Vec<Option<Term>> = self
+            .columns
+            .iter()
+            .map(|column| match column {
+                Column::Group(i) => Some(key[*i].clone()),
+                Column::Aggregate(i) => accumulators[*i].finish(),
+            })
+            .collect();
+        QuerySolution::from((self.variables.clone(), values))
+    }
+}
+
+/// Running per-group accumulators of one window instance
+#[derive(Debug, Clone)]
+pub struct AggregationState {
+    groups: HashMap<Vec<Term>, Vec<Accumulator>>,
+    fallback: bool,
+}
+
+impl AggregationState {
+    /// Whether this window must be evaluated with SPARQL
+    pub fn needs_fallback(&self) -> bool {
+        self.fallback
+    }
+
+    /// Require SPARQL evaluation for this window, e.g. after part of it was spilled to disk
+    pub(crate) fn mark_fallback(&mut self) {
+        self.fallback = true;
+    }
+
+    /// Number of groups currently tracked
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Add the aggregates of another state of the same plan, e.g. of a pane of the
+    /// window, as if its quads had been inserted in this state
+    pub(crate) fn merge(&mut self, other: &AggregationState) {
+        self.fallback |= other.fallback;
+        if self.fallback {
+            return;
+        }
+        for (key, accumulators) in &other.groups {
+            let Some(merged) = self.groups.get_mut(key) else {
+                self.groups.insert(key.clone(), accumulators.clone());
+                continue;
+            };
+            for (accumulator, other) in merged.iter_mut().zip(accumulators) {
+                if !accumulator.merge(other) {
+                    self.fallback = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// A numeric value following the SPARQL type promotion rules
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Numeric {
+    Integer(Integer),
+    Decimal(Decimal),
+    Float(Float),
+    Double(Double),
+}
+
+impl Numeric {
+    pub(crate) fn from_term(term: &Term) -> Option<Self> {
+        let Term::Literal(literal) = term else {
+            return None;
+        };
+        let datatype = literal.datatype().as_str().strip_prefix(XSD)?;
+        let value = literal.value();
+        match datatype {
+            "integer" | "long" | "int" | "short" | "byte" | "nonNegativeInteger"
+            | "nonPositiveInteger" | "negativeInteger" | "positiveInteger" | "unsignedLong"
+            | "unsignedInt" | "unsignedShort" | "unsignedByte" => {
+                value.parse().ok().map(Numeric::Integer)
+            }
+            "decimal" => value.parse().ok().map(Numeric::Decimal),
+            "float" => value.parse().ok().map(Numeric::Float),
+            "double" => value.parse().ok().map(Numeric::Double),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Numeric::Integer(_) => 0,
+            Numeric::Decimal(_) => 1,
+            Numeric::Float(_) => 2,
+            Numeric::Double(_) => 3,
+        }
+    }
+
+    /// Promote to the given rank
+    fn promote(self, rank: u8) -> Option<Self> {
+        Some(match (self, rank) {
+            (v, r) if v.rank() == r => v,
+            (Numeric::Integer(v), 1) => Numeric::Decimal(v.into()),
+            (Numeric::Integer(v), 2) => Numeric::Float(v.into()),
+            (Numeric::Integer(v), 3) => Numeric::Double(v.into()),
+            (Numeric::Decimal(v), 2) => Numeric::Float(v.into()),
+            (Numeric::Decimal(v), 3) => Numeric::Double(v.into()),
+            (Numeric::Float(v), 3) => Numeric::Double(v.into()),
+            _ => return None,
+        })
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        let rank = self.rank().max(other.rank());
+        Some(match (self.promote(rank)?, other.promote(rank)?) {
+            (Numeric::Integer(a), Numeric::Integer(b)) => Numeric::Integer(a.checked_add(b)?),
+            (Numeric::Decimal(a), Numeric::Decimal(b)) => Numeric::Decimal(a.checked_add(b)?),
+            (Numeric::Float(a), Numeric::Float(b)) => Numeric::Float(a + b),
+            (Numeric::Double(a), Numeric::Double(b)) => Numeric::Double(a + b),
+            _ => return None,
+        })
+    }
+
+    fn cmp(self, other: Self) -> Option<Ordering> {
+        let rank = self.rank().max(other.rank());
+        match (self.promote(rank)?, other.promote(rank)?) {
+            (Numeric::Integer(a), Numeric::Integer(b)) => a.partial_cmp(&b),
+            (Numeric::Decimal(a), Numeric::Decimal(b)) => a.partial_cmp(&b),
+            (Numeric::Float(a), Numeric::Float(b)) => a.partial_cmp(&b),
+            (Numeric::Double(a), Numeric::Double(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+
+    /// Divide by a count, as SPARQL AVG does
+    fn average(self, count: i64) -> Option<Self> {
+        let count = Integer::from(count);
+        Some(match self {
+            Numeric::Integer(sum) => Numeric::Decimal(Decimal::from(sum).checked_div(count)?),
+            Numeric::Decimal(sum) => Numeric::Decimal(sum.checked_div(count)?),
+            Numeric::Float(sum) => Numeric::Float(sum / Float::from(count)),
+            Numeric::Double(sum) => Numeric::Double(sum / Double::from(count)),
+        })
+    }
+
+    /// Convert to a double, as approximate aggregates do
+    pub(crate) fn to_f64(self) -> Option<f64> {
+        match self.promote(3)? {
+            Numeric::Double(v) => Some(v.into()),
+            _ => None,
+        }
+    }
+
+    fn into_term(self) -> Term {
+        match self {
+            Numeric::Integer(v) => Literal::from(v).into(),
+            Numeric::Decimal(v) => Literal::from(v).into(),
+            Numeric::Float(v) => Literal::from(v).into(),
+            Numeric::Double(v) => Literal::from(v).into(),
+        }
+    }
+}
+
+/// Running value of one aggregate for one group
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(i64),
+    /// `None` once a non-numeric value made the sum an error (unbound result)
+    Sum(Option<Numeric>),
+    Avg(Option<Numeric>, i64),
+    /// The original term is kept so the result keeps its lexical form and datatype
+    Min(Option<(Numeric, Term)>),
+    Max(Option<(Numeric, Term)>),
+    Approximate(Sketch),
+}
+
+impl Accumulator {
+    fn new(kind: AggregateKind) -> Self {
+        let zero = Some(Numeric::Integer(Integer::default()));
+        match kind {
+            AggregateKind::Count => Accumulator::Count(0),
+            AggregateKind::Sum => Accumulator::Sum(zero),
+            AggregateKind::Avg => Accumulator::Avg(zero, 0),
+            AggregateKind::Min => Accumulator::Min(None),
+            AggregateKind::Max => Accumulator::Max(None),
+            AggregateKind::ApproxCountDistinct => {
+                Accumulator::Approximate(Sketch::count_distinct())
+            }
+            AggregateKind::ApproxPercentile(percent) => {
+                Accumulator::Approximate(Sketch::percentile(percent))
+            }
+        }
+    }
+
+    /// Add a value, returning false if the value can't be handled incrementally
+    fn accumulate(&mut self, value: Option<&Term>) -> bool {
+        let numeric = value.and_then(Numeric::from_term);
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => *sum = sum.and_then(|s| s.add(numeric?)),
+            Accumulator::Avg(sum, count) => {
+                *sum = sum.and_then(|s| s.add(numeric?));
+                *count += 1;
+            }
+            Accumulator::Min(current) => {
+                return Self::replace_if(current, numeric, value, Ordering::Less);
+            }
+            Accumulator::Max(current) => {
+                return Self::replace_if(current, numeric, value, Ordering::Greater);
+            }
+            Accumulator::Approximate(sketch) => {
+                if let Some(value) = value {
+                    sketch.insert(value);
+                }
+            }
+        }
+        true
+    }
+
+    /// Add the values of another accumulator of the same aggregate, returning false
+    /// if they can't be combined
+    fn merge(&mut self, other: &Accumulator) -> bool {
+        match (self, other) {
+            (Accumulator::Count(count), Accumulator::Count(other)) => *count += other,
+            (Accumulator::Sum(sum), Accumulator::Sum(other)) => {
+                *sum = sum.and_then(|s| s.add((*other)?));
+            }
+            (Accumulator::Avg(sum, count), Accumulator::Avg(other_sum, other_count)) => {
+                *sum = sum.and_then(|s| s.add((*other_sum)?));
+                *count += other_count;
+            }
+            (Accumulator::Min(current), Accumulator::Min(other)) => {
+                if let Some((numeric, term)) = other {
+                    return Self::replace_if(current, Some(*numeric), Some(term), Ordering::Less);
+                }
+            }
+            (Accumulator::Max(current), Accumulator::Max(other)) => {
+                if let Some((numeric, term)) = other {
+                    return Self::replace_if(
+                        current,
+                        Some(*numeric),
+                        Some(term),
+                        Ordering::Greater,
+                    );
+                }
+            }
+            (Accumulator::Approximate(sketch), Accumulator::Approximate(other)) => {
+                return sketch.merge(other);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Keep the value if it compares as `wanted` against the current one
+    fn replace_if(
+        current: &mut Option<(Numeric, Term)>,
+        numeric: Option<Numeric>,
+        term: Option<&Term>,
+        wanted: Ordering,
+    ) -> bool {
+        let (Some(numeric), Some(term)) = (numeric, term) else {
+            return false;
+        };
+        let replace = match current {
+            Some((value, _)) => numeric.cmp(*value) == Some(wanted),
+            None => true,
+        };
+        if replace {
+            *current = Some((numeric, term.clone()));
+        }
+        true
+    }
+
+    fn finish(&self) -> Option<Term> {
+        match self {
+            Accumulator::Count(count) => Some(Literal::from(*count).into()),
+            Accumulator::Sum(sum) => sum.map(Numeric::into_term),
+            Accumulator::Avg(sum, 0) => sum.map(|_| Literal::from(0i64).into()),
+            Accumulator::Avg(sum, count) => {
+                sum.and_then(|s| s.average(*count)).map(Numeric::into_term)
+            }
+            Accumulator::Min(value) | Accumulator::Max(value) => {
+                value.as_ref().map(|(_, term)| term.clone())
+            }
+            Accumulator::Approximate(sketch) => sketch.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QuadContainer, R2ROperator};
+    use oxigraph::model::GraphName;
+    use oxigraph::sparql::QueryResults;
+    use std::collections::HashSet;
+
+    const WINDOW: &str = "http://example.org/w1";
+
+    fn observation(sensor: &str, value: Literal) -> Quad {
+        Quad::new(
+            NamedNode::new(format!("http://example.org/{}", sensor)).unwrap(),
+            NamedNode::new("http://example.org/temperature").unwrap(),
+            value,
+            GraphName::NamedNode(NamedNode::new(WINDOW).unwrap()),
+        )
+    }
+
+    fn integer(value: i64) -> Literal {
+        Literal::from(value)
+    }
+
+    /// Compare the incremental results with a full SPARQL evaluation
+    fn assert_matches_sparql(query: &str, quads: &[Quad]) {
+        let plan = AggregationPlan::detect(query).expect("query should be eligible");
+        let mut state = plan.new_state();
+        for quad in quads {
+            plan.insert(&mut state, quad);
+        }
+        let mut incremental: Vec<String> = plan
+            .solutions(&state)
+            .unwrap()
+            .iter()
+            .map(|s| format!("{:?}", s))
+            .collect();
+        incremental.sort();
+
+        let container = QuadContainer::new(quads.iter().cloned().collect::<HashSet<_>>(), 0);
+        let mut expected = Vec::new();
+        if let QueryResults::Solutions(solutions) = R2ROperator::new(query.to_string())
+            .execute(&container)
+            .unwrap()
+        {
+            for solution in solutions.flatten() {
+                expected.push(format!("{:?}", solution));
+            }
+        }
+        expected.sort();
+
+        assert_eq!(incremental, expected);
+    }
+
+    #[test]
+    fn test_detect_eligible_query() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT ?s (AVG(?t) AS ?avg) (COUNT(*) AS ?n)
+            WHERE { GRAPH ex:w1 { ?s ex:temperature ?t } }
+            GROUP BY ?s
+        "#;
+        let plan = AggregationPlan::detect(query).unwrap();
+        assert_eq!(plan.graph().as_str(), WINDOW);
+    }
+
+    #[test]
+    fn test_reject_ineligible_queries() {
+        for query in [
+            "SELECT * WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } }",
+            "SELECT (COUNT(*) AS ?n) WHERE { ?s ?p ?o }",
+            "SELECT (COUNT(*) AS ?n) WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o . ?o ?q ?r } }",
+            "SELECT (COUNT(DISTINCT ?o) AS ?n) WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } }",
+            "SELECT ?s (SUM(?o) AS ?n) WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } } GROUP BY ?s HAVING (SUM(?o) > 2)",
+            "SELECT (SAMPLE(?o) AS ?n) WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } }",
+        ] {
+            assert!(AggregationPlan::detect(query).is_none(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_grouped_aggregates_match_sparql() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT ?s (COUNT(?t) AS ?n) (SUM(?t) AS ?sum) (AVG(?t) AS ?avg) (MIN(?t) AS ?min) (MAX(?t) AS ?max)
+            WHERE { GRAPH ex:w1 { ?s ex:temperature ?t } }
+            GROUP BY ?s
+        "#;
+        let quads = vec![
+            observation("sensor1", integer(20)),
+            observation("sensor1", integer(25)),
+            observation("sensor1", integer(22)),
+            observation("sensor2", Literal::from(Decimal::from(18))),
+            observation("sensor2", integer(19)),
+            observation("sensor3", Literal::from(21.5f64)),
+        ];
+        assert_matches_sparql(query, &quads);
+    }
+
+    #[test]
+    fn test_approximate_aggregates_match_sparql() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            PREFIX rsp: <https://rsp.rs/ns#>
+            SELECT ?s (rsp:approxCountDistinct(?t) AS ?distinct) (rsp:approxMedian(?t) AS ?median)
+                (rsp:approxPercentile90(?t) AS ?p90)
+            WHERE { GRAPH ex:w1 { ?s ex:temperature ?t } }
+            GROUP BY ?s
+        "#;
+        let plan = AggregationPlan::detect(query).unwrap();
+        assert_eq!(plan.aggregates[2].kind, AggregateKind::ApproxPercentile(90));
+        assert_matches_sparql(
+            query,
+            &[
+                observation("sensor1", integer(20)),
+                observation("sensor1", integer(25)),
+                observation("sensor1", Literal::from(22.5f64)),
+                observation("sensor2", integer(18)),
+                observation("sensor2", Literal::new_simple_literal("n/a")),
+            ],
+        );
+
+        let unknown = "SELECT (<https://rsp.rs/ns#approxMode>(?o) AS ?n) \
+            WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } }";
+        assert!(AggregationPlan::detect(unknown).is_none());
+    }
+
+    #[test]
+    fn test_ungrouped_aggregates_match_sparql() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT (COUNT(*) AS ?n) (AVG(?t) AS ?avg) (MAX(?t) AS ?max)
+            WHERE { GRAPH ex:w1 { ?s ex:temperature ?t } }
+        "#;
+        assert_matches_sparql(query, &[]);
+        assert_matches_sparql(
+            query,
+            &[
+                observation("sensor1", integer(3)),
+                observation("sensor2", integer(4)),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_non_numeric_values() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT ?s (SUM(?t) AS ?sum)
+            WHERE { GRAPH ex:w1 { ?s ex:temperature ?t } }
+            GROUP BY ?s
+        "#;
+        // SUM over a string is an error, leaving the aggregate unbound
+        assert_matches_sparql(
+            query,
+            &[
+                observation("sensor1", integer(3)),
+                observation("sensor1", Literal::new_simple_literal("hot")),
+            ],
+        );
+
+        // MIN over strings isn't maintained incrementally
+        let plan = AggregationPlan::detect(
+            "SELECT (MIN(?t) AS ?min) WHERE { GRAPH <http://example.org/w1> { ?s ?p ?t } }",
+        )
+        .unwrap();
+        let mut state = plan.new_state();
+        plan.insert(
+            &mut state,
+            &observation("sensor1", Literal::new_simple_literal("hot")),
+        );
+        assert!(state.needs_fallback());
+        assert!(plan.solutions(&state).is_none());
+    }
+}
@@ -0,0 +1,184 @@
+//! Buffer between the producers of a window's streams and its ingestion thread
+//!
+//! Each producer thread pushes onto its own shard, so concurrent producers only
+//! contend when they share a shard; the ingestion thread takes every shard at once
+//! and merges their events back in arrival order.
+
+use crate::rsp_engine::StreamEvent;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Counter assigning shards to producer threads round-robin
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Shard of the current thread, modulo the number of shards of a buffer
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Events of the streams of a window, sharded by producer thread
+pub(crate) struct ShardedBuffer {
+    /// Events with their arrival sequence number
+    shards: Box<[Mutex<Vec<(u64, StreamEvent)>>]>,
+    /// Sequence number of the next event
+    sequence: AtomicU64,
+    /// Number of events pushed and not taken yet
+    pending: AtomicUsize,
+    /// Number of live producer handles
+    producers: AtomicUsize,
+    /// Lock the ingestion thread waits on while the buffer is empty
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+/// Producer handle of a sharded buffer; the ingestion thread stops once every
+/// handle is dropped and the buffer is drained
+pub(crate) struct ShardedSender(Arc<ShardedBuffer>);
+
+impl ShardedBuffer {
+    /// Create a buffer with one shard per available core, with its first producer
+    /// handle
+    pub(crate) fn new() -> (ShardedSender, Arc<ShardedBuffer>) {
+        let shards = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self::with_shards(shards)
+    }
+
+    fn with_shards(shards: usize) -> (ShardedSender, Arc<ShardedBuffer>) {
+        let buffer = Arc::new(Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::new(Vec::new())).collect(),
+            sequence: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+            producers: AtomicUsize::new(1),
+            idle: Mutex::new(()),
+            wake: Condvar::new(),
+        });
+        (ShardedSender(buffer.clone()), buffer)
+    }
+
+    /// Wait for events and take all of them, in arrival order; `None` once every
+    /// producer is gone and the buffer is empty
+    pub(crate) fn take(&self) -> Option<Vec<StreamEvent>> {
+        {
+            let mut idle = self.idle.lock().unwrap();
+            while self.pending.load(Ordering::Acquire) == 0 {
+                if self.producers.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+                idle = self.wake.wait(idle).unwrap();
+            }
+        }
+        let mut events = Vec::new();
+        for shard in self.shards.iter() {
+            events.append(&mut shard.lock().unwrap());
+        }
+        // Events are counted when pushed, under the lock of their shard
+        self.pending.fetch_sub(events.len(), Ordering::AcqRel);
+        events.sort_unstable_by_key(|(sequence, _)| *sequence);
+        Some(events.into_iter().map(|(_, event)| event).collect())
+    }
+
+    fn notify(&self) {
+        let _idle = self.idle.lock().unwrap();
+        self.wake.notify_one();
+    }
+}
+
+impl ShardedSender {
+    /// Push an event onto the shard of the current thread
+    pub(crate) fn send(&self, event: StreamEvent) {
+        let buffer = &self.0;
+        let shard = SHARD.with(|shard| *shard) % buffer.shards.len();
+        let first = {
+            let mut shard = buffer.shards[shard].lock().unwrap();
+            let sequence = buffer.sequence.fetch_add(1, Ordering::Relaxed);
+            shard.push((sequence, event));
+            buffer.pending.fetch_add(1, Ordering::AcqRel) == 0
+        };
+        // The ingestion thread only waits while the buffer is empty
+        if first {
+            buffer.notify();
+        }
+    }
+}
+
+impl Clone for ShardedSender {
+    fn clone(&self) -> Self {
+        self.0.producers.fetch_add(1, Ordering::AcqRel);
+        Self(self.0.clone())
+    }
+}
+
+impl Drop for ShardedSender {
+    fn drop(&mut self) {
+        if self.0.producers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QuadContainer;
+    use std::collections::HashSet;
+
+    fn event(producer: usize, timestamp: i64) -> StreamEvent {
+        let container = QuadContainer::new(HashSet::new(), timestamp);
+        let stream = format!("http://example.org/stream{}", producer);
+        (container, stream.into())
+    }
+
+    #[test]
+    fn test_take_merges_shards_in_arrival_order() {
+        let (sender, buffer) = ShardedBuffer::with_shards(4);
+        let producers: Vec<_> = (0..4)
+            .map(|producer| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for timestamp in 0..250 {
+                        sender.send(event(producer, timestamp));
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+        for producer in producers {
+            producer.join().unwrap();
+        }
+
+        // Every producer's events, each in the order it pushed them
+        let events = buffer.take().unwrap();
+        assert_eq!(events.len(), 1000);
+        for producer in 0..4 {
+            let stream = format!("http://example.org/stream{}", producer);
+            let timestamps: Vec<i64> = events
+                .iter()
+                .filter(|(_, name)| **name == *stream)
+                .map(|(container, _)| container.last_timestamp_changed)
+                .collect();
+            assert_eq!(timestamps, (0..250).collect::<Vec<_>>());
+        }
+        assert!(buffer.take().is_none());
+    }
+
+    #[test]
+    fn test_take_waits_for_producers() {
+        let (sender, buffer) = ShardedBuffer::with_shards(2);
+        let consumer = thread::spawn(move || {
+            let mut timestamps = Vec::new();
+            while let Some(events) = buffer.take() {
+                timestamps.extend(events.iter().map(|(c, _)| c.last_timestamp_changed));
+            }
+            timestamps
+        });
+        for timestamp in 0..100 {
+            sender.send(event(0, timestamp));
+        }
+        // The consumer stops once the last producer is gone and the buffer drained
+        drop(sender);
+        assert_eq!(consumer.join().unwrap(), (0..100).collect::<Vec<_>>());
+    }
+}
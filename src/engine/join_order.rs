@@ -0,0 +1,318 @@
+use crate::quad_container::QuadContainer;
+use crate::static_patterns::CachedQuery;
+use crate::union_dataset::StaticQuads;
+use oxigraph::model::{GraphName, NamedNode, Term, TermRef};
+use oxigraph::sparql::PreparedSparqlQuery;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Graphs a triple pattern is matched in
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PatternGraph {
+    /// The default graph, with the graphs of the `FROM` clauses
+    Default,
+    /// `GRAPH <g>`
+    Named(NamedNode),
+    /// `GRAPH ?g`, any named graph
+    Any,
+}
+
+/// Constants of a triple pattern and its graph, identifying its cardinality
+type PatternKey = (Option<Term>, Option<Term>, Option<Term>, PatternGraph);
+
+/// Data the cardinalities of the triple patterns are counted in
+pub(crate) struct Cardinalities<'a> {
+    pub(crate) static_data: &'a StaticQuads,
+    pub(crate) static_version: u64,
+    pub(crate) default_graphs: &'a [NamedNode],
+    pub(crate) window: &'a QuadContainer,
+}
+
+impl Cardinalities<'_> {
+    fn static_count(&self, (subject, predicate, object, graph): &PatternKey) -> usize {
+        let count = |graph: Option<Option<&Term>>| {
+            self.static_data.count_matching(
+                subject.as_ref(),
+                predicate.as_ref(),
+                object.as_ref(),
+                graph,
+            )
+        };
+        match graph {
+            PatternGraph::Default => {
+                let merged: usize = self
+                    .default_graphs
+                    .iter()
+                    .map(|graph| count(Some(Some(&graph.clone().into()))))
+                    .sum();
+                count(Some(None)) + merged
+            }
+            PatternGraph::Named(graph) => count(Some(Some(&graph.clone().into()))),
+            PatternGraph::Any => count(None),
+        }
+    }
+
+    fn window_count(&self, (subject, predicate, object, graph): &PatternKey) -> usize {
+        let matches = |constant: &Option<Term>, term: TermRef<'_>| {
+            constant
+                .as_ref()
+                .is_none_or(|constant| constant.as_ref() == term)
+        };
+        self.window
+            .iter()
+            .filter(|quad| {
+                let in_graph = match (graph, &quad.graph_name) {
+                    (PatternGraph::Default, GraphName::DefaultGraph) => true,
+                    (PatternGraph::Default, GraphName::NamedNode(name)) => {
+                        self.default_graphs.contains(name)
+                    }
+                    (PatternGraph::Named(graph), GraphName::NamedNode(name)) => graph == name,
+                    (PatternGraph::Any, name) => !name.is_default_graph(),
+                    _ => false,
+                };
+                in_graph
+                    && matches(subject, quad.subject.as_ref().into())
+                    && matches(predicate, quad.predicate.as_ref().into())
+                    && matches(object, quad.object.as_ref())
+            })
+            .count()
+    }
+}
+
+/// Order of the triple patterns of a query, most selective first, following the
+/// cardinalities of the patterns in the static data and the reported window
+#[derive(Default)]
+pub(crate) struct JoinOrder(Mutex<OrderState>);
+
+/// Clones keep the cardinalities and the ordered query
+impl Clone for JoinOrder {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
+#[derive(Default, Clone)]
+struct OrderState {
+    /// Query the patterns are ordered in, as text and parsed
+    query: Option<(String, Query)>,
+    /// Cardinalities of the patterns in the static data, for one version of it
+    static_cardinalities: HashMap<PatternKey, usize>,
+    static_version: u64,
+    /// Last ordered query, reused while the order doesn't change
+    ordered: Option<CachedQuery>,
+}
+
+impl JoinOrder {
+    /// Order the triple patterns of every basic graph pattern of `query`, preparing
+    /// the ordered query with `prepare` when the order changed
+    /// `None` if the query can't be parsed
+    pub(crate) fn order(
+        &self,
+        query: &str,
+        data: Cardinalities<'_>,
+        prepare: impl FnOnce(&str) -> Option<PreparedSparqlQuery>,
+    ) -> Option<CachedQuery> {
+        self.0.lock().unwrap().order(query, data, prepare)
+    }
+}
+
+impl OrderState {
+    fn order(
+        &mut self,
+        query: &str,
+        data: Cardinalities<'_>,
+        prepare: impl FnOnce(&str) -> Option<PreparedSparqlQuery>,
+    ) -> Option<CachedQuery> {
+        if self.query.as_ref().is_none_or(|(text, _)| text != query) {
+            let parsed = SparqlParser::new().parse_query(query).ok()?;
+            self.query = Some((query.to_string(), parsed));
+        }
+        if self.static_version != data.static_version {
+            self.static_cardinalities.clear();
+            self.static_version = data.static_version;
+        }
+        let mut ordered = self.query.as_ref()?.1.clone();
+        let (Query::Select { pattern, .. }
+        | Query::Construct { pattern, .. }
+        | Query::Describe { pattern, .. }
+        | Query::Ask { pattern, .. }) = &mut ordered;
+        let static_cardinalities = &mut self.static_cardinalities;
+        order_patterns(pattern, &PatternGraph::Default, &mut |triple, graph| {
+            let key = pattern_key(triple, graph);
+            let static_count = match static_cardinalities.get(&key) {
+                Some(count) => *count,
+                None => {
+                    let count = data.static_count(&key);
+                    static_cardinalities.insert(key.clone(), count);
+                    count
+                }
+            };
+            static_count + data.window_count(&key)
+        });
+
+        let text = ordered.to_string();
+        if let Some(ordered) = self.ordered.as_ref().filter(|ordered| ordered.0 == text) {
+            return Some(ordered.clone());
+        }
+        debug!(query = %text, "Reordered the triple patterns of the R2R query");
+        let ordered = Arc::new((text.clone(), prepare(&text)?));
+        self.ordered = Some(ordered.clone());
+        Some(ordered)
+    }
+}
+
+/// Order the triple patterns of the basic graph patterns within a pattern
+fn order_patterns(
+    pattern: &mut GraphPattern,
+    graph: &PatternGraph,
+    cardinality: &mut impl FnMut(&TriplePattern, &PatternGraph) -> usize,
+) {
+    match pattern {
+        GraphPattern::Bgp { patterns } => order_bgp(patterns, graph, cardinality),
+        GraphPattern::Graph { name, inner } => {
+            let graph = match name {
+                NamedNodePattern::NamedNode(name) => PatternGraph::Named(name.clone()),
+                NamedNodePattern::Variable(_) => PatternGraph::Any,
+            };
+            order_patterns(inner, &graph, cardinality);
+        }
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            order_patterns(left, graph, cardinality);
+            order_patterns(right, graph, cardinality);
+        }
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::Extend { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. }
+        | GraphPattern::Group { inner, .. } => order_patterns(inner, graph, cardinality),
+        _ => {}
+    }
+}
+
+/// Order the triple patterns of a basic graph pattern greedily: the pattern with
+/// the fewest matches first, then the one with the fewest matches among those
+/// sharing a variable with the patterns before, to avoid cartesian products
+/// Ties keep the order of the query
+fn order_bgp(
+    patterns: &mut Vec<TriplePattern>,
+    graph: &PatternGraph,
+    cardinality: &mut impl FnMut(&TriplePattern, &PatternGraph) -> usize,
+) {
+    let cardinalities: Vec<usize> = patterns.iter().map(|p| cardinality(p, graph)).collect();
+    let mut remaining: Vec<usize> = (0..patterns.len()).collect();
+    let mut bound: HashSet<TermPattern> = HashSet::new();
+    let mut order = Vec::with_capacity(patterns.len());
+    while !remaining.is_empty() {
+        let fewest = |joined: bool| {
+            (0..remaining.len())
+                .filter(|&position| {
+                    !joined
+                        || variables(&patterns[remaining[position]])
+                            .any(|variable| bound.contains(&variable))
+                })
+                .min_by_key(|&position| cardinalities[remaining[position]])
+        };
+        let position = fewest(true).or_else(|| fewest(false)).unwrap_or(0);
+        let next = remaining.remove(position);
+        bound.extend(variables(&patterns[next]));
+        order.push(next);
+    }
+    let mut unordered: Vec<Option<TriplePattern>> = patterns.drain(..).map(Some).collect();
+    patterns.extend(
+        order
+            .into_iter()
+            .filter_map(|index| unordered[index].take()),
+    );
+}
+
+/// Variables and blank nodes of a triple pattern, which join it to the others
+fn variables(pattern: &TriplePattern) -> impl Iterator<Item = TermPattern> {
+    let predicate = match &pattern.predicate {
+        NamedNodePattern::Variable(variable) => Some(TermPattern::Variable(variable.clone())),
+        NamedNodePattern::NamedNode(_) => None,
+    };
+    [
+        Some(pattern.subject.clone()),
+        predicate,
+        Some(pattern.object.clone()),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|term| matches!(term, TermPattern::Variable(_) | TermPattern::BlankNode(_)))
+}
+
+fn pattern_key(pattern: &TriplePattern, graph: &PatternGraph) -> PatternKey {
+    let constant = |term: &TermPattern| match term {
+        TermPattern::NamedNode(node) => Some(Term::NamedNode(node.clone())),
+        TermPattern::Literal(literal) => Some(Term::Literal(literal.clone())),
+        _ => None,
+    };
+    let predicate = match &pattern.predicate {
+        NamedNodePattern::NamedNode(node) => Some(Term::NamedNode(node.clone())),
+        NamedNodePattern::Variable(_) => None,
+    };
+    (
+        constant(&pattern.subject),
+        predicate,
+        constant(&pattern.object),
+        graph.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order the patterns of a query over `ex:` predicates with the given
+    /// cardinalities, returning the local names of the predicates in order
+    fn ordered_predicates(query: &str, counts: &[(&str, usize)]) -> Vec<String> {
+        let iri = |name: &str| format!("<http://example.org/{}>", name);
+        let mut query = SparqlParser::new().parse_query(query).unwrap();
+        let Query::Select { pattern, .. } = &mut query else {
+            unreachable!()
+        };
+        order_patterns(pattern, &PatternGraph::Default, &mut |triple, _| {
+            let predicate = triple.predicate.to_string();
+            counts
+                .iter()
+                .find(|(name, _)| predicate == iri(name))
+                .map_or(usize::MAX, |(_, count)| *count)
+        });
+        let text = query.to_string();
+        let mut names: Vec<String> = counts.iter().map(|(name, _)| name.to_string()).collect();
+        names.sort_by_key(|name| text.find(&iri(name)));
+        names
+    }
+
+    #[test]
+    fn test_patterns_are_ordered_by_cardinality() {
+        let query = "PREFIX ex: <http://example.org/>
+            SELECT * WHERE { ?s ex:a ?x . ?s ex:b ?y . ?y ex:c ?z }";
+        assert_eq!(
+            ordered_predicates(query, &[("a", 100), ("b", 50), ("c", 1)]),
+            vec!["c", "b", "a"]
+        );
+        // Joined patterns come before cheaper unconnected ones
+        let query = "PREFIX ex: <http://example.org/>
+            SELECT * WHERE { ?s ex:a ?x . ?t ex:c ?z . ?s ex:b ?y }";
+        assert_eq!(
+            ordered_predicates(query, &[("a", 10), ("b", 100), ("c", 50)]),
+            vec!["a", "b", "c"]
+        );
+        // Ties keep the order of the query
+        assert_eq!(
+            ordered_predicates(query, &[("a", 10), ("b", 10), ("c", 10)]),
+            vec!["a", "c", "b"]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Report reserved by a window, waiting for its evaluation and the earlier reports
+struct Slot<R, T> {
+    reservation: R,
+    report: Option<T>,
+}
+
+/// Merges the reports of the windows of a query in the order of their close time
+///
+/// Each report reserves its place with its close time when its window emits it and
+/// is released once evaluated, after every report closing earlier. Windows report
+/// in close order, so a report is only released when every window reported past
+/// it, or when one of them is `max_delay` ahead, so an idle window doesn't hold the
+/// others back; reports of windows lagging further behind are released late.
+/// Windows of closed streams stop holding the others back.
+pub(crate) struct ResultMerger<R, T> {
+    max_delay: i64,
+    next_ticket: u64,
+    /// Close time of the latest report of each window still reporting, `None`
+    /// before its first one
+    progress: HashMap<String, Option<i64>>,
+    /// Close time of the latest report of any window
+    latest: i64,
+    /// Reports by close time, then reservation order
+    pending: BTreeMap<(i64, u64), Slot<R, T>>,
+}
+
+impl<R, T> ResultMerger<R, T> {
+    pub(crate) fn new(windows: impl IntoIterator<Item = String>, max_delay: Duration) -> Self {
+        Self {
+            max_delay: max_delay.as_millis().try_into().unwrap_or(i64::MAX),
+            next_ticket: 0,
+            progress: windows.into_iter().map(|window| (window, None)).collect(),
+            latest: i64::MIN,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Reserve the place of a report of `window` closing at `close`
+    pub(crate) fn reserve(&mut self, window: &str, close: i64, reservation: R) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        if let Some(progress) = self.progress.get_mut(window) {
+            *progress = Some(progress.map_or(close, |progress| progress.max(close)));
+        }
+        self.latest = self.latest.max(close);
+        let slot = Slot {
+            reservation,
+            report: None,
+        };
+        self.pending.insert((close, ticket), slot);
+        ticket
+    }
+
+    /// Record the evaluation of a report and get the reports that can be delivered,
+    /// in close order
+    pub(crate) fn complete(&mut self, ticket: u64, close: i64, report: T) -> Vec<(R, T)> {
+        if let Some(slot) = self.pending.get_mut(&(close, ticket)) {
+            slot.report = Some(report);
+        }
+        self.release()
+    }
+
+    /// Stop waiting for a window, e.g. once its stream is closed, and get the
+    /// reports that can be delivered
+    pub(crate) fn finish(&mut self, window: &str) -> Vec<(R, T)> {
+        self.progress.remove(window);
+        self.release()
+    }
+
+    /// Close time up to which every report was reserved
+    fn watermark(&self) -> i64 {
+        let slowest = match self.progress.values().min() {
+            Some(progress) => progress.unwrap_or(i64::MIN),
+            None => return i64::MAX,
+        };
+        slowest.max(self.latest.saturating_sub(self.max_delay))
+    }
+
+    fn release(&mut self) -> Vec<(R, T)> {
+        let watermark = self.watermark();
+        let mut ready = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if entry.key().0 > watermark || entry.get().report.is_none() {
+                break;
+            }
+            let slot = entry.remove();
+            if let Some(report) = slot.report {
+                ready.push((slot.reservation, report));
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merger(max_delay: u64) -> ResultMerger<&'static str, ()> {
+        let windows = ["w1", "w2"].map(String::from);
+        ResultMerger::new(windows, Duration::from_millis(max_delay))
+    }
+
+    fn names(released: Vec<(&'static str, ())>) -> Vec<&'static str> {
+        released.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn test_reports_are_merged_by_close_time() {
+        let mut merger = merger(10_000);
+        let a = merger.reserve("w1", 2000, "w1@2000");
+        let b = merger.reserve("w2", 1000, "w2@1000");
+        // w2 may still report before 2000
+        assert!(merger.complete(a, 2000, ()).is_empty());
+        assert_eq!(names(merger.complete(b, 1000, ())), vec!["w2@1000"]);
+        let c = merger.reserve("w2", 3000, "w2@3000");
+        assert_eq!(names(merger.complete(c, 3000, ())), vec!["w1@2000"]);
+        // Nothing waits for the windows of closed streams
+        assert_eq!(names(merger.finish("w1")), vec!["w2@3000"]);
+    }
+
+    #[test]
+    fn test_idle_windows_hold_reports_up_to_max_delay() {
+        let mut merger = merger(1000);
+        let a = merger.reserve("w1", 1000, "w1@1000");
+        assert!(merger.complete(a, 1000, ()).is_empty());
+        let b = merger.reserve("w1", 1500, "w1@1500");
+        assert!(merger.complete(b, 1500, ()).is_empty());
+        let c = merger.reserve("w1", 2000, "w1@2000");
+        assert_eq!(names(merger.complete(c, 2000, ())), vec!["w1@1000"]);
+        assert_eq!(names(merger.finish("w2")), vec!["w1@1500", "w1@2000"]);
+    }
+}
@@ -0,0 +1,541 @@
+use crate::stream_stats::StreamStats;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the buckets of the evaluation latency histogram
+pub const EVALUATION_LATENCY_BUCKETS: [Duration; 8] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// Time a metrics scrape may take to send its request or read the response
+#[cfg(feature = "prometheus")]
+const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Operational metrics of an engine, from `RSPEngine::metrics`
+///
+/// Counters are totals since the engine was created; rates (events or results
+/// per second) are derived by the monitoring backend from consecutive scrapes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineMetrics {
+    /// Events (quad containers) taken from stream channels by their window
+    pub events_ingested: u64,
+    /// Events added to streams but not applied to their window yet
+    pub queue_depth: u64,
+    /// Window instances currently open, over all windows
+    pub active_windows: usize,
+    /// Window reports handed over for evaluation
+    pub window_reports: u64,
+    /// Reports evaluated with SPARQL, as opposed to incrementally
+    pub evaluations: u64,
+    /// Mean duration of SPARQL evaluations
+    pub mean_evaluation_latency: Duration,
+    /// Longest SPARQL evaluation
+    pub max_evaluation_latency: Duration,
+    /// Total duration of SPARQL evaluations
+    pub total_evaluation_latency: Duration,
+    /// SPARQL evaluations taking at most each bound of `EVALUATION_LATENCY_BUCKETS`,
+    /// cumulative as in Prometheus histograms
+    pub evaluation_latency_buckets: [u64; 8],
+    /// Results (solutions) delivered to consumers
+    pub results_emitted: u64,
+    /// Reports handed over for SPARQL evaluation but not evaluated yet
+    pub evaluation_backlog: u64,
+    /// Events dropped by load shedding, see `LoadSheddingPolicy`
+    pub events_shed: u64,
+    /// Reports left unevaluated by load shedding
+    pub reports_shed: u64,
+    /// Events dropped by the rate limits of their stream, see `RateLimit`
+    pub events_rate_limited: u64,
+    /// Event-time statistics of each stream, by stream IRI, see `StreamStats`
+    pub streams: BTreeMap<String, StreamStats>,
+}
+
+impl EngineMetrics {
+    /// Format the metrics in the Prometheus text exposition format
+    pub fn to_prometheus(&self) -> String {
+        let metrics: [(&str, &str, &str, String); 11] = [
+            (
+                "rsp_events_ingested_total",
+                "counter",
+                "Events taken from stream channels by their window",
+                self.events_ingested.to_string(),
+            ),
+            (
+                "rsp_queue_depth",
+                "gauge",
+                "Events waiting to be applied to their window",
+                self.queue_depth.to_string(),
+            ),
+            (
+                "rsp_active_windows",
+                "gauge",
+                "Open window instances",
+                self.active_windows.to_string(),
+            ),
+            (
+                "rsp_window_reports_total",
+                "counter",
+                "Window reports handed over for evaluation",
+                self.window_reports.to_string(),
+            ),
+            (
+                "rsp_evaluations_total",
+                "counter",
+                "Window reports evaluated with SPARQL",
+                self.evaluations.to_string(),
+            ),
+            (
+                "rsp_evaluation_latency_max_seconds",
+                "gauge",
+                "Longest SPARQL evaluation",
+                self.max_evaluation_latency.as_secs_f64().to_string(),
+            ),
+            (
+                "rsp_results_emitted_total",
+                "counter",
+                "Results delivered to consumers",
+                self.results_emitted.to_string(),
+            ),
+            (
+                "rsp_evaluation_backlog",
+                "gauge",
+                "Window reports waiting for SPARQL evaluation",
+                self.evaluation_backlog.to_string(),
+            ),
+            (
+                "rsp_events_shed_total",
+                "counter",
+                "Events dropped by load shedding",
+                self.events_shed.to_string(),
+            ),
+            (
+                "rsp_reports_shed_total",
+                "counter",
+                "Window reports left unevaluated by load shedding",
+                self.reports_shed.to_string(),
+            ),
+            (
+                "rsp_events_rate_limited_total",
+                "counter",
+                "Events dropped by the rate limits of their stream",
+                self.events_rate_limited.to_string(),
+            ),
+        ];
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            text.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+                name, help, name, kind, name, value
+            ));
+        }
+
+        let name = "rsp_evaluation_latency_seconds";
+        text.push_str(&format!(
+            "# HELP {} Duration of SPARQL evaluations\n# TYPE {} histogram\n",
+            name, name
+        ));
+        for (bound, count) in EVALUATION_LATENCY_BUCKETS
+            .iter()
+            .zip(self.evaluation_latency_buckets)
+        {
+            text.push_str(&format!(
+                "{}_bucket{{le=\"{}\"}} {}\n",
+                name,
+                bound.as_secs_f64(),
+                count
+            ));
+        }
+        text.push_str(&format!(
+            "{}_bucket{{le=\"+Inf\"}} {}\n{}_sum {}\n{}_count {}\n",
+            name,
+            self.evaluations,
+            name,
+            self.total_evaluation_latency.as_secs_f64(),
+            name,
+            self.evaluations
+        ));
+
+        // Statistics of the streams are labelled with their IRI
+        let streams: [(&str, &str, &str, fn(&StreamStats) -> String); 6] = [
+            (
+                "rsp_stream_events_total",
+                "counter",
+                "Events ingested from the stream",
+                |stats| stats.events.to_string(),
+            ),
+            (
+                "rsp_stream_out_of_order_events_total",
+                "counter",
+                "Events of the stream older than an event that arrived before them",
+                |stats| stats.out_of_order_events.to_string(),
+            ),
+            (
+                "rsp_stream_max_out_of_orderness_seconds",
+                "gauge",
+                "Largest distance between an out-of-order event and the watermark",
+                |stats| seconds(stats.max_out_of_orderness),
+            ),
+            (
+                "rsp_stream_late_events_total",
+                "counter",
+                "Events of the stream older than the last window report",
+                |stats| stats.late_events.to_string(),
+            ),
+            (
+                "rsp_stream_dropped_events_total",
+                "counter",
+                "Events of the stream dropped for exceeding the maximum lateness",
+                |stats| stats.dropped_events.to_string(),
+            ),
+            (
+                "rsp_stream_watermark_lag_seconds",
+                "gauge",
+                "Wall-clock time minus the watermark of the stream",
+                |stats| seconds(stats.watermark_lag),
+            ),
+        ];
+        if !self.streams.is_empty() {
+            for (name, kind, help, value) in streams {
+                text.push_str(&format!(
+                    "# HELP {} {}\n# TYPE {} {}\n",
+                    name, help, name, kind
+                ));
+                for (stream, stats) in &self.streams {
+                    text.push_str(&format!(
+                        "{}{{stream=\"{}\"}} {}\n",
+                        name,
+                        label_value(stream),
+                        value(stats)
+                    ));
+                }
+            }
+        }
+        text
+    }
+
+    /// Add the metrics of another engine, e.g. to report on a group of queries
+    pub(crate) fn combine(&mut self, other: &EngineMetrics) {
+        let evaluations = self.evaluations + other.evaluations;
+        if evaluations > 0 {
+            let total = self.mean_evaluation_latency.as_micros() * self.evaluations as u128
+                + other.mean_evaluation_latency.as_micros() * other.evaluations as u128;
+            let mean = total / evaluations as u128;
+            self.mean_evaluation_latency = Duration::from_micros(mean as u64);
+        }
+        self.events_ingested += other.events_ingested;
+        self.queue_depth += other.queue_depth;
+        self.active_windows += other.active_windows;
+        self.window_reports += other.window_reports;
+        self.evaluations = evaluations;
+        self.max_evaluation_latency = self
+            .max_evaluation_latency
+            .max(other.max_evaluation_latency);
+        self.total_evaluation_latency += other.total_evaluation_latency;
+        for (count, added) in self
+            .evaluation_latency_buckets
+            .iter_mut()
+            .zip(other.evaluation_latency_buckets)
+        {
+            *count += added;
+        }
+        self.results_emitted += other.results_emitted;
+        self.evaluation_backlog += other.evaluation_backlog;
+        self.events_shed += other.events_shed;
+        self.reports_shed += other.reports_shed;
+        self.events_rate_limited += other.events_rate_limited;
+        for (stream, stats) in &other.streams {
+            self.streams
+                .entry(stream.clone())
+                .or_default()
+                .combine(stats);
+        }
+    }
+}
+
+/// Format a duration in ms as seconds
+fn seconds(millis: i64) -> String {
+    (millis as f64 / 1000.0).to_string()
+}
+
+/// Escape a Prometheus label value
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Counters shared by the streams, ingestion threads and evaluations of an engine
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRecorder {
+    events_queued: AtomicU64,
+    events_ingested: AtomicU64,
+    window_reports: AtomicU64,
+    evaluations: AtomicU64,
+    evaluation_micros: AtomicU64,
+    max_evaluation_micros: AtomicU64,
+    /// Evaluations per bucket of `EVALUATION_LATENCY_BUCKETS`, not cumulative
+    evaluation_buckets: [AtomicU64; 8],
+    results_emitted: AtomicU64,
+    evaluations_queued: AtomicU64,
+    events_shed: AtomicU64,
+    reports_shed: AtomicU64,
+    events_rate_limited: AtomicU64,
+}
+
+impl MetricsRecorder {
+    pub(crate) fn record_queued(&self) {
+        self.events_queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_ingested(&self, events: usize) {
+        self.events_ingested
+            .fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_report(&self) {
+        self.window_reports.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_evaluation_queued(&self) {
+        self.evaluations_queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_evaluation(&self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        self.evaluation_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_evaluation_micros
+            .fetch_max(micros, Ordering::Relaxed);
+        if let Some(bucket) = EVALUATION_LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+        {
+            self.evaluation_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_results(&self, results: usize) {
+        self.results_emitted
+            .fetch_add(results as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_shed_event(&self) {
+        self.events_shed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_shed_report(&self) {
+        self.reports_shed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rate_limited_event(&self) {
+        self.events_rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the number of events added to streams but not applied to their window yet
+    pub(crate) fn queue_depth(&self) -> u64 {
+        let ingested = self.events_ingested.load(Ordering::Relaxed);
+        let queued = self.events_queued.load(Ordering::Relaxed);
+        queued.saturating_sub(ingested)
+    }
+
+    /// Get the number of reports handed over for evaluation but not evaluated yet
+    pub(crate) fn evaluation_backlog(&self) -> u64 {
+        let evaluations = self.evaluations.load(Ordering::Relaxed);
+        let queued = self.evaluations_queued.load(Ordering::Relaxed);
+        queued.saturating_sub(evaluations)
+    }
+
+    /// Read the counters, with the number of open window instances counted by the caller
+    pub(crate) fn snapshot(&self, active_windows: usize) -> EngineMetrics {
+        let events_ingested = self.events_ingested.load(Ordering::Relaxed);
+        let evaluations = self.evaluations.load(Ordering::Relaxed);
+        let evaluation_micros = self.evaluation_micros.load(Ordering::Relaxed);
+        let mut evaluation_latency_buckets = [0; 8];
+        let mut cumulative = 0;
+        for (count, bucket) in evaluation_latency_buckets
+            .iter_mut()
+            .zip(&self.evaluation_buckets)
+        {
+            cumulative += bucket.load(Ordering::Relaxed);
+            *count = cumulative;
+        }
+        EngineMetrics {
+            events_ingested,
+            queue_depth: self.queue_depth(),
+            active_windows,
+            window_reports: self.window_reports.load(Ordering::Relaxed),
+            evaluations,
+            mean_evaluation_latency: Duration::from_micros(
+                evaluation_micros.checked_div(evaluations).unwrap_or(0),
+            ),
+            max_evaluation_latency: Duration::from_micros(
+                self.max_evaluation_micros.load(Ordering::Relaxed),
+            ),
+            total_evaluation_latency: Duration::from_micros(evaluation_micros),
+            evaluation_latency_buckets,
+            results_emitted: self.results_emitted.load(Ordering::Relaxed),
+            evaluation_backlog: self.evaluation_backlog(),
+            events_shed: self.events_shed.load(Ordering::Relaxed),
+            reports_shed: self.reports_shed.load(Ordering::Relaxed),
+            events_rate_limited: self.events_rate_limited.load(Ordering::Relaxed),
+            streams: BTreeMap::new(),
+        }
+    }
+}
+
+/// Answer every HTTP request on `listener` with the metrics in the Prometheus format
+/// The connections are answered one at a time, each given `SCRAPE_TIMEOUT` so a
+/// stalled client can't block the following scrapes
+#[cfg(feature = "prometheus")]
+pub(crate) fn serve_prometheus<F>(
+    listener: std::net::TcpListener,
+    thread_name_prefix: &str,
+    metrics: F,
+) -> std::thread::JoinHandle<()>
+where
+    F: Fn() -> EngineMetrics + Send + 'static,
+{
+    use std::io::{BufRead, BufReader, Write};
+
+    crate::supervisor::spawn_named(thread_name_prefix, "metrics", move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let timeouts = stream
+                .set_read_timeout(Some(SCRAPE_TIMEOUT))
+                .and_then(|_| stream.set_write_timeout(Some(SCRAPE_TIMEOUT)));
+            if let Err(e) = timeouts {
+                tracing::warn!("Failed to set the timeouts of a metrics scrape: {}", e);
+                continue;
+            }
+            let mut reader = BufReader::new(stream);
+            // Skip the request, every path serves the metrics
+            let mut line = String::new();
+            while reader.read_line(&mut line).map(|n| n > 0).unwrap_or(false) && line != "\r\n" {
+                line.clear();
+            }
+            let body = metrics().to_prometheus();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = reader.get_mut().write_all(response.as_bytes()) {
+                tracing::warn!("Failed to answer metrics scrape: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_exposition() {
+        let recorder = MetricsRecorder::default();
+        for _ in 0..3 {
+            recorder.record_queued();
+        }
+        recorder.record_ingested(2);
+        recorder.record_report();
+        recorder.record_evaluation(Duration::from_millis(2));
+        recorder.record_evaluation(Duration::from_millis(4));
+        recorder.record_results(5);
+
+        let metrics = recorder.snapshot(4);
+        assert_eq!(metrics.queue_depth, 1);
+        assert_eq!(metrics.active_windows, 4);
+        assert_eq!(metrics.mean_evaluation_latency, Duration::from_millis(3));
+        assert_eq!(metrics.max_evaluation_latency, Duration::from_millis(4));
+
+        let text = metrics.to_prometheus();
+        assert!(
+            text.contains(
+                "# TYPE rsp_events_ingested_total counter\nrsp_events_ingested_total 2\n"
+            )
+        );
+        assert!(text.contains("rsp_results_emitted_total 5\n"));
+        assert!(text.contains("rsp_evaluation_latency_max_seconds 0.004\n"));
+        assert!(text.contains(
+            "# TYPE rsp_evaluation_latency_seconds histogram\n\
+             rsp_evaluation_latency_seconds_bucket{le=\"0.001\"} 0\n\
+             rsp_evaluation_latency_seconds_bucket{le=\"0.005\"} 2\n"
+        ));
+        assert!(text.contains(
+            "rsp_evaluation_latency_seconds_bucket{le=\"+Inf\"} 2\n\
+             rsp_evaluation_latency_seconds_sum 0.006\n\
+             rsp_evaluation_latency_seconds_count 2\n"
+        ));
+    }
+
+    #[test]
+    fn test_combined_metrics() {
+        let recorder = MetricsRecorder::default();
+        recorder.record_ingested(1);
+        recorder.record_evaluation(Duration::from_millis(6));
+        let mut metrics = recorder.snapshot(1);
+
+        let other = MetricsRecorder::default();
+        other.record_ingested(2);
+        for _ in 0..2 {
+            other.record_evaluation(Duration::from_millis(3));
+        }
+        metrics.combine(&other.snapshot(2));
+        assert_eq!(metrics.events_ingested, 3);
+        assert_eq!(metrics.active_windows, 3);
+        assert_eq!(metrics.evaluations, 3);
+        assert_eq!(metrics.mean_evaluation_latency, Duration::from_millis(4));
+        assert_eq!(metrics.max_evaluation_latency, Duration::from_millis(6));
+        assert_eq!(metrics.total_evaluation_latency, Duration::from_millis(12));
+        assert_eq!(metrics.evaluation_latency_buckets, [0, 2, 3, 3, 3, 3, 3, 3]);
+
+        // Engines without evaluations don't change the mean
+        metrics.combine(&EngineMetrics::default());
+        assert_eq!(metrics.mean_evaluation_latency, Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_stream_statistics_exposition() {
+        let mut metrics = MetricsRecorder::default().snapshot(0);
+        assert!(!metrics.to_prometheus().contains("rsp_stream_"));
+
+        let mut stats = StreamStats::default();
+        stats.record(3000, 0, 5000);
+        stats.record(1500, 2000, 5500);
+        metrics
+            .streams
+            .insert("https://rsp.rs/stream\"1\"".to_string(), stats.clone());
+        let mut other = EngineMetrics::default();
+        other
+            .streams
+            .insert("https://rsp.rs/stream2".to_string(), stats);
+        metrics.combine(&other);
+
+        let text = metrics.to_prometheus();
+        assert!(text.contains(
+            "# TYPE rsp_stream_late_events_total counter\n\
+             rsp_stream_late_events_total{stream=\"https://rsp.rs/stream\\\"1\\\"\"} 1\n\
+             rsp_stream_late_events_total{stream=\"https://rsp.rs/stream2\"} 1\n"
+        ));
+        assert!(text.contains(
+            "rsp_stream_max_out_of_orderness_seconds{stream=\"https://rsp.rs/stream2\"} 1.5\n"
+        ));
+        assert!(
+            text.contains(
+                "rsp_stream_watermark_lag_seconds{stream=\"https://rsp.rs/stream2\"} 2.5\n"
+            )
+        );
+    }
+}
@@ -1,2 +1,37 @@
+pub mod alerts;
+pub mod builder;
+#[cfg(feature = "debug-console")]
+pub(crate) mod console;
+pub mod dead_letter;
+pub mod dedup;
+pub mod delta;
+pub mod evaluation_pool;
+pub mod events;
+pub mod explain;
+pub mod gap_monitor;
+pub mod incremental;
+pub(crate) mod ingestion_buffer;
+pub(crate) mod join_order;
+pub(crate) mod merge;
+pub mod metrics;
+pub mod pause;
+pub mod processing;
+pub mod query_group;
 pub mod r2r;
+pub mod recording;
+pub mod result_filter;
+pub mod retention;
+pub mod router;
 pub mod rsp_engine;
+pub mod sequence;
+pub mod service;
+pub mod sharing;
+pub mod shedding;
+pub mod sketches;
+pub(crate) mod static_graphs;
+pub(crate) mod static_patterns;
+pub mod stream_stats;
+pub mod supervisor;
+pub(crate) mod telemetry;
+pub mod temporal_join;
+pub(crate) mod union_dataset;
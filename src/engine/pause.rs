@@ -0,0 +1,167 @@
+use crate::rsp_engine::{StreamEvent, WindowSender};
+use std::mem;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What streams do with the events added while their engine is paused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PausePolicy {
+    /// Keep the events and apply them, in arrival order, on resume
+    #[default]
+    Buffer,
+    /// Refuse the events with an error, e.g. for producers able to retry later
+    Reject,
+}
+
+/// Pause switch shared by an engine and its streams
+pub(crate) struct PauseControl {
+    policy: PausePolicy,
+    /// Read by the producers without locking while the engine runs; only set back
+    /// to `false` under the `buffered` lock, once nothing is buffered
+    paused: AtomicBool,
+    /// Events held back while paused, with the channel of their window
+    buffered: Mutex<Vec<(WindowSender, StreamEvent)>>,
+    /// Held while pausing or resuming, so one resume sends its events at a time
+    switching: Mutex<()>,
+}
+
+impl PauseControl {
+    pub(crate) fn new(policy: PausePolicy) -> Self {
+        Self {
+            policy,
+            paused: AtomicBool::new(false),
+            buffered: Mutex::new(Vec::new()),
+            switching: Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn pause(&self) {
+        let _switching = self.switching.lock().unwrap();
+        self.paused.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Let an event through, or hold it back while paused; `None` once buffered
+    pub(crate) fn admit(
+        &self,
+        sender: &WindowSender,
+        item: StreamEvent,
+    ) -> Result<Option<StreamEvent>, String> {
+        if !self.is_paused() {
+            return Ok(Some(item));
+        }
+        let mut buffered = self.buffered.lock().unwrap();
+        // Resumed while waiting for the lock, with every buffered event sent
+        if !self.is_paused() {
+            return Ok(Some(item));
+        }
+        match self.policy {
+            PausePolicy::Buffer => {
+                buffered.push((sender.clone(), item));
+                Ok(None)
+            }
+            PausePolicy::Reject => Err(format!(
+                "Engine is paused, rejecting event at {} on stream {}",
+                item.0.last_timestamp_changed, item.1
+            )),
+        }
+    }
+
+    /// Send the buffered events to their windows and let new events through
+    /// Events keep being buffered until the buffer is found empty, so buffered
+    /// events stay ahead of the new ones; they are sent without holding the lock
+    pub(crate) fn resume(&self) -> Result<(), String> {
+        let _switching = self.switching.lock().unwrap();
+        let mut result = Ok(());
+        loop {
+            let batch = {
+                let mut buffered = self.buffered.lock().unwrap();
+                if buffered.is_empty() {
+                    self.paused.store(false, Ordering::Release);
+                    return result;
+                }
+                mem::take(&mut *buffered)
+            };
+            for (sender, item) in batch {
+                if let Err(e) = sender.send(item) {
+                    result = Err(format!("Failed to send buffered data to window: {}", e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QuadContainer;
+    use std::collections::HashSet;
+    use std::sync::{Arc, mpsc};
+    use std::thread;
+
+    fn event(timestamp: i64) -> StreamEvent {
+        let container = QuadContainer::new(HashSet::new(), timestamp);
+        (container, "http://example.org/stream".into())
+    }
+
+    #[test]
+    fn test_buffered_events_are_sent_on_resume() {
+        let (tx, rx) = mpsc::channel();
+        let sender = WindowSender::Unbounded(tx);
+        let control = PauseControl::new(PausePolicy::Buffer);
+        assert!(control.admit(&sender, event(100)).unwrap().is_some());
+
+        control.pause();
+        assert!(control.is_paused());
+        assert!(control.admit(&sender, event(200)).unwrap().is_none());
+        assert!(control.admit(&sender, event(300)).unwrap().is_none());
+        assert!(rx.try_recv().is_err());
+
+        control.resume().unwrap();
+        assert!(!control.is_paused());
+        let sent: Vec<i64> = rx
+            .try_iter()
+            .map(|(c, _)| c.last_timestamp_changed)
+            .collect();
+        assert_eq!(sent, vec![200, 300]);
+    }
+
+    #[test]
+    fn test_events_added_while_resuming_stay_behind() {
+        let (tx, rx) = mpsc::sync_channel(1);
+        let sender = WindowSender::Bounded(tx);
+        let control = Arc::new(PauseControl::new(PausePolicy::Buffer));
+        control.pause();
+        for timestamp in [200, 300] {
+            assert!(control.admit(&sender, event(timestamp)).unwrap().is_none());
+        }
+
+        // Blocked on the full channel, without holding up the producers
+        let resuming = control.clone();
+        let resume = thread::spawn(move || resuming.resume());
+        assert!(control.admit(&sender, event(400)).unwrap().is_none());
+        let sent: Vec<i64> = rx
+            .iter()
+            .take(3)
+            .map(|(c, _)| c.last_timestamp_changed)
+            .collect();
+        assert_eq!(sent, vec![200, 300, 400]);
+        resume.join().unwrap().unwrap();
+        assert!(control.admit(&sender, event(500)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_rejected_events() {
+        let (tx, rx) = mpsc::channel();
+        let sender = WindowSender::Unbounded(tx);
+        let control = PauseControl::new(PausePolicy::Reject);
+        control.pause();
+        assert!(control.admit(&sender, event(100)).is_err());
+        control.resume().unwrap();
+        assert!(control.admit(&sender, event(200)).unwrap().is_some());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::BindingWithTimestamp;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, mpsc};
+
+type Subscriber = Box<dyn FnMut(&BindingWithTimestamp) + Send>;
+type ResultCallback = Arc<dyn Fn(&BindingWithTimestamp) + Send + Sync>;
+
+/// Destination of the results of one `start_processing` call, shared by the
+/// window callbacks and the handle controlling it
+pub(crate) struct ResultOutput {
+    /// `None` once stopped
+    sender: Mutex<Option<mpsc::Sender<BindingWithTimestamp>>>,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl ResultOutput {
+    pub(crate) fn new(sender: mpsc::Sender<BindingWithTimestamp>) -> Self {
+        Self {
+            sender: Mutex::new(Some(sender)),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.sender.lock().unwrap().is_some()
+    }
+
+    /// Hand a result to the subscribers, then to the channel
+    /// Results sent after stopping, e.g. by evaluations already running, are dropped
+    pub(crate) fn send(&self, result: BindingWithTimestamp) {
+        let sender = self.sender.lock().unwrap();
+        let Some(sender) = sender.as_ref() else {
+            return;
+        };
+        for subscriber in self.subscribers.lock().unwrap().iter_mut() {
+            subscriber(&result);
+        }
+        // Consumers may only use the subscribers and drop the receiver
+        let _ = sender.send(result);
+    }
+
+    fn stop(&self) {
+        self.sender.lock().unwrap().take();
+        self.subscribers.lock().unwrap().clear();
+    }
+}
+
+/// Handle on the results of `RSPEngine::start_processing`
+///
+/// Dereferences to the receiver of the results, so it's consumed like a channel
+/// (`recv`, `recv_timeout`, `try_iter`, iteration...), and controls the delivery
+/// for the lifetime of the engine: callbacks can be added at any time, and
+/// stopping ends the evaluation of later window reports. Dropping the handle
+/// doesn't stop the processing; results then only reach the subscribers.
+///
+/// ```rust,no_run
+/// use rsp_rs::RSPEngine;
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// let results = engine.start_processing();
+/// engine.initialize().unwrap();
+/// results.add_subscriber(|result| println!("{}", result.bindings));
+/// // ...
+/// results.stop();
+/// assert!(!results.is_running());
+/// ```
+pub struct ProcessingHandle {
+    receiver: mpsc::Receiver<BindingWithTimestamp>,
+    output: Arc<ResultOutput>,
+}
+
+impl ProcessingHandle {
+    pub(crate) fn new() -> (Self, Arc<ResultOutput>) {
+        let (tx, rx) = mpsc::channel();
+        let output = Arc::new(ResultOutput::new(tx));
+        let handle = Self {
+            receiver: rx,
+            output: output.clone(),
+        };
+        (handle, output)
+    }
+
+    /// Stop evaluating window reports for this handle
+    /// Results already queued can still be received, after which the channel
+    /// disconnects; the subscribers are dropped
+    pub fn stop(&self) {
+        self.output.stop();
+    }
+
+    /// Check whether results are still delivered, i.e. `stop` wasn't called
+    pub fn is_running(&self) -> bool {
+        self.output.is_running()
+    }
+
+    /// Call `callback` with every later result, before it's sent to the channel
+    /// Callbacks run on the threads evaluating the reports, one result at a time,
+    /// so they must not block, stop the handle or add subscribers themselves
+    /// Ignored once stopped
+    pub fn add_subscriber<F>(&self, callback: F)
+    where
+        F: FnMut(&BindingWithTimestamp) + Send + 'static,
+    {
+        if self.is_running() {
+            self.output
+                .subscribers
+                .lock()
+                .unwrap()
+                .push(Box::new(callback));
+        }
+    }
+
+    /// Get the receiver of the results, e.g. to hand it to a sink
+    /// The processing keeps running, without a way to stop it
+    pub fn into_receiver(self) -> mpsc::Receiver<BindingWithTimestamp> {
+        self.receiver
+    }
+}
+
+impl Deref for ProcessingHandle {
+    type Target = mpsc::Receiver<BindingWithTimestamp>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.receiver
+    }
+}
+
+impl IntoIterator for ProcessingHandle {
+    type Item = BindingWithTimestamp;
+    type IntoIter = mpsc::IntoIter<BindingWithTimestamp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.receiver.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ProcessingHandle {
+    type Item = BindingWithTimestamp;
+    type IntoIter = mpsc::Iter<'a, BindingWithTimestamp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.receiver.iter()
+    }
+}
+
+/// Token identifying a callback registered with `RSPEngine::on_result`, to
+/// unsubscribe it with `RSPEngine::unsubscribe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionToken(u64);
+
+/// Callbacks receiving the results of the engine's query
+#[derive(Default)]
+pub(crate) struct ResultCallbacks {
+    next_id: AtomicU64,
+    callbacks: RwLock<Vec<(SubscriptionToken, ResultCallback)>>,
+}
+
+impl ResultCallbacks {
+    pub(crate) fn add(&self, callback: ResultCallback) -> SubscriptionToken {
+        let token = SubscriptionToken(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.callbacks.write().unwrap().push((token, callback));
+        token
+    }
+
+    /// Remove a callback; `false` if it was already removed
+    pub(crate) fn remove(&self, token: SubscriptionToken) -> bool {
+        let mut callbacks = self.callbacks.write().unwrap();
+        let before = callbacks.len();
+        callbacks.retain(|(registered, _)| *registered != token);
+        callbacks.len() < before
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.callbacks.read().unwrap().is_empty()
+    }
+
+    /// Call every callback with a result
+    /// Callbacks are called without holding the registry, so they may unsubscribe
+    pub(crate) fn send(&self, result: &BindingWithTimestamp) {
+        let callbacks: Vec<ResultCallback> = self
+            .callbacks
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        for callback in callbacks {
+            callback(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Binding;
+
+    fn result(timestamp_from: i64) -> BindingWithTimestamp {
+        BindingWithTimestamp {
+            bindings: String::new(),
+            solution: Binding { values: Vec::new() },
+            timestamp_from,
+            timestamp_to: timestamp_from + 1000,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        }
+    }
+
+    #[test]
+    fn test_subscribers_and_stop() {
+        let (handle, output) = ProcessingHandle::new();
+        output.send(result(0));
+
+        let (tx, rx) = mpsc::channel();
+        handle.add_subscriber(move |result| tx.send(result.timestamp_from).unwrap());
+        output.send(result(1000));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1000]);
+
+        handle.stop();
+        assert!(!handle.is_running() && !output.is_running());
+        output.send(result(2000));
+        handle.add_subscriber(|_| panic!("Subscribed after stopping"));
+        output.send(result(3000));
+        // The subscriber was dropped with the stop
+        assert!(rx.recv().is_err());
+
+        let received: Vec<_> = handle.into_iter().map(|r| r.timestamp_from).collect();
+        assert_eq!(received, vec![0, 1000]);
+    }
+
+    #[test]
+    fn test_result_callbacks() {
+        let callbacks = ResultCallbacks::default();
+        assert!(callbacks.is_empty());
+        let (tx, rx) = mpsc::channel();
+        let first_tx = tx.clone();
+        let first = callbacks.add(Arc::new(move |r: &BindingWithTimestamp| {
+            first_tx.send(r.timestamp_from).unwrap()
+        }));
+        let second = callbacks.add(Arc::new(move |r: &BindingWithTimestamp| {
+            tx.send(-r.timestamp_from).unwrap()
+        }));
+        assert_ne!(first, second);
+        callbacks.send(&result(1000));
+        assert!(callbacks.remove(first));
+        assert!(!callbacks.remove(first));
+        callbacks.send(&result(2000));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1000, -1000, -2000]);
+        assert!(callbacks.remove(second));
+        assert!(callbacks.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+use crate::evaluation_pool::EvaluationPool;
+use crate::metrics::EngineMetrics;
+use crate::processing::ProcessingHandle;
+use crate::{BindingWithTimestamp, RSPEngine};
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+/// Limits on the resources shared by the queries of a `QueryGroup`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupQuota {
+    /// Maximum number of windows over all queries; queries beyond it are rejected
+    pub max_windows: Option<usize>,
+    /// Memory of the window buffers of the group, split evenly among its windows,
+    /// each spilling to disk beyond its share
+    pub max_memory_bytes: Option<usize>,
+    /// Threads evaluating the reports of all queries; a pool per processing
+    /// otherwise
+    pub evaluation_threads: Option<usize>,
+}
+
+/// Result of a query of a group, with the name of its query (its `REGISTER` IRI)
+pub type GroupResult = (String, BindingWithTimestamp);
+
+/// Queries of one tenant hosted on a shared engine instance, isolated from those of
+/// other groups and limited by a quota
+///
+/// Each query runs in its own engine, so queries only share their group's
+/// evaluation threads and memory. Results of all queries come out of the group's
+/// channels, tagged with their query, and metrics are summed over the group.
+///
+/// ```rust,no_run
+/// use rsp_rs::{GroupQuota, QueryGroup, RSPEngine};
+///
+/// let quota = GroupQuota {
+///     max_windows: Some(4),
+///     evaluation_threads: Some(2),
+///     ..GroupQuota::default()
+/// };
+/// let mut group = QueryGroup::new("tenant-a", quota);
+/// let results = group.start_processing();
+/// group.add_query(RSPEngine::new("...".to_string())).unwrap();
+/// for (query, result) in results {
+///     println!("{}: {}", query, result.bindings);
+/// }
+/// ```
+pub struct QueryGroup {
+    name: String,
+    quota: GroupQuota,
+    /// Pool shared by the queries, if the quota limits the threads
+    pool: Option<EvaluationPool>,
+    /// Engines of the queries by name
+    queries: HashMap<String, RSPEngine>,
+    /// Channels of `start_processing`, fed by every query added
+    outputs: Vec<mpsc::Sender<GroupResult>>,
+    /// Processing of each query for the channels, stopped when it's removed
+    processing: HashMap<String, Vec<ProcessingHandle>>,
+}
+
+impl QueryGroup {
+    /// Create an empty group
+    pub fn new(name: &str, quota: GroupQuota) -> Self {
+        Self {
+            name: name.to_string(),
+            pool: quota.evaluation_threads.map(EvaluationPool::new),
+            quota,
+            queries: HashMap::new(),
+            outputs: Vec::new(),
+            processing: HashMap::new(),
+        }
+    }
+
+    /// Get the name of the group
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the quota of the group
+    pub fn quota(&self) -> &GroupQuota {
+        &self.quota
+    }
+
+    /// Add a query with its engine, not initialized yet, returning its name
+    /// The engine is initialized within the quota of the group, and fails to join
+    /// it if the group would have too many windows or already has a query of the
+    /// same name
+    pub fn add_query(&mut self, mut engine: RSPEngine) -> Result<String, String> {
+        let name = engine.parsed_query().r2s.name.clone();
+        if self.queries.contains_key(&name) {
+            return Err(format!("Query {} already in group {}", name, self.name));
+        }
+        let windows = self.window_count() + engine.parsed_query().s2r.len();
+        if let Some(max_windows) = self.quota.max_windows {
+            if windows > max_windows {
+                return Err(format!(
+                    "Query {} exceeds the quota of group {}: {} windows, at most {}",
+                    name, self.name, windows, max_windows
+                ));
+            }
+        }
+        if let Some(pool) = &self.pool {
+            engine.share_evaluation_pool(pool.clone());
+        }
+        if let Some(max_bytes) = self.quota.max_memory_bytes {
+            engine.set_max_window_memory_bytes(Some(max_bytes / windows.max(1)));
+        }
+        engine.initialize()?;
+
+        let handles = self
+            .outputs
+            .iter()
+            .map(|output| Self::forward(&name, &engine, output.clone()))
+            .collect();
+        self.processing.insert(name.clone(), handles);
+        self.queries.insert(name.clone(), engine);
+        self.share_memory();
+        Ok(name)
+    }
+
+    /// Remove a query, stopping its results on the channels of the group
+    pub fn remove_query(&mut self, name: &str) -> Option<RSPEngine> {
+        let engine = self.queries.remove(name)?;
+        for handle in self.processing.remove(name).unwrap_or_default() {
+            handle.stop();
+        }
+        self.share_memory();
+        Some(engine)
+    }
+
+    /// Get the engine of a query, e.g. to feed its streams
+    pub fn query(&self, name: &str) -> Option<&RSPEngine> {
+        self.queries.get(name)
+    }
+
+    /// Get the engine of a query to change it, e.g. its static data
+    pub fn query_mut(&mut self, name: &str) -> Option<&mut RSPEngine> {
+        self.queries.get_mut(name)
+    }
+
+    /// Get the names of the queries of the group
+    pub fn queries(&self) -> Vec<String> {
+        self.queries.keys().cloned().collect()
+    }
+
+    /// Get the number of windows over all queries
+    pub fn window_count(&self) -> usize {
+        self.queries
+            .values()
+            .map(|engine| engine.parsed_query().s2r.len())
+            .sum()
+    }
+
+    /// Receive the results of every query of the group, including those added later
+    pub fn start_processing(&mut self) -> mpsc::Receiver<GroupResult> {
+        let (tx, rx) = mpsc::channel();
+        for (name, engine) in &self.queries {
+            let handle = Self::forward(name, engine, tx.clone());
+            self.processing
+                .entry(name.clone())
+                .or_default()
+                .push(handle);
+        }
+        self.outputs.push(tx);
+        rx
+    }
+
+    /// Get the metrics of the group, summed over its queries
+    pub fn metrics(&self) -> EngineMetrics {
+        let mut metrics = EngineMetrics::default();
+        for engine in self.queries.values() {
+            metrics.combine(&engine.metrics());
+        }
+        metrics
+    }
+
+    /// Send the results of a query to a channel of the group
+    fn forward(
+        name: &str,
+        engine: &RSPEngine,
+        output: mpsc::Sender<GroupResult>,
+    ) -> ProcessingHandle {
+        let name = name.to_string();
+        let handle = engine.start_processing();
+        handle.add_subscriber(move |result| {
+            let _ = output.send((name.clone(), result.clone()));
+        });
+        handle
+    }
+
+    /// Split the memory quota among the windows of the queries
+    fn share_memory(&mut self) {
+        let Some(max_bytes) = self.quota.max_memory_bytes else {
+            return;
+        };
+        let limit = max_bytes / self.window_count().max(1);
+        for engine in self.queries.values_mut() {
+            engine.set_max_window_memory_bytes(Some(limit));
+            for window in &engine.parsed_query().s2r {
+                if let Some(window) = engine.get_window(&window.window_name) {
+                    window.lock().unwrap().set_memory_limit(Some(limit));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RSPEngineBuilder;
+    use oxigraph::model::{GraphName, Literal, NamedNode, Quad};
+
+    fn query(output: &str, windows: usize) -> RSPEngine {
+        let declarations: String = (0..windows)
+            .map(|i| {
+                format!(
+                    "FROM NAMED WINDOW ex:{}w{} ON STREAM ex:stream [RANGE 1000 STEP 1000]\n",
+                    output, i
+                )
+            })
+            .collect();
+        let query = format!(
+            "PREFIX ex: <http://example.org/>
+            REGISTER RStream <http://example.org/{}> AS
+            SELECT ?s ?o
+            {}
+            WHERE {{ WINDOW ex:{}w0 {{ ?s ex:p ?o }} }}",
+            output, declarations, output
+        );
+        RSPEngineBuilder::new(query).synchronous(true).build()
+    }
+
+    #[test]
+    fn test_quota_limits_windows_and_memory() {
+        let quota = GroupQuota {
+            max_windows: Some(3),
+            max_memory_bytes: Some(3000),
+            evaluation_threads: None,
+        };
+        let mut group = QueryGroup::new("tenant", quota);
+        let first = group.add_query(query("first", 1)).unwrap();
+        assert_eq!(first, "http://example.org/first");
+        let memory = |group: &QueryGroup| group.query(&first).unwrap().max_window_memory_bytes();
+        assert_eq!(memory(&group), Some(3000));
+
+        assert!(group.add_query(query("first", 1)).is_err());
+        assert!(group.add_query(query("second", 3)).is_err());
+        let second = group.add_query(query("second", 2)).unwrap();
+        assert_eq!(group.window_count(), 3);
+        assert_eq!(memory(&group), Some(1000));
+
+        assert!(group.remove_query(&second).is_some());
+        assert_eq!(group.queries(), vec![first.clone()]);
+        assert_eq!(memory(&group), Some(3000));
+    }
+
+    #[test]
+    fn test_results_and_metrics_of_the_group() {
+        let mut group = QueryGroup::new("tenant", GroupQuota::default());
+        let results = group.start_processing();
+        let first = group.add_query(query("first", 1)).unwrap();
+        let second = group.add_query(query("second", 1)).unwrap();
+
+        for name in [&first, &second] {
+            let engine = group.query(name).unwrap();
+            let stream = engine.get_stream("http://example.org/stream").unwrap();
+            for timestamp in [100, 1100] {
+                let quad = Quad::new(
+                    NamedNode::new("http://example.org/s").unwrap(),
+                    NamedNode::new("http://example.org/p").unwrap(),
+                    Literal::from(timestamp),
+                    GraphName::DefaultGraph,
+                );
+                stream.add_quads(vec![quad], timestamp).unwrap();
+            }
+        }
+
+        let mut queries: Vec<String> = results.try_iter().map(|(query, _)| query).collect();
+        queries.sort();
+        assert_eq!(queries, vec![first.clone(), second.clone()]);
+        assert_eq!(group.metrics().events_ingested, 4);
+
+        // Results of removed queries stop
+        let removed = group.remove_query(&first).unwrap();
+        let stream = removed.get_stream("http://example.org/stream").unwrap();
+        stream.add_quads(Vec::new(), 2100).unwrap();
+        assert!(results.try_iter().next().is_none());
+        assert_eq!(group.metrics().events_ingested, 2);
+    }
+}
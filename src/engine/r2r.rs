@@ -1,74 +1,651 @@
+use crate::join_order::{Cardinalities, JoinOrder};
 use crate::quad_container::QuadContainer;
-use oxigraph::model::Quad;
-use oxigraph::sparql::QueryResults;
+use crate::service::ServiceClient;
+use crate::sharing::SharedPatterns;
+use crate::sketches;
+use crate::static_patterns::{
+    CachedQuery, StaticPatternCache, replace_static_patterns, replace_window_patterns,
+};
+use crate::union_dataset::{StaticQuads, UnionDataset};
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{
+    BlankNode, Dataset, GraphName, GraphNameRef, Literal, NamedNode, NamedOrBlankNode, Quad,
+    QuadRef, Term, Variable,
+};
+use oxigraph::sparql::{PreparedSparqlQuery, QueryResults, SparqlEvaluator};
 use oxigraph::store::Store;
-use std::collections::HashSet;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::GroundTerm;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, trace, warn};
+
+/// Function returning the open time (in ms) of the reported window instance,
+/// e.g. `BIND(rsp:windowStart() AS ?start)` with `PREFIX rsp: <https://rsp.rs/ns#>`
+pub const WINDOW_START: &str = "https://rsp.rs/ns#windowStart";
+/// Function returning the close time (in ms) of the reported window instance
+pub const WINDOW_END: &str = "https://rsp.rs/ns#windowEnd";
+/// Property of the reified window quads giving the timestamp (in ms) of their event
+pub const TIMESTAMP: &str = "https://rsp.rs/ns#timestamp";
+/// Property of the reified window quads giving the stream of their event
+pub const STREAM: &str = "https://rsp.rs/ns#stream";
+/// Property marking a subject of a side-input stream as deleted from the static data,
+/// e.g. `ex:sensor1 rsp:deleted true`
+pub const DELETED: &str = "https://rsp.rs/ns#deleted";
 
 /// R2R (Relation-to-Relation) Operator
 /// Executes SPARQL queries over streaming data combined with static data
 #[derive(Clone)]
 pub struct R2ROperator {
     pub(crate) query: String,
-    pub(crate) static_data: HashSet<Quad>,
+    /// Static data, shared by clones until one of them changes it; empty when the
+    /// static data is kept in an on-disk store
+    pub(crate) static_data: Arc<HashSet<Quad>>,
+    /// Indexed copy of the static data, shared like `static_data`, or the store
+    static_dataset: StaticQuads,
+    /// Incremented on every change of the static data
+    static_version: u64,
+    /// Static graphs merged into the default graph, from the `FROM` clauses
+    default_graphs: Arc<[NamedNode]>,
+    /// Query parsed once at construction, or the syntax error to report on execution
+    prepared: Result<PreparedSparqlQuery, String>,
+    /// Values substituted for query variables on every execution
+    parameters: HashMap<Variable, Term>,
+    /// Evaluator the query was prepared with, to prepare it again with window bounds
+    evaluator: SparqlEvaluator,
+    /// Whether the query may call the window bound functions
+    window_functions: bool,
+    /// Whether window quads are reified with their timestamp
+    annotate_timestamps: bool,
+    /// Whether window quads are reified with their stream
+    annotate_streams: bool,
+    /// Solutions of the static patterns of the query, if cached
+    static_cache: Option<StaticPatternCache>,
+    /// Order of the triple patterns, if reordered before each execution
+    join_order: Option<JoinOrder>,
+    /// Solutions of the window patterns shared with other queries, if any
+    shared_patterns: Option<Arc<SharedPatterns>>,
 }
 
 impl R2ROperator {
     /// Create a new R2ROperator with a SPARQL query
     pub fn new(query: String) -> Self {
+        Self::with_evaluator(query, SparqlEvaluator::new())
+    }
+
+    /// Create a new R2ROperator whose `SERVICE` clauses are evaluated by the given client
+    pub fn with_service_client(query: String, client: ServiceClient) -> Self {
+        Self::with_evaluator(
+            query,
+            SparqlEvaluator::new().with_default_service_handler(client),
+        )
+    }
+
+    fn with_evaluator(query: String, evaluator: SparqlEvaluator) -> Self {
+        let evaluator = sketches::with_approximate_aggregates(evaluator);
+        let prepared = evaluator.parse_query(&query).map_err(|e| e.to_string());
+        let window_functions = calls_window_functions(&query);
         Self {
             query,
-            static_data: HashSet::new(),
+            static_data: Arc::new(HashSet::new()),
+            static_dataset: StaticQuads::Memory(Arc::new(Dataset::new())),
+            static_version: 0,
+            default_graphs: Vec::new().into(),
+            prepared,
+            parameters: HashMap::new(),
+            evaluator,
+            window_functions,
+            annotate_timestamps: false,
+            annotate_streams: false,
+            static_cache: None,
+            join_order: None,
+            shared_patterns: None,
+        }
+    }
+
+    /// Keep the static data in an on-disk oxigraph store at `path`, created if
+    /// missing, instead of indexing it in memory, e.g. for static datasets larger
+    /// than RAM or to inspect them after the engine stopped
+    /// The quads already in the store are static data too, and the current static
+    /// data is added to it; all clones share the store and see its changes
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_store(&mut self, path: impl AsRef<std::path::Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let store = Store::open(path)
+            .map_err(|e| format!("Failed to open store {}: {}", path.display(), e))?;
+        store
+            .extend(self.static_data.iter().cloned())
+            .map_err(|e| format!("Failed to add static data to {}: {}", path.display(), e))?;
+        self.static_data = Arc::new(HashSet::new());
+        self.static_dataset = StaticQuads::Store(store);
+        self.static_version += 1;
+        Ok(())
+    }
+
+    /// Get the on-disk store of the static data, if any
+    pub fn store(&self) -> Option<&Store> {
+        match &self.static_dataset {
+            StaticQuads::Store(store) => Some(store),
+            StaticQuads::Memory(_) => None,
         }
     }
 
     /// Add a static quad to the operator's static data store
     pub fn add_static_data(&mut self, quad: Quad) {
-        self.static_data.insert(quad);
+        if let StaticQuads::Store(store) = &self.static_dataset {
+            match store.insert(&quad) {
+                Ok(true) => self.static_version += 1,
+                Ok(false) => {}
+                Err(e) => warn!(quad = %quad, "Failed to add static data to store: {}", e),
+            }
+            return;
+        }
+        if self.static_data.contains(&quad) {
+            return;
+        }
+        if let StaticQuads::Memory(dataset) = &mut self.static_dataset {
+            Arc::make_mut(dataset).insert(&quad);
+        }
+        Arc::make_mut(&mut self.static_data).insert(quad);
+        self.static_version += 1;
     }
 
-    /// Execute the SPARQL query over the container's quads combined with static data
-    pub fn execute(
-        &self,
-        container: &QuadContainer,
-    ) -> Result<QueryResults, Box<dyn std::error::Error>> {
-        // Create an in-memory store
-        let store = Store::new()?;
+    /// Remove a quad from the static data store
+    /// Returns whether the quad was part of the static data
+    pub fn remove_static_data(&mut self, quad: &Quad) -> bool {
+        if let StaticQuads::Store(store) = &self.static_dataset {
+            let removed = store.remove(quad).unwrap_or_else(|e| {
+                warn!(quad = %quad, "Failed to remove static data from store: {}", e);
+                false
+            });
+            if removed {
+                self.static_version += 1;
+            }
+            return removed;
+        }
+        if !self.static_data.contains(quad) {
+            return false;
+        }
+        if let StaticQuads::Memory(dataset) = &mut self.static_dataset {
+            Arc::make_mut(dataset).remove(quad);
+        }
+        Arc::make_mut(&mut self.static_data).remove(quad);
+        self.static_version += 1;
+        true
+    }
+
+    /// Replace the whole static data store
+    /// Clones taken before keep the previous data, unless it's kept in an on-disk store
+    pub fn replace_static_data(&mut self, quads: impl IntoIterator<Item = Quad>) {
+        self.static_version += 1;
+        if let StaticQuads::Store(store) = &self.static_dataset {
+            if let Err(e) = store.clear().and_then(|_| store.extend(quads)) {
+                warn!("Failed to replace static data in store: {}", e);
+            }
+            return;
+        }
+        let static_data: HashSet<Quad> = quads.into_iter().collect();
+        let mut static_dataset = Dataset::new();
+        for quad in &static_data {
+            static_dataset.insert(quad);
+        }
+        self.static_data = Arc::new(static_data);
+        self.static_dataset = StaticQuads::Memory(Arc::new(static_dataset));
+    }
+
+    /// Upsert the static description of the subjects of these quads, e.g. the latest
+    /// state of the entities of a slowly changing dimension
+    /// The quads of each subject replace its static quads in the same graph, and a
+    /// subject with an `rsp:deleted` quad is removed from that graph instead
+    pub fn upsert_static_subjects(&mut self, quads: impl IntoIterator<Item = Quad>) {
+        let mut subjects: HashMap<(NamedOrBlankNode, GraphName), HashSet<Quad>> = HashMap::new();
+        for quad in quads {
+            let key = (quad.subject.clone(), quad.graph_name.clone());
+            subjects.entry(key).or_default().insert(quad);
+        }
+        for ((subject, graph_name), mut quads) in subjects {
+            if quads.iter().any(|quad| quad.predicate.as_str() == DELETED) {
+                quads.clear();
+            }
+            let current = self.static_quads_of(&subject, &graph_name);
+            for quad in current.difference(&quads) {
+                self.remove_static_data(quad);
+            }
+            for quad in quads {
+                if !current.contains(&quad) {
+                    self.add_static_data(quad);
+                }
+            }
+        }
+    }
+
+    /// Get the static quads about a subject in a graph
+    fn static_quads_of(&self, subject: &NamedOrBlankNode, graph_name: &GraphName) -> HashSet<Quad> {
+        match &self.static_dataset {
+            StaticQuads::Memory(dataset) => dataset
+                .quads_for_subject(subject.as_ref())
+                .filter(|quad| quad.graph_name == graph_name.as_ref())
+                .map(QuadRef::into_owned)
+                .collect(),
+            StaticQuads::Store(store) => {
+                let graph_name = Some(graph_name.as_ref());
+                store
+                    .quads_for_pattern(Some(subject.as_ref()), None, None, graph_name)
+                    .filter_map(Result::ok)
+                    .collect()
+            }
+        }
+    }
+
+    /// Get a copy of the static quads, e.g. to index them
+    pub(crate) fn static_quads(&self) -> Vec<Quad> {
+        match &self.static_dataset {
+            StaticQuads::Memory(dataset) => dataset.iter().map(QuadRef::into_owned).collect(),
+            StaticQuads::Store(store) => store.iter().filter_map(Result::ok).collect(),
+        }
+    }
+
+    /// Whether a quad is part of the static data
+    fn is_static(&self, quad: &Quad) -> bool {
+        match &self.static_dataset {
+            StaticQuads::Store(store) => store.contains(quad).unwrap_or(false),
+            StaticQuads::Memory(_) => self.static_data.contains(quad),
+        }
+    }
+
+    /// Merge the triples of these static graphs into the default graph, as the
+    /// `FROM` clauses of the query do
+    pub fn set_default_graphs(&mut self, graphs: impl IntoIterator<Item = NamedNode>) {
+        self.default_graphs = graphs.into_iter().collect();
+        if let Some(cache) = &self.static_cache {
+            cache.clear();
+        }
+    }
 
-        // Add all quads from the container
-        for quad in &container.elements {
-            store.insert(quad)?;
+    /// Whether the static data has quads in a named graph
+    pub fn contains_graph(&self, graph: &NamedNode) -> bool {
+        let graph = GraphNameRef::from(graph.as_ref());
+        match &self.static_dataset {
+            StaticQuads::Memory(dataset) => dataset.quads_for_graph_name(graph).next().is_some(),
+            StaticQuads::Store(store) => store
+                .quads_for_pattern(None, None, None, Some(graph))
+                .next()
+                .is_some(),
         }
+    }
+
+    /// Get the static graphs merged into the default graph
+    pub fn default_graphs(&self) -> &[NamedNode] {
+        &self.default_graphs
+    }
+
+    /// Get the version of the static data, incremented on every change
+    pub fn static_data_version(&self) -> u64 {
+        self.static_version
+    }
+
+    /// Bind a query variable such as `?threshold` to a value for the next executions
+    /// The name may be given with or without its leading `?`
+    pub fn set_parameter(&mut self, name: &str, value: impl Into<Term>) -> Result<(), String> {
+        let variable = Self::parameter_variable(name)?;
+        self.parameters.insert(variable, value.into());
+        Ok(())
+    }
+
+    /// Unbind a parameter, returning its previous value
+    pub fn remove_parameter(&mut self, name: &str) -> Option<Term> {
+        let variable = Self::parameter_variable(name).ok()?;
+        self.parameters.remove(&variable)
+    }
+
+    /// Get the value bound to a parameter, if any
+    pub fn parameter(&self, name: &str) -> Option<&Term> {
+        let variable = Self::parameter_variable(name).ok()?;
+        self.parameters.get(&variable)
+    }
+
+    /// Whether any parameter is bound
+    pub fn has_parameters(&self) -> bool {
+        !self.parameters.is_empty()
+    }
+
+    /// Describe every window quad with an `rdf:Statement` in its graph, giving the
+    /// timestamp of its event with `rsp:timestamp`, so queries can order events or
+    /// select the latest one
+    /// The statements are matched by patterns like `?s ?p ?o` over the window too
+    pub fn set_annotate_timestamps(&mut self, enabled: bool) {
+        self.annotate_timestamps = enabled;
+    }
+
+    /// Whether window quads are reified with their timestamp
+    pub fn annotates_timestamps(&self) -> bool {
+        self.annotate_timestamps
+    }
+
+    /// Describe every window quad recorded with its stream, see
+    /// `CSPARQLWindow::set_record_streams`, with an `rdf:Statement` giving the stream
+    /// IRI with `rsp:stream`, so queries can tell the sources of a window apart
+    /// Statements of quads with a timestamp give it too when timestamps are annotated
+    pub fn set_annotate_streams(&mut self, enabled: bool) {
+        self.annotate_streams = enabled;
+    }
 
-        // Add all static quads
-        for quad in &self.static_data {
-            store.insert(quad)?;
+    /// Whether window quads are reified with their stream
+    pub fn annotates_streams(&self) -> bool {
+        self.annotate_streams
+    }
+
+    /// Evaluate the patterns of the query matching only static data once, and only
+    /// join the window data with their solutions on every execution, e.g. for
+    /// queries joining windows with large static graphs
+    ///
+    /// Static patterns are the triple patterns outside `GRAPH` clauses, and the
+    /// `GRAPH` clauses of other graphs than `window_graphs`, so window quads must be
+    /// in the graph of their window, as the engine puts them. Patterns with more
+    /// than `max_solutions` solutions, or binding blank nodes, are still evaluated
+    /// on every execution. The solutions are evaluated again once the static data
+    /// changed through the operator; changes made directly to the on-disk store
+    /// aren't noticed.
+    pub fn cache_static_patterns(
+        &mut self,
+        window_graphs: impl IntoIterator<Item = NamedNode>,
+        max_solutions: usize,
+    ) {
+        let windows = window_graphs.into_iter().collect();
+        self.static_cache = Some(StaticPatternCache::new(windows, max_solutions));
+        // Computed now rather than on the first report
+        self.cached_query();
+    }
+
+    /// Reorder the triple patterns of the query before each execution, most selective
+    /// first: the pattern with the fewest matches in the static data and the window,
+    /// then, among the patterns sharing a variable with those before, the one with
+    /// the fewest matches
+    /// The counts over the static data are kept until it changes; the query is only
+    /// prepared again when the order changes
+    pub fn set_join_reordering(&mut self, enabled: bool) {
+        self.join_order = enabled.then(JoinOrder::default);
+    }
+
+    /// Whether the triple patterns are reordered before each execution
+    pub fn reorders_joins(&self) -> bool {
+        self.join_order.is_some()
+    }
+
+    /// Share the solutions of the window patterns with the other queries of a
+    /// `PlanSharing`, see `execute_report`
+    pub(crate) fn share_window_patterns(&mut self, patterns: Arc<SharedPatterns>) {
+        self.shared_patterns = Some(patterns);
+    }
+
+    /// Get the query with its triple patterns ordered for the container's quads, or
+    /// `None` if they aren't reordered
+    fn ordered_query(&self, query: &str, container: &QuadContainer) -> Option<CachedQuery> {
+        let cardinalities = Cardinalities {
+            static_data: &self.static_dataset,
+            static_version: self.static_version,
+            default_graphs: &self.default_graphs,
+            window: container,
+        };
+        let prepare = |query: &str| self.evaluator.parse_query(query).ok();
+        self.join_order
+            .as_ref()?
+            .order(query, cardinalities, prepare)
+    }
+
+    /// Get the query rewritten with the solutions of its static patterns, computed
+    /// again if the static data changed since, or `None` if nothing is cached
+    fn cached_query(&self) -> Option<CachedQuery> {
+        let cache = self.static_cache.as_ref()?;
+        let mut cached = cache.cached.lock().unwrap();
+        if let Some((version, query)) = cached.as_ref() {
+            if *version == self.static_version {
+                return query.clone();
+            }
         }
+        let query = replace_static_patterns(
+            &self.query,
+            &cache.windows,
+            &self.default_graphs,
+            |pattern| self.solutions_table(pattern, Dataset::new(), cache.max_solutions),
+        )
+        .and_then(|query| {
+            let prepared = self.evaluator.parse_query(&query).ok()?;
+            Some(Arc::new((query, prepared)))
+        });
+        debug!(
+            version = self.static_version,
+            rewritten = query.as_ref().map(|query| query.0.as_str()),
+            "Cached the static patterns of the R2R query"
+        );
+        *cached = Some((self.static_version, query.clone()));
+        query
+    }
 
-        #[cfg(debug_assertions)]
+    /// Get the query with the `GRAPH` clauses of `window` replaced by their
+    /// solutions over its report, evaluated once for all the queries sharing them,
+    /// or `None` if nothing is shared
+    /// Static quads in the window graph, parameters and reified quads make the
+    /// solutions of a clause differ between queries, which then don't share them;
+    /// reports counting duplicates aren't shared either
+    fn shared_query(&self, window: &NamedNode, report: &Arc<QuadContainer>) -> Option<CachedQuery> {
+        let shared = self.shared_patterns.as_ref()?;
+        if self.has_parameters()
+            || self.annotate_timestamps
+            || self.annotate_streams
+            || self.contains_graph(window)
+            || report.multiplicities.is_some()
         {
-            println!("[R2R] Executing query:");
-            println!("{}", self.query);
-            println!("[R2R] Container has {} quads", container.len());
-            println!("[R2R] Static data has {} quads", self.static_data.len());
-            for (i, quad) in container.elements.iter().enumerate() {
-                println!("[R2R]   Quad {}: {:?}", i + 1, quad);
+            return None;
+        }
+        let cached = self.cached_query();
+        let query = cached
+            .as_ref()
+            .map_or(self.query.as_str(), |cached| cached.0.as_str());
+        let query = replace_window_patterns(query, window, |pattern| {
+            shared.solutions(window, report, pattern, || {
+                self.solutions_table(pattern, self.window_dataset(report), usize::MAX)
+            })
+        })?;
+        let prepared = self.evaluator.parse_query(&query).ok()?;
+        Some(Arc::new((query, prepared)))
+    }
+
+    /// Evaluate a pattern over the static data and a window, as the table of its
+    /// solutions
+    /// `None` if it has more than `max_solutions` or binds blank nodes, which
+    /// tables can't hold
+    fn solutions_table(
+        &self,
+        pattern: &GraphPattern,
+        window: Dataset,
+        max_solutions: usize,
+    ) -> Option<GraphPattern> {
+        let query = spargebra::Query::Select {
+            dataset: None,
+            pattern: pattern.clone(),
+            base_iri: None,
+        };
+        let prepared = self.evaluator.parse_query(&query.to_string()).ok()?;
+        let QueryResults::Solutions(solutions) = prepared
+            .on_queryable_dataset(UnionDataset::new(
+                self.static_dataset.clone(),
+                window,
+                self.default_graphs.clone(),
+            ))
+            .execute()
+            .ok()?
+        else {
+            return None;
+        };
+        let variables = solutions.variables().to_vec();
+        let mut bindings = Vec::new();
+        for solution in solutions {
+            let solution = solution.ok()?;
+            if bindings.len() == max_solutions {
+                return None;
+            }
+            let row = variables
+                .iter()
+                .map(|variable| match solution.get(variable) {
+                    None => Some(None),
+                    Some(Term::NamedNode(node)) => Some(Some(GroundTerm::NamedNode(node.clone()))),
+                    Some(Term::Literal(literal)) => {
+                        Some(Some(GroundTerm::Literal(literal.clone())))
+                    }
+                    Some(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            bindings.push(row);
+        }
+        Some(GraphPattern::Values {
+            variables,
+            bindings,
+        })
+    }
+
+    /// Add the statement reifying a window quad with its timestamp and stream
+    fn annotate(window: &mut Dataset, quad: &Quad, timestamp: Option<i64>, stream: Option<&str>) {
+        let statement = BlankNode::default();
+        let description: [Option<(NamedNode, Term)>; 6] = [
+            Some((rdf::TYPE.into(), rdf::STATEMENT.into_owned().into())),
+            Some((rdf::SUBJECT.into(), quad.subject.clone().into())),
+            Some((rdf::PREDICATE.into(), quad.predicate.clone().into())),
+            Some((rdf::OBJECT.into(), quad.object.clone())),
+            timestamp.map(|timestamp| {
+                let timestamp = Literal::from(timestamp).into();
+                (NamedNode::new_unchecked(TIMESTAMP), timestamp)
+            }),
+            stream
+                .and_then(|stream| NamedNode::new(stream).ok())
+                .map(|stream| (NamedNode::new_unchecked(STREAM), stream.into())),
+        ];
+        for (predicate, object) in description.into_iter().flatten() {
+            window.insert(&Quad::new(
+                statement.clone(),
+                predicate,
+                object,
+                quad.graph_name.clone(),
+            ));
+        }
+    }
+
+    fn parameter_variable(name: &str) -> Result<Variable, String> {
+        let name = name.strip_prefix(['?', '$']).unwrap_or(name);
+        Variable::new(name).map_err(|e| format!("Invalid parameter name {}: {}", name, e))
+    }
+
+    /// Index the container's quads, skipping those already in the static data
+    fn window_dataset(&self, container: &QuadContainer) -> Dataset {
+        let mut window = Dataset::new();
+        for quad in container.iter() {
+            if !self.is_static(quad) {
+                window.insert(quad);
+            }
+            let timestamp = container
+                .timestamp(quad)
+                .filter(|_| self.annotate_timestamps);
+            let stream = container.stream(quad).filter(|_| self.annotate_streams);
+            if timestamp.is_some() || stream.is_some() {
+                Self::annotate(&mut window, quad, timestamp, stream);
             }
         }
+        window
+    }
+
+    /// Execute the SPARQL query over the container's quads combined with static data
+    /// Static data is indexed once and shared, only the container's quads are indexed per call
+    pub fn execute(
+        &self,
+        container: &QuadContainer,
+    ) -> Result<QueryResults<'_>, Box<dyn std::error::Error>> {
+        self.execute_with(container, None)
+    }
+
+    /// Execute the SPARQL query over a report of `window`, with the solutions of its
+    /// window patterns shared with the other queries of a `PlanSharing`, if any
+    /// The rewritten query is parsed for every report
+    pub(crate) fn execute_report(
+        &self,
+        window: &NamedNode,
+        report: &Arc<QuadContainer>,
+    ) -> Result<QueryResults<'_>, Box<dyn std::error::Error>> {
+        let shared = self.shared_query(window, report);
+        self.execute_with(report, shared)
+    }
+
+    /// Execute the query, or the given rewriting of it
+    fn execute_with(
+        &self,
+        container: &QuadContainer,
+        rewritten: Option<CachedQuery>,
+    ) -> Result<QueryResults<'_>, Box<dyn std::error::Error>> {
+        let window = self.window_dataset(container);
+
+        debug!(
+            query = %self.query,
+            window_quads = container.len(),
+            static_quads = self.static_data_size(),
+            "Executing R2R query"
+        );
+        trace!(quads = ?container.elements, "Window content");
 
         // Execute the query
-        // Note: Oxigraph doesn't support custom extension functions in the same way as Comunica
-        // For custom functions like sqrt and pow, you would need to:
-        // 1. Preprocess the query to replace custom functions with SPARQL built-ins
-        // 2. Use SPARQL BIND expressions with standard math operations
-        // 3. Or implement a query rewriter
-        use oxigraph::sparql::SparqlEvaluator;
-        SparqlEvaluator::new()
-            .parse_query(&self.query)?
-            .on_store(&store)
+        // The window bound functions are custom functions, registered per report
+        // Binding consumes the prepared query, cloning it is much cheaper than parsing
+        let cached = rewritten.or_else(|| {
+            let cached = self.cached_query();
+            let query = cached
+                .as_ref()
+                .map_or(self.query.as_str(), |cached| cached.0.as_str());
+            self.ordered_query(query, container).or(cached)
+        });
+        let mut prepared = match (
+            container.window_bounds.filter(|_| self.window_functions),
+            &cached,
+        ) {
+            (Some((open, close)), _) => {
+                let query = cached
+                    .as_ref()
+                    .map_or(self.query.as_str(), |cached| cached.0.as_str());
+                self.prepare_with_bounds(query, open, close)?
+            }
+            (None, Some(cached)) => cached.1.clone(),
+            (None, None) => self.prepared.clone()?,
+        };
+        for (variable, value) in &self.parameters {
+            prepared = prepared.substitute_variable(variable.clone(), value.clone());
+        }
+        let dataset = UnionDataset::new(
+            self.static_dataset.clone(),
+            window,
+            self.default_graphs.clone(),
+        );
+        prepared
+            .on_queryable_dataset(dataset.with_repeated(container.repeated()))
             .execute()
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
     }
 
+    /// Prepare the query with the window bound functions returning `open` and `close`
+    /// Only queries calling them pay for parsing on every execution
+    fn prepare_with_bounds(
+        &self,
+        query: &str,
+        open: i64,
+        close: i64,
+    ) -> Result<PreparedSparqlQuery, String> {
+        let bound = |value: i64| {
+            move |args: &[Term]| args.is_empty().then(|| Term::from(Literal::from(value)))
+        };
+        self.evaluator
+            .clone()
+            .with_custom_function(NamedNode::new_unchecked(WINDOW_START), bound(open))
+            .with_custom_function(NamedNode::new_unchecked(WINDOW_END), bound(close))
+            .parse_query(query)
+            .map_err(|e| e.to_string())
+    }
+
     /// Execute the SPARQL query and return results as a vector of solution mappings
     /// This is a convenience method that handles common result types
     pub fn execute_select(
@@ -89,17 +666,74 @@ impl R2ROperator {
         Ok(output)
     }
 
+    /// Describe how oxigraph evaluates the query, over the static data only
+    /// With cached static patterns, the query joining their solutions is described
+    /// `None` if the query doesn't parse
+    pub fn explain(&self) -> Option<String> {
+        let prepared = match self.cached_query() {
+            Some(cached) => cached.1.clone(),
+            None => self.prepared.clone().ok()?,
+        };
+        let (_, explanation) = prepared
+            .on_queryable_dataset(UnionDataset::new(
+                self.static_dataset.clone(),
+                Dataset::new(),
+                self.default_graphs.clone(),
+            ))
+            .explain();
+        Some(format!("{:?}", explanation))
+    }
+
+    /// Replace the query, keeping the static data, parameters and settings
+    /// Fails without changing anything if the new query can't be parsed
+    pub fn set_query(&mut self, query: String) -> Result<(), String> {
+        let prepared = self
+            .evaluator
+            .parse_query(&query)
+            .map_err(|e| e.to_string())?;
+        self.window_functions = calls_window_functions(&query);
+        self.prepared = Ok(prepared);
+        self.query = query;
+        if let Some(cache) = &self.static_cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
     /// Get a reference to the query string
     pub fn query(&self) -> &str {
         &self.query
     }
 
+    /// Get the syntax error of the query, if it couldn't be parsed
+    pub fn parse_error(&self) -> Option<&str> {
+        self.prepared.as_ref().err().map(String::as_str)
+    }
+
     /// Get the number of static quads
+    /// Counting the quads of an on-disk store scans it, see `has_static_data`
     pub fn static_data_size(&self) -> usize {
-        self.static_data.len()
+        match &self.static_dataset {
+            StaticQuads::Memory(_) => self.static_data.len(),
+            StaticQuads::Store(store) => store.len().unwrap_or(0),
+        }
+    }
+
+    /// Whether there is any static quad
+    pub fn has_static_data(&self) -> bool {
+        match &self.static_dataset {
+            StaticQuads::Memory(_) => !self.static_data.is_empty(),
+            StaticQuads::Store(store) => !store.is_empty().unwrap_or(true),
+        }
     }
 }
 
+/// Whether a query may call the window bound functions
+/// Checked by local name, whatever the prefix; false positives only cost parsing
+fn calls_window_functions(query: &str) -> bool {
+    query.contains("windowStart") || query.contains("windowEnd")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -111,6 +745,16 @@ mod tests {
         let operator = R2ROperator::new(query.clone());
         assert_eq!(operator.query(), query);
         assert_eq!(operator.static_data_size(), 0);
+        assert!(operator.parse_error().is_none());
+    }
+
+    #[test]
+    fn test_invalid_query_fails_on_execution() {
+        let operator = R2ROperator::new("SELECT * WHERE { ?s ?p".to_string());
+        assert!(operator.parse_error().is_some());
+
+        let container = QuadContainer::new(HashSet::new(), 0);
+        assert!(operator.execute(&container).is_err());
     }
 
     #[test]
@@ -166,4 +810,436 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_static_data_is_shared_without_duplicates() -> Result<(), Box<dyn std::error::Error>> {
+        let quad = |s: &str| {
+            Quad::new(
+                NamedNode::new(format!("http://example.org/{}", s)).unwrap(),
+                NamedNode::new("http://example.org/p").unwrap(),
+                Literal::new_simple_literal("o"),
+                GraphName::DefaultGraph,
+            )
+        };
+        let mut operator = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        operator.add_static_data(quad("static"));
+        let snapshot = operator.clone();
+        operator.add_static_data(quad("late"));
+
+        // A quad both in the window and in the static data is only matched once
+        let container = QuadContainer::new([quad("static"), quad("window")].into(), 0);
+        let count = |operator: &R2ROperator| -> Result<usize, Box<dyn std::error::Error>> {
+            match operator.execute(&container)? {
+                QueryResults::Solutions(solutions) => Ok(solutions.count()),
+                _ => Ok(0),
+            }
+        };
+        assert_eq!(count(&operator)?, 3);
+        // Static data added after cloning doesn't leak into the clone
+        assert_eq!(count(&snapshot)?, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_and_replace_static_data() {
+        let quad = |s: &str| {
+            Quad::new(
+                NamedNode::new(format!("http://example.org/{}", s)).unwrap(),
+                NamedNode::new("http://example.org/p").unwrap(),
+                Literal::new_simple_literal("o"),
+                GraphName::DefaultGraph,
+            )
+        };
+        let mut operator = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        operator.add_static_data(quad("a"));
+        operator.add_static_data(quad("a"));
+        assert_eq!(operator.static_data_version(), 1);
+
+        assert!(operator.remove_static_data(&quad("a")));
+        assert!(!operator.remove_static_data(&quad("a")));
+        assert_eq!(operator.static_data_size(), 0);
+        assert_eq!(operator.static_data_version(), 2);
+
+        let snapshot = operator.clone();
+        operator.replace_static_data([quad("b"), quad("c")]);
+        assert_eq!(operator.static_data_size(), 2);
+        assert_eq!(operator.static_data_version(), 3);
+        assert_eq!(snapshot.static_data_size(), 0);
+    }
+
+    #[test]
+    fn test_static_subjects_are_upserted() {
+        let node = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let fact = |s: &str, p: &str, o: &str| {
+            Quad::new(node(s), node(p), node(o), GraphName::DefaultGraph)
+        };
+        let mut operator = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        operator.add_static_data(fact("sensor1", "room", "kitchen"));
+        operator.add_static_data(fact("sensor1", "owner", "alice"));
+        operator.add_static_data(fact("sensor2", "room", "hall"));
+
+        operator.upsert_static_subjects([fact("sensor1", "room", "attic")]);
+        assert!(operator.is_static(&fact("sensor1", "room", "attic")));
+        assert!(!operator.is_static(&fact("sensor1", "owner", "alice")));
+        assert_eq!(operator.static_data_size(), 2);
+
+        // Upserting the same state again changes nothing
+        let version = operator.static_data_version();
+        operator.upsert_static_subjects([fact("sensor1", "room", "attic")]);
+        assert_eq!(operator.static_data_version(), version);
+
+        let deleted = Quad::new(
+            node("sensor2"),
+            NamedNode::new(DELETED).unwrap(),
+            Literal::from(true),
+            GraphName::DefaultGraph,
+        );
+        operator.upsert_static_subjects([deleted]);
+        assert!(!operator.is_static(&fact("sensor2", "room", "hall")));
+        assert_eq!(operator.static_data_size(), 1);
+    }
+
+    #[test]
+    fn test_default_graphs_are_merged() -> Result<(), Box<dyn std::error::Error>> {
+        let quad = |s: &str, graph: &str| {
+            Quad::new(
+                NamedNode::new(format!("http://example.org/{}", s)).unwrap(),
+                NamedNode::new("http://example.org/p").unwrap(),
+                Literal::new_simple_literal("o"),
+                NamedNode::new(format!("http://example.org/{}", graph)).unwrap(),
+            )
+        };
+        let mut operator = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        operator.add_static_data(quad("a", "g1"));
+        operator.add_static_data(quad("a", "g2"));
+        operator.add_static_data(quad("b", "g2"));
+        operator.add_static_data(quad("c", "g3"));
+        let container = QuadContainer::new(HashSet::new(), 0);
+        let count = |operator: &R2ROperator| -> Result<usize, Box<dyn std::error::Error>> {
+            match operator.execute(&container)? {
+                QueryResults::Solutions(solutions) => Ok(solutions.count()),
+                _ => Ok(0),
+            }
+        };
+
+        // Named graphs aren't part of the default graph
+        assert_eq!(count(&operator)?, 0);
+        operator.set_default_graphs(
+            ["g1", "g2"].map(|g| NamedNode::new(format!("http://example.org/{}", g)).unwrap()),
+        );
+        // The triple in both graphs is matched once
+        assert_eq!(count(&operator)?, 2);
+        assert_eq!(operator.default_graphs().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_static_data_in_store() -> Result<(), Box<dyn std::error::Error>> {
+        let quad = |s: &str, graph: GraphName| {
+            Quad::new(
+                NamedNode::new(format!("http://example.org/{}", s)).unwrap(),
+                NamedNode::new("http://example.org/p").unwrap(),
+                Literal::new_simple_literal("o"),
+                graph,
+            )
+        };
+        let path = std::env::temp_dir().join(format!("rsp-rs-r2r-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let mut operator = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        operator.add_static_data(quad("a", GraphName::DefaultGraph));
+        operator.open_store(&path)?;
+        assert!(operator.store().is_some());
+        operator.add_static_data(quad("b", GraphName::DefaultGraph));
+        let g1 = NamedNode::new("http://example.org/g1")?;
+        operator.add_static_data(quad("c", g1.clone().into()));
+        assert!(operator.remove_static_data(&quad("b", GraphName::DefaultGraph)));
+        assert!(!operator.remove_static_data(&quad("b", GraphName::DefaultGraph)));
+        assert_eq!(operator.static_data_size(), 2);
+        assert_eq!(operator.static_data_version(), 5);
+        assert!(operator.contains_graph(&g1));
+
+        // The window quad already in the store is matched once
+        let window = [
+            quad("a", GraphName::DefaultGraph),
+            quad("d", GraphName::DefaultGraph),
+        ];
+        let container = QuadContainer::new(window.into_iter().collect(), 0);
+        let count = |operator: &R2ROperator| -> Result<usize, Box<dyn std::error::Error>> {
+            match operator.execute(&container)? {
+                QueryResults::Solutions(solutions) => Ok(solutions.count()),
+                _ => Ok(0),
+            }
+        };
+        assert_eq!(count(&operator)?, 2);
+        operator.set_default_graphs([g1]);
+        assert_eq!(count(&operator)?, 3);
+
+        // The static data outlives the operator
+        drop(operator);
+        let mut reopened = R2ROperator::new("SELECT * WHERE { ?s ?p ?o }".to_string());
+        reopened.open_store(&path)?;
+        assert!(reopened.has_static_data());
+        reopened.replace_static_data([quad("e", GraphName::DefaultGraph)]);
+        assert_eq!(reopened.static_data_size(), 1);
+        drop(reopened);
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_static_patterns_are_cached() -> Result<(), Box<dyn std::error::Error>> {
+        let ex = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let located = |sensor: &str, room: &str| {
+            Quad::new(ex(sensor), ex("room"), ex(room), GraphName::DefaultGraph)
+        };
+        let mut operator = R2ROperator::new(
+            "PREFIX ex: <http://example.org/>
+             SELECT ?sensor ?room WHERE {
+                 ?sensor ex:room ?room .
+                 GRAPH ex:w1 { ?sensor ex:value ?v }
+             }"
+            .to_string(),
+        );
+        operator.add_static_data(located("s1", "kitchen"));
+        operator.add_static_data(located("s2", "hall"));
+        operator.cache_static_patterns([ex("w1")], 10);
+        let cached = operator.cached_query().unwrap();
+        assert!(cached.0.contains("VALUES") && !cached.0.contains("<http://example.org/room>"));
+
+        let reading = Quad::new(ex("s1"), ex("value"), Literal::from(21), ex("w1"));
+        let container = QuadContainer::new(HashSet::from([reading]), 0);
+        let rooms = |operator: &R2ROperator| -> Result<Vec<Term>, Box<dyn std::error::Error>> {
+            let mut rooms = Vec::new();
+            if let QueryResults::Solutions(solutions) = operator.execute(&container)? {
+                for solution in solutions {
+                    rooms.extend(solution?.get("room").cloned());
+                }
+            }
+            Ok(rooms)
+        };
+        assert_eq!(rooms(&operator)?, vec![Term::from(ex("kitchen"))]);
+
+        // Changes of the static data are picked up
+        operator.replace_static_data([located("s1", "hall")]);
+        assert_eq!(rooms(&operator)?, vec![Term::from(ex("hall"))]);
+
+        // Too many solutions to cache, the pattern is evaluated on every execution
+        operator.cache_static_patterns([ex("w1")], 0);
+        assert!(operator.cached_query().is_none());
+        assert_eq!(rooms(&operator)?, vec![Term::from(ex("hall"))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_reordering() -> Result<(), Box<dyn std::error::Error>> {
+        let ex = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let mut operator = R2ROperator::new(
+            "PREFIX ex: <http://example.org/>
+             SELECT ?sensor ?value WHERE {
+                 ?sensor a ex:Sensor ;
+                     ex:critical true .
+                 GRAPH ex:w1 { ?sensor ex:value ?value }
+             }"
+            .to_string(),
+        );
+        operator.set_join_reordering(true);
+        assert!(operator.reorders_joins());
+        for i in 0..20 {
+            let sensor = ex(&format!("s{}", i));
+            operator.add_static_data(Quad::new(
+                sensor.clone(),
+                rdf::TYPE,
+                ex("Sensor"),
+                GraphName::DefaultGraph,
+            ));
+            if i == 3 {
+                operator.add_static_data(Quad::new(
+                    sensor,
+                    ex("critical"),
+                    Literal::from(true),
+                    GraphName::DefaultGraph,
+                ));
+            }
+        }
+
+        let reading = Quad::new(ex("s3"), ex("value"), Literal::from(21), ex("w1"));
+        let container = QuadContainer::new(HashSet::from([reading]), 0);
+        let ordered = operator.ordered_query(&operator.query, &container).unwrap();
+        let position = |iri: &str| ordered.0.find(iri).unwrap();
+        assert!(position("/critical>") < position("/Sensor>"));
+
+        let QueryResults::Solutions(solutions) = operator.execute(&container)? else {
+            panic!("Expected solutions");
+        };
+        let solutions = solutions.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].get("sensor"), Some(&ex("s3").into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parameters_are_substituted() -> Result<(), Box<dyn std::error::Error>> {
+        let mut operator = R2ROperator::new(
+            "SELECT ?s WHERE { ?s <http://example.org/value> ?v FILTER(?v > ?threshold) }"
+                .to_string(),
+        );
+        let container = QuadContainer::new(
+            [10, 20, 30]
+                .map(|value| {
+                    Quad::new(
+                        NamedNode::new(format!("http://example.org/s{}", value)).unwrap(),
+                        NamedNode::new("http://example.org/value").unwrap(),
+                        Literal::from(value),
+                        GraphName::DefaultGraph,
+                    )
+                })
+                .into(),
+            0,
+        );
+        let count = |operator: &R2ROperator| -> Result<usize, Box<dyn std::error::Error>> {
+            match operator.execute(&container)? {
+                QueryResults::Solutions(solutions) => Ok(solutions.count()),
+                _ => Ok(0),
+            }
+        };
+
+        // An unbound placeholder makes the filter fail
+        assert_eq!(count(&operator)?, 0);
+        operator.set_parameter("?threshold", Literal::from(15))?;
+        assert_eq!(count(&operator)?, 2);
+        operator.set_parameter("threshold", Literal::from(25))?;
+        assert_eq!(count(&operator)?, 1);
+        assert_eq!(
+            operator.parameter("threshold"),
+            Some(&Literal::from(25).into())
+        );
+
+        assert!(
+            operator
+                .set_parameter("not a variable", Literal::from(1))
+                .is_err()
+        );
+        assert!(operator.remove_parameter("threshold").is_some());
+        assert!(!operator.has_parameters());
+        Ok(())
+    }
+
+    #[test]
+    fn test_window_bound_functions() {
+        let operator = R2ROperator::new(
+            "PREFIX rsp: <https://rsp.rs/ns#>
+             SELECT ?start ?end WHERE { BIND(rsp:windowStart() AS ?start) BIND(rsp:windowEnd() AS ?end) }"
+                .to_string(),
+        );
+        let bounds = |container: &QuadContainer| {
+            let Ok(QueryResults::Solutions(mut solutions)) = operator.execute(container) else {
+                panic!("Expected solutions");
+            };
+            let solution = solutions.next().unwrap().unwrap();
+            (solution.get("start").cloned(), solution.get("end").cloned())
+        };
+
+        // Unbound outside of a window report
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        assert_eq!(bounds(&container), (None, None));
+        container.window_bounds = Some((1000, 2000));
+        assert_eq!(
+            bounds(&container),
+            (
+                Some(Literal::from(1000).into()),
+                Some(Literal::from(2000).into())
+            )
+        );
+    }
+
+    #[test]
+    fn test_timestamps_are_annotated() {
+        let mut operator = R2ROperator::new(
+            "PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+             PREFIX rsp: <https://rsp.rs/ns#>
+             SELECT ?v ?t WHERE {
+                 ?s <http://example.org/value> ?v .
+                 ?r rdf:subject ?s ; rdf:object ?v ; rsp:timestamp ?t
+             } ORDER BY ?t"
+                .to_string(),
+        );
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        for (value, timestamp) in [(10, 100), (20, 400)] {
+            let quad = Quad::new(
+                NamedNode::new("http://example.org/sensor1").unwrap(),
+                NamedNode::new("http://example.org/value").unwrap(),
+                Literal::from(value),
+                GraphName::DefaultGraph,
+            );
+            container.add(quad, timestamp);
+        }
+        let rows = |operator: &R2ROperator| {
+            let Ok(QueryResults::Solutions(solutions)) = operator.execute(&container) else {
+                panic!("Expected solutions");
+            };
+            solutions
+                .map(|solution| {
+                    let solution = solution.unwrap();
+                    (solution.get("v").cloned(), solution.get("t").cloned())
+                })
+                .collect::<Vec<_>>()
+        };
+        let row = |value: i64, timestamp: i64| {
+            (
+                Some(Term::from(Literal::from(value))),
+                Some(Term::from(Literal::from(timestamp))),
+            )
+        };
+
+        assert!(rows(&operator).is_empty());
+        operator.set_annotate_timestamps(true);
+        assert!(operator.annotates_timestamps());
+        assert_eq!(rows(&operator), vec![row(10, 100), row(20, 400)]);
+    }
+
+    #[test]
+    fn test_streams_are_annotated() {
+        let mut operator = R2ROperator::new(
+            "PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+             PREFIX rsp: <https://rsp.rs/ns#>
+             SELECT ?v ?stream WHERE {
+                 ?s <http://example.org/value> ?v .
+                 ?r rdf:subject ?s ; rdf:object ?v ; rsp:stream ?stream
+             } ORDER BY ?v"
+                .to_string(),
+        );
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        for (value, stream) in [
+            (10, "http://example.org/stream1"),
+            (20, "http://example.org/stream2"),
+        ] {
+            let quad = Quad::new(
+                NamedNode::new("http://example.org/sensor1").unwrap(),
+                NamedNode::new("http://example.org/value").unwrap(),
+                Literal::from(value),
+                GraphName::DefaultGraph,
+            );
+            container.add_from(quad, 100, Some(&Arc::from(stream)));
+        }
+        let rows = |operator: &R2ROperator| {
+            let Ok(QueryResults::Solutions(solutions)) = operator.execute(&container) else {
+                panic!("Expected solutions");
+            };
+            solutions
+                .map(|solution| solution.unwrap().get("stream").unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(rows(&operator).is_empty());
+        operator.set_annotate_streams(true);
+        assert!(operator.annotates_streams());
+        assert_eq!(
+            rows(&operator),
+            vec![
+                "<http://example.org/stream1>",
+                "<http://example.org/stream2>"
+            ]
+        );
+    }
 }
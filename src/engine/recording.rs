@@ -0,0 +1,165 @@
+use crate::rdf::parse_rdf;
+use oxigraph::io::RdfFormat;
+use oxigraph::model::Quad;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Header of the events added with `add_timestamped`, filtered and validated
+const EVENT: &str = "@event";
+/// Header of the containers added with `RDFStream::add`, as they are
+const CONTAINER: &str = "@container";
+
+/// Event added to a stream of an engine, as recorded in an input log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    /// IRI of the stream
+    pub stream: String,
+    pub timestamp: i64,
+    /// Quads of the event, before the filter and validation of the stream
+    pub quads: Vec<Quad>,
+    /// Whether the event was added as a container with `RDFStream::add`, bypassing
+    /// the filter and validation of the stream
+    pub container: bool,
+}
+
+/// Input of an engine recorded with `RSPEngineBuilder::record_input`, to replay the
+/// run with `RSPEngine::replay`
+///
+/// The log is a text file appended to as events arrive: each event is a header
+/// line `@event <timestamp> <stream IRI> <quad count>` (`@container` for events
+/// added with `RDFStream::add`) followed by its quads in N-Quads, one per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLog {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl InputLog {
+    /// Read the events recorded in a log, in the order they were added
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| format!("Failed to open input log {}: {}", path.display(), e))?;
+        let mut lines = BufReader::new(file).lines();
+        let mut events = Vec::new();
+        while let Some(header) = lines.next() {
+            let header = header.map_err(|e| format!("Failed to read input log: {}", e))?;
+            if header.trim().is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid input log header: {}", header);
+            let fields: Vec<&str> = header.split(' ').collect();
+            let [kind, timestamp, stream, count] = fields[..] else {
+                return Err(invalid());
+            };
+            if kind != EVENT && kind != CONTAINER {
+                return Err(invalid());
+            }
+            let timestamp: i64 = timestamp.parse().map_err(|_| invalid())?;
+            let count: usize = count.parse().map_err(|_| invalid())?;
+            let mut document = String::new();
+            for _ in 0..count {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| format!("Input log truncated in the event at {}", timestamp))?
+                    .map_err(|e| format!("Failed to read input log: {}", e))?;
+                document.push_str(&line);
+                document.push('\n');
+            }
+            events.push(RecordedEvent {
+                stream: stream.to_string(),
+                timestamp,
+                quads: parse_rdf(&document, RdfFormat::NQuads)?,
+                container: kind == CONTAINER,
+            });
+        }
+        Ok(Self { events })
+    }
+}
+
+/// Append-only log of the events added to the streams of an engine, shared by them
+pub(crate) struct InputRecorder {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl InputRecorder {
+    /// Open the log at `path`, appending to it if it exists
+    pub(crate) fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open input log {}: {}", path.display(), e))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Append an event, flushed at once so the log survives a crash of the engine
+    /// Failures are logged, recording never fails ingestion
+    pub(crate) fn record<'a>(
+        &self,
+        stream: &str,
+        timestamp: i64,
+        quads: impl ExactSizeIterator<Item = &'a Quad>,
+        container: bool,
+    ) {
+        let kind = if container { CONTAINER } else { EVENT };
+        let mut entry = format!("{} {} {} {}\n", kind, timestamp, stream, quads.len());
+        for quad in quads {
+            entry.push_str(&format!("{} .\n", quad));
+        }
+        let mut writer = self.writer.lock().unwrap();
+        let written = writer
+            .write_all(entry.as_bytes())
+            .and_then(|_| writer.flush());
+        if let Err(e) = written {
+            warn!(stream, timestamp, "Failed to record event: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{GraphName, Literal, NamedNode};
+
+    #[test]
+    fn test_record_and_read_events() {
+        let path = std::env::temp_dir().join(format!("rsp-rs-input-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let quad = Quad::new(
+            NamedNode::new_unchecked("http://example.org/sensor1"),
+            NamedNode::new_unchecked("http://example.org/note"),
+            Literal::from("multi\nline"),
+            GraphName::DefaultGraph,
+        );
+
+        let recorder = InputRecorder::open(&path).unwrap();
+        recorder.record("http://example.org/s1", 100, [&quad].into_iter(), false);
+        recorder.record("http://example.org/s2", 50, std::iter::empty(), true);
+        drop(recorder);
+
+        let log = InputLog::open(&path).unwrap();
+        assert_eq!(
+            log.events,
+            vec![
+                RecordedEvent {
+                    stream: "http://example.org/s1".to_string(),
+                    timestamp: 100,
+                    quads: vec![quad],
+                    container: false,
+                },
+                RecordedEvent {
+                    stream: "http://example.org/s2".to_string(),
+                    timestamp: 50,
+                    quads: Vec::new(),
+                    container: true,
+                },
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::delta::Binding;
+use std::fmt;
+use std::sync::Arc;
+
+/// Condition the results of the query must meet to be emitted, e.g. the threshold
+/// of an "alert when `AVG(?t) > 30`" query
+///
+/// Filters apply per result after the query, so they behave like a `HAVING` clause
+/// over the projected variables and suit thresholds chosen by the application
+/// rather than written in the query. Unbound or non-numeric variables never pass
+/// the numeric thresholds.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngineBuilder, ResultFilter};
+///
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .filter_results(ResultFilter::greater_than("avg_temp", 30.0))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct ResultFilter(Arc<dyn Fn(&Binding) -> bool + Send + Sync>);
+
+impl ResultFilter {
+    /// Create a filter keeping the results for which `predicate` holds
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&Binding) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(predicate))
+    }
+
+    /// Create a filter keeping the results whose numeric `variable` is above `threshold`
+    pub fn greater_than(variable: &str, threshold: f64) -> Self {
+        let variable = variable.to_string();
+        Self::new(move |binding| {
+            binding
+                .number(&variable)
+                .is_some_and(|value| value > threshold)
+        })
+    }
+
+    /// Create a filter keeping the results whose numeric `variable` is below `threshold`
+    pub fn less_than(variable: &str, threshold: f64) -> Self {
+        let variable = variable.to_string();
+        Self::new(move |binding| {
+            binding
+                .number(&variable)
+                .is_some_and(|value| value < threshold)
+        })
+    }
+
+    /// Check whether a result passes the filter
+    pub fn matches(&self, binding: &Binding) -> bool {
+        (self.0)(binding)
+    }
+}
+
+impl fmt::Debug for ResultFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResultFilter")
+    }
+}
+
+/// Filters are compared by identity
+impl PartialEq for ResultFilter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ResultFilter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::Literal;
+
+    fn average(avg: f64) -> Binding {
+        Binding {
+            values: vec![("avg".to_string(), Literal::from(avg).into())],
+        }
+    }
+
+    #[test]
+    fn test_threshold_filters() {
+        let high = ResultFilter::greater_than("avg", 30.0);
+        assert!(high.matches(&average(31.5)));
+        assert!(!high.matches(&average(30.0)));
+        assert!(ResultFilter::less_than("avg", 10.0).matches(&average(5.0)));
+        assert!(!ResultFilter::greater_than("missing", 0.0).matches(&average(31.5)));
+
+        assert_eq!(high, high.clone());
+        assert_ne!(high, ResultFilter::greater_than("avg", 30.0));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::BindingWithTimestamp;
+use std::collections::VecDeque;
+
+/// Ring buffer of the latest results of a query, so consumers attaching late can
+/// fetch recent history
+///
+/// Results are dropped once more than `max_results` are retained, or once their
+/// window ended more than `max_age` ms (in event time) before the latest one.
+#[derive(Debug, Clone)]
+pub struct ResultHistory {
+    max_results: Option<usize>,
+    max_age: Option<i64>,
+    results: VecDeque<BindingWithTimestamp>,
+}
+
+impl ResultHistory {
+    /// Create a buffer retaining at most `max_results` results, of at most `max_age` ms
+    pub fn new(max_results: Option<usize>, max_age: Option<i64>) -> Self {
+        Self {
+            max_results,
+            max_age,
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Retain a result, dropping those beyond the limits
+    pub fn push(&mut self, result: BindingWithTimestamp) {
+        self.results.push_back(result);
+        if let Some(max_results) = self.max_results {
+            while self.results.len() > max_results {
+                self.results.pop_front();
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            let latest = self
+                .results
+                .iter()
+                .map(|r| r.timestamp_to)
+                .max()
+                .unwrap_or(0);
+            self.results.retain(|r| latest - r.timestamp_to <= max_age);
+        }
+    }
+
+    /// Get the retained results whose window ends at or after `since`, oldest first
+    pub fn since(&self, since: i64) -> Vec<BindingWithTimestamp> {
+        self.results
+            .iter()
+            .filter(|r| r.timestamp_to >= since)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the number of retained results
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Whether no result is retained
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(timestamp: i64) -> BindingWithTimestamp {
+        BindingWithTimestamp {
+            bindings: format!("result at {}", timestamp),
+            solution: crate::Binding { values: Vec::new() },
+            timestamp_from: timestamp,
+            timestamp_to: timestamp + 10,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        }
+    }
+
+    #[test]
+    fn test_history_limits() {
+        let mut history = ResultHistory::new(Some(3), None);
+        for timestamp in 0..5 {
+            history.push(result(timestamp));
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.since(0)[0].timestamp_from, 2);
+        assert_eq!(history.since(13).len(), 2);
+
+        let mut history = ResultHistory::new(None, Some(100));
+        history.push(result(0));
+        history.push(result(50));
+        assert_eq!(history.len(), 2);
+        history.push(result(150));
+        let retained: Vec<i64> = history.since(0).iter().map(|r| r.timestamp_from).collect();
+        assert_eq!(retained, vec![50, 150]);
+    }
+}
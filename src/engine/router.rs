@@ -0,0 +1,43 @@
+use crate::quad_filter::QuadFilter;
+
+/// Dispatch of the quads of one physical feed, e.g. a single Kafka topic, to the
+/// windows of the engine by their content
+///
+/// Each quad of an event goes to every window whose route it passes, and quads
+/// passing no route are dropped. Every routed window receives every event, with the
+/// quads routed to it, so the feed advances the time of all of them.
+///
+/// ```rust,no_run
+/// use oxigraph::model::NamedNode;
+/// use rsp_rs::{QuadFilter, RSPEngineBuilder, StreamRouter};
+///
+/// let predicate = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+/// let router = StreamRouter::new()
+///     .route("http://example.org/temperatures", QuadFilter::predicates([predicate("temp")]))
+///     .route("http://example.org/doors", QuadFilter::predicates([predicate("open")]));
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .route_stream("http://example.org/feed", router)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamRouter {
+    routes: Vec<(String, QuadFilter)>,
+}
+
+impl StreamRouter {
+    /// Create a router without routes, dropping every quad
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the quads passing `filter` to a window, identified by its IRI
+    pub fn route(mut self, window_name: &str, filter: QuadFilter) -> Self {
+        self.routes.push((window_name.to_string(), filter));
+        self
+    }
+
+    /// Get the routes, as window IRIs and their filters
+    pub fn routes(&self) -> &[(String, QuadFilter)] {
+        &self.routes
+    }
+}
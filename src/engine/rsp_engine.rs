@@ -1,49 +1,945 @@
+use crate::alerts::{Alert, AlertRule};
+use crate::builder::{EngineConfig, RSPEngineBuilder};
+use crate::calendar::TimeZone;
+use crate::clock::{self, Clock};
+use crate::dead_letter::{DeadLetter, DeadLetters, DropReason};
+use crate::dedup::{Deduplicator, ResultDedup};
+use crate::delta::{Binding, DeltaTracker, ResultDelta};
+use crate::enrichment::LookupTable;
+use crate::evaluation_pool::EvaluationPool;
+use crate::events::{EngineEvent, EventPublisher};
+use crate::eviction::LatestValue;
+use crate::explain::{QueryPlan, WindowPlan};
+use crate::gap_monitor::{GapMonitor, GapThreshold, StreamGap};
+use crate::incremental::AggregationPlan;
+use crate::ingestion_buffer::{ShardedBuffer, ShardedSender};
+use crate::jsonld::parse_jsonld;
+use crate::merge::ResultMerger;
+use crate::metrics::{EngineMetrics, MetricsRecorder};
 use crate::parsed_query::WindowDefinition;
+use crate::partition::PartitionKey;
+use crate::pause::PauseControl;
+use crate::processing::{ProcessingHandle, ResultCallbacks, ResultOutput, SubscriptionToken};
+use crate::quad_filter::QuadFilter;
+use crate::rate_limit::RateLimiter;
+use crate::rdf::parse_rdf;
+use crate::recording::{InputLog, InputRecorder};
+use crate::result_filter::ResultFilter;
+use crate::retention::ResultHistory;
 use crate::rspql_parser::RSPQLParser;
-use crate::{CSPARQLWindow, QuadContainer, R2ROperator};
-use oxigraph::model::Quad;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex, mpsc};
+use crate::sequence::{SequenceMatch, SequencePattern};
+use crate::service::ServiceClient;
+use crate::shacl::StreamValidation;
+use crate::sharing::{SharedWindow, WindowSpec};
+use crate::shedding::LoadShedder;
+use crate::static_graphs;
+use crate::stream_stats::StreamStats;
+use crate::supervisor::{EngineError, ErrorChannel, spawn_named};
+use crate::telemetry::ReportSpan;
+use crate::temporal_join::{JoinMatch, TemporalJoin};
+use crate::timestamp::{Instant, TimestampExtractor, TimestampPolicy, wall_clock_millis};
+use crate::window_instance::WindowState;
+use crate::{CSPARQLWindow, QuadContainer, R2ROperator, ReportStrategy, Tick};
+use oxigraph::io::RdfFormat;
+use oxigraph::model::{Graph, GraphName, NamedNode, Quad, Term};
+use oxigraph::sparql::QuerySolution;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak, mpsc};
 use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Maximum number of queued containers applied to a window under one lock
+const MAX_INGESTION_BATCH: usize = 1024;
 
 /// Represents a binding result with timestamp information
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BindingWithTimestamp {
     pub bindings: String,
+    /// Typed values of the solution
+    pub solution: Binding,
     pub timestamp_from: i64,
     pub timestamp_to: i64,
+    /// Time from the report of the window instance to the emission of the result,
+    /// including its evaluation; `None` for results not emitted by an engine
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub evaluation_latency: Option<Duration>,
+    /// Time from the reception of the event closing the window instance by its
+    /// stream to the emission of the result; `None` if no event closed it, e.g. a
+    /// processing-time clock did
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ingestion_latency: Option<Duration>,
+    /// IRI of the window whose report produced the result; `None` for results not
+    /// emitted by an engine
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub window: Option<String>,
+    /// Position of the result among the solutions of its window report, from 0
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub row: usize,
+}
+
+/// Event sent by a stream to its window: its quads and the name of the stream,
+/// interned so events share it instead of cloning a `String` each
+pub type StreamEvent = (QuadContainer, Arc<str>);
+
+/// Sending side of the channel between a stream and its window
+#[derive(Clone)]
+pub(crate) enum WindowSender {
+    Unbounded(mpsc::Sender<StreamEvent>),
+    /// Pushes onto the shard of the producer's thread, without contending with the
+    /// producers on other threads
+    Sharded(ShardedSender),
+    /// Blocks producers while the channel is full
+    Bounded(mpsc::SyncSender<StreamEvent>),
+    /// Applies containers to the window on the producer's thread
+    Inline(Arc<InlineIngestion>),
+    /// Upserts the quads of containers into the static data on the producer's thread
+    SideInput(Arc<SideInput>),
+    /// Sends the quads of containers passing each filter to the window of its sender
+    Routed(Arc<[(QuadFilter, WindowSender)]>),
+}
+
+/// Window and statistics updated directly by its streams in synchronous mode
+pub(crate) struct InlineIngestion {
+    window: Arc<Mutex<CSPARQLWindow>>,
+    stream_stats: Arc<Mutex<HashMap<Arc<str>, StreamStats>>>,
+    metrics: Arc<MetricsRecorder>,
+    errors: ErrorChannel,
+    events: EventPublisher,
+    dead_letters: DeadLetters,
+}
+
+/// Callback subscribed to static data changes, called with the new version
+type StaticDataCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// Static data and statistics updated directly by a side-input stream
+pub(crate) struct SideInput {
+    r2r: Arc<RwLock<R2ROperator>>,
+    callbacks: Arc<RwLock<Vec<StaticDataCallback>>>,
+    stream_stats: Arc<Mutex<HashMap<Arc<str>, StreamStats>>>,
+}
+
+/// Receiving side of the queue between the streams of a window and its ingestion
+/// thread
+pub(crate) enum IngestionQueue {
+    Channel(mpsc::Receiver<StreamEvent>),
+    Sharded(Arc<ShardedBuffer>),
+}
+
+impl IngestionQueue {
+    /// Wait for events and take those queued so far, in arrival order, at most
+    /// `MAX_INGESTION_BATCH` from a channel; `None` once every producer is gone
+    fn take(&self) -> Option<Vec<StreamEvent>> {
+        match self {
+            IngestionQueue::Channel(rx) => {
+                let first = rx.recv().ok()?;
+                let mut batch = vec![first];
+                batch.extend(rx.try_iter().take(MAX_INGESTION_BATCH - 1));
+                Some(batch)
+            }
+            IngestionQueue::Sharded(buffer) => buffer.take(),
+        }
+    }
+}
+
+impl WindowSender {
+    /// Create a channel bounded to `capacity` pending containers if given, a
+    /// buffer sharded by producer thread otherwise
+    fn channel(capacity: Option<usize>) -> (Self, IngestionQueue) {
+        match capacity {
+            Some(capacity) => {
+                let (tx, rx) = mpsc::sync_channel(capacity);
+                (WindowSender::Bounded(tx), IngestionQueue::Channel(rx))
+            }
+            None => {
+                let (tx, buffer) = ShardedBuffer::new();
+                (WindowSender::Sharded(tx), IngestionQueue::Sharded(buffer))
+            }
+        }
+    }
+
+    fn send(&self, item: StreamEvent) -> Result<(), String> {
+        match self {
+            WindowSender::Unbounded(tx) => tx.send(item).map_err(|e| e.to_string()),
+            WindowSender::Bounded(tx) => tx.send(item).map_err(|e| e.to_string()),
+            WindowSender::Sharded(tx) => {
+                tx.send(item);
+                Ok(())
+            }
+            WindowSender::Inline(inline) => {
+                let applied = inline.errors.catch(|| {
+                    RSPEngine::apply_batch(
+                        &inline.window,
+                        vec![item],
+                        &inline.stream_stats,
+                        &inline.metrics,
+                        &inline.events,
+                        &inline.dead_letters,
+                    )
+                });
+                applied.map_err(|e| {
+                    // The panic left the window usable, only its lock was poisoned
+                    inline.window.clear_poison();
+                    format!("Window callback panicked: {}", e.message)
+                })
+            }
+            WindowSender::SideInput(side_input) => {
+                RSPEngine::apply_side_input(side_input, item);
+                Ok(())
+            }
+            WindowSender::Routed(routes) => {
+                let (container, stream_name) = item;
+                for (filter, sender) in routes.iter() {
+                    let quads = container
+                        .elements
+                        .iter()
+                        .filter(|quad| filter.matches(quad))
+                        .cloned()
+                        .collect();
+                    let timestamp = container.last_timestamp_changed;
+                    let mut routed = QuadContainer::from_shared(quads, timestamp);
+                    routed.received = container.received;
+                    routed.end_of_stream = container.end_of_stream;
+                    sender.send((routed, stream_name.clone()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Destination of the results of window reports
+#[derive(Clone)]
+enum ResultSink {
+    /// Bindings, also retained in the history of the query if any
+    Bindings(Arc<ResultOutput>, Option<Arc<Mutex<ResultHistory>>>),
+    /// Deltas of one window, diffed in report order by its tracker
+    Deltas(mpsc::Sender<ResultDelta>, Arc<Mutex<DeltaTracker>>),
+    /// Alerts of the rules, shared by all windows so debouncing spans them
+    Alerts(mpsc::Sender<Alert>, Arc<Mutex<Vec<AlertRule>>>),
+    /// Bindings handed to the callbacks registered with `RSPEngine::on_result`
+    Callbacks(Arc<ResultCallbacks>),
+    /// Results of another sink without those repeated by the previous reports of
+    /// one window, filtered in report order
+    Deduplicated(Box<ResultSink>, Arc<Mutex<Deduplicator<ReportTime>>>),
+    /// Reports of one window handed to another sink in close order, merged with
+    /// those of the other windows of the query
+    Merged(Arc<Mutex<Merger>>, Box<ResultSink>),
+    /// Results of another sink passing every filter
+    Filtered(Box<ResultSink>, Arc<[ResultFilter]>),
+}
+
+/// Merger of the reports of the windows, reserved with the sink of their window and
+/// its ticket
+type Merger = ResultMerger<(ResultSink, u64), MergedReport>;
+
+/// Evaluated report waiting in a merger: its solutions, time and window
+type MergedReport = (
+    Option<Vec<QuerySolution>>,
+    ReportTime,
+    Option<WindowDefinition>,
+);
+
+/// Time of a window report: the open time of its instance, when the window
+/// reported it, and when the event closing the instance was received, if any
+#[derive(Debug, Clone, Copy)]
+struct ReportTime {
+    timestamp: i64,
+    reported: Instant,
+    received: Option<Instant>,
+}
+
+impl ReportTime {
+    /// Time of a report the window is emitting
+    fn of(content: &QuadContainer) -> Self {
+        Self {
+            timestamp: content.last_timestamp_changed,
+            reported: Instant::now(),
+            received: content.received,
+        }
+    }
+}
+
+/// Close time of a window report opened at `timestamp`
+fn close_time(timestamp: i64, window_def: &Option<WindowDefinition>) -> i64 {
+    timestamp + window_def.as_ref().map_or(0, |def| def.width)
+}
+
+/// Deliver the reports released by a merger to the sinks of their windows
+fn deliver_merged(reports: Vec<((ResultSink, u64), MergedReport)>) {
+    for ((sink, ticket), (solutions, time, window_def)) in reports {
+        sink.deliver(ticket, solutions, time, &window_def);
+    }
+}
+
+impl ResultSink {
+    /// Check whether window reports still need evaluating for this sink
+    fn is_active(&self) -> bool {
+        match self {
+            ResultSink::Bindings(output, _) => output.is_running(),
+            ResultSink::Callbacks(callbacks) => !callbacks.is_empty(),
+            ResultSink::Deltas(..) | ResultSink::Alerts(..) => true,
+            ResultSink::Deduplicated(sink, _)
+            | ResultSink::Merged(_, sink)
+            | ResultSink::Filtered(sink, _) => sink.is_active(),
+        }
+    }
+
+    /// Reserve the position of a report of `window` closing at `close`, so deltas
+    /// follow the report order even when reports are evaluated in parallel
+    fn reserve(&self, window: &str, close: i64) -> u64 {
+        match self {
+            ResultSink::Bindings(..) | ResultSink::Alerts(..) | ResultSink::Callbacks(_) => 0,
+            ResultSink::Deltas(_, tracker) => tracker.lock().unwrap().reserve(),
+            ResultSink::Deduplicated(_, deduplicator) => deduplicator.lock().unwrap().reserve(),
+            ResultSink::Merged(merger, sink) => {
+                let ticket = sink.reserve(window, close);
+                let sink = sink.as_ref().clone();
+                merger
+                    .lock()
+                    .unwrap()
+                    .reserve(window, close, (sink, ticket))
+            }
+            ResultSink::Filtered(sink, _) => sink.reserve(window, close),
+        }
+    }
+
+    /// Deliver the solutions computed for one window report
+    /// `None` solutions (failed evaluation) only release the reserved position
+    fn deliver(
+        &self,
+        ticket: u64,
+        solutions: Option<Vec<QuerySolution>>,
+        time: ReportTime,
+        window_def: &Option<WindowDefinition>,
+    ) {
+        let timestamp = time.timestamp;
+        match self {
+            ResultSink::Bindings(output, history) => {
+                let (Some(solutions), Some(def)) = (solutions, window_def) else {
+                    return;
+                };
+                for result in Self::results(solutions, time, def) {
+                    if let Some(history) = history {
+                        history.lock().unwrap().push(result.clone());
+                    }
+                    output.send(result);
+                }
+            }
+            ResultSink::Callbacks(callbacks) => {
+                let (Some(solutions), Some(def)) = (solutions, window_def) else {
+                    return;
+                };
+                for result in Self::results(solutions, time, def) {
+                    callbacks.send(&result);
+                }
+            }
+            ResultSink::Deltas(tx, tracker) => {
+                let results = solutions.zip(window_def.as_ref()).map(|(solutions, def)| {
+                    let bindings = solutions.iter().map(Binding::from).collect();
+                    (bindings, (timestamp, timestamp + def.width))
+                });
+                let deltas = tracker.lock().unwrap().complete(ticket, results);
+                for delta in deltas {
+                    let _ = tx.send(delta);
+                }
+            }
+            ResultSink::Deduplicated(sink, deduplicator) => {
+                // Delivered under the lock, so the reports stay in order
+                let mut deduplicator = deduplicator.lock().unwrap();
+                let report = solutions.map(|solutions| (solutions, time));
+                for (solutions, time) in deduplicator.complete(ticket, report) {
+                    sink.deliver(0, Some(solutions), time, window_def);
+                }
+            }
+            ResultSink::Merged(merger, _) => {
+                let close = close_time(timestamp, window_def);
+                let report = (solutions, time, window_def.clone());
+                // Delivered under the lock, so the reports stay in close order
+                let mut merger = merger.lock().unwrap();
+                deliver_merged(merger.complete(ticket, close, report));
+            }
+            ResultSink::Filtered(sink, filters) => {
+                let solutions = solutions.map(|solutions| {
+                    solutions
+                        .into_iter()
+                        .filter(|solution| {
+                            let binding = Binding::from(solution);
+                            filters.iter().all(|filter| filter.matches(&binding))
+                        })
+                        .collect()
+                });
+                sink.deliver(ticket, solutions, time, window_def);
+            }
+            ResultSink::Alerts(tx, rules) => {
+                let (Some(solutions), Some(def)) = (solutions, window_def) else {
+                    return;
+                };
+                let window = (timestamp, timestamp + def.width);
+                let mut rules = rules.lock().unwrap();
+                for solution in &solutions {
+                    let binding = Binding::from(solution);
+                    for rule in rules.iter_mut() {
+                        if let Some(alert) = rule.check(&binding, window) {
+                            let _ = tx.send(alert);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turn the solutions of a window report into results, timed as they are emitted
+    fn results(
+        solutions: Vec<QuerySolution>,
+        time: ReportTime,
+        def: &WindowDefinition,
+    ) -> impl Iterator<Item = BindingWithTimestamp> {
+        let timestamp_to = time.timestamp + def.width;
+        let window = def.window_name.clone();
+        solutions
+            .into_iter()
+            .enumerate()
+            .map(move |(row, binding)| BindingWithTimestamp {
+                bindings: format!("{:?}", binding),
+                solution: Binding::from(&binding),
+                timestamp_from: time.timestamp,
+                timestamp_to,
+                evaluation_latency: Some(time.reported.elapsed()),
+                ingestion_latency: time.received.map(|received| received.elapsed()),
+                window: Some(window.clone()),
+                row,
+            })
+    }
 }
 
 /// Represents an RDF stream that feeds data into a window
 #[derive(Clone)]
 pub struct RDFStream {
-    pub name: String,
-    pub(crate) window_sender: mpsc::Sender<(QuadContainer, String)>,
+    pub name: Arc<str>,
+    pub(crate) window_sender: WindowSender,
+    pub(crate) timestamp_policy: TimestampPolicy,
+    /// Metrics of the engine owning the stream, counting queued events
+    pub(crate) metrics: Option<Arc<MetricsRecorder>>,
+    /// Gap monitors, shared by the clones of the stream
+    pub(crate) gap_monitors: Arc<Mutex<Vec<Arc<GapMonitor>>>>,
+    /// Events waiting to be coalesced, shared by the clones of the stream
+    pub(crate) micro_batch: Option<Arc<Mutex<MicroBatch>>>,
+    /// Events waiting to be sorted, shared by the clones of the stream
+    pub(crate) reorder: Option<Arc<Reorder>>,
+    /// Handles of the stream given by `RSPEngine::get_stream`, if they close its
+    /// window once dropped
+    pub(crate) handles: Option<Arc<StreamHandles>>,
+    /// Handle held by the clones of one `RSPEngine::get_stream` call
+    pub(crate) auto_close: Option<Arc<AutoClose>>,
+    /// Pause switch of the engine owning the stream
+    pub(crate) pause: Option<Arc<PauseControl>>,
+    /// Clock of the engine owning the stream, timestamping events on arrival
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Condition on the quads added, discarding the others
+    pub(crate) filter: Option<QuadFilter>,
+    /// Load shedding of the engine owning the stream, dropping events when overloaded
+    pub(crate) shedder: Option<Arc<LoadShedder>>,
+    /// Rate limits of the stream, shared by its clones
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    /// SHACL validation of the events added, if configured
+    pub(crate) validation: Option<Arc<StreamValidation>>,
+    /// Dead letters of the engine owning the stream, receiving the events it drops
+    pub(crate) dead_letters: DeadLetters,
+    /// Input log of the engine owning the stream, if it records its input
+    pub(crate) recorder: Option<Arc<InputRecorder>>,
+}
+
+/// Events of a stream sharing a timestamp, coalesced into one container
+pub(crate) struct MicroBatch {
+    max_quads: usize,
+    pending: Option<QuadContainer>,
+}
+
+impl MicroBatch {
+    pub(crate) fn new(max_quads: usize) -> Self {
+        Self {
+            max_quads: max_quads.max(1),
+            pending: None,
+        }
+    }
+
+    /// Add an event, returning the containers ready to be sent, in order
+    fn push(&mut self, container: QuadContainer) -> Vec<QuadContainer> {
+        let mut ready = Vec::new();
+        match &mut self.pending {
+            Some(pending) if pending.last_timestamp_changed == container.last_timestamp_changed => {
+                pending.merge(container);
+            }
+            _ => ready.extend(self.pending.replace(container)),
+        }
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|p| p.len() >= self.max_quads)
+        {
+            ready.extend(self.pending.take());
+        }
+        ready
+    }
+}
+
+/// Events of a stream added by several producers, held until no event more than
+/// the maximum skew older is expected, and released in timestamp order
+pub(crate) struct ReorderBuffer {
+    max_skew: i64,
+    pending: BTreeMap<i64, Vec<QuadContainer>>,
+    /// Latest timestamp added so far
+    latest: i64,
+}
+
+impl ReorderBuffer {
+    pub(crate) fn new(max_skew: i64) -> Self {
+        Self {
+            max_skew: max_skew.max(0),
+            pending: BTreeMap::new(),
+            latest: i64::MIN,
+        }
+    }
+
+    /// Add an event, returning the events ready to be sent, in order
+    fn push(&mut self, container: QuadContainer) -> Vec<QuadContainer> {
+        let timestamp = container.last_timestamp_changed;
+        self.latest = self.latest.max(timestamp);
+        self.pending.entry(timestamp).or_default().push(container);
+        // Events at least the skew older than the latest one are ready, late ones too
+        let held = self.latest.saturating_sub(self.max_skew).saturating_add(1);
+        let held = self.pending.split_off(&held);
+        std::mem::replace(&mut self.pending, held)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    /// Take every held event, in order
+    fn drain(&mut self) -> Vec<QuadContainer> {
+        let pending = std::mem::take(&mut self.pending);
+        pending.into_values().flatten().collect()
+    }
+}
+
+/// Reordering of the events of a stream, shared by its clones: the buffer, and the
+/// turns of the producers sending the events it released, in the order of release
+pub(crate) struct Reorder {
+    /// Buffer, with the number of releases so far
+    buffer: Mutex<(ReorderBuffer, u64)>,
+    /// Number of releases sent so far
+    sent: Mutex<u64>,
+    turn: Condvar,
+}
+
+impl Reorder {
+    pub(crate) fn new(max_skew: i64) -> Self {
+        Self {
+            buffer: Mutex::new((ReorderBuffer::new(max_skew), 0)),
+            sent: Mutex::new(0),
+            turn: Condvar::new(),
+        }
+    }
+
+    /// Add an event, returning the events ready to be sent with their turn, if any
+    fn push(&self, container: QuadContainer) -> Option<(u64, Vec<QuadContainer>)> {
+        let (buffer, releases) = &mut *self.buffer.lock().unwrap();
+        let released = buffer.push(container);
+        Self::release(releases, released)
+    }
+
+    /// Take every held event, with their turn to be sent, if any
+    fn drain(&self) -> Option<(u64, Vec<QuadContainer>)> {
+        let (buffer, releases) = &mut *self.buffer.lock().unwrap();
+        let released = buffer.drain();
+        Self::release(releases, released)
+    }
+
+    fn release(
+        releases: &mut u64,
+        released: Vec<QuadContainer>,
+    ) -> Option<(u64, Vec<QuadContainer>)> {
+        if released.is_empty() {
+            return None;
+        }
+        *releases += 1;
+        Some((*releases - 1, released))
+    }
+
+    /// Run `send` once the releases before `turn` are sent
+    fn in_turn<T>(&self, turn: u64, send: impl FnOnce() -> T) -> T {
+        let mut sent = self.sent.lock().unwrap();
+        while *sent != turn {
+            sent = self.turn.wait(sent).unwrap();
+        }
+        drop(sent);
+        let result = send();
+        *self.sent.lock().unwrap() += 1;
+        self.turn.notify_all();
+        result
+    }
+}
+
+/// Handles of the streams of a window given to their producers, closing the open
+/// instances of the window once the handles of all its streams are dropped
+pub(crate) struct WindowHandles {
+    window: Weak<Mutex<CSPARQLWindow>>,
+    /// Number of streams of the window whose producers didn't drop their handle,
+    /// including those never given one
+    running: Mutex<usize>,
+}
+
+impl WindowHandles {
+    pub(crate) fn new(window: Weak<Mutex<CSPARQLWindow>>, streams: usize) -> Self {
+        Self {
+            window,
+            running: Mutex::new(streams),
+        }
+    }
+}
+
+/// Handles of one stream of a window given to its producers, shared by its aliases
+pub(crate) struct StreamHandles {
+    window: Arc<WindowHandles>,
+    live: Mutex<HandleState>,
+}
+
+/// Handle of the producers of a stream, and whether they dropped the last one
+struct HandleState {
+    handle: Weak<AutoClose>,
+    dropped: bool,
+}
+
+impl StreamHandles {
+    pub(crate) fn new(window: Arc<WindowHandles>) -> Self {
+        Self {
+            window,
+            live: Mutex::new(HandleState {
+                handle: Weak::new(),
+                dropped: false,
+            }),
+        }
+    }
+
+    /// Get the handle of the producers of `stream`, created if they dropped it
+    fn acquire(&self, stream: &RDFStream) -> Arc<AutoClose> {
+        let mut live = self.live.lock().unwrap();
+        if let Some(handle) = live.handle.upgrade() {
+            return handle;
+        }
+        // Producers coming back hold the window open again
+        if live.dropped {
+            live.dropped = false;
+            *self.window.running.lock().unwrap() += 1;
+        }
+        let handle = Arc::new(AutoClose {
+            stream: stream.clone(),
+        });
+        live.handle = Arc::downgrade(&handle);
+        handle
+    }
+
+    /// Count the stream out of the running streams of its window once its last
+    /// handle is dropped, returning whether it was the last one running
+    fn release(&self) -> bool {
+        let mut live = self.live.lock().unwrap();
+        // Acquired again while this handle was being dropped
+        if live.handle.strong_count() > 0 || live.dropped {
+            return false;
+        }
+        live.dropped = true;
+        let mut running = self.window.running.lock().unwrap();
+        *running -= 1;
+        *running == 0
+    }
+}
+
+/// Handle of the producers of a stream, closing its window when dropped, once the
+/// handles of the other streams of the window are dropped too
+pub(crate) struct AutoClose {
+    stream: RDFStream,
+}
+
+impl Drop for AutoClose {
+    fn drop(&mut self) {
+        let stream = &self.stream;
+        let Some(handles) = &stream.handles else {
+            return;
+        };
+        if handles.window.window.strong_count() == 0 {
+            return;
+        }
+        // Events held by the stream go to the window either way
+        let closed = if handles.release() {
+            stream.end()
+        } else {
+            stream.flush()
+        };
+        if let Err(e) = closed {
+            warn!(stream = %stream.name, "Failed to close dropped stream: {}", e);
+        }
+    }
 }
 
 impl RDFStream {
-    pub fn new(name: String, window_sender: mpsc::Sender<(QuadContainer, String)>) -> Self {
+    pub fn new(name: impl Into<Arc<str>>, window_sender: mpsc::Sender<StreamEvent>) -> Self {
+        Self::with_sender(name, WindowSender::Unbounded(window_sender))
+    }
+
+    pub(crate) fn with_sender(name: impl Into<Arc<str>>, window_sender: WindowSender) -> Self {
         Self {
-            name,
+            name: name.into(),
             window_sender,
+            timestamp_policy: TimestampPolicy::Explicit,
+            metrics: None,
+            gap_monitors: Arc::new(Mutex::new(Vec::new())),
+            micro_batch: None,
+            reorder: None,
+            handles: None,
+            auto_close: None,
+            pause: None,
+            clock: clock::event_time(),
+            filter: None,
+            shedder: None,
+            rate_limiter: None,
+            validation: None,
+            dead_letters: DeadLetters::default(),
+            recorder: None,
         }
     }
 
+    /// Set the policy used to assign timestamps to events added to this stream
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = policy;
+        self
+    }
+
+    /// Get the timestamp policy of this stream
+    pub fn timestamp_policy(&self) -> &TimestampPolicy {
+        &self.timestamp_policy
+    }
+
+    /// Raise a gap on the returned channel whenever no event with an advancing
+    /// timestamp arrives for the threshold, e.g. to detect dead sensors
+    /// The monitor covers every clone of the stream
+    pub fn monitor_gaps(&self, threshold: GapThreshold) -> mpsc::Receiver<StreamGap> {
+        let (monitor, rx) = GapMonitor::start(&self.name, threshold);
+        self.gap_monitors.lock().unwrap().push(monitor);
+        rx
+    }
+
     /// Add a quad container to the stream
+    /// With micro-batching, events are only sent to the window once an event with
+    /// another timestamp arrives, the batch is full or the stream is flushed
     pub fn add(&self, container: QuadContainer) -> Result<(), String> {
+        if let Some(recorder) = &self.recorder {
+            let quads = container.elements.iter().map(|quad| &**quad);
+            recorder.record(&self.name, container.last_timestamp_changed, quads, true);
+        }
+        self.enqueue(container)
+    }
+
+    /// Pass a container on to the window, through reordering and micro-batching if
+    /// configured
+    fn enqueue(&self, mut container: QuadContainer) -> Result<(), String> {
+        container.received.get_or_insert_with(Instant::now);
+        for monitor in self.gap_monitors.lock().unwrap().iter() {
+            monitor.observe(container.last_timestamp_changed);
+        }
+        let Some(reorder) = &self.reorder else {
+            return self.batch(container);
+        };
+        let released = reorder.push(container);
+        self.send_released(reorder, released)
+    }
+
+    /// Pass the events released by reordering on to the window, after those released
+    /// earlier; the buffer isn't locked meanwhile, so a rate limit delaying one
+    /// producer doesn't block the others from adding events
+    fn send_released(
+        &self,
+        reorder: &Reorder,
+        released: Option<(u64, Vec<QuadContainer>)>,
+    ) -> Result<(), String> {
+        let Some((turn, containers)) = released else {
+            return Ok(());
+        };
+        reorder.in_turn(turn, || {
+            containers
+                .into_iter()
+                .try_for_each(|container| self.batch(container))
+        })
+    }
+
+    /// Pass a container on to the window, through micro-batching if configured
+    fn batch(&self, container: QuadContainer) -> Result<(), String> {
+        let Some(micro_batch) = &self.micro_batch else {
+            return self.send(container);
+        };
+        let ready = micro_batch.lock().unwrap().push(container);
+        for container in ready {
+            self.send(container)?;
+        }
+        Ok(())
+    }
+
+    /// Send the events held for reordering or coalesced by micro-batching to the window
+    pub fn flush(&self) -> Result<(), String> {
+        if let Some(reorder) = &self.reorder {
+            let released = reorder.drain();
+            self.send_released(reorder, released)?;
+        }
+        let pending = self
+            .micro_batch
+            .as_ref()
+            .and_then(|micro_batch| micro_batch.lock().unwrap().pending.take());
+        match pending {
+            Some(container) => self.send(container),
+            None => Ok(()),
+        }
+    }
+
+    /// Send the held events, then mark the end of the stream, so the window closes
+    /// the instances they open once it applied them, rather than at a closing time
+    /// computed before the events queued in its channel are applied
+    fn end(&self) -> Result<(), String> {
+        self.flush()?;
+        let mut container = QuadContainer::from_shared(HashSet::new(), i64::MIN);
+        container.end_of_stream = true;
+        self.deliver(container)
+    }
+
+    fn send(&self, container: QuadContainer) -> Result<(), String> {
+        if let Some(rate_limiter) = &self.rate_limiter
+            && !rate_limiter.admit(container.len())
+        {
+            self.dead_letter(container, DropReason::RateLimited);
+            return Ok(());
+        }
+        if let Some(shedder) = &self.shedder
+            && shedder.shed_event(container.len())
+        {
+            self.dead_letter(container, DropReason::Shed);
+            return Ok(());
+        }
+        self.deliver(container)
+    }
+
+    /// Send an event dropped before reaching the window to the dead letters of the
+    /// engine
+    fn dead_letter(&self, container: QuadContainer, reason: DropReason) {
+        self.dead_letters.send(|| DeadLetter {
+            stream: self.name.to_string(),
+            timestamp: Some(container.last_timestamp_changed),
+            quads: container
+                .elements
+                .into_iter()
+                .map(Arc::unwrap_or_clone)
+                .collect(),
+            document: None,
+            reason,
+        });
+    }
+
+    /// Send a container to the window, buffered while the engine is paused
+    fn deliver(&self, container: QuadContainer) -> Result<(), String> {
+        let item = (container, self.name.clone());
+        let admitted = match &self.pause {
+            Some(pause) => pause.admit(&self.window_sender, item)?,
+            None => Some(item),
+        };
+        // Buffered events count as queued until the engine resumes
+        if let Some(metrics) = &self.metrics {
+            metrics.record_queued();
+        }
+        let Some(item) = admitted else {
+            return Ok(());
+        };
         self.window_sender
-            .send((container, self.name.clone()))
+            .send(item)
             .map_err(|e| format!("Failed to send data to window: {}", e))
     }
 
     /// Add a set of quads with a timestamp to the stream
+    /// The stream's timestamp policy decides whether `timestamp` is used as-is
     pub fn add_quads(&self, quads: Vec<Quad>, timestamp: i64) -> Result<(), String> {
-        let mut elements = std::collections::HashSet::new();
-        for quad in quads {
-            elements.insert(quad);
+        let timestamp = self.resolve_timestamp(&quads, Some(timestamp))?;
+        self.add_timestamped(quads, timestamp)
+    }
+
+    /// Add a graph to the stream, timestamped according to the stream's timestamp policy
+    /// Fails for streams using the explicit policy, use `add_quads` for those
+    pub fn add_graph(&self, graph: &Graph) -> Result<(), String> {
+        let quads: Vec<Quad> = graph
+            .iter()
+            .map(|triple| triple.into_owned().in_graph(GraphName::DefaultGraph))
+            .collect();
+        let timestamp = self.resolve_timestamp(&quads, None)?;
+        self.add_timestamped(quads, timestamp)
+    }
+
+    /// Resolve the timestamp of an event, the wall-clock policy reading the engine's
+    /// clock when it keeps processing time
+    fn resolve_timestamp(&self, quads: &[Quad], explicit: Option<i64>) -> Result<i64, String> {
+        match (&self.timestamp_policy, self.clock.now()) {
+            (TimestampPolicy::WallClock, Some(now)) => Ok(now),
+            (policy, _) => policy.resolve(quads, explicit),
+        }
+    }
+
+    /// Add quads with a timestamp that has already been resolved
+    pub(crate) fn add_timestamped(&self, quads: Vec<Quad>, timestamp: i64) -> Result<(), String> {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&self.name, timestamp, quads.iter(), false);
         }
+        let mut quads = quads;
+        if let Some(filter) = &self.filter {
+            quads.retain(|quad| filter.matches(quad));
+        }
+        if let Some(validation) = &self.validation {
+            match validation.check(&self.name, quads, timestamp, &self.dead_letters)? {
+                Some(valid) => quads = valid,
+                // Quarantined
+                None => return Ok(()),
+            }
+        }
+        let elements = quads.into_iter().collect();
         let container = QuadContainer::new(elements, timestamp);
-        self.add(container)
+        self.enqueue(container)
+    }
+
+    /// Add an RDF document (Turtle, N-Triples, N-Quads, TriG...) describing one event
+    /// to the stream, timestamped like `add_quads`
+    /// Triple terms and annotations of Turtle 1.2 (Turtle-star) need the `rdf-star` feature
+    pub fn add_rdf(&self, document: &str, format: RdfFormat, timestamp: i64) -> Result<(), String> {
+        let quads = parse_rdf(document, format)
+            .inspect_err(|e| self.unparsable(document, Some(timestamp), e))?;
+        self.add_quads(quads, timestamp)
+    }
+
+    /// Add a JSON-LD document describing one event to the stream
+    /// The event timestamp is read from the document using the given extractor
+    pub fn add_jsonld(&self, document: &str, extractor: &TimestampExtractor) -> Result<(), String> {
+        let event = parse_jsonld(document)
+            .and_then(|quads| Ok((extractor.extract(&quads)?, quads)))
+            .inspect_err(|e| self.unparsable(document, None, e));
+        let (timestamp, quads) = event?;
+        self.add_quads(quads, timestamp)
+    }
+
+    /// Send a serialized event that failed to parse, or to yield a timestamp, to the
+    /// dead letters of the engine
+    pub(crate) fn unparsable(&self, document: &str, timestamp: Option<i64>, error: &str) {
+        self.dead_letters.send(|| DeadLetter {
+            stream: self.name.to_string(),
+            timestamp,
+            quads: Vec::new(),
+            document: Some(document.to_string()),
+            reason: DropReason::Unparsable(error.to_string()),
+        });
     }
 }
 
@@ -51,75 +947,873 @@ impl RDFStream {
 pub struct RSPEngine {
     windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
     streams: HashMap<String, RDFStream>,
-    r2r: R2ROperator,
+    /// Shared with the window callbacks, so static data changes reach running queries
+    r2r: Arc<RwLock<R2ROperator>>,
     parsed_query: crate::parsed_query::ParsedQuery,
+    config: EngineConfig,
+    stream_stats: Arc<Mutex<HashMap<Arc<str>, StreamStats>>>,
+    /// Shared with the side-input streams, which notify them of their changes
+    static_data_callbacks: Arc<RwLock<Vec<StaticDataCallback>>>,
+    /// Latest results of the query, if retention is enabled
+    history: Option<Arc<Mutex<ResultHistory>>>,
+    metrics: Arc<MetricsRecorder>,
+    /// Results collected in synchronous mode for `poll_results`
+    sync_results: Option<Mutex<mpsc::Receiver<BindingWithTimestamp>>>,
+    /// Results requested by `start_processing` before the windows were created
+    pending_processing: Mutex<Vec<ResultSink>>,
+    /// Callbacks registered with `on_result`, fed by a sink registered on `initialize`
+    result_callbacks: Arc<ResultCallbacks>,
+    pause: Arc<PauseControl>,
+    /// Load shedding, if configured
+    shedder: Option<Arc<LoadShedder>>,
+    /// Mergers of the window reports, told about closed streams
+    result_mergers: Mutex<Vec<Arc<Mutex<Merger>>>>,
+    /// Streams closed by `close_stream`
+    closed_streams: Mutex<HashSet<String>>,
+    /// Pool shared with other engines, see `share_evaluation_pool`
+    shared_pool: Option<EvaluationPool>,
+    /// Panics caught in the threads of the engine, see `errors`
+    errors: ErrorChannel,
+    /// Non-result events of the engine, see `subscribe_events`
+    events: EventPublisher,
+    /// Events dropped by the streams and windows, see `dead_letters`
+    dead_letters: DeadLetters,
+    /// Log of the events added to the streams, if recording, opened by `initialize`
+    recorder: Option<Arc<InputRecorder>>,
+    /// Windows shared with the engines of other queries, kept alive by each of them
+    shared_windows: Vec<Arc<SharedWindow>>,
+    /// Unsupported window setting of the query, reported by `initialize`
+    query_error: Option<String>,
 }
 
 impl RSPEngine {
     /// Create a new RSP Engine from an RSPQL query
     pub fn new(query: String) -> Self {
-        let parser = RSPQLParser::new(query);
+        Self::with_config(query, EngineConfig::default())
+    }
+
+    /// Start building an RSP Engine with a non-default configuration
+    pub fn builder(query: String) -> RSPEngineBuilder {
+        RSPEngineBuilder::new(query)
+    }
+
+    /// Create a new RSP Engine from an RSPQL query and a configuration
+    pub fn with_config(query: String, config: EngineConfig) -> Self {
+        let parser = RSPQLParser::new(query).with_dialect(config.dialect);
         let parsed_query = parser.parse();
 
-        #[cfg(debug_assertions)]
-        {
-            println!("[RSPEngine] Parsed SPARQL query:");
-            println!("{}", parsed_query.sparql_query);
-            println!();
+        debug!(query = %parsed_query.sparql_query, "Parsed RSP-QL query");
+        let query_error = parser.check_window_settings().err();
+        Self {
+            query_error,
+            ..Self::from_parsed_query(parsed_query, config)
         }
+    }
 
+    /// Create a new RSP Engine from a query built or deserialized without RSP-QL
+    /// text, see `ParsedQuery::builder`, and a configuration
+    pub fn from_parsed_query(
+        parsed_query: crate::parsed_query::ParsedQuery,
+        config: EngineConfig,
+    ) -> Self {
         let windows = HashMap::new();
         let streams = HashMap::new();
-        let r2r = R2ROperator::new(parsed_query.sparql_query.clone());
+        let mut r2r = R2ROperator::with_service_client(
+            parsed_query.sparql_query.clone(),
+            ServiceClient::new(config.service_timeout, config.service_cache_ttl),
+        );
+        r2r.set_default_graphs(
+            parsed_query
+                .default_graphs
+                .iter()
+                .filter_map(|graph| NamedNode::new(graph).ok()),
+        );
+        r2r.set_annotate_timestamps(config.annotate_timestamps);
+        r2r.set_annotate_streams(config.annotate_streams);
+        r2r.set_join_reordering(config.join_reordering);
+
+        let history = (config.retained_results.is_some() || config.retained_results_ms.is_some())
+            .then(|| {
+                Arc::new(Mutex::new(ResultHistory::new(
+                    config.retained_results,
+                    config.retained_results_ms,
+                )))
+            });
+        let pause = Arc::new(PauseControl::new(config.pause_policy));
+        let metrics = Arc::new(MetricsRecorder::default());
+        let events = EventPublisher::new();
+        let shedder = config
+            .load_shedding
+            .map(|policy| Arc::new(LoadShedder::new(policy, metrics.clone())));
 
         Self {
             windows,
             streams,
-            r2r,
+            r2r: Arc::new(RwLock::new(r2r)),
             parsed_query,
+            config,
+            stream_stats: Arc::new(Mutex::new(HashMap::new())),
+            static_data_callbacks: Arc::new(RwLock::new(Vec::new())),
+            history,
+            metrics,
+            sync_results: None,
+            pending_processing: Mutex::new(Vec::new()),
+            result_callbacks: Arc::new(ResultCallbacks::default()),
+            pause,
+            shedder,
+            result_mergers: Mutex::new(Vec::new()),
+            closed_streams: Mutex::new(HashSet::new()),
+            shared_pool: None,
+            errors: ErrorChannel::new(events.clone()),
+            events,
+            dead_letters: DeadLetters::default(),
+            recorder: None,
+            shared_windows: Vec::new(),
+            query_error: None,
         }
     }
 
     /// Initialize the engine by creating windows and streams
+    ///
+    /// Static graphs of the query (`FROM <g>`, `FROM NAMED <g>`) are read from the
+    /// static data, in the graph of the same IRI; those missing from it are fetched
+    /// over HTTP unless dereferencing is disabled, and a failed fetch is an error
+    /// With an on-disk static store, fetched graphs are kept in it for later runs
     pub fn initialize(&mut self) -> Result<(), String> {
+        if let Some(error) = &self.query_error {
+            return Err(error.clone());
+        }
+        self.publish_parse_warnings();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.config.static_store_path {
+            self.r2r.write().unwrap().open_store(path)?;
+        }
+        if self.config.dereference_static_graphs {
+            self.load_static_graphs()?;
+        }
+        if let Some(path) = &self.config.input_log_path {
+            self.recorder = Some(Arc::new(InputRecorder::open(path)?));
+        }
+        if let Some(max_solutions) = self.config.static_pattern_cache {
+            let windows = self
+                .parsed_query
+                .s2r
+                .iter()
+                .filter_map(|window| NamedNode::new(&window.window_name).ok());
+            self.r2r
+                .write()
+                .unwrap()
+                .cache_static_patterns(windows, max_solutions);
+        }
+        if let Some(sharing) = &self.config.plan_sharing {
+            let patterns = sharing.patterns();
+            self.r2r.write().unwrap().share_window_patterns(patterns);
+        }
+
+        let aggregation_plan = self.aggregation_plan();
+        let static_data = if self.config.window_lookups.is_empty() {
+            Vec::new()
+        } else {
+            self.r2r.read().unwrap().static_quads()
+        };
+        let mut lookup_tables = Vec::new();
+
         // Create windows and streams based on parsed query
+        let mut senders = HashMap::new();
         for window_def in &self.parsed_query.s2r {
-            let (tx, rx) = mpsc::channel::<(QuadContainer, String)>();
+            // Builder overrides take precedence over the query, then the defaults
+            let report = self
+                .config
+                .window_report_strategies
+                .get(&window_def.window_name)
+                .copied()
+                .or(window_def.report)
+                .unwrap_or(self.config.report_strategy);
+            let tick = self
+                .config
+                .window_ticks
+                .get(&window_def.window_name)
+                .copied()
+                .or(window_def.tick)
+                .unwrap_or(self.config.tick);
+            if !report.is_supported() || tick != Tick::TimeDriven {
+                return Err(format!(
+                    "Window {} reports {:?} on a {:?} tick: only NonEmptyContent and OnWindowClose reports on a TimeDriven tick are implemented",
+                    window_def.window_name, report, tick
+                ));
+            }
+            let origin = self
+                .config
+                .window_origins
+                .get(&window_def.window_name)
+                .copied()
+                .or(window_def.origin)
+                .or(self.config.origin);
+            let calendar = self.config.window_calendars.get(&window_def.window_name);
+            let calendar = match (calendar, &window_def.calendar) {
+                (None, Some(zone)) => Some(TimeZone::parse(zone)?),
+                (calendar, _) => calendar.cloned(),
+            };
+            let tumbling = window_def.width == window_def.slide
+                && report == ReportStrategy::OnWindowClose
+                && !window_def.landmark;
+            if calendar.is_some() && !tumbling {
+                return Err(format!(
+                    "Cannot align window {} on a calendar: it must be a tumbling window \
+                     (RANGE equal to STEP, not UNBOUNDED) reporting ON_WINDOW_CLOSE",
+                    window_def.window_name
+                ));
+            }
+            let emit_empty_windows = self.emit_empty_windows(window_def);
+            let delay = self
+                .config
+                .window_delays
+                .get(&window_def.window_name)
+                .copied()
+                .or(window_def.delay)
+                .unwrap_or(0);
+            let retention = self
+                .config
+                .window_retentions
+                .get(&window_def.window_name)
+                .copied()
+                .or(window_def.retention);
+            let partition_key = self.partition_key(&self.parsed_query.sparql_query, window_def)?;
+
+            let partitioned = partition_key.is_some();
+            let filter = self.config.window_filters.get(&window_def.window_name);
+            let lookup = self.config.window_lookups.get(&window_def.window_name);
+            let multiset = self
+                .config
+                .multiset_windows
+                .contains(&window_def.window_name);
+            let ttl = self.config.window_ttls.get(&window_def.window_name);
+            let aggregated = aggregation_plan
+                .as_ref()
+                .is_some_and(|plan| plan.graph().as_str() == window_def.window_name);
+
+            // A window set up identically by an engine sharing plans with this one is
+            // reused as it is, with its ingestion
+            let shareable = !partitioned && filter.is_none() && lookup.is_none() && !aggregated;
+            let sharing = self.config.plan_sharing.as_ref().filter(|_| shareable);
+            let spec = sharing.map(|_| WindowSpec {
+                definition: window_def.clone(),
+                report,
+                tick,
+                start_time: self.config.start_time,
+                origin,
+                calendar: calendar.clone(),
+                emit_empty_windows,
+                delay,
+                max_lateness: self.config.max_lateness,
+                memory_limit: self.config.max_window_memory_bytes,
+                pane_aggregation: self.config.pane_aggregation,
+                record_streams: self.config.annotate_streams,
+                multiset,
+                ttl: ttl.cloned(),
+                synchronous: self.config.synchronous,
+                channel_capacity: self.config.channel_capacity,
+            });
+            let shared = sharing
+                .zip(spec.as_ref())
+                .and_then(|(sharing, spec)| sharing.find(spec));
 
             // Create window with full parameters
-            let window = Arc::new(Mutex::new(CSPARQLWindow::new(
-                window_def.window_name.clone(),
-                window_def.width,
-                window_def.slide,
-                crate::ReportStrategy::OnWindowClose,
-                crate::Tick::TimeDriven,
-                0,
-            )));
-
-            // Create stream
-            let stream = RDFStream::new(window_def.stream_name.clone(), tx);
-
-            // Store window and stream
+            let window = match &shared {
+                Some(shared) => shared.window.clone(),
+                None => Arc::new(Mutex::new(CSPARQLWindow::new(
+                    window_def.window_name.clone(),
+                    window_def.width,
+                    window_def.slide,
+                    report,
+                    tick,
+                    self.config.start_time,
+                ))),
+            };
+            if shared.is_none() {
+                let mut window = window.lock().unwrap();
+                window.set_max_lateness(self.config.max_lateness);
+                window.set_origin(origin);
+                window.set_calendar(calendar);
+                window.set_partition_key(partition_key);
+                if let Some(limit) = self.config.max_window_memory_bytes {
+                    window.set_memory_limit(Some(limit));
+                }
+                if window_def.latest_value {
+                    window.add_eviction_policy(LatestValue);
+                }
+                if let Some(ttl) = ttl {
+                    window.add_eviction_policy(ttl.clone());
+                }
+                window.set_emit_empty_windows(emit_empty_windows);
+                window.set_delay(delay);
+                window.set_landmark(window_def.landmark);
+                window.set_pane_aggregation(self.config.pane_aggregation);
+                window.set_retention(retention.filter(|_| window_def.landmark));
+                window.set_record_streams(self.config.annotate_streams);
+                window.set_multiset(multiset);
+                window.set_quad_filter(filter.cloned());
+                let table = lookup.map(|lookup| Arc::new(LookupTable::new(lookup, &static_data)));
+                lookup_tables.extend(table.as_ref().map(Arc::downgrade));
+                window.set_lookup_table(table);
+            }
+            let events = self.events.clone();
+            let name = window_def.window_name.clone();
+            window.lock().unwrap().subscribe_lifecycle(move |instance| {
+                if instance.state() == WindowState::Evicted {
+                    events.publish(|| EngineEvent::WindowEvicted {
+                        window: name.clone(),
+                        open: instance.open,
+                        close: instance.close,
+                    });
+                }
+            });
+            if let Some(plan) = &aggregation_plan {
+                if plan.graph().as_str() == window_def.window_name {
+                    window.lock().unwrap().set_aggregation_plan(plan.clone());
+                }
+            }
+            let incremental = window.lock().unwrap().aggregation_plan().is_some();
+            if window_def.landmark && retention.is_some() && !incremental {
+                warn!(
+                    window = %window_def.window_name,
+                    "Landmark window evaluated with SPARQL: results only cover the retained quads"
+                );
+            }
+
+            // In synchronous mode the streams apply events themselves, otherwise a
+            // thread moves them from the channel into the window
+            let tx = if let Some(shared) = &shared {
+                shared.sender.clone()
+            } else if self.config.synchronous {
+                WindowSender::Inline(Arc::new(InlineIngestion {
+                    window: window.clone(),
+                    stream_stats: self.stream_stats.clone(),
+                    metrics: self.metrics.clone(),
+                    errors: self.errors.clone(),
+                    events: self.events.clone(),
+                    dead_letters: self.dead_letters.clone(),
+                }))
+            } else {
+                let (tx, queue) = WindowSender::channel(self.config.channel_capacity);
+                let window_clone = window.clone();
+                let stats = self.stream_stats.clone();
+                let metrics = self.metrics.clone();
+                let errors = self.errors.clone();
+                let events = self.events.clone();
+                let dead_letters = self.dead_letters.clone();
+                self.spawn(&format!("window-{}", window_def.window_name), move || {
+                    Self::ingest(
+                        queue,
+                        window_clone,
+                        stats,
+                        metrics,
+                        errors,
+                        events,
+                        dead_letters,
+                    )
+                });
+                tx
+            };
+            if let (Some(sharing), Some(spec)) = (sharing, spec) {
+                let shared = shared.unwrap_or_else(|| {
+                    let window = window.clone();
+                    let sender = tx.clone();
+                    sharing.register(spec, SharedWindow { window, sender })
+                });
+                self.shared_windows.push(shared);
+            }
+
+            // Store window and its streams, which all feed the same channel
             self.windows
                 .insert(window_def.window_name.clone(), window.clone());
-            self.streams.insert(window_def.stream_name.clone(), stream);
-
-            // Spawn thread to handle incoming data
-            let window_clone = window.clone();
-            thread::spawn(move || {
-                while let Ok((container, _stream_name)) = rx.recv() {
-                    let mut win = window_clone.lock().unwrap();
-                    // Add all quads from the container to the window
-                    for quad in &container.elements {
-                        win.add(quad.clone(), container.last_timestamp_changed);
+            senders.insert(window_def.window_name.clone(), tx.clone());
+            // Streams without an idle timeout of their own use the shortest one of
+            // the window, which only closes once all its streams are idle
+            let idle_timeouts = &self.config.stream_idle_timeouts;
+            let idle_timeout = window_def
+                .stream_names()
+                .filter_map(|stream_name| idle_timeouts.get(stream_name))
+                .min()
+                .copied();
+            let (idle_tx, idle_rx) = mpsc::channel();
+            let (mut idle_monitors, mut idle_streams) = (Vec::new(), Vec::new());
+            let handles = self.config.auto_close_streams.then(|| {
+                let streams = window_def.stream_names().collect::<HashSet<_>>().len();
+                Arc::new(WindowHandles::new(Arc::downgrade(&window), streams))
+            });
+            for stream_name in window_def.stream_names() {
+                let mut stream = RDFStream::with_sender(stream_name, tx.clone());
+                stream.metrics = Some(self.metrics.clone());
+                stream.pause = Some(self.pause.clone());
+                stream.clock = self.config.clock.clone();
+                stream.filter = self.config.stream_filters.get(stream_name).cloned();
+                stream.shedder = self.shedder.clone();
+                stream.rate_limiter = self.rate_limiter(stream_name);
+                stream.validation = self.validation(stream_name);
+                stream.dead_letters = self.dead_letters.clone();
+                stream.recorder = self.recorder.clone();
+                if let Some(max_quads) = self.config.stream_micro_batches.get(stream_name) {
+                    // Events of different keys sharing a timestamp must stay apart
+                    if partitioned {
+                        warn!(
+                            stream = stream_name,
+                            "Micro-batching ignored for partitioned windows"
+                        );
+                    } else {
+                        let micro_batch = MicroBatch::new(*max_quads);
+                        stream.micro_batch = Some(Arc::new(Mutex::new(micro_batch)));
                     }
                 }
+                if let Some(max_skew) = self.config.stream_max_skews.get(stream_name) {
+                    stream.reorder = Some(Arc::new(Reorder::new(*max_skew)));
+                }
+                if let Some(handles) = &handles {
+                    stream.handles = Some(Arc::new(StreamHandles::new(handles.clone())));
+                }
+                if let Some(shortest) = idle_timeout {
+                    let timeout = idle_timeouts.get(stream_name).copied().unwrap_or(shortest);
+                    let threshold = GapThreshold::WallClock(timeout);
+                    let monitor = GapMonitor::with_sender(stream_name, threshold, idle_tx.clone());
+                    idle_monitors.push(Arc::downgrade(&monitor));
+                    stream.gap_monitors.lock().unwrap().push(monitor);
+                    // Its own monitors would keep the stream's monitor alive
+                    let mut idle_stream = stream.clone();
+                    idle_stream.gap_monitors = Arc::new(Mutex::new(Vec::new()));
+                    idle_streams.push(idle_stream);
+                }
+                self.stream_stats
+                    .lock()
+                    .unwrap()
+                    .insert(stream.name.clone(), StreamStats::default());
+                self.streams.insert(stream_name.to_string(), stream);
+            }
+            drop(idle_tx);
+            if !idle_streams.is_empty() {
+                let window = Arc::downgrade(&window);
+                self.spawn(&format!("idle-{}", window_def.window_name), move || {
+                    Self::close_when_idle(idle_rx, idle_monitors, idle_streams, window)
+                });
+            }
+        }
+
+        // Lookup tables follow the static data, whichever way it changes
+        if !lookup_tables.is_empty() {
+            let r2r = self.r2r.clone();
+            self.on_static_data_change(move |_| {
+                let static_data = r2r.read().unwrap().static_quads();
+                for table in lookup_tables.iter().filter_map(Weak::upgrade) {
+                    table.rebuild(&static_data);
+                }
             });
         }
 
+        // Side-input streams update the static data instead of a window
+        let side_input = Arc::new(SideInput {
+            r2r: self.r2r.clone(),
+            callbacks: self.static_data_callbacks.clone(),
+            stream_stats: self.stream_stats.clone(),
+        });
+        for stream_name in &self.config.side_input_streams {
+            if self.streams.contains_key(stream_name) {
+                return Err(format!(
+                    "Stream {} feeds a window, it cannot be a side input",
+                    stream_name
+                ));
+            }
+            let sender = WindowSender::SideInput(side_input.clone());
+            let mut stream = RDFStream::with_sender(stream_name.as_str(), sender);
+            stream.clock = self.config.clock.clone();
+            stream.filter = self.config.stream_filters.get(stream_name).cloned();
+            stream.rate_limiter = self.rate_limiter(stream_name);
+            stream.validation = self.validation(stream_name);
+            stream.dead_letters = self.dead_letters.clone();
+            stream.recorder = self.recorder.clone();
+            self.stream_stats
+                .lock()
+                .unwrap()
+                .insert(stream.name.clone(), StreamStats::default());
+            self.streams.insert(stream_name.clone(), stream);
+        }
+
+        // Routed feeds dispatch their quads to the channels of several windows
+        for (stream_name, router) in &self.config.stream_routes {
+            if self.streams.contains_key(stream_name) {
+                return Err(format!(
+                    "Stream {} is already declared, it cannot be routed",
+                    stream_name
+                ));
+            }
+            let mut routes = Vec::new();
+            for (window_name, filter) in router.routes() {
+                let sender = senders.get(window_name).ok_or_else(|| {
+                    format!(
+                        "Stream {} is routed to unknown window {}",
+                        stream_name, window_name
+                    )
+                })?;
+                routes.push((filter.clone(), sender.clone()));
+            }
+            let sender = WindowSender::Routed(routes.into());
+            let mut stream = RDFStream::with_sender(stream_name.as_str(), sender);
+            if let Some(max_skew) = self.config.stream_max_skews.get(stream_name) {
+                stream.reorder = Some(Arc::new(Reorder::new(*max_skew)));
+            }
+            stream.pause = Some(self.pause.clone());
+            stream.clock = self.config.clock.clone();
+            stream.filter = self.config.stream_filters.get(stream_name).cloned();
+            stream.shedder = self.shedder.clone();
+            stream.rate_limiter = self.rate_limiter(stream_name);
+            stream.validation = self.validation(stream_name);
+            stream.dead_letters = self.dead_letters.clone();
+            stream.recorder = self.recorder.clone();
+            self.stream_stats
+                .lock()
+                .unwrap()
+                .insert(stream.name.clone(), StreamStats::default());
+            self.streams.insert(stream_name.clone(), stream);
+        }
+
+        if self.config.synchronous {
+            let (tx, rx) = mpsc::channel();
+            let output = Arc::new(ResultOutput::new(tx));
+            Self::register_sink(
+                self.windows.clone(),
+                self.r2r.clone(),
+                self.parsed_query.s2r.clone(),
+                EvaluationPool::inline(),
+                self.metrics.clone(),
+                self.shedder.clone(),
+                self.filtered(self.merged(
+                    self.deduplicated(|| {
+                        ResultSink::Bindings(output.clone(), self.history.clone())
+                    }),
+                )),
+            );
+            self.sync_results = Some(Mutex::new(rx));
+        } else if self.config.clock.now().is_some() {
+            let windows = self.windows.values().map(Arc::downgrade).collect();
+            let clock = self.config.clock.clone();
+            let pause = self.pause.clone();
+            let interval = self.config.clock_interval;
+            let errors = self.errors.clone();
+            let events = self.events.clone();
+            self.spawn("clock", move || {
+                Self::follow_clock(windows, clock, pause, interval, errors, events)
+            });
+        }
+
+        let mut pending = std::mem::take(&mut *self.pending_processing.lock().unwrap());
+        // Reports are only evaluated for it while callbacks are registered
+        pending.push(ResultSink::Callbacks(self.result_callbacks.clone()));
+        for sink in pending {
+            Self::register_sink(
+                self.windows.clone(),
+                self.r2r.clone(),
+                self.parsed_query.s2r.clone(),
+                self.evaluation_pool(),
+                self.metrics.clone(),
+                self.shedder.clone(),
+                self.filtered(self.merged(self.deduplicated(|| sink.clone()))),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Close the open instances of a window whenever all its streams are idle, by
+    /// sending an event without quads after them, until the streams are dropped
+    fn close_when_idle(
+        gaps: mpsc::Receiver<StreamGap>,
+        monitors: Vec<Weak<GapMonitor>>,
+        streams: Vec<RDFStream>,
+        window: Weak<Mutex<CSPARQLWindow>>,
+    ) {
+        let [stream, others @ ..] = streams.as_slice() else {
+            return;
+        };
+        for _ in gaps {
+            // Another stream may still be feeding the window
+            let silent = monitors
+                .iter()
+                .all(|monitor| monitor.upgrade().is_none_or(|monitor| monitor.is_silent()));
+            if !silent {
+                continue;
+            }
+            let Some(window) = window.upgrade() else {
+                return;
+            };
+            let Some(watermark) = window.lock().unwrap().closing_time() else {
+                continue;
+            };
+            debug!(stream = %stream.name, watermark, "Closing the window of idle streams");
+            // Queued behind the streams' pending events, so they are applied first
+            let closed = others
+                .iter()
+                .try_for_each(RDFStream::flush)
+                .and_then(|_| stream.add_timestamped(Vec::new(), watermark))
+                .and_then(|_| stream.flush());
+            if let Err(e) = closed {
+                warn!(stream = %stream.name, "Failed to close idle stream: {}", e);
+            }
+        }
+    }
+
+    /// Advance the windows to the time of a processing-time clock every `interval`,
+    /// until the engine is dropped
+    fn follow_clock(
+        windows: Vec<Weak<Mutex<CSPARQLWindow>>>,
+        clock: Arc<dyn Clock>,
+        pause: Arc<PauseControl>,
+        interval: Duration,
+        errors: ErrorChannel,
+        events: EventPublisher,
+    ) {
+        loop {
+            thread::sleep(interval);
+            let windows: Vec<_> = windows.iter().filter_map(Weak::upgrade).collect();
+            if windows.is_empty() {
+                return;
+            }
+            let Some(now) = clock.now() else {
+                return;
+            };
+            if !pause.is_paused() {
+                for window in &windows {
+                    // A panicking callback doesn't stop the clock of the other windows
+                    let advanced = errors.catch(|| {
+                        let mut window = window.lock().unwrap();
+                        let watermark = window.time;
+                        window.advance_to(now);
+                        Self::publish_watermark(&events, &window, watermark);
+                    });
+                    if advanced.is_err() {
+                        window.clear_poison();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publish the time of the latest report of a window, if it moved past `previous`
+    fn publish_watermark(events: &EventPublisher, window: &CSPARQLWindow, previous: i64) {
+        if window.time > previous {
+            events.publish(|| EngineEvent::WatermarkAdvanced {
+                window: window.name.clone(),
+                watermark: window.time,
+            });
+        }
+    }
+
+    /// Publish the parts of the query the engine can't run as written, as parse
+    /// warnings
+    fn publish_parse_warnings(&self) {
+        if let Some(error) = self.r2r.read().unwrap().parse_error() {
+            warn!("Query does not parse as SPARQL: {}", error);
+            self.events.publish(|| {
+                EngineEvent::ParseWarning(format!("Query does not parse as SPARQL: {}", error))
+            });
+        }
+        if self.parsed_query.s2r.is_empty() {
+            warn!("Query has no windows, it will never report");
+            self.events.publish(|| {
+                EngineEvent::ParseWarning("Query has no windows, it will never report".to_string())
+            });
+        }
+    }
+
+    /// Get the rate limits of a stream, if configured
+    fn rate_limiter(&self, stream_name: &str) -> Option<Arc<RateLimiter>> {
+        let limit = self.config.stream_rate_limits.get(stream_name)?;
+        Some(Arc::new(RateLimiter::new(*limit, self.metrics.clone())))
+    }
+
+    /// Get the SHACL validation of a stream, if configured
+    fn validation(&self, stream_name: &str) -> Option<Arc<StreamValidation>> {
+        let (shapes, policy) = self.config.stream_shapes.get(stream_name)?;
+        Some(Arc::new(StreamValidation {
+            shapes: shapes.clone(),
+            policy: *policy,
+        }))
+    }
+
+    /// Spawn a thread of the engine, named after the prefix of the configuration
+    fn spawn(&self, name: &str, f: impl FnOnce() + Send + 'static) {
+        spawn_named(&self.config.thread_name_prefix, name, f);
+    }
+
+    /// Fetch the static graphs of the query that aren't in the static data yet
+    fn load_static_graphs(&mut self) -> Result<(), String> {
+        let missing: Vec<NamedNode> = {
+            let r2r = self.r2r.read().unwrap();
+            self.parsed_query
+                .static_graphs()
+                .filter_map(|graph| NamedNode::new(graph).ok())
+                .filter(|graph| !r2r.contains_graph(graph))
+                .collect()
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let agent = ureq::AgentBuilder::new()
+            .timeout(self.config.service_timeout)
+            .build();
+        let mut quads = Vec::new();
+        for graph in &missing {
+            quads.extend(static_graphs::dereference(&agent, graph)?);
+        }
+        self.update_static_data(|r2r| {
+            for quad in quads {
+                r2r.add_static_data(quad);
+            }
+        });
         Ok(())
     }
 
+    /// Move containers from the queue of a window's streams into the window
+    ///
+    /// Producers never touch the window mutex, they only push onto the queue: the
+    /// shard of their thread, or a bounded channel. Every container queued since
+    /// the last wake-up is applied in arrival order, in batches of at most
+    /// `MAX_INGESTION_BATCH` under a single window lock, so the window is locked
+    /// once per batch rather than once per container; containers arriving out of
+    /// order are handled by the window as if added one by one.
+    ///
+    /// A panic while applying a batch, e.g. in a window callback, is reported to the
+    /// errors of the engine and the loop restarts with the next batch, so the stream
+    /// doesn't stall; the rest of the failed batch is lost.
+    fn ingest(
+        queue: IngestionQueue,
+        window: Arc<Mutex<CSPARQLWindow>>,
+        stream_stats: Arc<Mutex<HashMap<Arc<str>, StreamStats>>>,
+        metrics: Arc<MetricsRecorder>,
+        errors: ErrorChannel,
+        events: EventPublisher,
+        dead_letters: DeadLetters,
+    ) {
+        loop {
+            let ingested = errors.catch(|| {
+                while let Some(mut queued) = queue.take() {
+                    while !queued.is_empty() {
+                        let size = queued.len().min(MAX_INGESTION_BATCH);
+                        let batch = queued.drain(..size).collect();
+                        Self::apply_batch(
+                            &window,
+                            batch,
+                            &stream_stats,
+                            &metrics,
+                            &events,
+                            &dead_letters,
+                        );
+                    }
+                }
+            });
+            if ingested.is_ok() {
+                return;
+            }
+            window.clear_poison();
+        }
+    }
+
+    /// Apply a batch of containers to a window, in arrival order, under one lock
+    fn apply_batch(
+        window: &Mutex<CSPARQLWindow>,
+        batch: Vec<StreamEvent>,
+        stream_stats: &Mutex<HashMap<Arc<str>, StreamStats>>,
+        metrics: &MetricsRecorder,
+        events: &EventPublisher,
+        dead_letters: &DeadLetters,
+    ) {
+        metrics.record_ingested(batch.len());
+        let mut win = window.lock().unwrap();
+        let watermark = win.time;
+
+        // Statistics are recorded against the watermark before the batch
+        {
+            let now = wall_clock_millis();
+            let mut stats = stream_stats.lock().unwrap();
+            let batch = batch
+                .iter()
+                .filter(|(container, _)| !container.end_of_stream);
+            for (container, stream_name) in batch {
+                stats.entry(stream_name.clone()).or_default().record(
+                    container.last_timestamp_changed,
+                    win.time,
+                    now,
+                );
+            }
+        }
+
+        for (mut container, stream_name) in batch {
+            if container.end_of_stream {
+                // After the events queued before it, so it closes the instances they open
+                let Some(closing_time) = win.closing_time() else {
+                    continue;
+                };
+                debug!(
+                    window = %win.name,
+                    stream = %stream_name,
+                    watermark = closing_time,
+                    "Closing the window of a dropped stream"
+                );
+                container.last_timestamp_changed = closing_time;
+            }
+            if !win.accepts(container.last_timestamp_changed) {
+                debug!(
+                    window = %win.name,
+                    stream = %stream_name,
+                    timestamp = container.last_timestamp_changed,
+                    "Dropping late event"
+                );
+                if let Some(stats) = stream_stats.lock().unwrap().get_mut(&stream_name) {
+                    stats.dropped_events += 1;
+                }
+                events.publish(|| EngineEvent::LateEventDropped {
+                    window: win.name.clone(),
+                    stream: stream_name.to_string(),
+                    timestamp: container.last_timestamp_changed,
+                });
+                let timestamp = container.last_timestamp_changed;
+                let quads = container.elements;
+                dead_letters.send(|| DeadLetter {
+                    stream: stream_name.to_string(),
+                    timestamp: Some(timestamp),
+                    quads: quads.into_iter().map(Arc::unwrap_or_clone).collect(),
+                    document: None,
+                    reason: DropReason::Late {
+                        window: win.name.clone(),
+                        watermark: win.time,
+                    },
+                });
+                continue;
+            }
+            win.add_stream_container(container, &stream_name);
+        }
+        Self::publish_watermark(events, &win, watermark);
+    }
+
+    /// Upsert the quads of an event of a side-input stream into the static data
+    fn apply_side_input(side_input: &SideInput, event: StreamEvent) {
+        let (container, stream_name) = event;
+        if container.end_of_stream {
+            return;
+        }
+        // No window reports, so no event is late
+        let now = wall_clock_millis();
+        side_input
+            .stream_stats
+            .lock()
+            .unwrap()
+            .entry(stream_name)
+            .or_default()
+            .record(container.last_timestamp_changed, i64::MIN, now);
+        let quads = container.elements.into_iter().map(Arc::unwrap_or_clone);
+        Self::change_static_data(&side_input.r2r, &side_input.callbacks, |r2r| {
+            r2r.upsert_static_subjects(quads)
+        });
+    }
+
     /// Register a callback for processing window content
     /// Returns a receiver for binding results
     pub fn register(
@@ -127,82 +1821,922 @@ impl RSPEngine {
         r2r: R2ROperator,
         window_defs: Vec<WindowDefinition>,
     ) -> mpsc::Receiver<BindingWithTimestamp> {
+        Self::register_with_pool(windows, r2r, window_defs, EvaluationPool::default())
+    }
+
+    /// Register a callback for processing window content,
+    /// evaluating the R2R query of each window report on the given pool
+    /// Returns a receiver for binding results
+    pub fn register_with_pool(
+        windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+        r2r: R2ROperator,
+        window_defs: Vec<WindowDefinition>,
+        pool: EvaluationPool,
+    ) -> mpsc::Receiver<BindingWithTimestamp> {
+        let (tx, rx) = mpsc::channel();
+        let output = Arc::new(ResultOutput::new(tx));
+        let r2r = Arc::new(RwLock::new(r2r));
+        let metrics = Arc::new(MetricsRecorder::default());
+        Self::register_sink(windows, r2r, window_defs, pool, metrics, None, || {
+            ResultSink::Bindings(output.clone(), None)
+        });
+        rx
+    }
+
+    /// Register a callback for processing window content,
+    /// evaluating the R2R query of each window report on the given pool
+    /// Returns a receiver for the changes of each window's results between reports
+    pub fn register_deltas(
+        windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+        r2r: R2ROperator,
+        window_defs: Vec<WindowDefinition>,
+        pool: EvaluationPool,
+    ) -> mpsc::Receiver<ResultDelta> {
         let (tx, rx) = mpsc::channel();
+        let r2r = Arc::new(RwLock::new(r2r));
+        let metrics = Arc::new(MetricsRecorder::default());
+        Self::register_sink(windows, r2r, window_defs, pool, metrics, None, || {
+            ResultSink::Deltas(tx.clone(), Arc::new(Mutex::new(DeltaTracker::default())))
+        });
+        rx
+    }
+
+    /// Subscribe every window to have its reports evaluated and delivered to
+    /// the sink created for it
+    /// Each report is evaluated with the R2R operator (and its static data) as it
+    /// is when the window reports
+    fn register_sink(
+        windows: HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+        r2r: Arc<RwLock<R2ROperator>>,
+        window_defs: Vec<WindowDefinition>,
+        pool: EvaluationPool,
+        metrics: Arc<MetricsRecorder>,
+        shedder: Option<Arc<LoadShedder>>,
+        sink_for: impl Fn() -> ResultSink,
+    ) {
+        // For each window, subscribe to its RStream output
+        for (window_name, window_arc) in windows.iter() {
+            let r2r_shared = r2r.clone();
+            let sink = sink_for();
+            let all_windows = windows.clone();
+            let window_def = window_defs
+                .iter()
+                .find(|w| w.window_name == *window_name)
+                .cloned();
+            // Shared by the reports of the window rather than cloned for each
+            let window_name_owned: Arc<str> = window_name.as_str().into();
+            let pool = pool.clone();
+            let metrics = metrics.clone();
+            let shedder = shedder.clone();
+            // Reports evaluated with SPARQL so far, for shedding every Nth
+            let reports = AtomicU64::new(0);
+
+            // Subscribe to window emissions using the callback system
+            // The callback runs while the window is locked, so it only hands the
+            // report over to the pool and returns
+            {
+                let mut window = window_arc.lock().unwrap();
+                let evaluations = window.evaluation_recorder();
+
+                if window.aggregation_plan().is_some() {
+                    window.subscribe_aggregates(move |report| {
+                        if !sink.is_active() {
+                            return;
+                        }
+                        let timestamp = report.content.last_timestamp_changed;
+                        let time = ReportTime::of(&report.content);
+                        let close = close_time(timestamp, &window_def);
+                        let ticket = sink.reserve(&window_name_owned, close);
+                        debug!(
+                            window = %window_name_owned,
+                            report_time = timestamp,
+                            quads = report.content.len(),
+                            incremental = report.solutions.is_some(),
+                            "Window report"
+                        );
+                        metrics.record_report();
+                        let span = ReportSpan::window_closed(
+                            &window_name_owned,
+                            timestamp,
+                            report.content.len(),
+                        );
+                        let r2r = r2r_shared.read().unwrap().clone();
+                        // Static data could match the window pattern and parameters
+                        // change the query, so both need SPARQL
+                        let solutions = report
+                            .solutions
+                            .filter(|_| !r2r.has_static_data() && !r2r.has_parameters());
+                        let Some(solutions) = solutions else {
+                            let index = reports.fetch_add(1, Ordering::Relaxed);
+                            if shedder.as_ref().is_some_and(|s| s.shed_report(index)) {
+                                sink.deliver(ticket, None, time, &window_def);
+                                return;
+                            }
+                            metrics.record_evaluation_queued();
+                            let sink = sink.clone();
+                            let all_windows = all_windows.clone();
+                            let window_def = window_def.clone();
+                            let window_name = window_name_owned.clone();
+                            let metrics = metrics.clone();
+                            let evaluations = evaluations.clone();
+                            pool.execute(move || {
+                                let started = Instant::now();
+                                let others =
+                                    Self::other_contents(&window_name, &all_windows, timestamp);
+                                let solutions = span.evaluate(|| {
+                                    Self::evaluate(report.content, others, &window_name, &r2r)
+                                });
+                                let duration = started.elapsed();
+                                metrics.record_evaluation(duration);
+                                evaluations.record(duration);
+                                let results = solutions.as_ref().map_or(0, Vec::len);
+                                span.emit(results, || {
+                                    sink.deliver(ticket, solutions, time, &window_def)
+                                });
+                                metrics.record_results(results);
+                            });
+                            return;
+                        };
+                        let results = solutions.len();
+                        span.emit(results, || {
+                            sink.deliver(ticket, Some(solutions), time, &window_def)
+                        });
+                        metrics.record_results(results);
+                    });
+                    continue;
+                }
+
+                window.subscribe(crate::StreamType::RStream, move |container| {
+                    if !sink.is_active() {
+                        return;
+                    }
+                    let close = close_time(container.last_timestamp_changed, &window_def);
+                    let ticket = sink.reserve(&window_name_owned, close);
+                    let time = ReportTime::of(&container);
+                    debug!(
+                        window = %window_name_owned,
+                        report_time = container.last_timestamp_changed,
+                        quads = container.len(),
+                        "Window report"
+                    );
+                    metrics.record_report();
+                    let span = ReportSpan::window_closed(
+                        &window_name_owned,
+                        container.last_timestamp_changed,
+                        container.len(),
+                    );
+                    let index = reports.fetch_add(1, Ordering::Relaxed);
+                    if shedder.as_ref().is_some_and(|s| s.shed_report(index)) {
+                        sink.deliver(ticket, None, time, &window_def);
+                        return;
+                    }
+                    metrics.record_evaluation_queued();
+                    let r2r = r2r_shared.read().unwrap().clone();
+                    let sink = sink.clone();
+                    let all_windows = all_windows.clone();
+                    let window_def = window_def.clone();
+                    let window_name = window_name_owned.clone();
+                    let metrics = metrics.clone();
+                    let evaluations = evaluations.clone();
+                    pool.execute(move || {
+                        let started = Instant::now();
+                        let timestamp = container.last_timestamp_changed;
+                        let others = Self::other_contents(&window_name, &all_windows, timestamp);
+                        let solutions =
+                            span.evaluate(|| Self::evaluate(container, others, &window_name, &r2r));
+                        let duration = started.elapsed();
+                        metrics.record_evaluation(duration);
+                        evaluations.record(duration);
+                        let results = solutions.as_ref().map_or(0, Vec::len);
+                        span.emit(results, || {
+                            sink.deliver(ticket, solutions, time, &window_def)
+                        });
+                        metrics.record_results(results);
+                    });
+                });
+            }
+        }
+    }
+
+    /// Snapshot the content of the other windows at the time of a report of
+    /// `window_name`
+    /// Taken by the worker evaluating the report, once the reporting window is
+    /// unlocked: locking the other windows from the report callback would deadlock
+    /// windows reporting at the same time on their own ingestion threads
+    fn other_contents(
+        window_name: &str,
+        all_windows: &HashMap<String, Arc<Mutex<CSPARQLWindow>>>,
+        timestamp: i64,
+    ) -> Vec<QuadContainer> {
+        all_windows
+            .iter()
+            .filter(|(other_name, _)| other_name.as_str() != window_name)
+            .filter_map(|(_, other_window)| other_window.lock().ok()?.get_full_content(timestamp))
+            .collect()
+    }
+
+    /// Evaluate the R2R query over one window report and the snapshot of the other
+    /// windows taken with it
+    /// Returns `None` if the query failed or doesn't produce solutions
+    fn evaluate(
+        container: Arc<QuadContainer>,
+        others: Vec<QuadContainer>,
+        window_name: &str,
+        r2r: &R2ROperator,
+    ) -> Option<Vec<QuerySolution>> {
+        // Merge content from other windows, copying the report only if there is any
+        let mut merged: Option<QuadContainer> = None;
+        for other_container in others {
+            let merged = merged.get_or_insert_with(|| container.as_ref().clone());
+            merged.merge(other_container);
+        }
+
+        // Execute R2R query; reports of a shared window are the same for every query,
+        // and so are the solutions of its patterns over them
+        let results = match &merged {
+            Some(merged) => r2r.execute(merged),
+            None => match NamedNode::new(window_name) {
+                Ok(window) => r2r.execute_report(&window, &container),
+                Err(_) => r2r.execute(&container),
+            },
+        };
+        if let Ok(oxigraph::sparql::QueryResults::Solutions(solutions)) = results {
+            return Some(solutions.flatten().collect());
+        }
+        None
+    }
+
+    /// Whether the instances of a window without events report, from the builder or
+    /// the negation of the window in the query
+    fn emit_empty_windows(&self, window_def: &WindowDefinition) -> bool {
+        self.config
+            .window_emit_empty_windows
+            .get(&window_def.window_name)
+            .copied()
+            .unwrap_or(window_def.emit_empty_windows || self.config.emit_empty_windows)
+    }
+
+    /// Key partitioning a window, located in the pattern of the window in the query
+    fn partition_key(
+        &self,
+        sparql_query: &str,
+        window_def: &WindowDefinition,
+    ) -> Result<Option<PartitionKey>, String> {
+        let Some(variable) = self
+            .config
+            .window_partitions
+            .get(&window_def.window_name)
+            .or(window_def.partition_by.as_ref())
+        else {
+            return Ok(None);
+        };
+        PartitionKey::from_query(sparql_query, &window_def.window_name, variable)
+            .map(Some)
+            .ok_or_else(|| {
+                format!(
+                    "Cannot partition window {} by ?{}: not a subject or object in its pattern",
+                    window_def.window_name, variable
+                )
+            })
+    }
+
+    /// Plan of the incremental evaluation of the query, if enabled and eligible
+    fn aggregation_plan(&self) -> Option<AggregationPlan> {
+        // Reified quads are matched by the query, but not by incremental aggregates
+        let incremental = self.config.incremental_aggregation
+            && !self.config.annotate_timestamps
+            && !self.config.annotate_streams;
+        if incremental {
+            AggregationPlan::detect(&self.parsed_query.sparql_query)
+        } else {
+            None
+        }
+    }
+
+    /// Replace the query with one over the same windows, keeping their content, so
+    /// e.g. thresholds or projections change without losing the events of long
+    /// windows
+    /// The next reports are evaluated with the new query, reports already being
+    /// evaluated keep the previous one; fails without changing anything if the
+    /// windows differ or the SPARQL part can't be parsed
+    /// Window settings derived from the query, whether instances without events
+    /// report and the partition keys, follow the new query
+    pub fn replace_query(&mut self, query: &str) -> Result<(), String> {
+        let parsed = RSPQLParser::new(query.to_string())
+            .with_dialect(self.config.dialect)
+            .parse();
+        // Negating a window in the query only derives a setting of the window
+        let declared = |windows: &[WindowDefinition]| -> Vec<WindowDefinition> {
+            windows
+                .iter()
+                .map(|window_def| WindowDefinition {
+                    emit_empty_windows: false,
+                    ..window_def.clone()
+                })
+                .collect()
+        };
+        if declared(&parsed.s2r) != declared(&self.parsed_query.s2r) {
+            return Err("The replacing query must declare the same windows".to_string());
+        }
+
+        let mut settings = Vec::new();
+        for window_def in &parsed.s2r {
+            let Some(window) = self.windows.get(&window_def.window_name) else {
+                continue;
+            };
+            let emit_empty_windows = self.emit_empty_windows(window_def);
+            let partition_key = self.partition_key(&parsed.sparql_query, window_def)?;
+            let shared = self
+                .shared_windows
+                .iter()
+                .any(|shared| Arc::ptr_eq(&shared.window, window));
+            if shared && emit_empty_windows != window.lock().unwrap().emits_empty_windows() {
+                return Err(format!(
+                    "Window {} is shared with other queries, the replacing query can't \
+                     change whether its empty instances report",
+                    window_def.window_name
+                ));
+            }
+            settings.push((window.clone(), emit_empty_windows, partition_key));
+        }
+        {
+            let mut r2r = self.r2r.write().unwrap();
+            r2r.set_query(parsed.sparql_query.clone())?;
+            if parsed.default_graphs != self.parsed_query.default_graphs {
+                r2r.set_default_graphs(
+                    parsed
+                        .default_graphs
+                        .iter()
+                        .filter_map(|graph| NamedNode::new(graph).ok()),
+                );
+            }
+        }
+        debug!(query = %parsed.sparql_query, "Replaced the R2R query");
+        self.parsed_query = parsed;
+        for (window, emit_empty_windows, partition_key) in settings {
+            let mut window = window.lock().unwrap();
+            window.set_emit_empty_windows(emit_empty_windows);
+            window.set_partition_key(partition_key);
+        }
+
+        // Windows evaluated incrementally follow the plan of the new query, or fall
+        // back to SPARQL
+        let plan = self.aggregation_plan();
+        for (name, window) in &self.windows {
+            let mut window = window.lock().unwrap();
+            if window.aggregation_plan().is_none() {
+                continue;
+            }
+            match plan.as_ref().filter(|plan| plan.graph().as_str() == name) {
+                Some(plan) => window.set_aggregation_plan(plan.clone()),
+                None => window.clear_aggregation_plan(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Convenience method to register using the engine's own data
+    /// Static data changed afterwards is picked up by the following reports
+    /// Can be called before `initialize`, the windows then deliver to the handle
+    /// once created, and again for independent consumers; see `ProcessingHandle`
+    /// to subscribe callbacks or stop the delivery later
+    pub fn start_processing(&self) -> ProcessingHandle {
+        let (handle, output) = ProcessingHandle::new();
+        // In synchronous mode the results for `poll_results` already feed the history
+        let history = self.history.clone().filter(|_| !self.config.synchronous);
+        let sink = ResultSink::Bindings(output, history);
+        // Windows are created by `initialize`
+        if self.windows.is_empty() {
+            self.pending_processing.lock().unwrap().push(sink);
+            return handle;
+        }
+        Self::register_sink(
+            self.windows.clone(),
+            self.r2r.clone(),
+            self.parsed_query.s2r.clone(),
+            self.evaluation_pool(),
+            self.metrics.clone(),
+            self.shedder.clone(),
+            self.filtered(self.merged(self.deduplicated(|| sink.clone()))),
+        );
+        handle
+    }
+
+    /// Give each window a deduplicating sink, following the configured mode
+    fn deduplicated(&self, sink_for: impl Fn() -> ResultSink) -> impl Fn() -> ResultSink {
+        let mode = self.config.result_dedup;
+        move || {
+            let sink = sink_for();
+            if mode == ResultDedup::None {
+                return sink;
+            }
+            let deduplicator = Arc::new(Mutex::new(Deduplicator::new(mode)));
+            ResultSink::Deduplicated(Box::new(sink), deduplicator)
+        }
+    }
+
+    /// Give each window a sink filtering its results, if filters are configured
+    fn filtered(&self, sink_for: impl Fn() -> ResultSink) -> impl Fn() -> ResultSink {
+        let filters: Arc<[ResultFilter]> = self.config.result_filters.clone().into();
+        move || {
+            let sink = sink_for();
+            if filters.is_empty() {
+                return sink;
+            }
+            ResultSink::Filtered(Box::new(sink), filters.clone())
+        }
+    }
+
+    /// Give the windows sinks sharing a merger of their reports, if configured
+    fn merged(&self, sink_for: impl Fn() -> ResultSink) -> impl Fn() -> ResultSink {
+        let merger = self.config.result_merge_delay.map(|max_delay| {
+            let windows = self.parsed_query.s2r.iter();
+            let windows = windows.map(|window| window.window_name.clone());
+            let merger = Arc::new(Mutex::new(ResultMerger::new(windows, max_delay)));
+            self.result_mergers.lock().unwrap().push(merger.clone());
+            merger
+        });
+        move || {
+            let sink = sink_for();
+            match &merger {
+                Some(merger) => ResultSink::Merged(merger.clone(), Box::new(sink)),
+                None => sink,
+            }
+        }
+    }
+
+    /// Get the results produced since the last call, in synchronous mode
+    /// Every report triggered by the quads added so far has already been evaluated,
+    /// so no waiting is needed; always empty when not synchronous
+    pub fn poll_results(&self) -> Vec<BindingWithTimestamp> {
+        match &self.sync_results {
+            Some(results) => results.lock().unwrap().try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Get the pool evaluating reports: inline in synchronous mode, else worker threads
+    fn evaluation_pool(&self) -> EvaluationPool {
+        if self.config.synchronous {
+            EvaluationPool::inline()
+        } else if let Some(pool) = &self.shared_pool {
+            pool.clone()
+        } else {
+            let prefix = &self.config.thread_name_prefix;
+            let threads = self.config.evaluation_threads;
+            EvaluationPool::supervised(threads, prefix, self.errors.clone())
+        }
+    }
+
+    /// Convenience method to register using the engine's own data, receiving
+    /// the results of each window as insert/delete deltas (IStream/DStream)
+    /// between consecutive reports instead of full result sets
+    pub fn start_processing_deltas(&self) -> mpsc::Receiver<ResultDelta> {
+        let (tx, rx) = mpsc::channel();
+        Self::register_sink(
+            self.windows.clone(),
+            self.r2r.clone(),
+            self.parsed_query.s2r.clone(),
+            self.evaluation_pool(),
+            self.metrics.clone(),
+            self.shedder.clone(),
+            || ResultSink::Deltas(tx.clone(), Arc::new(Mutex::new(DeltaTracker::default()))),
+        );
+        rx
+    }
+
+    /// Convenience method to register using the engine's own data, receiving
+    /// only the results matching the alert rules
+    pub fn start_processing_alerts(&self, rules: Vec<AlertRule>) -> mpsc::Receiver<Alert> {
+        let (tx, rx) = mpsc::channel();
+        let rules = Arc::new(Mutex::new(rules));
+        Self::register_sink(
+            self.windows.clone(),
+            self.r2r.clone(),
+            self.parsed_query.s2r.clone(),
+            self.evaluation_pool(),
+            self.metrics.clone(),
+            self.shedder.clone(),
+            || ResultSink::Alerts(tx.clone(), rules.clone()),
+        );
+        rx
+    }
+
+    /// Match a sequence pattern over the events of every report of one window,
+    /// identified by its IRI, without evaluating the R2R query
+    /// A match is sent once, even if several (sliding) window instances hold it;
+    /// matching runs while the window is locked
+    pub fn start_processing_sequences(
+        &self,
+        window_name: &str,
+        pattern: SequencePattern,
+    ) -> Result<mpsc::Receiver<SequenceMatch>, String> {
+        let window = self
+            .windows
+            .get(window_name)
+            .ok_or_else(|| format!("Unknown window {}", window_name))?;
+        let (tx, rx) = mpsc::channel();
+        let sent: Mutex<HashSet<SequenceMatch>> = Mutex::new(HashSet::new());
+        window
+            .lock()
+            .unwrap()
+            .subscribe(crate::StreamType::RStream, move |container| {
+                let mut sent = sent.lock().unwrap();
+                // Later instances open later, so they can't hold matches starting earlier
+                if let Some((open, _)) = container.window_bounds {
+                    sent.retain(|matched| matched.timestamps[0] >= open);
+                }
+                for matched in pattern.matches(&container) {
+                    if sent.insert(matched.clone()) {
+                        let _ = tx.send(matched);
+                    }
+                }
+            });
+        Ok(rx)
+    }
+
+    /// Join the events of two streams of one window, identified by its IRI, at most
+    /// the tolerance of the join apart, over every report of the window, without
+    /// evaluating the R2R query
+    /// The window records the stream of its quads from now on; a match is sent once,
+    /// even if several (sliding) window instances hold it
+    /// Returns an error for unknown windows, or streams not feeding the window
+    pub fn start_processing_joins(
+        &self,
+        window_name: &str,
+        join: TemporalJoin,
+    ) -> Result<mpsc::Receiver<JoinMatch>, String> {
+        let window = self
+            .windows
+            .get(window_name)
+            .ok_or_else(|| format!("Unknown window {}", window_name))?;
+        let definition = self
+            .parsed_query
+            .s2r
+            .iter()
+            .find(|definition| definition.window_name == window_name)
+            .ok_or_else(|| format!("Unknown window {}", window_name))?;
+        let feeds = |stream: &str| {
+            let mut streams = definition.additional_stream_names.iter();
+            definition.stream_name == stream || streams.any(|name| name == stream)
+        };
+        if let Some(stream) = join.streams().find(|stream| !feeds(stream)) {
+            return Err(format!(
+                "Stream {} doesn't feed window {}",
+                stream, window_name
+            ));
+        }
+        let (tx, rx) = mpsc::channel();
+        let sent: Mutex<HashSet<JoinMatch>> = Mutex::new(HashSet::new());
+        let mut window = window.lock().unwrap();
+        window.set_record_streams(true);
+        window.subscribe(crate::StreamType::RStream, move |container| {
+            let mut sent = sent.lock().unwrap();
+            // Later instances open later, so they can't hold matches starting earlier
+            if let Some((open, _)) = container.window_bounds {
+                sent.retain(|matched| matched.timestamps.0.min(matched.timestamps.1) >= open);
+            }
+            for matched in join.matches(&container) {
+                if sent.insert(matched.clone()) {
+                    let _ = tx.send(matched);
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    /// Call `callback` with every result of a query, identified by its output stream
+    /// IRI, without spawning a thread to consume a channel, e.g. for GUIs or FFI layers
+    /// Callbacks can be registered before or after `initialize`, and run on the
+    /// threads evaluating the reports, so they should return quickly; reports are
+    /// only evaluated for them while at least one is registered
+    /// Returns an error for unknown queries
+    pub fn on_result<F>(&self, query_id: &str, callback: F) -> Result<SubscriptionToken, String>
+    where
+        F: Fn(&BindingWithTimestamp) + Send + Sync + 'static,
+    {
+        if query_id != self.parsed_query.r2s.name {
+            return Err(format!("Unknown query {}", query_id));
+        }
+        Ok(self.result_callbacks.add(Arc::new(callback)))
+    }
+
+    /// Remove a callback registered with `on_result`; it may still receive the
+    /// results being delivered concurrently
+    /// Returns `false` if it was already removed
+    pub fn unsubscribe(&self, token: SubscriptionToken) -> bool {
+        self.result_callbacks.remove(token)
+    }
+
+    /// Get the retained results of a query, identified by its output stream IRI,
+    /// whose window ends at or after `since`
+    /// Only results delivered through `start_processing` are retained; returns `None`
+    /// for unknown queries or when retention isn't enabled in the configuration
+    pub fn recent_results(&self, query_id: &str, since: i64) -> Option<Vec<BindingWithTimestamp>> {
+        if query_id != self.parsed_query.r2s.name {
+            return None;
+        }
+        let history = self.history.as_ref()?;
+        Some(history.lock().unwrap().since(since))
+    }
+
+    /// Receive the panics caught from now on in the threads of the engine, e.g. in
+    /// window callbacks or result sinks, instead of losing them with the thread
+    /// Ingestion and evaluation threads keep running after a panic; in synchronous
+    /// mode, adding the event also returns an error
+    pub fn errors(&self) -> mpsc::Receiver<EngineError> {
+        self.errors.subscribe()
+    }
+
+    /// Receive the non-result events of the engine from now on: parse warnings,
+    /// dropped late events, sink failures, callback panics, watermark advances and
+    /// window evictions
+    /// Parse warnings are published by `initialize`, so subscribe before it; sinks
+    /// only publish their failures once given the publisher, see `event_publisher`
+    pub fn subscribe_events(&self) -> mpsc::Receiver<EngineEvent> {
+        self.events.subscribe()
+    }
+
+    /// Get the publisher of the events of the engine, e.g. for a sink to report its
+    /// failures with `FileSink::with_events`
+    pub fn event_publisher(&self) -> EventPublisher {
+        self.events.clone()
+    }
+
+    /// Receive the events dropped from now on, each with the reason it was dropped:
+    /// later than the allowed lateness of a window, invalid against the shapes of its
+    /// stream, or failing to parse, e.g. to audit them and add them again once repaired
+    /// Events shed under load aren't included, copying them would add to the load
+    pub fn dead_letters(&self) -> mpsc::Receiver<DeadLetter> {
+        self.dead_letters.subscribe()
+    }
+
+    /// Get the operational metrics of the engine: ingestion, queue depth, open
+    /// windows, evaluation latency and results
+    pub fn metrics(&self) -> EngineMetrics {
+        let active_windows = self
+            .windows
+            .values()
+            .map(|window| window.lock().unwrap().get_active_window_count())
+            .sum();
+        let mut metrics = self.metrics.snapshot(active_windows);
+        metrics.streams = StreamStats::snapshot(&self.stream_stats);
+        metrics
+    }
+
+    /// Serve the metrics of the engine to Prometheus scrapes on `address`, e.g.
+    /// `"0.0.0.0:9898"`, from a background thread
+    #[cfg(feature = "prometheus")]
+    pub fn serve_metrics(&self, address: &str) -> Result<thread::JoinHandle<()>, String> {
+        let listener = std::net::TcpListener::bind(address)
+            .map_err(|e| format!("Failed to bind metrics endpoint {}: {}", address, e))?;
+        let windows = self.windows.clone();
+        let metrics = self.metrics.clone();
+        let stream_stats = self.stream_stats.clone();
+        let snapshot = move || {
+            let active_windows = windows
+                .values()
+                .map(|window| window.lock().unwrap().get_active_window_count())
+                .sum();
+            let mut metrics = metrics.snapshot(active_windows);
+            metrics.streams = StreamStats::snapshot(&stream_stats);
+            metrics
+        };
+        let prefix = &self.config.thread_name_prefix;
+        Ok(crate::metrics::serve_prometheus(listener, prefix, snapshot))
+    }
+
+    /// Serve a live debug console on `address`, e.g. `"127.0.0.1:9899"`, from a
+    /// background thread: a web page showing the open instances of every window with
+    /// their range and size per stream, the latest results, throughput charts and the
+    /// query, refreshed every second
+    /// Call after `initialize`; results are recorded from then on through `on_result`
+    /// The thread stops once the engine and its processing threads are dropped
+    #[cfg(feature = "debug-console")]
+    pub fn serve_console(&self, address: &str) -> Result<thread::JoinHandle<()>, String> {
+        let listener = std::net::TcpListener::bind(address)
+            .map_err(|e| format!("Failed to bind debug console {}: {}", address, e))?;
+        let console = Arc::new(crate::console::Console::new(
+            self.parsed_query.clone(),
+            self.windows.clone(),
+            self.metrics.clone(),
+        ));
+        // Kept alive by the callback only, so the console stops with the engine
+        let recorder = console.clone();
+        self.on_result(&self.parsed_query.r2s.name, move |result| {
+            recorder.record(result)
+        })?;
+        let name = format!("{}-console", self.config.thread_name_prefix);
+        crate::console::serve_console(listener, Arc::downgrade(&console), name)
+    }
+
+    /// Set the number of threads evaluating R2R queries in parallel
+    /// Takes effect on the next call to `start_processing`
+    pub fn set_evaluation_threads(&mut self, threads: usize) {
+        self.config.evaluation_threads = threads.max(1);
+    }
+
+    /// Get the number of threads evaluating R2R queries
+    pub fn evaluation_threads(&self) -> usize {
+        self.config.evaluation_threads
+    }
+
+    /// Evaluate the reports of later `start_processing` calls on `pool`, e.g. shared
+    /// by the queries of a `QueryGroup`, instead of a pool of their own
+    /// Ignored in synchronous mode
+    pub fn share_evaluation_pool(&mut self, pool: EvaluationPool) {
+        self.shared_pool = Some(pool);
+    }
+
+    /// Enable or disable incremental evaluation of eligible aggregation queries
+    /// (single triple pattern, GROUP BY, COUNT/SUM/AVG/MIN/MAX)
+    /// Takes effect on the next call to `initialize`
+    pub fn set_incremental_aggregation(&mut self, enabled: bool) {
+        self.config.incremental_aggregation = enabled;
+    }
+
+    /// Whether eligible aggregation queries are evaluated incrementally
+    pub fn incremental_aggregation(&self) -> bool {
+        self.config.incremental_aggregation
+    }
+
+    /// Limit the estimated memory of each window's buffers; beyond it the oldest
+    /// windows spill their content to disk until they report
+    /// Takes effect on the next call to `initialize`
+    pub fn set_max_window_memory_bytes(&mut self, max_bytes: Option<usize>) {
+        self.config.max_window_memory_bytes = max_bytes;
+    }
+
+    /// Get the memory limit of each window's buffers, if any
+    pub fn max_window_memory_bytes(&self) -> Option<usize> {
+        self.config.max_window_memory_bytes
+    }
+
+    /// Get the configuration of the engine
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Add the events of an input log, recorded with `RSPEngineBuilder::record_input`,
+    /// to the streams again in their original order, e.g. to reproduce a run on
+    /// another machine; returns the number of events replayed
+    /// Runs are reproduced exactly by synchronous engines keeping event time, with
+    /// the same query and configuration; events failing again, e.g. invalid ones, are
+    /// skipped as they were in the recorded run
+    /// Fails before `initialize` and for events of streams the engine doesn't have
+    pub fn replay(&self, log: &InputLog) -> Result<usize, String> {
+        for event in &log.events {
+            let stream = self
+                .streams
+                .get(&event.stream)
+                .ok_or_else(|| format!("Unknown stream {} in the input log", event.stream))?;
+            let quads = event.quads.clone();
+            let added = if event.container {
+                let elements = quads.into_iter().collect();
+                stream.add(QuadContainer::new(elements, event.timestamp))
+            } else {
+                stream.add_timestamped(quads, event.timestamp)
+            };
+            if let Err(e) = added {
+                debug!(
+                    stream = %event.stream,
+                    timestamp = event.timestamp,
+                    "Replayed event failed again: {}",
+                    e
+                );
+            }
+        }
+        Ok(log.events.len())
+    }
+
+    /// Get a stream by name (returns a clone for easier usage)
+    /// With `RSPEngineBuilder::auto_close_streams`, dropping the last clone of the
+    /// streams returned closes the open instances of their window, once every other
+    /// stream of the window was dropped too
+    pub fn get_stream(&self, stream_name: &str) -> Option<RDFStream> {
+        let mut stream = self.streams.get(stream_name).cloned()?;
+        if let Some(handles) = &stream.handles {
+            stream.auto_close = Some(handles.acquire(&stream));
+        }
+        Some(stream)
+    }
+
+    /// Add static data to the R2R operator
+    /// Window reports from then on see the new quad, even after `start_processing`
+    pub fn add_static_data(&mut self, quad: Quad) {
+        self.update_static_data(|r2r| r2r.add_static_data(quad));
+    }
 
-        // For each window, subscribe to its RStream output
-        for (window_name, window_arc) in windows.iter() {
-            let r2r_clone = r2r.clone();
-            let tx_clone = tx.clone();
-            let all_windows = windows.clone();
-            let window_def = window_defs
-                .iter()
-                .find(|w| w.window_name == *window_name)
-                .cloned();
-            let window_name_owned = window_name.clone();
+    /// Remove a quad from the static data
+    /// Returns whether the quad was part of the static data
+    pub fn remove_static_data(&mut self, quad: &Quad) -> bool {
+        self.update_static_data(|r2r| r2r.remove_static_data(quad))
+    }
 
-            // Subscribe to window emissions using the callback system
-            {
-                let mut window = window_arc.lock().unwrap();
-                window.subscribe(crate::StreamType::RStream, move |mut container| {
-                    let timestamp = container.last_timestamp_changed;
+    /// Replace all static data at once, e.g. after reloading it from its source
+    /// Window reports see either the old or the new data, never a mix of both
+    pub fn replace_static_data(&mut self, quads: impl IntoIterator<Item = Quad>) {
+        self.update_static_data(|r2r| r2r.replace_static_data(quads));
+    }
 
-                    // Merge content from other windows
-                    for (other_name, other_window_arc) in &all_windows {
-                        if other_name != &window_name_owned {
-                            if let Ok(other_window) = other_window_arc.lock() {
-                                if let Some(other_container) =
-                                    other_window.get_content_from_window(timestamp)
-                                {
-                                    for quad in &other_container.elements {
-                                        container.add(quad.clone(), timestamp);
-                                    }
-                                }
-                            }
-                        }
-                    }
+    /// Get the version of the static data, incremented on every change
+    pub fn static_data_version(&self) -> u64 {
+        self.r2r.read().unwrap().static_data_version()
+    }
 
-                    // Execute R2R query
-                    if let Ok(results) = r2r_clone.execute(&container) {
-                        if let Some(def) = &window_def {
-                            if let oxigraph::sparql::QueryResults::Solutions(solutions) = results {
-                                for solution in solutions {
-                                    if let Ok(binding) = solution {
-                                        let binding_str = format!("{:?}", binding);
-                                        let result = BindingWithTimestamp {
-                                            bindings: binding_str,
-                                            timestamp_from: timestamp,
-                                            timestamp_to: timestamp + def.width,
-                                        };
-                                        let _ = tx_clone.send(result);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                });
+    /// Subscribe a callback to static data changes, called with the new version
+    pub fn on_static_data_change<F>(&mut self, callback: F)
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        self.static_data_callbacks
+            .write()
+            .unwrap()
+            .push(Arc::new(callback));
+    }
+
+    /// Apply a change to the static data and notify subscribers if the version changed
+    fn update_static_data<T>(&mut self, change: impl FnOnce(&mut R2ROperator) -> T) -> T {
+        Self::change_static_data(&self.r2r, &self.static_data_callbacks, change)
+    }
+
+    /// Apply a change to shared static data and notify its subscribers if the
+    /// version changed
+    fn change_static_data<T>(
+        r2r: &RwLock<R2ROperator>,
+        callbacks: &RwLock<Vec<StaticDataCallback>>,
+        change: impl FnOnce(&mut R2ROperator) -> T,
+    ) -> T {
+        let (result, before, after) = {
+            let mut r2r = r2r.write().unwrap();
+            let before = r2r.static_data_version();
+            let result = change(&mut r2r);
+            (result, before, r2r.static_data_version())
+        };
+        if after != before {
+            for callback in callbacks.read().unwrap().iter() {
+                callback(after);
             }
         }
+        result
+    }
 
-        rx
+    /// Bind a placeholder variable of the query, e.g. `?threshold`, to a value
+    /// Window reports from then on use the new value, without re-registering the query
+    pub fn set_parameter(&mut self, name: &str, value: impl Into<Term>) -> Result<(), String> {
+        self.r2r.write().unwrap().set_parameter(name, value)
     }
 
-    /// Convenience method to register using the engine's own data
-    pub fn start_processing(&self) -> mpsc::Receiver<BindingWithTimestamp> {
-        Self::register(
-            self.windows.clone(),
-            self.r2r.clone(),
-            self.parsed_query.s2r.clone(),
-        )
+    /// Unbind a placeholder variable, returning its previous value
+    pub fn remove_parameter(&mut self, name: &str) -> Option<Term> {
+        self.r2r.write().unwrap().remove_parameter(name)
     }
 
-    /// Get a stream by name (returns a clone for easier usage)
-    pub fn get_stream(&self, stream_name: &str) -> Option<RDFStream> {
-        self.streams.get(stream_name).cloned()
+    /// Get the value bound to a placeholder variable, if any
+    pub fn parameter(&self, name: &str) -> Option<Term> {
+        self.r2r.read().unwrap().parameter(name).cloned()
     }
 
-    /// Add static data to the R2R operator
-    pub fn add_static_data(&mut self, quad: Quad) {
-        self.r2r.add_static_data(quad);
+    /// Set the timestamp policy of a stream
+    /// Streams obtained through `get_stream` afterwards use the new policy
+    pub fn set_timestamp_policy(
+        &mut self,
+        stream_uri: &str,
+        policy: TimestampPolicy,
+    ) -> Result<(), String> {
+        let stream = self
+            .streams
+            .get_mut(stream_uri)
+            .ok_or_else(|| format!("Stream {} not found", stream_uri))?;
+        stream.timestamp_policy = policy;
+        Ok(())
+    }
+
+    /// Get the event-time statistics of a stream: out-of-orderness, late events
+    /// and watermark lag
+    pub fn stream_stats(&self, stream_uri: &str) -> Option<StreamStats> {
+        self.stream_stats.lock().unwrap().get(stream_uri).cloned()
+    }
+
+    /// Register `alias` as another stream feeding the window(s) of `stream_uri`
+    /// Events of both streams are interleaved by timestamp in the window, and
+    /// statistics are kept per stream
+    pub fn register_stream_alias(&mut self, alias: &str, stream_uri: &str) -> Result<(), String> {
+        if self.streams.contains_key(alias) {
+            return Err(format!("Stream {} already exists", alias));
+        }
+        let mut stream = self
+            .streams
+            .get(stream_uri)
+            .cloned()
+            .ok_or_else(|| format!("Stream {} not found", stream_uri))?;
+        stream.name = alias.into();
+        self.stream_stats
+            .lock()
+            .unwrap()
+            .insert(stream.name.clone(), StreamStats::default());
+        self.streams.insert(alias.to_string(), stream);
+        Ok(())
     }
 
     /// Get all stream names
@@ -211,25 +2745,148 @@ impl RSPEngine {
     }
 
     /// Add a sentinel event to trigger closure of all open windows
-    /// This should be called when the stream ends to emit final results
+    /// This should be called when the stream ends to emit final results; the quad of
+    /// the event is set by `RSPEngineBuilder::close_sentinel`
     pub fn close_stream(&self, stream_uri: &str, final_timestamp: i64) -> Result<(), String> {
-        if let Some(stream) = self.get_stream(stream_uri) {
+        // Side inputs have no window to close
+        if self.config.side_input_streams.contains(stream_uri) {
+            if !self.streams.contains_key(stream_uri) {
+                return Err(format!("Stream {} not found", stream_uri));
+            }
+            return Ok(());
+        }
+        // Not a handle of the producers, which would close the window again when dropped
+        if let Some(mut stream) = self.streams.get(stream_uri).cloned() {
+            // The sentinel is never over the rate of the stream
+            stream.rate_limiter = None;
             // Add a dummy quad with timestamp far in the future
-            let sentinel = oxigraph::model::Quad::new(
-                oxigraph::model::NamedNode::new("urn:rsp:sentinel")
-                    .map_err(|e| format!("Failed to create sentinel node: {}", e))?,
-                oxigraph::model::NamedNode::new("urn:rsp:type")
-                    .map_err(|e| format!("Failed to create sentinel node: {}", e))?,
-                oxigraph::model::Literal::new_simple_literal("end"),
-                oxigraph::model::GraphName::DefaultGraph,
-            );
-            stream.add_quads(vec![sentinel], final_timestamp)?;
+            let sentinel: Vec<Quad> = self.config.close_sentinel.iter().cloned().collect();
+            // The sentinel always uses the given timestamp, whatever the stream's policy
+            stream.add_timestamped(sentinel, final_timestamp)?;
+            stream.flush()?;
+            self.finish_merged_windows(stream_uri);
             Ok(())
         } else {
             Err(format!("Stream {} not found", stream_uri))
         }
     }
 
+    /// Close every stream of the engine at once, see `close_stream`
+    pub fn close_all_streams(&self, final_timestamp: i64) -> Result<(), String> {
+        let mut streams: Vec<&String> = self.streams.keys().collect();
+        streams.sort();
+        for stream in streams {
+            self.close_stream(stream, final_timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Stop holding merged results back for the windows whose streams are all closed
+    fn finish_merged_windows(&self, stream_uri: &str) {
+        let mut closed = self.closed_streams.lock().unwrap();
+        closed.insert(stream_uri.to_string());
+        let finished: Vec<&str> = self
+            .parsed_query
+            .s2r
+            .iter()
+            .filter(|window| {
+                std::iter::once(&window.stream_name)
+                    .chain(&window.additional_stream_names)
+                    .all(|stream| closed.contains(stream))
+            })
+            .map(|window| window.window_name.as_str())
+            .collect();
+        for merger in self.result_mergers.lock().unwrap().iter() {
+            let mut merger = merger.lock().unwrap();
+            for window in &finished {
+                deliver_merged(merger.finish(window));
+            }
+        }
+    }
+
+    /// Stop feeding events to the windows, so no window reports or evaluates until
+    /// `resume`, e.g. during maintenance or until every source is connected
+    ///
+    /// Events added meanwhile are buffered or rejected according to the pause
+    /// policy; those already queued for an ingestion thread are still applied.
+    /// Window contents are kept as they are
+    pub fn pause(&self) {
+        debug!("Pausing engine");
+        self.pause.pause();
+    }
+
+    /// Apply the events buffered while paused, in arrival order, and let new events
+    /// through again
+    pub fn resume(&self) -> Result<(), String> {
+        debug!("Resuming engine");
+        self.pause.resume()
+    }
+
+    /// Whether the engine is paused
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// Advance every window to the current time of the engine's clock, reporting the
+    /// instances it is past, see `CSPARQLWindow::advance_to`
+    /// Processing-time clocks are followed by a background thread, except in
+    /// synchronous mode where this is how they take effect. Returns the clock's
+    /// time, `None` for event time or while paused
+    pub fn advance_to_clock(&self) -> Option<i64> {
+        let now = self.config.clock.now()?;
+        if self.pause.is_paused() {
+            return None;
+        }
+        for window in self.windows.values() {
+            window.lock().unwrap().advance_to(now);
+        }
+        Some(now)
+    }
+
+    /// Describe the compiled pipeline, from the streams through the windows and
+    /// their settings to the SPARQL query and the output, e.g. to find out why a
+    /// query yields nothing; fails before `initialize`
+    pub fn explain(&self) -> Result<QueryPlan, String> {
+        let mut windows = Vec::new();
+        for def in &self.parsed_query.s2r {
+            let window = self
+                .windows
+                .get(&def.window_name)
+                .ok_or_else(|| format!("Window {} is not initialized", def.window_name))?;
+            let window = window.lock().unwrap();
+            let partition_by = self
+                .config
+                .window_partitions
+                .get(&def.window_name)
+                .or(def.partition_by.as_ref())
+                .cloned();
+            windows.push(WindowPlan {
+                name: def.window_name.clone(),
+                streams: def.stream_names().map(str::to_string).collect(),
+                width: window.width,
+                slide: window.slide,
+                report: window.report,
+                tick: window.tick,
+                origin: window.origin(),
+                delay: window.delay(),
+                emit_empty_windows: window.emits_empty_windows(),
+                partition_by,
+                latest_value: def.latest_value,
+                incremental: window.aggregation_plan().is_some(),
+            });
+        }
+        let r2r = self.r2r.read().unwrap();
+        Ok(QueryPlan {
+            output: self.parsed_query.r2s.name.clone(),
+            operator: self.parsed_query.r2s.operator.clone(),
+            windows,
+            sparql_query: self.parsed_query.sparql_query.clone(),
+            default_graphs: self.parsed_query.default_graphs.clone(),
+            named_graphs: self.parsed_query.named_graphs.clone(),
+            evaluation_plan: r2r.explain(),
+        })
+    }
+
     /// Get the parsed query
     pub fn parsed_query(&self) -> &crate::parsed_query::ParsedQuery {
         &self.parsed_query
@@ -244,6 +2901,7 @@ impl RSPEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
 
     #[test]
     fn test_rsp_engine_creation() {
@@ -280,4 +2938,622 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(engine.get_all_streams().len(), 1);
     }
+
+    #[test]
+    fn test_static_data_hot_reload() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?name
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ex:p ?o }
+                ?s ex:name ?name .
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+        let name = |value: &str| {
+            Quad::new(
+                node("s1"),
+                node("name"),
+                oxigraph::model::Literal::new_simple_literal(value),
+                GraphName::DefaultGraph,
+            )
+        };
+
+        let mut engine = RSPEngine::new(query);
+        engine.initialize().unwrap();
+        let versions = Arc::new(Mutex::new(Vec::new()));
+        let seen = versions.clone();
+        engine.on_static_data_change(move |version| seen.lock().unwrap().push(version));
+        engine.add_static_data(name("before"));
+
+        let results = engine.start_processing();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        let event = Quad::new(node("s1"), node("p"), node("o"), GraphName::DefaultGraph);
+        stream.add_quads(vec![event.clone()], 1).unwrap();
+        stream.add_quads(vec![event.clone()], 11).unwrap();
+        let first = results.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(first.bindings.contains("before"));
+
+        // Running queries pick up the new data without restarting
+        engine.replace_static_data([name("after")]);
+        stream.add_quads(vec![event], 21).unwrap();
+        let second = results.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(second.bindings.contains("after"));
+
+        assert!(engine.remove_static_data(&name("after")));
+        assert_eq!(engine.static_data_version(), 3);
+        assert_eq!(*versions.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parameters_update_running_query() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?v
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ex:value ?v FILTER(?v > ?threshold) }
+            }
+        "#
+        .to_string();
+        let reading = |sensor: &str, value: i64| {
+            vec![Quad::new(
+                oxigraph::model::NamedNode::new(format!("http://example.org/{}", sensor)).unwrap(),
+                oxigraph::model::NamedNode::new("http://example.org/value").unwrap(),
+                oxigraph::model::Literal::from(value),
+                GraphName::DefaultGraph,
+            )]
+        };
+
+        let mut engine = RSPEngine::new(query);
+        engine.initialize().unwrap();
+        engine
+            .set_parameter("threshold", oxigraph::model::Literal::from(10))
+            .unwrap();
+        let results = engine.start_processing();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        stream.add_quads(reading("s1", 20), 1).unwrap();
+        stream.add_quads(reading("s2", 5), 2).unwrap();
+        stream.add_quads(reading("s1", 20), 11).unwrap();
+        let first = results.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(first.bindings.contains("s1"));
+        assert!(results.recv_timeout(Duration::from_millis(300)).is_err());
+
+        // Lowering the threshold applies to the next report of the registered query
+        engine
+            .set_parameter("?threshold", oxigraph::model::Literal::from(1))
+            .unwrap();
+        stream.add_quads(reading("s2", 5), 12).unwrap();
+        stream.add_quads(reading("s3", 0), 21).unwrap();
+        let sensors: Vec<String> = (0..2)
+            .map(|_| {
+                results
+                    .recv_timeout(Duration::from_secs(5))
+                    .unwrap()
+                    .bindings
+            })
+            .collect();
+        assert!(sensors.iter().any(|bindings| bindings.contains("s2")));
+        assert_eq!(
+            engine.parameter("threshold"),
+            Some(oxigraph::model::Literal::from(1).into())
+        );
+    }
+
+    #[test]
+    fn test_metrics_follow_ingestion_and_reports() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngine::new(query);
+        engine.initialize().unwrap();
+        let results = engine.start_processing();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        for (subject, timestamp) in [("a", 1), ("b", 2), ("c", 11)] {
+            let quad = Quad::new(node(subject), node("p"), node("o"), GraphName::DefaultGraph);
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+        for _ in 0..2 {
+            results.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        thread::sleep(Duration::from_millis(100));
+
+        let metrics = engine.metrics();
+        assert_eq!(metrics.events_ingested, 3);
+        assert_eq!(metrics.queue_depth, 0);
+        assert_eq!(metrics.active_windows, 1);
+        assert_eq!(metrics.window_reports, 1);
+        assert_eq!(metrics.evaluations, 1);
+        assert_eq!(metrics.results_emitted, 2);
+    }
+
+    #[test]
+    fn test_micro_batching_coalesces_events() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngineBuilder::new(query)
+            .synchronous(true)
+            .stream_micro_batch("http://example.org/stream1", 3)
+            .build();
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        let events = [("a", 1), ("b", 1), ("c", 1), ("d", 1), ("e", 2), ("f", 11)];
+        for (subject, timestamp) in events {
+            let quad = Quad::new(node(subject), node("p"), node("o"), GraphName::DefaultGraph);
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+
+        // a-c fill a batch, d and e are sent once a later timestamp arrives, and
+        // f waits for the flush to close the window
+        assert_eq!(engine.metrics().events_ingested, 3);
+        assert!(engine.poll_results().is_empty());
+        stream.flush().unwrap();
+        assert_eq!(engine.metrics().events_ingested, 4);
+        assert_eq!(engine.poll_results().len(), 5);
+    }
+
+    #[test]
+    fn test_reordering_sorts_concurrent_producers() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngineBuilder::new(query)
+            .synchronous(true)
+            .stream_reorder("http://example.org/stream1", 10)
+            .build();
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        // c, from another producer, arrives after the later b
+        let events = [("a", 1), ("b", 12), ("c", 5), ("d", 25)];
+        for (subject, timestamp) in events {
+            let quad = Quad::new(node(subject), node("p"), node("o"), GraphName::DefaultGraph);
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+
+        // d releases c and b, in order, so [0, 10) holds a and c; d waits for the flush
+        assert_eq!(engine.metrics().events_ingested, 3);
+        let mut subjects: Vec<String> = engine
+            .poll_results()
+            .iter()
+            .map(|result| result.solution.get("s").unwrap().to_string())
+            .collect();
+        subjects.sort();
+        let expected: Vec<String> = ["a", "c"].map(|name| node(name).to_string()).into();
+        assert_eq!(subjects, expected);
+        stream.flush().unwrap();
+        assert_eq!(engine.metrics().events_ingested, 4);
+    }
+
+    #[test]
+    fn test_reorder_sends_releases_in_turn() {
+        let reorder = Arc::new(Reorder::new(0));
+        let container = |timestamp| QuadContainer::new(HashSet::new(), timestamp);
+        let (first, _) = reorder.push(container(1)).unwrap();
+        let (second, _) = reorder.push(container(2)).unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let later = {
+            let (reorder, order) = (reorder.clone(), order.clone());
+            thread::spawn(move || reorder.in_turn(second, || order.lock().unwrap().push(second)))
+        };
+        thread::sleep(Duration::from_millis(50));
+        // The buffer isn't locked while the second release waits for the first
+        assert!(reorder.push(container(3)).is_some());
+        assert!(order.lock().unwrap().is_empty());
+        reorder.in_turn(first, || order.lock().unwrap().push(first));
+        later.join().unwrap();
+        assert_eq!(*order.lock().unwrap(), vec![first, second]);
+    }
+
+    #[test]
+    fn test_dropping_last_stream_handle_closes_window() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngineBuilder::new(query)
+            .synchronous(true)
+            .auto_close_streams(true)
+            .build();
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        let producer = stream.clone();
+        let other = engine.get_stream("http://example.org/stream1").unwrap();
+        for (stream, subject, timestamp) in [(&stream, "a", 1), (&producer, "b", 2)] {
+            let quad = Quad::new(node(subject), node("p"), node("o"), GraphName::DefaultGraph);
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+
+        drop(stream);
+        drop(producer);
+        assert!(engine.poll_results().is_empty());
+        // The last handle closes [0, 10) with an empty event
+        drop(other);
+        assert_eq!(engine.poll_results().len(), 2);
+    }
+
+    #[test]
+    fn test_dropping_one_stream_keeps_shared_window_open() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1, ex:stream2 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngineBuilder::new(query)
+            .synchronous(true)
+            .auto_close_streams(true)
+            .build();
+        engine.initialize().unwrap();
+        let first = engine.get_stream("http://example.org/stream1").unwrap();
+        let second = engine.get_stream("http://example.org/stream2").unwrap();
+        let quad = Quad::new(node("a"), node("p"), node("o"), GraphName::DefaultGraph);
+        first.add_quads(vec![quad], 1).unwrap();
+
+        // The second stream still feeds [0, 10)
+        drop(first);
+        assert!(engine.poll_results().is_empty());
+        let quad = Quad::new(node("b"), node("p"), node("o"), GraphName::DefaultGraph);
+        second.add_quads(vec![quad], 2).unwrap();
+        assert!(engine.poll_results().is_empty());
+        drop(second);
+        assert_eq!(engine.poll_results().len(), 2);
+    }
+
+    #[test]
+    fn test_dropping_stream_handle_closes_window_after_queued_events() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+
+        let mut engine = RSPEngineBuilder::new(query)
+            .auto_close_streams(true)
+            .build();
+        engine.initialize().unwrap();
+        let results = engine.start_processing();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        for (subject, timestamp) in [("a", 1), ("b", 2), ("c", 15)] {
+            let quad = Quad::new(node(subject), node("p"), node("o"), GraphName::DefaultGraph);
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+
+        // Dropped while the events may still be queued for the window thread, which
+        // closes [10, 20), opened by the last one, once it applied them
+        drop(stream);
+        let mut subjects: Vec<String> = (0..3)
+            .map(|_| results.recv_timeout(Duration::from_secs(5)).unwrap())
+            .map(|result| result.solution.get("s").unwrap().to_string())
+            .collect();
+        subjects.sort();
+        let expected: Vec<String> = ["a", "b", "c"].map(|name| node(name).to_string()).into();
+        assert_eq!(subjects, expected);
+    }
+
+    #[test]
+    fn test_several_streams_feed_one_window() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?s ?p ?o
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1, ex:stream2 [RANGE 1000 STEP 1000]
+            WHERE {
+                WINDOW ex:win1 { ?s ?p ?o }
+            }
+        "#
+        .to_string();
+
+        let mut engine = RSPEngine::new(query);
+        engine.initialize().unwrap();
+        engine
+            .register_stream_alias("http://example.org/stream3", "http://example.org/stream2")
+            .unwrap();
+        assert!(
+            engine
+                .register_stream_alias("http://example.org/stream3", "http://example.org/stream1")
+                .is_err()
+        );
+
+        let quad = |i: i64| {
+            Quad::new(
+                oxigraph::model::NamedNode::new(format!("http://example.org/s{}", i)).unwrap(),
+                oxigraph::model::NamedNode::new("http://example.org/p").unwrap(),
+                oxigraph::model::Literal::from(i),
+                GraphName::DefaultGraph,
+            )
+        };
+        for (i, stream) in ["stream1", "stream2", "stream3"].iter().enumerate() {
+            let stream = engine
+                .get_stream(&format!("http://example.org/{}", stream))
+                .unwrap();
+            stream
+                .add_quads(vec![quad(i as i64)], i as i64 + 1)
+                .unwrap();
+        }
+        thread::sleep(std::time::Duration::from_millis(200));
+
+        let window = engine.get_window("http://example.org/win1").unwrap();
+        assert_eq!(window.lock().unwrap().get_content(2).unwrap().len(), 3);
+        assert_eq!(
+            engine
+                .stream_stats("http://example.org/stream3")
+                .unwrap()
+                .events,
+            1
+        );
+    }
+
+    #[test]
+    fn test_partitioned_window_reports_per_key() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor (COUNT(?obs) AS ?n)
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 10 STEP 10 ALIGN 0 PARTITION BY ?sensor]
+            WHERE {
+                WINDOW ex:win1 { ?obs ex:madeBySensor ?sensor }
+            }
+            GROUP BY ?sensor
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+        let observation = |obs: &str, sensor: &str| {
+            vec![Quad::new(
+                node(obs),
+                node("madeBySensor"),
+                node(sensor),
+                GraphName::DefaultGraph,
+            )]
+        };
+
+        let mut engine = RSPEngine::new(query);
+        engine.initialize().unwrap();
+        let results = engine.start_processing();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        stream.add_quads(observation("a", "s1"), 1).unwrap();
+        stream.add_quads(observation("b", "s2"), 2).unwrap();
+        stream.add_quads(observation("c", "s1"), 5).unwrap();
+        stream.add_quads(observation("d", "s1"), 12).unwrap();
+
+        // Only the partition of s1 saw an event closing its window
+        let first = results.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(first.bindings.contains("s1") && first.bindings.contains("\"2\""));
+        assert!(results.recv_timeout(Duration::from_millis(300)).is_err());
+        {
+            let window = engine.get_window("http://example.org/win1").unwrap();
+            let window = window.lock().unwrap();
+            assert_eq!(window.partition_count(), 2);
+            let s2 = window.partition(&node("s2").into()).unwrap();
+            assert_eq!(s2.get_content(2).unwrap().len(), 1);
+        }
+
+        // The sentinel has no key and closes the windows of every partition
+        engine
+            .close_stream("http://example.org/stream1", 100)
+            .unwrap();
+        let mut sensors: Vec<bool> = (0..2)
+            .map(|_| {
+                let result = results.recv_timeout(Duration::from_secs(5)).unwrap();
+                result.bindings.contains("s1")
+            })
+            .collect();
+        sensors.sort();
+        assert_eq!(sensors, vec![false, true]);
+    }
+
+    #[test]
+    fn test_latest_value_window() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?property ?value
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0 LATEST]
+            WHERE {
+                WINDOW ex:win1 { ?sensor ?property ?value }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| {
+            oxigraph::model::NamedNode::new(format!("http://example.org/{}", name)).unwrap()
+        };
+        let reading = |sensor: &str, property: &str, value: i64| {
+            vec![Quad::new(
+                node(sensor),
+                node(property),
+                oxigraph::model::Literal::from(value),
+                GraphName::DefaultGraph,
+            )]
+        };
+
+        let mut engine = RSPEngineBuilder::new(query).synchronous(true).build();
+        engine.initialize().unwrap();
+        assert!(engine.parsed_query().s2r[0].latest_value);
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        stream
+            .add_quads(reading("s1", "temperature", 20), 100)
+            .unwrap();
+        stream
+            .add_quads(reading("s1", "humidity", 50), 200)
+            .unwrap();
+        stream
+            .add_quads(reading("s2", "temperature", 18), 300)
+            .unwrap();
+        stream
+            .add_quads(reading("s1", "temperature", 21), 400)
+            .unwrap();
+        engine
+            .close_stream("http://example.org/stream1", 1500)
+            .unwrap();
+
+        // The current state of each sensor property
+        let results = engine.poll_results();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|r| r.bindings.contains("\"21\"")));
+        assert!(!results.iter().any(|r| r.bindings.contains("\"20\"")));
+    }
+
+    #[test]
+    fn test_repeated_window_references() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?t ?h
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?sensor ex:temperature ?t }
+                WINDOW ex:win1 { ?sensor ex:humidity ?h }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let reading = |sensor: &str, property: &str, value: i64| {
+            vec![Quad::new(
+                node(sensor),
+                node(property),
+                oxigraph::model::Literal::from(value),
+                GraphName::DefaultGraph,
+            )]
+        };
+
+        let mut engine = RSPEngineBuilder::new(query).synchronous(true).build();
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        // Readings of the two patterns arrive as separate events
+        stream
+            .add_quads(reading("s1", "temperature", 20), 100)
+            .unwrap();
+        stream
+            .add_quads(reading("s1", "humidity", 50), 200)
+            .unwrap();
+        stream
+            .add_quads(reading("s2", "temperature", 18), 300)
+            .unwrap();
+        engine
+            .close_stream("http://example.org/stream1", 1500)
+            .unwrap();
+
+        let results = engine.poll_results();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].bindings.contains("s1") && results[0].bindings.contains("\"50\""));
+    }
+
+    #[test]
+    fn test_static_graphs() {
+        let query = r#"
+            REGISTER RStream <http://example.org/output> AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?room ?offset
+            FROM ex:building
+            FROM NAMED ex:calibration
+            FROM NAMED WINDOW ex:win1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+            WHERE {
+                WINDOW ex:win1 { ?sensor ex:value ?value }
+                ?sensor ex:room ?room
+                GRAPH ex:calibration { ?sensor ex:offset ?offset }
+            }
+        "#
+        .to_string();
+        let node = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let fact =
+            |s: &str, p: &str, o: Term, graph: &str| Quad::new(node(s), node(p), o, node(graph));
+        let value = |value: i64| Term::from(oxigraph::model::Literal::from(value));
+
+        let mut engine = RSPEngineBuilder::new(query.clone())
+            .synchronous(true)
+            .build();
+        // Graphs already in the static data aren't fetched
+        engine.add_static_data(fact("s1", "room", node("kitchen").into(), "building"));
+        engine.add_static_data(fact("s1", "offset", value(2), "calibration"));
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream1").unwrap();
+        let reading = fact("s1", "value", value(20), "stream1");
+        stream.add_quads(vec![reading], 100).unwrap();
+        engine
+            .close_stream("http://example.org/stream1", 1500)
+            .unwrap();
+
+        let results = engine.poll_results();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].bindings.contains("kitchen"));
+
+        // Missing graphs are dereferenced, unless disabled
+        let unreachable = query
+            .replace("ex:building", "<http://127.0.0.1:1/building>")
+            .replace("ex:calibration", "<http://127.0.0.1:1/calibration>");
+        let mut engine = RSPEngineBuilder::new(unreachable.clone()).build();
+        assert!(engine.initialize().is_err());
+        let mut engine = RSPEngineBuilder::new(unreachable)
+            .dereference_static_graphs(false)
+            .build();
+        assert!(engine.initialize().is_ok());
+    }
 }
@@ -0,0 +1,295 @@
+use crate::delta::Binding;
+use crate::quad_container::QuadContainer;
+use oxigraph::model::{Quad, Term, Variable};
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+/// Bindings of the variables of the steps matched so far
+pub(crate) type Bindings = HashMap<Variable, Term>;
+
+/// Events matching the steps of a sequence pattern, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SequenceMatch {
+    /// Values of the variables of all steps
+    pub binding: Binding,
+    /// Timestamp of the event matching each step
+    pub timestamps: Vec<i64>,
+}
+
+/// Ordered pattern over the events of a window, e.g. a door opening followed by
+/// a temperature drop in the same room within 5 minutes
+///
+/// Each step is a basic graph pattern matched by the quads of a single event, i.e.
+/// the quads sharing a timestamp, and each step must match a strictly later event
+/// than the previous one. Variables shared by several steps must have the same value.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngine, SequencePattern};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let pattern = SequencePattern::new(300_000)
+///     .with_prefix("ex", "http://example.org/")
+///     .then("?door ex:state ex:open ; ex:room ?room")
+///     .unwrap()
+///     .then("?sensor ex:room ?room ; ex:state ex:cold")
+///     .unwrap();
+/// let matches = engine
+///     .start_processing_sequences("http://example.org/w1", pattern)
+///     .unwrap();
+/// for matched in matches {
+///     println!("{:?} at {:?}", matched.binding.get("room"), matched.timestamps);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SequencePattern {
+    steps: Vec<Vec<TriplePattern>>,
+    within: i64,
+    prefixes: Vec<(String, String)>,
+}
+
+impl SequencePattern {
+    /// Create a pattern whose last event follows the first one by at most `within` ms
+    pub fn new(within: i64) -> Self {
+        Self {
+            steps: Vec::new(),
+            within,
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Declare a prefix for the steps added afterwards
+    pub fn with_prefix(mut self, prefix: &str, iri: &str) -> Self {
+        self.prefixes.push((prefix.to_string(), iri.to_string()));
+        self
+    }
+
+    /// Add the next step, a basic graph pattern in SPARQL syntax such as
+    /// `?door ex:state ex:open`, without filters or blank nodes
+    pub fn then(mut self, pattern: &str) -> Result<Self, String> {
+        let patterns = parse_event_pattern(&self.prefixes, pattern, "Sequence step")?;
+        self.steps.push(patterns);
+        Ok(self)
+    }
+
+    /// Get the maximum time (in ms) between the first and the last event of a match
+    pub fn within(&self) -> i64 {
+        self.within
+    }
+
+    /// Find every sequence of events of a window report matching the steps
+    /// Quads without a timestamp, e.g. read back from a spill file, are ignored
+    pub fn matches(&self, content: &QuadContainer) -> Vec<SequenceMatch> {
+        let mut events: BTreeMap<i64, Vec<&Quad>> = BTreeMap::new();
+        for quad in content.iter() {
+            if let Some(timestamp) = content.timestamp(quad) {
+                events.entry(timestamp).or_default().push(quad);
+            }
+        }
+        let mut matches = Vec::new();
+        if !self.steps.is_empty() {
+            self.search(&events, &Bindings::new(), &mut Vec::new(), &mut matches);
+        }
+        matches
+    }
+
+    /// Extend a partial match with the events following its last one
+    fn search(
+        &self,
+        events: &BTreeMap<i64, Vec<&Quad>>,
+        bindings: &Bindings,
+        timestamps: &mut Vec<i64>,
+        matches: &mut Vec<SequenceMatch>,
+    ) {
+        let Some(step) = self.steps.get(timestamps.len()) else {
+            matches.push(SequenceMatch {
+                binding: to_binding(bindings),
+                timestamps: timestamps.clone(),
+            });
+            return;
+        };
+        let after = timestamps
+            .last()
+            .map_or(Bound::Unbounded, |t| Bound::Excluded(*t));
+        for (timestamp, quads) in events.range((after, Bound::Unbounded)) {
+            if timestamps
+                .first()
+                .is_some_and(|first| timestamp - first > self.within)
+            {
+                break;
+            }
+            timestamps.push(*timestamp);
+            for bindings in match_event(step, quads, bindings.clone()) {
+                self.search(events, &bindings, timestamps, matches);
+            }
+            timestamps.pop();
+        }
+    }
+}
+
+/// Parse the pattern matched by the quads of one event, a basic graph pattern in
+/// SPARQL syntax without filters or blank nodes, `kind` naming it in errors
+pub(crate) fn parse_event_pattern(
+    prefixes: &[(String, String)],
+    pattern: &str,
+    kind: &str,
+) -> Result<Vec<TriplePattern>, String> {
+    let mut parser = SparqlParser::new();
+    for (prefix, iri) in prefixes {
+        parser = parser
+            .with_prefix(prefix, iri)
+            .map_err(|e| format!("Invalid prefix {}: {}", prefix, e))?;
+    }
+    let query = parser
+        .parse_query(&format!("SELECT * WHERE {{ {} }}", pattern))
+        .map_err(|e| format!("Invalid {} {}: {}", kind.to_lowercase(), pattern, e))?;
+    let Query::Select { pattern: root, .. } = query else {
+        unreachable!("parsed as a SELECT query");
+    };
+    let bgp = match root {
+        GraphPattern::Project { inner, .. } => *inner,
+        other => other,
+    };
+    let GraphPattern::Bgp { patterns } = bgp else {
+        return Err(format!("{} {} is not a basic graph pattern", kind, pattern));
+    };
+    let supported = |term: &TermPattern| {
+        matches!(
+            term,
+            TermPattern::Variable(_) | TermPattern::NamedNode(_) | TermPattern::Literal(_)
+        )
+    };
+    let supported = patterns
+        .iter()
+        .all(|p| supported(&p.subject) && supported(&p.object));
+    if patterns.is_empty() || !supported {
+        return Err(format!("{} {} has no or unsupported terms", kind, pattern));
+    }
+    Ok(patterns)
+}
+
+/// Match the triple patterns of an event pattern against the quads of one event,
+/// extending the bindings of the patterns matched before
+pub(crate) fn match_event(
+    patterns: &[TriplePattern],
+    quads: &[&Quad],
+    bindings: Bindings,
+) -> Vec<Bindings> {
+    let Some((pattern, rest)) = patterns.split_first() else {
+        return vec![bindings];
+    };
+    let predicate = match &pattern.predicate {
+        NamedNodePattern::NamedNode(node) => TermPattern::NamedNode(node.clone()),
+        NamedNodePattern::Variable(v) => TermPattern::Variable(v.clone()),
+    };
+    let mut results = Vec::new();
+    for quad in quads {
+        let mut extended = bindings.clone();
+        let positions = [
+            (&pattern.subject, Term::from(quad.subject.clone())),
+            (&predicate, Term::from(quad.predicate.clone())),
+            (&pattern.object, quad.object.clone()),
+        ];
+        if positions
+            .into_iter()
+            .all(|(pattern, term)| unify(pattern, term, &mut extended))
+        {
+            results.extend(match_event(rest, quads, extended));
+        }
+    }
+    results
+}
+
+/// Match a term against a pattern, binding its variable if still unbound
+fn unify(pattern: &TermPattern, term: Term, bindings: &mut Bindings) -> bool {
+    match pattern {
+        TermPattern::Variable(v) => match bindings.get(v) {
+            Some(bound) => *bound == term,
+            None => {
+                bindings.insert(v.clone(), term);
+                true
+            }
+        },
+        TermPattern::NamedNode(node) => Term::from(node.clone()) == term,
+        TermPattern::Literal(literal) => Term::from(literal.clone()) == term,
+        _ => false,
+    }
+}
+
+/// Turn the bindings of a match into a binding sorted by variable name
+pub(crate) fn to_binding(bindings: &Bindings) -> Binding {
+    let mut values: Vec<(String, Term)> = bindings
+        .iter()
+        .map(|(variable, term)| (variable.as_str().to_string(), term.clone()))
+        .collect();
+    values.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Binding { values }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{GraphName, NamedNode};
+    use std::collections::HashSet;
+
+    fn event(container: &mut QuadContainer, subject: &str, predicate: &str, object: &str, ts: i64) {
+        let node = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        container.add(
+            Quad::new(
+                node(subject),
+                node(predicate),
+                node(object),
+                GraphName::DefaultGraph,
+            ),
+            ts,
+        );
+    }
+
+    fn pattern(within: i64) -> SequencePattern {
+        SequencePattern::new(within)
+            .with_prefix("ex", "http://example.org/")
+            .then("?door ex:state ex:open . ?door ex:room ?room")
+            .unwrap()
+            .then("?alarm ex:room ?room")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sequence_matches() {
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        event(&mut container, "alarm1", "room", "kitchen", 50);
+        event(&mut container, "door1", "state", "open", 100);
+        event(&mut container, "door1", "room", "kitchen", 100);
+        event(&mut container, "alarm2", "room", "hall", 200);
+        event(&mut container, "alarm3", "room", "kitchen", 400);
+
+        // alarm1 precedes the door, alarm2 is in another room
+        let matches = pattern(1000).matches(&container);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].timestamps, vec![100, 400]);
+        assert_eq!(
+            matches[0].binding.get("alarm"),
+            Some(&NamedNode::new("http://example.org/alarm3").unwrap().into())
+        );
+        assert!(pattern(200).matches(&container).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_steps() {
+        let pattern = SequencePattern::new(1000).with_prefix("ex", "http://example.org/");
+        assert!(pattern.clone().then("?s ex:p").is_err());
+        assert!(pattern.clone().then("[] ex:p ?o").is_err());
+        assert!(pattern.clone().then("?s ex:p ?o FILTER(?o > 1)").is_err());
+        assert!(pattern.then("?s ex:p 1").is_ok());
+        assert_eq!(
+            SequencePattern::new(1000)
+                .then("?s <http://example.org/p> ?o")
+                .unwrap()
+                .within(),
+            1000
+        );
+    }
+}
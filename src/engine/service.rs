@@ -0,0 +1,158 @@
+use oxigraph::model::{NamedNode, Term, Variable};
+use oxigraph::sparql::results::{
+    QueryResultsFormat, QueryResultsParser, ReaderQueryResultsParserOutput,
+};
+use spareval::{DefaultServiceHandler, QueryEvaluationError, QuerySolutionIter};
+use spargebra::Query;
+use spargebra::algebra::GraphPattern;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Solutions of a remote query: its variables and one row of values per solution
+type RemoteSolutions = (Arc<[Variable]>, Arc<[Vec<Option<Term>>]>);
+
+/// Client evaluating the `SERVICE` clauses of R2R queries against remote endpoints
+///
+/// The pattern of each clause is sent as a `SELECT *` query when a window reports.
+/// Results are cached per endpoint and query for `cache_ttl`, so remote knowledge
+/// that rarely changes isn't fetched again for every report.
+#[derive(Clone)]
+pub struct ServiceClient {
+    agent: ureq::Agent,
+    cache_ttl: Option<Duration>,
+    cache: Arc<Mutex<HashMap<(String, String), (Instant, RemoteSolutions)>>>,
+}
+
+impl ServiceClient {
+    /// Create a client giving up on requests after `timeout`, caching results for
+    /// `cache_ttl`, or not at all with `None`
+    pub fn new(timeout: Duration, cache_ttl: Option<Duration>) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            cache_ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the solutions of a query on an endpoint, from the cache if still fresh
+    fn solutions(&self, endpoint: &str, query: &str) -> io::Result<RemoteSolutions> {
+        let key = (endpoint.to_string(), query.to_string());
+        if let Some(ttl) = self.cache_ttl {
+            let mut cache = self.cache.lock().unwrap();
+            cache.retain(|_, (fetched, _)| fetched.elapsed() < ttl);
+            if let Some((_, solutions)) = cache.get(&key) {
+                return Ok(solutions.clone());
+            }
+        }
+
+        let solutions = self.fetch(endpoint, query)?;
+        if self.cache_ttl.is_some() {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(key, (Instant::now(), solutions.clone()));
+        }
+        Ok(solutions)
+    }
+
+    /// Send a query to an endpoint and read its JSON results
+    fn fetch(&self, endpoint: &str, query: &str) -> io::Result<RemoteSolutions> {
+        let response = self
+            .agent
+            .get(endpoint)
+            .query("query", query)
+            .set("Accept", "application/sparql-results+json")
+            .call()
+            .map_err(|e| io::Error::other(format!("SERVICE <{}> failed: {}", endpoint, e)))?;
+
+        let output = QueryResultsParser::from_format(QueryResultsFormat::Json)
+            .for_reader(response.into_reader())
+            .map_err(io::Error::other)?;
+        let ReaderQueryResultsParserOutput::Solutions(parser) = output else {
+            return Err(io::Error::other(format!(
+                "SERVICE <{}> returned a boolean instead of solutions",
+                endpoint
+            )));
+        };
+        let variables: Arc<[Variable]> = parser.variables().into();
+        let rows = parser
+            .map(|solution| {
+                solution
+                    .map(|solution| solution.values().to_vec())
+                    .map_err(io::Error::other)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok((variables, rows.into()))
+    }
+}
+
+impl DefaultServiceHandler for ServiceClient {
+    type Error = io::Error;
+
+    fn handle(
+        &self,
+        service_name: &NamedNode,
+        pattern: &GraphPattern,
+        _base_iri: Option<&str>,
+    ) -> Result<QuerySolutionIter<'static>, Self::Error> {
+        let query = Query::Select {
+            dataset: None,
+            pattern: pattern.clone(),
+            base_iri: None,
+        };
+        let (variables, rows) = self.solutions(service_name.as_str(), &query.to_string())?;
+        let rows = (0..rows.len()).map(move |i| Ok::<_, QueryEvaluationError>(rows[i].clone()));
+        Ok(QuerySolutionIter::new(variables, rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_test_server::{Response, serve};
+    use crate::{QuadContainer, R2ROperator};
+    use oxigraph::model::{GraphName, Quad};
+    use oxigraph::sparql::QueryResults;
+
+    #[test]
+    fn test_service_enriches_window_content() {
+        let results = r#"{"head":{"vars":["o","label"]},"results":{"bindings":[{"o":{"type":"uri","value":"http://example.org/room1"},"label":{"type":"literal","value":"Kitchen"}}]}}"#;
+        let (endpoint, _) = serve(
+            "/sparql",
+            vec![Response::ok("application/sparql-results+json", results)],
+        );
+        let query = format!(
+            "SELECT ?s ?label WHERE {{ GRAPH <http://example.org/w> {{ ?s <http://example.org/in> ?o }} SERVICE <{}> {{ ?o <http://www.w3.org/2000/01/rdf-schema#label> ?label }} }}",
+            endpoint
+        );
+        let operator = R2ROperator::with_service_client(
+            query,
+            ServiceClient::new(Duration::from_secs(5), Some(Duration::from_secs(60))),
+        );
+        let container = QuadContainer::new(
+            [Quad::new(
+                NamedNode::new("http://example.org/sensor1").unwrap(),
+                NamedNode::new("http://example.org/in").unwrap(),
+                NamedNode::new("http://example.org/room1").unwrap(),
+                GraphName::NamedNode(NamedNode::new("http://example.org/w").unwrap()),
+            )]
+            .into(),
+            0,
+        );
+
+        // The server answers once, the second report is served from the cache
+        for _ in 0..2 {
+            let QueryResults::Solutions(solutions) = operator.execute(&container).unwrap() else {
+                panic!("Expected solutions");
+            };
+            let solutions: Vec<_> = solutions.collect::<Result<_, _>>().unwrap();
+            assert_eq!(solutions.len(), 1);
+            assert_eq!(
+                solutions[0].get("label").unwrap().to_string(),
+                "\"Kitchen\""
+            );
+        }
+    }
+}
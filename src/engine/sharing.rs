@@ -0,0 +1,205 @@
+use crate::parsed_query::WindowDefinition;
+use crate::rsp_engine::WindowSender;
+use crate::{CSPARQLWindow, QuadContainer, QuadTtl, ReportStrategy, Tick, TimeZone};
+use oxigraph::model::NamedNode;
+use spargebra::algebra::GraphPattern;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Registry of the windows and window patterns shared by the engines configured
+/// with it, so queries over the same streams ingest and match their events once
+///
+/// A window declared by several queries with the same IRI, streams and settings is
+/// created by the first engine initialized, and reused by the others: each of them
+/// gets its reports, and its events may be added through the stream of any of
+/// them, once. Windows with a partition, a quad filter, a static lookup or
+/// incremental aggregates are never shared.
+///
+/// The `GRAPH` clauses of a window made of a basic graph pattern are evaluated once
+/// per report of the window, and their solutions joined with the rest of each
+/// query sharing the clause, see `RSPEngineBuilder::share_plans`.
+///
+/// ```rust,no_run
+/// use rsp_rs::{PlanSharing, RSPEngineBuilder};
+///
+/// let sharing = PlanSharing::new();
+/// let mut first = RSPEngineBuilder::new("...".to_string())
+///     .share_plans(&sharing)
+///     .build();
+/// let mut second = RSPEngineBuilder::new("...".to_string())
+///     .share_plans(&sharing)
+///     .build();
+/// first.initialize().unwrap();
+/// second.initialize().unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct PlanSharing(Arc<Registry>);
+
+#[derive(Default)]
+struct Registry {
+    /// Windows by their settings, dropped with the last engine using them
+    windows: Mutex<Vec<(WindowSpec, Weak<SharedWindow>)>>,
+    patterns: Arc<SharedPatterns>,
+}
+
+impl PlanSharing {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of windows shared by live engines
+    pub fn window_count(&self) -> usize {
+        let windows = self.0.windows.lock().unwrap();
+        windows
+            .iter()
+            .filter(|(_, window)| window.strong_count() > 0)
+            .count()
+    }
+
+    /// Get the window with these settings, if an engine still uses it
+    pub(crate) fn find(&self, spec: &WindowSpec) -> Option<Arc<SharedWindow>> {
+        let windows = self.0.windows.lock().unwrap();
+        windows
+            .iter()
+            .filter(|(shared, _)| shared == spec)
+            .find_map(|(_, window)| window.upgrade())
+    }
+
+    /// Make a window available to the engines initialized later
+    pub(crate) fn register(&self, spec: WindowSpec, window: SharedWindow) -> Arc<SharedWindow> {
+        let window = Arc::new(window);
+        let mut windows = self.0.windows.lock().unwrap();
+        windows.retain(|(_, window)| window.strong_count() > 0);
+        windows.push((spec, Arc::downgrade(&window)));
+        window
+    }
+
+    /// Get the solutions of the window patterns shared by the queries
+    pub(crate) fn patterns(&self) -> Arc<SharedPatterns> {
+        self.0.patterns.clone()
+    }
+}
+
+impl fmt::Debug for PlanSharing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PlanSharing")
+    }
+}
+
+/// Registries are compared by identity
+impl PartialEq for PlanSharing {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for PlanSharing {}
+
+/// Everything that sets a window up, as resolved by the engine creating it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct WindowSpec {
+    pub(crate) definition: WindowDefinition,
+    pub(crate) report: ReportStrategy,
+    pub(crate) tick: Tick,
+    pub(crate) start_time: i64,
+    pub(crate) origin: Option<i64>,
+    pub(crate) calendar: Option<TimeZone>,
+    pub(crate) emit_empty_windows: bool,
+    pub(crate) delay: i64,
+    pub(crate) max_lateness: Option<i64>,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) pane_aggregation: bool,
+    pub(crate) record_streams: bool,
+    pub(crate) multiset: bool,
+    pub(crate) ttl: Option<QuadTtl>,
+    /// Whether the window is applied on the producer's thread, and the capacity of
+    /// its channel otherwise
+    pub(crate) synchronous: bool,
+    pub(crate) channel_capacity: Option<usize>,
+}
+
+/// Window shared by engines, with the sender its streams feed
+pub(crate) struct SharedWindow {
+    pub(crate) window: Arc<Mutex<CSPARQLWindow>>,
+    pub(crate) sender: WindowSender,
+}
+
+/// Solutions of the patterns evaluated over the latest report of each window
+#[derive(Default)]
+pub(crate) struct SharedPatterns {
+    reports: Mutex<HashMap<NamedNode, LatestReport>>,
+}
+
+/// Report of a window, kept alive so it isn't mistaken for a later one, with the
+/// solutions of each pattern by its text, `None` if they can't be shared
+struct LatestReport {
+    content: Arc<QuadContainer>,
+    solutions: HashMap<String, Option<GraphPattern>>,
+}
+
+impl SharedPatterns {
+    /// Get the solutions of a pattern over a report of `window`, as a table, given
+    /// by `evaluate` unless a query sharing the pattern already evaluated it
+    /// The queries evaluating a report wait for each other, so the pattern is
+    /// evaluated once
+    pub(crate) fn solutions(
+        &self,
+        window: &NamedNode,
+        content: &Arc<QuadContainer>,
+        pattern: &GraphPattern,
+        evaluate: impl FnOnce() -> Option<GraphPattern>,
+    ) -> Option<GraphPattern> {
+        let mut reports = self.reports.lock().unwrap();
+        let report = reports
+            .entry(window.clone())
+            .or_insert_with(|| LatestReport {
+                content: content.clone(),
+                solutions: HashMap::new(),
+            });
+        if !Arc::ptr_eq(&report.content, content) {
+            report.content = content.clone();
+            report.solutions.clear();
+        }
+        let solutions = report.solutions.entry(pattern.to_string());
+        solutions.or_insert_with(evaluate).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spargebra::term::{GroundTerm, Literal, Variable};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_patterns_evaluated_once_per_report() {
+        let patterns = SharedPatterns::default();
+        let window = NamedNode::new_unchecked("http://example.org/w1");
+        let pattern = GraphPattern::Bgp {
+            patterns: Vec::new(),
+        };
+        let table = GraphPattern::Values {
+            variables: vec![Variable::new_unchecked("v")],
+            bindings: vec![vec![Some(GroundTerm::Literal(Literal::from(1)))]],
+        };
+        let evaluations = Mutex::new(0);
+        let evaluate = || {
+            *evaluations.lock().unwrap() += 1;
+            Some(table.clone())
+        };
+
+        let first = Arc::new(QuadContainer::new(HashSet::new(), 100));
+        let solutions = patterns.solutions(&window, &first, &pattern, evaluate);
+        assert_eq!(solutions, Some(table.clone()));
+        let solutions = patterns.solutions(&window, &first, &pattern, evaluate);
+        assert_eq!(solutions, Some(table.clone()));
+        assert_eq!(*evaluations.lock().unwrap(), 1);
+
+        // An equal report of the next instance is evaluated again
+        let second = Arc::new(QuadContainer::new(HashSet::new(), 100));
+        patterns.solutions(&window, &second, &pattern, evaluate);
+        assert_eq!(*evaluations.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::metrics::MetricsRecorder;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How an overloaded engine sheds load
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheddingStrategy {
+    /// Drop events with quads at random, each with this probability in percent;
+    /// events without quads still advance time
+    SampleInput { drop_percent: u8 },
+    /// Leave the reports of the windows unevaluated, without results
+    SkipEvaluations,
+    /// Evaluate only every Nth report of each window
+    EveryNthReport(u32),
+}
+
+/// When and how the engine sheds load, e.g. for edge deployments with bursty
+/// sensors where late results are worth less than degraded ones
+///
+/// The engine is overloaded while one of the thresholds is reached, and sheds load
+/// with its strategy until it isn't anymore. Shed events and reports are counted
+/// in the `EngineMetrics`, and shed events go to `RSPEngine::dead_letters`.
+///
+/// ```rust,no_run
+/// use rsp_rs::{LoadSheddingPolicy, RSPEngineBuilder, SheddingStrategy};
+///
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .load_shedding(LoadSheddingPolicy {
+///         max_queue_depth: Some(10_000),
+///         max_evaluation_backlog: Some(8),
+///         strategy: SheddingStrategy::EveryNthReport(4),
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSheddingPolicy {
+    /// Events added to streams but not applied to their window yet at which the
+    /// engine is overloaded
+    pub max_queue_depth: Option<u64>,
+    /// Reports waiting for their SPARQL evaluation at which the engine is overloaded
+    pub max_evaluation_backlog: Option<u64>,
+    /// How load is shed while overloaded
+    pub strategy: SheddingStrategy,
+}
+
+/// Load shedding of an engine, shared by its streams and window callbacks
+pub(crate) struct LoadShedder {
+    policy: LoadSheddingPolicy,
+    metrics: Arc<MetricsRecorder>,
+    /// Draws of the input sampling so far, seeding the next one
+    draws: AtomicU64,
+}
+
+impl LoadShedder {
+    pub(crate) fn new(policy: LoadSheddingPolicy, metrics: Arc<MetricsRecorder>) -> Self {
+        Self {
+            policy,
+            metrics,
+            draws: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the engine has reached one of the thresholds of the policy
+    pub(crate) fn is_overloaded(&self) -> bool {
+        let reached = |max: Option<u64>, value: u64| max.is_some_and(|max| value >= max);
+        reached(self.policy.max_queue_depth, self.metrics.queue_depth())
+            || reached(
+                self.policy.max_evaluation_backlog,
+                self.metrics.evaluation_backlog(),
+            )
+    }
+
+    /// Decide whether an event with `quads` quads is dropped, counting it if so
+    pub(crate) fn shed_event(&self, quads: usize) -> bool {
+        let SheddingStrategy::SampleInput { drop_percent } = self.policy.strategy else {
+            return false;
+        };
+        if quads == 0 || !self.is_overloaded() {
+            return false;
+        }
+        let draw = self.draws.fetch_add(1, Ordering::Relaxed);
+        let shed = splitmix64(draw) % 100 < u64::from(drop_percent);
+        if shed {
+            self.metrics.record_shed_event();
+        }
+        shed
+    }
+
+    /// Decide whether the `index`th report of a window (from 0) is left
+    /// unevaluated, counting it if so
+    pub(crate) fn shed_report(&self, index: u64) -> bool {
+        let shed = match self.policy.strategy {
+            SheddingStrategy::SampleInput { .. } => false,
+            SheddingStrategy::SkipEvaluations => self.is_overloaded(),
+            SheddingStrategy::EveryNthReport(n) => {
+                index % u64::from(n.max(1)) != 0 && self.is_overloaded()
+            }
+        };
+        if shed {
+            self.metrics.record_shed_report();
+        }
+        shed
+    }
+}
+
+/// Mix a counter into a uniformly distributed value (SplitMix64)
+fn splitmix64(counter: u64) -> u64 {
+    let mut z = counter.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_shedder(strategy: SheddingStrategy) -> (LoadShedder, Arc<MetricsRecorder>) {
+        let metrics = Arc::new(MetricsRecorder::default());
+        let policy = LoadSheddingPolicy {
+            max_queue_depth: Some(2),
+            max_evaluation_backlog: None,
+            strategy,
+        };
+        (LoadShedder::new(policy, metrics.clone()), metrics)
+    }
+
+    #[test]
+    fn test_input_is_sampled_while_overloaded() {
+        let (shedder, metrics) = new_shedder(SheddingStrategy::SampleInput { drop_percent: 50 });
+        metrics.record_queued();
+        assert!(!shedder.is_overloaded());
+        assert!(!shedder.shed_event(3));
+
+        metrics.record_queued();
+        assert!(shedder.is_overloaded());
+        let shed = (0..1000).filter(|_| shedder.shed_event(3)).count();
+        assert!((400..600).contains(&shed), "{}", shed);
+        assert!(!shedder.shed_event(0));
+        assert!(!shedder.shed_report(1));
+        assert_eq!(metrics.snapshot(0).events_shed, shed as u64);
+    }
+
+    #[test]
+    fn test_reports_are_thinned_while_overloaded() {
+        let (shedder, metrics) = new_shedder(SheddingStrategy::EveryNthReport(3));
+        assert!(!shedder.shed_report(1));
+        for _ in 0..2 {
+            metrics.record_queued();
+        }
+        let evaluated: Vec<u64> = (0..7).filter(|&i| !shedder.shed_report(i)).collect();
+        assert_eq!(evaluated, vec![0, 3, 6]);
+        assert!(!shedder.shed_event(3));
+
+        let (shedder, metrics) = new_shedder(SheddingStrategy::SkipEvaluations);
+        metrics.record_queued();
+        assert!(!shedder.shed_report(0));
+        metrics.record_queued();
+        assert!(shedder.shed_report(0));
+        metrics.record_ingested(1);
+        assert!(!shedder.shed_report(1));
+        assert_eq!(metrics.snapshot(0).reports_shed, 1);
+    }
+}
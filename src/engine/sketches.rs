@@ -0,0 +1,344 @@
+use crate::incremental::Numeric;
+use oxigraph::model::{Literal, NamedNode, Term};
+use oxigraph::sparql::SparqlEvaluator;
+use spareval::AggregateFunctionAccumulator;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Aggregate estimating the number of distinct values, e.g.
+/// `(rsp:approxCountDistinct(?device) AS ?devices)` with `PREFIX rsp: <https://rsp.rs/ns#>`
+pub const APPROX_COUNT_DISTINCT: &str = "https://rsp.rs/ns#approxCountDistinct";
+/// Aggregate estimating the median of numeric values
+pub const APPROX_MEDIAN: &str = "https://rsp.rs/ns#approxMedian";
+/// Aggregate estimating the 90th percentile of numeric values
+pub const APPROX_PERCENTILE_90: &str = "https://rsp.rs/ns#approxPercentile90";
+/// Aggregate estimating the 95th percentile of numeric values
+pub const APPROX_PERCENTILE_95: &str = "https://rsp.rs/ns#approxPercentile95";
+/// Aggregate estimating the 99th percentile of numeric values
+pub const APPROX_PERCENTILE_99: &str = "https://rsp.rs/ns#approxPercentile99";
+
+/// Percentile estimated by each percentile aggregate
+const PERCENTILES: [(&str, u8); 4] = [
+    (APPROX_MEDIAN, 50),
+    (APPROX_PERCENTILE_90, 90),
+    (APPROX_PERCENTILE_95, 95),
+    (APPROX_PERCENTILE_99, 99),
+];
+
+/// Bits of the hash selecting a register, for 4096 registers and a standard error
+/// of about 1.6%
+const PRECISION: u32 = 12;
+
+/// Compression of the digests of the percentile aggregates
+const COMPRESSION: f64 = 100.0;
+
+/// HyperLogLog sketch estimating the number of distinct values in constant memory
+/// (4 KiB), whatever their number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HyperLogLog {
+    /// Create an empty sketch
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << PRECISION],
+        }
+    }
+
+    /// Add a value to the sketch
+    pub fn insert<T: Hash + ?Sized>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // The sentinel bit bounds the rank when the remaining bits are all zero
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() + 1;
+        let register = &mut self.registers[index];
+        *register = (*register).max(rank as u8);
+    }
+
+    /// Add the values of another sketch, e.g. of another window partition
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    /// Estimate the number of distinct values added
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        // Linear counting is more accurate for small cardinalities
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// t-digest estimating the quantiles of numeric values in bounded memory
+///
+/// Values are kept as centroids (mean and weight), small near the extremes and
+/// larger around the median, so tail quantiles such as the 99th percentile stay
+/// accurate; digests of fewer values than their compression are exact.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    /// Centroids as mean and weight, by mean
+    centroids: Vec<(f64, f64)>,
+    /// Values not merged into the centroids yet
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// Create an empty digest keeping about `compression` centroids
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression: compression.max(1.0),
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Add a value to the digest, ignoring NaN
+    pub fn insert(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() as f64 >= 5.0 * self.compression {
+            self.centroids = self.merged();
+            self.buffer.clear();
+        }
+    }
+
+    /// Add the values of another digest, e.g. of an adjacent pane of a window
+    pub fn merge(&mut self, other: &TDigest) {
+        self.buffer.extend(other.buffer.iter().copied());
+        self.centroids.extend(other.centroids.iter().copied());
+        self.centroids = self.merged();
+        self.buffer.clear();
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Get the number of values added
+    pub fn count(&self) -> u64 {
+        let weight: f64 = self.centroids.iter().map(|(_, weight)| weight).sum();
+        weight as u64 + self.buffer.len() as u64
+    }
+
+    /// Estimate the value below which a fraction `q` (0 to 1) of the values fall,
+    /// interpolating between centroids; `None` for an empty digest
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let centroids = self.merged();
+        let total: f64 = centroids.iter().map(|(_, weight)| weight).sum();
+        let target = q.clamp(0.0, 1.0) * total;
+
+        // Position of the center of the previous centroid and its mean
+        let mut previous: Option<(f64, f64)> = None;
+        let mut seen = 0.0;
+        for &(mean, weight) in &centroids {
+            let center = seen + weight / 2.0;
+            if target < center {
+                return Some(match previous {
+                    Some((previous_center, previous_mean)) => {
+                        let fraction = (target - previous_center) / (center - previous_center);
+                        previous_mean + (mean - previous_mean) * fraction
+                    }
+                    None => self.min + (mean - self.min) * (target / center),
+                });
+            }
+            previous = Some((center, mean));
+            seen += weight;
+        }
+        let (center, mean) = previous?;
+        Some(mean + (self.max - mean) * (target - center) / (total - center))
+    }
+
+    /// Get the centroids with the buffered values merged in
+    fn merged(&self) -> Vec<(f64, f64)> {
+        let mut points = self.centroids.clone();
+        points.extend(self.buffer.iter().map(|&value| (value, 1.0)));
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let total: f64 = points.iter().map(|(_, weight)| weight).sum();
+
+        let mut merged: Vec<(f64, f64)> = Vec::with_capacity(points.len());
+        // Weight of the centroids before the last merged one
+        let mut seen = 0.0;
+        for (mean, weight) in points {
+            if let Some(last) = merged.last_mut() {
+                let combined = last.1 + weight;
+                let q = (seen + combined / 2.0) / total;
+                // Centroids may grow with their distance to the extremes
+                if combined <= 4.0 * total * q * (1.0 - q) / self.compression {
+                    last.0 += (mean - last.0) * weight / combined;
+                    last.1 = combined;
+                    continue;
+                }
+                seen += last.1;
+            }
+            merged.push((mean, weight));
+        }
+        merged
+    }
+}
+
+/// Running value of an approximate aggregate, evaluated by oxigraph or maintained
+/// incrementally by a window
+#[derive(Debug, Clone)]
+pub(crate) enum Sketch {
+    CountDistinct(HyperLogLog),
+    /// Digest of the values and the quantile estimated
+    Percentile(TDigest, f64),
+}
+
+impl Sketch {
+    pub(crate) fn count_distinct() -> Self {
+        Sketch::CountDistinct(HyperLogLog::new())
+    }
+
+    pub(crate) fn percentile(percent: u8) -> Self {
+        Sketch::Percentile(TDigest::new(COMPRESSION), f64::from(percent) / 100.0)
+    }
+
+    /// Add a value; percentiles ignore non-numeric values
+    pub(crate) fn insert(&mut self, term: &Term) {
+        match self {
+            Sketch::CountDistinct(sketch) => sketch.insert(term),
+            Sketch::Percentile(digest, _) => {
+                if let Some(value) = Numeric::from_term(term).and_then(Numeric::to_f64) {
+                    digest.insert(value);
+                }
+            }
+        }
+    }
+
+    /// Add the values of another sketch of the same aggregate
+    /// Returns false for sketches of different aggregates
+    pub(crate) fn merge(&mut self, other: &Sketch) -> bool {
+        match (self, other) {
+            (Sketch::CountDistinct(sketch), Sketch::CountDistinct(other)) => sketch.merge(other),
+            (Sketch::Percentile(digest, _), Sketch::Percentile(other, _)) => digest.merge(other),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Get the estimate, unbound for the percentile of no values
+    pub(crate) fn finish(&self) -> Option<Term> {
+        match self {
+            Sketch::CountDistinct(sketch) => {
+                Some(Literal::from(i64::try_from(sketch.estimate()).unwrap_or(i64::MAX)).into())
+            }
+            Sketch::Percentile(digest, q) => digest.quantile(*q).map(|v| Literal::from(v).into()),
+        }
+    }
+}
+
+impl AggregateFunctionAccumulator for Sketch {
+    fn accumulate(&mut self, element: Term) {
+        self.insert(&element);
+    }
+
+    fn finish(&mut self) -> Option<Term> {
+        Sketch::finish(self)
+    }
+}
+
+/// Get the percentile estimated by an approximate percentile aggregate
+pub(crate) fn function_percentile(function: &str) -> Option<u8> {
+    PERCENTILES
+        .iter()
+        .find(|(name, _)| *name == function)
+        .map(|(_, percent)| *percent)
+}
+
+/// Make the approximate aggregates available to the queries of an evaluator
+pub(crate) fn with_approximate_aggregates(evaluator: SparqlEvaluator) -> SparqlEvaluator {
+    type Accumulator = Box<dyn AggregateFunctionAccumulator + Send + Sync>;
+    let mut evaluator = evaluator.with_custom_aggregate_function(
+        NamedNode::new_unchecked(APPROX_COUNT_DISTINCT),
+        || -> Accumulator { Box::new(Sketch::count_distinct()) },
+    );
+    for (function, percent) in PERCENTILES {
+        evaluator = evaluator.with_custom_aggregate_function(
+            NamedNode::new_unchecked(function),
+            move || -> Accumulator { Box::new(Sketch::percentile(percent)) },
+        );
+    }
+    evaluator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distinct_count_estimate() {
+        let mut sketch = HyperLogLog::new();
+        assert_eq!(sketch.estimate(), 0);
+        for i in 0..10 {
+            sketch.insert(&(i % 5));
+        }
+        assert_eq!(sketch.estimate(), 5);
+
+        for i in 0..100_000 {
+            sketch.insert(&format!("device{}", i));
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!(
+            (estimate - 100_005.0).abs() / 100_005.0 < 0.05,
+            "{}",
+            estimate
+        );
+
+        let mut other = HyperLogLog::new();
+        other.insert(&"device0");
+        other.merge(&sketch);
+        assert_eq!(other, sketch);
+    }
+
+    #[test]
+    fn test_quantile_estimate() {
+        let mut digest = TDigest::new(COMPRESSION);
+        assert_eq!(digest.quantile(0.5), None);
+        for value in [50.0, 10.0, 40.0, 20.0, 30.0] {
+            digest.insert(value);
+        }
+        // Small digests are exact
+        assert_eq!(digest.quantile(0.0), Some(10.0));
+        assert_eq!(digest.quantile(0.5), Some(30.0));
+        assert_eq!(digest.quantile(1.0), Some(50.0));
+
+        let mut digest = TDigest::new(COMPRESSION);
+        for i in 0..100_000 {
+            digest.insert(f64::from((i * 7919) % 100_000));
+        }
+        assert_eq!(digest.count(), 100_000);
+        for (q, expected) in [(0.5, 50_000.0), (0.99, 99_000.0)] {
+            let estimate = digest.quantile(q).unwrap();
+            assert!((estimate - expected).abs() < 1000.0, "{}: {}", q, estimate);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::model::{NamedNode, Quad};
+use tracing::debug;
+
+/// Media types accepted when dereferencing a static graph, by preference
+const ACCEPT: &str = "text/turtle, application/n-triples;q=0.9, application/rdf+xml;q=0.8";
+
+/// Fetch the RDF document at a graph IRI, returning its triples in that graph
+/// Quads of named graphs in dataset formats keep their own graph
+pub(crate) fn dereference(agent: &ureq::Agent, graph: &NamedNode) -> Result<Vec<Quad>, String> {
+    let response = agent
+        .get(graph.as_str())
+        .set("Accept", ACCEPT)
+        .call()
+        .map_err(|e| format!("Failed to dereference static graph <{}>: {}", graph, e))?;
+    // Servers without a proper content type are assumed to serve Turtle
+    let format = RdfFormat::from_media_type(response.content_type()).unwrap_or(RdfFormat::Turtle);
+    debug!(graph = %graph, ?format, "Dereferencing static graph");
+
+    RdfParser::from_format(format)
+        .with_base_iri(graph.as_str())
+        .map_err(|e| format!("Invalid static graph IRI <{}>: {}", graph, e))?
+        .with_default_graph(graph.clone())
+        .for_reader(response.into_reader())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse static graph <{}>: {}", graph, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_test_server::{Response, serve};
+
+    #[test]
+    fn test_dereference_turtle() {
+        let document = "<#sensor1> <http://example.org/room> <#kitchen> .";
+        let (iri, _) = serve("/graph", vec![Response::ok("text/turtle", document)]);
+        let graph = NamedNode::new(&iri).unwrap();
+        let quads = dereference(&ureq::Agent::new(), &graph).unwrap();
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].graph_name, graph.clone().into());
+        // Relative IRIs are resolved against the graph IRI
+        assert_eq!(quads[0].subject.to_string(), format!("<{}#sensor1>", iri));
+
+        let missing = NamedNode::new("http://127.0.0.1:1/graph").unwrap();
+        assert!(dereference(&ureq::Agent::new(), &missing).is_err());
+    }
+}
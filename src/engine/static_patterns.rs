@@ -0,0 +1,226 @@
+use oxigraph::model::NamedNode;
+use oxigraph::sparql::PreparedSparqlQuery;
+use spargebra::algebra::GraphPattern;
+use spargebra::term::NamedNodePattern;
+use spargebra::{Query, SparqlParser};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+/// Query rewritten with the solutions of its static patterns, and its prepared form
+pub(crate) type CachedQuery = Arc<(String, PreparedSparqlQuery)>;
+
+/// Cache of the static-only part of a query, for one version of the static data
+pub(crate) struct StaticPatternCache {
+    /// Graphs holding window content, never static
+    pub(crate) windows: HashSet<NamedNode>,
+    /// Patterns with more solutions than this are evaluated on every report
+    pub(crate) max_solutions: usize,
+    /// Version of the static data and the rewritten query, `None` if no pattern
+    /// could be cached
+    pub(crate) cached: Mutex<Option<(u64, Option<CachedQuery>)>>,
+}
+
+impl StaticPatternCache {
+    pub(crate) fn new(windows: HashSet<NamedNode>, max_solutions: usize) -> Self {
+        Self {
+            windows,
+            max_solutions,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Forget the rewritten query, e.g. after the default graph changed
+    pub(crate) fn clear(&self) {
+        self.cached.lock().unwrap().take();
+    }
+}
+
+/// Clones keep the rewritten query, and compute their own after their static data
+/// diverged
+impl Clone for StaticPatternCache {
+    fn clone(&self) -> Self {
+        Self {
+            windows: self.windows.clone(),
+            max_solutions: self.max_solutions,
+            cached: Mutex::new(self.cached.lock().unwrap().clone()),
+        }
+    }
+}
+
+/// Replace the patterns of a query matching only static data with the table of
+/// their solutions, given by `solutions`, which may decline a pattern with `None`
+///
+/// Static patterns are the triple patterns and property paths of the default
+/// graph, and the `GRAPH` clauses of static graphs made only of those. Returns the
+/// rewritten query, or `None` if nothing was replaced or the default graph merges
+/// a window.
+pub(crate) fn replace_static_patterns(
+    query: &str,
+    windows: &HashSet<NamedNode>,
+    default_graphs: &[NamedNode],
+    mut solutions: impl FnMut(&GraphPattern) -> Option<GraphPattern>,
+) -> Option<String> {
+    if default_graphs.iter().any(|graph| windows.contains(graph)) {
+        return None;
+    }
+    let is_static = |pattern: &GraphPattern| match pattern {
+        GraphPattern::Bgp { patterns } => !patterns.is_empty(),
+        GraphPattern::Path { .. } => true,
+        GraphPattern::Graph {
+            name: NamedNodePattern::NamedNode(graph),
+            inner,
+        } => !windows.contains(graph) && is_static_in_graph(inner),
+        _ => false,
+    };
+    replace_patterns(query, is_static, &mut solutions)
+}
+
+/// Replace the `GRAPH` clauses of a window made of a basic graph pattern with the
+/// table of their solutions, given by `solutions`, which may decline a clause with
+/// `None`
+/// Returns the rewritten query, or `None` if nothing was replaced
+pub(crate) fn replace_window_patterns(
+    query: &str,
+    window: &NamedNode,
+    mut solutions: impl FnMut(&GraphPattern) -> Option<GraphPattern>,
+) -> Option<String> {
+    let is_window = |pattern: &GraphPattern| match pattern {
+        GraphPattern::Graph {
+            name: NamedNodePattern::NamedNode(graph),
+            inner,
+        } => {
+            graph == window
+                && matches!(&**inner, GraphPattern::Bgp { patterns } if !patterns.is_empty())
+        }
+        _ => false,
+    };
+    replace_patterns(query, is_window, &mut solutions)
+}
+
+/// Replace the patterns of a query selected by `selected` with their tables
+fn replace_patterns(
+    query: &str,
+    selected: impl Fn(&GraphPattern) -> bool,
+    solutions: &mut impl FnMut(&GraphPattern) -> Option<GraphPattern>,
+) -> Option<String> {
+    let mut query = SparqlParser::new().parse_query(query).ok()?;
+    let (Query::Select { pattern, .. }
+    | Query::Construct { pattern, .. }
+    | Query::Describe { pattern, .. }
+    | Query::Ask { pattern, .. }) = &mut query;
+    replace(pattern, &selected, solutions).then(|| query.to_string())
+}
+
+/// Replace the selected patterns in a pattern, returning whether any was replaced
+/// Tables are equivalent to the patterns they replace wherever they are, as
+/// patterns are evaluated bottom-up
+fn replace(
+    pattern: &mut GraphPattern,
+    selected: &impl Fn(&GraphPattern) -> bool,
+    solutions: &mut impl FnMut(&GraphPattern) -> Option<GraphPattern>,
+) -> bool {
+    if selected(pattern) {
+        let Some(table) = solutions(pattern) else {
+            return false;
+        };
+        *pattern = table;
+        return true;
+    }
+    match pattern {
+        GraphPattern::Join { left, right }
+        | GraphPattern::LeftJoin { left, right, .. }
+        | GraphPattern::Union { left, right }
+        | GraphPattern::Minus { left, right } => {
+            let left = replace(left, selected, solutions);
+            replace(right, selected, solutions) || left
+        }
+        GraphPattern::Filter { inner, .. }
+        | GraphPattern::Extend { inner, .. }
+        | GraphPattern::OrderBy { inner, .. }
+        | GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. }
+        | GraphPattern::Group { inner, .. } => replace(inner, selected, solutions),
+        // The patterns of other graphs match the graph given by their clause
+        _ => false,
+    }
+}
+
+/// Whether the pattern of a static `GRAPH` clause only matches that graph
+fn is_static_in_graph(pattern: &GraphPattern) -> bool {
+    match pattern {
+        GraphPattern::Bgp { .. } | GraphPattern::Path { .. } => true,
+        GraphPattern::Join { left, right } => is_static_in_graph(left) && is_static_in_graph(right),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spargebra::term::{GroundTerm, Literal, Variable};
+
+    fn table(pattern: &GraphPattern) -> Option<GraphPattern> {
+        // Decline the patterns with a literal, to keep them
+        if pattern.to_string().contains('"') {
+            return None;
+        }
+        Some(GraphPattern::Values {
+            variables: vec![Variable::new_unchecked("room")],
+            bindings: vec![vec![Some(GroundTerm::Literal(Literal::from(1)))]],
+        })
+    }
+
+    #[test]
+    fn test_replace_static_patterns() {
+        let windows: HashSet<_> = [NamedNode::new_unchecked("http://example.org/w1")].into();
+        let rewrite = |query: &str| replace_static_patterns(query, &windows, &[], table);
+        let query = "PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?room WHERE {
+                ?sensor ex:room ?room .
+                GRAPH ex:w1 { ?sensor ex:value ?v }
+                GRAPH ex:building { ?room ex:floor ?floor }
+                GRAPH ?g { ?room ex:label ?label }
+                OPTIONAL { ?sensor ex:name \"a\" }
+            }";
+        let rewritten = rewrite(query).unwrap();
+        assert!(!rewritten.contains("ex:room") && !rewritten.contains("<http://example.org/room>"));
+        assert!(!rewritten.contains("<http://example.org/floor>"));
+        assert!(rewritten.contains("<http://example.org/value>"));
+        assert!(rewritten.contains("<http://example.org/label>"));
+        assert!(rewritten.contains("<http://example.org/name>"));
+        assert_eq!(rewritten.matches("VALUES").count(), 2);
+
+        // Nothing is static
+        let query = "SELECT * WHERE { GRAPH <http://example.org/w1> { ?s ?p ?o } }";
+        assert_eq!(rewrite(query), None);
+        // Window content merged into the default graph
+        let default_graphs = [NamedNode::new_unchecked("http://example.org/w1")];
+        let query = "SELECT * WHERE { ?s ?p ?o }";
+        assert_eq!(
+            replace_static_patterns(query, &windows, &default_graphs, table),
+            None
+        );
+    }
+
+    #[test]
+    fn test_replace_window_patterns() {
+        let window = NamedNode::new_unchecked("http://example.org/w1");
+        let query = "PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?room WHERE {
+                GRAPH ex:w1 { ?sensor ex:value ?v }
+                GRAPH ex:w2 { ?sensor ex:value ?w }
+                ?sensor ex:room ?room .
+                FILTER(?v > 10)
+            }";
+        let rewritten = replace_window_patterns(query, &window, table).unwrap();
+        assert_eq!(rewritten.matches("VALUES").count(), 1);
+        assert!(rewritten.contains("<http://example.org/w2>"));
+        assert!(!rewritten.contains("<http://example.org/w1>"));
+        assert!(rewritten.contains("<http://example.org/room>"));
+
+        let other = NamedNode::new_unchecked("http://example.org/w3");
+        assert_eq!(replace_window_patterns(query, &other, table), None);
+    }
+}
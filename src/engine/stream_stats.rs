@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Event-time statistics of one stream, for tuning lateness settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Number of events (quad containers) ingested
+    pub events: u64,
+    /// Number of events older than an event that arrived before them
+    pub out_of_order_events: u64,
+    /// Largest distance in ms between an out-of-order event and the watermark
+    pub max_out_of_orderness: i64,
+    /// Number of events older than the last window report; the reports that
+    /// already happened miss them
+    pub late_events: u64,
+    /// Number of events dropped for exceeding the configured maximum lateness
+    pub dropped_events: u64,
+    /// Highest event timestamp seen so far
+    pub watermark: Option<i64>,
+    /// Wall-clock time in ms minus the watermark, when the last event arrived
+    pub watermark_lag: i64,
+}
+
+impl StreamStats {
+    /// Record an event arriving at wall-clock time `now`, while the window
+    /// last reported at `report_time`
+    pub fn record(&mut self, timestamp: i64, report_time: i64, now: i64) {
+        self.events += 1;
+
+        match self.watermark {
+            Some(watermark) if timestamp < watermark => {
+                self.out_of_order_events += 1;
+                self.max_out_of_orderness = self.max_out_of_orderness.max(watermark - timestamp);
+            }
+            _ => self.watermark = Some(timestamp),
+        }
+        if timestamp < report_time {
+            self.late_events += 1;
+        }
+        if let Some(watermark) = self.watermark {
+            self.watermark_lag = now.saturating_sub(watermark);
+        }
+    }
+
+    /// Add the statistics of the same stream in another engine, e.g. of a group
+    /// of queries; the lag is the one of the most advanced watermark
+    pub(crate) fn combine(&mut self, other: &StreamStats) {
+        self.events += other.events;
+        self.out_of_order_events += other.out_of_order_events;
+        self.max_out_of_orderness = self.max_out_of_orderness.max(other.max_out_of_orderness);
+        self.late_events += other.late_events;
+        self.dropped_events += other.dropped_events;
+        if other.watermark > self.watermark {
+            self.watermark = other.watermark;
+            self.watermark_lag = other.watermark_lag;
+        }
+    }
+
+    /// Copy the statistics of the streams of an engine, by stream IRI
+    pub(crate) fn snapshot(
+        stream_stats: &Mutex<HashMap<Arc<str>, StreamStats>>,
+    ) -> BTreeMap<String, StreamStats> {
+        stream_stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(stream, stats)| (stream.to_string(), stats.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_out_of_order_and_late_events() {
+        let mut stats = StreamStats::default();
+        stats.record(1000, 0, 5000);
+        stats.record(3000, 0, 5000);
+        stats.record(1500, 0, 5000);
+        stats.record(2000, 2500, 6000);
+
+        assert_eq!(stats.events, 4);
+        assert_eq!(stats.out_of_order_events, 2);
+        assert_eq!(stats.max_out_of_orderness, 1500);
+        assert_eq!(stats.late_events, 1);
+        assert_eq!(stats.watermark, Some(3000));
+        assert_eq!(stats.watermark_lag, 3000);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::events::{EngineEvent, EventPublisher};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use tracing::error;
+
+/// Panic caught in a thread of the engine, e.g. in a window callback, which keeps
+/// running instead of stalling its stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineError {
+    /// Name of the thread the panic happened on, e.g. `rsp-window-<window IRI>`
+    pub thread: String,
+    /// Message of the panic
+    pub message: String,
+}
+
+/// Subscribers to the errors of an engine, shared by its threads
+#[derive(Clone, Default)]
+pub(crate) struct ErrorChannel {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<EngineError>>>>,
+    /// Events of the engine, which also carry its errors
+    events: EventPublisher,
+}
+
+impl ErrorChannel {
+    /// Create a channel also publishing its errors as engine events
+    pub(crate) fn new(events: EventPublisher) -> Self {
+        Self {
+            subscribers: Arc::default(),
+            events,
+        }
+    }
+
+    /// Receive the errors reported from now on
+    pub(crate) fn subscribe(&self) -> mpsc::Receiver<EngineError> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Run `f`, catching and reporting its panic
+    pub(crate) fn catch<T>(&self, f: impl FnOnce() -> T) -> Result<T, EngineError> {
+        panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| self.report(payload))
+    }
+
+    /// Report the panic caught on the current thread to the subscribers still listening
+    fn report(&self, payload: Box<dyn Any + Send>) -> EngineError {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Unknown panic".to_string(),
+            },
+        };
+        let error = EngineError {
+            thread: thread::current().name().unwrap_or("unnamed").to_string(),
+            message,
+        };
+        error!(thread = %error.thread, "Recovered from a panic: {}", error.message);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(error.clone()).is_ok());
+        self.events
+            .publish(|| EngineEvent::CallbackPanicked(error.clone()));
+        error
+    }
+}
+
+/// Prefix of the names of the threads started outside an engine, e.g. by sinks,
+/// and of engine threads unless `RSPEngineBuilder::thread_name_prefix` is set
+pub(crate) const THREAD_NAME_PREFIX: &str = "rsp";
+
+/// Spawn a thread named `{prefix}-{role}`, e.g. `rsp-metrics`
+pub(crate) fn spawn_named<T: Send + 'static>(
+    prefix: &str,
+    role: &str,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> thread::JoinHandle<T> {
+    thread::Builder::new()
+        .name(format!("{}-{}", prefix, role))
+        .spawn(f)
+        .expect("Failed to spawn engine thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panics_are_reported_to_subscribers() {
+        let events = EventPublisher::new();
+        let published = events.subscribe();
+        let errors = ErrorChannel::new(events);
+        assert_eq!(errors.catch(|| 1), Ok(1));
+
+        let rx = errors.subscribe();
+        drop(errors.subscribe());
+        let handle = thread::Builder::new()
+            .name("rsp-test".to_string())
+            .spawn(move || {
+                let caught = errors.catch(|| panic!("callback failed at {}", 100));
+                (caught, errors.subscribers.lock().unwrap().len())
+            })
+            .unwrap();
+        let (caught, subscribers) = handle.join().unwrap();
+        let expected = EngineError {
+            thread: "rsp-test".to_string(),
+            message: "callback failed at 100".to_string(),
+        };
+        assert_eq!(caught, Err(expected.clone()));
+        assert_eq!(rx.try_recv(), Ok(expected.clone()));
+        let event = EngineEvent::CallbackPanicked(expected);
+        assert_eq!(published.try_recv(), Ok(event));
+        // The dropped receiver is forgotten
+        assert_eq!(subscribers, 1);
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(feature = "opentelemetry")]
+use opentelemetry::{
+    Context, KeyValue, global,
+    trace::{Span, TraceContextExt, Tracer},
+};
+
+/// Trace of one window report, from the window closing to its results being emitted
+///
+/// With the `opentelemetry` feature, every report gets a `window.close` span with
+/// the window name, report time and quad count, and `r2r.evaluate` and `result.emit`
+/// child spans, sent to the tracer provider installed globally by the application.
+/// Without the feature, it only runs the closures given to it.
+pub(crate) struct ReportSpan {
+    #[cfg(feature = "opentelemetry")]
+    context: Context,
+}
+
+impl ReportSpan {
+    /// Start the trace of a report of `window` at `timestamp` over `quads` quads
+    #[cfg_attr(not(feature = "opentelemetry"), allow(unused_variables))]
+    pub(crate) fn window_closed(window: &str, timestamp: i64, quads: usize) -> Self {
+        #[cfg(feature = "opentelemetry")]
+        {
+            let mut span = global::tracer("rsp-rs").start("window.close");
+            span.set_attribute(KeyValue::new("rsp.window", window.to_string()));
+            span.set_attribute(KeyValue::new("rsp.report_time", timestamp));
+            span.set_attribute(KeyValue::new("rsp.quads", quads as i64));
+            Self {
+                context: Context::current_with_span(span),
+            }
+        }
+        #[cfg(not(feature = "opentelemetry"))]
+        Self {}
+    }
+
+    /// Run the R2R evaluation of the report in a child span
+    pub(crate) fn evaluate<T>(&self, evaluation: impl FnOnce() -> T) -> T {
+        self.child("r2r.evaluate", None, evaluation)
+    }
+
+    /// Deliver the `results` results of the report in a child span, ending the trace
+    pub(crate) fn emit<T>(self, results: usize, delivery: impl FnOnce() -> T) -> T {
+        let delivered = self.child("result.emit", Some(results), delivery);
+        #[cfg(feature = "opentelemetry")]
+        self.context.span().end();
+        delivered
+    }
+
+    #[cfg_attr(not(feature = "opentelemetry"), allow(unused_variables))]
+    fn child<T>(&self, name: &'static str, results: Option<usize>, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "opentelemetry")]
+        {
+            let mut span = global::tracer("rsp-rs").start_with_context(name, &self.context);
+            if let Some(results) = results {
+                span.set_attribute(KeyValue::new("rsp.results", results as i64));
+            }
+            let value = f();
+            span.end();
+            value
+        }
+        #[cfg(not(feature = "opentelemetry"))]
+        f()
+    }
+}
@@ -0,0 +1,194 @@
+use crate::delta::Binding;
+use crate::quad_container::QuadContainer;
+use crate::sequence::{Bindings, match_event, parse_event_pattern, to_binding};
+use oxigraph::model::Quad;
+use spargebra::term::TriplePattern;
+use std::collections::BTreeMap;
+
+/// Pair of events of two streams matched by a `TemporalJoin`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JoinMatch {
+    /// Values of the variables of both sides
+    pub binding: Binding,
+    /// Timestamps of the left and the right event
+    pub timestamps: (i64, i64),
+}
+
+/// Join of the events of two streams of a window whose timestamps are at most a
+/// tolerance apart, e.g. the GPS and accelerometer readings of a vehicle within
+/// 100 ms, which joining the window patterns of both streams can't express
+///
+/// Each side is a basic graph pattern matched by the quads of a single event of
+/// its stream, i.e. the quads of the stream sharing a timestamp. Variables shared
+/// by both sides must have the same value. Events are only joined within a window
+/// instance, so sliding windows should step by at most their range minus the
+/// tolerance.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngine, TemporalJoin};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let join = TemporalJoin::new(100)
+///     .with_prefix("ex", "http://example.org/")
+///     .left("http://example.org/gps", "?vehicle ex:position ?position")
+///     .unwrap()
+///     .right("http://example.org/accelerometer", "?vehicle ex:acceleration ?a")
+///     .unwrap();
+/// let matches = engine
+///     .start_processing_joins("http://example.org/w1", join)
+///     .unwrap();
+/// for matched in matches {
+///     println!("{:?} at {:?}", matched.binding.get("vehicle"), matched.timestamps);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TemporalJoin {
+    tolerance: i64,
+    prefixes: Vec<(String, String)>,
+    left: Option<JoinSide>,
+    right: Option<JoinSide>,
+}
+
+/// Stream of one side of a join, and the pattern its events match
+#[derive(Debug, Clone)]
+struct JoinSide {
+    stream: String,
+    patterns: Vec<TriplePattern>,
+}
+
+impl TemporalJoin {
+    /// Create a join of events at most `tolerance` ms apart, in either order
+    pub fn new(tolerance: i64) -> Self {
+        Self {
+            tolerance: tolerance.max(0),
+            prefixes: Vec::new(),
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Declare a prefix for the sides set afterwards
+    pub fn with_prefix(mut self, prefix: &str, iri: &str) -> Self {
+        self.prefixes.push((prefix.to_string(), iri.to_string()));
+        self
+    }
+
+    /// Set the left side: the events of a stream, identified by its IRI, matching a
+    /// basic graph pattern in SPARQL syntax such as `?vehicle ex:position ?p`,
+    /// without filters or blank nodes
+    pub fn left(mut self, stream: &str, pattern: &str) -> Result<Self, String> {
+        self.left = Some(self.side(stream, pattern)?);
+        Ok(self)
+    }
+
+    /// Set the right side, like the left one
+    pub fn right(mut self, stream: &str, pattern: &str) -> Result<Self, String> {
+        self.right = Some(self.side(stream, pattern)?);
+        Ok(self)
+    }
+
+    fn side(&self, stream: &str, pattern: &str) -> Result<JoinSide, String> {
+        Ok(JoinSide {
+            stream: stream.to_string(),
+            patterns: parse_event_pattern(&self.prefixes, pattern, "Join side")?,
+        })
+    }
+
+    /// Get the maximum time (in ms) between the events of a match
+    pub fn tolerance(&self) -> i64 {
+        self.tolerance
+    }
+
+    /// Get the streams of the sides set so far
+    pub fn streams(&self) -> impl Iterator<Item = &str> {
+        let sides = self.left.iter().chain(&self.right);
+        sides.map(|side| side.stream.as_str())
+    }
+
+    /// Find every pair of events of a window report matching the sides
+    /// Quads without a timestamp or a stream, e.g. read back from a spill file or
+    /// added by a window not recording streams, are ignored
+    pub fn matches(&self, content: &QuadContainer) -> Vec<JoinMatch> {
+        let (Some(left), Some(right)) = (&self.left, &self.right) else {
+            return Vec::new();
+        };
+        let events = |side: &JoinSide| {
+            let mut events: BTreeMap<i64, Vec<&Quad>> = BTreeMap::new();
+            for quad in content.iter() {
+                if content.stream(quad) != Some(side.stream.as_str()) {
+                    continue;
+                }
+                if let Some(timestamp) = content.timestamp(quad) {
+                    events.entry(timestamp).or_default().push(quad);
+                }
+            }
+            events
+        };
+        let right_events = events(right);
+        let mut matches = Vec::new();
+        for (left_time, quads) in &events(left) {
+            for bindings in match_event(&left.patterns, quads, Bindings::new()) {
+                let nearby = left_time.saturating_sub(self.tolerance)
+                    ..=left_time.saturating_add(self.tolerance);
+                for (right_time, quads) in right_events.range(nearby) {
+                    for bindings in match_event(&right.patterns, quads, bindings.clone()) {
+                        matches.push(JoinMatch {
+                            binding: to_binding(&bindings),
+                            timestamps: (*left_time, *right_time),
+                        });
+                    }
+                }
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{GraphName, Literal, NamedNode};
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_join_events_within_tolerance() {
+        let node = |name: &str| NamedNode::new(format!("http://example.org/{}", name)).unwrap();
+        let gps: Arc<str> = Arc::from("http://example.org/gps");
+        let accelerometer: Arc<str> = Arc::from("http://example.org/accelerometer");
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        let mut reading = |stream: &Arc<str>, vehicle: &str, predicate: &str, ts: i64| {
+            let quad = Quad::new(
+                node(vehicle),
+                node(predicate),
+                Literal::from(ts),
+                GraphName::DefaultGraph,
+            );
+            container.add_from(quad, ts, Some(stream));
+        };
+        reading(&gps, "car1", "position", 1000);
+        reading(&accelerometer, "car1", "acceleration", 1080);
+        reading(&accelerometer, "car2", "acceleration", 1010);
+        reading(&accelerometer, "car1", "acceleration", 1200);
+        reading(&gps, "car1", "position", 1300);
+
+        let join = TemporalJoin::new(100)
+            .with_prefix("ex", "http://example.org/")
+            .left(&gps, "?vehicle ex:position ?position")
+            .unwrap()
+            .right(&accelerometer, "?vehicle ex:acceleration ?a")
+            .unwrap();
+        let streams: Vec<&str> = join.streams().collect();
+        assert_eq!(streams, vec![&*gps, &*accelerometer]);
+        let mut matches: Vec<(i64, i64)> = join
+            .matches(&container)
+            .iter()
+            .map(|matched| matched.timestamps)
+            .collect();
+        matches.sort();
+        // car2 is another vehicle, the reading at 1200 is 200 ms from either position
+        assert_eq!(matches, vec![(1000, 1080)]);
+        assert!(TemporalJoin::new(100).left(&gps, "?s ex:p").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use oxigraph::model::{
+    Dataset, GraphNameRef, NamedNode, NamedNodeRef, NamedOrBlankNodeRef, Quad, QuadRef, Term,
+    TermRef,
+};
+use oxigraph::store::{StorageError, Store};
+use spareval::{InternalQuad, QueryableDataset};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Static background data, indexed in memory or kept in an on-disk store
+#[derive(Clone)]
+pub(crate) enum StaticQuads {
+    /// Shared by clones until one of them changes it
+    Memory(Arc<Dataset>),
+    /// Shared by all clones, changes included
+    Store(Store),
+}
+
+impl StaticQuads {
+    /// Find the quads matching a pattern, with the same graph semantics as `quads_matching`
+    fn quads_matching(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        graph_name: Option<Option<&Term>>,
+    ) -> Result<Vec<InternalQuad<Term>>, StorageError> {
+        let store = match self {
+            StaticQuads::Memory(dataset) => {
+                let quads = quads_matching(dataset, subject, predicate, object, graph_name);
+                return Ok(quads.into_iter().map(internal_quad).collect());
+            }
+            StaticQuads::Store(store) => store,
+        };
+        let Some((subject, predicate, object, graph_name)) =
+            pattern(subject, predicate, object, graph_name)
+        else {
+            return Ok(Vec::new());
+        };
+        let mut quads = Vec::new();
+        for quad in store.quads_for_pattern(subject, predicate, object, graph_name) {
+            let quad = quad?;
+            if graph_name.is_some() || !quad.graph_name.is_default_graph() {
+                quads.push(internal_quad(quad.as_ref()));
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Count the quads matching a pattern, e.g. to estimate its selectivity
+    pub(crate) fn count_matching(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        graph_name: Option<Option<&Term>>,
+    ) -> usize {
+        match self {
+            StaticQuads::Memory(dataset) => {
+                quads_matching(dataset, subject, predicate, object, graph_name).len()
+            }
+            StaticQuads::Store(_) => self
+                .quads_matching(subject, predicate, object, graph_name)
+                .map_or(0, |quads| quads.len()),
+        }
+    }
+}
+
+/// Read-only union of the static background data and the content of one window report
+///
+/// The static dataset is built once and shared between evaluations, so only the
+/// window quads have to be indexed for each report.
+/// The graphs of `FROM` clauses are merged into the default graph, whether static
+/// graphs or windows.
+pub(crate) struct UnionDataset {
+    static_data: StaticQuads,
+    window: Dataset,
+    default_graphs: Arc<[NamedNode]>,
+    /// Number of events adding the window quads added by several, matched as many
+    /// times, for windows counting duplicates
+    repeated: HashMap<Quad, usize>,
+}
+
+impl UnionDataset {
+    /// Create the union view; `window` must not repeat quads from `static_data`
+    pub(crate) fn new(
+        static_data: StaticQuads,
+        window: Dataset,
+        default_graphs: Arc<[NamedNode]>,
+    ) -> Self {
+        Self {
+            static_data,
+            window,
+            default_graphs,
+            repeated: HashMap::new(),
+        }
+    }
+
+    /// Match the window quads added by several events once per event
+    pub(crate) fn with_repeated<'a>(
+        mut self,
+        repeated: impl IntoIterator<Item = (&'a Quad, usize)>,
+    ) -> Self {
+        for (quad, count) in repeated {
+            self.repeated.insert(quad.clone(), count);
+        }
+        self
+    }
+}
+
+/// Pattern of quads, as references
+type PatternRef<'a> = (
+    Option<NamedOrBlankNodeRef<'a>>,
+    Option<NamedNodeRef<'a>>,
+    Option<TermRef<'a>>,
+    Option<GraphNameRef<'a>>,
+);
+
+/// Turn the terms of a pattern into references of their positions
+/// `None` if no quad can match, e.g. with a literal subject
+fn pattern<'a>(
+    subject: Option<&'a Term>,
+    predicate: Option<&'a Term>,
+    object: Option<&'a Term>,
+    graph_name: Option<Option<&'a Term>>,
+) -> Option<PatternRef<'a>> {
+    let subject = match subject.map(Term::as_ref) {
+        None => None,
+        Some(TermRef::NamedNode(s)) => Some(NamedOrBlankNodeRef::from(s)),
+        Some(TermRef::BlankNode(s)) => Some(s.into()),
+        Some(_) => return None,
+    };
+    let predicate = match predicate.map(Term::as_ref) {
+        None => None,
+        Some(TermRef::NamedNode(p)) => Some(p),
+        Some(_) => return None,
+    };
+    let object = object.map(Term::as_ref);
+    let graph_name = match graph_name {
+        None => None,
+        Some(None) => Some(GraphNameRef::DefaultGraph),
+        Some(Some(g)) => match g.as_ref() {
+            TermRef::NamedNode(g) => Some(g.into()),
+            TermRef::BlankNode(g) => Some(g.into()),
+            _ => return None,
+        },
+    };
+    Some((subject, predicate, object, graph_name))
+}
+
+/// Find the quads of one dataset matching a pattern, using the most selective index
+/// A `None` graph name matches all named graphs, as spareval expects
+fn quads_matching<'a>(
+    dataset: &'a Dataset,
+    subject: Option<&Term>,
+    predicate: Option<&Term>,
+    object: Option<&Term>,
+    graph_name: Option<Option<&Term>>,
+) -> Vec<QuadRef<'a>> {
+    let Some((subject, predicate, object, graph_name)) =
+        pattern(subject, predicate, object, graph_name)
+    else {
+        return Vec::new();
+    };
+
+    let matches = |quad: &QuadRef<'_>| {
+        subject.is_none_or(|s| s == quad.subject)
+            && predicate.is_none_or(|p: NamedNodeRef<'_>| p == quad.predicate)
+            && object.is_none_or(|o| o == quad.object)
+            && graph_name.map_or_else(
+                || !quad.graph_name.is_default_graph(),
+                |g| g == quad.graph_name,
+            )
+    };
+
+    if let Some(s) = subject {
+        dataset.quads_for_subject(s).filter(matches).collect()
+    } else if let Some(o) = object {
+        dataset.quads_for_object(o).filter(matches).collect()
+    } else if let Some(p) = predicate {
+        dataset.quads_for_predicate(p).filter(matches).collect()
+    } else if let Some(g) = graph_name {
+        dataset.quads_for_graph_name(g).filter(matches).collect()
+    } else {
+        dataset.iter().filter(matches).collect()
+    }
+}
+
+fn internal_quad(quad: QuadRef<'_>) -> InternalQuad<Term> {
+    InternalQuad {
+        subject: quad.subject.into_owned().into(),
+        predicate: quad.predicate.into_owned().into(),
+        object: quad.object.into_owned(),
+        graph_name: match quad.graph_name {
+            GraphNameRef::NamedNode(g) => Some(g.into_owned().into()),
+            GraphNameRef::BlankNode(g) => Some(g.into_owned().into()),
+            GraphNameRef::DefaultGraph => None,
+        },
+    }
+}
+
+/// Get the triple of a quad, to compare quads regardless of their graph
+fn triple(quad: &InternalQuad<Term>) -> (Term, Term, Term) {
+    (
+        quad.subject.clone(),
+        quad.predicate.clone(),
+        quad.object.clone(),
+    )
+}
+
+impl UnionDataset {
+    /// Find the quads of the static data and the window matching a pattern
+    fn matching(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        graph_name: Option<Option<&Term>>,
+    ) -> Result<Vec<InternalQuad<Term>>, StorageError> {
+        let mut quads = self
+            .static_data
+            .quads_matching(subject, predicate, object, graph_name)?;
+        let window = quads_matching(&self.window, subject, predicate, object, graph_name);
+        for quad in window {
+            // Reports without duplicates don't pay for copying their quads
+            let count = match self.repeated.is_empty() {
+                true => None,
+                false => self.repeated.get(&quad.into_owned()).copied(),
+            };
+            for _ in 0..count.unwrap_or(1) {
+                quads.push(internal_quad(quad));
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Find the quads matching a pattern, with the `FROM` graphs merged into the
+    /// default graph
+    fn all_matching(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        graph_name: Option<Option<&Term>>,
+    ) -> Result<Vec<InternalQuad<Term>>, StorageError> {
+        let mut quads = self.matching(subject, predicate, object, graph_name)?;
+        if graph_name == Some(None) && !self.default_graphs.is_empty() {
+            // The default graph is the RDF merge of its own triples and those of the
+            // `FROM` graphs, so a triple found in several of them is matched once
+            let mut seen: HashSet<_> = quads.iter().map(triple).collect();
+            for graph in self.default_graphs.iter() {
+                let graph = Term::from(graph.clone());
+                let matching = self.matching(subject, predicate, object, Some(Some(&graph)))?;
+                for mut quad in matching {
+                    if seen.insert(triple(&quad)) {
+                        quad.graph_name = None;
+                        quads.push(quad);
+                    }
+                }
+            }
+        }
+        Ok(quads)
+    }
+}
+
+impl QueryableDataset<'static> for UnionDataset {
+    type InternalTerm = Term;
+    type Error = StorageError;
+
+    fn internal_quads_for_pattern(
+        &self,
+        subject: Option<&Term>,
+        predicate: Option<&Term>,
+        object: Option<&Term>,
+        graph_name: Option<Option<&Term>>,
+    ) -> impl Iterator<Item = Result<InternalQuad<Term>, StorageError>> + use<> {
+        let quads: Vec<_> = match self.all_matching(subject, predicate, object, graph_name) {
+            Ok(quads) => quads.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        quads.into_iter()
+    }
+
+    fn internalize_term(&self, term: Term) -> Result<Term, StorageError> {
+        Ok(term)
+    }
+
+    fn externalize_term(&self, term: Term) -> Result<Term, StorageError> {
+        Ok(term)
+    }
+}
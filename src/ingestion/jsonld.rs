@@ -0,0 +1,36 @@
+use oxigraph::io::{JsonLdProfileSet, RdfFormat, RdfParser};
+use oxigraph::model::Quad;
+
+/// Parse a JSON-LD document describing a single event into quads
+/// Remote contexts are not fetched, so documents must use inline contexts
+pub fn parse_jsonld(document: &str) -> Result<Vec<Quad>, String> {
+    RdfParser::from_format(RdfFormat::JsonLd {
+        profile: JsonLdProfileSet::empty(),
+    })
+    .for_slice(document.as_bytes())
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to parse JSON-LD event: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_jsonld() {
+        let document = r#"{
+            "@context": { "ex": "http://example.org/" },
+            "@id": "ex:sensor1",
+            "ex:value": 42
+        }"#;
+
+        let quads = parse_jsonld(document).unwrap();
+        assert_eq!(quads.len(), 1);
+        assert_eq!(quads[0].subject.to_string(), "<http://example.org/sensor1>");
+    }
+
+    #[test]
+    fn test_parse_invalid_jsonld() {
+        assert!(parse_jsonld("{ not json").is_err());
+    }
+}
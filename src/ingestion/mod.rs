@@ -0,0 +1,5 @@
+pub mod jsonld;
+pub mod rate_limit;
+pub mod rdf;
+pub mod shacl;
+pub mod timestamp;
@@ -0,0 +1,183 @@
+use crate::metrics::MetricsRecorder;
+use crate::timestamp::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What a stream does with the events over its rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Block the producer adding the event until the stream is back under its rate,
+    /// like a full bounded channel would
+    Delay,
+    /// Drop the events over the rate
+    Drop,
+    /// Keep every Nth event over the rate, dropping the others
+    Sample(u32),
+}
+
+/// Maximum rate at which events are added to a stream, protecting an engine
+/// shared by several producers from a runaway one
+///
+/// Each limit is a token bucket refilled continuously at its rate and holding one
+/// second of it, so short bursts pass. Events without quads only advance time and
+/// are never limited; with micro-batching, a batch counts as one event. Dropped
+/// events are counted in the `EngineMetrics` and go to `RSPEngine::dead_letters`.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngineBuilder, RateLimit, RateLimitAction};
+///
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .stream_rate_limit(
+///         "http://example.org/stream1",
+///         RateLimit {
+///             max_quads_per_second: Some(10_000),
+///             max_events_per_second: Some(100),
+///             action: RateLimitAction::Drop,
+///         },
+///     )
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Quads added to the stream per second
+    pub max_quads_per_second: Option<u64>,
+    /// Events (or micro-batches) added to the stream per second
+    pub max_events_per_second: Option<u64>,
+    /// What happens to the events over the rate
+    pub action: RateLimitAction,
+}
+
+/// Token buckets of a rate-limited stream, shared by its clones
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    metrics: Arc<MetricsRecorder>,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    /// Tokens left for quads and events, negative while delayed producers wait
+    quads: f64,
+    events: f64,
+    refilled: Instant,
+    /// Events over the rate so far, for sampling
+    excess: u64,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit, metrics: Arc<MetricsRecorder>) -> Self {
+        let full = |rate: Option<u64>| rate.map_or(0.0, |rate| rate.max(1) as f64);
+        Self {
+            limit,
+            metrics,
+            buckets: Mutex::new(Buckets {
+                quads: full(limit.max_quads_per_second),
+                events: full(limit.max_events_per_second),
+                refilled: Instant::now(),
+                excess: 0,
+            }),
+        }
+    }
+
+    /// Decide whether an event with `quads` quads is sent to its window, blocking
+    /// the caller first when delaying, and counting it if dropped
+    pub(crate) fn admit(&self, quads: usize) -> bool {
+        if quads == 0 {
+            return true;
+        }
+        match self.acquire(quads, Instant::now()) {
+            Some(wait) => {
+                if !wait.is_zero() {
+                    std::thread::sleep(wait);
+                }
+                true
+            }
+            None => {
+                self.metrics.record_rate_limited_event();
+                false
+            }
+        }
+    }
+
+    /// Take the tokens of an event arriving at `now`, returning how long its
+    /// producer waits for them, or `None` if the event is dropped
+    fn acquire(&self, quads: usize, now: Instant) -> Option<Duration> {
+        let buckets = &mut *self.buckets.lock().unwrap();
+        let elapsed = now.saturating_duration_since(buckets.refilled);
+        buckets.refilled = buckets.refilled.max(now);
+        let RateLimit {
+            max_quads_per_second,
+            max_events_per_second,
+            action,
+        } = self.limit;
+        let mut limits = [
+            (max_quads_per_second, &mut buckets.quads, quads as f64),
+            (max_events_per_second, &mut buckets.events, 1.0),
+        ];
+        let mut wait: f64 = 0.0;
+        for (rate, tokens, cost) in &mut limits {
+            let Some(rate) = rate.map(|rate| rate.max(1) as f64) else {
+                continue;
+            };
+            **tokens = (**tokens + elapsed.as_secs_f64() * rate).min(rate);
+            // An event larger than the bucket only waits for it to be full
+            *cost = cost.min(rate);
+            wait = wait.max((*cost - **tokens) / rate);
+        }
+        if wait > 0.0 {
+            match action {
+                RateLimitAction::Delay => {}
+                RateLimitAction::Drop => return None,
+                RateLimitAction::Sample(n) => {
+                    buckets.excess += 1;
+                    let kept = (buckets.excess - 1) % u64::from(n.max(1)) == 0;
+                    return kept.then_some(Duration::ZERO);
+                }
+            }
+        }
+        for (rate, tokens, cost) in limits {
+            if rate.is_some() {
+                *tokens -= cost;
+            }
+        }
+        Some(Duration::from_secs_f64(wait))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(quads: Option<u64>, events: Option<u64>, action: RateLimitAction) -> RateLimiter {
+        let limit = RateLimit {
+            max_quads_per_second: quads,
+            max_events_per_second: events,
+            action,
+        };
+        RateLimiter::new(limit, Arc::new(MetricsRecorder::default()))
+    }
+
+    #[test]
+    fn test_rate_limit_actions() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        let dropping = limiter(None, Some(2), RateLimitAction::Drop);
+        assert_eq!(dropping.acquire(1, at(0)), Some(Duration::ZERO));
+        assert_eq!(dropping.acquire(1, at(0)), Some(Duration::ZERO));
+        assert_eq!(dropping.acquire(1, at(0)), None);
+        // Half a second refills one event
+        assert_eq!(dropping.acquire(1, at(500)), Some(Duration::ZERO));
+        assert_eq!(dropping.acquire(1, at(500)), None);
+
+        let delaying = limiter(Some(10), None, RateLimitAction::Delay);
+        assert_eq!(delaying.acquire(10, at(0)), Some(Duration::ZERO));
+        assert_eq!(delaying.acquire(5, at(0)), Some(Duration::from_millis(500)));
+        assert_eq!(delaying.acquire(5, at(0)), Some(Duration::from_secs(1)));
+
+        let sampling = limiter(None, Some(1), RateLimitAction::Sample(2));
+        let kept: Vec<bool> = (0..5)
+            .map(|_| sampling.acquire(1, at(0)).is_some())
+            .collect();
+        assert_eq!(kept, vec![true, true, false, true, false]);
+    }
+}
@@ -0,0 +1,45 @@
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::model::Quad;
+
+/// Parse an RDF document describing a single event into quads
+/// Triple terms and annotations (Turtle-star, N-Triples-star) are read with the
+/// `rdf-star` feature, and are syntax errors without it
+pub fn parse_rdf(document: &str, format: RdfFormat) -> Result<Vec<Quad>, String> {
+    RdfParser::from_format(format)
+        .for_slice(document.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse {} event: {}", format.name(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rdf() {
+        let document = "<http://example.org/sensor1> <http://example.org/value> 42 .";
+        let quads = parse_rdf(document, RdfFormat::NTriples).unwrap();
+        assert_eq!(quads.len(), 1);
+        assert!(parse_rdf("<http://example.org/sensor1> .", RdfFormat::Turtle).is_err());
+    }
+
+    #[cfg(feature = "rdf-star")]
+    #[test]
+    fn test_parse_turtle_star() {
+        use oxigraph::model::Term;
+
+        let document = r#"
+            PREFIX ex: <http://example.org/>
+            ex:sensor1 ex:value 42 {| ex:confidence 0.9 |} .
+            ex:sensor2 ex:states <<( ex:sensor1 ex:value 42 )>> .
+        "#;
+        let quads = parse_rdf(document, RdfFormat::Turtle).unwrap();
+        // The asserted triple, the reifier with its confidence, and the triple term
+        assert_eq!(quads.len(), 4);
+        let triple_terms = quads
+            .iter()
+            .filter(|quad| matches!(quad.object, Term::Triple(_)))
+            .count();
+        assert_eq!(triple_terms, 2);
+    }
+}
@@ -0,0 +1,792 @@
+use crate::dead_letter::{DeadLetter, DeadLetters, DropReason};
+use crate::rdf::parse_rdf;
+use oxigraph::io::RdfFormat;
+use oxigraph::model::vocab::{rdf, xsd};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedNodeRef, Quad, Term};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use tracing::debug;
+
+const SH: &str = "http://www.w3.org/ns/shacl#";
+
+/// SHACL predicates of node shapes within the supported subset
+const NODE_SHAPE_PREDICATES: [&str; 5] = [
+    "targetClass",
+    "targetNode",
+    "targetSubjectsOf",
+    "targetObjectsOf",
+    "property",
+];
+
+/// SHACL predicates of property shapes within the supported subset
+const PROPERTY_SHAPE_PREDICATES: [&str; 12] = [
+    "path",
+    "minCount",
+    "maxCount",
+    "datatype",
+    "class",
+    "nodeKind",
+    "in",
+    "pattern",
+    "minInclusive",
+    "maxInclusive",
+    "minExclusive",
+    "maxExclusive",
+];
+
+/// SHACL predicates describing shapes without constraining the data
+const ANNOTATION_PREDICATES: [&str; 6] = [
+    "name",
+    "description",
+    "message",
+    "order",
+    "group",
+    "defaultValue",
+];
+
+/// Datatypes whose literals are compared by numeric range constraints
+const NUMERIC_DATATYPES: [NamedNodeRef<'static>; 16] = [
+    xsd::INTEGER,
+    xsd::DECIMAL,
+    xsd::FLOAT,
+    xsd::DOUBLE,
+    xsd::LONG,
+    xsd::INT,
+    xsd::SHORT,
+    xsd::BYTE,
+    xsd::NON_NEGATIVE_INTEGER,
+    xsd::POSITIVE_INTEGER,
+    xsd::NON_POSITIVE_INTEGER,
+    xsd::NEGATIVE_INTEGER,
+    xsd::UNSIGNED_LONG,
+    xsd::UNSIGNED_INT,
+    xsd::UNSIGNED_SHORT,
+    xsd::UNSIGNED_BYTE,
+];
+
+/// What a stream does with the events violating its shapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationPolicy {
+    /// Drop the event, adding it to the stream fails; it is still sent to the
+    /// dead letters of the engine
+    #[default]
+    Reject,
+    /// Drop the event silently, only sending it to the dead letters of the engine,
+    /// see `RSPEngine::dead_letters`
+    Quarantine,
+    /// Keep the event, adding a `sh:ValidationResult` to its quads for every
+    /// violation, e.g. for queries to skip the focus nodes of invalid readings
+    Annotate,
+}
+
+/// Violation of a constraint of a shape by a node of an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaclViolation {
+    /// Node the shape targets
+    pub focus_node: Term,
+    /// Predicate of the property shape violated, if any
+    pub path: Option<NamedNode>,
+    /// Node shape the constraint belongs to
+    pub shape: Term,
+    /// Description of the violation
+    pub message: String,
+}
+
+impl ShaclViolation {
+    /// Describe the violation as a `sh:ValidationResult` in the default graph
+    pub fn to_quads(&self) -> Vec<Quad> {
+        let result = BlankNode::default();
+        let quad = |predicate: &str, object: Term| {
+            Quad::new(
+                result.clone(),
+                NamedNode::new_unchecked(format!("{}{}", SH, predicate)),
+                object,
+                GraphName::DefaultGraph,
+            )
+        };
+        let mut quads = vec![
+            Quad::new(
+                result.clone(),
+                rdf::TYPE,
+                sh("ValidationResult"),
+                GraphName::DefaultGraph,
+            ),
+            quad("focusNode", self.focus_node.clone()),
+            quad("sourceShape", self.shape.clone()),
+            quad("resultSeverity", sh("Violation").into()),
+            quad("resultMessage", Literal::from(self.message.as_str()).into()),
+        ];
+        if let Some(path) = &self.path {
+            quads.push(quad("resultPath", path.clone().into()));
+        }
+        quads
+    }
+}
+
+/// SHACL shapes validating the graph of each event of a stream, see
+/// `RSPEngineBuilder::validate_stream`
+///
+/// Every event is validated on its own, as the data graph; no inference is made,
+/// so `sh:targetClass` only targets nodes typed in the event itself, and
+/// `sh:targetNode` only nodes occurring in it. The supported subset of SHACL Core:
+/// - targets: `sh:targetClass`, `sh:targetNode`, `sh:targetSubjectsOf`,
+///   `sh:targetObjectsOf`
+/// - property shapes whose `sh:path` is a predicate, with `sh:minCount`,
+///   `sh:maxCount`, `sh:datatype`, `sh:class`, `sh:nodeKind`, `sh:in`,
+///   `sh:pattern`, `sh:minInclusive`, `sh:maxInclusive`, `sh:minExclusive` and
+///   `sh:maxExclusive`, the range constraints only accepting numeric literals
+///
+/// Shapes using any other SHACL constraint, e.g. `sh:minLength`, `sh:flags`,
+/// `sh:node`, `sh:or` or `sh:closed`, are rejected rather than partially checked.
+///
+/// ```rust
+/// use oxigraph::io::RdfFormat;
+/// use rsp_rs::ShaclShapes;
+///
+/// let shapes = ShaclShapes::parse(
+///     r#"
+///     PREFIX sh: <http://www.w3.org/ns/shacl#>
+///     PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+///     PREFIX ex: <http://example.org/>
+///     ex:ReadingShape a sh:NodeShape ;
+///         sh:targetClass ex:Reading ;
+///         sh:property [ sh:path ex:value ; sh:minCount 1 ; sh:datatype xsd:double ] .
+///     "#,
+///     RdfFormat::Turtle,
+/// )
+/// .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ShaclShapes(Arc<Vec<NodeShape>>);
+
+struct NodeShape {
+    id: Term,
+    targets: Vec<Target>,
+    properties: Vec<PropertyShape>,
+}
+
+enum Target {
+    Class(Term),
+    Node(Term),
+    SubjectsOf(NamedNode),
+    ObjectsOf(NamedNode),
+}
+
+struct PropertyShape {
+    path: NamedNode,
+    min_count: Option<usize>,
+    max_count: Option<usize>,
+    constraints: Vec<ValueConstraint>,
+}
+
+enum ValueConstraint {
+    Datatype(NamedNode),
+    Class(Term),
+    NodeKind(NamedNode),
+    In(Vec<Term>),
+    Pattern(Regex),
+    MinInclusive(f64),
+    MaxInclusive(f64),
+    MinExclusive(f64),
+    MaxExclusive(f64),
+}
+
+impl ShaclShapes {
+    /// Parse the shapes from an RDF document, e.g. in Turtle
+    pub fn parse(document: &str, format: RdfFormat) -> Result<Self, String> {
+        Self::from_quads(&parse_rdf(document, format)?)
+    }
+
+    /// Read the shapes from the quads of a shapes graph, whatever their graph
+    /// Node shapes are the subjects typed `sh:NodeShape` or declaring a target
+    pub fn from_quads(quads: &[Quad]) -> Result<Self, String> {
+        let graph = ShapesGraph::new(quads);
+        let target_predicates = [
+            "targetClass",
+            "targetNode",
+            "targetSubjectsOf",
+            "targetObjectsOf",
+        ];
+        let mut ids: Vec<&Term> = Vec::new();
+        for (subject, properties) in &graph.properties {
+            let node_shape = properties.iter().any(|(predicate, object)| {
+                (*predicate == rdf::TYPE && *object == Term::from(sh("NodeShape")))
+                    || target_predicates
+                        .iter()
+                        .any(|target| predicate.as_str() == format!("{}{}", SH, target))
+            });
+            if node_shape && !ids.contains(&subject) {
+                ids.push(subject);
+            }
+        }
+        let shapes = ids
+            .into_iter()
+            .map(|id| graph.node_shape(id))
+            .collect::<Result<Vec<_>, String>>()?;
+        if shapes.is_empty() {
+            return Err("No SHACL node shape found in the shapes graph".to_string());
+        }
+        Ok(Self(Arc::new(shapes)))
+    }
+
+    /// Validate the quads of one event, returning the violations found
+    pub fn validate(&self, quads: &[Quad]) -> Vec<ShaclViolation> {
+        let data = DataGraph::new(quads);
+        let mut violations = Vec::new();
+        for shape in self.0.iter() {
+            for focus in shape.focus_nodes(&data) {
+                for property in &shape.properties {
+                    property.validate(shape, &focus, &data, &mut violations);
+                }
+            }
+        }
+        violations
+    }
+}
+
+impl fmt::Debug for ShaclShapes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ShaclShapes({} node shapes)", self.0.len())
+    }
+}
+
+/// Shapes are compared by identity
+impl PartialEq for ShaclShapes {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for ShaclShapes {}
+
+impl NodeShape {
+    /// Get the nodes of an event the shape targets, without duplicates
+    fn focus_nodes(&self, data: &DataGraph) -> Vec<Term> {
+        let mut nodes: Vec<Term> = Vec::new();
+        for target in &self.targets {
+            let targeted: Vec<Term> = match target {
+                Target::Class(class) => data
+                    .quads
+                    .iter()
+                    .filter(|quad| quad.predicate == rdf::TYPE && quad.object == *class)
+                    .map(|quad| quad.subject.clone().into())
+                    .collect(),
+                Target::Node(node) => data
+                    .quads
+                    .iter()
+                    .any(|quad| Term::from(quad.subject.clone()) == *node || quad.object == *node)
+                    .then(|| node.clone())
+                    .into_iter()
+                    .collect(),
+                Target::SubjectsOf(predicate) => data
+                    .quads
+                    .iter()
+                    .filter(|quad| quad.predicate == *predicate)
+                    .map(|quad| quad.subject.clone().into())
+                    .collect(),
+                Target::ObjectsOf(predicate) => data
+                    .quads
+                    .iter()
+                    .filter(|quad| quad.predicate == *predicate)
+                    .map(|quad| quad.object.clone())
+                    .collect(),
+            };
+            for node in targeted {
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+            }
+        }
+        nodes
+    }
+}
+
+impl PropertyShape {
+    fn validate(
+        &self,
+        shape: &NodeShape,
+        focus: &Term,
+        data: &DataGraph,
+        violations: &mut Vec<ShaclViolation>,
+    ) {
+        let mut violation = |message: String| {
+            violations.push(ShaclViolation {
+                focus_node: focus.clone(),
+                path: Some(self.path.clone()),
+                shape: shape.id.clone(),
+                message,
+            })
+        };
+        let values = data.objects(focus, &self.path);
+        if let Some(min_count) = self.min_count {
+            if values.len() < min_count {
+                violation(format!(
+                    "Expected at least {} values of {}, found {}",
+                    min_count,
+                    self.path,
+                    values.len()
+                ));
+            }
+        }
+        if let Some(max_count) = self.max_count {
+            if values.len() > max_count {
+                violation(format!(
+                    "Expected at most {} values of {}, found {}",
+                    max_count,
+                    self.path,
+                    values.len()
+                ));
+            }
+        }
+        for value in values {
+            for constraint in &self.constraints {
+                if !constraint.accepts(value, data) {
+                    violation(format!(
+                        "Value {} of {} {}",
+                        value,
+                        self.path,
+                        constraint.describe()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl ValueConstraint {
+    fn accepts(&self, value: &Term, data: &DataGraph) -> bool {
+        match self {
+            ValueConstraint::Datatype(datatype) => {
+                matches!(value, Term::Literal(literal) if literal.datatype() == datatype.as_ref())
+            }
+            ValueConstraint::Class(class) => data
+                .objects(value, &rdf::TYPE.into_owned())
+                .contains(&class),
+            ValueConstraint::NodeKind(kind) => {
+                let (iri, blank, literal) = (
+                    value.is_named_node(),
+                    value.is_blank_node(),
+                    value.is_literal(),
+                );
+                match kind.as_str().strip_prefix(SH).unwrap_or_default() {
+                    "IRI" => iri,
+                    "BlankNode" => blank,
+                    "Literal" => literal,
+                    "BlankNodeOrIRI" => blank || iri,
+                    "BlankNodeOrLiteral" => blank || literal,
+                    "IRIOrLiteral" => iri || literal,
+                    _ => false,
+                }
+            }
+            ValueConstraint::In(members) => members.contains(value),
+            ValueConstraint::Pattern(pattern) => match value {
+                Term::NamedNode(node) => pattern.is_match(node.as_str()),
+                Term::Literal(literal) => pattern.is_match(literal.value()),
+                _ => false,
+            },
+            ValueConstraint::MinInclusive(bound) => numeric(value).is_some_and(|v| v >= *bound),
+            ValueConstraint::MaxInclusive(bound) => numeric(value).is_some_and(|v| v <= *bound),
+            ValueConstraint::MinExclusive(bound) => numeric(value).is_some_and(|v| v > *bound),
+            ValueConstraint::MaxExclusive(bound) => numeric(value).is_some_and(|v| v < *bound),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ValueConstraint::Datatype(datatype) => format!("is not of datatype {}", datatype),
+            ValueConstraint::Class(class) => format!("is not an instance of {}", class),
+            ValueConstraint::NodeKind(kind) => format!("is not of node kind {}", kind),
+            ValueConstraint::In(_) => "is not among the allowed values".to_string(),
+            ValueConstraint::Pattern(pattern) => format!("does not match {}", pattern),
+            ValueConstraint::MinInclusive(bound) => format!("is not at least {}", bound),
+            ValueConstraint::MaxInclusive(bound) => format!("is not at most {}", bound),
+            ValueConstraint::MinExclusive(bound) => format!("is not above {}", bound),
+            ValueConstraint::MaxExclusive(bound) => format!("is not below {}", bound),
+        }
+    }
+}
+
+/// Value of a literal of a numeric datatype, `None` for other terms
+fn numeric(term: &Term) -> Option<f64> {
+    match term {
+        Term::Literal(literal) if NUMERIC_DATATYPES.contains(&literal.datatype()) => {
+            literal.value().parse().ok()
+        }
+        _ => None,
+    }
+}
+
+fn sh(name: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{}{}", SH, name))
+}
+
+/// Quads of an event indexed by subject
+struct DataGraph<'a> {
+    quads: &'a [Quad],
+    properties: HashMap<Term, Vec<&'a Quad>>,
+}
+
+impl<'a> DataGraph<'a> {
+    fn new(quads: &'a [Quad]) -> Self {
+        let mut properties: HashMap<Term, Vec<&Quad>> = HashMap::new();
+        for quad in quads {
+            properties
+                .entry(quad.subject.clone().into())
+                .or_default()
+                .push(quad);
+        }
+        Self { quads, properties }
+    }
+
+    fn objects(&self, subject: &Term, predicate: &NamedNode) -> Vec<&'a Term> {
+        self.properties
+            .get(subject)
+            .into_iter()
+            .flatten()
+            .filter(|quad| quad.predicate == *predicate)
+            .map(|quad| &quad.object)
+            .collect()
+    }
+}
+
+/// Triples of a shapes graph indexed by subject
+struct ShapesGraph {
+    properties: HashMap<Term, Vec<(NamedNode, Term)>>,
+}
+
+impl ShapesGraph {
+    fn new(quads: &[Quad]) -> Self {
+        let mut properties: HashMap<Term, Vec<(NamedNode, Term)>> = HashMap::new();
+        for quad in quads {
+            properties
+                .entry(quad.subject.clone().into())
+                .or_default()
+                .push((quad.predicate.clone(), quad.object.clone()));
+        }
+        Self { properties }
+    }
+
+    /// Get the objects of a subject for a SHACL predicate
+    fn objects<'a>(&'a self, subject: &Term, name: &str) -> impl Iterator<Item = &'a Term> {
+        let predicate = format!("{}{}", SH, name);
+        self.properties
+            .get(subject)
+            .into_iter()
+            .flatten()
+            .filter(move |(p, _)| p.as_str() == predicate)
+            .map(|(_, object)| object)
+    }
+
+    fn object(&self, subject: &Term, name: &str) -> Option<&Term> {
+        self.objects(subject, name).next()
+    }
+
+    /// Fail if a shape uses a SHACL predicate other than `supported` or annotations
+    fn check_supported(&self, id: &Term, supported: &[&str]) -> Result<(), String> {
+        for (predicate, _) in self.properties.get(id).into_iter().flatten() {
+            if let Some(name) = predicate.as_str().strip_prefix(SH)
+                && !supported.contains(&name)
+                && !ANNOTATION_PREDICATES.contains(&name)
+            {
+                return Err(format!(
+                    "Unsupported SHACL constraint sh:{} in shape {}",
+                    name, id
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn node_shape(&self, id: &Term) -> Result<NodeShape, String> {
+        self.check_supported(id, &NODE_SHAPE_PREDICATES)?;
+        let mut targets = Vec::new();
+        targets.extend(self.objects(id, "targetClass").cloned().map(Target::Class));
+        targets.extend(self.objects(id, "targetNode").cloned().map(Target::Node));
+        for object in self.objects(id, "targetSubjectsOf") {
+            targets.push(Target::SubjectsOf(iri(object, "sh:targetSubjectsOf")?));
+        }
+        for object in self.objects(id, "targetObjectsOf") {
+            targets.push(Target::ObjectsOf(iri(object, "sh:targetObjectsOf")?));
+        }
+        let properties = self
+            .objects(id, "property")
+            .map(|property| self.property_shape(property))
+            .collect::<Result<_, _>>()?;
+        Ok(NodeShape {
+            id: id.clone(),
+            targets,
+            properties,
+        })
+    }
+
+    fn property_shape(&self, id: &Term) -> Result<PropertyShape, String> {
+        self.check_supported(id, &PROPERTY_SHAPE_PREDICATES)?;
+        let path = self
+            .object(id, "path")
+            .ok_or_else(|| format!("Property shape {} has no sh:path", id))?;
+        let path = match path {
+            Term::NamedNode(path) => path.clone(),
+            _ => {
+                return Err(format!(
+                    "Only predicate paths are supported, found {}",
+                    path
+                ));
+            }
+        };
+        let count = |name: &str| -> Result<Option<usize>, String> {
+            self.object(id, name)
+                .map(|value| match value {
+                    Term::Literal(literal) => literal
+                        .value()
+                        .parse()
+                        .map_err(|_| format!("Invalid sh:{} {}", name, value)),
+                    _ => Err(format!("Invalid sh:{} {}", name, value)),
+                })
+                .transpose()
+        };
+        let bound = |name: &str| -> Result<Option<f64>, String> {
+            self.object(id, name)
+                .map(|value| numeric(value).ok_or_else(|| format!("Invalid sh:{} {}", name, value)))
+                .transpose()
+        };
+
+        let mut constraints = Vec::new();
+        if let Some(datatype) = self.object(id, "datatype") {
+            constraints.push(ValueConstraint::Datatype(iri(datatype, "sh:datatype")?));
+        }
+        if let Some(class) = self.object(id, "class") {
+            constraints.push(ValueConstraint::Class(class.clone()));
+        }
+        if let Some(kind) = self.object(id, "nodeKind") {
+            constraints.push(ValueConstraint::NodeKind(iri(kind, "sh:nodeKind")?));
+        }
+        if let Some(list) = self.object(id, "in") {
+            constraints.push(ValueConstraint::In(self.list(list)?));
+        }
+        if let Some(pattern) = self.object(id, "pattern") {
+            let Term::Literal(pattern) = pattern else {
+                return Err(format!("Invalid sh:pattern {}", pattern));
+            };
+            let pattern = Regex::new(pattern.value())
+                .map_err(|e| format!("Invalid sh:pattern {}: {}", pattern, e))?;
+            constraints.push(ValueConstraint::Pattern(pattern));
+        }
+        let bounds: [(&str, fn(f64) -> ValueConstraint); 4] = [
+            ("minInclusive", ValueConstraint::MinInclusive),
+            ("maxInclusive", ValueConstraint::MaxInclusive),
+            ("minExclusive", ValueConstraint::MinExclusive),
+            ("maxExclusive", ValueConstraint::MaxExclusive),
+        ];
+        for (name, constraint) in bounds {
+            constraints.extend(bound(name)?.map(constraint));
+        }
+
+        Ok(PropertyShape {
+            path,
+            min_count: count("minCount")?,
+            max_count: count("maxCount")?,
+            constraints,
+        })
+    }
+
+    /// Read the members of an RDF list
+    fn list(&self, mut node: &Term) -> Result<Vec<Term>, String> {
+        let mut members = Vec::new();
+        let mut visited = HashSet::new();
+        let nil = Term::from(rdf::NIL.into_owned());
+        while *node != nil {
+            if !visited.insert(node) {
+                return Err(format!("Cyclic RDF list at {}", node));
+            }
+            let at = node;
+            let properties = self
+                .properties
+                .get(at)
+                .ok_or_else(|| format!("Invalid RDF list at {}", at))?;
+            let find = |predicate: NamedNode| {
+                properties
+                    .iter()
+                    .find(|(p, _)| *p == predicate)
+                    .map(|(_, object)| object)
+                    .ok_or_else(|| format!("Invalid RDF list at {}", at))
+            };
+            members.push(find(rdf::FIRST.into_owned())?.clone());
+            node = find(rdf::REST.into_owned())?;
+        }
+        Ok(members)
+    }
+}
+
+fn iri(term: &Term, name: &str) -> Result<NamedNode, String> {
+    match term {
+        Term::NamedNode(node) => Ok(node.clone()),
+        _ => Err(format!("Invalid {} {}, expected an IRI", name, term)),
+    }
+}
+
+/// Shapes and policy of a stream, applied to each event before it is sent
+pub(crate) struct StreamValidation {
+    pub(crate) shapes: ShaclShapes,
+    pub(crate) policy: ValidationPolicy,
+}
+
+impl StreamValidation {
+    /// Validate the quads of an event of `stream`, returning those to send, if any
+    /// Dropped events are sent to the dead letters
+    pub(crate) fn check(
+        &self,
+        stream: &str,
+        mut quads: Vec<Quad>,
+        timestamp: i64,
+        dead_letters: &DeadLetters,
+    ) -> Result<Option<Vec<Quad>>, String> {
+        let violations = self.shapes.validate(&quads);
+        if violations.is_empty() {
+            return Ok(Some(quads));
+        }
+        if self.policy == ValidationPolicy::Annotate {
+            quads.extend(violations.iter().flat_map(ShaclViolation::to_quads));
+            return Ok(Some(quads));
+        }
+        let error = format!(
+            "Event at {} of stream {} violates its shapes: {}",
+            timestamp, stream, violations[0].message
+        );
+        debug!(
+            stream,
+            timestamp,
+            violations = violations.len(),
+            "Dropping invalid event"
+        );
+        dead_letters.send(|| DeadLetter {
+            stream: stream.to_string(),
+            timestamp: Some(timestamp),
+            quads,
+            document: None,
+            reason: DropReason::Invalid(violations),
+        });
+        match self.policy {
+            ValidationPolicy::Reject => Err(error),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHAPES: &str = r#"
+        PREFIX sh: <http://www.w3.org/ns/shacl#>
+        PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+        PREFIX ex: <http://example.org/>
+        ex:ReadingShape a sh:NodeShape ;
+            sh:targetClass ex:Reading ;
+            sh:property [
+                sh:path ex:value ;
+                sh:minCount 1 ;
+                sh:maxCount 1 ;
+                sh:datatype xsd:double ;
+                sh:minInclusive -50 ;
+                sh:maxInclusive 150
+            ] ;
+            sh:property [ sh:path ex:unit ; sh:in ( "C" "F" ) ] .
+    "#;
+
+    fn event(value: &str) -> Vec<Quad> {
+        let document = format!(
+            r#"
+            PREFIX ex: <http://example.org/>
+            ex:r1 a ex:Reading ; ex:value {} ; ex:unit "C" .
+            "#,
+            value
+        );
+        parse_rdf(&document, RdfFormat::Turtle).unwrap()
+    }
+
+    #[test]
+    fn test_validate_events() {
+        let shapes = ShaclShapes::parse(SHAPES, RdfFormat::Turtle).unwrap();
+        assert!(shapes.validate(&event("21.5e0")).is_empty());
+
+        let violations = shapes.validate(&event("999.0e0"));
+        assert_eq!(violations.len(), 1);
+        let violation = &violations[0];
+        let r1 = NamedNode::new_unchecked("http://example.org/r1");
+        assert_eq!(violation.focus_node, Term::from(r1));
+        assert_eq!(
+            violation.path,
+            Some(NamedNode::new_unchecked("http://example.org/value"))
+        );
+        let shape = NamedNode::new_unchecked("http://example.org/ReadingShape");
+        assert_eq!(violation.shape, Term::from(shape));
+
+        // A string is neither a double nor within the bounds, even if it reads as a
+        // number
+        assert_eq!(shapes.validate(&event("\"hot\"")).len(), 3);
+        assert_eq!(shapes.validate(&event("\"21\"")).len(), 3);
+        // Nodes of other classes aren't targeted
+        let other = parse_rdf(
+            "<http://example.org/s1> <http://example.org/unit> \"K\" .",
+            RdfFormat::NTriples,
+        )
+        .unwrap();
+        assert!(shapes.validate(&other).is_empty());
+    }
+
+    #[test]
+    fn test_violations_as_validation_results() {
+        let shapes = ShaclShapes::parse(SHAPES, RdfFormat::Turtle).unwrap();
+        let violations = shapes.validate(&event("999.0e0"));
+        let quads = violations[0].to_quads();
+        assert_eq!(quads.len(), 6);
+        let result = Term::from(sh("ValidationResult"));
+        assert!(quads.iter().any(|quad| quad.object == result));
+    }
+
+    #[test]
+    fn test_unsupported_shapes() {
+        let document = r#"
+            PREFIX sh: <http://www.w3.org/ns/shacl#>
+            PREFIX ex: <http://example.org/>
+            ex:Shape sh:targetClass ex:Reading ;
+                sh:property [ sh:path ( ex:a ex:b ) ; sh:minCount 1 ] .
+        "#;
+        assert!(ShaclShapes::parse(document, RdfFormat::Turtle).is_err());
+        assert!(ShaclShapes::parse("", RdfFormat::Turtle).is_err());
+
+        // Constraints outside the supported subset, on property or node shapes
+        for constraint in [
+            "sh:property [ sh:path ex:unit ; sh:minLength 1 ]",
+            "sh:property [ sh:path ex:unit ; sh:pattern \"^c$\" ; sh:flags \"i\" ]",
+            "sh:property [ sh:path ex:unit ; sh:hasValue \"C\" ]",
+            "sh:closed true",
+            "sh:in ( ex:r1 )",
+        ] {
+            let document = format!(
+                "PREFIX sh: <http://www.w3.org/ns/shacl#>\n\
+                 PREFIX ex: <http://example.org/>\n\
+                 ex:Shape sh:targetClass ex:Reading ; sh:name \"Readings\" ; {} .",
+                constraint
+            );
+            let error = ShaclShapes::parse(&document, RdfFormat::Turtle).unwrap_err();
+            assert!(
+                error.starts_with("Unsupported SHACL constraint"),
+                "{}",
+                error
+            );
+        }
+
+        let cyclic = r#"
+            PREFIX sh: <http://www.w3.org/ns/shacl#>
+            PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+            PREFIX ex: <http://example.org/>
+            ex:Shape sh:targetClass ex:Reading ; sh:property [ sh:path ex:unit ; sh:in ex:units ] .
+            ex:units rdf:first "C" ; rdf:rest ex:units .
+        "#;
+        let error = ShaclShapes::parse(cyclic, RdfFormat::Turtle).unwrap_err();
+        assert!(error.starts_with("Cyclic RDF list"));
+    }
+}
@@ -0,0 +1,486 @@
+use crate::jsonld::parse_jsonld;
+use oxigraph::model::vocab::rdf;
+use oxigraph::model::{Literal, NamedNode, Quad, Term};
+use oxsdatatypes::{DateTime, Integer, TimezoneOffset};
+use std::str::FromStr;
+// Browsers only provide time through JavaScript, `std::time` panics there
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Extracts the event timestamp from the content of an event graph
+///
+/// The timestamp is located by following a property path (one or more predicates)
+/// from any subject in the event, optionally restricted to nodes of given types. The
+/// value reached at the end of the path must be a literal, either an `xsd:dateTime`
+/// (converted to Unix milliseconds) or an integer (taken as milliseconds as-is).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampExtractor {
+    path: Vec<NamedNode>,
+    /// Types required of the node each predicate of the path starts from
+    types: Vec<Vec<NamedNode>>,
+}
+
+impl TimestampExtractor {
+    /// Create an extractor reading the timestamp from a single predicate
+    pub fn new(predicate: NamedNode) -> Self {
+        Self::from_path(vec![predicate])
+    }
+
+    /// Create an extractor following a sequence of predicates,
+    /// e.g. `[prov:qualifiedGeneration, prov:atTime]`
+    pub fn from_path(path: Vec<NamedNode>) -> Self {
+        let types = vec![Vec::new(); path.len()];
+        Self { path, types }
+    }
+
+    /// Create an extractor from a JSON-LD frame of the events
+    ///
+    /// The frame selects one property per node and ends with an empty object standing
+    /// for the timestamp, e.g. `{"@context": {"sosa": "http://www.w3.org/ns/sosa/"},
+    /// "@type": "sosa:Observation", "sosa:resultTime": {}}`. `@type` restricts the
+    /// nodes the path goes through. Remote contexts are not fetched.
+    pub fn from_frame(frame: &str) -> Result<Self, String> {
+        let quads = parse_jsonld(frame).map_err(|e| format!("Invalid timestamp frame: {}", e))?;
+        let subject = |quad: &Quad| Term::from(quad.subject.clone());
+
+        // The root of the frame is the only node no property points to
+        let mut roots = quads.iter().map(subject).filter(|node| {
+            !quads
+                .iter()
+                .any(|q| q.predicate != rdf::TYPE && q.object == *node)
+        });
+        let mut node = roots
+            .next()
+            .ok_or_else(|| "Timestamp frame selects no property".to_string())?;
+        if roots.any(|root| root != node) {
+            return Err("Timestamp frame must have a single root object".to_string());
+        }
+
+        let (mut path, mut types) = (Vec::new(), Vec::new());
+        loop {
+            let of_node = |q: &&Quad| subject(q) == node;
+            let node_types: Vec<NamedNode> = quads
+                .iter()
+                .filter(of_node)
+                .filter(|q| q.predicate == rdf::TYPE)
+                .filter_map(|q| match &q.object {
+                    Term::NamedNode(node_type) => Some(node_type.clone()),
+                    _ => None,
+                })
+                .collect();
+            let mut properties = quads
+                .iter()
+                .filter(of_node)
+                .filter(|q| q.predicate != rdf::TYPE);
+            let (Some(property), None) = (properties.next(), properties.next()) else {
+                break;
+            };
+            if path.len() == quads.len() {
+                return Err("Timestamp frame must not be cyclic".to_string());
+            }
+            path.push(property.predicate.clone());
+            types.push(node_types);
+            node = property.object.clone();
+        }
+
+        // The loop stops at the timestamp, or at a node selecting several properties
+        let is_empty_object =
+            matches!(node, Term::BlankNode(_)) && !quads.iter().any(|q| subject(q) == node);
+        if path.is_empty() || !is_empty_object {
+            return Err(
+                "Timestamp frame must select a single property per node and end with an empty object"
+                    .to_string(),
+            );
+        }
+        Ok(Self { path, types })
+    }
+
+    /// Extractor for `sosa:resultTime`, the most common case for sensor observations
+    pub fn sosa_result_time() -> Self {
+        Self::new(NamedNode::new_unchecked(
+            "http://www.w3.org/ns/sosa/resultTime",
+        ))
+    }
+
+    /// Get the property path followed by this extractor
+    pub fn path(&self) -> &[NamedNode] {
+        &self.path
+    }
+
+    /// Extract the timestamp from the given quads
+    /// The first match in quad order wins when several subjects carry the path
+    pub fn extract(&self, quads: &[Quad]) -> Result<i64, String> {
+        let first = self
+            .path
+            .first()
+            .ok_or_else(|| "Timestamp extractor has an empty property path".to_string())?;
+
+        for quad in quads.iter().filter(|q| q.predicate == *first) {
+            if self.has_types(quads, &quad.subject.clone().into(), 0)
+                && let Some(Term::Literal(literal)) = self.follow(quads, &quad.object, 1)
+            {
+                return parse_timestamp_literal(&literal);
+            }
+        }
+
+        Err(format!(
+            "No timestamp found for property path {}",
+            self.path
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+                .join("/")
+        ))
+    }
+
+    /// Value reached from `start` along the path from its predicate at `depth`
+    fn follow(&self, quads: &[Quad], start: &Term, depth: usize) -> Option<Term> {
+        let Some(predicate) = self.path.get(depth) else {
+            return Some(start.clone());
+        };
+        if !self.has_types(quads, start, depth) {
+            return None;
+        }
+        quads
+            .iter()
+            .filter(|q| q.predicate == *predicate && Term::from(q.subject.clone()) == *start)
+            .find_map(|q| self.follow(quads, &q.object, depth + 1))
+    }
+
+    /// Whether `node` has the types required of the start of the path at `depth`
+    fn has_types(&self, quads: &[Quad], node: &Term, depth: usize) -> bool {
+        self.types[depth].iter().all(|node_type| {
+            quads.iter().any(|q| {
+                q.predicate == rdf::TYPE
+                    && matches!(&q.object, Term::NamedNode(object) if object == node_type)
+                    && Term::from(q.subject.clone()) == *node
+            })
+        })
+    }
+}
+
+/// Policy deciding how a stream assigns timestamps to incoming events
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TimestampPolicy {
+    /// Use the timestamp passed by the caller
+    #[default]
+    Explicit,
+    /// Read the timestamp from the event content,
+    /// falling back to the caller's timestamp when the event doesn't carry one
+    Predicate(TimestampExtractor),
+    /// Use the wall-clock time at ingestion, in Unix milliseconds
+    WallClock,
+}
+
+impl TimestampPolicy {
+    /// Policy extracting the timestamp from a single predicate
+    pub fn predicate(predicate: NamedNode) -> Self {
+        Self::Predicate(TimestampExtractor::new(predicate))
+    }
+
+    /// Resolve the timestamp of an event
+    /// `explicit` is the timestamp supplied by the caller, if any
+    pub fn resolve(&self, quads: &[Quad], explicit: Option<i64>) -> Result<i64, String> {
+        match self {
+            TimestampPolicy::Explicit => explicit.ok_or_else(|| {
+                "Stream uses an explicit timestamp policy but no timestamp was given".to_string()
+            }),
+            TimestampPolicy::Predicate(extractor) => match extractor.extract(quads) {
+                Ok(timestamp) => Ok(timestamp),
+                Err(e) => explicit.ok_or(e),
+            },
+            TimestampPolicy::WallClock => Ok(wall_clock_millis()),
+        }
+    }
+}
+
+/// Current wall-clock time in Unix milliseconds
+pub(crate) fn wall_clock_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Convert a timestamp literal into Unix milliseconds
+/// Accepts `xsd:dateTime` values and integer literals already expressed in milliseconds
+pub fn parse_timestamp_literal(literal: &Literal) -> Result<i64, String> {
+    let value = literal.value();
+    let datatype = literal.datatype().as_str();
+
+    if datatype == format!("{}dateTime", XSD) || datatype == format!("{}dateTimeStamp", XSD) {
+        parse_date_time(value).ok_or_else(|| format!("Invalid xsd:dateTime '{}'", value))
+    } else {
+        value
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| format!("Literal '{}' is not a usable timestamp", value))
+    }
+}
+
+/// Parse an `xsd:dateTime` lexical value into Unix milliseconds
+/// Values without a timezone are interpreted as UTC
+pub(crate) fn parse_date_time(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if is_past_end_of_day(value) {
+        return None;
+    }
+    let date_time = DateTime::from_str(value)
+        .ok()?
+        .adjust(Some(TimezoneOffset::UTC))?;
+    let epoch = DateTime::from_str("1970-01-01T00:00:00Z").ok()?;
+    let millis = date_time
+        .checked_sub(epoch)?
+        .as_seconds()
+        .checked_mul(1000)?
+        .checked_floor()?;
+    Some(Integer::try_from(millis).ok()?.into())
+}
+
+/// Whether the time of day of an `xsd:dateTime` has hour 24 with other than
+/// `24:00:00`, which oxsdatatypes accepts
+fn is_past_end_of_day(value: &str) -> bool {
+    let Some(time) = value
+        .split_once('T')
+        .and_then(|(_, time)| time.strip_prefix("24:"))
+    else {
+        return false;
+    };
+    let Some(rest) = time.strip_prefix("00:00") else {
+        return true;
+    };
+    !rest
+        .strip_prefix('.')
+        .unwrap_or_default()
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .all(|digit| digit == '0')
+}
+
+/// Proleptic Gregorian date of the given number of days since 1970-01-01
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Format Unix milliseconds as an `xsd:dateTime` lexical value in UTC
+pub(crate) fn format_date_time(millis: i64) -> String {
+    let (year, month, day) = civil_from_days(millis.div_euclid(86_400_000));
+    let millis_of_day = millis.rem_euclid(86_400_000);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000
+    )
+}
+
+/// `xsd:dateTime` literal of Unix milliseconds
+pub(crate) fn date_time_literal(millis: i64) -> Literal {
+    Literal::new_typed_literal(
+        format_date_time(millis),
+        NamedNode::new_unchecked(format!("{}dateTime", XSD)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{BlankNode, GraphName};
+
+    fn date_time(value: &str) -> Literal {
+        Literal::new_typed_literal(value, NamedNode::new_unchecked(format!("{}dateTime", XSD)))
+    }
+
+    #[test]
+    fn test_parse_date_time() {
+        assert_eq!(parse_date_time("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_date_time("2024-11-26T10:15:30.250Z"),
+            Some(1_732_616_130_250)
+        );
+        assert_eq!(
+            parse_date_time("2024-11-26T11:15:30.250+01:00"),
+            Some(1_732_616_130_250)
+        );
+        assert_eq!(parse_date_time("1969-12-31T23:59:59Z"), Some(-1000));
+        assert_eq!(
+            parse_date_time("2024-11-26T24:00:00Z"),
+            Some(1_732_665_600_000)
+        );
+        assert_eq!(parse_date_time("not a date"), None);
+        for invalid in [
+            "2024-11-26T24:30:00Z",
+            "2024-11-26T24:00:00.5Z",
+            "2024-02-31T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "2024-11-26T-1:00:00Z",
+            "2024-11-26T10:-5:00Z",
+            "2024-11-26T10:15:30+01:-30",
+        ] {
+            assert_eq!(parse_date_time(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_format_date_time() {
+        assert_eq!(format_date_time(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_date_time(1_732_616_130_250),
+            "2024-11-26T10:15:30.250Z"
+        );
+        assert_eq!(format_date_time(-1000), "1969-12-31T23:59:59.000Z");
+        for millis in [951_782_400_000, 4_107_542_400_123] {
+            assert_eq!(parse_date_time(&format_date_time(millis)), Some(millis));
+        }
+    }
+
+    #[test]
+    fn test_parse_integer_literal() {
+        let literal = Literal::from(1_000i64);
+        assert_eq!(parse_timestamp_literal(&literal), Ok(1000));
+    }
+
+    #[test]
+    fn test_extract_along_path() {
+        let observation = NamedNode::new_unchecked("http://example.org/obs1");
+        let generation = BlankNode::default();
+        let qualified = NamedNode::new_unchecked("http://www.w3.org/ns/prov#qualifiedGeneration");
+        let at_time = NamedNode::new_unchecked("http://www.w3.org/ns/prov#atTime");
+
+        let quads = vec![
+            Quad::new(
+                observation,
+                qualified.clone(),
+                generation.clone(),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                generation,
+                at_time.clone(),
+                date_time("1970-01-01T00:00:02Z"),
+                GraphName::DefaultGraph,
+            ),
+        ];
+
+        let extractor = TimestampExtractor::from_path(vec![qualified, at_time]);
+        assert_eq!(extractor.extract(&quads), Ok(2000));
+        assert!(
+            TimestampExtractor::sosa_result_time()
+                .extract(&quads)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_extract_from_frame() {
+        let extractor = TimestampExtractor::from_frame(
+            r#"{
+                "@context": { "prov": "http://www.w3.org/ns/prov#" },
+                "prov:qualifiedGeneration": { "prov:atTime": {} }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            extractor.path(),
+            [
+                NamedNode::new_unchecked("http://www.w3.org/ns/prov#qualifiedGeneration"),
+                NamedNode::new_unchecked("http://www.w3.org/ns/prov#atTime"),
+            ]
+        );
+
+        // Only observations are matched
+        let extractor = TimestampExtractor::from_frame(
+            r#"{
+                "@context": { "sosa": "http://www.w3.org/ns/sosa/" },
+                "@type": "sosa:Observation",
+                "sosa:resultTime": {}
+            }"#,
+        )
+        .unwrap();
+        let result_time = NamedNode::new_unchecked("http://www.w3.org/ns/sosa/resultTime");
+        let event = |subject: &str, timestamp: i64| {
+            let subject = NamedNode::new_unchecked(subject);
+            vec![
+                Quad::new(
+                    subject.clone(),
+                    result_time.clone(),
+                    Literal::from(timestamp),
+                    GraphName::DefaultGraph,
+                ),
+                Quad::new(
+                    subject,
+                    rdf::TYPE,
+                    NamedNode::new_unchecked("http://www.w3.org/ns/sosa/Observation"),
+                    GraphName::DefaultGraph,
+                ),
+            ]
+        };
+        let mut quads = event("http://example.org/obs1", 2000);
+        assert_eq!(extractor.extract(&quads), Ok(2000));
+        quads.pop();
+        assert!(extractor.extract(&quads).is_err());
+        quads.extend(event("http://example.org/obs2", 3000));
+        assert_eq!(extractor.extract(&quads), Ok(3000));
+
+        for invalid in [
+            r#"{ "http://example.org/p": 1 }"#,
+            r#"{ "@type": "http://example.org/T" }"#,
+            r#"{ "http://example.org/p": {}, "http://example.org/q": {} }"#,
+            "{ not json",
+        ] {
+            assert!(
+                TimestampExtractor::from_frame(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamp_policies() {
+        let result_time = NamedNode::new_unchecked("http://www.w3.org/ns/sosa/resultTime");
+        let quads = vec![Quad::new(
+            NamedNode::new_unchecked("http://example.org/obs1"),
+            result_time.clone(),
+            Literal::from(1500i64),
+            GraphName::DefaultGraph,
+        )];
+
+        assert_eq!(TimestampPolicy::Explicit.resolve(&quads, Some(10)), Ok(10));
+        assert!(TimestampPolicy::Explicit.resolve(&quads, None).is_err());
+
+        let policy = TimestampPolicy::predicate(result_time);
+        assert_eq!(policy.resolve(&quads, Some(10)), Ok(1500));
+        assert_eq!(policy.resolve(&[], Some(10)), Ok(10));
+        assert!(policy.resolve(&[], None).is_err());
+
+        let now = wall_clock_millis();
+        assert!(
+            TimestampPolicy::WallClock
+                .resolve(&quads, Some(10))
+                .unwrap()
+                >= now
+        );
+    }
+}
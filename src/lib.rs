@@ -34,7 +34,8 @@
 //! **Important:** If your last event has timestamp=1500, NO results will be emitted
 //! because no subsequent event with a higher timestamp triggered window closure.
 //! Use `close_stream()` to add a "sentinel" event with a future timestamp to trigger
-//! remaining window closures.
+//! remaining window closures, or `RSPEngineBuilder::stream_idle_timeout` to close them
+//! once a stream stays silent, e.g. because its producer died.
 //!
 //! ### Complete Example with Stream Closure:
 //!
@@ -143,24 +144,99 @@
 //!     // window_lock.set_debug_mode(true);
 //! }
 //! ```
+//!
+//! Log output goes through [`tracing`](https://docs.rs/tracing), with the window, stream,
+//! window bounds and quad counts as fields. Install a subscriber such as
+//! `tracing-subscriber` to see it, e.g. with `RUST_LOG=rsp_rs=debug` to get the debug
+//! events of every module, also in release builds.
 
+#[cfg(feature = "benchmark")]
+pub mod benchmark;
+#[cfg(feature = "capi")]
+pub mod capi;
 mod engine;
+#[cfg(test)]
+mod http_test_server;
+mod ingestion;
 mod parsing;
 mod quad_container;
+mod sink;
+pub mod testing;
+mod transport;
+pub mod validation;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
 mod windowing;
 
 // Re-export modules for easier access
 pub use engine::*;
+pub use ingestion::*;
 pub use parsing::*;
+pub use sink::*;
+pub use transport::*;
 pub use windowing::*;
 
 // Public API exports
+pub use engine::alerts::{Alert, AlertRule};
+pub use engine::builder::{EngineConfig, RSPEngineBuilder};
+pub use engine::dead_letter::{DeadLetter, DropReason};
+pub use engine::dedup::ResultDedup;
+pub use engine::delta::{Binding, ResultDelta};
+pub use engine::evaluation_pool::EvaluationPool;
+pub use engine::events::{EngineEvent, EventPublisher};
+pub use engine::explain::{QueryPlan, WindowPlan};
+pub use engine::gap_monitor::{GapThreshold, StreamGap};
+pub use engine::incremental::{AggregateKind, AggregationPlan, AggregationState};
+pub use engine::metrics::{EVALUATION_LATENCY_BUCKETS, EngineMetrics};
+pub use engine::pause::PausePolicy;
+pub use engine::processing::{ProcessingHandle, SubscriptionToken};
+pub use engine::query_group::{GroupQuota, GroupResult, QueryGroup};
 pub use engine::r2r::R2ROperator;
-pub use engine::rsp_engine::{BindingWithTimestamp, RDFStream, RSPEngine};
-pub use parsing::parsed_query::{Operator, ParsedQuery, WindowDefinition};
-pub use parsing::rspql_parser::RSPQLParser;
+pub use engine::recording::{InputLog, RecordedEvent};
+pub use engine::result_filter::ResultFilter;
+pub use engine::retention::ResultHistory;
+pub use engine::router::StreamRouter;
+pub use engine::rsp_engine::{BindingWithTimestamp, RDFStream, RSPEngine, StreamEvent};
+pub use engine::sequence::{SequenceMatch, SequencePattern};
+pub use engine::service::ServiceClient;
+pub use engine::sharing::PlanSharing;
+pub use engine::shedding::{LoadSheddingPolicy, SheddingStrategy};
+pub use engine::sketches::{HyperLogLog, TDigest};
+pub use engine::stream_stats::StreamStats;
+pub use engine::supervisor::EngineError;
+pub use engine::temporal_join::{JoinMatch, TemporalJoin};
+pub use ingestion::jsonld::parse_jsonld;
+pub use ingestion::rate_limit::{RateLimit, RateLimitAction};
+pub use ingestion::rdf::parse_rdf;
+pub use ingestion::shacl::{ShaclShapes, ShaclViolation, ValidationPolicy};
+pub use ingestion::timestamp::{TimestampExtractor, TimestampPolicy, parse_timestamp_literal};
+pub use parsing::parsed_query::{Operator, ParsedQuery, ParsedQueryBuilder, WindowDefinition};
+pub use parsing::rspql_parser::{QueryDialect, RSPQLParser};
 pub use quad_container::QuadContainer;
+pub use sink::delivery::{
+    AckSink, DeliveryGuarantee, HttpAckSink, OverflowPolicy, ReliableSink, SequencedResult,
+};
+pub use sink::file::{FileFormat, FileSink};
+pub use sink::ldes::LdesPublisher;
+pub use sink::sparql_update::SparqlUpdateSink;
+pub use sink::template::RdfTemplate;
+#[cfg(feature = "coap")]
+pub use transport::coap::{CoapSource, ObservationMapping};
+pub use transport::message::{MessageFormat, ResultFormat};
+#[cfg(feature = "nats")]
+pub use transport::nats::{NatsSink, NatsSource};
+#[cfg(feature = "zeromq")]
+pub use transport::zeromq::{ZmqSink, ZmqSource};
+pub use windowing::calendar::TimeZone;
+pub use windowing::clock::{Clock, EventTimeClock, ManualClock, SystemClock};
 pub use windowing::csparql_window::{
-    CSPARQLWindow, ReportStrategy, StreamType, Tick, execute_query,
+    AggregateReport, CSPARQLWindow, ReportStrategy, StreamType, Tick, execute_query,
+};
+pub use windowing::enrichment::StaticLookup;
+pub use windowing::eviction::{
+    EvictionPolicy, GraphTtl, LatestPerSubject, LatestValue, MaxQuads, QuadTtl, WindowQuad,
 };
-pub use windowing::window_instance::WindowInstance;
+pub use windowing::partition::PartitionKey;
+pub use windowing::quad_filter::QuadFilter;
+pub use windowing::window_instance::{WindowInstance, WindowState};
+pub use windowing::window_stats::WindowStats;
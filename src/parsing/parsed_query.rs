@@ -1,29 +1,109 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operator {
     RStream,
     IStream,
     DStream,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct R2S {
     pub operator: Operator,
     pub name: String,
 }
 
-#[derive(Debug, Clone)]
+use crate::rspql_parser::RSPQLParser;
+use crate::{ReportStrategy, Tick};
+use spargebra::SparqlParser;
+
+/// Window of a query; optional fields may be left out when deserialized
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WindowDefinition {
     pub window_name: String,
     pub stream_name: String,
+    /// Further streams feeding the window, interleaved with `stream_name` by timestamp
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub additional_stream_names: Vec<String>,
     pub width: i64,
     pub slide: i64,
+    /// Report strategy from the query, overriding the engine default
+    pub report: Option<ReportStrategy>,
+    /// Tick from the query, overriding the engine default
+    pub tick: Option<Tick>,
+    /// Alignment origin (t0) of the window boundaries from the query
+    pub origin: Option<i64>,
+    /// POSIX time zone on whose calendar the instances of a tumbling window are
+    /// aligned instead, from `ALIGN CALENDAR "tz"`, see `TimeZone`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub calendar: Option<String>,
+    /// Variable whose value partitions the window state, from `PARTITION BY ?var`
+    pub partition_by: Option<String>,
+    /// Whether the window only keeps the latest quad of each subject and predicate,
+    /// from `LATEST`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub latest_value: bool,
+    /// Whether instances without events report, for windows matched under
+    /// `NOT EXISTS` or `MINUS` to detect the absence of events
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub emit_empty_windows: bool,
+    /// How long (in ms of event time) closed instances wait for late events before
+    /// reporting, from `DELAY d`
+    pub delay: Option<i64>,
+    /// Whether the window covers everything since its start, from `RANGE UNBOUNDED`,
+    /// reporting every `slide` ms; its `width` is then the slide
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub landmark: bool,
+    /// How long (in ms of event time) a landmark window keeps the raw quads, from
+    /// `RETAIN r`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub retention: Option<i64>,
 }
 
-#[derive(Debug, Clone)]
+impl WindowDefinition {
+    /// Create a window of `width` ms sliding every `slide` ms over one stream, with
+    /// the engine's defaults for every other setting
+    pub fn new(window_name: &str, stream_name: &str, width: i64, slide: i64) -> Self {
+        Self {
+            window_name: window_name.to_string(),
+            stream_name: stream_name.to_string(),
+            additional_stream_names: Vec::new(),
+            width,
+            slide,
+            report: None,
+            tick: None,
+            origin: None,
+            calendar: None,
+            partition_by: None,
+            latest_value: false,
+            emit_empty_windows: false,
+            delay: None,
+            landmark: false,
+            retention: None,
+        }
+    }
+
+    /// Get the names of all streams feeding the window
+    pub fn stream_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.stream_name.as_str())
+            .chain(self.additional_stream_names.iter().map(String::as_str))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ParsedQuery {
     pub sparql_query: String,
     pub r2s: R2S,
     pub s2r: Vec<WindowDefinition>,
+    /// Graphs merged into the default graph, from `FROM <g>`; these are static graphs
+    /// or windows, as C-SPARQL queries match their windows in the default graph
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub default_graphs: Vec<String>,
+    /// Static graphs available to `GRAPH` patterns, from `FROM NAMED <g>`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub named_graphs: Vec<String>,
 }
 
 impl ParsedQuery {
@@ -35,6 +115,15 @@ impl ParsedQuery {
                 name: "undefined".to_string(),
             },
             s2r: Vec::new(),
+            default_graphs: Vec::new(),
+            named_graphs: Vec::new(),
+        }
+    }
+
+    /// Start building a query without RSP-QL text, e.g. from a configuration file
+    pub fn builder() -> ParsedQueryBuilder {
+        ParsedQueryBuilder {
+            query: Self::new(String::new()),
         }
     }
 
@@ -49,4 +138,176 @@ impl ParsedQuery {
     pub fn add_s2r_window(&mut self, window: WindowDefinition) {
         self.s2r.push(window);
     }
+
+    /// Get the IRIs of every static graph of the query, default and named, i.e. the
+    /// graphs of `FROM` clauses that aren't windows
+    pub fn static_graphs(&self) -> impl Iterator<Item = &str> {
+        self.default_graphs
+            .iter()
+            .chain(&self.named_graphs)
+            .map(String::as_str)
+            .filter(|graph| !self.s2r.iter().any(|window| window.window_name == *graph))
+    }
+}
+
+/// Builds a parsed query from its parts instead of RSP-QL text
+///
+/// ```rust
+/// use rsp_rs::{Operator, ParsedQuery, WindowDefinition};
+///
+/// let query = ParsedQuery::builder()
+///     .register(Operator::RStream, "http://example.org/output")
+///     .window(WindowDefinition::new(
+///         "http://example.org/w",
+///         "http://example.org/stream",
+///         10_000,
+///         2000,
+///     ))
+///     .sparql("SELECT * WHERE { GRAPH <http://example.org/w> { ?s ?p ?o } }")
+///     .build()
+///     .unwrap();
+/// assert_eq!(query.s2r.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParsedQueryBuilder {
+    query: ParsedQuery,
+}
+
+impl ParsedQueryBuilder {
+    /// Set the stream operator producing the results and their name
+    pub fn register(mut self, operator: Operator, name: &str) -> Self {
+        self.query.set_r2s(operator, name.to_string());
+        self
+    }
+
+    /// Add a window, matched by `GRAPH` patterns named after it in the SPARQL query
+    pub fn window(mut self, window: WindowDefinition) -> Self {
+        self.query.add_s2r_window(window);
+        self
+    }
+
+    /// Set the SPARQL query evaluated on every window report
+    pub fn sparql(mut self, query: &str) -> Self {
+        self.query.set_sparql_query(query.to_string());
+        self
+    }
+
+    /// Merge a static graph, or a window, into the default graph
+    pub fn default_graph(mut self, graph: &str) -> Self {
+        self.query.default_graphs.push(graph.to_string());
+        self
+    }
+
+    /// Make a static graph available to `GRAPH` patterns
+    pub fn named_graph(mut self, graph: &str) -> Self {
+        self.query.named_graphs.push(graph.to_string());
+        self
+    }
+
+    /// Check the query like `RSPQLParser::try_parse`, with at least one window and
+    /// positive window sizes, and make windows matched under `NOT EXISTS` or
+    /// `MINUS` emit their empty instances
+    pub fn build(mut self) -> Result<ParsedQuery, String> {
+        SparqlParser::new()
+            .parse_query(&self.query.sparql_query)
+            .map_err(|e| format!("Invalid SPARQL query: {}", e))?;
+        if self.query.s2r.is_empty() {
+            return Err("A query needs at least one window".to_string());
+        }
+        if let Some(window) = self.query.s2r.iter().find(|w| w.width <= 0 || w.slide <= 0) {
+            return Err(format!(
+                "Window {} has no positive range and step",
+                window.window_name
+            ));
+        }
+        let negated = RSPQLParser::negated_windows(&self.query.sparql_query);
+        for window in &mut self.query.s2r {
+            window.emit_empty_windows |= negated.contains(&window.window_name);
+        }
+        Ok(self.query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> ParsedQueryBuilder {
+        ParsedQuery::builder()
+            .register(Operator::IStream, "http://example.org/output")
+            .window(WindowDefinition::new(
+                "http://example.org/w",
+                "http://example.org/stream",
+                1000,
+                500,
+            ))
+    }
+
+    #[test]
+    fn test_builder_matches_parser() {
+        let built = builder()
+            .sparql("SELECT * WHERE { GRAPH <http://example.org/w> { ?s ?p ?o } }")
+            .build()
+            .unwrap();
+        let parsed = RSPQLParser::new(
+            "REGISTER IStream <http://example.org/output> AS
+             SELECT * FROM NAMED WINDOW <http://example.org/w> ON STREAM
+             <http://example.org/stream> [RANGE 1000 STEP 500]
+             WHERE { WINDOW <http://example.org/w> { ?s ?p ?o } }"
+                .to_string(),
+        )
+        .parse();
+        assert_eq!(built.r2s, parsed.r2s);
+        assert_eq!(built.s2r, parsed.s2r);
+        assert_eq!(
+            SparqlParser::new()
+                .parse_query(&built.sparql_query)
+                .unwrap(),
+            SparqlParser::new()
+                .parse_query(&parsed.sparql_query)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_queries() {
+        assert!(builder().sparql("SELECT * WHERE {").build().is_err());
+        let no_window = ParsedQuery::builder().sparql("SELECT * WHERE { ?s ?p ?o }");
+        assert!(no_window.build().is_err());
+        let empty = WindowDefinition::new("http://example.org/w2", "http://example.org/s", 0, 0);
+        let query = builder()
+            .window(empty)
+            .sparql("SELECT * WHERE { ?s ?p ?o }");
+        assert!(query.build().is_err());
+
+        // Absence detection makes the window emit empty instances
+        let query = builder()
+            .sparql("ASK { FILTER NOT EXISTS { GRAPH <http://example.org/w> { ?s ?p ?o } } }")
+            .build()
+            .unwrap();
+        assert!(query.s2r[0].emit_empty_windows);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut query = builder()
+            .sparql("SELECT * WHERE { GRAPH <http://example.org/w> { ?s ?p ?o } }")
+            .build()
+            .unwrap();
+        query.s2r[0].report = Some(ReportStrategy::NonEmptyContent);
+        let json = serde_json::to_string(&query).unwrap();
+        let read: ParsedQuery = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, query);
+
+        // Optional window settings can be left out of configuration files
+        let window: WindowDefinition = serde_json::from_str(
+            r#"{"window_name": "http://example.org/w", "stream_name": "http://example.org/s",
+                "width": 1000, "slide": 500}"#,
+        )
+        .unwrap();
+        let expected =
+            WindowDefinition::new("http://example.org/w", "http://example.org/s", 1000, 500);
+        assert_eq!(window, expected);
+    }
 }
@@ -1,79 +1,513 @@
 use crate::parsed_query::{Operator, ParsedQuery, WindowDefinition};
-use regex::Regex;
-use std::collections::HashMap;
+use crate::{ReportStrategy, Tick};
+use spargebra::algebra::{Expression, GraphPattern};
+use spargebra::term::NamedNodePattern;
+use spargebra::{Query, SparqlParser};
+use std::collections::{HashMap, HashSet};
+
+/// Syntax of the continuous queries read by the parser
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueryDialect {
+    /// RSP-QL, with `FROM NAMED WINDOW` clauses and `WINDOW` blocks
+    #[default]
+    RspQl,
+    /// C-SPARQL, with `FROM STREAM <s> [RANGE 10s STEP 2s]` clauses whose windows
+    /// are merged into the default graph
+    CSparql,
+    /// CQELS, with `STREAM <s> [RANGE 10s SLIDE 2s] { ... }` blocks in the pattern
+    Cqels,
+}
 
 pub struct RSPQLParser {
     pub rspql_query: String,
+    dialect: QueryDialect,
+}
+
+/// Kind of a lexical token of a query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// Keyword, prefixed name, variable or number
+    Word,
+    /// IRI between angle brackets
+    Iri,
+    /// String literal, in any of the four quote styles
+    Literal,
+    /// Comment up to the end of the line
+    Comment,
+    /// Any other character
+    Punct,
+}
+
+/// Token of a query, with its position in the query text
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Token<'a> {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        self.kind == TokenKind::Punct && self.text == punct
+    }
+
+    /// Get the IRI of an IRI token without its angle brackets
+    fn iri(&self) -> Option<&'a str> {
+        (self.kind == TokenKind::Iri).then(|| &self.text[1..self.text.len() - 1])
+    }
+}
+
+/// Reads the tokens of an RSP-QL clause one by one
+struct Cursor<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    pos: usize,
+}
+
+impl<'a> Cursor<'_, 'a> {
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Option<()> {
+        self.next()
+            .filter(|token| token.is_keyword(keyword))
+            .map(|_| ())
+    }
+
+    /// Consume the next token if it is the given punctuation
+    fn punct(&mut self, punct: &str) -> bool {
+        let found = self.tokens.get(self.pos).is_some_and(|t| t.is_punct(punct));
+        self.pos += usize::from(found);
+        found
+    }
+
+    /// Read an IRI or a prefixed name
+    fn name(&mut self) -> Option<Token<'a>> {
+        self.next()
+            .filter(|token| matches!(token.kind, TokenKind::Word | TokenKind::Iri))
+    }
 }
 
 impl RSPQLParser {
     pub fn new(query: String) -> Self {
-        Self { rspql_query: query }
+        Self {
+            rspql_query: query,
+            dialect: QueryDialect::default(),
+        }
     }
 
+    /// Read the query in another dialect, translated into RSP-QL before parsing
+    ///
+    /// Window durations such as `10s` or `500ms` become milliseconds and windows
+    /// without a step are tumbling. Count-based and `NOW` windows have no RSP-QL
+    /// equivalent and are left as written, so `try_parse` reports them
+    pub fn with_dialect(mut self, dialect: QueryDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Split the query into its stream operator, its windows and the SPARQL query
+    /// evaluated over the windows
+    ///
+    /// The `REGISTER`, `FROM NAMED WINDOW` and static `FROM` / `FROM NAMED` clauses
+    /// are taken out and `WINDOW` blocks become `GRAPH` blocks; everything else is
+    /// kept as written, so any SPARQL 1.1 construct goes through unchanged
     pub fn parse(&self) -> ParsedQuery {
+        let query = match self.dialect {
+            QueryDialect::RspQl => self.rspql_query.clone(),
+            QueryDialect::CSparql => Self::translate_csparql(&self.rspql_query),
+            QueryDialect::Cqels => Self::translate_cqels(&self.rspql_query),
+        };
+        let query = query.as_str();
+        let tokens = significant_tokens(query);
         let mut parsed = ParsedQuery::new("".to_string());
-        let mut sparql_lines: Vec<String> = Vec::new();
         let mut prefix_mapper: HashMap<String, String> = HashMap::new();
+        // Ranges of the query text replaced in the SPARQL query, in order
+        let mut edits: Vec<(usize, usize, &str)> = Vec::new();
 
-        for line in self.rspql_query.lines() {
-            let trimmed_line = line.trim();
-            if trimmed_line.starts_with("REGISTER") {
-                let re = Regex::new(r"REGISTER +([^ ]+) +<([^>]+)> AS").unwrap();
-                for captures in re.captures_iter(trimmed_line) {
-                    let op_str = captures.get(1).unwrap().as_str();
-                    let name = captures.get(2).unwrap().as_str();
-                    if let Some(operator) = Self::parse_operator(op_str) {
-                        parsed.set_r2s(operator, name.to_string());
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = tokens[i];
+            if token.is_keyword("PREFIX") {
+                if let [prefix, iri, ..] = &tokens[i + 1..] {
+                    if let (Some(prefix), Some(iri)) = (prefix.text.strip_suffix(':'), iri.iri()) {
+                        prefix_mapper.insert(prefix.to_string(), iri.to_string());
+                        i += 3;
+                        continue;
                     }
                 }
-            } else if trimmed_line.starts_with("FROM NAMED WINDOW") {
-                let re = Regex::new(r"FROM +NAMED +WINDOW +([^ ]+) +ON +STREAM +([^ ]+) +\[RANGE +([^ ]+) +STEP +([^ ]+)\]").unwrap();
-                for captures in re.captures_iter(trimmed_line) {
-                    let window_name =
-                        Self::unwrap(captures.get(1).unwrap().as_str(), &prefix_mapper);
-                    let stream_name =
-                        Self::unwrap(captures.get(2).unwrap().as_str(), &prefix_mapper);
-                    let width = captures
-                        .get(3)
-                        .unwrap()
-                        .as_str()
-                        .parse::<i64>()
-                        .unwrap_or(0);
-                    let slide = captures
-                        .get(4)
-                        .unwrap()
-                        .as_str()
-                        .parse::<i64>()
-                        .unwrap_or(0);
-                    let window_def = WindowDefinition {
-                        window_name,
-                        stream_name,
-                        width,
-                        slide,
-                    };
-                    parsed.add_s2r_window(window_def);
+            } else if token.is_keyword("REGISTER") {
+                if let [operator, name, as_keyword, ..] = &tokens[i + 1..] {
+                    let name = name.iri().filter(|_| as_keyword.is_keyword("AS"));
+                    if let Some(name) = name {
+                        if let Some(operator) = Self::parse_operator(operator.text) {
+                            parsed.set_r2s(operator, name.to_string());
+                        }
+                        edits.push((token.start, as_keyword.end, ""));
+                        i += 4;
+                        continue;
+                    }
                 }
-            } else {
-                let mut sparql_line = trimmed_line.to_string();
-                if sparql_line.starts_with("WINDOW") {
-                    sparql_line = sparql_line.replace("WINDOW", "GRAPH");
+            } else if token.is_keyword("FROM") {
+                if let Some((window_def, next)) = Self::parse_window(&tokens, i, &prefix_mapper) {
+                    edits.push((token.start, tokens[next - 1].end, ""));
+                    parsed.add_s2r_window(window_def);
+                    i = next;
+                    continue;
                 }
-                if sparql_line.starts_with("PREFIX") {
-                    let re = Regex::new(r"PREFIX +([^:]*): +<([^>]+)>").unwrap();
-                    for captures in re.captures_iter(&sparql_line) {
-                        let prefix = captures.get(1).unwrap().as_str().to_string();
-                        let iri = captures.get(2).unwrap().as_str().to_string();
-                        prefix_mapper.insert(prefix, iri);
+                // Static graphs are loaded by the engine rather than left in the query,
+                // where they would hide the windows from the dataset
+                if let Some((graph, named, next)) = Self::parse_graph(&tokens, i, &prefix_mapper) {
+                    edits.push((token.start, tokens[next - 1].end, ""));
+                    if named {
+                        parsed.named_graphs.push(graph);
+                    } else {
+                        parsed.default_graphs.push(graph);
                     }
+                    i = next;
+                    continue;
                 }
-                sparql_lines.push(sparql_line);
+            } else if token.is_keyword("WINDOW") {
+                edits.push((token.start, token.end, "GRAPH"));
             }
+            i += 1;
+        }
+
+        let sparql_query = apply_edits(query, &edits);
+        parsed.set_sparql_query(sparql_query.trim().to_string());
+
+        let negated = Self::negated_windows(&parsed.sparql_query);
+        for window_def in &mut parsed.s2r {
+            window_def.emit_empty_windows = negated.contains(&window_def.window_name);
         }
-        parsed.set_sparql_query(sparql_lines.join("\n"));
         parsed
     }
 
+    /// Translate a C-SPARQL query into RSP-QL
+    ///
+    /// Each `FROM STREAM` clause becomes a window named after its stream, merged into
+    /// the default graph with a `FROM` clause, since C-SPARQL matches the pattern
+    /// over the union of the windows and the static data
+    fn translate_csparql(query: &str) -> String {
+        let tokens = significant_tokens(query);
+        let mut edits = Vec::new();
+        let mut windows = HashMap::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let mut cursor = Cursor {
+                tokens: &tokens,
+                pos: i,
+            };
+            if let Some(name) = Self::parse_csparql_register(&mut cursor) {
+                let replacement = format!("REGISTER RStream <{}> AS", name);
+                edits.push((tokens[i].start, tokens[cursor.pos - 1].end, replacement));
+                i = cursor.pos;
+                continue;
+            }
+            let mut cursor = Cursor {
+                tokens: &tokens,
+                pos: i,
+            };
+            if let Some((stream, width, slide)) = Self::parse_csparql_stream(&mut cursor) {
+                let window = window_name(stream, &mut windows);
+                let replacement = format!(
+                    "FROM NAMED WINDOW {} ON STREAM {} [RANGE {} STEP {}] FROM {}",
+                    window, stream, width, slide, window
+                );
+                edits.push((tokens[i].start, tokens[cursor.pos - 1].end, replacement));
+                i = cursor.pos;
+                continue;
+            }
+            i += 1;
+        }
+        apply_edits(query, &edits)
+    }
+
+    /// Parse `REGISTER QUERY q AS`, or `REGISTER STREAM s AS` for CONSTRUCT queries,
+    /// returning the name of the query
+    fn parse_csparql_register<'a>(cursor: &mut Cursor<'_, 'a>) -> Option<&'a str> {
+        cursor.keyword("REGISTER")?;
+        let kind = cursor.next()?;
+        if !kind.is_keyword("QUERY") && !kind.is_keyword("STREAM") {
+            return None;
+        }
+        let name = cursor.name()?;
+        cursor.keyword("AS")?;
+        Some(name.iri().unwrap_or(name.text))
+    }
+
+    /// Parse `FROM STREAM s [RANGE r STEP s]`, returning the stream and the window
+    fn parse_csparql_stream<'a>(cursor: &mut Cursor<'_, 'a>) -> Option<(&'a str, i64, i64)> {
+        cursor.keyword("FROM")?;
+        cursor.keyword("STREAM")?;
+        let stream = cursor.name()?.text;
+        let (width, slide) = Self::parse_dialect_window(cursor)?;
+        Some((stream, width, slide))
+    }
+
+    /// Translate a CQELS query into RSP-QL
+    ///
+    /// Each `STREAM s [RANGE r SLIDE s] { ... }` block becomes a `WINDOW` block over a
+    /// window named after its stream, declared before `WHERE`
+    fn translate_cqels(query: &str) -> String {
+        let tokens = significant_tokens(query);
+        let mut edits = Vec::new();
+        let mut clauses = String::new();
+        let mut windows = HashMap::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let mut cursor = Cursor {
+                tokens: &tokens,
+                pos: i,
+            };
+            let block = cursor.keyword("STREAM").and_then(|_| {
+                let stream = cursor.name()?.text;
+                let (width, slide) = Self::parse_dialect_window(&mut cursor)?;
+                let opens_block = cursor.tokens.get(cursor.pos)?.is_punct("{");
+                opens_block.then_some((stream, width, slide))
+            });
+            if let Some((stream, width, slide)) = block {
+                let window = window_name(stream, &mut windows);
+                clauses.push_str(&format!(
+                    "FROM NAMED WINDOW {} ON STREAM {} [RANGE {} STEP {}]\n",
+                    window, stream, width, slide
+                ));
+                let replacement = format!("WINDOW {}", window);
+                edits.push((tokens[i].start, tokens[cursor.pos - 1].end, replacement));
+                i = cursor.pos;
+                continue;
+            }
+            i += 1;
+        }
+
+        // Queries may omit `WHERE` before their pattern, but not after a CONSTRUCT template
+        let pattern = tokens
+            .iter()
+            .find(|token| token.is_keyword("WHERE"))
+            .or_else(|| tokens.iter().find(|token| token.is_punct("{")));
+        if let Some(pattern) = pattern.filter(|_| !clauses.is_empty()) {
+            edits.insert(0, (pattern.start, pattern.start, clauses));
+        }
+        apply_edits(query, &edits)
+    }
+
+    /// Parse the `[RANGE r STEP s]` window of a C-SPARQL or CQELS stream, also
+    /// accepting `SLIDE` for the step; without a step, or with `TUMBLING`, the window
+    /// is tumbling
+    fn parse_dialect_window(cursor: &mut Cursor) -> Option<(i64, i64)> {
+        if !cursor.punct("[") {
+            return None;
+        }
+        cursor.keyword("RANGE")?;
+        let width = parse_duration(cursor.next()?.text)?;
+        let mut slide = width;
+        while !cursor.punct("]") {
+            let option = cursor.next()?;
+            if option.is_keyword("STEP") || option.is_keyword("SLIDE") {
+                slide = parse_duration(cursor.next()?.text)?;
+            } else if !option.is_keyword("TUMBLING") {
+                return None;
+            }
+        }
+        Some((width, slide))
+    }
+
+    /// Find the windows matched under `NOT EXISTS` or on the right of `MINUS`, whose
+    /// empty instances have to report for the query to detect missing events
+    pub(crate) fn negated_windows(sparql_query: &str) -> HashSet<String> {
+        let mut windows = HashSet::new();
+        if let Ok(
+            Query::Select { pattern, .. }
+            | Query::Construct { pattern, .. }
+            | Query::Describe { pattern, .. }
+            | Query::Ask { pattern, .. },
+        ) = SparqlParser::new().parse_query(sparql_query)
+        {
+            Self::negated_in_pattern(&pattern, false, &mut windows);
+        }
+        windows
+    }
+
+    fn negated_in_pattern(pattern: &GraphPattern, negated: bool, windows: &mut HashSet<String>) {
+        match pattern {
+            GraphPattern::Graph { name, inner } => {
+                if let (true, NamedNodePattern::NamedNode(name)) = (negated, name) {
+                    windows.insert(name.as_str().to_string());
+                }
+                Self::negated_in_pattern(inner, negated, windows);
+            }
+            GraphPattern::Filter { expr, inner } => {
+                Self::negated_in_expression(expr, negated, windows);
+                Self::negated_in_pattern(inner, negated, windows);
+            }
+            GraphPattern::Minus { left, right } => {
+                Self::negated_in_pattern(left, negated, windows);
+                Self::negated_in_pattern(right, true, windows);
+            }
+            GraphPattern::Join { left, right }
+            | GraphPattern::LeftJoin { left, right, .. }
+            | GraphPattern::Union { left, right } => {
+                Self::negated_in_pattern(left, negated, windows);
+                Self::negated_in_pattern(right, negated, windows);
+            }
+            GraphPattern::Extend { inner, .. }
+            | GraphPattern::OrderBy { inner, .. }
+            | GraphPattern::Project { inner, .. }
+            | GraphPattern::Distinct { inner }
+            | GraphPattern::Reduced { inner }
+            | GraphPattern::Slice { inner, .. }
+            | GraphPattern::Group { inner, .. }
+            | GraphPattern::Service { inner, .. } => {
+                Self::negated_in_pattern(inner, negated, windows);
+            }
+            _ => {}
+        }
+    }
+
+    fn negated_in_expression(
+        expression: &Expression,
+        negated: bool,
+        windows: &mut HashSet<String>,
+    ) {
+        match expression {
+            Expression::Not(inner) => match inner.as_ref() {
+                Expression::Exists(pattern) => Self::negated_in_pattern(pattern, true, windows),
+                inner => Self::negated_in_expression(inner, negated, windows),
+            },
+            Expression::Exists(pattern) => Self::negated_in_pattern(pattern, negated, windows),
+            Expression::And(left, right) | Expression::Or(left, right) => {
+                Self::negated_in_expression(left, negated, windows);
+                Self::negated_in_expression(right, negated, windows);
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse the query like `parse`, checking that the SPARQL query evaluated over
+    /// the windows is valid SPARQL 1.1
+    pub fn try_parse(&self) -> Result<ParsedQuery, String> {
+        self.check_window_settings()?;
+        let parsed = self.parse();
+        SparqlParser::new()
+            .parse_query(&parsed.sparql_query)
+            .map_err(|e| format!("Invalid SPARQL query: {}", e))?;
+        Ok(parsed)
+    }
+
+    /// Parse a `FROM NAMED WINDOW w ON STREAM s [RANGE r STEP s ...]` clause starting
+    /// at token `i`, returning the window and the index of the token after the clause
+    fn parse_window(
+        tokens: &[Token],
+        i: usize,
+        prefix_mapper: &HashMap<String, String>,
+    ) -> Option<(WindowDefinition, usize)> {
+        let mut cursor = Cursor { tokens, pos: i };
+        cursor.keyword("FROM")?;
+        cursor.keyword("NAMED")?;
+        cursor.keyword("WINDOW")?;
+        let window_name = Self::unwrap(cursor.name()?.text, prefix_mapper);
+        cursor.keyword("ON")?;
+        cursor.keyword("STREAM")?;
+        // Several streams can feed one window: `ON STREAM ex:s1, ex:s2`
+        let stream_name = Self::unwrap(cursor.name()?.text, prefix_mapper);
+        let mut additional_stream_names = Vec::new();
+        while cursor.punct(",") {
+            additional_stream_names.push(Self::unwrap(cursor.name()?.text, prefix_mapper));
+        }
+        if !cursor.punct("[") {
+            return None;
+        }
+
+        let (mut width, mut slide) = (None, None);
+        let mut window_def = WindowDefinition {
+            window_name,
+            stream_name,
+            additional_stream_names,
+            width: 0,
+            slide: 0,
+            report: None,
+            tick: None,
+            origin: None,
+            calendar: None,
+            partition_by: None,
+            latest_value: false,
+            emit_empty_windows: false,
+            delay: None,
+            landmark: false,
+            retention: None,
+        };
+        while !cursor.punct("]") {
+            let option = cursor.next()?;
+            if option.is_keyword("RANGE") {
+                let range = cursor.next()?;
+                window_def.landmark = range.is_keyword("UNBOUNDED");
+                width = Some(range.text.parse::<i64>().unwrap_or(0));
+            } else if option.is_keyword("STEP") {
+                slide = Some(cursor.next()?.text.parse::<i64>().unwrap_or(0));
+            } else if option.is_keyword("REPORT") {
+                window_def.report = Some(Self::parse_report_strategy(cursor.next()?.text)?);
+            } else if option.is_keyword("TICK") {
+                window_def.tick = Some(Self::parse_tick(cursor.next()?.text)?);
+            } else if option.is_keyword("ALIGN") {
+                let origin = cursor.next()?;
+                if origin.is_keyword("CALENDAR") {
+                    // `ALIGN CALENDAR "CET-1CEST,M3.5.0,M10.5.0/3"`
+                    let zone = cursor.next()?;
+                    let quoted = zone.kind == TokenKind::Literal && zone.text.len() >= 2;
+                    let zone = quoted.then(|| &zone.text[1..zone.text.len() - 1])?;
+                    window_def.calendar = Some(zone.to_string());
+                } else {
+                    window_def.origin = origin.text.parse::<i64>().ok();
+                }
+            } else if option.is_keyword("DELAY") {
+                window_def.delay = cursor.next()?.text.parse::<i64>().ok();
+            } else if option.is_keyword("RETAIN") {
+                window_def.retention = cursor.next()?.text.parse::<i64>().ok();
+            } else if option.is_keyword("PARTITION") {
+                cursor.keyword("BY")?;
+                let variable = cursor.next()?.text.strip_prefix('?')?;
+                window_def.partition_by = Some(variable.to_string());
+            } else if option.is_keyword("LATEST") {
+                window_def.latest_value = true;
+            } else {
+                return None;
+            }
+        }
+        window_def.slide = slide?;
+        // Landmark windows report every step, so results span one step
+        window_def.width = if window_def.landmark {
+            window_def.slide
+        } else {
+            width?
+        };
+        Some((window_def, cursor.pos))
+    }
+
+    /// Parse a `FROM <g>` or `FROM NAMED <g>` clause starting at token `i`, returning
+    /// the graph, whether it is named and the index of the token after the clause
+    fn parse_graph(
+        tokens: &[Token],
+        i: usize,
+        prefix_mapper: &HashMap<String, String>,
+    ) -> Option<(String, bool, usize)> {
+        let mut cursor = Cursor { tokens, pos: i };
+        cursor.keyword("FROM")?;
+        let mut name = cursor.name()?;
+        let named = name.is_keyword("NAMED");
+        if named {
+            name = cursor.name()?;
+        }
+        let graph = Self::unwrap(name.text, prefix_mapper);
+        (!graph.is_empty()).then_some((graph, named, cursor.pos))
+    }
+
     fn parse_operator(op_str: &str) -> Option<Operator> {
         match op_str {
             "RStream" => Some(Operator::RStream),
@@ -83,6 +517,44 @@ impl RSPQLParser {
         }
     }
 
+    /// Parse a `REPORT` setting; strategies the windows don't implement are unknown
+    fn parse_report_strategy(report_str: &str) -> Option<ReportStrategy> {
+        match report_str {
+            "NON_EMPTY_CONTENT" => Some(ReportStrategy::NonEmptyContent),
+            "ON_WINDOW_CLOSE" => Some(ReportStrategy::OnWindowClose),
+            _ => None,
+        }
+    }
+
+    /// Parse a `TICK` setting; only time-driven windows are implemented
+    fn parse_tick(tick_str: &str) -> Option<Tick> {
+        match tick_str {
+            "TIME_DRIVEN" => Some(Tick::TimeDriven),
+            _ => None,
+        }
+    }
+
+    /// Check the `REPORT` and `TICK` settings of the window clauses, whose clause
+    /// is left in the query when the setting is unknown
+    pub(crate) fn check_window_settings(&self) -> Result<(), String> {
+        for pair in significant_tokens(&self.rspql_query).windows(2) {
+            let (setting, value) = (&pair[0], pair[1].text);
+            if setting.is_keyword("REPORT") && Self::parse_report_strategy(value).is_none() {
+                return Err(format!(
+                    "Unsupported report strategy {}: only NON_EMPTY_CONTENT and ON_WINDOW_CLOSE are implemented",
+                    value
+                ));
+            }
+            if setting.is_keyword("TICK") && Self::parse_tick(value).is_none() {
+                return Err(format!(
+                    "Unsupported tick {}: only TIME_DRIVEN is implemented",
+                    value
+                ));
+            }
+        }
+        Ok(())
+    }
+
     fn unwrap(prefixed_iri: &str, mapper: &HashMap<String, String>) -> String {
         let trimmed = prefixed_iri.trim();
         if trimmed.starts_with('<') && trimmed.ends_with('>') {
@@ -101,3 +573,513 @@ impl RSPQLParser {
         }
     }
 }
+
+/// Replace ranges of a query, given in order, with other text
+fn apply_edits<S: AsRef<str>>(query: &str, edits: &[(usize, usize, S)]) -> String {
+    let mut edited = String::with_capacity(query.len());
+    let mut copied = 0;
+    for (start, end, replacement) in edits {
+        edited.push_str(&query[copied..*start]);
+        edited.push_str(replacement.as_ref());
+        copied = *end;
+    }
+    edited.push_str(&query[copied..]);
+    edited
+}
+
+/// Name the window over a stream of a C-SPARQL or CQELS query after the stream,
+/// suffixing the streams with several windows, e.g. `ex:s_2`
+fn window_name(stream: &str, windows: &mut HashMap<String, usize>) -> String {
+    let count = windows.entry(stream.to_string()).or_insert(0);
+    *count += 1;
+    match (*count, stream.strip_suffix('>')) {
+        (1, _) => stream.to_string(),
+        (n, Some(iri)) => format!("{}_{}>", iri, n),
+        (n, None) => format!("{}_{}", stream, n),
+    }
+}
+
+/// Parse a C-SPARQL or CQELS duration in ms, e.g. `10s`, `5m` or `500ms`; plain
+/// numbers are ms
+fn parse_duration(text: &str) -> Option<i64> {
+    let digits = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (value, unit) = text.split_at(digits);
+    let factor = match unit.to_ascii_lowercase().as_str() {
+        "" | "ms" => 1,
+        "s" => 1000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        _ => return None,
+    };
+    value.parse::<i64>().ok()?.checked_mul(factor)
+}
+
+/// Split a query into tokens, without comments
+fn significant_tokens(query: &str) -> Vec<Token<'_>> {
+    tokenize(query)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect()
+}
+
+/// Split a query into tokens, keeping IRIs, string literals and comments whole
+fn tokenize(query: &str) -> Vec<Token<'_>> {
+    let bytes = query.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        let kind = match bytes[pos] {
+            byte if byte.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            b'#' => {
+                pos = bytes[pos..]
+                    .iter()
+                    .position(|byte| *byte == b'\n')
+                    .map_or(bytes.len(), |offset| pos + offset);
+                TokenKind::Comment
+            }
+            b'<' => match iri_end(bytes, pos) {
+                Some(end) => {
+                    pos = end;
+                    TokenKind::Iri
+                }
+                // A comparison
+                None => {
+                    pos += 1;
+                    TokenKind::Punct
+                }
+            },
+            quote @ (b'"' | b'\'') => {
+                pos = literal_end(bytes, pos, quote);
+                TokenKind::Literal
+            }
+            byte if is_word_byte(byte) => {
+                while pos < bytes.len() && is_word_byte(bytes[pos]) {
+                    pos += 1;
+                }
+                // Names can contain dots but not end with one, e.g. `?s ?p ex:o.`
+                while pos > start + 1 && bytes[pos - 1] == b'.' {
+                    pos -= 1;
+                }
+                TokenKind::Word
+            }
+            _ => {
+                pos += 1;
+                TokenKind::Punct
+            }
+        };
+        tokens.push(Token {
+            kind,
+            text: &query[start..pos],
+            start,
+            end: pos,
+        });
+    }
+    tokens
+}
+
+/// Whether a byte belongs to a keyword, prefixed name, variable or number
+/// Multi-byte characters are part of words
+fn is_word_byte(byte: u8) -> bool {
+    !byte.is_ascii()
+        || byte.is_ascii_alphanumeric()
+        || matches!(byte, b'_' | b':' | b'?' | b'$' | b'-' | b'.' | b'%')
+}
+
+/// Get the end of the IRI starting at `start`, if `<` opens an IRI there
+fn iri_end(bytes: &[u8], start: usize) -> Option<usize> {
+    for (offset, byte) in bytes[start + 1..].iter().enumerate() {
+        match byte {
+            b'>' => return Some(start + offset + 2),
+            b'<' | b'"' | b'{' | b'}' | b'|' | b'^' | b'`' | b'\\' => return None,
+            byte if byte.is_ascii_whitespace() => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Get the end of the string literal starting at `start` with `quote`
+fn literal_end(bytes: &[u8], start: usize, quote: u8) -> usize {
+    let long = bytes[start..].starts_with(&[quote; 3]);
+    let mut pos = start + if long { 3 } else { 1 };
+    while pos < bytes.len() {
+        if bytes[pos] == b'\\' {
+            pos += 2;
+        } else if long && bytes[pos..].starts_with(&[quote; 3]) {
+            return pos + 3;
+        } else if !long && bytes[pos] == quote {
+            return pos + 1;
+        } else {
+            pos += 1;
+        }
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the SPARQL query of an RSP-QL query has the algebra of `expected`
+    fn assert_sparql(rspql: &str, expected: &str) -> ParsedQuery {
+        let parsed = RSPQLParser::new(rspql.to_string()).try_parse().unwrap();
+        let actual = SparqlParser::new()
+            .parse_query(&parsed.sparql_query)
+            .unwrap();
+        let expected = SparqlParser::new().parse_query(expected).unwrap();
+        assert_eq!(actual, expected, "{}", parsed.sparql_query);
+        parsed
+    }
+
+    #[test]
+    fn test_window_clauses() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER IStream <http://example.org/out> AS
+            SELECT *
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:s1, <http://example.org/s2> [RANGE 1000 STEP 500 REPORT NON_EMPTY_CONTENT TICK TIME_DRIVEN ALIGN -250 DELAY 200 PARTITION BY ?sensor LATEST]
+            FROM NAMED WINDOW <http://example.org/w2> ON STREAM ex:s.3 [RANGE UNBOUNDED STEP 10 RETAIN 6]
+            FROM NAMED WINDOW ex:w3 ON STREAM ex:s1 [RANGE 86400000 STEP 86400000 ALIGN CALENDAR "CET-1CEST,M3.5.0,M10.5.0/3"]
+            WHERE { WINDOW ex:w1 { ?sensor ?p ?o } WINDOW <http://example.org/w2> { ?o ?q ?r } }
+        "#;
+        let parsed = assert_sparql(
+            query,
+            "PREFIX ex: <http://example.org/>
+             SELECT * WHERE {
+                 GRAPH ex:w1 { ?sensor ?p ?o } GRAPH <http://example.org/w2> { ?o ?q ?r }
+             }",
+        );
+        assert!(matches!(parsed.r2s.operator, Operator::IStream));
+        assert_eq!(parsed.r2s.name, "http://example.org/out");
+        assert_eq!(parsed.s2r.len(), 3);
+
+        let w1 = &parsed.s2r[0];
+        assert_eq!(w1.window_name, "http://example.org/w1");
+        assert_eq!(w1.stream_name, "http://example.org/s1");
+        assert_eq!(w1.additional_stream_names, vec!["http://example.org/s2"]);
+        assert_eq!((w1.width, w1.slide), (1000, 500));
+        assert_eq!(w1.report, Some(ReportStrategy::NonEmptyContent));
+        assert_eq!(w1.tick, Some(Tick::TimeDriven));
+        assert_eq!(w1.origin, Some(-250));
+        assert_eq!(w1.delay, Some(200));
+        assert_eq!(w1.partition_by.as_deref(), Some("sensor"));
+        assert!(w1.latest_value);
+        assert!(!w1.landmark && w1.retention.is_none() && w1.calendar.is_none());
+
+        let w2 = &parsed.s2r[1];
+        assert_eq!(w2.window_name, "http://example.org/w2");
+        assert_eq!(w2.stream_name, "http://example.org/s.3");
+        assert_eq!((w2.width, w2.slide), (10, 10));
+        assert!(w2.report.is_none() && w2.origin.is_none() && !w2.latest_value);
+        assert!(w2.delay.is_none());
+        assert!(w2.landmark);
+        assert_eq!(w2.retention, Some(6));
+
+        let w3 = &parsed.s2r[2];
+        assert_eq!((w3.width, w3.slide), (86_400_000, 86_400_000));
+        let calendar = w3.calendar.as_deref();
+        assert_eq!(calendar, Some("CET-1CEST,M3.5.0,M10.5.0/3"));
+        assert!(w3.origin.is_none());
+    }
+
+    #[test]
+    fn test_sparql_constructs_survive_rewriting() {
+        let sparql = r#"
+            PREFIX ex: <http://example.org/>
+            PREFIX rdfs: <http://www.w3.org/2000/01/rdf-schema#>
+            SELECT ?sensor (AVG(?value) AS ?average) (COUNT(*) AS ?n)
+            WHERE {
+                # A comment mentioning WINDOW ex:w is left alone
+                WINDOW ex:w {
+                    ?sensor ex:observes/rdfs:label ?property ; ex:value ?value .
+                    OPTIONAL { ?sensor ex:location ?location }
+                    FILTER(?value < 100 && ?property != "WINDOW" && ?value>0)
+                    BIND(?value * 2 AS ?double)
+                    {
+                        SELECT ?sensor (MAX(?value) AS ?max)
+                        WHERE { ?sensor ex:value ?value }
+                        GROUP BY ?sensor
+                    }
+                    VALUES ?property { "temperature" """humidity""" }
+                    MINUS { ?sensor ex:broken true }
+                }
+                { WINDOW ex:w { ?sensor a ex:Sensor } } UNION { WINDOW ex:w { ?sensor a ex:Node } }
+            }
+            GROUP BY ?sensor
+            HAVING (AVG(?value) > 10)
+            ORDER BY DESC(?average)
+            LIMIT 5 OFFSET 1
+        "#;
+        let rspql = sparql.replacen("SELECT", "REGISTER RStream <out> AS\nSELECT", 1);
+        let rspql = rspql.replacen(
+            "WHERE",
+            "FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 1000 STEP 100]\nWHERE",
+            1,
+        );
+        let parsed = assert_sparql(&rspql, &sparql.replace("WINDOW ex:w {", "GRAPH ex:w {"));
+        assert_eq!(parsed.s2r.len(), 1);
+        assert!(
+            parsed
+                .sparql_query
+                .contains("# A comment mentioning WINDOW ex:w")
+        );
+        assert!(parsed.sparql_query.contains("\"WINDOW\""));
+    }
+
+    #[test]
+    fn test_query_forms() {
+        let prefix = "PREFIX ex: <http://example.org/>\n";
+        let window = "FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 10 STEP 10]";
+        let forms = [
+            ("ASK", "ASK"),
+            (
+                "CONSTRUCT { ?s ex:seen true }",
+                "CONSTRUCT { ?s ex:seen true }",
+            ),
+            ("DESCRIBE ?s", "DESCRIBE ?s"),
+            ("SELECT DISTINCT ?s", "SELECT DISTINCT ?s"),
+        ];
+        for (form, expected_form) in forms {
+            // RSP-QL clauses and WINDOW blocks on a single line
+            let rspql = format!(
+                "{}REGISTER RStream <out> AS {} {} WHERE {{ WINDOW ex:w {{ ?s ?p ?o }} }}",
+                prefix, form, window
+            );
+            let expected = format!(
+                "{}{} WHERE {{ GRAPH ex:w {{ ?s ?p ?o }} }}",
+                prefix, expected_form
+            );
+            let parsed = assert_sparql(&rspql, &expected);
+            assert_eq!(parsed.s2r.len(), 1, "{}", form);
+        }
+    }
+
+    #[test]
+    fn test_repeated_window() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <out> AS
+            SELECT ?s ?x ?y
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:s [RANGE 10 STEP 10]
+            WHERE { WINDOW ex:w1 { ?s ex:a ?x } WINDOW ex:w1 { ?s ex:b ?y } }
+        "#;
+        let parsed = assert_sparql(
+            query,
+            "PREFIX ex: <http://example.org/>
+             SELECT ?s ?x ?y WHERE { GRAPH ex:w1 { ?s ex:a ?x } GRAPH ex:w1 { ?s ex:b ?y } }",
+        );
+        assert_eq!(parsed.s2r.len(), 1);
+    }
+
+    #[test]
+    fn test_negated_windows_emit_empty_windows() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <out> AS
+            SELECT ?sensor
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:s1 [RANGE 10 STEP 10]
+            FROM NAMED WINDOW ex:w2 ON STREAM ex:s2 [RANGE 10 STEP 10]
+            FROM NAMED WINDOW ex:w3 ON STREAM ex:s3 [RANGE 10 STEP 10]
+            WHERE {
+                WINDOW ex:w3 { ?sensor a ex:Sensor }
+                FILTER (NOT EXISTS { WINDOW ex:w1 { ?sensor ex:heartbeat ?t } } && BOUND(?sensor))
+                MINUS { WINDOW ex:w2 { ?sensor ex:disabled true } }
+            }
+        "#;
+        let parsed = RSPQLParser::new(query.to_string()).parse();
+        let emit_empty_windows: Vec<bool> =
+            parsed.s2r.iter().map(|w| w.emit_empty_windows).collect();
+        assert_eq!(emit_empty_windows, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_static_graphs() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <out> AS
+            SELECT ?sensor ?room
+            FROM <http://example.org/building>
+            FROM NAMED ex:calibration
+            FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 10 STEP 10]
+            WHERE {
+                WINDOW ex:w { ?sensor ex:value ?v }
+                ?sensor ex:room ?room
+                GRAPH ex:calibration { ?sensor ex:offset ?offset }
+            }
+        "#;
+        let parsed = assert_sparql(
+            query,
+            "PREFIX ex: <http://example.org/>
+             SELECT ?sensor ?room WHERE {
+                 GRAPH ex:w { ?sensor ex:value ?v }
+                 ?sensor ex:room ?room
+                 GRAPH ex:calibration { ?sensor ex:offset ?offset }
+             }",
+        );
+        assert_eq!(parsed.default_graphs, vec!["http://example.org/building"]);
+        assert_eq!(parsed.named_graphs, vec!["http://example.org/calibration"]);
+        assert_eq!(parsed.s2r.len(), 1);
+        assert_eq!(parsed.static_graphs().count(), 2);
+    }
+
+    #[test]
+    fn test_csparql_dialect() {
+        let query = r#"
+            REGISTER QUERY temperatures AS
+            PREFIX ex: <http://example.org/>
+            SELECT ?room (AVG(?v) AS ?avg)
+            FROM STREAM ex:s1 [RANGE 10s STEP 2s]
+            FROM STREAM <http://example.org/s2> [RANGE 1m TUMBLING]
+            FROM <http://example.org/building>
+            WHERE { ?sensor ex:value ?v ; ex:room ?room }
+            GROUP BY ?room
+        "#;
+        let parsed = RSPQLParser::new(query.to_string())
+            .with_dialect(QueryDialect::CSparql)
+            .try_parse()
+            .unwrap();
+        assert_eq!(parsed.r2s.name, "temperatures");
+        let windows: Vec<_> = parsed
+            .s2r
+            .iter()
+            .map(|w| {
+                (
+                    w.window_name.as_str(),
+                    w.stream_name.as_str(),
+                    w.width,
+                    w.slide,
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                (
+                    "http://example.org/s1",
+                    "http://example.org/s1",
+                    10_000,
+                    2000
+                ),
+                (
+                    "http://example.org/s2",
+                    "http://example.org/s2",
+                    60_000,
+                    60_000
+                ),
+            ]
+        );
+        // The windows are matched in the default graph, but aren't static graphs
+        assert_eq!(parsed.default_graphs.len(), 3);
+        assert_eq!(
+            parsed.static_graphs().collect::<Vec<_>>(),
+            vec!["http://example.org/building"]
+        );
+
+        // Count-based windows have no equivalent
+        let count_based = query.replace("[RANGE 1m TUMBLING]", "[RANGE TRIPLES 10]");
+        let parser = RSPQLParser::new(count_based).with_dialect(QueryDialect::CSparql);
+        assert!(parser.try_parse().is_err());
+    }
+
+    #[test]
+    fn test_cqels_dialect() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT ?sensor ?v ?room
+            WHERE {
+                STREAM ex:s1 [RANGE 500ms SLIDE 100ms] { ?sensor ex:value ?v }
+                STREAM ex:s1 [RANGE 1h] { ?sensor ex:status ex:ok }
+                GRAPH ex:building { ?sensor ex:room ?room }
+            }
+        "#;
+        let parsed = RSPQLParser::new(query.to_string())
+            .with_dialect(QueryDialect::Cqels)
+            .try_parse()
+            .unwrap();
+        let windows: Vec<_> = parsed
+            .s2r
+            .iter()
+            .map(|w| {
+                (
+                    w.window_name.as_str(),
+                    w.stream_name.as_str(),
+                    w.width,
+                    w.slide,
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                ("http://example.org/s1", "http://example.org/s1", 500, 100),
+                (
+                    "http://example.org/s1_2",
+                    "http://example.org/s1",
+                    3_600_000,
+                    3_600_000
+                ),
+            ]
+        );
+        let actual = SparqlParser::new()
+            .parse_query(&parsed.sparql_query)
+            .unwrap();
+        let expected = SparqlParser::new()
+            .parse_query(
+                "PREFIX ex: <http://example.org/>
+                 SELECT ?sensor ?v ?room WHERE {
+                     GRAPH ex:s1 { ?sensor ex:value ?v }
+                     GRAPH ex:s1_2 { ?sensor ex:status ex:ok }
+                     GRAPH ex:building { ?sensor ex:room ?room }
+                 }",
+            )
+            .unwrap();
+        assert_eq!(actual, expected, "{}", parsed.sparql_query);
+    }
+
+    #[test]
+    fn test_invalid_sparql_is_reported() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <out> AS
+            SELECT * FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 10 STEP 10]
+            WHERE { WINDOW ex:w { ?s ?p
+        "#;
+        let parser = RSPQLParser::new(query.to_string());
+        assert!(parser.try_parse().is_err());
+        // Still split into its RSP-QL parts
+        assert_eq!(parser.parse().s2r.len(), 1);
+    }
+
+    #[test]
+    fn test_unsupported_window_settings_are_reported() {
+        let window = |settings: &str| {
+            let query = format!(
+                "PREFIX ex: <http://example.org/>
+                 SELECT * FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 10 STEP 10 {}]
+                 WHERE {{ WINDOW ex:w {{ ?s ?p ?o }} }}",
+                settings
+            );
+            RSPQLParser::new(query).try_parse().map(|parsed| parsed.s2r)
+        };
+        assert_eq!(window("REPORT ON_WINDOW_CLOSE").unwrap().len(), 1);
+        for settings in [
+            "REPORT PERIODIC",
+            "REPORT ON_CONTENT_CHANGE",
+            "REPORT NEVER",
+        ] {
+            let error = window(settings).unwrap_err();
+            assert!(error.contains("Unsupported report strategy"), "{}", error);
+        }
+        for settings in ["TICK TUPLE_DRIVEN", "TICK BATCH_DRIVEN", "TICK NEVER"] {
+            let error = window(settings).unwrap_err();
+            assert!(error.contains("Unsupported tick"), "{}", error);
+        }
+    }
+}
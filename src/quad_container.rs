@@ -1,36 +1,143 @@
+use crate::timestamp::Instant;
 use oxigraph::model::Quad;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 // Representing a container for RDF Quads in the Window.
 #[derive(Debug, Clone)]
 pub struct QuadContainer {
-    pub elements: HashSet<Quad>,
+    /// Quads are shared, so copying them into several windows or reports doesn't clone them
+    pub elements: HashSet<Arc<Quad>>,
     pub last_timestamp_changed: i64,
+    /// Timestamp of the last event adding each quad; quads read back from a spill
+    /// file have none
+    pub timestamps: HashMap<Arc<Quad>, i64>,
+    /// Stream of the last event adding each quad, for windows recording it
+    pub streams: HashMap<Arc<Quad>, Arc<str>>,
+    /// Bounds `[open, close)` of the window instance the container was reported for
+    pub window_bounds: Option<(i64, i64)>,
+    /// When the event was received by its stream or, for a report, the event closing
+    /// the window instance; `None` if unknown
+    pub received: Option<Instant>,
+    /// Number of events adding each quad, for containers keeping duplicate events,
+    /// see `count_duplicates`; `None` for a set of quads
+    pub multiplicities: Option<HashMap<Arc<Quad>, usize>>,
+    /// Whether the container marks the end of its stream instead of carrying an
+    /// event: the window applies it at the closing time of its open instances
+    pub(crate) end_of_stream: bool,
 }
 
 impl QuadContainer {
     pub fn new(elements: HashSet<Quad>, ts: i64) -> Self {
+        Self::from_shared(elements.into_iter().map(Arc::new).collect(), ts)
+    }
+
+    /// Create a container from quads that are already shared
+    pub fn from_shared(elements: HashSet<Arc<Quad>>, ts: i64) -> Self {
         Self {
+            timestamps: elements.iter().map(|quad| (quad.clone(), ts)).collect(),
+            streams: HashMap::new(),
             elements,
             last_timestamp_changed: ts,
+            window_bounds: None,
+            received: None,
+            multiplicities: None,
+            end_of_stream: false,
+        }
+    }
+
+    /// Count the events adding each quad from now on, so identical observations at
+    /// different times are kept apart instead of collapsing into one quad
+    /// Quads already in the container count once
+    pub fn count_duplicates(&mut self) {
+        if self.multiplicities.is_none() {
+            let counts = self.elements.iter().map(|quad| (quad.clone(), 1)).collect();
+            self.multiplicities = Some(counts);
         }
     }
 
+    /// Get the number of events adding a quad, at most 1 unless duplicates are counted
+    pub fn multiplicity(&self, quad: &Quad) -> usize {
+        match &self.multiplicities {
+            Some(counts) => counts.get(quad).copied().unwrap_or(0),
+            None => usize::from(self.contains(quad)),
+        }
+    }
+
+    /// Iterate over the quads added by several events, with their number
+    pub fn repeated(&self) -> impl Iterator<Item = (&Quad, usize)> {
+        let counts = self.multiplicities.iter().flatten();
+        counts
+            .filter(|(_, count)| **count > 1)
+            .map(|(quad, count)| (&**quad, *count))
+    }
+
     pub fn len(&self) -> usize {
         self.elements.len()
     }
 
-    pub fn add(&mut self, quad: Quad, ts: i64) {
+    /// Add a quad, either owned or shared with other containers
+    pub fn add(&mut self, quad: impl Into<Arc<Quad>>, ts: i64) {
+        let quad = quad.into();
+        if let Some(counts) = &mut self.multiplicities {
+            *counts.entry(quad.clone()).or_default() += 1;
+        }
+        self.timestamps.insert(quad.clone(), ts);
         self.elements.insert(quad);
         self.last_timestamp_changed = ts;
     }
 
+    /// Add a quad from an event of `stream`, if known
+    pub fn add_from(&mut self, quad: impl Into<Arc<Quad>>, ts: i64, stream: Option<&Arc<str>>) {
+        let quad = quad.into();
+        match stream {
+            Some(stream) => self.streams.insert(quad.clone(), stream.clone()),
+            None => self.streams.remove(&quad),
+        };
+        self.add(quad, ts);
+    }
+
+    /// Add the quads of another container with their own timestamps, keeping the
+    /// time of this container's last change
+    /// Containers counting duplicates add up the events of both
+    pub fn merge(&mut self, other: QuadContainer) {
+        for quad in other.elements {
+            if let Some(counts) = &mut self.multiplicities {
+                let counted = other.multiplicities.as_ref();
+                let count = counted.and_then(|counted| counted.get(&quad)).copied();
+                *counts.entry(quad.clone()).or_default() += count.unwrap_or(1);
+            }
+            if let Some(ts) = other.timestamps.get(&quad) {
+                self.timestamps.insert(quad.clone(), *ts);
+            }
+            if let Some(stream) = other.streams.get(&quad) {
+                self.streams.insert(quad.clone(), stream.clone());
+            }
+            self.elements.insert(quad);
+        }
+    }
+
+    /// Get the timestamp of the last event adding a quad
+    pub fn timestamp(&self, quad: &Quad) -> Option<i64> {
+        self.timestamps.get(quad).copied()
+    }
+
+    /// Get the stream of the last event adding a quad, if recorded
+    pub fn stream(&self, quad: &Quad) -> Option<&str> {
+        self.streams.get(quad).map(|stream| &**stream)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.elements.is_empty()
     }
 
     pub fn remove(&mut self, quad: &Quad, ts: i64) {
         self.elements.remove(quad);
+        self.timestamps.remove(quad);
+        self.streams.remove(quad);
+        if let Some(counts) = &mut self.multiplicities {
+            counts.remove(quad);
+        }
         self.last_timestamp_changed = ts;
     }
 
@@ -38,8 +145,18 @@ impl QuadContainer {
         self.elements.contains(quad)
     }
 
+    /// Iterate over the quads of the container
+    pub fn iter(&self) -> impl Iterator<Item = &Quad> {
+        self.elements.iter().map(|quad| &**quad)
+    }
+
     pub fn clear(&mut self, ts: i64) {
         self.elements.clear();
+        self.timestamps.clear();
+        self.streams.clear();
+        if let Some(counts) = &mut self.multiplicities {
+            counts.clear();
+        }
         self.last_timestamp_changed = ts;
     }
 }
@@ -75,13 +192,73 @@ mod tests {
         container.add(quad2.clone(), 2);
         assert_eq!(container.len(), 2);
         assert!(container.contains(&quad2));
+        assert_eq!(container.timestamp(&quad1), Some(1));
+        assert_eq!(container.timestamp(&quad2), Some(2));
+
+        // Sharing a quad with another container doesn't copy it
+        let mut other = QuadContainer::new(HashSet::new(), 0);
+        for quad in &container.elements {
+            other.add(quad.clone(), 2);
+        }
+        assert_eq!(other.len(), 2);
+        assert!(other.elements.iter().all(|quad| {
+            container
+                .elements
+                .get(quad)
+                .is_some_and(|q| Arc::ptr_eq(q, quad))
+        }));
 
         container.remove(&quad1, 3);
         assert_eq!(container.len(), 1);
         assert!(!container.contains(&quad1));
+        assert_eq!(container.timestamp(&quad1), None);
+
+        // Merged quads keep their own timestamp
+        let mut merged = QuadContainer::new(HashSet::new(), 5);
+        merged.merge(container.clone());
+        assert_eq!(merged.timestamp(&quad2), Some(2));
+        assert_eq!(merged.last_timestamp_changed, 5);
+
+        // Quads keep the stream of their last event, if any
+        let stream: Arc<str> = Arc::from("http://example.org/stream");
+        merged.add_from(quad1.clone(), 6, Some(&stream));
+        assert_eq!(merged.stream(&quad1), Some("http://example.org/stream"));
+        assert_eq!(merged.stream(&quad2), None);
+        merged.add_from(quad1.clone(), 7, None);
+        assert_eq!(merged.stream(&quad1), None);
 
         container.clear(4);
         assert_eq!(container.len(), 0);
         assert!(container.is_empty());
     }
+
+    #[test]
+    fn test_count_duplicates() {
+        let quad = Quad::new(
+            NamedNode::new("http://example.org/sensor1").unwrap(),
+            NamedNode::new("http://example.org/value").unwrap(),
+            Literal::from(20),
+            NamedNode::new("http://example.org/w1").unwrap(),
+        );
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        container.add(quad.clone(), 1);
+        container.add(quad.clone(), 2);
+        assert_eq!(container.multiplicity(&quad), 1);
+
+        container.count_duplicates();
+        container.add(quad.clone(), 3);
+        assert_eq!(container.len(), 1);
+        assert_eq!(container.multiplicity(&quad), 2);
+        assert_eq!(container.repeated().collect::<Vec<_>>(), vec![(&quad, 2)]);
+
+        // Merged events add up, whether their container counts them or not
+        let mut merged = container.clone();
+        merged.merge(container.clone());
+        merged.merge(QuadContainer::new(HashSet::from([quad.clone()]), 4));
+        assert_eq!(merged.multiplicity(&quad), 5);
+
+        container.remove(&quad, 5);
+        assert_eq!(container.multiplicity(&quad), 0);
+        assert_eq!(container.repeated().count(), 0);
+    }
 }
@@ -0,0 +1,589 @@
+use crate::BindingWithTimestamp;
+use crate::delta::Binding;
+use crate::file::{json_object, json_string};
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Delivery guarantee of a `ReliableSink`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryGuarantee {
+    /// Results are retried until acknowledged, so the target may receive some twice
+    AtLeastOnce,
+    /// Results are retried until acknowledged, and results whose dedup key was
+    /// already delivered are not sent again; targets drop the duplicates of a
+    /// replay after a restart by the same key
+    ExactlyOnce,
+}
+
+/// What a `ReliableSink` does with new results while its queue is full, e.g. during
+/// an outage of its target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Refuse the new result with an error, keeping the queued ones
+    #[default]
+    Reject,
+    /// Drop the oldest result not delivered yet to make room for the new one
+    DropOldest,
+}
+
+/// Result numbered for delivery by a `ReliableSink`
+#[derive(Debug, Clone)]
+pub struct SequencedResult {
+    /// Position of the result in the output of the sink, from 1
+    pub sequence: u64,
+    /// Key identifying the result across retries and replays
+    pub key: String,
+    pub result: BindingWithTimestamp,
+}
+
+/// System results are delivered to by a `ReliableSink`, e.g. a Kafka producer or
+/// an HTTP endpoint, acknowledging the batches it received
+pub trait AckSink: Send {
+    /// Deliver a batch of results, returning once the target acknowledged all of them
+    fn deliver(&mut self, batch: &[SequencedResult]) -> Result<(), String>;
+}
+
+impl<F> AckSink for F
+where
+    F: FnMut(&[SequencedResult]) -> Result<(), String> + Send,
+{
+    fn deliver(&mut self, batch: &[SequencedResult]) -> Result<(), String> {
+        self(batch)
+    }
+}
+
+/// Target posting batches to an HTTP endpoint as JSON lines, each result with its
+/// `sequence` and `key`; any 2xx response acknowledges the batch
+pub struct HttpAckSink {
+    endpoint: String,
+    agent: ureq::Agent,
+}
+
+impl HttpAckSink {
+    /// Create a target posting to `endpoint`
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Set the timeout of each request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    /// Format a batch as the body of a request
+    pub fn body(batch: &[SequencedResult]) -> String {
+        let mut body = String::new();
+        for result in batch {
+            let object = json_object(&result.result);
+            body.push_str(&format!(
+                "{{\"sequence\":{},\"key\":{},\"result\":{}}}\n",
+                result.sequence,
+                json_string(&result.key),
+                object
+            ));
+        }
+        body
+    }
+}
+
+impl AckSink for HttpAckSink {
+    fn deliver(&mut self, batch: &[SequencedResult]) -> Result<(), String> {
+        // The first and last keys let the endpoint recognize a retried batch
+        let idempotency_key = match (batch.first(), batch.last()) {
+            (Some(first), Some(last)) => format!("{}..{}", first.key, last.key),
+            _ => return Ok(()),
+        };
+        self.agent
+            .post(&self.endpoint)
+            .set("Content-Type", "application/x-ndjson")
+            .set("Idempotency-Key", &idempotency_key)
+            .send_string(&Self::body(batch))
+            .map(|_| ())
+            .map_err(|e| format!("Delivery to {} failed: {}", self.endpoint, e))
+    }
+}
+
+/// Function computing the dedup key of a result
+type DedupKey = Arc<dyn Fn(&BindingWithTimestamp) -> String + Send + Sync>;
+
+/// Function committing the delivery up to a sequence number, see `ReliableSink::on_commit`
+type CommitHook = Box<dyn FnMut(u64) -> Result<(), String> + Send>;
+
+/// Sink delivering window results to an external system at least or exactly once
+///
+/// Results are numbered and buffered until their target acknowledges them; failed
+/// batches stay buffered and are retried with exponential backoff, then again on
+/// the next flush, instead of being dropped. With a commit hook, acknowledged
+/// results only count as delivered once the hook committed them, e.g. together
+/// with the offsets of the input in a checkpoint or a Kafka transaction, and are
+/// delivered again if the commit fails. The queue is bounded, see `with_max_pending`.
+///
+/// ```rust,no_run
+/// use rsp_rs::{DeliveryGuarantee, HttpAckSink, RSPEngine, ReliableSink};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let last_checkpoint = 0;
+/// let sink = ReliableSink::new(
+///     HttpAckSink::new("http://localhost:8080/results"),
+///     DeliveryGuarantee::ExactlyOnce,
+/// )
+/// .resume_after(last_checkpoint)
+/// .on_commit(|sequence| {
+///     println!("Delivered up to {}", sequence);
+///     Ok(())
+/// });
+/// sink.spawn(engine.start_processing().into_receiver());
+/// ```
+pub struct ReliableSink<S: AckSink> {
+    target: S,
+    guarantee: DeliveryGuarantee,
+    batch_size: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    dedup_key: DedupKey,
+    commit_hook: Option<CommitHook>,
+    next_sequence: u64,
+    /// Results not acknowledged yet, in order
+    pending: VecDeque<SequencedResult>,
+    /// Results acknowledged but not committed yet, with a commit hook
+    uncommitted: Vec<SequencedResult>,
+    /// Keys of the pending and uncommitted results, for exactly-once delivery
+    queued_keys: HashSet<String>,
+    max_pending: usize,
+    overflow: OverflowPolicy,
+    /// Last sequence number delivered (acknowledged, and committed with a hook)
+    delivered: u64,
+    /// Keys of the delivered results, oldest first, for exactly-once delivery
+    delivered_keys: HashSet<String>,
+    key_order: VecDeque<String>,
+    max_keys: usize,
+}
+
+impl<S: AckSink> ReliableSink<S> {
+    /// Create a sink delivering to `target`
+    pub fn new(target: S, guarantee: DeliveryGuarantee) -> Self {
+        Self {
+            target,
+            guarantee,
+            batch_size: 64,
+            max_retries: 3,
+            retry_delay: Duration::from_millis(100),
+            dedup_key: Arc::new(default_dedup_key),
+            commit_hook: None,
+            next_sequence: 1,
+            pending: VecDeque::new(),
+            uncommitted: Vec::new(),
+            queued_keys: HashSet::new(),
+            max_pending: 100_000,
+            overflow: OverflowPolicy::Reject,
+            delivered: 0,
+            delivered_keys: HashSet::new(),
+            key_order: VecDeque::new(),
+            max_keys: 100_000,
+        }
+    }
+
+    /// Set the maximum number of results delivered in one batch
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Retry failed batches up to `max_retries` times, waiting `retry_delay`
+    /// before the first retry and twice as long before each next one
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Compute the dedup key of each result, by default from its window, its row in
+    /// the window report and its solution
+    pub fn with_dedup_key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&BindingWithTimestamp) -> String + Send + Sync + 'static,
+    {
+        self.dedup_key = Arc::new(key);
+        self
+    }
+
+    /// Remember the keys of the last `max_keys` delivered results for exactly-once
+    /// delivery
+    pub fn with_remembered_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    /// Queue at most `max_pending` results waiting for their delivery, applying
+    /// `overflow` to the results pushed beyond
+    pub fn with_max_pending(mut self, max_pending: usize, overflow: OverflowPolicy) -> Self {
+        self.max_pending = max_pending.max(1);
+        self.overflow = overflow;
+        self
+    }
+
+    /// Commit the delivery of the results acknowledged so far, called with the last
+    /// of their sequence numbers after each flush delivering results
+    pub fn on_commit<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(u64) -> Result<(), String> + Send + 'static,
+    {
+        self.commit_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Number the results after `sequence`, the last one committed before a restart
+    pub fn resume_after(mut self, sequence: u64) -> Self {
+        self.next_sequence = sequence + 1;
+        self.delivered = sequence;
+        self
+    }
+
+    /// Get the last sequence number delivered
+    pub fn delivered(&self) -> u64 {
+        self.delivered
+    }
+
+    /// Get the number of results waiting for their delivery
+    pub fn pending_results(&self) -> usize {
+        self.pending.len() + self.uncommitted.len()
+    }
+
+    /// Queue a result, delivering a batch once it is full
+    /// Exactly-once sinks skip results already delivered or queued with the same key
+    pub fn push(&mut self, result: BindingWithTimestamp) -> Result<(), String> {
+        let key = (self.dedup_key)(&result);
+        let exactly_once = self.guarantee == DeliveryGuarantee::ExactlyOnce;
+        if exactly_once && self.is_known(&key) {
+            return Ok(());
+        }
+        if self.pending_results() >= self.max_pending {
+            // Results acknowledged and waiting for their commit are never dropped
+            let oldest = match self.overflow {
+                OverflowPolicy::DropOldest => self.pending.pop_front(),
+                OverflowPolicy::Reject => None,
+            };
+            let Some(oldest) = oldest else {
+                return Err(format!(
+                    "Delivery queue full with {} results, result {} rejected",
+                    self.pending_results(),
+                    key
+                ));
+            };
+            warn!(
+                sequence = oldest.sequence,
+                "Delivery queue full, dropped the oldest result"
+            );
+            self.queued_keys.remove(&oldest.key);
+        }
+        if exactly_once {
+            self.queued_keys.insert(key.clone());
+        }
+        self.pending.push_back(SequencedResult {
+            sequence: self.next_sequence,
+            key,
+            result,
+        });
+        self.next_sequence += 1;
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Deliver the queued results and commit them
+    /// Results not acknowledged or committed stay queued for the next flush
+    pub fn flush(&mut self) -> Result<(), String> {
+        while !self.pending.is_empty() {
+            let size = self.batch_size.min(self.pending.len());
+            let batch: Vec<SequencedResult> = self.pending.iter().take(size).cloned().collect();
+            self.deliver_with_retries(&batch)?;
+            self.pending.drain(..size);
+            self.uncommitted.extend(batch);
+        }
+        self.commit()
+    }
+
+    /// Send every result received on the channel from a background thread
+    /// Results are flushed whenever the channel is drained
+    pub fn spawn(mut self, results: mpsc::Receiver<BindingWithTimestamp>) -> thread::JoinHandle<()>
+    where
+        S: 'static,
+    {
+        spawn_named(THREAD_NAME_PREFIX, "delivery", move || {
+            while let Ok(first) = results.recv() {
+                for result in std::iter::once(first).chain(results.try_iter()) {
+                    if let Err(e) = self.push(result) {
+                        error!("{}", e);
+                    }
+                }
+                if let Err(e) = self.flush() {
+                    error!("{}", e);
+                }
+            }
+        })
+    }
+
+    fn deliver_with_retries(&mut self, batch: &[SequencedResult]) -> Result<(), String> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let error = match self.target.deliver(batch) {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            if attempt >= self.max_retries {
+                return Err(format!(
+                    "Delivery of results {} to {} failed after {} attempts: {}",
+                    batch[0].sequence,
+                    batch[batch.len() - 1].sequence,
+                    attempt + 1,
+                    error
+                ));
+            }
+            attempt += 1;
+            thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+
+    /// Commit the acknowledged results, queueing them again if the hook fails
+    fn commit(&mut self) -> Result<(), String> {
+        let Some(last) = self.uncommitted.last().map(|result| result.sequence) else {
+            return Ok(());
+        };
+        if let Some(hook) = &mut self.commit_hook {
+            if let Err(e) = hook(last) {
+                // The results are delivered again, ahead of those queued since
+                for result in self.uncommitted.drain(..).rev() {
+                    self.pending.push_front(result);
+                }
+                return Err(format!("Commit of results up to {} failed: {}", last, e));
+            }
+        }
+        self.delivered = last;
+        for result in std::mem::take(&mut self.uncommitted) {
+            self.queued_keys.remove(&result.key);
+            self.remember(result.key);
+        }
+        Ok(())
+    }
+
+    fn is_known(&self, key: &str) -> bool {
+        self.delivered_keys.contains(key) || self.queued_keys.contains(key)
+    }
+
+    fn remember(&mut self, key: String) {
+        if self.guarantee != DeliveryGuarantee::ExactlyOnce || self.max_keys == 0 {
+            return;
+        }
+        if self.delivered_keys.insert(key.clone()) {
+            self.key_order.push_back(key);
+        }
+        while self.key_order.len() > self.max_keys {
+            if let Some(oldest) = self.key_order.pop_front() {
+                self.delivered_keys.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Key a result by its window, its row in the window report and a hash of its
+/// solution, so identical rows of a report stay distinct and the keys of a replay
+/// after a restart match those delivered before
+fn default_dedup_key(result: &BindingWithTimestamp) -> String {
+    format!(
+        "{}@{}-{}#{}-{:016x}",
+        result.window.as_deref().unwrap_or_default(),
+        result.timestamp_from,
+        result.timestamp_to,
+        result.row,
+        stable_hash(&result.solution)
+    )
+}
+
+/// FNV-1a hash of the names and values of a solution, unlike `DefaultHasher` the
+/// same in every process and release
+fn stable_hash(solution: &Binding) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (name, term) in &solution.values {
+        let value = term.to_string();
+        for byte in name.bytes().chain([0]).chain(value.bytes()).chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Binding;
+    use oxigraph::model::Literal;
+    use std::sync::Mutex;
+
+    fn result(value: i64, timestamp_to: i64) -> BindingWithTimestamp {
+        BindingWithTimestamp {
+            bindings: String::new(),
+            solution: Binding {
+                values: vec![("avg".to_string(), Literal::from(value).into())],
+            },
+            timestamp_from: timestamp_to - 1000,
+            timestamp_to,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        }
+    }
+
+    /// Target failing the given number of times before acknowledging, recording
+    /// the sequence numbers of each batch it acknowledged
+    fn target(failures: usize) -> (impl AckSink, Arc<Mutex<Vec<Vec<u64>>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let acknowledged = received.clone();
+        let mut failures = failures;
+        let target = move |batch: &[SequencedResult]| {
+            if failures > 0 {
+                failures -= 1;
+                return Err("unavailable".to_string());
+            }
+            let sequences = batch.iter().map(|result| result.sequence).collect();
+            acknowledged.lock().unwrap().push(sequences);
+            Ok(())
+        };
+        (target, received)
+    }
+
+    #[test]
+    fn test_failed_batches_are_retried() {
+        let (target, received) = target(2);
+        let mut sink = ReliableSink::new(target, DeliveryGuarantee::AtLeastOnce)
+            .with_batch_size(2)
+            .with_retries(0, Duration::from_millis(1));
+        sink.push(result(20, 1000)).unwrap();
+        assert!(sink.push(result(21, 1000)).is_err());
+        assert_eq!(sink.pending_results(), 2);
+        assert!(sink.flush().is_err());
+
+        // Failed results keep their place ahead of newer ones
+        sink.push(result(22, 2000)).unwrap();
+        assert_eq!(sink.pending_results(), 0);
+        assert_eq!(sink.delivered(), 3);
+        assert_eq!(*received.lock().unwrap(), vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn test_exactly_once_skips_delivered_keys() {
+        let (target, received) = target(0);
+        let mut sink = ReliableSink::new(target, DeliveryGuarantee::ExactlyOnce).resume_after(10);
+        for value in [20, 20, 21] {
+            sink.push(result(value, 1000)).unwrap();
+        }
+        sink.flush().unwrap();
+        sink.push(result(21, 1000)).unwrap();
+        sink.push(result(21, 2000)).unwrap();
+        // Identical solutions in different rows of a report are different results
+        let mut second_row = result(21, 2000);
+        second_row.row = 1;
+        sink.push(second_row).unwrap();
+        sink.flush().unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![vec![11, 12], vec![13, 14]]);
+
+        let (target, received) = target(0);
+        let mut sink = ReliableSink::new(target, DeliveryGuarantee::AtLeastOnce);
+        for value in [20, 20] {
+            sink.push(result(value, 1000)).unwrap();
+        }
+        sink.flush().unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_failed_commits_deliver_again() {
+        let (target, received) = target(0);
+        let commits = Arc::new(Mutex::new(Vec::new()));
+        let committed = commits.clone();
+        let mut fail = true;
+        let mut sink =
+            ReliableSink::new(target, DeliveryGuarantee::ExactlyOnce).on_commit(move |sequence| {
+                committed.lock().unwrap().push(sequence);
+                if std::mem::take(&mut fail) {
+                    return Err("checkpoint failed".to_string());
+                }
+                Ok(())
+            });
+        sink.push(result(20, 1000)).unwrap();
+        assert!(sink.flush().is_err());
+        assert_eq!(sink.delivered(), 0);
+        // Queued again, so the same result isn't queued twice
+        sink.push(result(20, 1000)).unwrap();
+        sink.push(result(21, 1000)).unwrap();
+        sink.flush().unwrap();
+        assert_eq!(sink.delivered(), 2);
+        assert_eq!(*commits.lock().unwrap(), vec![1, 2]);
+        assert_eq!(*received.lock().unwrap(), vec![vec![1], vec![1, 2]]);
+    }
+
+    #[test]
+    fn test_full_queue_applies_overflow_policy() {
+        for (overflow, expected) in [
+            (OverflowPolicy::Reject, vec![1, 2]),
+            (OverflowPolicy::DropOldest, vec![2, 3]),
+        ] {
+            let (target, received) = target(0);
+            let mut sink = ReliableSink::new(target, DeliveryGuarantee::ExactlyOnce)
+                .with_batch_size(10)
+                .with_max_pending(2, overflow);
+            sink.push(result(20, 1000)).unwrap();
+            sink.push(result(21, 1000)).unwrap();
+            let pushed = sink.push(result(22, 1000));
+            assert_eq!(pushed.is_err(), overflow == OverflowPolicy::Reject);
+            assert_eq!(sink.pending_results(), 2);
+            sink.flush().unwrap();
+            assert_eq!(*received.lock().unwrap(), vec![expected]);
+
+            // Keys of dropped results are forgotten, those delivered remembered
+            sink.push(result(20, 1000)).unwrap();
+            sink.push(result(21, 1000)).unwrap();
+            let redelivered = overflow == OverflowPolicy::DropOldest;
+            assert_eq!(sink.pending_results(), usize::from(redelivered));
+        }
+    }
+
+    #[test]
+    fn test_default_dedup_key() {
+        let mut result = result(20, 1000);
+        assert_eq!(default_dedup_key(&result), "@0-1000#0-009658c616941bcc");
+        result.window = Some("https://rsp.rs/w1".to_string());
+        result.row = 2;
+        assert_eq!(
+            default_dedup_key(&result),
+            "https://rsp.rs/w1@0-1000#2-009658c616941bcc"
+        );
+    }
+
+    #[test]
+    fn test_http_body() {
+        let batch = [SequencedResult {
+            sequence: 7,
+            key: "w\"1\"@0-1000".to_string(),
+            result: result(20, 1000),
+        }];
+        assert_eq!(
+            HttpAckSink::body(&batch),
+            "{\"sequence\":7,\"key\":\"w\\\"1\\\"@0-1000\",\"result\":\
+             {\"avg\":\"20\",\"window_start\":0,\"window_end\":1000}}\n"
+        );
+    }
+}
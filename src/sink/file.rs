@@ -0,0 +1,395 @@
+use crate::BindingWithTimestamp;
+use crate::events::EventPublisher;
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use oxigraph::model::Term;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Row format of a `FileSink`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// Comma-separated values, with a header row per file
+    Csv,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Sink archiving window results to a CSV or JSON-lines file
+///
+/// Every result becomes one row, with a column per variable followed by
+/// `window_start` and `window_end`. IRIs are written without brackets and literals
+/// as their lexical value. CSV columns are those given to `with_columns`, or else the
+/// variables of the first result written to the file.
+///
+/// Once the file reaches its size or age limit, it is renamed with the next rotation
+/// number before its extension (`results.csv` to `results.1.csv`) and a new file is
+/// started at the same path.
+///
+/// ```rust,no_run
+/// use rsp_rs::{FileFormat, FileSink, RSPEngine};
+/// use std::time::Duration;
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let sink = FileSink::new("results.csv", FileFormat::Csv)
+///     .with_max_bytes(64 * 1024 * 1024)
+///     .with_max_age(Duration::from_secs(3600));
+/// sink.spawn(engine.start_processing().into_receiver());
+/// ```
+pub struct FileSink {
+    path: PathBuf,
+    format: FileFormat,
+    columns: Option<Vec<String>>,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    writer: Option<BufWriter<File>>,
+    /// Bytes in the current file
+    written: u64,
+    opened: Instant,
+    /// Files rotated by this sink, oldest first
+    rotated: Vec<PathBuf>,
+    next_rotation: usize,
+    events: EventPublisher,
+}
+
+impl FileSink {
+    /// Create a sink writing to `path`, appending if the file already exists
+    pub fn new(path: impl Into<PathBuf>, format: FileFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            columns: None,
+            max_bytes: None,
+            max_age: None,
+            writer: None,
+            written: 0,
+            opened: Instant::now(),
+            rotated: Vec::new(),
+            next_rotation: 1,
+            events: EventPublisher::default(),
+        }
+    }
+
+    /// Set the variables written as CSV columns, in order
+    pub fn with_columns(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Rotate the file once it holds `max_bytes` bytes
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Rotate the file once it has been written to for `max_age`
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Publish the failures of the sink as engine events, see
+    /// `RSPEngine::event_publisher`
+    pub fn with_events(mut self, events: EventPublisher) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Get the path of the file being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the paths of the files rotated by this sink, oldest first
+    pub fn rotated_files(&self) -> &[PathBuf] {
+        &self.rotated
+    }
+
+    /// Write one result as a row, rotating the file first if it is full or too old
+    pub fn write(&mut self, result: &BindingWithTimestamp) -> Result<(), String> {
+        let full = self.max_bytes.is_some_and(|max| self.written >= max);
+        let old = self.max_age.is_some_and(|max| self.opened.elapsed() >= max);
+        if self.writer.is_some() && self.written > 0 && (full || old) {
+            self.rotate()?;
+        }
+        if self.writer.is_none() {
+            self.open(result)?;
+        }
+
+        let row = self.row(result);
+        let writer = self.writer.as_mut().expect("file opened above");
+        writer
+            .write_all(row.as_bytes())
+            .map_err(|e| format!("Failed to write to {}: {}", self.path.display(), e))?;
+        self.written += row.len() as u64;
+        Ok(())
+    }
+
+    /// Flush the rows buffered in memory to the file
+    pub fn flush(&mut self) -> Result<(), String> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to flush {}: {}", self.path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    /// Write every result received on the channel from a background thread
+    /// Rows are flushed whenever the channel is drained
+    pub fn spawn(
+        mut self,
+        results: mpsc::Receiver<BindingWithTimestamp>,
+    ) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "file-sink", move || {
+            let sink = self.path.display().to_string();
+            while let Ok(first) = results.recv() {
+                for result in std::iter::once(first).chain(results.try_iter()) {
+                    if let Err(e) = self.write(&result) {
+                        self.events.sink_failed(&sink, &e);
+                    }
+                }
+                if let Err(e) = self.flush() {
+                    self.events.sink_failed(&sink, &e);
+                }
+            }
+        })
+    }
+
+    /// Open the file for appending, writing the CSV header if it is new
+    fn open(&mut self, first: &BindingWithTimestamp) -> Result<(), String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        self.written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.opened = Instant::now();
+        self.writer = Some(BufWriter::new(file));
+
+        if self.columns.is_none() {
+            let variables = first.solution.values.iter().map(|(name, _)| name.clone());
+            self.columns = Some(variables.collect());
+        }
+        if self.format == FileFormat::Csv && self.written == 0 {
+            let mut header: Vec<String> = self
+                .columns
+                .iter()
+                .flatten()
+                .map(|c| csv_field(c))
+                .collect();
+            header.extend(["window_start".to_string(), "window_end".to_string()]);
+            let header = format!("{}\n", header.join(","));
+            let writer = self.writer.as_mut().expect("file opened above");
+            writer
+                .write_all(header.as_bytes())
+                .map_err(|e| format!("Failed to write to {}: {}", self.path.display(), e))?;
+            self.written += header.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Close the current file and move it aside under the next free rotation number
+    fn rotate(&mut self) -> Result<(), String> {
+        self.flush()?;
+        self.writer = None;
+        // Files rotated by a previous run are kept
+        let mut rotated = self.rotated_path(self.next_rotation);
+        while rotated.exists() {
+            self.next_rotation += 1;
+            rotated = self.rotated_path(self.next_rotation);
+        }
+        fs::rename(&self.path, &rotated).map_err(|e| {
+            format!(
+                "Failed to rotate {} to {}: {}",
+                self.path.display(),
+                rotated.display(),
+                e
+            )
+        })?;
+        self.next_rotation += 1;
+        self.rotated.push(rotated);
+        self.written = 0;
+        Ok(())
+    }
+
+    fn rotated_path(&self, rotation: usize) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, rotation, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, rotation),
+        };
+        self.path.with_file_name(name)
+    }
+
+    /// Format a result as a line of the file
+    fn row(&self, result: &BindingWithTimestamp) -> String {
+        match self.format {
+            FileFormat::Csv => {
+                let mut fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .flatten()
+                    .map(|column| {
+                        result
+                            .solution
+                            .get(column)
+                            .map(term_text)
+                            .unwrap_or_default()
+                    })
+                    .map(|value| csv_field(&value))
+                    .collect();
+                fields.push(result.timestamp_from.to_string());
+                fields.push(result.timestamp_to.to_string());
+                format!("{}\n", fields.join(","))
+            }
+            FileFormat::JsonLines => format!("{}\n", json_object(result)),
+        }
+    }
+}
+
+/// Format a result as a JSON object, with a field per variable followed by
+/// `window_start` and `window_end`
+pub(crate) fn json_object(result: &BindingWithTimestamp) -> String {
+    let mut fields: Vec<String> = result
+        .solution
+        .values
+        .iter()
+        .map(|(name, term)| format!("{}:{}", json_string(name), json_string(&term_text(term))))
+        .collect();
+    fields.push(format!("\"window_start\":{}", result.timestamp_from));
+    fields.push(format!("\"window_end\":{}", result.timestamp_to));
+    format!("{{{}}}", fields.join(","))
+}
+
+/// Get the plain text of a term: the IRI, the blank node label or the lexical value
+fn term_text(term: &Term) -> String {
+    match term {
+        Term::NamedNode(node) => node.as_str().to_string(),
+        Term::BlankNode(node) => format!("_:{}", node.as_str()),
+        Term::Literal(literal) => literal.value().to_string(),
+        #[allow(unreachable_patterns)]
+        other => other.to_string(),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Binding;
+    use oxigraph::model::{Literal, NamedNode};
+
+    fn result(sensor: &str, value: &str, timestamp: i64) -> BindingWithTimestamp {
+        let solution = Binding {
+            values: vec![
+                (
+                    "sensor".to_string(),
+                    NamedNode::new(format!("http://example.org/{}", sensor))
+                        .unwrap()
+                        .into(),
+                ),
+                (
+                    "value".to_string(),
+                    Literal::new_simple_literal(value).into(),
+                ),
+            ],
+        };
+        BindingWithTimestamp {
+            bindings: format!("{:?}", solution),
+            solution,
+            timestamp_from: timestamp,
+            timestamp_to: timestamp + 10,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsp-rs-file-sink-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let path = temp_path("rows.csv");
+        let mut sink = FileSink::new(&path, FileFormat::Csv);
+        sink.write(&result("s1", "20", 0)).unwrap();
+        sink.write(&result("s2", "a \"quoted\", value", 10))
+            .unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "sensor,value,window_start,window_end\n\
+             http://example.org/s1,20,0,10\n\
+             http://example.org/s2,\"a \"\"quoted\"\", value\",10,20\n"
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_json_lines_rotate_by_size() {
+        let path = temp_path("rows.jsonl");
+        let mut sink = FileSink::new(&path, FileFormat::JsonLines).with_max_bytes(1);
+        for timestamp in [0, 10, 20] {
+            sink.write(&result("s1", "20", timestamp)).unwrap();
+        }
+        sink.flush().unwrap();
+
+        let rotated = sink.rotated_files();
+        assert_eq!(rotated.len(), 2);
+        assert!(rotated[0].ends_with("rows.1.jsonl"));
+        assert_eq!(
+            fs::read_to_string(&rotated[0]).unwrap(),
+            "{\"sensor\":\"http://example.org/s1\",\"value\":\"20\",\"window_start\":0,\"window_end\":10}\n"
+        );
+        assert!(
+            fs::read_to_string(&path)
+                .unwrap()
+                .contains("\"window_start\":20")
+        );
+        for file in rotated.iter().chain([&path]) {
+            fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use crate::BindingWithTimestamp;
+use crate::events::EventPublisher;
+use crate::sink::template::RdfTemplate;
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use crate::timestamp::date_time_literal;
+use oxigraph::model::{NamedNode, Term, Triple};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+const LDES: &str = "https://w3id.org/ldes#";
+const TREE: &str = "https://w3id.org/tree#";
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// Variable of a template bound to the IRI of the member being published
+pub const MEMBER_VARIABLE: &str = "member";
+
+/// Sink publishing window results as a Linked Data Event Stream, e.g. into a
+/// Solid pod or any HTTP server accepting `PUT`
+///
+/// Each window report becomes one member of the event stream: the triples of its
+/// results rendered with an RDF template, where `?member` is bound to the IRI of
+/// the member, and the close time of the window on the `ldes:timestampPath`.
+/// Members are written into pages of at most `page_size` members under the
+/// container; a full page gets a `tree:GreaterThanOrEqualToRelation` to the next
+/// one, so consumers can follow the stream in time order from `{container}stream`.
+///
+/// ```rust,no_run
+/// use rsp_rs::{LdesPublisher, RSPEngine, RdfTemplate};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let template = RdfTemplate::new(
+///     "PREFIX ex: <http://example.org/>
+///      CONSTRUCT { ?member ex:sensor ?sensor ; ex:average ?avg }",
+/// )
+/// .unwrap();
+/// let publisher = LdesPublisher::new("http://localhost:3000/pod/averages/", template)
+///     .with_page_size(50);
+/// publisher.spawn(engine.start_processing().into_receiver());
+/// ```
+pub struct LdesPublisher {
+    container: String,
+    template: RdfTemplate,
+    timestamp_path: NamedNode,
+    page_size: usize,
+    agent: ureq::Agent,
+    /// Number of the page members are currently added to, from 1
+    page: usize,
+    /// Serialized members of the current page
+    page_members: Vec<(NamedNode, String)>,
+    members: usize,
+    events: EventPublisher,
+}
+
+impl LdesPublisher {
+    /// Create a publisher writing the event stream under `container`, rendering
+    /// the results of each window report with `template`
+    pub fn new(container: &str, template: RdfTemplate) -> Self {
+        let mut container = container.to_string();
+        if !container.ends_with('/') {
+            container.push('/');
+        }
+        Self {
+            container,
+            template,
+            timestamp_path: NamedNode::new_unchecked("http://www.w3.org/ns/prov#generatedAtTime"),
+            page_size: 100,
+            agent: ureq::Agent::new(),
+            page: 1,
+            page_members: Vec::new(),
+            members: 0,
+            events: EventPublisher::default(),
+        }
+    }
+
+    /// Set the predicate linking members to the close time of their window,
+    /// `prov:generatedAtTime` by default
+    pub fn with_timestamp_path(mut self, path: NamedNode) -> Self {
+        self.timestamp_path = path;
+        self
+    }
+
+    /// Set the maximum number of members of a page
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Set the timeout of each request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    /// Publish the failures of the sink as engine events, see
+    /// `RSPEngine::event_publisher`
+    pub fn with_events(mut self, events: EventPublisher) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Get the IRI of the event stream
+    pub fn stream_iri(&self) -> String {
+        format!("{}stream#EventStream", self.container)
+    }
+
+    /// Get the number of members published so far
+    pub fn published_members(&self) -> usize {
+        self.members
+    }
+
+    /// Publish the results of the report of `window` as a new member
+    pub fn publish(
+        &mut self,
+        results: &[BindingWithTimestamp],
+        window: (i64, i64),
+    ) -> Result<(), String> {
+        if self.members == 0 {
+            self.put(
+                &format!("{}stream", self.container),
+                &self.stream_document(),
+            )?;
+        }
+        let page_url = self.page_url(self.page);
+        let member = NamedNode::new(format!("{}#member-{}", page_url, self.members + 1))
+            .map_err(|e| format!("Invalid LDES member IRI: {}", e))?;
+        let mut triples = vec![Triple::new(
+            member.clone(),
+            self.timestamp_path.clone(),
+            date_time_literal(window.1),
+        )];
+        for result in results {
+            let mut binding = result.solution.clone();
+            if binding.get(MEMBER_VARIABLE).is_none() {
+                binding
+                    .values
+                    .push((MEMBER_VARIABLE.to_string(), member.clone().into()));
+            }
+            triples.extend(self.template.render(&binding, window));
+        }
+        let mut document = String::new();
+        for triple in triples {
+            document.push_str(&format!("{} .\n", triple));
+        }
+        self.page_members.push((member, document));
+
+        // A full page links to the next one, members of which close later
+        let relation = (self.page_members.len() >= self.page_size).then_some(window.1);
+        self.put(&page_url, &self.page_document(relation))?;
+        self.members += 1;
+        if relation.is_some() {
+            self.page += 1;
+            self.page_members.clear();
+        }
+        Ok(())
+    }
+
+    /// Publish the results received on the channel from a background thread, one
+    /// member per window report
+    /// A report is complete once results of another window arrive, or after a
+    /// short pause in the results
+    pub fn spawn(
+        mut self,
+        results: mpsc::Receiver<BindingWithTimestamp>,
+    ) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "ldes", move || {
+            let mut report: Vec<BindingWithTimestamp> = Vec::new();
+            loop {
+                let received = if report.is_empty() {
+                    results.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                } else {
+                    results.recv_timeout(Duration::from_millis(100))
+                };
+                match received {
+                    Ok(result)
+                        if report.first().is_none_or(|first| {
+                            (first.timestamp_from, first.timestamp_to)
+                                == (result.timestamp_from, result.timestamp_to)
+                        }) =>
+                    {
+                        report.push(result)
+                    }
+                    Ok(result) => {
+                        self.publish_report(&report);
+                        report = vec![result];
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.publish_report(&report);
+                        report.clear();
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        if !report.is_empty() {
+                            self.publish_report(&report);
+                        }
+                        return;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Publish the results of one window report, logging failures
+    fn publish_report(&mut self, report: &[BindingWithTimestamp]) {
+        let window = (report[0].timestamp_from, report[0].timestamp_to);
+        if let Err(e) = self.publish(report, window) {
+            self.events.sink_failed(&self.container, &e);
+        }
+    }
+
+    fn page_url(&self, page: usize) -> String {
+        format!("{}page-{}", self.container, page)
+    }
+
+    /// Describe the event stream, its timestamp path and its first page
+    fn stream_document(&self) -> String {
+        let stream = self.stream_iri();
+        format!(
+            "<{stream}> <{RDF_TYPE}> <{LDES}EventStream> .\n\
+             <{stream}> <{LDES}timestampPath> {} .\n\
+             <{stream}> <{TREE}view> <{}> .\n",
+            self.timestamp_path,
+            self.page_url(1),
+        )
+    }
+
+    /// Serialize the current page, with a relation to the next page for members
+    /// closing at or after `next_from`
+    fn page_document(&self, next_from: Option<i64>) -> String {
+        let page = self.page_url(self.page);
+        let stream = self.stream_iri();
+        let mut document = format!("<{page}> <{RDF_TYPE}> <{TREE}Node> .\n");
+        for (member, _) in &self.page_members {
+            document.push_str(&format!("<{stream}> <{TREE}member> {member} .\n"));
+        }
+        if let Some(next_from) = next_from {
+            let relation = format!("<{}#next>", page);
+            let value = Term::from(date_time_literal(next_from));
+            document.push_str(&format!(
+                "<{page}> <{TREE}relation> {relation} .\n\
+                 {relation} <{RDF_TYPE}> <{TREE}GreaterThanOrEqualToRelation> .\n\
+                 {relation} <{TREE}path> {} .\n\
+                 {relation} <{TREE}value> {value} .\n\
+                 {relation} <{TREE}node> <{}> .\n",
+                self.timestamp_path,
+                self.page_url(self.page + 1),
+            ));
+        }
+        for (_, triples) in &self.page_members {
+            document.push_str(triples);
+        }
+        document
+    }
+
+    fn put(&self, url: &str, document: &str) -> Result<(), String> {
+        self.agent
+            .put(url)
+            .set("Content-Type", "text/turtle")
+            .send_string(document)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to publish LDES document {}: {}", url, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Binding;
+    use crate::http_test_server::{self, Request, Response};
+    use oxigraph::model::Literal;
+
+    /// Accept `requests` requests, returning the URL of the container
+    fn serve(requests: usize) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let responses = vec![Response::status(201); requests];
+        http_test_server::serve("/pod/averages/", responses)
+    }
+
+    fn result(avg: i64, window: (i64, i64)) -> BindingWithTimestamp {
+        BindingWithTimestamp {
+            bindings: String::new(),
+            solution: Binding {
+                values: vec![("avg".to_string(), Literal::from(avg).into())],
+            },
+            timestamp_from: window.0,
+            timestamp_to: window.1,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        }
+    }
+
+    fn publisher(container: &str) -> LdesPublisher {
+        let template = RdfTemplate::new("CONSTRUCT { ?member <http://example.org/avg> ?avg }");
+        LdesPublisher::new(container, template.unwrap()).with_page_size(2)
+    }
+
+    #[test]
+    fn test_members_are_paginated() {
+        let (container, server) = serve(4);
+        let mut publisher = publisher(&container);
+        for (avg, window) in [(20, (0, 1000)), (21, (1000, 2000)), (22, (2000, 3000))] {
+            publisher.publish(&[result(avg, window)], window).unwrap();
+        }
+        assert_eq!(publisher.published_members(), 3);
+
+        let requests = server.join().unwrap();
+        let paths: Vec<&str> = requests
+            .iter()
+            .map(|request| request.path.trim_start_matches("/pod/averages/"))
+            .collect();
+        assert_eq!(paths, vec!["stream", "page-1", "page-1", "page-2"]);
+        assert!(requests.iter().all(|request| request.method == "PUT"));
+        assert!(
+            requests[0]
+                .body
+                .contains("<https://w3id.org/ldes#timestampPath>")
+        );
+        assert!(requests[0].body.contains(&format!("<{}page-1>", container)));
+
+        // The full page links to the next one from its last close time
+        let full_page = &requests[2].body;
+        assert_eq!(
+            full_page.matches("<https://w3id.org/tree#member>").count(),
+            2
+        );
+        let next_page = format!("<https://w3id.org/tree#node> <{}page-2>", container);
+        assert!(full_page.contains(&next_page));
+        assert!(full_page.contains("\"1970-01-01T00:00:02.000Z\"^^"));
+        assert!(!requests[1].body.contains("tree#relation"));
+
+        let last_page = &requests[3].body;
+        let member = format!("<{}page-2#member-3>", container);
+        assert!(last_page.contains(&format!("{} <http://example.org/avg> \"22\"", member)));
+        assert!(last_page.contains(&format!(
+            "{} <http://www.w3.org/ns/prov#generatedAtTime> \"1970-01-01T00:00:03.000Z\"",
+            member
+        )));
+    }
+
+    #[test]
+    fn test_spawn_groups_results_by_window() {
+        let (container, server) = serve(3);
+        let (tx, rx) = mpsc::channel();
+        let handle = publisher(&container).spawn(rx);
+        tx.send(result(20, (0, 1000))).unwrap();
+        tx.send(result(30, (0, 1000))).unwrap();
+        tx.send(result(21, (1000, 2000))).unwrap();
+        drop(tx);
+        handle.join().unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[1].body.matches("<http://example.org/avg>").count(),
+            2
+        );
+        assert_eq!(
+            requests[2]
+                .body
+                .matches("<https://w3id.org/tree#member>")
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub mod delivery;
+pub mod file;
+pub mod ldes;
+pub mod sparql_update;
+pub mod template;
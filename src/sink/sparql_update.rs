@@ -0,0 +1,321 @@
+use crate::delta::{Binding, ResultDelta};
+use crate::events::EventPublisher;
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use regex::{Captures, Regex};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Sink applying window results to a remote SPARQL endpoint with SPARQL UPDATE
+///
+/// Every binding instantiates a triple template such as
+/// `?sensor <http://example.org/average> ?avg .`, whose variables are replaced by
+/// the bound terms. Added bindings are inserted with `INSERT DATA` and removed ones
+/// deleted with `DELETE DATA`, so the endpoint keeps the latest results of the query.
+/// Operations are sent in batches, and failed requests are retried with
+/// exponential backoff.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngine, SparqlUpdateSink};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let sink = SparqlUpdateSink::new(
+///     "http://localhost:3030/ds/update",
+///     "?sensor <http://example.org/average> ?avg .",
+/// )
+/// .with_graph("http://example.org/latest");
+/// sink.spawn(engine.start_processing_deltas());
+/// ```
+pub struct SparqlUpdateSink {
+    endpoint: String,
+    template: String,
+    graph: Option<String>,
+    prefixes: Vec<(String, String)>,
+    batch_size: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    agent: ureq::Agent,
+    /// Update operations waiting to be sent
+    pending: Vec<String>,
+    /// Matches template variables, skipping IRIs and literals that may contain `?`
+    variable_re: Regex,
+    events: EventPublisher,
+}
+
+impl SparqlUpdateSink {
+    /// Create a sink sending updates to `endpoint`, built from a triple template
+    pub fn new(endpoint: &str, template: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            template: template.to_string(),
+            graph: None,
+            prefixes: Vec::new(),
+            batch_size: 64,
+            max_retries: 3,
+            retry_delay: Duration::from_millis(100),
+            agent: ureq::Agent::new(),
+            pending: Vec::new(),
+            variable_re: Regex::new(r#"<[^>]*>|"(?:[^"\\]|\\.)*"|\?([A-Za-z_][A-Za-z0-9_]*)"#)
+                .unwrap(),
+            events: EventPublisher::default(),
+        }
+    }
+
+    /// Write the triples into a named graph instead of the default graph
+    pub fn with_graph(mut self, graph: &str) -> Self {
+        self.graph = Some(graph.to_string());
+        self
+    }
+
+    /// Declare a prefix usable in the template
+    pub fn with_prefix(mut self, prefix: &str, iri: &str) -> Self {
+        self.prefixes.push((prefix.to_string(), iri.to_string()));
+        self
+    }
+
+    /// Set the maximum number of operations sent in one request
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Retry failed requests up to `max_retries` times, waiting `retry_delay`
+    /// before the first retry and twice as long before each next one
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Set the timeout of each request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        self
+    }
+
+    /// Publish the failures of the sink as engine events, see
+    /// `RSPEngine::event_publisher`
+    pub fn with_events(mut self, events: EventPublisher) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Instantiate the template for one binding
+    /// Returns `None` if a variable of the template is unbound
+    pub fn render(&self, binding: &Binding) -> Option<String> {
+        let mut unbound = false;
+        let triples = self
+            .variable_re
+            .replace_all(&self.template, |captures: &Captures| {
+                let Some(variable) = captures.get(1) else {
+                    return captures[0].to_string();
+                };
+                match binding.get(variable.as_str()) {
+                    Some(term) => term.to_string(),
+                    None => {
+                        unbound = true;
+                        String::new()
+                    }
+                }
+            })
+            .into_owned();
+        (!unbound).then_some(triples)
+    }
+
+    /// Queue the changes of one window report, sending a request once the batch is full
+    pub fn apply(&mut self, delta: &ResultDelta) -> Result<(), String> {
+        if let Some(data) = self.data_block(&delta.removed) {
+            self.pending.push(format!("DELETE DATA {{\n{}\n}}", data));
+        }
+        if let Some(data) = self.data_block(&delta.added) {
+            self.pending.push(format!("INSERT DATA {{\n{}\n}}", data));
+        }
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Render the triples of the bindings, in the target graph if any
+    fn data_block(&self, bindings: &[Binding]) -> Option<String> {
+        let triples: Vec<String> = bindings.iter().filter_map(|b| self.render(b)).collect();
+        if triples.is_empty() {
+            return None;
+        }
+        let triples = triples.join("\n");
+        Some(match &self.graph {
+            Some(graph) => format!("GRAPH <{}> {{\n{}\n}}", graph, triples),
+            None => triples,
+        })
+    }
+
+    /// Get the body of the next update request, made of the queued operations
+    pub fn update_request(&self) -> String {
+        let mut request = String::new();
+        for (prefix, iri) in &self.prefixes {
+            request.push_str(&format!("PREFIX {}: <{}>\n", prefix, iri));
+        }
+        request.push_str(&self.pending.join(" ;\n"));
+        request
+    }
+
+    /// Get the number of operations waiting to be sent
+    pub fn pending_operations(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Send the queued operations to the endpoint
+    /// The batch is dropped if it still fails after the retries, so one bad
+    /// request doesn't block the following ones
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let request = self.update_request();
+        self.pending.clear();
+
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .agent
+                .post(&self.endpoint)
+                .set("Content-Type", "application/sparql-update")
+                .send_string(&request);
+            let error = match response {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            // Client errors won't succeed on a retry
+            let retryable = match &error {
+                ureq::Error::Status(status, _) => *status >= 500 || *status == 429,
+                ureq::Error::Transport(_) => true,
+            };
+            if !retryable || attempt >= self.max_retries {
+                return Err(format!(
+                    "SPARQL UPDATE to {} failed after {} attempts: {}",
+                    self.endpoint,
+                    attempt + 1,
+                    error
+                ));
+            }
+            attempt += 1;
+            thread::sleep(delay);
+            delay *= 2;
+        }
+    }
+
+    /// Apply every delta received on the channel from a background thread
+    /// Deltas queued together are sent in one batch
+    pub fn spawn(mut self, deltas: mpsc::Receiver<ResultDelta>) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "sparql-update", move || {
+            while let Ok(first) = deltas.recv() {
+                for delta in std::iter::once(first).chain(deltas.try_iter()) {
+                    if let Err(e) = self.apply(&delta) {
+                        self.events.sink_failed(&self.endpoint, &e);
+                    }
+                }
+                if let Err(e) = self.flush() {
+                    self.events.sink_failed(&self.endpoint, &e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_test_server::{Response, serve};
+    use oxigraph::model::{Literal, NamedNode, Term};
+
+    fn binding(sensor: &str, value: i64) -> Binding {
+        Binding {
+            values: vec![
+                ("avg".to_string(), Term::from(Literal::from(value))),
+                (
+                    "sensor".to_string(),
+                    NamedNode::new(format!("http://example.org/{}", sensor))
+                        .unwrap()
+                        .into(),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let sink = SparqlUpdateSink::new(
+            "http://localhost/update",
+            "?sensor <http://example.org/avg?unit=c> ?avg .",
+        );
+        assert_eq!(
+            sink.render(&binding("s1", 20)).unwrap(),
+            "<http://example.org/s1> <http://example.org/avg?unit=c> \"20\"^^<http://www.w3.org/2001/XMLSchema#integer> ."
+        );
+
+        let sink = SparqlUpdateSink::new("http://localhost/update", "?sensor ex:unit ?unit .");
+        assert_eq!(sink.render(&binding("s1", 20)), None);
+    }
+
+    #[test]
+    fn test_deltas_become_update_operations() {
+        let mut sink = SparqlUpdateSink::new("http://localhost/update", "?sensor ex:avg ?avg .")
+            .with_prefix("ex", "http://example.org/")
+            .with_graph("http://example.org/latest");
+        let delta = ResultDelta {
+            added: vec![binding("s1", 21)],
+            removed: vec![binding("s1", 20)],
+            window: (0, 10),
+        };
+        sink.apply(&delta).unwrap();
+        assert_eq!(sink.pending_operations(), 2);
+
+        let request = sink.update_request();
+        assert!(request.starts_with("PREFIX ex: <http://example.org/>\n"));
+        let delete = request.find("DELETE DATA").unwrap();
+        let insert = request.find("INSERT DATA").unwrap();
+        assert!(delete < insert);
+        assert_eq!(
+            request.matches("GRAPH <http://example.org/latest>").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_flush_retries_server_errors() {
+        let responses = vec![Response::status(503), Response::status(200)];
+        let (endpoint, server) = serve("/update", responses);
+        let mut sink = SparqlUpdateSink::new(&endpoint, "?sensor <http://example.org/avg> ?avg .")
+            .with_retries(2, Duration::from_millis(1));
+        sink.apply(&ResultDelta {
+            added: vec![binding("s1", 20)],
+            removed: Vec::new(),
+            window: (0, 10),
+        })
+        .unwrap();
+        sink.flush().unwrap();
+        assert_eq!(sink.pending_operations(), 0);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body, requests[1].body);
+        assert!(requests[0].body.starts_with("INSERT DATA {"));
+    }
+
+    #[test]
+    fn test_flush_gives_up_on_client_errors() {
+        let (endpoint, server) = serve("/update", vec![Response::status(400)]);
+        let mut sink = SparqlUpdateSink::new(&endpoint, "?sensor <http://example.org/avg> ?avg .")
+            .with_retries(2, Duration::from_millis(1));
+        sink.apply(&ResultDelta {
+            added: vec![binding("s1", 20)],
+            removed: Vec::new(),
+            window: (0, 10),
+        })
+        .unwrap();
+        assert!(sink.flush().is_err());
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
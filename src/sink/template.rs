@@ -0,0 +1,189 @@
+use crate::{Binding, BindingWithTimestamp};
+use oxigraph::model::{BlankNode, Graph, Literal, NamedOrBlankNode, Term, Triple, Variable};
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+use std::collections::HashMap;
+
+/// Variable of a template bound to the open time (in ms) of the window report
+pub const WINDOW_START_VARIABLE: &str = "windowStart";
+/// Variable of a template bound to the close time (in ms) of the window report
+pub const WINDOW_END_VARIABLE: &str = "windowEnd";
+
+/// Template turning the bindings of a SELECT query into RDF triples, for sinks
+/// publishing RDF (LDES, Solid pods, RDF over Kafka...)
+///
+/// The template is a CONSTRUCT clause, instantiated once per binding as a CONSTRUCT
+/// query would: triples with an unbound variable or an invalid term are skipped and
+/// blank nodes are fresh for each binding. Besides the variables of the query,
+/// `?windowStart` and `?windowEnd` are bound to the time range of the report.
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngine, RdfTemplate};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let template = RdfTemplate::new(
+///     "PREFIX ex: <http://example.org/>
+///      CONSTRUCT { [] ex:sensor ?sensor ; ex:average ?avg ; ex:until ?windowEnd }",
+/// )
+/// .unwrap();
+/// for result in engine.start_processing() {
+///     for triple in template.render_result(&result) {
+///         println!("{} .", triple);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RdfTemplate {
+    triples: Vec<TriplePattern>,
+}
+
+impl RdfTemplate {
+    /// Parse a template written as a CONSTRUCT query without its WHERE clause
+    pub fn new(template: &str) -> Result<Self, String> {
+        let query = SparqlParser::new()
+            .parse_query(&format!("{} WHERE {{}}", template))
+            .map_err(|e| format!("Invalid RDF template {}: {}", template, e))?;
+        let Query::Construct {
+            template: triples, ..
+        } = query
+        else {
+            return Err(format!(
+                "RDF template {} is not a CONSTRUCT clause",
+                template
+            ));
+        };
+        Ok(Self { triples })
+    }
+
+    /// Instantiate the template for one binding of the report of `window`
+    pub fn render(&self, binding: &Binding, window: (i64, i64)) -> Vec<Triple> {
+        let mut blank_nodes = HashMap::new();
+        let mut triples = Vec::new();
+        for pattern in &self.triples {
+            let mut term = |pattern: &TermPattern| {
+                if let TermPattern::BlankNode(node) = pattern {
+                    let fresh = blank_nodes
+                        .entry(node.clone())
+                        .or_insert_with(BlankNode::default);
+                    return Some(Term::from(fresh.clone()));
+                }
+                match pattern {
+                    TermPattern::NamedNode(node) => Some(node.clone().into()),
+                    TermPattern::Literal(literal) => Some(literal.clone().into()),
+                    TermPattern::Variable(variable) => Self::value(variable, binding, window),
+                    _ => None,
+                }
+            };
+            let subject = match term(&pattern.subject) {
+                Some(Term::NamedNode(node)) => NamedOrBlankNode::from(node),
+                Some(Term::BlankNode(node)) => NamedOrBlankNode::from(node),
+                _ => continue,
+            };
+            let predicate = match &pattern.predicate {
+                NamedNodePattern::NamedNode(node) => node.clone(),
+                NamedNodePattern::Variable(variable) => {
+                    match Self::value(variable, binding, window) {
+                        Some(Term::NamedNode(node)) => node,
+                        _ => continue,
+                    }
+                }
+            };
+            let Some(object) = term(&pattern.object) else {
+                continue;
+            };
+            triples.push(Triple::new(subject, predicate, object));
+        }
+        triples
+    }
+
+    /// Instantiate the template for one result of the engine
+    pub fn render_result(&self, result: &BindingWithTimestamp) -> Vec<Triple> {
+        self.render(
+            &result.solution,
+            (result.timestamp_from, result.timestamp_to),
+        )
+    }
+
+    /// Instantiate the template for every result, e.g. those of one window report
+    pub fn render_graph(&self, results: &[BindingWithTimestamp]) -> Graph {
+        let mut graph = Graph::new();
+        for result in results {
+            graph.extend(self.render_result(result));
+        }
+        graph
+    }
+
+    /// Get the value of a variable, the bindings of the query taking precedence
+    /// over the window bounds
+    fn value(variable: &Variable, binding: &Binding, window: (i64, i64)) -> Option<Term> {
+        if let Some(term) = binding.get(variable.as_str()) {
+            return Some(term.clone());
+        }
+        match variable.as_str() {
+            WINDOW_START_VARIABLE => Some(Literal::from(window.0).into()),
+            WINDOW_END_VARIABLE => Some(Literal::from(window.1).into()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::NamedNode;
+
+    fn binding(values: &[(&str, Term)]) -> Binding {
+        Binding {
+            values: values
+                .iter()
+                .map(|(name, term)| (name.to_string(), term.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_render_bindings() {
+        let template = RdfTemplate::new(
+            "PREFIX ex: <http://example.org/>
+             CONSTRUCT {
+                 _:report ex:sensor ?sensor ; ex:average ?avg ; ex:from ?windowStart .
+                 ?sensor ex:label ?label .
+             }",
+        )
+        .unwrap();
+        let sensor = NamedNode::new("http://example.org/sensor1").unwrap();
+        let triples = template.render(
+            &binding(&[
+                ("sensor", sensor.clone().into()),
+                ("avg", Literal::from(21.5).into()),
+            ]),
+            (1000, 2000),
+        );
+
+        // The label is unbound, so its triple is skipped
+        assert_eq!(triples.len(), 3);
+        let report = triples[0].subject.clone();
+        assert!(matches!(report, NamedOrBlankNode::BlankNode(_)));
+        assert!(triples.iter().all(|triple| triple.subject == report));
+        assert_eq!(triples[0].object, sensor.into());
+        assert_eq!(triples[2].object, Literal::from(1000).into());
+
+        // Each binding gets its own blank nodes
+        let other = template.render(&binding(&[("avg", Literal::from(3).into())]), (0, 10));
+        assert_eq!(other.len(), 2);
+        assert_ne!(other[0].subject, report);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(RdfTemplate::new("CONSTRUCT { ?s ?p }").is_err());
+        assert!(RdfTemplate::new("SELECT *").is_err());
+        let template = RdfTemplate::new("CONSTRUCT { ?s <http://example.org/p> ?o }").unwrap();
+        let literal_subject = binding(&[
+            ("s", Literal::from(1).into()),
+            ("o", Literal::from(2).into()),
+        ]);
+        assert!(template.render(&literal_subject, (0, 10)).is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! Synthetic streams and assertions for testing queries and benchmarking the engine
+//!
+//! A [`StreamSimulator`] generates sensor readings following a schema: a number of
+//! sensors and properties, an event rate, timestamp jitter and a fraction of events
+//! arriving out of order. Generation is seeded, so a simulator always produces the
+//! same events, and the events it returns can be used to compute the expected
+//! window results.
+//!
+//! ```rust,no_run
+//! use rsp_rs::testing::{StreamSimulator, assert_numbers, window_quads};
+//! use rsp_rs::RSPEngineBuilder;
+//!
+//! let query = r#"
+//!     PREFIX ex: <http://example.org/>
+//!     REGISTER RStream <output> AS
+//!     SELECT (COUNT(?value) AS ?readings)
+//!     FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 1000 STEP 1000 ALIGN 0]
+//!     WHERE { WINDOW ex:w { ?sensor ex:property0 ?value } }
+//! "#;
+//! let mut engine = RSPEngineBuilder::new(query.to_string()).synchronous(true).build();
+//! engine.initialize().unwrap();
+//!
+//! let mut simulator = StreamSimulator::new(4, 1).with_rate(20.0);
+//! let events = simulator.drive(&engine, "http://example.org/stream", 40).unwrap();
+//! engine.close_stream("http://example.org/stream", 10_000).unwrap();
+//!
+//! let expected = [(0, 1000), (1000, 2000)].map(|(open, close)| {
+//!     window_quads(&events, open, close).len() as f64
+//! });
+//! assert_numbers(&engine.poll_results(), "readings", &expected);
+//! ```
+
+use crate::{BindingWithTimestamp, RDFStream, RSPEngine};
+use oxigraph::model::{GraphName, Literal, NamedNode, Quad, Term};
+use std::collections::HashSet;
+
+/// Base IRI of the sensors and properties of generated quads
+const BASE: &str = "http://example.org/";
+
+/// Create the quad `ex:sensor{sensor} ex:property{property} "value_{value}"`,
+/// as used by the benchmarks
+pub fn generate_quad(sensor: usize, property: usize, value: usize) -> Quad {
+    Quad::new(
+        sensor_node(sensor),
+        property_node(property),
+        Literal::new_simple_literal(format!("value_{}", value)),
+        GraphName::DefaultGraph,
+    )
+}
+
+fn sensor_node(sensor: usize) -> NamedNode {
+    NamedNode::new(format!("{}sensor{}", BASE, sensor)).expect("valid IRI")
+}
+
+fn property_node(property: usize) -> NamedNode {
+    NamedNode::new(format!("{}property{}", BASE, property)).expect("valid IRI")
+}
+
+/// One reading generated by a `StreamSimulator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedEvent {
+    pub sensor: usize,
+    pub property: usize,
+    /// Integer value of the reading, between 0 and 99
+    pub value: i64,
+    /// Event time of the reading, in ms
+    pub timestamp: i64,
+}
+
+impl SimulatedEvent {
+    /// Get the reading as `ex:sensor{sensor} ex:property{property} value` with an
+    /// `xsd:integer` value
+    pub fn quad(&self) -> Quad {
+        Quad::new(
+            sensor_node(self.sensor),
+            property_node(self.property),
+            Literal::from(self.value),
+            GraphName::DefaultGraph,
+        )
+    }
+}
+
+/// Generator of synthetic sensor streams
+///
+/// Readings go round the sensors, each reporting all properties in turn, one reading
+/// every `1000 / rate` ms of event time from the start time. Jitter moves each
+/// timestamp by up to that many ms either way (never before the start time). Out of
+/// order events are swapped with the next one, so they arrive one interval late; with
+/// out of order events or jitter above the interval, set a `max_lateness` on the
+/// engine so they aren't dropped.
+#[derive(Debug, Clone)]
+pub struct StreamSimulator {
+    sensors: usize,
+    properties: usize,
+    rate: f64,
+    jitter: i64,
+    out_of_order: f64,
+    start: i64,
+    /// State of the random generator
+    state: u64,
+    /// Readings generated so far
+    generated: u64,
+}
+
+impl StreamSimulator {
+    /// Create a simulator of `sensors` sensors with `properties` properties each,
+    /// at 10 readings per second without jitter or out of order events
+    pub fn new(sensors: usize, properties: usize) -> Self {
+        Self {
+            sensors: sensors.max(1),
+            properties: properties.max(1),
+            rate: 10.0,
+            jitter: 0,
+            out_of_order: 0.0,
+            start: 0,
+            state: 0x5EED,
+            generated: 0,
+        }
+    }
+
+    /// Set the number of readings per second of event time, over all sensors
+    pub fn with_rate(mut self, events_per_second: f64) -> Self {
+        self.rate = events_per_second;
+        self
+    }
+
+    /// Move each timestamp randomly by up to `max_ms` ms either way
+    pub fn with_jitter(mut self, max_ms: i64) -> Self {
+        self.jitter = max_ms.max(0);
+        self
+    }
+
+    /// Deliver this fraction of the readings (between 0 and 1) out of order
+    pub fn with_out_of_order(mut self, fraction: f64) -> Self {
+        self.out_of_order = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the timestamp of the first reading
+    pub fn with_start(mut self, start: i64) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Seed the random generator, so different simulators produce different values
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.state = seed;
+        self
+    }
+
+    /// Generate the next `count` readings, in arrival order
+    /// Readings are only reordered within one call
+    pub fn next_events(&mut self, count: usize) -> Vec<SimulatedEvent> {
+        let interval = 1000.0 / self.rate;
+        let mut events: Vec<SimulatedEvent> = (0..count)
+            .map(|_| {
+                let index = self.generated;
+                self.generated += 1;
+                let slot = index as usize;
+                let mut timestamp = self.start + (index as f64 * interval) as i64;
+                if self.jitter > 0 {
+                    timestamp += self.next_below(2 * self.jitter as u64 + 1) as i64 - self.jitter;
+                }
+                SimulatedEvent {
+                    sensor: slot % self.sensors,
+                    property: (slot / self.sensors) % self.properties,
+                    value: self.next_below(100) as i64,
+                    timestamp: timestamp.max(self.start),
+                }
+            })
+            .collect();
+
+        let mut i = 0;
+        while i + 1 < events.len() {
+            if self.next_fraction() < self.out_of_order {
+                events.swap(i, i + 1);
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        events
+    }
+
+    /// Add the next `count` readings to a stream, one event each, returning them
+    pub fn feed(
+        &mut self,
+        stream: &RDFStream,
+        count: usize,
+    ) -> Result<Vec<SimulatedEvent>, String> {
+        let events = self.next_events(count);
+        for event in &events {
+            stream.add_quads(vec![event.quad()], event.timestamp)?;
+        }
+        Ok(events)
+    }
+
+    /// Add the next `count` readings to the stream `stream_uri` of an engine,
+    /// returning them
+    pub fn drive(
+        &mut self,
+        engine: &RSPEngine,
+        stream_uri: &str,
+        count: usize,
+    ) -> Result<Vec<SimulatedEvent>, String> {
+        let stream = engine
+            .get_stream(stream_uri)
+            .ok_or_else(|| format!("Stream {} not found", stream_uri))?;
+        self.feed(&stream, count)
+    }
+
+    /// SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    fn next_fraction(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Get the distinct quads of the readings in the window `[open, close)`
+/// Windows hold sets of quads, so a sensor reporting the same value twice in a
+/// window counts once
+pub fn window_quads(events: &[SimulatedEvent], open: i64, close: i64) -> Vec<Quad> {
+    let mut seen = HashSet::new();
+    events
+        .iter()
+        .filter(|event| open <= event.timestamp && event.timestamp < close)
+        .map(SimulatedEvent::quad)
+        .filter(|quad| seen.insert(quad.clone()))
+        .collect()
+}
+
+/// Assert that there are exactly `expected` results
+#[track_caller]
+pub fn assert_result_count(results: &[BindingWithTimestamp], expected: usize) {
+    assert_eq!(
+        results.len(),
+        expected,
+        "expected {} results, got {:?}",
+        expected,
+        results.iter().map(|r| &r.bindings).collect::<Vec<_>>()
+    );
+}
+
+/// Assert that the numbers bound to `variable` in the results are `expected`,
+/// in any order
+#[track_caller]
+pub fn assert_numbers(results: &[BindingWithTimestamp], variable: &str, expected: &[f64]) {
+    let mut actual: Vec<f64> = results
+        .iter()
+        .map(|r| {
+            r.solution.number(variable).unwrap_or_else(|| {
+                panic!("?{} is not bound to a number in {}", variable, r.bindings)
+            })
+        })
+        .collect();
+    let mut expected = expected.to_vec();
+    actual.sort_by(f64::total_cmp);
+    expected.sort_by(f64::total_cmp);
+    let matches = actual.len() == expected.len()
+        && actual
+            .iter()
+            .zip(&expected)
+            .all(|(a, e)| (a - e).abs() <= 1e-9 * e.abs().max(1.0));
+    assert!(
+        matches,
+        "values of ?{}: expected {:?}, got {:?}",
+        variable, expected, actual
+    );
+}
+
+/// Assert that the values bound to `variable` in the results are `expected`, in any
+/// order, comparing IRIs and the lexical form of literals
+#[track_caller]
+pub fn assert_values(results: &[BindingWithTimestamp], variable: &str, expected: &[&str]) {
+    let mut actual: Vec<String> = results
+        .iter()
+        .map(|r| match r.solution.get(variable) {
+            Some(Term::NamedNode(node)) => node.as_str().to_string(),
+            Some(Term::Literal(literal)) => literal.value().to_string(),
+            Some(other) => other.to_string(),
+            None => panic!("?{} is unbound in {}", variable, r.bindings),
+        })
+        .collect();
+    let mut expected: Vec<String> = expected.iter().map(|e| e.to_string()).collect();
+    actual.sort();
+    expected.sort();
+    assert_eq!(actual, expected, "values of ?{}", variable);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RSPEngineBuilder;
+
+    #[test]
+    fn test_generation_follows_schema() {
+        let events = StreamSimulator::new(3, 2).with_rate(4.0).next_events(8);
+        let timestamps: Vec<i64> = events.iter().map(|e| e.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 250, 500, 750, 1000, 1250, 1500, 1750]);
+        let slots: Vec<(usize, usize)> = events.iter().map(|e| (e.sensor, e.property)).collect();
+        assert_eq!(slots[..4], [(0, 0), (1, 0), (2, 0), (0, 1)]);
+        assert!(events.iter().all(|e| (0..100).contains(&e.value)));
+
+        // The same seed gives the same readings
+        let again = StreamSimulator::new(3, 2).with_rate(4.0).next_events(8);
+        assert_eq!(events, again);
+
+        let jittered = StreamSimulator::new(3, 2)
+            .with_rate(4.0)
+            .with_jitter(50)
+            .with_start(1000)
+            .next_events(100);
+        for (i, event) in jittered.iter().enumerate() {
+            let nominal = 1000 + 250 * i as i64;
+            assert!((event.timestamp - nominal).abs() <= 50);
+            assert!(event.timestamp >= 1000);
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_fraction() {
+        let events = StreamSimulator::new(1, 1)
+            .with_out_of_order(0.2)
+            .next_events(1000);
+        let late = events
+            .windows(2)
+            .filter(|pair| pair[1].timestamp < pair[0].timestamp)
+            .count();
+        assert!((100..=300).contains(&late), "{} late events", late);
+
+        let in_order = StreamSimulator::new(1, 1).next_events(1000);
+        assert!(
+            in_order
+                .windows(2)
+                .all(|pair| pair[0].timestamp <= pair[1].timestamp)
+        );
+    }
+
+    #[test]
+    fn test_drive_engine_and_assert_windows() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <output> AS
+            SELECT (COUNT(?value) AS ?readings)
+            FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 1000 STEP 1000 ALIGN 0]
+            WHERE { WINDOW ex:w { ?sensor ex:property0 ?value } }
+        "#;
+        let mut engine = RSPEngineBuilder::new(query.to_string())
+            .synchronous(true)
+            .build();
+        engine.initialize().unwrap();
+
+        let mut simulator = StreamSimulator::new(2, 1).with_rate(10.0);
+        let events = simulator
+            .drive(&engine, "http://example.org/stream", 25)
+            .unwrap();
+        engine
+            .close_stream("http://example.org/stream", 10_000)
+            .unwrap();
+
+        let expected = [(0, 1000), (1000, 2000), (2000, 3000)]
+            .map(|(open, close)| window_quads(&events, open, close).len() as f64);
+        let results = engine.poll_results();
+        assert_result_count(&results, 3);
+        assert_numbers(&results, "readings", &expected);
+        assert!(
+            simulator
+                .drive(&engine, "http://example.org/missing", 1)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,432 @@
+use crate::RDFStream;
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use crate::timestamp::{date_time_literal, wall_clock_millis};
+use coap_lite::{CoapRequest, Packet, RequestType, ResponseType};
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, Quad, Term};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use tracing::{error, warn};
+
+const SOSA: &str = "http://www.w3.org/ns/sosa/";
+const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// SenML times below 2^28 s are relative to the time the pack is received
+const SENML_RELATIVE_TIME: f64 = 268_435_456.0;
+
+/// One value reported by a device
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Measurement {
+    pub(crate) name: String,
+    pub(crate) value: Literal,
+    /// Device-provided time in Unix milliseconds
+    pub(crate) time: Option<i64>,
+}
+
+/// How the measurements of devices are described in RDF
+///
+/// Each measurement becomes a `sosa:Observation` made by the device, of the
+/// property named by the measurement, with the value as `sosa:hasSimpleResult` and
+/// the time as `sosa:resultTime`, so `TimestampExtractor::sosa_result_time` reads it
+/// back. Devices and properties named by plain identifiers are resolved against
+/// the base IRIs, those named by absolute IRIs (e.g. `urn:dev:mac:0024befffe804ff1`)
+/// are kept as-is.
+#[derive(Debug, Clone)]
+pub struct ObservationMapping {
+    device_base: String,
+    property_base: String,
+    properties: HashMap<String, NamedNode>,
+}
+
+impl ObservationMapping {
+    /// Create a mapping naming devices and properties under the given base IRIs
+    pub fn new(device_base: &str, property_base: &str) -> Self {
+        Self {
+            device_base: device_base.to_string(),
+            property_base: property_base.to_string(),
+            properties: HashMap::new(),
+        }
+    }
+
+    /// Map the measurements named `name` to `property` instead of the base IRI
+    pub fn with_property(mut self, name: &str, property: NamedNode) -> Self {
+        self.properties.insert(name.to_string(), property);
+        self
+    }
+
+    /// Describe the measurements of a device, grouped into one event per timestamp
+    /// Measurements without a time of their own are timestamped with `now`
+    pub(crate) fn events(
+        &self,
+        device: &str,
+        measurements: &[Measurement],
+        now: i64,
+    ) -> Result<BTreeMap<i64, Vec<Quad>>, String> {
+        let device = Self::iri(&self.device_base, device)?;
+        let sosa = |name: &str| NamedNode::new_unchecked(format!("{}{}", SOSA, name));
+        let mut events: BTreeMap<i64, Vec<Quad>> = BTreeMap::new();
+        for measurement in measurements {
+            let property = match self.properties.get(&measurement.name) {
+                Some(property) => property.clone(),
+                None => Self::iri(&self.property_base, &measurement.name)?,
+            };
+            let time = measurement.time.unwrap_or(now);
+            let observation = BlankNode::default();
+            let triples: [(NamedNode, Term); 5] = [
+                (
+                    NamedNode::new_unchecked(RDF_TYPE),
+                    sosa("Observation").into(),
+                ),
+                (sosa("madeBySensor"), device.clone().into()),
+                (sosa("observedProperty"), property.into()),
+                (sosa("hasSimpleResult"), measurement.value.clone().into()),
+                (sosa("resultTime"), date_time_literal(time).into()),
+            ];
+            events
+                .entry(time)
+                .or_default()
+                .extend(triples.into_iter().map(|(predicate, object)| {
+                    Quad::new(
+                        observation.clone(),
+                        predicate,
+                        object,
+                        GraphName::DefaultGraph,
+                    )
+                }));
+        }
+        Ok(events)
+    }
+
+    fn iri(base: &str, name: &str) -> Result<NamedNode, String> {
+        let iri = if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("{}{}", base, name)
+        };
+        NamedNode::new(iri).map_err(|e| format!("Invalid IRI for {}: {}", name, e))
+    }
+}
+
+/// Parse a SenML JSON pack, returning the base name of its first record if any and
+/// its measurements; `now` resolves relative times
+pub(crate) fn parse_senml(
+    payload: &str,
+    now: i64,
+) -> Result<(Option<String>, Vec<Measurement>), String> {
+    let pack: Vec<serde_json::Map<String, Value>> =
+        serde_json::from_str(payload).map_err(|e| format!("Invalid SenML pack: {}", e))?;
+    let mut device = None;
+    let mut base_time = 0.0;
+    let mut measurements = Vec::new();
+    for record in pack {
+        if let Some(name) = record.get("bn").and_then(Value::as_str) {
+            device.get_or_insert_with(|| name.trim_end_matches([':', '/']).to_string());
+        }
+        if let Some(time) = record.get("bt").and_then(Value::as_f64) {
+            base_time = time;
+        }
+        let value = if let Some(value) = record.get("v").and_then(Value::as_f64) {
+            Literal::from(value)
+        } else if let Some(value) = record.get("vs").and_then(Value::as_str) {
+            Literal::from(value)
+        } else if let Some(value) = record.get("vb").and_then(Value::as_bool) {
+            Literal::from(value)
+        } else {
+            // Data values and records only setting base fields
+            continue;
+        };
+        let mut time = base_time + record.get("t").and_then(Value::as_f64).unwrap_or(0.0);
+        if time.abs() < SENML_RELATIVE_TIME {
+            time += now as f64 / 1000.0;
+        }
+        let name = record.get("n").and_then(Value::as_str).unwrap_or_default();
+        measurements.push(Measurement {
+            name: name.to_string(),
+            value,
+            time: Some((time * 1000.0).round() as i64),
+        });
+    }
+    Ok((device, measurements))
+}
+
+/// Parse a payload of `name=value` pairs separated by `&`, `;` or new lines, such as
+/// `temperature=21.5&humidity=40&t=1732616130`
+/// The `t` pair is the time of all measurements in Unix seconds, as in SenML
+pub(crate) fn parse_key_values(payload: &str) -> Result<Vec<Measurement>, String> {
+    let mut time = None;
+    let mut measurements = Vec::new();
+    for pair in payload
+        .split(['&', ';', '\n'])
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+    {
+        let Some((name, value)) = pair.split_once('=') else {
+            return Err(format!("Invalid key/value pair {}", pair));
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name == "t" {
+            let seconds: f64 = value
+                .parse()
+                .map_err(|_| format!("Invalid measurement time {}", value))?;
+            time = Some((seconds * 1000.0).round() as i64);
+            continue;
+        }
+        let value = if let Ok(value) = value.parse::<i64>() {
+            Literal::from(value)
+        } else if let Ok(value) = value.parse::<f64>() {
+            Literal::from(value)
+        } else if let Ok(value) = value.parse::<bool>() {
+            Literal::from(value)
+        } else {
+            Literal::from(value)
+        };
+        measurements.push(Measurement {
+            name: name.to_string(),
+            value,
+            time: None,
+        });
+    }
+    for measurement in &mut measurements {
+        measurement.time = time;
+    }
+    Ok(measurements)
+}
+
+/// CoAP server receiving the observations of constrained devices into streams
+///
+/// Devices `POST` (or `PUT`) a SenML JSON pack or `name=value` pairs to the path
+/// routed to a stream, optionally followed by their identifier, e.g.
+/// `coap://engine/sensors/thermometer1`; the base name of a SenML pack identifies
+/// the device otherwise. Measurements are described with the observation mapping
+/// and added to the stream at the time reported by the device, or the time of
+/// arrival if it reports none. Requests are answered with 2.04 Changed, or 4.00
+/// Bad Request if the payload can't be mapped.
+///
+/// ```rust,no_run
+/// use rsp_rs::{CoapSource, ObservationMapping, RSPEngine};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let stream = engine.get_stream("http://example.org/stream1").unwrap();
+/// let mapping =
+///     ObservationMapping::new("http://example.org/device/", "http://example.org/property/");
+/// CoapSource::bind("0.0.0.0:5683", mapping)
+///     .unwrap()
+///     .route("sensors", stream)
+///     .spawn();
+/// ```
+pub struct CoapSource {
+    socket: UdpSocket,
+    mapping: ObservationMapping,
+    routes: HashMap<String, RDFStream>,
+}
+
+impl CoapSource {
+    /// Bind the server to a UDP address, 5683 being the default CoAP port
+    pub fn bind(address: &str, mapping: ObservationMapping) -> Result<Self, String> {
+        let socket = UdpSocket::bind(address)
+            .map_err(|e| format!("Failed to bind CoAP server to {}: {}", address, e))?;
+        Ok(Self {
+            socket,
+            mapping,
+            routes: HashMap::new(),
+        })
+    }
+
+    /// Add the observations posted under `path` to `stream`
+    pub fn route(mut self, path: &str, stream: RDFStream) -> Self {
+        self.routes
+            .insert(path.trim_matches('/').to_string(), stream);
+        self
+    }
+
+    /// Get the address the server is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr, String> {
+        self.socket
+            .local_addr()
+            .map_err(|e| format!("Failed to get CoAP server address: {}", e))
+    }
+
+    /// Serve requests from a background thread, until the socket fails
+    pub fn spawn(self) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "coap", move || {
+            // Block-wise transfers aren't supported, so a pack fits a datagram
+            let mut buffer = [0; 1500];
+            loop {
+                let (length, source) = match self.socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        error!("CoAP server stopped: {}", e);
+                        return;
+                    }
+                };
+                let packet = match Packet::from_bytes(&buffer[..length]) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        warn!(%source, "Skipping invalid CoAP message: {}", e);
+                        continue;
+                    }
+                };
+                let mut request = CoapRequest::from_packet(packet, source);
+                let status = self.handle(&request);
+                let Some(response) = request.response.as_mut() else {
+                    continue;
+                };
+                response.set_status(status);
+                match response.message.to_bytes() {
+                    Ok(bytes) => {
+                        if let Err(e) = self.socket.send_to(&bytes, source) {
+                            warn!(%source, "Failed to answer CoAP request: {}", e);
+                        }
+                    }
+                    Err(e) => warn!(%source, "Failed to encode CoAP response: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Add the observations of a request to its stream
+    fn handle(&self, request: &CoapRequest<SocketAddr>) -> ResponseType {
+        if !matches!(request.get_method(), RequestType::Post | RequestType::Put) {
+            return ResponseType::MethodNotAllowed;
+        }
+        let path = request.get_path();
+        let (route, device) = match path.trim_matches('/').rsplit_once('/') {
+            Some((route, device)) if self.routes.contains_key(route) => (route, Some(device)),
+            _ => (path.trim_matches('/'), None),
+        };
+        let Some(stream) = self.routes.get(route) else {
+            return ResponseType::NotFound;
+        };
+        match self.ingest(stream, device, &request.message.payload) {
+            Ok(()) => ResponseType::Changed,
+            Err(e) => {
+                warn!(stream = %stream.name, path = %path, "Rejecting CoAP observation: {}", e);
+                ResponseType::BadRequest
+            }
+        }
+    }
+
+    /// Observations failing to parse go to the dead letters of the stream's engine
+    fn ingest(
+        &self,
+        stream: &RDFStream,
+        device: Option<&str>,
+        payload: &[u8],
+    ) -> Result<(), String> {
+        let events = self.decode(device, payload).inspect_err(|e| {
+            stream.unparsable(&String::from_utf8_lossy(payload), None, e);
+        })?;
+        for (timestamp, quads) in events {
+            stream.add_timestamped(quads, timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Parse an observation into the timestamped quads of its events
+    fn decode(
+        &self,
+        device: Option<&str>,
+        payload: &[u8],
+    ) -> Result<BTreeMap<i64, Vec<Quad>>, String> {
+        let payload = std::str::from_utf8(payload)
+            .map_err(|e| format!("Payload is not valid UTF-8: {}", e))?;
+        let now = wall_clock_millis();
+        let (base_name, measurements) = if payload.trim_start().starts_with('[') {
+            parse_senml(payload, now)?
+        } else {
+            (None, parse_key_values(payload)?)
+        };
+        let device = device
+            .map(str::to_string)
+            .or(base_name)
+            .ok_or("No device in the path nor a SenML base name")?;
+        self.mapping.events(&device, &measurements, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_senml() {
+        let pack = r#"[
+            {"bn": "urn:dev:ow:10e2073a01080063:", "bt": 1732616130, "n": "temp", "v": 23.1},
+            {"n": "label", "vs": "kitchen", "t": 1},
+            {"n": "blob", "vd": "aGk"},
+            {"n": "count", "v": 2, "t": -5}
+        ]"#;
+        let (device, measurements) = parse_senml(pack, 0).unwrap();
+        assert_eq!(device.as_deref(), Some("urn:dev:ow:10e2073a01080063"));
+        let times: Vec<_> = measurements.iter().map(|m| m.time).collect();
+        assert_eq!(
+            times,
+            vec![
+                Some(1_732_616_130_000),
+                Some(1_732_616_131_000),
+                Some(1_732_616_125_000)
+            ]
+        );
+        assert_eq!(measurements[1].value, Literal::from("kitchen"));
+
+        // Relative times
+        let relative = r#"[{"n": "temp", "v": 20, "t": -2}]"#;
+        let (_, measurements) = parse_senml(relative, 10_000).unwrap();
+        assert_eq!(measurements[0].time, Some(8000));
+        assert!(parse_senml(r#"{"n": "temp"}"#, 0).is_err());
+    }
+
+    #[test]
+    fn test_parse_key_values() {
+        let payload = "temperature=21.5&humidity=40;t=1732616130.5";
+        let measurements = parse_key_values(payload).unwrap();
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements[0].value, Literal::from(21.5));
+        assert_eq!(measurements[1].value, Literal::from(40));
+        assert!(
+            measurements
+                .iter()
+                .all(|m| m.time == Some(1_732_616_130_500))
+        );
+        assert_eq!(parse_key_values("door=open").unwrap()[0].time, None);
+        assert!(parse_key_values("temperature").is_err());
+    }
+
+    #[test]
+    fn test_observations_grouped_by_time() {
+        let mapping =
+            ObservationMapping::new("http://example.org/device/", "http://example.org/property/")
+                .with_property(
+                    "temp",
+                    NamedNode::new("http://example.org/Temperature").unwrap(),
+                );
+        let measurements = vec![
+            Measurement {
+                name: "temp".to_string(),
+                value: Literal::from(20),
+                time: Some(1000),
+            },
+            Measurement {
+                name: "humidity".to_string(),
+                value: Literal::from(40),
+                time: None,
+            },
+        ];
+        let events = mapping.events("sensor1", &measurements, 5000).unwrap();
+        assert_eq!(events.keys().copied().collect::<Vec<_>>(), vec![1000, 5000]);
+        let objects = |time: i64| -> Vec<Term> {
+            events[&time]
+                .iter()
+                .map(|quad| quad.object.clone())
+                .collect()
+        };
+        let iri = |iri: &str| Term::from(NamedNode::new(iri).unwrap());
+        assert!(objects(1000).contains(&iri("http://example.org/Temperature")));
+        assert!(objects(5000).contains(&iri("http://example.org/property/humidity")));
+        assert!(objects(5000).contains(&iri("http://example.org/device/sensor1")));
+        let time = crate::TimestampExtractor::sosa_result_time().extract(&events[&1000]);
+        assert_eq!(time, Ok(1000));
+    }
+}
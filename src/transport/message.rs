@@ -0,0 +1,146 @@
+use crate::file::json_object;
+use crate::jsonld::parse_jsonld;
+use crate::rdf::parse_rdf;
+use crate::template::RdfTemplate;
+use crate::timestamp::TimestampPolicy;
+use crate::{BindingWithTimestamp, RDFStream};
+use oxigraph::io::RdfFormat;
+use oxigraph::model::Quad;
+
+/// Serialization of the events received from a pub/sub topic, one event per message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Turtle, N-Triples, N-Quads, TriG...
+    Rdf(RdfFormat),
+    JsonLd,
+}
+
+/// Serialization of the results published to a pub/sub topic, one result per message
+#[derive(Debug, Clone)]
+pub enum ResultFormat {
+    /// JSON object with a field per variable, `window_start` and `window_end`
+    Json,
+    /// N-Triples rendered with an RDF template
+    Template(RdfTemplate),
+}
+
+/// Turns the messages of a topic into events of a stream
+#[derive(Debug, Clone)]
+pub(crate) struct MessageDecoder {
+    pub(crate) format: MessageFormat,
+    /// Messages carry no timestamp of their own, so the explicit policy can't apply
+    pub(crate) timestamp: TimestampPolicy,
+}
+
+// Only used by the transports enabled with their features
+#[cfg_attr(not(any(feature = "nats", feature = "zeromq")), allow(dead_code))]
+impl MessageDecoder {
+    pub(crate) fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            timestamp: TimestampPolicy::WallClock,
+        }
+    }
+
+    /// Parse a message into the quads of one event
+    pub(crate) fn decode(&self, payload: &[u8]) -> Result<Vec<Quad>, String> {
+        let document = std::str::from_utf8(payload)
+            .map_err(|e| format!("Message is not valid UTF-8: {}", e))?;
+        match self.format {
+            MessageFormat::Rdf(format) => parse_rdf(document, format),
+            MessageFormat::JsonLd => parse_jsonld(document),
+        }
+    }
+
+    /// Add a message to a stream, timestamped with the decoder's policy
+    /// Messages failing to parse or to yield a timestamp go to the dead letters
+    pub(crate) fn ingest(&self, stream: &RDFStream, payload: &[u8]) -> Result<(), String> {
+        let event = self
+            .decode(payload)
+            .and_then(|quads| Ok((self.timestamp.resolve(&quads, None)?, quads)))
+            .inspect_err(|e| stream.unparsable(&String::from_utf8_lossy(payload), None, e));
+        let (timestamp, quads) = event?;
+        stream.add_timestamped(quads, timestamp)
+    }
+}
+
+#[cfg_attr(not(any(feature = "nats", feature = "zeromq")), allow(dead_code))]
+impl ResultFormat {
+    /// Serialize a result as the payload of a message
+    pub(crate) fn encode(&self, result: &BindingWithTimestamp) -> Vec<u8> {
+        match self {
+            ResultFormat::Json => json_object(result).into_bytes(),
+            ResultFormat::Template(template) => {
+                let mut document = String::new();
+                for triple in template.render_result(result) {
+                    document.push_str(&format!("{} .\n", triple));
+                }
+                document.into_bytes()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Binding, TimestampExtractor};
+    use oxigraph::model::{Literal, NamedNode};
+
+    #[test]
+    fn test_decode_messages() {
+        let decoder = MessageDecoder::new(MessageFormat::Rdf(RdfFormat::NTriples));
+        let quads = decoder
+            .decode(b"<http://example.org/s1> <http://example.org/value> 42 .")
+            .unwrap();
+        assert_eq!(quads.len(), 1);
+        assert!(decoder.decode(b"not rdf").is_err());
+        assert!(decoder.decode(&[0xff, 0xfe]).is_err());
+
+        let decoder = MessageDecoder {
+            format: MessageFormat::Rdf(RdfFormat::Turtle),
+            timestamp: TimestampPolicy::Predicate(TimestampExtractor::sosa_result_time()),
+        };
+        let quads = decoder
+            .decode(
+                br#"@prefix sosa: <http://www.w3.org/ns/sosa/> .
+                    <http://example.org/o1> sosa:resultTime 1500 ."#,
+            )
+            .unwrap();
+        assert_eq!(decoder.timestamp.resolve(&quads, None), Ok(1500));
+    }
+
+    #[test]
+    fn test_encode_results() {
+        let result = BindingWithTimestamp {
+            bindings: String::new(),
+            solution: Binding {
+                values: vec![
+                    (
+                        "sensor".to_string(),
+                        NamedNode::new("http://example.org/s1").unwrap().into(),
+                    ),
+                    ("avg".to_string(), Literal::from(21).into()),
+                ],
+            },
+            timestamp_from: 0,
+            timestamp_to: 1000,
+            evaluation_latency: None,
+            ingestion_latency: None,
+            window: None,
+            row: 0,
+        };
+        assert_eq!(
+            ResultFormat::Json.encode(&result),
+            br#"{"sensor":"http://example.org/s1","avg":"21","window_start":0,"window_end":1000}"#
+        );
+
+        let template = RdfTemplate::new("CONSTRUCT { ?sensor <http://example.org/avg> ?avg }");
+        let payload = ResultFormat::Template(template.unwrap()).encode(&result);
+        assert_eq!(
+            String::from_utf8(payload).unwrap(),
+            "<http://example.org/s1> <http://example.org/avg> \
+             \"21\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(feature = "coap")]
+pub mod coap;
+pub mod message;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "zeromq")]
+pub mod zeromq;
@@ -0,0 +1,126 @@
+use crate::message::{MessageDecoder, MessageFormat, ResultFormat};
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use crate::timestamp::TimestampPolicy;
+use crate::{BindingWithTimestamp, RDFStream};
+use std::sync::mpsc;
+use std::thread;
+use tracing::{error, warn};
+
+/// Source subscribing to a NATS subject and adding its messages to a stream
+///
+/// Each message is one event in the configured serialization. Subjects may use
+/// the `*` and `>` wildcards. Events are timestamped on arrival unless another
+/// timestamp policy is set.
+///
+/// ```rust,no_run
+/// use rsp_rs::{MessageFormat, NatsSource, RSPEngine};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let stream = engine.get_stream("http://example.org/stream1").unwrap();
+/// NatsSource::new("nats://localhost:4222", "sensors.>", MessageFormat::JsonLd)
+///     .spawn(stream)
+///     .unwrap();
+/// ```
+pub struct NatsSource {
+    url: String,
+    subject: String,
+    decoder: MessageDecoder,
+}
+
+impl NatsSource {
+    /// Create a source subscribing to `subject` on the server at `url`
+    pub fn new(url: &str, subject: &str, format: MessageFormat) -> Self {
+        Self {
+            url: url.to_string(),
+            subject: subject.to_string(),
+            decoder: MessageDecoder::new(format),
+        }
+    }
+
+    /// Set how events are timestamped, the wall-clock time on arrival by default
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.decoder.timestamp = policy;
+        self
+    }
+
+    /// Subscribe to the subject and add its messages to `stream` from a background
+    /// thread, which stops when the connection is closed
+    /// Messages that don't parse are skipped with a warning
+    pub fn spawn(self, stream: RDFStream) -> Result<thread::JoinHandle<()>, String> {
+        let connection = nats::connect(&self.url)
+            .map_err(|e| format!("Failed to connect to {}: {}", self.url, e))?;
+        let subscription = connection
+            .subscribe(&self.subject)
+            .map_err(|e| format!("Failed to subscribe to {}: {}", self.subject, e))?;
+        let decoder = self.decoder;
+        Ok(spawn_named(THREAD_NAME_PREFIX, "nats-source", move || {
+            // Keep the connection open for as long as the subscription is read
+            let _connection = connection;
+            for message in subscription.messages() {
+                if let Err(e) = decoder.ingest(&stream, &message.data) {
+                    warn!(
+                        stream = %stream.name,
+                        subject = %message.subject,
+                        "Skipping NATS message: {}",
+                        e
+                    );
+                }
+            }
+        }))
+    }
+}
+
+/// Sink publishing results to a NATS subject, one message per result
+///
+/// ```rust,no_run
+/// use rsp_rs::{NatsSink, RSPEngine, ResultFormat};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let sink = NatsSink::connect("nats://localhost:4222", "averages", ResultFormat::Json)
+///     .unwrap();
+/// sink.spawn(engine.start_processing().into_receiver());
+/// ```
+pub struct NatsSink {
+    connection: nats::Connection,
+    subject: String,
+    format: ResultFormat,
+}
+
+impl NatsSink {
+    /// Connect to the server at `url`, publishing to `subject`
+    pub fn connect(url: &str, subject: &str, format: ResultFormat) -> Result<Self, String> {
+        let connection =
+            nats::connect(url).map_err(|e| format!("Failed to connect to {}: {}", url, e))?;
+        Ok(Self {
+            connection,
+            subject: subject.to_string(),
+            format,
+        })
+    }
+
+    /// Publish one result
+    pub fn publish(&self, result: &BindingWithTimestamp) -> Result<(), String> {
+        self.connection
+            .publish(&self.subject, self.format.encode(result))
+            .map_err(|e| format!("Failed to publish to {}: {}", self.subject, e))
+    }
+
+    /// Publish every result received on the channel from a background thread,
+    /// flushing the connection whenever the channel is drained
+    pub fn spawn(self, results: mpsc::Receiver<BindingWithTimestamp>) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "nats-sink", move || {
+            while let Ok(first) = results.recv() {
+                for result in std::iter::once(first).chain(results.try_iter()) {
+                    if let Err(e) = self.publish(&result) {
+                        error!("{}", e);
+                    }
+                }
+                if let Err(e) = self.connection.flush() {
+                    error!("Failed to flush NATS connection: {}", e);
+                }
+            }
+        })
+    }
+}
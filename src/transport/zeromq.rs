@@ -0,0 +1,151 @@
+use crate::message::{MessageDecoder, MessageFormat, ResultFormat};
+use crate::supervisor::{THREAD_NAME_PREFIX, spawn_named};
+use crate::timestamp::TimestampPolicy;
+use crate::{BindingWithTimestamp, RDFStream};
+use std::sync::mpsc;
+use std::thread;
+use tracing::{error, warn};
+
+/// Source subscribing to ZeroMQ topics and adding their messages to a stream
+///
+/// The last frame of each message is the event, e.g. after a topic frame, in the
+/// configured serialization. Events are timestamped on arrival unless another
+/// timestamp policy is set.
+///
+/// ```rust,no_run
+/// use oxigraph::io::RdfFormat;
+/// use rsp_rs::{MessageFormat, RSPEngine, ZmqSource};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let stream = engine.get_stream("http://example.org/stream1").unwrap();
+/// ZmqSource::new("tcp://localhost:5556", MessageFormat::Rdf(RdfFormat::NTriples))
+///     .with_topic("sensors")
+///     .spawn(stream)
+///     .unwrap();
+/// ```
+pub struct ZmqSource {
+    endpoint: String,
+    topics: Vec<String>,
+    decoder: MessageDecoder,
+}
+
+impl ZmqSource {
+    /// Create a source connecting to the publisher at `endpoint`
+    pub fn new(endpoint: &str, format: MessageFormat) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            topics: Vec::new(),
+            decoder: MessageDecoder::new(format),
+        }
+    }
+
+    /// Subscribe to messages starting with `topic`; all messages are received if
+    /// no topic is set
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topics.push(topic.to_string());
+        self
+    }
+
+    /// Set how events are timestamped, the wall-clock time on arrival by default
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.decoder.timestamp = policy;
+        self
+    }
+
+    /// Connect to the publisher and add its messages to `stream` from a background
+    /// thread, which stops when the connection fails
+    /// Messages that don't parse are skipped with a warning
+    pub fn spawn(self, stream: RDFStream) -> Result<thread::JoinHandle<()>, String> {
+        let context = zmq::Context::new();
+        let socket = context
+            .socket(zmq::SUB)
+            .map_err(|e| format!("Failed to create ZeroMQ socket: {}", e))?;
+        socket
+            .connect(&self.endpoint)
+            .map_err(|e| format!("Failed to connect to {}: {}", self.endpoint, e))?;
+        let topics = if self.topics.is_empty() {
+            vec![String::new()]
+        } else {
+            self.topics
+        };
+        for topic in &topics {
+            socket
+                .set_subscribe(topic.as_bytes())
+                .map_err(|e| format!("Failed to subscribe to topic {}: {}", topic, e))?;
+        }
+        let decoder = self.decoder;
+        let endpoint = self.endpoint;
+        let receive = move || {
+            loop {
+                let frames = match socket.recv_multipart(0) {
+                    Ok(frames) => frames,
+                    Err(e) => {
+                        error!("Failed to receive from {}: {}", endpoint, e);
+                        return;
+                    }
+                };
+                let Some(payload) = frames.last() else {
+                    continue;
+                };
+                if let Err(e) = decoder.ingest(&stream, payload) {
+                    warn!(stream = %stream.name, "Skipping ZeroMQ message: {}", e);
+                }
+            }
+        };
+        Ok(spawn_named(THREAD_NAME_PREFIX, "zeromq-source", receive))
+    }
+}
+
+/// Sink publishing results on a ZeroMQ PUB socket, one message per result made of
+/// a topic frame and the serialized result
+///
+/// ```rust,no_run
+/// use rsp_rs::{RSPEngine, ResultFormat, ZmqSink};
+///
+/// let mut engine = RSPEngine::new("...".to_string());
+/// engine.initialize().unwrap();
+/// let sink = ZmqSink::bind("tcp://*:5557", "averages", ResultFormat::Json).unwrap();
+/// sink.spawn(engine.start_processing().into_receiver());
+/// ```
+pub struct ZmqSink {
+    socket: zmq::Socket,
+    topic: String,
+    format: ResultFormat,
+}
+
+impl ZmqSink {
+    /// Bind a PUB socket at `endpoint`, publishing on `topic`
+    pub fn bind(endpoint: &str, topic: &str, format: ResultFormat) -> Result<Self, String> {
+        let socket = zmq::Context::new()
+            .socket(zmq::PUB)
+            .map_err(|e| format!("Failed to create ZeroMQ socket: {}", e))?;
+        socket
+            .bind(endpoint)
+            .map_err(|e| format!("Failed to bind {}: {}", endpoint, e))?;
+        Ok(Self {
+            socket,
+            topic: topic.to_string(),
+            format,
+        })
+    }
+
+    /// Publish one result
+    pub fn publish(&self, result: &BindingWithTimestamp) -> Result<(), String> {
+        let frames = [self.topic.as_bytes().to_vec(), self.format.encode(result)];
+        self.socket
+            .send_multipart(frames, 0)
+            .map_err(|e| format!("Failed to publish on topic {}: {}", self.topic, e))
+    }
+
+    /// Publish every result received on the channel from a background thread
+    pub fn spawn(self, results: mpsc::Receiver<BindingWithTimestamp>) -> thread::JoinHandle<()> {
+        spawn_named(THREAD_NAME_PREFIX, "zeromq-sink", move || {
+            for result in results {
+                if let Err(e) = self.publish(&result) {
+                    error!("{}", e);
+                }
+            }
+        })
+    }
+}
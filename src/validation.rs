@@ -0,0 +1,381 @@
+//! Reference evaluation of RSP-QL queries, to validate the output of an engine
+//!
+//! A [`ReferenceEvaluator`] materializes every event of the streams, computes the
+//! window instances from their definition alone and runs the R2R query over each
+//! closed instance, without any of the engine's incremental state. Comparing its
+//! results with those of an engine fed the same events, with [`compare_results`],
+//! shows reports that the engine missed, added or evaluated differently.
+//!
+//! The reference follows the RSP-QL semantics with on-window-close reporting: the
+//! instance `[open, close)` reports once an event at or after `close` arrives on one
+//! of its streams, over the events in `[open, close)`. Other windows of the query
+//! contribute the content of their earliest-closing instance `[open, close)` that
+//! contains the reported instance's last event, so an event at an instance's close
+//! belongs to the next instance. Partitioning is not applied.
+//!
+//! ```rust,no_run
+//! use rsp_rs::validation::{ReferenceEvaluator, compare_results};
+//! use rsp_rs::RSPEngineBuilder;
+//!
+//! let query = "...";
+//! let mut engine = RSPEngineBuilder::new(query.to_string()).synchronous(true).build();
+//! engine.initialize().unwrap();
+//! let mut reference = ReferenceEvaluator::new(query);
+//!
+//! let stream = engine.get_stream("http://example.org/stream").unwrap();
+//! # let events: Vec<(Vec<oxigraph::model::Quad>, i64)> = Vec::new();
+//! for (quads, timestamp) in events {
+//!     reference.add_event("http://example.org/stream", quads.clone(), timestamp);
+//!     stream.add_quads(quads, timestamp).unwrap();
+//! }
+//!
+//! let report = compare_results(&reference.evaluate().unwrap(), &engine.poll_results());
+//! assert!(report.is_valid(), "{:?}", report);
+//! ```
+
+use crate::{
+    Binding, BindingWithTimestamp, ParsedQuery, QuadContainer, R2ROperator, RSPQLParser,
+    WindowDefinition,
+};
+use oxigraph::model::{GraphName, NamedNode, Quad};
+use oxigraph::sparql::QueryResults;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Naive evaluator computing the results a query should produce over a set of events
+pub struct ReferenceEvaluator {
+    parsed_query: ParsedQuery,
+    static_data: Vec<Quad>,
+    /// Events by stream IRI, in the order they were added
+    events: HashMap<String, Vec<(i64, Vec<Quad>)>>,
+    origin: Option<i64>,
+}
+
+impl ReferenceEvaluator {
+    /// Create an evaluator for an RSP-QL query
+    pub fn new(query: &str) -> Self {
+        Self {
+            parsed_query: RSPQLParser::new(query.to_string()).parse(),
+            static_data: Vec::new(),
+            events: HashMap::new(),
+            origin: None,
+        }
+    }
+
+    /// Align the windows without an `ALIGN` clause to `origin`, as the engine's
+    /// `origin` setting does; by default they are aligned to their first event
+    pub fn with_origin(mut self, origin: i64) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Add a quad to the static data joined with every window
+    pub fn add_static_data(&mut self, quad: Quad) {
+        self.static_data.push(quad);
+    }
+
+    /// Add an event to a stream
+    pub fn add_event(&mut self, stream_uri: &str, quads: Vec<Quad>, timestamp: i64) {
+        self.events
+            .entry(stream_uri.to_string())
+            .or_default()
+            .push((timestamp, quads));
+    }
+
+    /// Compute the results of every window instance closed by the events added so far,
+    /// ordered by window end
+    pub fn evaluate(&self) -> Result<Vec<BindingWithTimestamp>, String> {
+        let mut r2r = R2ROperator::new(self.parsed_query.sparql_query.clone());
+        if let Some(error) = r2r.parse_error() {
+            return Err(format!("Invalid R2R query: {}", error));
+        }
+        r2r.set_default_graphs(
+            self.parsed_query
+                .default_graphs
+                .iter()
+                .filter_map(|graph| NamedNode::new(graph).ok()),
+        );
+        for quad in &self.static_data {
+            r2r.add_static_data(quad.clone());
+        }
+
+        let mut reports = Vec::new();
+        for def in &self.parsed_query.s2r {
+            let events = self.window_events(def);
+            let Some(end) = events.iter().map(|(timestamp, _)| *timestamp).max() else {
+                continue;
+            };
+            for open in self.instances(def, &events) {
+                let close = open + def.width;
+                if close > end {
+                    continue;
+                }
+                let Some(last) = Self::last_timestamp(&events, open, close) else {
+                    continue;
+                };
+                let mut container = Self::content(def, &events, open, close, last);
+                let others = self
+                    .parsed_query
+                    .s2r
+                    .iter()
+                    .filter(|w| w.window_name != def.window_name);
+                for other in others {
+                    let other_events = self.window_events(other);
+                    let other_open = self
+                        .instances(other, &other_events)
+                        .into_iter()
+                        .find(|o| *o <= last && last < o + other.width);
+                    if let Some(other_open) = other_open {
+                        let other_content = Self::content(
+                            other,
+                            &other_events,
+                            other_open,
+                            other_open + other.width,
+                            last,
+                        );
+                        for quad in other_content.elements {
+                            container.add(quad, last);
+                        }
+                    }
+                }
+                reports.push((close, def.width, container));
+            }
+        }
+        reports.sort_by_key(|(close, _, _)| *close);
+
+        let mut results = Vec::new();
+        for (_, width, container) in reports {
+            let solutions = match r2r.execute(&container) {
+                Ok(QueryResults::Solutions(solutions)) => solutions,
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Failed to evaluate the R2R query: {}", e)),
+            };
+            for solution in solutions {
+                let solution = solution.map_err(|e| format!("Failed to evaluate: {}", e))?;
+                results.push(BindingWithTimestamp {
+                    bindings: format!("{:?}", solution),
+                    solution: Binding::from(&solution),
+                    timestamp_from: container.last_timestamp_changed,
+                    timestamp_to: container.last_timestamp_changed + width,
+                    evaluation_latency: None,
+                    ingestion_latency: None,
+                    window: None,
+                    row: 0,
+                });
+            }
+        }
+        Ok(results)
+    }
+
+    /// Get the events of every stream feeding a window
+    fn window_events(&self, def: &WindowDefinition) -> Vec<(i64, &[Quad])> {
+        std::iter::once(&def.stream_name)
+            .chain(&def.additional_stream_names)
+            .filter_map(|stream| self.events.get(stream))
+            .flatten()
+            .map(|(timestamp, quads)| (*timestamp, quads.as_slice()))
+            .collect()
+    }
+
+    /// Get the opening times of the instances of a window holding at least one event
+    fn instances(&self, def: &WindowDefinition, events: &[(i64, &[Quad])]) -> BTreeSet<i64> {
+        let mut opens = BTreeSet::new();
+        let first = events.iter().map(|(timestamp, _)| *timestamp).min();
+        let Some(t0) = def.origin.or(self.origin).or(first) else {
+            return opens;
+        };
+        for (timestamp, _) in events {
+            let mut open = t0 + (timestamp - t0).div_euclid(def.slide) * def.slide;
+            while open + def.width > *timestamp {
+                opens.insert(open);
+                open -= def.slide;
+            }
+        }
+        opens
+    }
+
+    fn last_timestamp(events: &[(i64, &[Quad])], open: i64, close: i64) -> Option<i64> {
+        events
+            .iter()
+            .map(|(timestamp, _)| *timestamp)
+            .filter(|timestamp| open <= *timestamp && *timestamp < close)
+            .max()
+    }
+
+    /// Get the quads of the events in `[open, close)`, in the window's graph
+    fn content(
+        def: &WindowDefinition,
+        events: &[(i64, &[Quad])],
+        open: i64,
+        close: i64,
+        timestamp: i64,
+    ) -> QuadContainer {
+        let graph = NamedNode::new(&def.window_name)
+            .unwrap_or_else(|_| NamedNode::new_unchecked("http://default-window"));
+        let elements: HashSet<Quad> = events
+            .iter()
+            .filter(|(t, _)| open <= *t && *t < close)
+            .flat_map(|(_, quads)| quads.iter())
+            .map(|quad| {
+                Quad::new(
+                    quad.subject.clone(),
+                    quad.predicate.clone(),
+                    quad.object.clone(),
+                    GraphName::NamedNode(graph.clone()),
+                )
+            })
+            .collect();
+        let mut container = QuadContainer::new(elements, timestamp);
+        container.window_bounds = Some((open, close));
+        container
+    }
+}
+
+/// Differences between the expected results of a query and those of an engine
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Expected results the engine didn't produce
+    pub missing: Vec<BindingWithTimestamp>,
+    /// Results of the engine that weren't expected
+    pub unexpected: Vec<BindingWithTimestamp>,
+}
+
+impl ValidationReport {
+    /// Whether the engine produced exactly the expected results
+    pub fn is_valid(&self) -> bool {
+        self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Compare results as multisets of solutions with their time range, whatever their order
+pub fn compare_results(
+    expected: &[BindingWithTimestamp],
+    actual: &[BindingWithTimestamp],
+) -> ValidationReport {
+    let key = |r: &BindingWithTimestamp| (r.timestamp_from, r.timestamp_to, r.solution.clone());
+    let mut remaining: HashMap<_, usize> = HashMap::new();
+    for result in actual {
+        *remaining.entry(key(result)).or_default() += 1;
+    }
+
+    let mut report = ValidationReport::default();
+    for result in expected {
+        match remaining.get_mut(&key(result)) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => report.missing.push(result.clone()),
+        }
+    }
+    for result in actual.iter().rev() {
+        if let Some(count) = remaining.get_mut(&key(result)).filter(|count| **count > 0) {
+            *count -= 1;
+            report.unexpected.push(result.clone());
+        }
+    }
+    report.unexpected.reverse();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RSPEngineBuilder;
+    use oxigraph::model::Literal;
+
+    const QUERY: &str = r#"
+        PREFIX ex: <http://example.org/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor (COUNT(?value) AS ?readings)
+        FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 1000 STEP 500]
+        WHERE { WINDOW ex:w { ?sensor ex:value ?value } }
+        GROUP BY ?sensor
+    "#;
+
+    fn reading(sensor: usize, value: i64) -> Vec<Quad> {
+        vec![Quad::new(
+            NamedNode::new(format!("http://example.org/sensor{}", sensor)).unwrap(),
+            NamedNode::new("http://example.org/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )]
+    }
+
+    #[test]
+    fn test_reference_matches_engine() {
+        let mut engine = RSPEngineBuilder::new(QUERY.to_string())
+            .synchronous(true)
+            .build();
+        engine.initialize().unwrap();
+        let stream = engine.get_stream("http://example.org/stream").unwrap();
+        let mut reference = ReferenceEvaluator::new(QUERY);
+
+        // One event every 250 ms, so each event closes at most one window
+        for i in 0..12 {
+            let quads = reading(i % 2, i as i64);
+            reference.add_event("http://example.org/stream", quads.clone(), i as i64 * 250);
+            stream.add_quads(quads, i as i64 * 250).unwrap();
+        }
+        // Closing in steps, as a sentinel closing two windows at once would only
+        // report the latest in the engine
+        for sentinel in [3250, 3750, 10_000] {
+            reference.add_event("http://example.org/stream", Vec::new(), sentinel);
+            engine
+                .close_stream("http://example.org/stream", sentinel)
+                .unwrap();
+        }
+
+        let expected = reference.evaluate().unwrap();
+        let actual = engine.poll_results();
+        assert!(!expected.is_empty());
+        let report = compare_results(&expected, &actual);
+        assert!(report.is_valid(), "{:?}", report);
+    }
+
+    #[test]
+    fn test_other_window_instance_at_boundary() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            REGISTER RStream <output> AS
+            SELECT ?value
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000]
+            FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 500 STEP 500]
+            WHERE { WINDOW ex:w1 { ?s ex:value ?x } WINDOW ex:w2 { ?t ex:value ?value } }
+        "#;
+        let mut reference = ReferenceEvaluator::new(query).with_origin(0);
+        reference.add_event("http://example.org/stream2", reading(0, 1), 100);
+        reference.add_event("http://example.org/stream2", reading(0, 2), 500);
+        reference.add_event("http://example.org/stream1", reading(1, 0), 500);
+        reference.add_event("http://example.org/stream1", Vec::new(), 1000);
+
+        let results = reference.evaluate().unwrap();
+        // The last event of [0, 1000) of w1 is at 500, the close of [0, 500) of w2,
+        // so it is joined with [500, 1000) of w2
+        let w1 = results.iter().find(|r| r.timestamp_from == 500).unwrap();
+        assert_eq!(w1.timestamp_to, 1500);
+        assert_eq!(w1.solution.number("value"), Some(2.0));
+        // [0, 500) of w2, closed by the event at 500, is joined with [0, 1000) of w1
+        let w2 = results.iter().find(|r| r.timestamp_from == 100).unwrap();
+        assert_eq!(w2.solution.number("value"), Some(1.0));
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_compare_reports_differences() {
+        let mut reference = ReferenceEvaluator::new(QUERY);
+        for i in 0..4 {
+            reference.add_event("http://example.org/stream", reading(0, i), i * 400);
+        }
+        let expected = reference.evaluate().unwrap();
+        // Windows [-500, 500) and [0, 1000) close by t=1200
+        assert_eq!(expected.len(), 2);
+
+        let mut actual = expected.clone();
+        let dropped = actual.remove(0);
+        let mut extra = actual[0].clone();
+        extra.timestamp_from += 1;
+        actual.push(extra.clone());
+
+        let report = compare_results(&expected, &actual);
+        assert!(!report.is_valid());
+        assert_eq!(report.missing, vec![dropped]);
+        assert_eq!(report.unexpected, vec![extra]);
+    }
+}
@@ -0,0 +1,125 @@
+//! JavaScript API for running RSP-QL queries in the browser, like RSP-JS
+//!
+//! Built for `wasm32-unknown-unknown`, where there are no threads: engines are
+//! synchronous, so windows report and results reach the callbacks before
+//! `addQuads` returns. Events are JSON-LD documents and results are passed to the
+//! callbacks as objects with a field per variable, then `window_start` and
+//! `window_end`. Build with
+//! `cargo rustc --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and generate the bindings with `wasm-bindgen --target web`.
+//!
+//! ```js
+//! import init, { RSPEngine } from "./pkg/rsp_rs.js";
+//!
+//! await init();
+//! const engine = new RSPEngine(query);
+//! engine.onResult((result) => console.log(result.window_start, result));
+//! engine.addQuads(JSON.stringify(event), Date.now());
+//! engine.closeStream("http://example.org/stream1", Date.now() + 60000);
+//! ```
+
+use crate::file::json_object;
+use crate::{BindingWithTimestamp, RSPEngine, RSPEngineBuilder, parse_jsonld};
+use wasm_bindgen::prelude::*;
+
+/// RSP engine registering one RSP-QL query
+#[wasm_bindgen(js_name = RSPEngine)]
+pub struct WasmEngine {
+    engine: RSPEngine,
+}
+
+/// JavaScript callback receiving the results
+struct ResultCallback(js_sys::Function);
+
+// JavaScript values can't cross threads, but wasm32-unknown-unknown has none:
+// callbacks always run on the thread calling `addQuads`
+unsafe impl Send for ResultCallback {}
+unsafe impl Sync for ResultCallback {}
+
+impl ResultCallback {
+    fn call(&self, result: &BindingWithTimestamp) {
+        let Ok(value) = js_sys::JSON::parse(&json_object(result)) else {
+            return;
+        };
+        // Exceptions of the callback don't stop the other callbacks
+        let _ = self.0.call1(&JsValue::NULL, &value);
+    }
+}
+
+#[wasm_bindgen(js_class = RSPEngine)]
+impl WasmEngine {
+    /// Create an engine for an RSP-QL query, with its windows and streams
+    #[wasm_bindgen(constructor)]
+    pub fn new(query: &str) -> Result<WasmEngine, JsError> {
+        let mut engine = RSPEngineBuilder::new(query.to_string())
+            .synchronous(true)
+            .build();
+        engine.initialize().map_err(|e| JsError::new(&e))?;
+        Ok(Self { engine })
+    }
+
+    /// Add a JSON-LD document as one event at `timestamp` (in ms)
+    /// The stream can be omitted if the query reads a single stream
+    #[wasm_bindgen(js_name = addQuads)]
+    pub fn add_quads(
+        &self,
+        jsonld: &str,
+        timestamp: f64,
+        stream: Option<String>,
+    ) -> Result<(), JsError> {
+        let stream_name = match stream {
+            Some(stream) => stream,
+            None => self.single_stream()?,
+        };
+        let stream = self
+            .engine
+            .get_stream(&stream_name)
+            .ok_or_else(|| JsError::new(&format!("Unknown stream {}", stream_name)))?;
+        let quads = parse_jsonld(jsonld).map_err(|e| JsError::new(&e))?;
+        stream
+            .add_quads(quads, timestamp as i64)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// Call `callback` with every later result
+    #[wasm_bindgen(js_name = onResult)]
+    pub fn on_result(&self, callback: js_sys::Function) -> Result<(), JsError> {
+        let callback = ResultCallback(callback);
+        let query_id = self.engine.parsed_query().r2s.name.clone();
+        self.engine
+            .on_result(&query_id, move |result| callback.call(result))
+            .map_err(|e| JsError::new(&e))?;
+        Ok(())
+    }
+
+    /// Close a stream, reporting the windows open before `timestamp` (in ms)
+    #[wasm_bindgen(js_name = closeStream)]
+    pub fn close_stream(&self, stream: &str, timestamp: f64) -> Result<(), JsError> {
+        self.engine
+            .close_stream(stream, timestamp as i64)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// Get the IRIs of the streams read by the query
+    pub fn streams(&self) -> Vec<String> {
+        self.engine.get_all_streams()
+    }
+}
+
+impl WasmEngine {
+    /// Get the stream of a query reading a single one
+    fn single_stream(&self) -> Result<String, JsError> {
+        let mut streams = self.engine.parsed_query().s2r.iter().flat_map(|window| {
+            std::iter::once(&window.stream_name).chain(&window.additional_stream_names)
+        });
+        let first = streams
+            .next()
+            .ok_or_else(|| JsError::new("Query has no stream"))?;
+        if streams.any(|stream| stream != first) {
+            return Err(JsError::new(
+                "Query reads several streams, pass the stream IRI",
+            ));
+        }
+        Ok(first.clone())
+    }
+}
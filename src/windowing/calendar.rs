@@ -0,0 +1,330 @@
+const SECOND: i64 = 1000;
+const MINUTE: i64 = 60 * SECOND;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// Time zone on whose calendar the instances of a tumbling window are aligned,
+/// e.g. on the hours, or at midnight, of local time rather than of UTC
+///
+/// Zones are given as POSIX `TZ` strings, the rules a zone currently follows as
+/// found at the end of its tzdata file, so they need no time zone database: a
+/// standard offset, and the daylight saving time offset with the dates it starts
+/// and ends, if any. Offsets are hours west of Greenwich, as in POSIX. IANA names
+/// such as `Europe/Paris` are not supported.
+///
+/// ```rust
+/// use rsp_rs::TimeZone;
+///
+/// let paris = TimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+/// let new_york = TimeZone::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+/// let india = TimeZone::parse("IST-5:30").unwrap();
+/// assert_eq!(india, TimeZone::fixed(19_800_000));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeZone {
+    /// Standard offset from UTC, in ms east of Greenwich
+    std_offset: i64,
+    dst: Option<DaylightSaving>,
+}
+
+/// Daylight saving time of a zone, in ms east of Greenwich
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DaylightSaving {
+    offset: i64,
+    start: TransitionRule,
+    end: TransitionRule,
+}
+
+/// Date of a yearly offset change, with its local time (in ms) before the change
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TransitionRule {
+    date: TransitionDate,
+    time: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TransitionDate {
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` of month `m`, 5 being the last
+    MonthWeekDay { month: i64, week: i64, weekday: i64 },
+    /// `Jn`: day 1 to 365 of the year, never counting 29 February
+    Julian(i64),
+    /// `n`: day 0 to 365 of the year
+    ZeroBased(i64),
+}
+
+impl TimeZone {
+    /// Get the UTC time zone
+    pub fn utc() -> Self {
+        Self::fixed(0)
+    }
+
+    /// Get a time zone without daylight saving time, `offset` ms east of Greenwich
+    pub fn fixed(offset: i64) -> Self {
+        Self {
+            std_offset: offset,
+            dst: None,
+        }
+    }
+
+    /// Parse a POSIX `TZ` string such as `CET-1CEST,M3.5.0,M10.5.0/3` or `<+0530>-5:30`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        Self::parse_posix(spec.trim()).ok_or_else(|| {
+            format!(
+                "Invalid time zone {}: only POSIX TZ strings such as \
+                 CET-1CEST,M3.5.0,M10.5.0/3 are supported, not IANA names such as Europe/Paris",
+                spec
+            )
+        })
+    }
+
+    fn parse_posix(mut rest: &str) -> Option<Self> {
+        take_name(&mut rest)?;
+        let std_offset = -take_time(&mut rest)?;
+        if rest.is_empty() {
+            return Some(Self::fixed(std_offset));
+        }
+        take_name(&mut rest)?;
+        let offset = if rest.starts_with(',') {
+            std_offset + HOUR
+        } else {
+            -take_time(&mut rest)?
+        };
+        let (start, end) = rest.strip_prefix(',')?.split_once(',')?;
+        let dst = DaylightSaving {
+            offset,
+            start: TransitionRule::parse(start)?,
+            end: TransitionRule::parse(end)?,
+        };
+        Some(Self {
+            std_offset,
+            dst: Some(dst),
+        })
+    }
+
+    /// Get the offset from UTC (in ms) of the zone at `utc`
+    pub fn offset_at(&self, utc: i64) -> i64 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let year = year_of((utc + self.std_offset).div_euclid(DAY));
+        // Daylight saving time starts in standard time, and ends in its own
+        let start = dst.start.utc_in(year, self.std_offset);
+        let end = dst.end.utc_in(year, dst.offset);
+        // Southern zones are in daylight saving time across the new year
+        let in_dst = if start < end {
+            start <= utc && utc < end
+        } else {
+            utc < end || start <= utc
+        };
+        if in_dst { dst.offset } else { self.std_offset }
+    }
+
+    /// Get the UTC time of the local time `local`, the earliest one when clocks go
+    /// back, or the end of the gap when they go forward past it
+    pub fn to_utc(&self, local: i64) -> i64 {
+        let dst_offset = self.dst.as_ref().map_or(self.std_offset, |dst| dst.offset);
+        let offsets = [self.std_offset, dst_offset];
+        let valid = offsets
+            .iter()
+            .map(|offset| local - offset)
+            .filter(|&utc| utc + self.offset_at(utc) == local)
+            .min();
+        // The offset before a gap is the smaller one
+        valid.unwrap_or(local - self.std_offset.min(dst_offset))
+    }
+
+    /// Get the bounds of the instance `width` ms wide containing `timestamp`, aligned
+    /// on the multiples of the width in local time
+    pub(crate) fn instance_at(&self, timestamp: i64, width: i64) -> (i64, i64) {
+        let local = timestamp + self.offset_at(timestamp);
+        let open = local - local.rem_euclid(width);
+        (self.to_utc(open), self.to_utc(open + width))
+    }
+
+    /// Get how much longer than their width instances can last
+    pub(crate) fn max_shift(&self) -> i64 {
+        let dst = self.dst.as_ref();
+        dst.map_or(0, |dst| (dst.offset - self.std_offset).abs())
+    }
+}
+
+impl TransitionRule {
+    fn parse(rule: &str) -> Option<Self> {
+        let (date, time) = match rule.split_once('/') {
+            Some((date, mut time)) => {
+                let parsed = take_time(&mut time)?;
+                (date, time.is_empty().then_some(parsed)?)
+            }
+            None => (rule, 2 * HOUR),
+        };
+        let date = if let Some(date) = date.strip_prefix('M') {
+            let fields: Vec<i64> = date
+                .split('.')
+                .map(|n| n.parse().ok())
+                .collect::<Option<_>>()?;
+            let [month, week, weekday] = fields[..] else {
+                return None;
+            };
+            let valid =
+                (1..=12).contains(&month) && (1..=5).contains(&week) && (0..=6).contains(&weekday);
+            valid.then_some(TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            })?
+        } else if let Some(day) = date.strip_prefix('J') {
+            let day = day
+                .parse::<i64>()
+                .ok()
+                .filter(|day| (1..=365).contains(day))?;
+            TransitionDate::Julian(day)
+        } else {
+            let day = date
+                .parse::<i64>()
+                .ok()
+                .filter(|day| (0..=365).contains(day))?;
+            TransitionDate::ZeroBased(day)
+        };
+        Some(Self { date, time })
+    }
+
+    /// Get the UTC time of the transition in `year`, in a zone `offset` ms east of
+    /// Greenwich before it
+    fn utc_in(&self, year: i64, offset: i64) -> i64 {
+        let new_year = days_from_civil(year, 1, 1);
+        let day = match self.date {
+            TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                let next_month = match month {
+                    12 => days_from_civil(year + 1, 1, 1),
+                    _ => days_from_civil(year, month + 1, 1),
+                };
+                // 1 January 1970 was a Thursday
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first + (weekday - first_weekday).rem_euclid(7) + 7 * (week - 1);
+                while day >= next_month {
+                    day -= 7;
+                }
+                day
+            }
+            TransitionDate::Julian(day) => {
+                let leap = days_from_civil(year, 3, 1) - days_from_civil(year, 2, 1) == 29;
+                new_year + day - 1 + i64::from(leap && day >= 60)
+            }
+            TransitionDate::ZeroBased(day) => new_year + day,
+        };
+        day * DAY + self.time - offset
+    }
+}
+
+/// Skip the name of a zone, either 3 letters or more, or quoted in `<>`
+fn take_name(rest: &mut &str) -> Option<()> {
+    let length = match rest.strip_prefix('<') {
+        Some(quoted) => quoted.find('>').filter(|&end| end > 0)? + 2,
+        None => {
+            let letters = rest.find(|c: char| !c.is_ascii_alphabetic());
+            letters.unwrap_or(rest.len())
+        }
+    };
+    if length < 3 {
+        return None;
+    }
+    *rest = &rest[length..];
+    Some(())
+}
+
+/// Take a signed `hh[:mm[:ss]]` time, in ms
+fn take_time(rest: &mut &str) -> Option<i64> {
+    let sign = if rest.starts_with('-') { -1 } else { 1 };
+    let unsigned = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+    let end = unsigned.find(|c: char| !c.is_ascii_digit() && c != ':');
+    let (time, tail) = unsigned.split_at(end.unwrap_or(unsigned.len()));
+    let mut ms = 0;
+    for (i, part) in time.split(':').enumerate() {
+        let unit = [HOUR, MINUTE, SECOND].get(i).copied()?;
+        ms += i64::from(part.parse::<u32>().ok()?) * unit;
+    }
+    *rest = tail;
+    Some(sign * ms)
+}
+
+/// Get the days since 1 January 1970 of a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Get the year of a day since 1 January 1970
+fn year_of(days: i64) -> i64 {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Years start in March, so January and February belong to the next one
+    let march_based_month = (5 * day_of_year + 2) / 153;
+    era * 400 + year_of_era + i64::from(march_based_month >= 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calendar_instances_across_daylight_saving_time() {
+        let paris = TimeZone::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let day = |month: i64, day: i64| days_from_civil(2024, month, day) * DAY;
+        assert_eq!(day(3, 31), 1_711_843_200_000);
+        assert_eq!(year_of(days_from_civil(2024, 12, 31)), 2024);
+        assert_eq!(year_of(days_from_civil(2025, 1, 1)), 2025);
+
+        // Daylight saving time starts on 31 March and ends on 27 October 2024 at 1 UTC
+        assert_eq!(paris.offset_at(day(3, 31) + HOUR - 1), HOUR);
+        assert_eq!(paris.offset_at(day(3, 31) + HOUR), 2 * HOUR);
+        assert_eq!(paris.offset_at(day(10, 27) + HOUR), HOUR);
+
+        // Days start at local midnight, and last 23 or 25 hours on transitions
+        let daily = |month: i64, day_of_month: i64| {
+            let (open, close) = paris.instance_at(day(month, day_of_month) + 12 * HOUR, DAY);
+            (open - day(month, day_of_month), close - open)
+        };
+        assert_eq!(daily(3, 31), (-HOUR, 23 * HOUR));
+        assert_eq!(daily(6, 1), (-2 * HOUR, DAY));
+        assert_eq!(daily(10, 27), (-2 * HOUR, 25 * HOUR));
+
+        // Hours skip 2:00 in spring, and 2:00 lasts two hours in autumn
+        let hourly = |timestamp: i64| paris.instance_at(timestamp, HOUR);
+        let spring = day(3, 31);
+        assert_eq!(hourly(spring + HOUR / 2), (spring, spring + HOUR));
+        assert_eq!(hourly(spring + HOUR), (spring + HOUR, spring + 2 * HOUR));
+        let autumn = day(10, 27);
+        assert_eq!(hourly(autumn + HOUR / 2), (autumn, autumn + 2 * HOUR));
+        assert_eq!(hourly(autumn + 3 * HOUR / 2), (autumn, autumn + 2 * HOUR));
+        assert_eq!(
+            hourly(autumn + 2 * HOUR),
+            (autumn + 2 * HOUR, autumn + 3 * HOUR)
+        );
+        assert_eq!(paris.max_shift(), HOUR);
+
+        let sydney = TimeZone::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(day(1, 15)), 11 * HOUR);
+        assert_eq!(sydney.offset_at(day(7, 15)), 10 * HOUR);
+        let kathmandu = TimeZone::parse("<+0545>-5:45").unwrap();
+        assert_eq!(kathmandu, TimeZone::fixed(5 * HOUR + 45 * MINUTE));
+        assert_eq!(TimeZone::parse("UTC0"), Ok(TimeZone::utc()));
+        for invalid in ["CET", "CET-1CEST", "X1", "CET-1CEST,M13.5.0,M10.5.0"] {
+            assert!(TimeZone::parse(invalid).is_err());
+        }
+        let error = TimeZone::parse("Europe/Paris").unwrap_err();
+        assert!(error.contains("only POSIX TZ strings"), "{}", error);
+    }
+}
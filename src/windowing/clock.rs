@@ -0,0 +1,107 @@
+use crate::timestamp::wall_clock_millis;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, LazyLock};
+
+/// Source of the processing time consulted by an engine's windows and streams
+///
+/// With an event-time clock, time only advances with the timestamps of the events,
+/// so windows close when later events arrive. With a processing-time clock, the
+/// engine also advances its windows to the clock's time, so instances of idle
+/// streams close, delayed instances report once their delay has passed, and
+/// periodic windows report on every advance; streams timestamping events on
+/// arrival read the clock too.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Get the current time in Unix milliseconds, or `None` if time only advances
+    /// with the events
+    fn now(&self) -> Option<i64>;
+}
+
+/// Clocks are compared by identity
+impl PartialEq for dyn Clock {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self, other)
+    }
+}
+
+impl Eq for dyn Clock {}
+
+/// Clock advancing only with the timestamps of the events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventTimeClock;
+
+impl Clock for EventTimeClock {
+    fn now(&self) -> Option<i64> {
+        None
+    }
+}
+
+/// Clock following the system's wall-clock time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Option<i64> {
+        Some(wall_clock_millis())
+    }
+}
+
+/// Clock moved by hand, e.g. to test processing-time behavior deterministically
+/// Clones share the same time
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    time: Arc<AtomicI64>,
+}
+
+impl ManualClock {
+    /// Create a clock starting at `time` (in ms)
+    pub fn new(time: i64) -> Self {
+        Self {
+            time: Arc::new(AtomicI64::new(time)),
+        }
+    }
+
+    /// Set the time of the clock
+    pub fn set(&self, time: i64) {
+        self.time.store(time, Ordering::SeqCst);
+    }
+
+    /// Move the clock forward by `millis` ms
+    pub fn advance(&self, millis: i64) {
+        self.time.fetch_add(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Option<i64> {
+        Some(self.time.load(Ordering::SeqCst))
+    }
+}
+
+/// Event-time clock shared by the default configurations, so they compare equal
+pub(crate) fn event_time() -> Arc<dyn Clock> {
+    static EVENT_TIME: LazyLock<Arc<dyn Clock>> = LazyLock::new(|| Arc::new(EventTimeClock));
+    EVENT_TIME.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clocks() {
+        assert_eq!(EventTimeClock.now(), None);
+        assert!(SystemClock.now().is_some_and(|now| now > 1_700_000_000_000));
+
+        let clock = ManualClock::new(1000);
+        let shared = clock.clone();
+        clock.advance(500);
+        assert_eq!(shared.now(), Some(1500));
+        shared.set(200);
+        assert_eq!(clock.now(), Some(200));
+
+        assert!(event_time() == event_time());
+        let manual: Arc<dyn Clock> = Arc::new(clock);
+        assert!(manual != event_time());
+    }
+}
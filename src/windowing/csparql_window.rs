@@ -1,26 +1,51 @@
-use crate::{QuadContainer, WindowInstance};
-use oxigraph::model::Quad;
+use crate::calendar::TimeZone;
+use crate::enrichment::LookupTable;
+use crate::eviction::{EvictionPolicy, WindowQuad};
+use crate::panes::Panes;
+use crate::partition::PartitionKey;
+use crate::quad_filter::QuadFilter;
+use crate::spill::WindowSpill;
+use crate::timestamp::Instant;
+use crate::window_stats::{ArrivalRate, EvaluationRecorder, WindowStats};
+use crate::{AggregationPlan, AggregationState, QuadContainer, WindowInstance, WindowState};
+use oxigraph::model::{GraphName, NamedNode, Quad, Term};
+use oxigraph::sparql::QuerySolution;
 use oxigraph::store::Store;
-use std::collections::{HashMap, HashSet};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
+use tracing::{debug, error, warn};
 
 /// Report strategy for window content emission
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ReportStrategy {
     NonEmptyContent,
+    /// Not implemented yet; rejected by the engine
     OnContentChange,
     OnWindowClose,
+    /// Not implemented yet; rejected by the engine
     Periodic,
 }
 
 /// Tick mechanism for window progression
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Tick {
     TimeDriven,
+    /// Not implemented yet; rejected by the engine
     TupleDriven,
+    /// Not implemented yet; rejected by the engine
     BatchDriven,
 }
 
+impl ReportStrategy {
+    /// Whether windows implement the strategy
+    pub(crate) fn is_supported(self) -> bool {
+        matches!(self, Self::NonEmptyContent | Self::OnWindowClose)
+    }
+}
+
 /// Output stream type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum StreamType {
@@ -30,19 +55,85 @@ pub enum StreamType {
 }
 
 /// Callback type for window content emission
-pub type WindowCallback = Arc<dyn Fn(QuadContainer) + Send + Sync>;
+/// The content is shared between all subscribers of the report
+pub type WindowCallback = Arc<dyn Fn(Arc<QuadContainer>) + Send + Sync>;
+
+/// Window content emitted together with its incrementally maintained aggregates
+#[derive(Debug)]
+pub struct AggregateReport {
+    pub content: Arc<QuadContainer>,
+    /// `None` when the window has to be evaluated with SPARQL instead
+    pub solutions: Option<Vec<QuerySolution>>,
+}
+
+/// Callback type for aggregate report emission
+pub type AggregateCallback = Arc<dyn Fn(AggregateReport) + Send + Sync>;
+
+/// Callback type for window instance lifecycle transitions, called with the
+/// instance in its new state
+pub type LifecycleCallback = Arc<dyn Fn(&WindowInstance) + Send + Sync>;
 
 /// CSPARQL Window implementation
 pub struct CSPARQLWindow {
     pub name: String,
+    /// Graph of the quads in the window, parsed from its name once rather than for
+    /// every event
+    graph: GraphName,
     pub width: i64,
     pub slide: i64,
     pub time: i64,
     pub t0: i64,
-    pub active_windows: HashMap<WindowInstance, QuadContainer>,
+    /// Open window instances, sorted by open time; all instances have the same
+    /// width, so they are sorted by close time too
+    pub active_windows: BTreeMap<WindowInstance, QuadContainer>,
     pub report: ReportStrategy,
     pub tick: Tick,
     callbacks: HashMap<StreamType, Vec<WindowCallback>>,
+    aggregation_plan: Option<AggregationPlan>,
+    aggregation_states: HashMap<WindowInstance, AggregationState>,
+    /// Aggregates per pane instead of per instance, see `set_pane_aggregation`
+    panes: Option<Panes>,
+    aggregate_callbacks: Vec<AggregateCallback>,
+    lifecycle_callbacks: Vec<LifecycleCallback>,
+    eviction_policies: Vec<Arc<dyn EvictionPolicy>>,
+    /// Arrival of the quads of each instance, kept only for the eviction policies
+    arrivals: HashMap<WindowInstance, HashMap<Arc<Quad>, WindowQuad>>,
+    spill: Option<WindowSpill>,
+    max_lateness: Option<i64>,
+    origin: Option<i64>,
+    /// Time zone aligning tumbling instances on its calendar, see `set_calendar`
+    calendar: Option<TimeZone>,
+    partition_key: Option<PartitionKey>,
+    /// Independent windows per partition key, when partitioned
+    partitions: HashMap<Term, CSPARQLWindow>,
+    /// Whether instances without any event report too, see `set_emit_empty_windows`
+    emit_empty_windows: bool,
+    /// Latest event time scoped so far
+    last_event: Option<i64>,
+    /// How long (in ms of event time) closed instances wait before reporting
+    delay: i64,
+    /// Whether the quads keep the stream of their event, see `set_record_streams`
+    record_streams: bool,
+    /// Whether instances count the events adding each quad, see `set_multiset`
+    multiset: bool,
+    /// Whether a single instance covers everything since t0, see `set_landmark`
+    landmark: bool,
+    /// How long (in ms of event time) a landmark window keeps the raw quads
+    retention: Option<i64>,
+    /// Step boundary of the last report of a landmark window
+    landmark_reported: Option<i64>,
+    /// Condition on the quads of events, see `set_quad_filter`
+    quad_filter: Option<QuadFilter>,
+    /// Static attributes attached to the subjects of events, see `StaticLookup`
+    lookup_table: Option<Arc<LookupTable>>,
+    /// When the event being added was received by its stream, for the reports it
+    /// triggers
+    event_received: Option<Instant>,
+    /// Counters of `stats`, as events, quads and filtered quads
+    ingested: (u64, u64, u64),
+    arrival_rate: ArrivalRate,
+    /// Shared with the engine evaluating the reports, and with the partitions
+    evaluations: Arc<EvaluationRecorder>,
     pub debug_mode: bool,
 }
 
@@ -56,6 +147,7 @@ impl CSPARQLWindow {
         start_time: i64,
     ) -> Self {
         Self {
+            graph: Self::graph_of(&name),
             name,
             width,
             slide,
@@ -63,121 +155,369 @@ impl CSPARQLWindow {
             tick,
             time: start_time,
             t0: start_time,
-            active_windows: HashMap::new(),
+            active_windows: BTreeMap::new(),
             callbacks: HashMap::new(),
+            aggregation_plan: None,
+            aggregation_states: HashMap::new(),
+            panes: None,
+            aggregate_callbacks: Vec::new(),
+            lifecycle_callbacks: Vec::new(),
+            eviction_policies: Vec::new(),
+            arrivals: HashMap::new(),
+            spill: None,
+            max_lateness: None,
+            origin: None,
+            calendar: None,
+            partition_key: None,
+            partitions: HashMap::new(),
+            emit_empty_windows: false,
+            last_event: None,
+            delay: 0,
+            record_streams: false,
+            multiset: false,
+            landmark: false,
+            retention: None,
+            landmark_reported: None,
+            quad_filter: None,
+            lookup_table: None,
+            event_received: None,
+            ingested: (0, 0, 0),
+            arrival_rate: ArrivalRate::default(),
+            evaluations: Arc::new(EvaluationRecorder::default()),
             debug_mode: false,
         }
     }
 
+    /// Get the graph of the quads of a window named `name`
+    fn graph_of(name: &str) -> GraphName {
+        // Fallback if window name isn't a valid IRI
+        let graph = NamedNode::new(name)
+            .unwrap_or_else(|_| NamedNode::new("http://default-window").unwrap());
+        graph.into()
+    }
+
     /// Get window content at a specific timestamp
-    /// Returns the window with the smallest close time that contains the timestamp
+    /// Returns the window with the smallest close time that contains the timestamp,
+    /// see `WindowInstance::contains`
+    /// Partitioned windows have no content of their own, see `partition`
     pub fn get_content(&self, timestamp: i64) -> Option<&QuadContainer> {
-        let mut max_window: Option<&WindowInstance> = None;
-        let mut max_time = i64::MAX;
+        self.instance_at(timestamp)
+            .and_then(|w| self.active_windows.get(w))
+    }
 
-        for (window, _container) in &self.active_windows {
-            if window.open <= timestamp && timestamp <= window.close {
-                if window.close < max_time {
-                    max_time = window.close;
-                    max_window = Some(window);
-                }
-            }
+    /// Get the instance an evaluation at `timestamp` reads, the one with the smallest
+    /// close time containing the timestamp, with its content
+    pub fn get_window_at(&self, timestamp: i64) -> Option<(&WindowInstance, &QuadContainer)> {
+        let window = self.instance_at(timestamp)?;
+        self.active_windows.get_key_value(window)
+    }
+
+    /// Get the open instances overlapping the half-open range `[from, to)`, sorted by
+    /// open time, with their content
+    pub fn get_windows_overlapping(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Vec<(&WindowInstance, &QuadContainer)> {
+        self.active_windows
+            .range(..WindowInstance::new(to, i64::MIN))
+            .filter(|(window, _)| window.overlaps(from, to))
+            .collect()
+    }
+
+    /// Get the content of an open instance, e.g. the one a report was made for, from
+    /// the `window_bounds` of the report
+    pub fn get_window_content(&self, window: &WindowInstance) -> Option<&QuadContainer> {
+        self.active_windows.get(window)
+    }
+
+    /// List the open instances with the number of quads in memory of each, sorted by
+    /// open time
+    /// Partitioned windows have no instances of their own, see `partition`
+    pub fn list_windows(&self) -> Vec<(WindowInstance, usize)> {
+        self.active_windows
+            .iter()
+            .map(|(window, container)| (window.clone(), container.len()))
+            .collect()
+    }
+
+    /// Find the window instance with the smallest close time containing the timestamp
+    /// This is the first instance opening less than one width before the timestamp
+    fn instance_at(&self, timestamp: i64) -> Option<&WindowInstance> {
+        if self.landmark {
+            let landmark = self.active_windows.keys().next();
+            return landmark.filter(|w| w.contains(timestamp));
         }
+        self.active_windows
+            .range(self.instances_containing(timestamp))
+            .map(|(window, _)| window)
+            .next()
+            .filter(|window| window.contains(timestamp))
+    }
+
+    /// Get the instances containing the timestamp, i.e. opening in `(t - width, t]`
+    fn instances_containing(&self, timestamp: i64) -> std::ops::RangeInclusive<WindowInstance> {
+        let first_open = timestamp.saturating_sub(self.max_width()).saturating_add(1);
+        WindowInstance::new(first_open.min(timestamp), i64::MIN)
+            ..=WindowInstance::new(timestamp, i64::MAX)
+    }
+
+    /// Get the instances closed at the timestamp, i.e. with `close <= t`
+    fn instances_closed_at(&self, timestamp: i64) -> std::ops::RangeTo<WindowInstance> {
+        let first_open = timestamp.saturating_sub(self.width).saturating_add(1);
+        ..WindowInstance::new(first_open, i64::MIN)
+    }
 
-        max_window.and_then(|w| self.active_windows.get(w))
+    /// Get the longest an instance lasts, the width unless a calendar instance spans
+    /// the end of daylight saving time
+    fn max_width(&self) -> i64 {
+        let calendar = self.calendar.as_ref().filter(|_| self.is_tumbling());
+        self.width + calendar.map_or(0, TimeZone::max_shift)
+    }
+
+    /// Add all quads of an event to the window
+    /// Partitioned windows route the whole event by the key found among its quads
+    pub fn add_container(&mut self, container: QuadContainer) {
+        let timestamp = container.last_timestamp_changed;
+        let quads: Vec<Arc<Quad>> = container.elements.into_iter().collect();
+        self.event_received = container.received;
+        self.add_batch(&quads, timestamp);
+        self.event_received = None;
+    }
+
+    /// Add all quads of an event of `stream` to the window, which they keep as their
+    /// stream if the window records streams
+    /// The name is shared with the stream rather than copied into every quad
+    pub fn add_stream_container(&mut self, container: QuadContainer, stream: &Arc<str>) {
+        let timestamp = container.last_timestamp_changed;
+        let quads: Vec<Arc<Quad>> = container.elements.into_iter().collect();
+        self.event_received = container.received;
+        self.add_event(&quads, timestamp, Some(stream));
+        self.event_received = None;
     }
 
     /// Add a quad to the window at the given timestamp
     pub fn add(&mut self, quad: Quad, timestamp: i64) {
+        self.add_batch(std::slice::from_ref(&quad), timestamp);
+    }
+
+    /// Add the quads of one event to the window at the given timestamp
+    /// Windows are scoped and the report condition is checked once for the whole
+    /// batch, instead of once per quad
+    pub fn add_batch<Q: Borrow<Quad>>(&mut self, quads: &[Q], timestamp: i64) {
+        self.add_event(quads, timestamp, None);
+    }
+
+    /// Add the quads of one event, from `stream` if known
+    fn add_event<Q: Borrow<Quad>>(
+        &mut self,
+        quads: &[Q],
+        timestamp: i64,
+        stream: Option<&Arc<str>>,
+    ) {
+        if let Some(filter) = self.quad_filter.clone() {
+            if !quads.iter().all(|quad| filter.matches(quad.borrow())) {
+                let kept: Vec<&Quad> = quads
+                    .iter()
+                    .map(Borrow::borrow)
+                    .filter(|quad| filter.matches(quad))
+                    .collect();
+                self.ingested.2 += (quads.len() - kept.len()) as u64;
+                self.add_event(&kept, timestamp, stream);
+                return;
+            }
+        }
+        self.ingested.0 += 1;
+        self.ingested.1 += quads.len() as u64;
+        self.arrival_rate.record(timestamp, quads.len(), self.width);
+        // Events without quads only advance time, e.g. the watermarks of idle streams
+        if quads.is_empty() {
+            self.advance_to(timestamp);
+            return;
+        }
+        if let Some(partition_key) = &self.partition_key {
+            let key = partition_key.extract(quads.iter().map(Borrow::borrow));
+            self.add_partitioned(key.as_ref(), quads, timestamp, stream);
+            return;
+        }
+
         if self.debug_mode {
-            eprintln!(
-                "[WINDOW {}] Received element ({:?},{}) ",
-                self.name, quad, timestamp
-            );
+            for quad in quads {
+                let quad: &Quad = quad.borrow();
+                debug!(window = %self.name, timestamp, ?quad, "Received element");
+            }
         }
 
-        // Create a new quad with the window's graph name
-        // This ensures the quad's graph matches the SPARQL query's GRAPH clause
-        let quad_with_window_graph = oxigraph::model::Quad::new(
-            quad.subject.clone(),
-            quad.predicate.clone(),
-            quad.object.clone(),
-            oxigraph::model::GraphName::NamedNode(
-                oxigraph::model::NamedNode::new(&self.name).unwrap_or_else(|_| {
-                    // Fallback if window name isn't a valid IRI
-                    oxigraph::model::NamedNode::new("http://default-window").unwrap()
-                }),
-            ),
-        );
+        if !self.accepts(timestamp) {
+            if self.debug_mode {
+                debug!(
+                    window = %self.name,
+                    timestamp,
+                    last_report = self.time,
+                    quads = quads.len(),
+                    "Dropping late element"
+                );
+            }
+            return;
+        }
+
+        // Move the quads to the window's graph
+        // This ensures the quads' graph matches the SPARQL query's GRAPH clause
+        // Shared by every window instance the quads are added to
+        let mut quads_in_window_graph: Vec<Arc<Quad>> = quads
+            .iter()
+            .map(|quad| {
+                let quad: &Quad = quad.borrow();
+                Arc::new(oxigraph::model::Quad::new(
+                    quad.subject.clone(),
+                    quad.predicate.clone(),
+                    quad.object.clone(),
+                    self.graph.clone(),
+                ))
+            })
+            .collect();
+        if let Some(table) = &self.lookup_table {
+            let attributes = table.attributes_of(&quads_in_window_graph, &self.graph);
+            quads_in_window_graph.extend(attributes);
+        }
+
+        // Quads keep their stream if the window records streams, eviction policies
+        // always see it
+        let recorded = stream.filter(|_| self.record_streams);
+        if self.landmark {
+            self.add_landmark(quads_in_window_graph, timestamp, recorded);
+            return;
+        }
+        if self.is_tumbling() {
+            self.add_tumbling(quads, quads_in_window_graph, timestamp, stream);
+            return;
+        }
 
-        let mut to_evict = Vec::new();
         let t_e = timestamp;
 
         if self.time > t_e {
-            eprintln!("OUT OF ORDER NOT HANDLED");
+            warn!(
+                window = %self.name,
+                timestamp = t_e,
+                last_report = self.time,
+                "Out of order element not handled"
+            );
         }
 
         self.scope(t_e);
 
-        // Add element to appropriate windows
-        for (window, container) in &mut self.active_windows {
+        // Add the elements to the windows containing them, found by their open time
+        let containing = self.instances_containing(t_e);
+        if let (Some(plan), Some(panes)) = (&self.aggregation_plan, &mut self.panes) {
+            let instances = self.active_windows.range(containing.clone());
+            let quads = &quads_in_window_graph;
+            panes.insert(plan, self.t0, t_e, quads, instances, self.multiset);
+        }
+        // Panes aggregate the quads once for all the instances
+        let instance_plan = match self.panes {
+            Some(_) => None,
+            None => self.aggregation_plan.as_ref(),
+        };
+        for (window, container) in self.active_windows.range_mut(containing) {
             if self.debug_mode {
-                eprintln!(
-                    "[WINDOW {}] Processing Window [{},{}) for element ({:?},{})",
-                    self.name, window.open, window.close, quad_with_window_graph, timestamp
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    timestamp,
+                    quads = quads_in_window_graph.len(),
+                    "Adding elements to window"
                 );
             }
-
-            if window.open <= t_e && t_e < window.close {
-                if self.debug_mode {
-                    eprintln!(
-                        "[WINDOW {}] Adding element to Window [{},{})",
-                        self.name, window.open, window.close
-                    );
+            for quad in &quads_in_window_graph {
+                let new = !container.contains(quad);
+                // Duplicates don't change a set of quads, so they don't count either
+                if let Some(plan) = instance_plan.filter(|_| new || self.multiset) {
+                    let state = self
+                        .aggregation_states
+                        .entry(window.clone())
+                        .or_insert_with(|| plan.new_state());
+                    plan.insert(state, quad);
                 }
-                container.add(quad_with_window_graph.clone(), timestamp);
-                if self.debug_mode {
-                    eprintln!(
-                        "[WINDOW {}] Window [{},{}) now has {} quads",
-                        self.name,
-                        window.open,
-                        window.close,
-                        container.len()
-                    );
-                }
-            } else if t_e >= window.close {
-                if self.debug_mode {
-                    eprintln!(
-                        "[WINDOW {}] Scheduling for Eviction [{},{})",
-                        self.name, window.open, window.close
-                    );
+                if let Some(spill) = self.spill.as_mut().filter(|_| new) {
+                    spill.record(window, quad);
                 }
-                // Don't add to eviction list yet - windows need to report before being evicted
-                // to_evict.push(window.clone());
+                container.add_from(quad.clone(), timestamp, recorded);
+            }
+            if self.debug_mode {
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    quads = container.len(),
+                    "Element added"
+                );
+            }
+        }
+        if self.debug_mode {
+            // Don't add to eviction list yet - windows need to report before being evicted
+            for window in self
+                .active_windows
+                .range(self.instances_closed_at(t_e))
+                .map(|(w, _)| w)
+            {
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    "Scheduling window for eviction"
+                );
             }
         }
 
+        if !self.eviction_policies.is_empty() {
+            let windows: Vec<WindowInstance> = self
+                .active_windows
+                .range(self.instances_containing(t_e))
+                .map(|(w, _)| w.clone())
+                .collect();
+            self.record_arrivals(&windows, quads, &quads_in_window_graph, t_e, stream);
+            self.apply_eviction_policies(t_e);
+        }
+        self.enforce_memory_limit();
+        self.report_due(timestamp);
+    }
+
+    /// Report and evict the instances due at `timestamp` according to the report
+    /// strategy, once the event at `timestamp` has been added
+    fn report_due(&mut self, timestamp: i64) {
+        let mut to_evict = Vec::new();
+
         // Find the window to report
         if self.debug_mode {
-            eprintln!(
-                "[WINDOW {}] Active windows before reporting check: {}",
-                self.name,
-                self.active_windows.len()
+            debug!(
+                window = %self.name,
+                active_windows = self.active_windows.len(),
+                "Checking which windows report"
             );
         }
 
         let mut max_window: Option<WindowInstance> = None;
         let mut max_time = 0i64;
 
-        for (window, container) in &self.active_windows {
+        // Only closed windows can report on close, other strategies check every window
+        let candidates = match self.report {
+            ReportStrategy::OnWindowClose => {
+                // `close + delay < timestamp`, i.e. closed one ms before the delay
+                let closed = timestamp.saturating_sub(self.delay).saturating_sub(1);
+                self.active_windows.range(self.instances_closed_at(closed))
+            }
+            _ => self.active_windows.range(..),
+        };
+        for (window, container) in candidates {
             if self.compute_report(window, container, timestamp) {
                 if self.debug_mode {
-                    eprintln!(
-                        "[WINDOW {}] Window [{},{}) should report (has {} quads)",
-                        self.name,
-                        window.open,
-                        window.close,
-                        container.len()
+                    debug!(
+                        window = %self.name,
+                        open = window.open,
+                        close = window.close,
+                        quads = container.len(),
+                        "Window should report"
                     );
                 }
                 if window.close > max_time {
@@ -192,48 +532,710 @@ impl CSPARQLWindow {
         // Emit window content if conditions are met
         if let Some(window) = max_window {
             if self.debug_mode {
-                eprintln!(
-                    "[WINDOW {}] Max window selected for reporting: [{},{})",
-                    self.name, window.open, window.close
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    "Latest window selected for reporting"
                 );
             }
-            if self.tick == Tick::TimeDriven {
-                if timestamp > self.time {
-                    self.time = timestamp;
-                    if let Some(content) = self.active_windows.get(&window) {
-                        if self.debug_mode {
-                            eprintln!(
-                                "[WINDOW {}] Emitting {} quads at t={} for window [{},{})",
-                                self.name,
-                                content.len(),
-                                timestamp,
-                                window.open,
-                                window.close
-                            );
-                        }
-                        self.emit(StreamType::RStream, content.clone());
-                    } else {
-                        if self.debug_mode {
-                            eprintln!(
-                                "[WINDOW {}] ERROR: Window [{},{}) not found in active_windows!",
-                                self.name, window.open, window.close
-                            );
-                        }
-                    }
-                }
-            }
+            // When empty instances report, every instance reports so the empty
+            // instance of a gap doesn't hide those before it
+            let reporting = if self.emit_empty_windows {
+                to_evict.clone()
+            } else {
+                vec![window]
+            };
+            self.report_instances(&reporting, timestamp);
         }
 
         // Evict old windows
         for window in to_evict {
+            self.evict(&window);
+        }
+    }
+
+    /// Add an event to the partition of `key`, or to every partition without a key
+    /// (e.g. the sentinel of `close_stream`)
+    fn add_partitioned<Q: Borrow<Quad>>(
+        &mut self,
+        key: Option<&Term>,
+        quads: &[Q],
+        timestamp: i64,
+        stream: Option<&Arc<str>>,
+    ) {
+        // All partitions share the alignment of the first event
+        if self.t0 == 0 && self.origin.is_none() {
+            self.t0 = timestamp;
+        }
+        match key {
+            Some(key) => {
+                let missing = !self.partitions.contains_key(key);
+                if let Some(partition) = missing.then(|| self.new_partition()) {
+                    self.partitions.insert(key.clone(), partition);
+                }
+                let partition = self.partitions.get_mut(key).unwrap();
+                partition.event_received = self.event_received;
+                partition.add_event(quads, timestamp, stream);
+                partition.event_received = None;
+                self.time = self.time.max(partition.time);
+            }
+            None => {
+                for partition in self.partitions.values_mut() {
+                    partition.event_received = self.event_received;
+                    partition.add_event(quads, timestamp, stream);
+                    partition.event_received = None;
+                    self.time = self.time.max(partition.time);
+                }
+            }
+        }
+    }
+
+    /// Create the window of a new partition with the settings and subscribers of this one
+    fn new_partition(&self) -> CSPARQLWindow {
+        let mut partition = CSPARQLWindow::new(
+            self.name.clone(),
+            self.width,
+            self.slide,
+            self.report,
+            self.tick,
+            self.t0,
+        );
+        partition.origin = Some(self.t0);
+        partition.calendar = self.calendar.clone();
+        partition.callbacks = self.callbacks.clone();
+        partition.aggregate_callbacks = self.aggregate_callbacks.clone();
+        partition.lifecycle_callbacks = self.lifecycle_callbacks.clone();
+        partition.eviction_policies = self.eviction_policies.clone();
+        let panes = self.panes.as_ref();
+        partition.panes = panes.map(|_| Panes::new(self.width, self.slide));
+        if let Some(plan) = &self.aggregation_plan {
+            partition.set_aggregation_plan(plan.clone());
+        }
+        partition.set_memory_limit(self.memory_limit());
+        partition.max_lateness = self.max_lateness;
+        partition.emit_empty_windows = self.emit_empty_windows;
+        partition.delay = self.delay;
+        partition.record_streams = self.record_streams;
+        partition.multiset = self.multiset;
+        partition.lookup_table = self.lookup_table.clone();
+        partition.landmark = self.landmark;
+        partition.retention = self.retention;
+        partition.evaluations = self.evaluations.clone();
+        partition.debug_mode = self.debug_mode;
+        partition
+    }
+
+    /// Maintain window state per partition key, so every key reports and evicts
+    /// independently, or keep a single state with `None`
+    /// Existing content is dropped when the partitioning changes
+    pub fn set_partition_key(&mut self, partition_key: Option<PartitionKey>) {
+        if partition_key != self.partition_key {
+            self.active_windows.clear();
+            self.aggregation_states.clear();
+            if let Some(panes) = &mut self.panes {
+                panes.reset(std::iter::empty());
+            }
+            self.partitions.clear();
+        }
+        self.partition_key = partition_key;
+    }
+
+    /// Get the partition key of the window, if partitioned
+    pub fn partition_key(&self) -> Option<&PartitionKey> {
+        self.partition_key.as_ref()
+    }
+
+    /// Get the window of one partition
+    pub fn partition(&self, key: &Term) -> Option<&CSPARQLWindow> {
+        self.partitions.get(key)
+    }
+
+    /// Get the number of partitions seen so far
+    pub fn partition_count(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// Whether the window is tumbling (RANGE equals STEP) and reports on close,
+    /// in which case a single buffer is kept instead of the sliding machinery
+    pub fn is_tumbling(&self) -> bool {
+        self.width == self.slide && self.report == ReportStrategy::OnWindowClose
+    }
+
+    /// Add the elements of an event to a tumbling window
+    /// The buffer reports and is dropped as soon as an element reaches its close plus
+    /// the delay, so only the windows containing the element are ever touched
+    fn add_tumbling<Q: Borrow<Quad>>(
+        &mut self,
+        originals: &[Q],
+        quads: Vec<Arc<Quad>>,
+        t_e: i64,
+        stream: Option<&Arc<str>>,
+    ) {
+        if self.t0 == 0 && self.origin.is_none() {
+            self.t0 = t_e;
+        }
+        let window = self.tumbling_instance(t_e);
+
+        if let Some(oldest) = self.active_windows.keys().next().map(|w| w.open) {
+            if t_e < oldest {
+                if self.debug_mode {
+                    debug!(
+                        window = %self.name,
+                        timestamp = t_e,
+                        open = oldest,
+                        "Dropping element before the current window"
+                    );
+                }
+                return;
+            }
+        }
+
+        if self.emit_empty_windows {
+            self.open_empty_instances(window.open);
+        }
+        self.last_event = Some(self.last_event.map_or(t_e, |last| last.max(t_e)));
+        self.close_tumbling(t_e);
+
+        self.compute_window_if_absent(window.clone());
+        let recorded = stream.filter(|_| self.record_streams);
+        let container = self.active_windows.get_mut(&window).unwrap();
+        for quad in &quads {
+            let new = !container.contains(quad);
+            let plan = self.aggregation_plan.as_ref();
+            if let Some(plan) = plan.filter(|_| new || self.multiset) {
+                let state = self
+                    .aggregation_states
+                    .entry(window.clone())
+                    .or_insert_with(|| plan.new_state());
+                plan.insert(state, quad);
+            }
+            if let Some(spill) = self.spill.as_mut().filter(|_| new) {
+                spill.record(&window, quad);
+            }
+            container.add_from(quad.clone(), t_e, recorded);
+        }
+
+        let windows = std::slice::from_ref(&window);
+        self.record_arrivals(windows, originals, &quads, t_e, stream);
+        self.apply_eviction_policies(t_e);
+        self.enforce_memory_limit();
+    }
+
+    /// Add the elements of an event to a landmark window, after reporting the steps
+    /// the event ends
+    /// Its single instance opens at the first event, or the origin, and never closes
+    fn add_landmark(&mut self, quads: Vec<Arc<Quad>>, t_e: i64, stream: Option<&Arc<str>>) {
+        if self.t0 == 0 && self.origin.is_none() {
+            self.t0 = t_e;
+        }
+        if t_e < self.t0 {
             if self.debug_mode {
-                eprintln!(
-                    "[WINDOW {}] Evicting [{},{})",
-                    self.name, window.open, window.close
+                debug!(
+                    window = %self.name,
+                    timestamp = t_e,
+                    open = self.t0,
+                    "Dropping element before the landmark"
                 );
             }
-            self.active_windows.remove(&window);
+            return;
+        }
+        self.report_landmark(t_e);
+        self.last_event = Some(self.last_event.map_or(t_e, |last| last.max(t_e)));
+
+        let window = WindowInstance::new(self.t0, i64::MAX);
+        self.compute_window_if_absent(window.clone());
+        let container = self.active_windows.get_mut(&window).unwrap();
+        for quad in &quads {
+            let plan = self.aggregation_plan.as_ref();
+            if let Some(plan) = plan.filter(|_| self.multiset || !container.contains(quad)) {
+                let state = self
+                    .aggregation_states
+                    .entry(window.clone())
+                    .or_insert_with(|| plan.new_state());
+                plan.insert(state, quad);
+            }
+            container.add_from(quad.clone(), t_e, stream);
+        }
+    }
+
+    /// Report the content of a landmark window at the latest step boundary reached,
+    /// with the delay, at `timestamp`, then compact it
+    /// The report is stamped with its last step, and bounded by the landmark and the
+    /// boundary
+    fn report_landmark(&mut self, timestamp: i64) {
+        let Some(window) = self.active_windows.keys().next().cloned() else {
+            return;
+        };
+        let reached = timestamp.saturating_sub(self.delay) - self.t0;
+        let boundary = self.t0 + reached.div_euclid(self.slide) * self.slide;
+        if boundary <= self.landmark_reported.unwrap_or(self.t0)
+            || self.tick != Tick::TimeDriven
+            || timestamp <= self.time
+        {
+            return;
+        }
+        self.time = timestamp;
+        self.landmark_reported = Some(boundary);
+        let mut content = self.active_windows[&window].clone();
+        content.last_timestamp_changed = boundary - self.slide;
+        content.window_bounds = Some((window.open, boundary));
+        content.received = self.event_received;
+        let content = Arc::new(content);
+        if self.debug_mode {
+            debug!(
+                window = %self.name,
+                open = window.open,
+                boundary,
+                timestamp,
+                quads = content.len(),
+                "Emitting landmark window content"
+            );
+        }
+        self.emit(StreamType::RStream, content.clone());
+        self.emit_aggregates(&window, &content);
+        self.compact(&window, boundary);
+    }
+
+    /// Drop the raw quads of a landmark window added more than the retention before
+    /// `boundary`, keeping them in its aggregates
+    fn compact(&mut self, window: &WindowInstance, boundary: i64) {
+        let Some(retention) = self.retention else {
+            return;
+        };
+        let Some(container) = self.active_windows.get_mut(window) else {
+            return;
+        };
+        let cutoff = boundary.saturating_sub(retention);
+        let expired: Vec<Arc<Quad>> = container
+            .timestamps
+            .iter()
+            .filter(|(_, timestamp)| **timestamp < cutoff)
+            .map(|(quad, _)| quad.clone())
+            .collect();
+        let last_changed = container.last_timestamp_changed;
+        for quad in &expired {
+            container.remove(quad, last_changed);
+        }
+        if self.debug_mode && !expired.is_empty() {
+            debug!(
+                window = %self.name,
+                cutoff,
+                quads = expired.len(),
+                "Compacted landmark window"
+            );
+        }
+    }
+
+    /// Report and evict the tumbling instances closed, with the delay, at `t_e`
+    /// Tumbling instances don't overlap, so they are sorted by close time too, even
+    /// on a calendar where their widths differ
+    fn close_tumbling(&mut self, t_e: i64) {
+        let reached = t_e.saturating_sub(self.delay);
+        let closed: Vec<WindowInstance> = self
+            .active_windows
+            .keys()
+            .take_while(|w| w.close <= reached)
+            .cloned()
+            .collect();
+        if self.emit_empty_windows {
+            self.report_instances(&closed, t_e);
+        } else if let Some(window) = closed.iter().max_by_key(|w| w.close) {
+            self.report_instances(std::slice::from_ref(window), t_e);
+        }
+        for window in &closed {
+            self.evict(window);
+        }
+    }
+
+    /// Let processing time `now` report and evict the instances it is past, as an
+    /// event at `now` would but without opening the instance containing `now`, so
+    /// windows of idle streams still close
+    /// Nothing happens before the first event, nor until `now` passes the latest event
+    pub fn advance_to(&mut self, now: i64) {
+        if self.partition_key.is_some() {
+            for partition in self.partitions.values_mut() {
+                partition.advance_to(now);
+                self.time = self.time.max(partition.time);
+            }
+            return;
+        }
+        if self.last_event.is_none_or(|last| now <= last) {
+            return;
+        }
+        if self.landmark {
+            self.report_landmark(now);
+        } else if self.is_tumbling() {
+            if self.emit_empty_windows {
+                self.open_empty_instances(self.tumbling_instance(now).open);
+            }
+            self.close_tumbling(now);
+        } else {
+            if self.emit_empty_windows {
+                self.open_empty_instances(self.grid_ceiling(now) - self.slide);
+            }
+            self.report_due(now);
+        }
+    }
+
+    /// Get the time at which every open instance has closed and reported, including
+    /// the delay; `None` without open instances
+    pub fn closing_time(&self) -> Option<i64> {
+        if self.landmark {
+            // The landmark instance never closes, but its current step does
+            if self.active_windows.is_empty() {
+                return None;
+            }
+            let last = self.last_event?;
+            let step = (last - self.t0).div_euclid(self.slide) + 1;
+            return Some(self.t0 + step * self.slide + self.delay);
+        }
+        // Instances are sorted by open time, and all have the same width
+        let own = self
+            .active_windows
+            .keys()
+            .next_back()
+            .map(|window| window.close.saturating_add(self.delay).saturating_add(1));
+        self.partitions
+            .values()
+            .filter_map(CSPARQLWindow::closing_time)
+            .chain(own)
+            .max()
+    }
+
+    /// Get the earliest boundary of the slide grid at or after `timestamp`
+    fn grid_ceiling(&self, timestamp: i64) -> i64 {
+        // Use integer arithmetic to avoid precision loss with large timestamps
+        // This computes ceiling division, also for events before an explicit origin
+        let delta = timestamp - self.t0;
+        let periods = delta.div_euclid(self.slide) + i64::from(delta.rem_euclid(self.slide) != 0);
+        self.t0 + periods * self.slide
+    }
+
+    /// Get the tumbling instance containing `timestamp`, on the calendar if any
+    fn tumbling_instance(&self, timestamp: i64) -> WindowInstance {
+        if let Some(calendar) = &self.calendar {
+            let (open, close) = calendar.instance_at(timestamp, self.width);
+            return WindowInstance::new(open, close);
         }
+        let open = self.t0 + (timestamp - self.t0).div_euclid(self.width) * self.width;
+        WindowInstance::new(open, open + self.width)
+    }
+
+    /// Emit the content of the window instances that should report at `timestamp`,
+    /// in order
+    fn report_instances(&mut self, windows: &[WindowInstance], timestamp: i64) {
+        if self.tick != Tick::TimeDriven || timestamp <= self.time {
+            return;
+        }
+        for window in windows {
+            self.report_instance(window, timestamp);
+        }
+    }
+
+    /// Emit the content of one window instance, unless it already reported
+    fn report_instance(&mut self, window: &WindowInstance, timestamp: i64) {
+        // Each instance reports at most once
+        if let Some((instance, _)) = self.active_windows.get_key_value(window) {
+            if instance.state() != WindowState::Open {
+                if self.debug_mode {
+                    debug!(
+                        window = %self.name,
+                        open = window.open,
+                        close = window.close,
+                        "Window already reported"
+                    );
+                }
+                return;
+            }
+        }
+        self.time = timestamp;
+        if let Some(mut content) = self.instance_content(window) {
+            content.window_bounds = Some((window.open, window.close));
+            content.received = self.event_received;
+            let content = Arc::new(content);
+            if self.debug_mode {
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    timestamp,
+                    quads = content.len(),
+                    "Emitting window content"
+                );
+            }
+            self.emit(StreamType::RStream, content.clone());
+            self.emit_aggregates(window, &content);
+            self.transition(window, WindowState::Reported);
+        } else {
+            error!(
+                window = %self.name,
+                open = window.open,
+                close = window.close,
+                "Reporting window not found among the active windows"
+            );
+        }
+    }
+
+    /// Drop a window instance together with its aggregates and spilled quads
+    fn evict(&mut self, window: &WindowInstance) {
+        if self.debug_mode {
+            debug!(
+                window = %self.name,
+                open = window.open,
+                close = window.close,
+                "Evicting window"
+            );
+        }
+        self.transition(window, WindowState::Evicted);
+        self.aggregation_states.remove(window);
+        if let Some(panes) = &mut self.panes {
+            let first = self.active_windows.keys().next();
+            panes.evict(window, first.map_or(window.open + self.slide, |w| w.open));
+        }
+        self.arrivals.remove(window);
+        if let Some(spill) = &mut self.spill {
+            spill.evict(window);
+        }
+    }
+
+    /// Move an active window instance to a later state of its lifecycle and notify
+    /// the lifecycle subscribers; evicted instances are removed from the active windows
+    fn transition(&mut self, window: &WindowInstance, state: WindowState) {
+        // Keys can't be changed in place, so the instance is taken out and put back
+        let Some((mut instance, container)) = self.active_windows.remove_entry(window) else {
+            return;
+        };
+        if instance.advance(state) {
+            for callback in &self.lifecycle_callbacks {
+                callback(&instance);
+            }
+        }
+        if instance.state() != WindowState::Evicted {
+            self.active_windows.insert(instance, container);
+        }
+    }
+
+    /// Spill the oldest window buffers to disk until they fit the memory limit
+    fn enforce_memory_limit(&mut self) {
+        let Some(spill) = &mut self.spill else {
+            return;
+        };
+        while let Some(window) = spill.next_to_spill() {
+            let Some(container) = self.active_windows.get_mut(&window) else {
+                spill.evict(&window);
+                continue;
+            };
+            if let Err(e) = spill.spill(&window, container) {
+                // Keep the buffers in memory rather than losing quads
+                error!(window = %self.name, "{}", e);
+                return;
+            }
+            if self.debug_mode {
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    "Spilled window to disk"
+                );
+            }
+            // Duplicates of spilled quads can't be detected anymore
+            if let Some(state) = self.aggregation_states.get_mut(&window) {
+                state.mark_fallback();
+            }
+            if let Some(panes) = &mut self.panes {
+                panes.mark_fallback(&window);
+            }
+            // Spilled quads are out of reach of the eviction policies
+            self.arrivals.remove(&window);
+        }
+    }
+
+    /// Add a policy dropping quads from the window instances beyond time-based
+    /// eviction, applied to the quads added from now on
+    pub fn add_eviction_policy(&mut self, policy: impl EvictionPolicy + 'static) {
+        let policy: Arc<dyn EvictionPolicy> = Arc::new(policy);
+        for partition in self.partitions.values_mut() {
+            partition.eviction_policies.push(policy.clone());
+        }
+        self.eviction_policies.push(policy);
+    }
+
+    /// Remember when the quads of an event arrived in the given instances, their
+    /// graph in the stream and the stream, if known, for the eviction policies
+    fn record_arrivals<Q: Borrow<Quad>>(
+        &mut self,
+        windows: &[WindowInstance],
+        originals: &[Q],
+        quads: &[Arc<Quad>],
+        timestamp: i64,
+        stream: Option<&Arc<str>>,
+    ) {
+        if self.eviction_policies.is_empty() {
+            return;
+        }
+        for window in windows {
+            let arrivals = self.arrivals.entry(window.clone()).or_default();
+            for (original, quad) in originals.iter().zip(quads) {
+                let entry = WindowQuad {
+                    quad: quad.clone(),
+                    graph: original.borrow().graph_name.clone(),
+                    timestamp,
+                    stream: stream.cloned(),
+                };
+                arrivals.insert(quad.clone(), entry);
+            }
+        }
+    }
+
+    /// Drop the quads selected by the eviction policies from every active instance
+    fn apply_eviction_policies(&mut self, timestamp: i64) {
+        if self.eviction_policies.is_empty() {
+            return;
+        }
+        for (window, container) in self.active_windows.iter_mut() {
+            let Some(arrivals) = self.arrivals.get_mut(window) else {
+                continue;
+            };
+            let content: Vec<&WindowQuad> = arrivals.values().collect();
+            let dropped: Vec<Arc<Quad>> = self
+                .eviction_policies
+                .iter()
+                .flat_map(|policy| policy.evict(window, &content, timestamp))
+                .collect();
+            if dropped.is_empty() {
+                continue;
+            }
+            for quad in &dropped {
+                if arrivals.remove(quad).is_none() || !container.elements.remove(quad) {
+                    continue;
+                }
+                container.timestamps.remove(quad);
+                if let Some(counts) = &mut container.multiplicities {
+                    counts.remove(quad);
+                }
+                if let Some(spill) = &mut self.spill {
+                    spill.forget(window, quad);
+                }
+                // Incremental aggregates can't retract quads
+                if let Some(state) = self.aggregation_states.get_mut(window) {
+                    state.mark_fallback();
+                }
+                if let Some(panes) = &mut self.panes {
+                    panes.mark_fallback(window);
+                }
+            }
+            if self.debug_mode {
+                debug!(
+                    window = %self.name,
+                    open = window.open,
+                    close = window.close,
+                    dropped = dropped.len(),
+                    quads = container.len(),
+                    "Applied eviction policies"
+                );
+            }
+        }
+    }
+
+    /// Get the full content of a window instance, including quads spilled to disk
+    fn instance_content(&self, window: &WindowInstance) -> Option<QuadContainer> {
+        let container = self.active_windows.get(window)?;
+        match &self.spill {
+            Some(spill) if spill.is_spilled(window) => match spill.rehydrate(window, container) {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    error!(window = %self.name, "{}", e);
+                    Some(container.clone())
+                }
+            },
+            _ => Some(container.clone()),
+        }
+    }
+
+    /// Limit the estimated memory used by the window buffers, spilling the oldest
+    /// windows to disk beyond it, or keep everything in memory with `None`
+    pub fn set_memory_limit(&mut self, max_bytes: Option<usize>) {
+        for partition in self.partitions.values_mut() {
+            partition.set_memory_limit(max_bytes);
+        }
+        self.spill = max_bytes.map(|limit| {
+            let mut spill = WindowSpill::new(limit);
+            for (window, container) in &self.active_windows {
+                for quad in &container.elements {
+                    spill.record(window, quad);
+                }
+            }
+            spill
+        });
+        self.enforce_memory_limit();
+    }
+
+    /// Get the memory limit of the window buffers, if any
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.spill.as_ref().map(WindowSpill::limit)
+    }
+
+    /// Whether an element with this timestamp is within the allowed lateness
+    /// Partitioned windows accept every element, each partition checking its own lateness
+    pub fn accepts(&self, timestamp: i64) -> bool {
+        if self.partition_key.is_some() {
+            return true;
+        }
+        match self.max_lateness {
+            Some(max_lateness) => timestamp >= self.time.saturating_sub(max_lateness),
+            None => true,
+        }
+    }
+
+    /// Drop elements more than `max_lateness` ms older than the last report,
+    /// or keep every element with `None`
+    pub fn set_max_lateness(&mut self, max_lateness: Option<i64>) {
+        for partition in self.partitions.values_mut() {
+            partition.set_max_lateness(max_lateness);
+        }
+        self.max_lateness = max_lateness;
+    }
+
+    /// Get the maximum lateness of elements, if any
+    pub fn max_lateness(&self) -> Option<i64> {
+        self.max_lateness
+    }
+
+    /// Align window boundaries to `origin` instead of the first element,
+    /// so the same events always produce the same windows
+    /// With `None`, windows are aligned to the start time or, if it is 0, the first element
+    pub fn set_origin(&mut self, origin: Option<i64>) {
+        self.origin = origin;
+        if let Some(origin) = origin {
+            self.t0 = origin;
+        }
+    }
+
+    /// Get the explicit alignment origin of the windows, if any
+    pub fn origin(&self) -> Option<i64> {
+        self.origin
+    }
+
+    /// Align the instances of a tumbling window on the calendar of a time zone, e.g.
+    /// with a width of an hour on the hours, or of a day at midnight, in local time,
+    /// or on the origin with `None`
+    ///
+    /// Instances are aligned on the multiples of the width since 1 January 1970 in
+    /// local time, and last from one local boundary to the next: a daily instance
+    /// lasts 23 or 25 hours when daylight saving time starts or ends, and the hour
+    /// repeated when clocks go back is a single instance of two hours. Sliding and
+    /// landmark windows, and windows not reporting on close, ignore the calendar.
+    pub fn set_calendar(&mut self, calendar: Option<TimeZone>) {
+        for partition in self.partitions.values_mut() {
+            partition.set_calendar(calendar.clone());
+        }
+        self.calendar = calendar;
+    }
+
+    /// Get the time zone the instances are aligned on, if any
+    pub fn calendar(&self) -> Option<&TimeZone> {
+        self.calendar.as_ref()
     }
 
     /// Compute whether to report this window based on the report strategy
@@ -244,39 +1246,39 @@ impl CSPARQLWindow {
         timestamp: i64,
     ) -> bool {
         match self.report {
-            ReportStrategy::OnWindowClose => window.close < timestamp,
+            ReportStrategy::OnWindowClose => window.close.saturating_add(self.delay) < timestamp,
             ReportStrategy::NonEmptyContent => !_content.is_empty(),
-            ReportStrategy::OnContentChange => true, // TODO : Tracking content changes needed here but for now always true as a placeholder for future implementation
-            ReportStrategy::Periodic => true, // TODO : Implement periodic reporting logic here as content is always true for now
+            // Not implemented, see `ReportStrategy::is_supported`: windows created
+            // without an engine report every instance
+            ReportStrategy::OnContentChange | ReportStrategy::Periodic => true,
         }
     }
 
     /// Calculate and create windows based on the event time
     pub fn scope(&mut self, t_e: i64) {
-        if self.t0 == 0 {
+        if self.t0 == 0 && self.origin.is_none() {
             self.t0 = t_e;
         }
 
-        // Use integer arithmetic to avoid precision loss with large timestamps
-        // This computes ceiling division: ceil(dividend / divisor) = (dividend + divisor - 1) / divisor
-        let delta = (t_e - self.t0).abs();
-        let c_sup = self.t0 + ((delta + self.slide - 1) / self.slide) * self.slide;
+        let c_sup = self.grid_ceiling(t_e);
         let mut o_i = c_sup - self.width;
 
         if self.debug_mode {
-            eprintln!(
-                "[WINDOW {}] Calculating the Windows to Open. First one opens at [{}] and closes at [{}]",
-                self.name, o_i, c_sup
+            debug!(
+                window = %self.name,
+                open = o_i,
+                close = c_sup,
+                "Calculating the windows to open from the first one"
             );
         }
 
         while o_i <= t_e {
             if self.debug_mode {
-                eprintln!(
-                    "[WINDOW {}] Computing Window [{},{}) if absent",
-                    self.name,
-                    o_i,
-                    o_i + self.width
+                debug!(
+                    window = %self.name,
+                    open = o_i,
+                    close = o_i + self.width,
+                    "Computing window if absent"
                 );
             }
 
@@ -284,26 +1286,291 @@ impl CSPARQLWindow {
             self.compute_window_if_absent(window);
             o_i += self.slide;
         }
+
+        if self.emit_empty_windows {
+            self.open_empty_instances(c_sup - self.slide);
+        }
+        self.last_event = Some(self.last_event.map_or(t_e, |last| last.max(t_e)));
+    }
+
+    /// Open the instances up to the one closing at `latest_close` in which no event
+    /// fell, i.e. opening after the latest event, so they report even though empty
+    /// The instances are stamped with their open time, as they have no last event
+    fn open_empty_instances(&mut self, latest_close: i64) {
+        let Some(last) = self.last_event else {
+            return;
+        };
+        if self.calendar.is_some() && self.is_tumbling() {
+            // Calendar instances differ in width, so each one opens at the previous close
+            let mut window = self.tumbling_instance(last);
+            loop {
+                let next = self.tumbling_instance(window.close);
+                if next.close > latest_close || next.open <= window.open {
+                    return;
+                }
+                window = next;
+                self.open_empty_instance(window.clone());
+            }
+        }
+        let latest_open = latest_close - self.width;
+        if latest_open <= last {
+            return;
+        }
+        let mut open = latest_open - (latest_open - last - 1).div_euclid(self.slide) * self.slide;
+        while open <= latest_open {
+            self.open_empty_instance(WindowInstance::new(open, open + self.width));
+            open += self.slide;
+        }
+    }
+
+    /// Open an instance without events, stamped with its open time
+    fn open_empty_instance(&mut self, window: WindowInstance) {
+        if self.debug_mode {
+            debug!(
+                window = %self.name,
+                open = window.open,
+                close = window.close,
+                "Opening empty window"
+            );
+        }
+        self.compute_window_if_absent(window.clone());
+        if let Some(container) = self.active_windows.get_mut(&window) {
+            container.last_timestamp_changed = window.open;
+        }
+    }
+
+    /// Report window instances in which no event occurred, e.g. for `COUNT = 0` alerts
+    /// or queries detecting missing events with `FILTER NOT EXISTS`, or only those
+    /// with events
+    ///
+    /// Instances are otherwise only opened by the events they contain: when an event
+    /// follows a gap, every instance that closed within the gap is opened empty, and
+    /// every instance closed by the event reports rather than only the latest.
+    /// Instances before the first event are never opened. The report strategy still
+    /// applies, so `NonEmptyContent` windows never report empty instances.
+    pub fn set_emit_empty_windows(&mut self, enabled: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_emit_empty_windows(enabled);
+        }
+        self.emit_empty_windows = enabled;
+    }
+
+    /// Whether instances without any event report too
+    pub fn emits_empty_windows(&self) -> bool {
+        self.emit_empty_windows
+    }
+
+    /// Defer the report of closed instances by `delay` ms of event time, so events
+    /// arriving late but within the delay are still part of their instance
+    pub fn set_delay(&mut self, delay: i64) {
+        for partition in self.partitions.values_mut() {
+            partition.set_delay(delay);
+        }
+        self.delay = delay.max(0);
+    }
+
+    /// Get the delay of the reports of closed instances, in ms
+    pub fn delay(&self) -> i64 {
+        self.delay
+    }
+
+    /// Make the window a landmark window, i.e. `RANGE UNBOUNDED`: a single instance
+    /// opening at the first event (or the origin) that never closes, and reports
+    /// every `slide` ms of event time
+    ///
+    /// The memory limit and eviction policies don't apply to landmark windows; a
+    /// retention bounds their content instead, see `set_retention`
+    pub fn set_landmark(&mut self, landmark: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_landmark(landmark);
+        }
+        self.landmark = landmark;
+    }
+
+    /// Whether the window is a landmark window
+    pub fn is_landmark(&self) -> bool {
+        self.landmark
+    }
+
+    /// Maintain the incremental aggregates of a sliding window per pane, the slices
+    /// of `gcd(width, slide)` ms its instances are made of, and combine the panes of
+    /// an instance when it reports, instead of aggregating every quad in each of the
+    /// `width / slide` instances containing it; a win for a long range with a short
+    /// step
+    /// Tumbling and landmark windows keep a single state per instance anyway;
+    /// instances already open when panes are enabled are evaluated with SPARQL
+    pub fn set_pane_aggregation(&mut self, enabled: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_pane_aggregation(enabled);
+        }
+        if enabled == self.panes.is_some() {
+            return;
+        }
+        self.panes = enabled.then(|| {
+            let mut panes = Panes::new(self.width, self.slide);
+            panes.reset(self.active_windows.keys());
+            panes
+        });
+    }
+
+    /// Whether the incremental aggregates are maintained per pane
+    pub fn pane_aggregation(&self) -> bool {
+        self.panes.is_some()
+    }
+
+    /// Whether the instances are aggregated from panes, only sliding windows are
+    fn uses_panes(&self) -> bool {
+        self.panes.is_some() && !self.landmark && !self.is_tumbling()
+    }
+
+    /// Drop the raw quads of a landmark window `retention` ms of event time after
+    /// their event, at its reports, or keep them all with `None`
+    ///
+    /// Incrementally maintained aggregates keep counting dropped quads, so they
+    /// still cover everything since the landmark; SPARQL evaluation only sees the
+    /// retained quads. A quad repeated after being dropped counts again.
+    pub fn set_retention(&mut self, retention: Option<i64>) {
+        for partition in self.partitions.values_mut() {
+            partition.set_retention(retention);
+        }
+        self.retention = retention.map(|retention| retention.max(0));
+    }
+
+    /// Get the retention of the raw quads of a landmark window, if any
+    pub fn retention(&self) -> Option<i64> {
+        self.retention
+    }
+
+    /// Keep the stream of the event adding each quad, see `QuadContainer::stream`,
+    /// for the events added with `add_stream_container`
+    pub fn set_record_streams(&mut self, enabled: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_record_streams(enabled);
+        }
+        self.record_streams = enabled;
+    }
+
+    /// Whether the quads keep the stream of their event
+    pub fn records_streams(&self) -> bool {
+        self.record_streams
+    }
+
+    /// Keep every event adding a quad, counting identical observations at different
+    /// times within an instance instead of collapsing them into one quad, so
+    /// `COUNT` and `AVG` see each of them; a set of quads otherwise
+    /// Instances already open count their quads once from then on, or drop their
+    /// counts, with their incremental aggregates evaluated with SPARQL
+    pub fn set_multiset(&mut self, enabled: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_multiset(enabled);
+        }
+        if enabled == self.multiset {
+            return;
+        }
+        self.multiset = enabled;
+        for (window, container) in self.active_windows.iter_mut() {
+            if enabled {
+                container.count_duplicates();
+                continue;
+            }
+            container.multiplicities = None;
+            if let Some(state) = self.aggregation_states.get_mut(window) {
+                state.mark_fallback();
+            }
+            if let Some(panes) = &mut self.panes {
+                panes.mark_fallback(window);
+            }
+        }
+    }
+
+    /// Whether the instances count the events adding each quad
+    pub fn multiset(&self) -> bool {
+        self.multiset
+    }
+
+    /// Only store the quads of events passing a filter, discarding the others before
+    /// they reach any instance or partition; `None` stores every quad
+    pub fn set_quad_filter(&mut self, filter: Option<QuadFilter>) {
+        self.quad_filter = filter;
+    }
+
+    /// Get the filter of the quads of events, if any
+    pub fn quad_filter(&self) -> Option<&QuadFilter> {
+        self.quad_filter.as_ref()
+    }
+
+    /// Attach the static attributes of the subjects of events from a lookup table,
+    /// shared with the partitions created from then on
+    pub(crate) fn set_lookup_table(&mut self, table: Option<Arc<LookupTable>>) {
+        self.lookup_table = table;
+    }
+
+    /// Get the runtime statistics of the window, over all its partitions
+    pub fn stats(&self) -> WindowStats {
+        let (events, quads, filtered_quads) = self.ingested;
+        let mut stats = WindowStats {
+            events,
+            quads,
+            filtered_quads,
+            arrival_rate: self.arrival_rate.per_second(self.width),
+            ..WindowStats::default()
+        };
+        let mut subjects = HashSet::new();
+        let partitions = self.partitions.values();
+        let partition_instances = partitions.flat_map(|partition| &partition.active_windows);
+        for (window, container) in self.active_windows.iter().chain(partition_instances) {
+            stats
+                .quads_per_window
+                .push((window.open, window.close, container.len()));
+            subjects.extend(container.elements.iter().map(|quad| &quad.subject));
+        }
+        stats.quads_per_window.sort_unstable();
+        stats.distinct_subjects = subjects.len();
+        self.evaluations.read_into(&mut stats);
+        stats
+    }
+
+    /// Get the recorder of the evaluations of the window's reports
+    pub(crate) fn evaluation_recorder(&self) -> Arc<EvaluationRecorder> {
+        self.evaluations.clone()
     }
 
     /// Add window if it doesn't already exist
     fn compute_window_if_absent(&mut self, key: WindowInstance) {
-        self.active_windows
-            .entry(key)
-            .or_insert_with(|| QuadContainer::new(HashSet::new(), 0));
+        if self.active_windows.contains_key(&key) {
+            return;
+        }
+        for callback in &self.lifecycle_callbacks {
+            callback(&key);
+        }
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+        if self.multiset {
+            container.count_duplicates();
+        }
+        self.active_windows.insert(key, container);
     }
 
     /// Subscribe a callback to window emissions
     pub fn subscribe<F>(&mut self, stream_type: StreamType, callback: F)
     where
-        F: Fn(QuadContainer) + Send + Sync + 'static,
+        F: Fn(Arc<QuadContainer>) + Send + Sync + 'static,
     {
-        let callbacks = self.callbacks.entry(stream_type).or_insert_with(Vec::new);
-        callbacks.push(Arc::new(callback));
+        let callback: WindowCallback = Arc::new(callback);
+        for partition in self.partitions.values_mut() {
+            partition
+                .callbacks
+                .entry(stream_type)
+                .or_default()
+                .push(callback.clone());
+        }
+        self.callbacks
+            .entry(stream_type)
+            .or_default()
+            .push(callback);
     }
 
     /// Emit window content to subscribers
-    fn emit(&self, stream_type: StreamType, content: QuadContainer) {
+    fn emit(&self, stream_type: StreamType, content: Arc<QuadContainer>) {
         if let Some(callbacks) = self.callbacks.get(&stream_type) {
             for callback in callbacks {
                 callback(content.clone());
@@ -311,6 +1578,112 @@ impl CSPARQLWindow {
         }
     }
 
+    /// Maintain the aggregates of the given plan incrementally as quads are added
+    pub fn set_aggregation_plan(&mut self, plan: AggregationPlan) {
+        for partition in self.partitions.values_mut() {
+            partition.set_aggregation_plan(plan.clone());
+        }
+        // Seed the states of the windows that already have content; panes can't
+        // be told apart in existing content, those instances are left to SPARQL
+        self.aggregation_states.clear();
+        if self.uses_panes() {
+            if let Some(panes) = &mut self.panes {
+                panes.reset(self.active_windows.keys());
+            }
+            self.aggregation_plan = Some(plan);
+            return;
+        }
+        for (window, container) in &self.active_windows {
+            let mut state = plan.new_state();
+            for quad in &container.elements {
+                plan.insert(&mut state, quad);
+            }
+            self.aggregation_states.insert(window.clone(), state);
+        }
+        self.aggregation_plan = Some(plan);
+    }
+
+    /// Stop maintaining aggregates, e.g. after the query changed, so aggregate
+    /// subscribers get reports without solutions and evaluate them with SPARQL
+    pub fn clear_aggregation_plan(&mut self) {
+        for partition in self.partitions.values_mut() {
+            partition.clear_aggregation_plan();
+        }
+        self.aggregation_states.clear();
+        self.aggregation_plan = None;
+    }
+
+    /// Get the incremental aggregation plan of this window, if any
+    pub fn aggregation_plan(&self) -> Option<&AggregationPlan> {
+        self.aggregation_plan.as_ref()
+    }
+
+    /// Subscribe a callback to window emissions carrying the incremental aggregates
+    /// Only called for windows with an aggregation plan
+    pub fn subscribe_aggregates<F>(&mut self, callback: F)
+    where
+        F: Fn(AggregateReport) + Send + Sync + 'static,
+    {
+        let callback: AggregateCallback = Arc::new(callback);
+        for partition in self.partitions.values_mut() {
+            partition.aggregate_callbacks.push(callback.clone());
+        }
+        self.aggregate_callbacks.push(callback);
+    }
+
+    /// Subscribe a callback to the lifecycle transitions of the window instances:
+    /// called when an instance opens, reports and is evicted
+    pub fn subscribe_lifecycle<F>(&mut self, callback: F)
+    where
+        F: Fn(&WindowInstance) + Send + Sync + 'static,
+    {
+        let callback: LifecycleCallback = Arc::new(callback);
+        for partition in self.partitions.values_mut() {
+            partition.lifecycle_callbacks.push(callback.clone());
+        }
+        self.lifecycle_callbacks.push(callback);
+    }
+
+    /// Emit the aggregates of a window instance to subscribers
+    fn emit_aggregates(&self, window: &WindowInstance, content: &Arc<QuadContainer>) {
+        if self.aggregate_callbacks.is_empty() {
+            return;
+        }
+        let solutions = self.aggregation_plan.as_ref().and_then(|plan| {
+            if let Some(panes) = self.panes.as_ref().filter(|_| self.uses_panes()) {
+                return plan.solutions(&panes.state(plan, window));
+            }
+            let empty = plan.new_state();
+            let state = self.aggregation_states.get(window).unwrap_or(&empty);
+            plan.solutions(state)
+        });
+        for callback in &self.aggregate_callbacks {
+            callback(AggregateReport {
+                content: content.clone(),
+                solutions: solutions.clone(),
+            });
+        }
+    }
+
+    /// Get the full content of the window at a specific timestamp
+    /// Unlike `get_content`, this includes quads spilled to disk, and the content
+    /// of every partition of partitioned windows
+    pub fn get_full_content(&self, timestamp: i64) -> Option<QuadContainer> {
+        if self.partition_key.is_some() {
+            return self
+                .partitions
+                .values()
+                .filter_map(|partition| partition.get_full_content(timestamp))
+                .reduce(|mut merged, content| {
+                    merged.merge(content);
+                    merged.last_timestamp_changed = timestamp;
+                    merged
+                });
+        }
+        self.instance_at(timestamp)
+            .and_then(|w| self.instance_content(w))
+    }
+
     /// Get content from window at specific timestamp (alternative method name for compatibility)
     pub fn get_content_from_window(&self, timestamp: i64) -> Option<&QuadContainer> {
         self.get_content(timestamp)
@@ -319,18 +1692,33 @@ impl CSPARQLWindow {
     /// Get the current number of active windows
     pub fn get_active_window_count(&self) -> usize {
         self.active_windows.len()
+            + self
+                .partitions
+                .values()
+                .map(CSPARQLWindow::get_active_window_count)
+                .sum::<usize>()
     }
 
     /// Get the timestamp range of active windows
     pub fn get_active_window_ranges(&self) -> Vec<(i64, i64)> {
         self.active_windows
-            .iter()
-            .map(|(window, _)| (window.open, window.close))
+            .keys()
+            .map(|window| (window.open, window.close))
+            .chain(
+                self.partitions
+                    .values()
+                    .flat_map(CSPARQLWindow::get_active_window_ranges),
+            )
             .collect()
     }
 
     /// Enable or disable debug mode for verbose logging
+    /// The events are emitted with `tracing` at DEBUG level, so a subscriber has to
+    /// enable that level for this module to see them
     pub fn set_debug_mode(&mut self, enabled: bool) {
+        for partition in self.partitions.values_mut() {
+            partition.set_debug_mode(enabled);
+        }
         self.debug_mode = enabled;
     }
 }
@@ -342,7 +1730,7 @@ pub fn execute_query<'a>(
     query: &str,
 ) -> Result<QueryResults<'a>, Box<dyn std::error::Error>> {
     let store = Store::new()?;
-    for quad in &container.elements {
+    for quad in container.iter() {
         store.insert(quad)?;
     }
     use oxigraph::sparql::SparqlEvaluator;
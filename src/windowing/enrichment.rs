@@ -0,0 +1,150 @@
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Quad, Term};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Static attributes attached to the subjects of the quads of a window as they
+/// arrive, e.g. the room and building of a sensor, so simple enrichment queries
+/// don't join the window with the static data on every report
+///
+/// The engine indexes the static quads with the selected predicates by subject
+/// when it is initialized, and again whenever the static data changes. Every
+/// event then carries, in the window graph, the attributes of its subjects found
+/// in the static data, whatever their graph there.
+///
+/// ```rust,no_run
+/// use oxigraph::model::NamedNode;
+/// use rsp_rs::{RSPEngineBuilder, StaticLookup};
+///
+/// let room = NamedNode::new("http://example.org/room").unwrap();
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .enrich_window("http://example.org/w1", StaticLookup::predicates([room]))
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticLookup {
+    /// Predicates of the attributes, every predicate if `None`
+    predicates: Option<Vec<NamedNode>>,
+}
+
+impl StaticLookup {
+    /// Attach the static attributes with one of these predicates
+    pub fn predicates(predicates: impl IntoIterator<Item = NamedNode>) -> Self {
+        Self {
+            predicates: Some(predicates.into_iter().collect()),
+        }
+    }
+
+    /// Attach every static quad about the subjects
+    pub fn all() -> Self {
+        Self { predicates: None }
+    }
+}
+
+/// Hash table of the attributes of a `StaticLookup` by subject, shared by a window
+/// and its partitions, and rebuilt by the engine when its static data changes
+pub(crate) struct LookupTable {
+    predicates: Option<HashSet<NamedNode>>,
+    attributes: RwLock<HashMap<NamedOrBlankNode, Vec<(NamedNode, Term)>>>,
+}
+
+impl LookupTable {
+    pub(crate) fn new(lookup: &StaticLookup, static_data: &[Quad]) -> Self {
+        let table = Self {
+            predicates: lookup
+                .predicates
+                .as_ref()
+                .map(|predicates| predicates.iter().cloned().collect()),
+            attributes: RwLock::new(HashMap::new()),
+        };
+        table.rebuild(static_data);
+        table
+    }
+
+    /// Index the attributes of the subjects of the static data again
+    pub(crate) fn rebuild(&self, static_data: &[Quad]) {
+        let mut attributes: HashMap<NamedOrBlankNode, Vec<(NamedNode, Term)>> = HashMap::new();
+        let selected = static_data.iter().filter(|quad| {
+            let predicates = self.predicates.as_ref();
+            predicates.is_none_or(|predicates| predicates.contains(&quad.predicate))
+        });
+        for quad in selected {
+            let attribute = (quad.predicate.clone(), quad.object.clone());
+            let subject = attributes.entry(quad.subject.clone()).or_default();
+            if !subject.contains(&attribute) {
+                subject.push(attribute);
+            }
+        }
+        *self.attributes.write().unwrap() = attributes;
+    }
+
+    /// Get the attributes of the subjects of these quads as quads in `graph`, once
+    /// per subject
+    pub(crate) fn attributes_of(&self, quads: &[Arc<Quad>], graph: &GraphName) -> Vec<Arc<Quad>> {
+        let attributes = self.attributes.read().unwrap();
+        let mut seen = HashSet::new();
+        let mut enriched = Vec::new();
+        for quad in quads {
+            if !seen.insert(&quad.subject) {
+                continue;
+            }
+            let Some(found) = attributes.get(&quad.subject) else {
+                continue;
+            };
+            enriched.extend(found.iter().map(|(predicate, object)| {
+                Arc::new(Quad::new(
+                    quad.subject.clone(),
+                    predicate.clone(),
+                    object.clone(),
+                    graph.clone(),
+                ))
+            }));
+        }
+        enriched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::Literal;
+
+    #[test]
+    fn test_attributes_of_event_subjects() {
+        let node = |name: &str| NamedNode::new_unchecked(format!("http://example.org/{}", name));
+        let graph = GraphName::from(node("w1"));
+        let fact = |s: &str, p: &str, o: &str| {
+            Quad::new(node(s), node(p), node(o), GraphName::DefaultGraph)
+        };
+        let static_data = vec![
+            fact("sensor1", "room", "kitchen"),
+            fact("sensor1", "owner", "alice"),
+            fact("kitchen", "floor", "ground"),
+        ];
+        let table = LookupTable::new(&StaticLookup::predicates([node("room")]), &static_data);
+
+        let reading = |s: &str, value: i64| {
+            Arc::new(Quad::new(
+                node(s),
+                node("value"),
+                Literal::from(value),
+                graph.clone(),
+            ))
+        };
+        let event = [
+            reading("sensor1", 20),
+            reading("sensor1", 21),
+            reading("sensor2", 5),
+        ];
+        let expected = Arc::new(Quad::new(
+            node("sensor1"),
+            node("room"),
+            node("kitchen"),
+            graph.clone(),
+        ));
+        assert_eq!(table.attributes_of(&event, &graph), vec![expected]);
+
+        table.rebuild(&[fact("sensor2", "room", "hall")]);
+        assert!(table.attributes_of(&event[..1], &graph).is_empty());
+        assert_eq!(table.attributes_of(&event[2..], &graph).len(), 1);
+    }
+}
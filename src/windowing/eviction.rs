@@ -0,0 +1,359 @@
+use crate::WindowInstance;
+use oxigraph::model::{GraphName, NamedNode, NamedOrBlankNode, Quad};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A quad of a window instance, as seen by eviction policies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowQuad {
+    /// The quad, moved to the window's graph
+    pub quad: Arc<Quad>,
+    /// Graph of the quad in its stream
+    pub graph: GraphName,
+    /// Timestamp of the last event adding the quad to the instance
+    pub timestamp: i64,
+    /// Stream of that event, if added through a stream
+    pub stream: Option<Arc<str>>,
+}
+
+/// Drops quads from window instances beyond time-based eviction, e.g. to keep only
+/// the latest reading of each sensor of a high-cardinality stream
+///
+/// Policies are applied to every active window instance whenever the windows are
+/// scoped for an event, once its quads are inserted and before any window reports.
+/// They only see the quads added after they were set, and not those spilled to disk.
+pub trait EvictionPolicy: Send + Sync {
+    /// Select the quads to drop from a window instance, given its content at `timestamp`
+    fn evict(
+        &self,
+        window: &WindowInstance,
+        content: &[&WindowQuad],
+        timestamp: i64,
+    ) -> Vec<Arc<Quad>>;
+}
+
+/// Keep at most this many quads per window instance, dropping the oldest
+/// Quads of the same event are dropped in no particular order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxQuads(pub usize);
+
+impl EvictionPolicy for MaxQuads {
+    fn evict(
+        &self,
+        _window: &WindowInstance,
+        content: &[&WindowQuad],
+        _timestamp: i64,
+    ) -> Vec<Arc<Quad>> {
+        if content.len() <= self.0 {
+            return Vec::new();
+        }
+        let excess = content.len() - self.0;
+        let mut by_age = content.to_vec();
+        by_age.sort_by_key(|entry| entry.timestamp);
+        by_age[..excess]
+            .iter()
+            .map(|entry| entry.quad.clone())
+            .collect()
+    }
+}
+
+/// Keep only the quads of the latest event about each subject, e.g. the latest
+/// reading of each sensor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatestPerSubject {
+    predicate: Option<NamedNode>,
+}
+
+impl LatestPerSubject {
+    /// Consider the quads of every predicate
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only consider the quads with this predicate, other quads are kept
+    pub fn with_predicate(mut self, predicate: NamedNode) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    fn matches(&self, quad: &Quad) -> bool {
+        self.predicate
+            .as_ref()
+            .is_none_or(|predicate| quad.predicate == *predicate)
+    }
+}
+
+impl EvictionPolicy for LatestPerSubject {
+    fn evict(
+        &self,
+        _window: &WindowInstance,
+        content: &[&WindowQuad],
+        _timestamp: i64,
+    ) -> Vec<Arc<Quad>> {
+        let mut latest: HashMap<&NamedOrBlankNode, i64> = HashMap::new();
+        for entry in content.iter().filter(|entry| self.matches(&entry.quad)) {
+            let time = latest.entry(&entry.quad.subject).or_insert(entry.timestamp);
+            *time = (*time).max(entry.timestamp);
+        }
+        content
+            .iter()
+            .filter(|entry| self.matches(&entry.quad))
+            .filter(|entry| entry.timestamp < latest[&entry.quad.subject])
+            .map(|entry| entry.quad.clone())
+            .collect()
+    }
+}
+
+/// Keep only the most recent quad of each subject and predicate, i.e. the current
+/// state of each sensor property; quads of the same event are all kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatestValue;
+
+impl EvictionPolicy for LatestValue {
+    fn evict(
+        &self,
+        _window: &WindowInstance,
+        content: &[&WindowQuad],
+        _timestamp: i64,
+    ) -> Vec<Arc<Quad>> {
+        let key = |entry: &WindowQuad| (entry.quad.subject.clone(), entry.quad.predicate.clone());
+        let mut latest: HashMap<(NamedOrBlankNode, NamedNode), i64> = HashMap::new();
+        for entry in content {
+            let time = latest.entry(key(entry)).or_insert(entry.timestamp);
+            *time = (*time).max(entry.timestamp);
+        }
+        content
+            .iter()
+            .filter(|entry| entry.timestamp < latest[&key(entry)])
+            .map(|entry| entry.quad.clone())
+            .collect()
+    }
+}
+
+/// Drop quads a given time (in ms) after they arrived, depending on their graph
+/// in the stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphTtl {
+    ttls: HashMap<GraphName, i64>,
+    default_ttl: Option<i64>,
+}
+
+impl GraphTtl {
+    /// Keep every quad until its window is evicted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the quads of `graph` `ttl` ms after they arrived
+    pub fn with_ttl(mut self, graph: impl Into<GraphName>, ttl: i64) -> Self {
+        self.ttls.insert(graph.into(), ttl);
+        self
+    }
+
+    /// Drop the quads of graphs without a TTL of their own `ttl` ms after they arrived
+    pub fn with_default_ttl(mut self, ttl: i64) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+}
+
+impl EvictionPolicy for GraphTtl {
+    fn evict(
+        &self,
+        _window: &WindowInstance,
+        content: &[&WindowQuad],
+        timestamp: i64,
+    ) -> Vec<Arc<Quad>> {
+        content
+            .iter()
+            .filter(|entry| {
+                let ttl = self.ttls.get(&entry.graph).copied().or(self.default_ttl);
+                ttl.is_some_and(|ttl| timestamp - entry.timestamp > ttl)
+            })
+            .map(|entry| entry.quad.clone())
+            .collect()
+    }
+}
+
+/// Drop quads a given time (in ms) after they arrived, depending on their predicate
+/// or the stream of their event, so fast-changing observations expire from an
+/// instance before it closes while slower context quads stay for its whole range
+///
+/// The TTL of a predicate applies over that of a stream. A TTL longer than the
+/// range of the window has no effect.
+///
+/// ```rust,no_run
+/// use oxigraph::model::NamedNode;
+/// use rsp_rs::{QuadTtl, RSPEngineBuilder};
+///
+/// let value = NamedNode::new("http://example.org/value").unwrap();
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .window_ttl("http://example.org/w1", QuadTtl::new().with_predicate_ttl(value, 500))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuadTtl {
+    predicates: HashMap<NamedNode, i64>,
+    streams: HashMap<String, i64>,
+    default_ttl: Option<i64>,
+}
+
+impl QuadTtl {
+    /// Keep every quad until its window is evicted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop the quads with `predicate` `ttl` ms after they arrived
+    pub fn with_predicate_ttl(mut self, predicate: NamedNode, ttl: i64) -> Self {
+        self.predicates.insert(predicate, ttl);
+        self
+    }
+
+    /// Drop the quads of the events of a stream, identified by its IRI, `ttl` ms
+    /// after they arrived
+    pub fn with_stream_ttl(mut self, stream: &str, ttl: i64) -> Self {
+        self.streams.insert(stream.to_string(), ttl);
+        self
+    }
+
+    /// Drop the other quads `ttl` ms after they arrived
+    pub fn with_default_ttl(mut self, ttl: i64) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Get the TTL of a quad of a window, if any
+    fn ttl(&self, entry: &WindowQuad) -> Option<i64> {
+        let stream = entry.stream.as_ref();
+        self.predicates
+            .get(&entry.quad.predicate)
+            .or_else(|| stream.and_then(|stream| self.streams.get(&**stream)))
+            .copied()
+            .or(self.default_ttl)
+    }
+}
+
+impl EvictionPolicy for QuadTtl {
+    fn evict(
+        &self,
+        _window: &WindowInstance,
+        content: &[&WindowQuad],
+        timestamp: i64,
+    ) -> Vec<Arc<Quad>> {
+        content
+            .iter()
+            .filter(|entry| {
+                let ttl = self.ttl(entry);
+                ttl.is_some_and(|ttl| timestamp - entry.timestamp > ttl)
+            })
+            .map(|entry| entry.quad.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::Literal;
+
+    fn entry(subject: &str, predicate: &str, graph: GraphName, timestamp: i64) -> WindowQuad {
+        WindowQuad {
+            quad: Arc::new(Quad::new(
+                NamedNode::new(format!("http://example.org/{}", subject)).unwrap(),
+                NamedNode::new(format!("http://example.org/{}", predicate)).unwrap(),
+                Literal::from(timestamp),
+                NamedNode::new("http://example.org/w").unwrap(),
+            )),
+            graph,
+            timestamp,
+            stream: None,
+        }
+    }
+
+    fn evicted(policy: &dyn EvictionPolicy, content: &[WindowQuad], timestamp: i64) -> Vec<i64> {
+        let content: Vec<&WindowQuad> = content.iter().collect();
+        let dropped = policy.evict(&WindowInstance::new(0, 1000), &content, timestamp);
+        let mut times: Vec<i64> = content
+            .iter()
+            .filter(|entry| dropped.contains(&entry.quad))
+            .map(|entry| entry.timestamp)
+            .collect();
+        times.sort();
+        times
+    }
+
+    #[test]
+    fn test_max_quads() {
+        let content: Vec<_> = (0..5)
+            .map(|i| entry("s", "value", GraphName::DefaultGraph, i * 100))
+            .collect();
+        assert_eq!(evicted(&MaxQuads(3), &content, 400), vec![0, 100]);
+        assert!(evicted(&MaxQuads(5), &content, 400).is_empty());
+    }
+
+    #[test]
+    fn test_latest_per_subject() {
+        let content = vec![
+            entry("sensor1", "value", GraphName::DefaultGraph, 100),
+            entry("sensor2", "value", GraphName::DefaultGraph, 200),
+            entry("sensor1", "value", GraphName::DefaultGraph, 300),
+            entry("sensor1", "label", GraphName::DefaultGraph, 50),
+        ];
+        assert_eq!(
+            evicted(&LatestPerSubject::new(), &content, 300),
+            vec![50, 100]
+        );
+        let values = LatestPerSubject::new()
+            .with_predicate(NamedNode::new("http://example.org/value").unwrap());
+        assert_eq!(evicted(&values, &content, 300), vec![100]);
+    }
+
+    #[test]
+    fn test_latest_value() {
+        let content = vec![
+            entry("sensor1", "value", GraphName::DefaultGraph, 100),
+            entry("sensor1", "value", GraphName::DefaultGraph, 300),
+            entry("sensor1", "label", GraphName::DefaultGraph, 50),
+            entry("sensor2", "value", GraphName::DefaultGraph, 200),
+            entry("sensor2", "value", GraphName::DefaultGraph, 200),
+        ];
+        assert_eq!(evicted(&LatestValue, &content, 300), vec![100]);
+    }
+
+    #[test]
+    fn test_graph_ttl() {
+        let fast = NamedNode::new("http://example.org/fast").unwrap();
+        let content = vec![
+            entry("s", "value", fast.clone().into(), 100),
+            entry("s", "value", fast.clone().into(), 450),
+            entry("s", "value", GraphName::DefaultGraph, 0),
+        ];
+        let policy = GraphTtl::new().with_ttl(fast.clone(), 300);
+        assert_eq!(evicted(&policy, &content, 500), vec![100]);
+        let policy = GraphTtl::new().with_ttl(fast, 300).with_default_ttl(400);
+        assert_eq!(evicted(&policy, &content, 500), vec![0, 100]);
+    }
+
+    #[test]
+    fn test_quad_ttl() {
+        let stream: Arc<str> = Arc::from("http://example.org/context");
+        let mut content = vec![
+            entry("sensor1", "value", GraphName::DefaultGraph, 100),
+            entry("sensor1", "value", GraphName::DefaultGraph, 450),
+            entry("sensor1", "room", GraphName::DefaultGraph, 0),
+            entry("room1", "floor", GraphName::DefaultGraph, 50),
+        ];
+        content[3].stream = Some(stream.clone());
+        let value = NamedNode::new("http://example.org/value").unwrap();
+        let policy = QuadTtl::new().with_predicate_ttl(value.clone(), 300);
+        assert_eq!(evicted(&policy, &content, 500), vec![100]);
+
+        // Predicates apply over streams, and streams over the default
+        let policy = QuadTtl::new()
+            .with_predicate_ttl(value, 300)
+            .with_stream_ttl(&stream, 1000)
+            .with_default_ttl(400);
+        assert_eq!(evicted(&policy, &content, 500), vec![0, 100]);
+    }
+}
@@ -1,2 +1,11 @@
+pub mod calendar;
+pub mod clock;
 pub mod csparql_window;
+pub mod enrichment;
+pub mod eviction;
+pub(crate) mod panes;
+pub mod partition;
+pub mod quad_filter;
+pub mod spill;
 pub mod window_instance;
+pub mod window_stats;
@@ -0,0 +1,182 @@
+use crate::{AggregationPlan, AggregationState, QuadContainer, WindowInstance};
+use oxigraph::model::Quad;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Aggregates of a sliding window kept per pane, the slices of `gcd(width, slide)`
+/// ms its instances are made of, and combined when an instance reports, instead of
+/// aggregating every quad in each of the `width / slide` instances containing it
+///
+/// Window content is a set, so a quad repeated in another pane of an instance
+/// would be counted twice: the instances it is new to are evaluated with SPARQL
+/// instead, like those with spilled or evicted quads. Multiset windows count
+/// every event, whatever the pane.
+#[derive(Debug, Clone)]
+pub(crate) struct Panes {
+    size: i64,
+    /// Aggregates of the quads of each pane, by open time
+    states: BTreeMap<i64, AggregationState>,
+    /// Instances whose quads aren't all aggregated in their panes
+    fallback: HashSet<WindowInstance>,
+}
+
+impl Panes {
+    pub(crate) fn new(width: i64, slide: i64) -> Self {
+        let (mut a, mut b) = (width.max(1), slide.max(1));
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        Self {
+            size: a,
+            states: BTreeMap::new(),
+            fallback: HashSet::new(),
+        }
+    }
+
+    /// Aggregate the quads of an event at `timestamp` in its pane, given the window
+    /// instances containing it before the event is added to them, and whether they
+    /// count duplicates
+    /// Panes are aligned on `t0`, like the instances
+    pub(crate) fn insert<'a>(
+        &mut self,
+        plan: &AggregationPlan,
+        t0: i64,
+        timestamp: i64,
+        quads: &[Arc<Quad>],
+        instances: impl Iterator<Item = (&'a WindowInstance, &'a QuadContainer)>,
+        multiset: bool,
+    ) {
+        let instances: Vec<_> = instances.collect();
+        if instances.is_empty() {
+            return;
+        }
+        let pane = timestamp - (timestamp - t0).rem_euclid(self.size);
+        let mut seen = HashSet::new();
+        for quad in quads {
+            if !seen.insert(quad) {
+                continue;
+            }
+            let new_to = instances
+                .iter()
+                .filter(|(_, container)| multiset || !container.contains(quad));
+            let new_to: Vec<&WindowInstance> = new_to.map(|(window, _)| *window).collect();
+            if new_to.len() == instances.len() {
+                let state = self.states.entry(pane).or_insert_with(|| plan.new_state());
+                plan.insert(state, quad);
+            } else {
+                self.fallback.extend(new_to.into_iter().cloned());
+            }
+        }
+    }
+
+    /// Evaluate an instance with SPARQL, e.g. after part of it was spilled to disk
+    pub(crate) fn mark_fallback(&mut self, window: &WindowInstance) {
+        self.fallback.insert(window.clone());
+    }
+
+    /// Combine the aggregates of the panes of an instance
+    pub(crate) fn state(
+        &self,
+        plan: &AggregationPlan,
+        window: &WindowInstance,
+    ) -> AggregationState {
+        let mut state = plan.new_state();
+        if self.fallback.contains(window) {
+            state.mark_fallback();
+            return state;
+        }
+        for (_, pane) in self.states.range(window.open..window.close) {
+            state.merge(pane);
+        }
+        state
+    }
+
+    /// Forget an evicted instance, and the panes before `first_open`, the open time
+    /// of the earliest instance still active
+    pub(crate) fn evict(&mut self, window: &WindowInstance, first_open: i64) {
+        self.fallback.remove(window);
+        self.states = self.states.split_off(&first_open);
+    }
+
+    /// Drop every pane, leaving the instances already open to SPARQL, e.g. after the
+    /// aggregation plan changed
+    pub(crate) fn reset<'a>(&mut self, open: impl Iterator<Item = &'a WindowInstance>) {
+        self.states.clear();
+        self.fallback = open.cloned().collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AggregationPlan, CSPARQLWindow, ReportStrategy, Tick};
+    use oxigraph::model::{GraphName, Literal, NamedNode, Quad};
+    use std::sync::{Arc, Mutex};
+
+    /// Solutions of every report of a window, as sorted text
+    fn reports(panes: bool, events: &[(&str, i64, i64)]) -> Vec<Option<Vec<String>>> {
+        let query = "PREFIX ex: <http://example.org/>
+            SELECT ?sensor (COUNT(?v) AS ?n) (AVG(?v) AS ?avg) (MAX(?v) AS ?max)
+            WHERE { GRAPH ex:w1 { ?sensor ex:value ?v } } GROUP BY ?sensor";
+        let mut window = CSPARQLWindow::new(
+            "http://example.org/w1".to_string(),
+            1000,
+            200,
+            ReportStrategy::OnWindowClose,
+            Tick::TimeDriven,
+            0,
+        );
+        window.set_origin(Some(0));
+        window.set_pane_aggregation(panes);
+        window.set_aggregation_plan(AggregationPlan::detect(query).unwrap());
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let sink = collected.clone();
+        window.subscribe_aggregates(move |report| {
+            let solutions = report.solutions.map(|solutions| {
+                let mut rows: Vec<String> = solutions
+                    .iter()
+                    .map(|solution| format!("{:?}", solution.iter().collect::<Vec<_>>()))
+                    .collect();
+                rows.sort();
+                rows
+            });
+            sink.lock().unwrap().push(solutions);
+        });
+        for &(sensor, value, timestamp) in events {
+            let quad = Quad::new(
+                NamedNode::new_unchecked(format!("http://example.org/{}", sensor)),
+                NamedNode::new_unchecked("http://example.org/value"),
+                Literal::from(value),
+                GraphName::DefaultGraph,
+            );
+            window.add(quad, timestamp);
+        }
+        collected.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_panes_match_instance_aggregates() {
+        let events: Vec<(&str, i64, i64)> = (0..60)
+            .map(|i| (["s1", "s2", "s3"][i as usize % 3], i % 7, i * 50))
+            .collect();
+        let expected = reports(false, &events);
+        assert!(expected.len() > 10);
+        assert!(expected.iter().all(Option::is_some));
+        assert_eq!(reports(true, &events), expected);
+    }
+
+    #[test]
+    fn test_repeated_quads_fall_back_to_sparql() {
+        // The reading at 300 repeats the one at 100, except in the instance [200, 1200)
+        let events = [
+            ("s1", 5, 100),
+            ("s1", 5, 300),
+            ("s1", 6, 500),
+            ("s1", 1, 1300),
+        ];
+        let with_panes = reports(true, &events);
+        let without = reports(false, &events);
+        assert_eq!(with_panes[..2], without[..2]);
+        assert!(without[2].is_some());
+        assert_eq!(with_panes[2], None);
+    }
+}
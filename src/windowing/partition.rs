@@ -0,0 +1,210 @@
+use oxigraph::model::{NamedNode, Quad, Term, Variable};
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+
+/// Where the partition key of an event is read from, e.g. the sensor of an observation
+///
+/// The key is the subject or object of the first quad of the event with the key
+/// predicate. Events without such a quad have no key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionKey {
+    /// Predicate of the quads carrying the key; `None` matches any predicate
+    predicate: Option<NamedNode>,
+    /// Whether the key is the subject of those quads, or else their object
+    subject: bool,
+}
+
+impl PartitionKey {
+    /// Partition by the subject of quads with the given predicate, or of any quad
+    pub fn subject_of(predicate: Option<NamedNode>) -> Self {
+        Self {
+            predicate,
+            subject: true,
+        }
+    }
+
+    /// Partition by the object of quads with the given predicate, or of any quad
+    pub fn object_of(predicate: Option<NamedNode>) -> Self {
+        Self {
+            predicate,
+            subject: false,
+        }
+    }
+
+    /// Resolve `PARTITION BY ?variable` from the triple patterns of a window in a query
+    /// Returns `None` if the variable isn't the subject or object of a pattern in the window
+    pub fn from_query(query: &str, window: &str, variable: &str) -> Option<Self> {
+        let query = SparqlParser::new().parse_query(query).ok()?;
+        let (Query::Select { pattern, .. }
+        | Query::Construct { pattern, .. }
+        | Query::Describe { pattern, .. }
+        | Query::Ask { pattern, .. }) = query;
+        let variable = Variable::new(variable).ok()?;
+
+        let mut blocks = Vec::new();
+        Self::window_triples(&pattern, window, false, &mut blocks);
+
+        let is_variable =
+            |term: &TermPattern| matches!(term, TermPattern::Variable(v) if *v == variable);
+        if let Some(predicate) = Self::key_predicate(&blocks, |t| is_variable(&t.subject)) {
+            return Some(Self::subject_of(predicate));
+        }
+        Self::key_predicate(&blocks, |t| is_variable(&t.object)).map(Self::object_of)
+    }
+
+    /// Find the predicate of the key in the first matching triple of each `GRAPH` block
+    /// of the window; `Some(None)` for any predicate when the blocks disagree, as the
+    /// events matching one block may not have the predicate of another
+    fn key_predicate(
+        blocks: &[Vec<TriplePattern>],
+        matches: impl Fn(&TriplePattern) -> bool,
+    ) -> Option<Option<NamedNode>> {
+        let mut predicates = blocks
+            .iter()
+            .filter_map(|block| block.iter().find(|triple| matches(triple)))
+            .map(|triple| match &triple.predicate {
+                NamedNodePattern::NamedNode(predicate) => Some(predicate.clone()),
+                NamedNodePattern::Variable(_) => None,
+            });
+        let first = predicates.next()?;
+        Some(if predicates.all(|predicate| predicate == first) {
+            first
+        } else {
+            None
+        })
+    }
+
+    /// Collect the triple patterns inside each `GRAPH` clause of a window, as a query
+    /// can match several patterns of one window in separate clauses
+    fn window_triples(
+        pattern: &GraphPattern,
+        window: &str,
+        in_window: bool,
+        blocks: &mut Vec<Vec<TriplePattern>>,
+    ) {
+        match pattern {
+            GraphPattern::Bgp { patterns } if in_window => {
+                if let Some(block) = blocks.last_mut() {
+                    block.extend(patterns.iter().cloned());
+                }
+            }
+            GraphPattern::Graph { name, inner } => {
+                let is_window =
+                    matches!(name, NamedNodePattern::NamedNode(n) if n.as_str() == window);
+                if is_window {
+                    blocks.push(Vec::new());
+                }
+                Self::window_triples(inner, window, is_window, blocks);
+            }
+            GraphPattern::Join { left, right }
+            | GraphPattern::LeftJoin { left, right, .. }
+            | GraphPattern::Union { left, right }
+            | GraphPattern::Minus { left, right } => {
+                Self::window_triples(left, window, in_window, blocks);
+                Self::window_triples(right, window, in_window, blocks);
+            }
+            GraphPattern::Filter { inner, .. }
+            | GraphPattern::Extend { inner, .. }
+            | GraphPattern::OrderBy { inner, .. }
+            | GraphPattern::Project { inner, .. }
+            | GraphPattern::Distinct { inner }
+            | GraphPattern::Reduced { inner }
+            | GraphPattern::Slice { inner, .. }
+            | GraphPattern::Group { inner, .. } => {
+                Self::window_triples(inner, window, in_window, blocks);
+            }
+            _ => {}
+        }
+    }
+
+    /// Find the key of an event
+    pub fn extract<'a>(&self, quads: impl IntoIterator<Item = &'a Quad>) -> Option<Term> {
+        let quad = quads.into_iter().find(|quad| match &self.predicate {
+            Some(predicate) => quad.predicate == *predicate,
+            None => true,
+        })?;
+        Some(if self.subject {
+            quad.subject.clone().into()
+        } else {
+            quad.object.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{GraphName, Literal};
+
+    const QUERY: &str = r#"
+        PREFIX ex: <http://example.org/>
+        SELECT ?sensor (AVG(?value) AS ?avg)
+        WHERE {
+            GRAPH ex:w1 { ?obs ex:madeBySensor ?sensor . ?obs ex:value ?value }
+            ?sensor ex:room ?room .
+        }
+        GROUP BY ?sensor
+    "#;
+
+    fn observation(sensor: &str) -> Vec<Quad> {
+        let obs = NamedNode::new("http://example.org/obs1").unwrap();
+        vec![
+            Quad::new(
+                obs.clone(),
+                NamedNode::new("http://example.org/value").unwrap(),
+                Literal::from(20),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(
+                obs,
+                NamedNode::new("http://example.org/madeBySensor").unwrap(),
+                NamedNode::new(format!("http://example.org/{}", sensor)).unwrap(),
+                GraphName::DefaultGraph,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_key_from_query() {
+        let key = PartitionKey::from_query(QUERY, "http://example.org/w1", "sensor").unwrap();
+        assert_eq!(
+            key,
+            PartitionKey::object_of(Some(
+                NamedNode::new("http://example.org/madeBySensor").unwrap()
+            ))
+        );
+        assert_eq!(
+            key.extract(&observation("s1")),
+            Some(NamedNode::new("http://example.org/s1").unwrap().into())
+        );
+
+        let key = PartitionKey::from_query(QUERY, "http://example.org/w1", "obs").unwrap();
+        assert_eq!(
+            key.extract(&observation("s1")).unwrap().to_string(),
+            "<http://example.org/obs1>"
+        );
+
+        // ?room is only bound outside the window
+        assert!(PartitionKey::from_query(QUERY, "http://example.org/w1", "room").is_none());
+    }
+
+    #[test]
+    fn test_key_from_repeated_window() {
+        let query = r#"
+            PREFIX ex: <http://example.org/>
+            SELECT * WHERE {
+                GRAPH ex:w1 { ?sensor ex:temperature ?t }
+                GRAPH ex:w1 { ?sensor ex:humidity ?h }
+                GRAPH ex:w1 { ?sensor ex:temperature ?t2 }
+            }
+        "#;
+        // Readings of either pattern carry the key
+        let key = PartitionKey::from_query(query, "http://example.org/w1", "sensor").unwrap();
+        assert_eq!(key, PartitionKey::subject_of(None));
+
+        let key = PartitionKey::from_query(query, "http://example.org/w1", "t").unwrap();
+        let temperature = NamedNode::new("http://example.org/temperature").unwrap();
+        assert_eq!(key, PartitionKey::object_of(Some(temperature)));
+    }
+}
@@ -0,0 +1,154 @@
+use oxigraph::model::{GraphName, NamedNode, Quad, Term};
+use spargebra::algebra::GraphPattern;
+use spargebra::term::{NamedNodePattern, TermPattern, TriplePattern};
+use spargebra::{Query, SparqlParser};
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// Cheap condition on the quads of events, discarding the others at ingestion
+/// before they are stored in every active window instance, e.g. the quads of a
+/// shared stream that the `?s ex:temperature ?t` pattern of the query never matches
+///
+/// Events whose quads are all discarded still advance the time of their window.
+///
+/// ```rust,no_run
+/// use oxigraph::model::NamedNode;
+/// use rsp_rs::{QuadFilter, RSPEngineBuilder};
+///
+/// let temperature = NamedNode::new("http://example.org/temperature").unwrap();
+/// let engine = RSPEngineBuilder::new("...".to_string())
+///     .filter_window("http://example.org/w1", QuadFilter::predicates([temperature]))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct QuadFilter(Arc<dyn Fn(&Quad) -> bool + Send + Sync>);
+
+impl QuadFilter {
+    /// Create a filter keeping the quads for which `predicate` holds
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&Quad) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(predicate))
+    }
+
+    /// Create a filter keeping the quads with one of these predicates
+    pub fn predicates(predicates: impl IntoIterator<Item = NamedNode>) -> Self {
+        let predicates: HashSet<NamedNode> = predicates.into_iter().collect();
+        Self::new(move |quad| predicates.contains(&quad.predicate))
+    }
+
+    /// Create a filter keeping the quads in one of these named graphs of their stream
+    pub fn graphs(graphs: impl IntoIterator<Item = NamedNode>) -> Self {
+        let graphs: HashSet<GraphName> = graphs.into_iter().map(GraphName::from).collect();
+        Self::new(move |quad| graphs.contains(&quad.graph_name))
+    }
+
+    /// Create a filter keeping the quads matching one of the triple patterns of a
+    /// basic graph pattern, e.g. `?s <http://example.org/temperature> ?t`
+    /// Variables and blank nodes match any term
+    pub fn pattern(pattern: &str) -> Result<Self, String> {
+        let query = SparqlParser::new()
+            .parse_query(&format!("ASK {{ {} }}", pattern))
+            .map_err(|e| format!("Invalid quad filter pattern: {}", e))?;
+        let Query::Ask {
+            pattern: GraphPattern::Bgp { patterns },
+            ..
+        } = query
+        else {
+            return Err(format!(
+                "Quad filter pattern {} isn't a basic graph pattern",
+                pattern
+            ));
+        };
+        Ok(Self::new(move |quad| {
+            patterns
+                .iter()
+                .any(|pattern| Self::matches_pattern(pattern, quad))
+        }))
+    }
+
+    /// Check whether a quad passes the filter
+    pub fn matches(&self, quad: &Quad) -> bool {
+        (self.0)(quad)
+    }
+
+    fn matches_pattern(pattern: &TriplePattern, quad: &Quad) -> bool {
+        let matches_term = |pattern: &TermPattern, term: Term| match pattern {
+            TermPattern::NamedNode(node) => Term::from(node.clone()) == term,
+            TermPattern::Literal(literal) => Term::from(literal.clone()) == term,
+            _ => true,
+        };
+        let predicate = match &pattern.predicate {
+            NamedNodePattern::NamedNode(predicate) => *predicate == quad.predicate,
+            NamedNodePattern::Variable(_) => true,
+        };
+        predicate
+            && matches_term(&pattern.subject, quad.subject.clone().into())
+            && matches_term(&pattern.object, quad.object.clone())
+    }
+}
+
+impl fmt::Debug for QuadFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("QuadFilter")
+    }
+}
+
+/// Filters are compared by identity
+impl PartialEq for QuadFilter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for QuadFilter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::Literal;
+
+    fn quad(predicate: &str, object: impl Into<Term>) -> Quad {
+        Quad::new(
+            NamedNode::new("http://example.org/sensor1").unwrap(),
+            NamedNode::new(format!("http://example.org/{}", predicate)).unwrap(),
+            object,
+            GraphName::DefaultGraph,
+        )
+    }
+
+    #[test]
+    fn test_quad_filters() {
+        let temperature = quad("temperature", Literal::from(20));
+        let humidity = quad("humidity", Literal::from(50));
+
+        let predicates =
+            QuadFilter::predicates([NamedNode::new("http://example.org/temperature").unwrap()]);
+        assert!(predicates.matches(&temperature));
+        assert!(!predicates.matches(&humidity));
+
+        let graph = NamedNode::new("http://example.org/building1").unwrap();
+        let graphs = QuadFilter::graphs([graph.clone()]);
+        assert!(!graphs.matches(&temperature));
+        let mut in_graph = temperature.clone();
+        in_graph.graph_name = graph.into();
+        assert!(graphs.matches(&in_graph));
+
+        let pattern = QuadFilter::pattern(
+            "?s <http://example.org/temperature> ?t . ?s <http://example.org/humidity> 50",
+        )
+        .unwrap();
+        assert!(pattern.matches(&temperature));
+        assert!(pattern.matches(&humidity));
+        assert!(!pattern.matches(&quad("humidity", Literal::from(60))));
+        assert!(QuadFilter::pattern("?s ?p ?o FILTER(?o > 1)").is_err());
+        assert!(QuadFilter::pattern("?s ?p").is_err());
+
+        let closure = QuadFilter::new(|quad| quad.object.is_literal());
+        assert!(closure.matches(&temperature));
+        assert_eq!(closure, closure.clone());
+        assert_ne!(closure, QuadFilter::new(|quad| quad.object.is_literal()));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{QuadContainer, WindowInstance};
+use oxigraph::io::{RdfFormat, RdfParser, RdfSerializer};
+use oxigraph::model::{NamedOrBlankNode, Quad, Term};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes the spill directories of windows sharing a process
+static SPILL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Rough number of bytes a quad takes in a window buffer
+pub fn estimated_size(quad: &Quad) -> usize {
+    let subject = match &quad.subject {
+        NamedOrBlankNode::NamedNode(node) => node.as_str().len(),
+        NamedOrBlankNode::BlankNode(node) => node.as_str().len(),
+    };
+    let object = match &quad.object {
+        Term::NamedNode(node) => node.as_str().len(),
+        Term::BlankNode(node) => node.as_str().len(),
+        Term::Literal(literal) => {
+            literal.value().len()
+                + literal.language().map_or(0, str::len)
+                + literal.datatype().as_str().len()
+        }
+        // RDF 1.2 triple terms
+        #[allow(unreachable_patterns)]
+        _ => quad.object.to_string().len(),
+    };
+    std::mem::size_of::<Quad>() + subject + quad.predicate.as_str().len() + object
+}
+
+/// Moves window buffers to N-Quads files once they exceed a memory limit
+///
+/// Spilled windows keep receiving quads in memory; the file content is merged
+/// back in when the window reports.
+pub struct WindowSpill {
+    limit: usize,
+    directory: PathBuf,
+    buffered: HashMap<WindowInstance, usize>,
+    spilled: HashMap<WindowInstance, PathBuf>,
+}
+
+impl WindowSpill {
+    /// Create a spill area for one window, limited to `limit` bytes in memory
+    pub fn new(limit: usize) -> Self {
+        let directory = std::env::temp_dir().join(format!(
+            "rsp-rs-spill-{}-{}",
+            std::process::id(),
+            SPILL_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self {
+            limit,
+            directory,
+            buffered: HashMap::new(),
+            spilled: HashMap::new(),
+        }
+    }
+
+    /// Get the memory limit in bytes
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Get the estimated number of bytes buffered in memory
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered.values().sum()
+    }
+
+    /// Record a quad newly added to the buffer of a window instance
+    pub fn record(&mut self, window: &WindowInstance, quad: &Quad) {
+        *self.buffered.entry(window.clone()).or_default() += estimated_size(quad);
+    }
+
+    /// Record a quad removed from the in-memory buffer of a window instance
+    pub fn forget(&mut self, window: &WindowInstance, quad: &Quad) {
+        if let Some(bytes) = self.buffered.get_mut(window) {
+            *bytes = bytes.saturating_sub(estimated_size(quad));
+        }
+    }
+
+    /// Pick the oldest window instance still holding quads in memory,
+    /// if the buffers exceed the limit
+    pub fn next_to_spill(&self) -> Option<WindowInstance> {
+        if self.buffered_bytes() <= self.limit {
+            return None;
+        }
+        self.buffered
+            .iter()
+            .filter(|(_, bytes)| **bytes > 0)
+            .map(|(window, _)| window)
+            .min_by_key(|window| window.open)
+            .cloned()
+    }
+
+    /// Append the buffer of a window instance to its spill file and clear it
+    pub fn spill(
+        &mut self,
+        window: &WindowInstance,
+        container: &mut QuadContainer,
+    ) -> Result<(), String> {
+        fs::create_dir_all(&self.directory)
+            .map_err(|e| format!("Failed to create spill directory: {}", e))?;
+        let path = self
+            .directory
+            .join(format!("{}_{}.nq", window.open, window.close));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open spill file: {}", e))?;
+
+        let mut serializer =
+            RdfSerializer::from_format(RdfFormat::NQuads).for_writer(BufWriter::new(file));
+        // Quads added by several events are repeated, for containers counting them
+        for quad in container.iter() {
+            for _ in 0..container.multiplicity(quad) {
+                serializer
+                    .serialize_quad(quad)
+                    .map_err(|e| format!("Failed to spill quad: {}", e))?;
+            }
+        }
+        serializer
+            .finish()
+            .map_err(|e| format!("Failed to spill quads: {}", e))?;
+
+        container.elements.clear();
+        container.timestamps.clear();
+        if let Some(counts) = &mut container.multiplicities {
+            counts.clear();
+        }
+        self.buffered.insert(window.clone(), 0);
+        self.spilled.insert(window.clone(), path);
+        Ok(())
+    }
+
+    /// Whether part of the window instance's content lives on disk
+    pub fn is_spilled(&self, window: &WindowInstance) -> bool {
+        self.spilled.contains_key(window)
+    }
+
+    /// Get the full content of a window instance, reading back its spilled quads
+    /// without their timestamps
+    pub fn rehydrate(
+        &self,
+        window: &WindowInstance,
+        container: &QuadContainer,
+    ) -> Result<QuadContainer, String> {
+        let mut content = container.clone();
+        let Some(path) = self.spilled.get(window) else {
+            return Ok(content);
+        };
+        let file = File::open(path).map_err(|e| format!("Failed to open spill file: {}", e))?;
+        for quad in RdfParser::from_format(RdfFormat::NQuads).for_reader(BufReader::new(file)) {
+            let quad = Arc::new(quad.map_err(|e| format!("Failed to read spilled quad: {}", e))?);
+            if let Some(counts) = &mut content.multiplicities {
+                *counts.entry(quad.clone()).or_default() += 1;
+            }
+            content.elements.insert(quad);
+        }
+        Ok(content)
+    }
+
+    /// Forget an evicted window instance, deleting its spill file
+    pub fn evict(&mut self, window: &WindowInstance) {
+        self.buffered.remove(window);
+        if let Some(path) = self.spilled.remove(window) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for WindowSpill {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode};
+    use std::collections::HashSet;
+
+    fn quad(i: i64) -> Quad {
+        Quad::new(
+            BlankNode::new(format!("b{}", i)).unwrap(),
+            NamedNode::new("http://example.org/value").unwrap(),
+            Literal::from(i),
+            GraphName::NamedNode(NamedNode::new("http://example.org/w1").unwrap()),
+        )
+    }
+
+    #[test]
+    fn test_spill_and_rehydrate() {
+        let window = WindowInstance::new(0, 10);
+        let mut spill = WindowSpill::new(estimated_size(&quad(0)));
+        let mut container = QuadContainer::new(HashSet::new(), 0);
+
+        for i in 0..3 {
+            container.add(quad(i), i);
+            spill.record(&window, &quad(i));
+        }
+        assert_eq!(spill.next_to_spill(), Some(window.clone()));
+        spill.spill(&window, &mut container).unwrap();
+        assert!(container.is_empty());
+        assert!(spill.is_spilled(&window));
+        assert_eq!(spill.next_to_spill(), None);
+
+        // Quads added after spilling stay in memory until the report
+        container.add(quad(3), 3);
+        let content = spill.rehydrate(&window, &container).unwrap();
+        assert_eq!(content.len(), 4);
+        assert!(content.contains(&quad(0)));
+        assert_eq!(content.last_timestamp_changed, 3);
+
+        spill.evict(&window);
+        assert!(!spill.is_spilled(&window));
+    }
+}
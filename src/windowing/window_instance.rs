@@ -1,12 +1,24 @@
+/// Lifecycle of a window instance: `Open` → `Reported` → `Evicted`
+/// Instances closing without reporting go straight from `Open` to `Evicted`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WindowState {
+    /// Collecting elements
+    Open,
+    /// Content emitted to the subscribers, which happens at most once
+    Reported,
+    /// Dropped from the active windows
+    Evicted,
+}
+
 // Representing the instance of a Window.
 #[derive(Debug, Clone)]
 pub struct WindowInstance {
     pub open: i64,
     pub close: i64,
-    pub has_triggered_and_emitted: bool,
+    state: WindowState,
 }
 
-// Implement PartialEq and Eq based only on open and close
+// Implement PartialEq and Eq based only on open and close, whatever the state
 impl PartialEq for WindowInstance {
     fn eq(&self, other: &Self) -> bool {
         self.open == other.open && self.close == other.close
@@ -15,6 +27,19 @@ impl PartialEq for WindowInstance {
 
 impl Eq for WindowInstance {}
 
+// Order by open then close, so instances of a window are sorted by time
+impl Ord for WindowInstance {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.open, self.close).cmp(&(other.open, other.close))
+    }
+}
+
+impl PartialOrd for WindowInstance {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 // Implement Hash based only on open and close
 impl std::hash::Hash for WindowInstance {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
@@ -28,15 +53,78 @@ impl WindowInstance {
         Self {
             open,
             close,
-            has_triggered_and_emitted: false,
+            state: WindowState::Open,
+        }
+    }
+
+    /// Get the lifecycle state of the instance
+    pub fn state(&self) -> WindowState {
+        self.state
+    }
+
+    /// Move the instance to a later state of its lifecycle
+    /// Returns false, leaving the state unchanged, if `state` isn't after the current one
+    pub(crate) fn advance(&mut self, state: WindowState) -> bool {
+        if state <= self.state {
+            return false;
         }
+        self.state = state;
+        true
     }
 
+    #[deprecated(note = "use `state`, instances are marked as reported by their window")]
+    pub fn has_triggered_and_emitted(&self) -> bool {
+        self.state != WindowState::Open
+    }
+
+    #[deprecated(note = "use `state`, instances are marked as reported by their window")]
     pub fn set_triggered_and_emitted(&mut self, val: bool) {
-        self.has_triggered_and_emitted = val;
+        self.state = if val {
+            WindowState::Reported
+        } else {
+            WindowState::Open
+        };
     }
 
     pub fn is_same_window(&self, other: &WindowInstance) -> bool {
         self.open == other.open && self.close == other.close
     }
+
+    /// Whether the timestamp falls in the instance, the half-open interval
+    /// `[open, close)`: an event at `close` belongs to the next instance
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.open <= timestamp && timestamp < self.close
+    }
+
+    /// Whether the instance overlaps the half-open range `[from, to)`
+    pub fn overlaps(&self, from: i64, to: i64) -> bool {
+        self.open < to && from < self.close
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_only_moves_forward() {
+        let mut window = WindowInstance::new(0, 10);
+        assert_eq!(window.state(), WindowState::Open);
+        assert!(window.advance(WindowState::Reported));
+        assert!(!window.advance(WindowState::Reported));
+        assert!(!window.advance(WindowState::Open));
+        assert!(window.advance(WindowState::Evicted));
+        assert_eq!(window.state(), WindowState::Evicted);
+        // The state doesn't take part in identity
+        assert_eq!(window, WindowInstance::new(0, 10));
+    }
+
+    #[test]
+    fn test_instances_are_half_open() {
+        let window = WindowInstance::new(0, 10);
+        assert!(window.contains(0) && window.contains(9));
+        assert!(!window.contains(10) && !window.contains(-1));
+        assert!(window.overlaps(9, 20) && window.overlaps(-5, 1));
+        assert!(!window.overlaps(10, 20) && !window.overlaps(-5, 0));
+    }
 }
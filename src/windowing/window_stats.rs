@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Runtime statistics of a window, from `CSPARQLWindow::stats`, for tuning its
+/// step, lateness or filter while it runs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowStats {
+    /// Events added to the window, including those dropped as late
+    pub events: u64,
+    /// Quads of the events passing the filter of the window
+    pub quads: u64,
+    /// Quads discarded by the filter of the window
+    pub filtered_quads: u64,
+    /// Quads held in memory by each open window instance, as its open and close
+    /// times and its number of quads, sorted by open time
+    pub quads_per_window: Vec<(i64, i64, usize)>,
+    /// Distinct subjects over the open window instances
+    pub distinct_subjects: usize,
+    /// Quads arriving per second of event time, over the last window width
+    pub arrival_rate: f64,
+    /// Reports of the window evaluated with SPARQL
+    pub evaluations: u64,
+    /// Mean duration of the evaluations of the reports of the window
+    pub mean_evaluation_duration: Duration,
+    /// Duration of the last evaluation of a report of the window
+    pub last_evaluation_duration: Duration,
+}
+
+/// Quads arriving within the last window width of event time, for the arrival rate
+#[derive(Debug, Default)]
+pub(crate) struct ArrivalRate {
+    /// Timestamp and number of quads of the recent events, by arrival
+    events: VecDeque<(i64, usize)>,
+    quads: usize,
+    latest: Option<i64>,
+}
+
+impl ArrivalRate {
+    /// Record the quads of an event, forgetting the events, by arrival, a `width`
+    /// older than the latest one
+    pub(crate) fn record(&mut self, timestamp: i64, quads: usize, width: i64) {
+        let latest = self
+            .latest
+            .map_or(timestamp, |latest| latest.max(timestamp));
+        self.latest = Some(latest);
+        self.events.push_back((timestamp, quads));
+        self.quads += quads;
+        let start = latest.saturating_sub(width);
+        while let Some(&(timestamp, quads)) = self.events.front() {
+            if timestamp > start {
+                break;
+            }
+            self.events.pop_front();
+            self.quads -= quads;
+        }
+    }
+
+    /// Get the quads per second over `width` ms
+    pub(crate) fn per_second(&self, width: i64) -> f64 {
+        if width <= 0 {
+            return 0.0;
+        }
+        self.quads as f64 * 1000.0 / width as f64
+    }
+}
+
+/// Durations of the evaluations of a window's reports, recorded by the engine while
+/// the window itself may be locked
+#[derive(Debug, Default)]
+pub(crate) struct EvaluationRecorder {
+    evaluations: AtomicU64,
+    total_micros: AtomicU64,
+    last_micros: AtomicU64,
+}
+
+impl EvaluationRecorder {
+    pub(crate) fn record(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.last_micros.store(micros, Ordering::Relaxed);
+    }
+
+    /// Fill in the evaluation statistics of a window
+    pub(crate) fn read_into(&self, stats: &mut WindowStats) {
+        let evaluations = self.evaluations.load(Ordering::Relaxed);
+        let total_micros = self.total_micros.load(Ordering::Relaxed);
+        stats.evaluations = evaluations;
+        stats.mean_evaluation_duration =
+            Duration::from_micros(total_micros.checked_div(evaluations).unwrap_or(0));
+        stats.last_evaluation_duration =
+            Duration::from_micros(self.last_micros.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrival_rate_over_the_last_width() {
+        let mut rate = ArrivalRate::default();
+        rate.record(100, 4, 1000);
+        rate.record(600, 2, 1000);
+        assert_eq!(rate.per_second(1000), 6.0);
+        // The event at 100 is a width older than the one at 1100
+        rate.record(1100, 1, 1000);
+        assert_eq!(rate.per_second(1000), 3.0);
+        // Late events are forgotten once the events arriving before them are
+        rate.record(200, 5, 1000);
+        rate.record(1300, 0, 1000);
+        assert_eq!(rate.per_second(1000), 8.0);
+    }
+
+    #[test]
+    fn test_evaluation_durations() {
+        let recorder = EvaluationRecorder::default();
+        let mut stats = WindowStats::default();
+        recorder.read_into(&mut stats);
+        assert_eq!(stats.mean_evaluation_duration, Duration::ZERO);
+
+        recorder.record(Duration::from_millis(2));
+        recorder.record(Duration::from_millis(6));
+        recorder.read_into(&mut stats);
+        assert_eq!(stats.evaluations, 2);
+        assert_eq!(stats.mean_evaluation_duration, Duration::from_millis(4));
+        assert_eq!(stats.last_evaluation_duration, Duration::from_millis(6));
+    }
+}
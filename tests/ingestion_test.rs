@@ -0,0 +1,188 @@
+//! Tests for event ingestion helpers on RDFStream
+
+use oxigraph::model::*;
+use rsp_rs::{RSPEngine, RSPEngineBuilder, TimestampExtractor, TimestampPolicy};
+use std::thread;
+use std::time::Duration;
+
+const QUERY: &str = r#"
+    PREFIX ex: <https://rsp.rs/>
+    REGISTER RStream <output> AS
+    SELECT *
+    FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10000 STEP 2000]
+    WHERE {
+        WINDOW ex:w1 { ?s ?p ?o }
+    }
+"#;
+
+#[test]
+fn test_add_jsonld_extracts_timestamp() {
+    let mut engine = RSPEngine::new(QUERY.to_string());
+    engine.initialize().unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let document = r#"{
+        "@context": {
+            "sosa": "http://www.w3.org/ns/sosa/",
+            "xsd": "http://www.w3.org/2001/XMLSchema#"
+        },
+        "@id": "https://rsp.rs/observation1",
+        "sosa:hasSimpleResult": 21.5,
+        "sosa:resultTime": { "@value": "1970-01-01T00:00:05Z", "@type": "xsd:dateTime" }
+    }"#;
+
+    stream
+        .add_jsonld(document, &TimestampExtractor::sosa_result_time())
+        .unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let window = engine.get_window("https://rsp.rs/w1").unwrap();
+    let window_lock = window.lock().unwrap();
+    // Windows are scoped around the extracted timestamp (5000ms)
+    let ranges = window_lock.get_active_window_ranges();
+    assert!(
+        ranges
+            .iter()
+            .any(|(start, end)| *start <= 5000 && 5000 < *end)
+    );
+}
+
+#[test]
+fn test_add_jsonld_with_frame_extractor() {
+    let mut engine = RSPEngine::new(QUERY.to_string());
+    engine.initialize().unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let extractor = TimestampExtractor::from_frame(
+        r#"{
+            "@context": { "prov": "http://www.w3.org/ns/prov#" },
+            "prov:qualifiedGeneration": { "prov:atTime": {} }
+        }"#,
+    )
+    .unwrap();
+    let document = r#"{
+        "@context": { "prov": "http://www.w3.org/ns/prov#" },
+        "@id": "https://rsp.rs/observation1",
+        "prov:qualifiedGeneration": { "prov:atTime": 7000 }
+    }"#;
+
+    stream.add_jsonld(document, &extractor).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let window = engine.get_window("https://rsp.rs/w1").unwrap();
+    let ranges = window.lock().unwrap().get_active_window_ranges();
+    assert!(
+        ranges
+            .iter()
+            .any(|(start, end)| *start <= 7000 && 7000 < *end)
+    );
+}
+
+#[test]
+fn test_add_jsonld_without_timestamp_fails() {
+    let mut engine = RSPEngine::new(QUERY.to_string());
+    engine.initialize().unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let document = r#"{
+        "@id": "https://rsp.rs/observation1",
+        "https://rsp.rs/value": 1
+    }"#;
+
+    let result = stream.add_jsonld(document, &TimestampExtractor::sosa_result_time());
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_predicate_timestamp_policy_with_add_graph() {
+    let mut engine = RSPEngine::new(QUERY.to_string());
+    engine.initialize().unwrap();
+
+    // Explicit policy (default) requires a timestamp
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+    assert!(stream.add_graph(&Graph::new()).is_err());
+
+    let result_time = NamedNode::new("https://rsp.rs/resultTime").unwrap();
+    engine
+        .set_timestamp_policy(
+            "https://rsp.rs/stream1",
+            TimestampPolicy::predicate(result_time.clone()),
+        )
+        .unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let mut graph = Graph::new();
+    graph.insert(TripleRef::new(
+        NamedNodeRef::new("https://rsp.rs/observation1").unwrap(),
+        result_time.as_ref(),
+        LiteralRef::new_typed_literal("7000", vocab::xsd::INTEGER),
+    ));
+    stream.add_graph(&graph).unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    let window = engine.get_window("https://rsp.rs/w1").unwrap();
+    let ranges = window.lock().unwrap().get_active_window_ranges();
+    assert!(
+        ranges
+            .iter()
+            .any(|(start, end)| *start <= 7000 && 7000 < *end)
+    );
+}
+
+#[test]
+fn test_stream_stats_track_out_of_order_events() {
+    let mut engine = RSPEngine::new(QUERY.to_string());
+    engine.initialize().unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+    assert_eq!(
+        engine.stream_stats("https://rsp.rs/stream1"),
+        Some(Default::default())
+    );
+    assert!(engine.stream_stats("https://rsp.rs/unknown").is_none());
+
+    for timestamp in [1000, 4000, 2500, 9000] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/subject").unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    let stats = engine.stream_stats("https://rsp.rs/stream1").unwrap();
+    assert_eq!(stats.events, 4);
+    assert_eq!(stats.out_of_order_events, 1);
+    assert_eq!(stats.max_out_of_orderness, 1500);
+    assert_eq!(stats.watermark, Some(9000));
+}
+
+#[test]
+fn test_max_lateness_drops_late_events() {
+    let mut engine = RSPEngineBuilder::new(QUERY.to_string())
+        .max_lateness(1000)
+        .channel_capacity(16)
+        .build();
+    engine.initialize().unwrap();
+    let stream = engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    // The event at 13000 closes windows, then 12500 is within the lateness and 2000 isn't
+    for timestamp in [1000, 3000, 13000, 12500, 2000] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/subject").unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+    thread::sleep(Duration::from_millis(100));
+
+    let stats = engine.stream_stats("https://rsp.rs/stream1").unwrap();
+    assert_eq!(stats.events, 5);
+    assert_eq!(stats.late_events, 2);
+    assert_eq!(stats.dropped_events, 1);
+}
@@ -411,3 +411,58 @@ fn test_group_by_aggregation() {
         "GROUP BY query should return results"
     );
 }
+
+/// Run an aggregation query through the engine and collect its sorted results
+fn run_engine_aggregation(incremental: bool) -> Vec<(String, i64)> {
+    let query = r#"
+        PREFIX ex: <http://example.org/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor (AVG(?temperature) AS ?avg) (COUNT(*) AS ?count) (MAX(?temperature) AS ?max)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 4000 STEP 2000]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?temperature }
+        }
+        GROUP BY ?sensor
+    "#;
+
+    let mut engine = rsp_rs::RSPEngine::new(query.to_string());
+    engine.set_incremental_aggregation(incremental);
+    engine.set_evaluation_threads(1);
+    engine.initialize().unwrap();
+    let window = engine.get_window("http://example.org/w1").unwrap();
+    assert_eq!(
+        window.lock().unwrap().aggregation_plan().is_some(),
+        incremental
+    );
+
+    let stream = engine.get_stream("http://example.org/stream1").unwrap();
+    let receiver = engine.start_processing();
+
+    for i in 0..10i64 {
+        let quad = Quad::new(
+            NamedNode::new(format!("http://example.org/sensor{}", i % 3)).unwrap(),
+            NamedNode::new("http://example.org/temperature").unwrap(),
+            Literal::from(20 + i),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], i * 700).unwrap();
+    }
+    engine
+        .close_stream("http://example.org/stream1", 20000)
+        .unwrap();
+
+    let mut results = Vec::new();
+    while let Ok(result) = receiver.recv_timeout(std::time::Duration::from_millis(500)) {
+        results.push((result.bindings, result.timestamp_from));
+    }
+    results.sort();
+    results
+}
+
+#[test]
+fn test_incremental_aggregation_matches_sparql() {
+    let incremental = run_engine_aggregation(true);
+    let sparql = run_engine_aggregation(false);
+    assert!(!incremental.is_empty());
+    assert_eq!(incremental, sparql);
+}
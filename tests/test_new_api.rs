@@ -34,7 +34,7 @@ fn test_stream_is_cloneable() {
 
     // Both streams should be usable
     assert_eq!(stream1.name, stream2.name);
-    assert_eq!(stream1.name, "https://rsp.rs/stream1");
+    assert_eq!(&*stream1.name, "https://rsp.rs/stream1");
 }
 
 #[test]
@@ -297,3 +297,3094 @@ fn test_window_graph_names() {
         results.len()
     );
 }
+
+#[test]
+fn test_window_memory_limit_spills_to_disk() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        10000,
+        10000,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    // Room for roughly one quad in memory
+    window.set_memory_limit(Some(200));
+    assert_eq!(window.memory_limit(), Some(200));
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send(content.len()).unwrap();
+    });
+
+    for i in 1..=5 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, i * 1000);
+    }
+
+    // Most of the content lives on disk, but is complete when read back
+    assert!(window.get_content(5000).unwrap().len() < 5);
+    assert_eq!(window.get_full_content(5000).unwrap().len(), 5);
+
+    let sentinel = Quad::new(
+        NamedNode::new("urn:rsp:sentinel").unwrap(),
+        NamedNode::new("urn:rsp:type").unwrap(),
+        Literal::new_simple_literal("end"),
+        GraphName::DefaultGraph,
+    );
+    window.add(sentinel, 20000);
+    // The last report covers the window holding all five quads
+    assert_eq!(rx.try_iter().last(), Some(5));
+}
+
+#[test]
+fn test_tumbling_window_keeps_one_buffer() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        1000,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+    assert!(window.is_tumbling());
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send(content.len()).unwrap();
+    });
+
+    for i in 0..25 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, i * 100);
+        assert_eq!(window.get_active_window_count(), 1);
+    }
+
+    // Windows [0,1000) and [1000,2000) closed with ten quads each
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![10, 10]);
+    assert_eq!(window.get_active_window_ranges(), vec![(2000, 3000)]);
+    assert_eq!(window.get_content(2400).unwrap().len(), 5);
+}
+
+#[test]
+fn test_sliding_window_with_many_overlapping_instances() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    // A hundred instances overlap at any time
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        10000,
+        100,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send(content.len()).unwrap();
+    });
+
+    for i in 0..200 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, i * 100);
+    }
+
+    // Every event closes the instance ending 100 ms before it
+    let expected: Vec<usize> = (2..200).map(|k| (k - 1).min(100)).collect();
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), expected);
+    assert_eq!(window.get_active_window_count(), 101);
+    let ranges = window.get_active_window_ranges();
+    assert_eq!(ranges.first(), Some(&(9900, 19900)));
+    assert_eq!(ranges.last(), Some(&(19900, 29900)));
+    assert_eq!(window.get_content(15000).unwrap().len(), 100);
+    assert_eq!(window.get_content(24999).unwrap().len(), 50);
+    // Instances are half-open, the one closing at 25000 doesn't contain it
+    let (instance, content) = window.get_window_at(25000).unwrap();
+    assert_eq!((instance.open, content.len()), (15100, 49));
+    let overlapping = window.get_windows_overlapping(29700, 29900);
+    let opens: Vec<i64> = overlapping.iter().map(|(w, _)| w.open).collect();
+    assert_eq!(opens, vec![19800, 19900]);
+}
+
+#[test]
+fn test_window_add_batch_reports_once() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        1000,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send(content.len()).unwrap();
+    });
+
+    let batch = |offset: i64| -> Vec<Quad> {
+        (0..50)
+            .map(|i| {
+                Quad::new(
+                    NamedNode::new(format!("https://rsp.rs/subject{}", offset + i)).unwrap(),
+                    NamedNode::new("https://rsp.rs/predicate").unwrap(),
+                    Literal::from(i),
+                    GraphName::DefaultGraph,
+                )
+            })
+            .collect()
+    };
+
+    window.add_batch(&batch(0), 200);
+    window.add_batch(&batch(50), 600);
+    assert!(rx.try_iter().next().is_none());
+    assert_eq!(window.get_content(600).unwrap().len(), 100);
+
+    // One report for the whole closing batch, with the content of the closed window
+    window.add_batch(&batch(100), 1500);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![100]);
+    assert_eq!(window.get_content(1500).unwrap().len(), 50);
+}
+
+#[test]
+fn test_window_content_by_instance() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick, WindowInstance};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        500,
+        ReportStrategy::OnContentChange,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send(content.window_bounds).unwrap();
+    });
+
+    for i in 0..3 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, 600 + i * 100);
+    }
+
+    let expected = vec![
+        (WindowInstance::new(0, 1000), 3),
+        (WindowInstance::new(500, 1500), 3),
+    ];
+    assert_eq!(window.list_windows(), expected);
+    // The content of a report is found again from its bounds
+    let (open, close) = rx.try_iter().last().flatten().unwrap();
+    let instance = WindowInstance::new(open, close);
+    assert_eq!(window.get_window_content(&instance).unwrap().len(), 3);
+    let unopened = WindowInstance::new(1000, 2000);
+    assert!(window.get_window_content(&unopened).is_none());
+}
+
+#[test]
+fn test_window_report_is_shared_between_subscribers() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+    use std::sync::Arc;
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        500,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..2 {
+        let tx = tx.clone();
+        window.subscribe(StreamType::RStream, move |content| {
+            tx.send(content).unwrap();
+        });
+    }
+
+    for i in 0..4 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, i * 300);
+    }
+
+    let reports: Vec<_> = rx.try_iter().collect();
+    assert_eq!(reports.len(), 2);
+    assert!(Arc::ptr_eq(&reports[0], &reports[1]));
+    // Overlapping windows share the quads instead of holding copies
+    let current = window.get_content(900).unwrap();
+    let next = window.get_content(1200).unwrap();
+    let shared = current.elements.iter().filter(|quad| {
+        next.elements
+            .get(*quad)
+            .is_some_and(|other| Arc::ptr_eq(quad, other))
+    });
+    assert_eq!((current.len(), next.len(), shared.count()), (4, 2, 2));
+}
+
+#[test]
+fn test_window_lifecycle_transitions() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick, WindowState};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        500,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+
+    let (tx, rx) = mpsc::channel();
+    window.subscribe_lifecycle(move |instance| {
+        tx.send((instance.open, instance.close, instance.state()))
+            .unwrap();
+    });
+    let (reports_tx, reports_rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        reports_tx.send(content.len()).unwrap();
+    });
+
+    for (i, timestamp) in [0, 300, 600, 900, 1200, 1700].into_iter().enumerate() {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i as i64),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, timestamp);
+    }
+
+    use WindowState::*;
+    let transitions: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        transitions,
+        vec![
+            (-1000, 0, Open),
+            (-500, 500, Open),
+            (0, 1000, Open),
+            // Closed before any element could make it report
+            (-1000, 0, Evicted),
+            (500, 1500, Open),
+            (-500, 500, Reported),
+            (-500, 500, Evicted),
+            (1000, 2000, Open),
+            (0, 1000, Reported),
+            (0, 1000, Evicted),
+            (1500, 2500, Open),
+            (500, 1500, Reported),
+            (500, 1500, Evicted),
+        ]
+    );
+    assert_eq!(reports_rx.try_iter().collect::<Vec<_>>(), vec![2, 4, 3]);
+    assert_eq!(
+        window.get_active_window_ranges(),
+        vec![(1000, 2000), (1500, 2500)]
+    );
+}
+
+#[test]
+fn test_result_deltas_between_reports() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER IStream <output> AS
+        SELECT ?s
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:predicate ?o }
+        }
+    "#;
+
+    let mut rsp_engine = RSPEngine::new(query.to_string());
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let deltas = rsp_engine.start_processing_deltas();
+
+    let event = |subject: &str| {
+        Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", subject)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            NamedNode::new("https://rsp.rs/object").unwrap(),
+            GraphName::DefaultGraph,
+        )
+    };
+    stream.add_quads(vec![event("s1")], 100).unwrap();
+    stream.add_quads(vec![event("s2")], 500).unwrap();
+    stream
+        .add_quads(vec![event("s2"), event("s3")], 1100)
+        .unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+
+    let subjects = |bindings: &[rsp_rs::Binding]| {
+        let mut subjects: Vec<String> = bindings
+            .iter()
+            .map(|binding| binding.get("s").unwrap().to_string())
+            .collect();
+        subjects.sort();
+        subjects
+    };
+
+    let first = deltas.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(
+        subjects(&first.added),
+        vec!["<https://rsp.rs/s1>", "<https://rsp.rs/s2>"]
+    );
+    assert!(first.removed.is_empty());
+
+    let second = deltas.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(subjects(&second.added), vec!["<https://rsp.rs/s3>"]);
+    assert_eq!(subjects(&second.removed), vec!["<https://rsp.rs/s1>"]);
+    assert!(second.window.0 > first.window.0);
+}
+
+#[test]
+fn test_alert_rules_filter_results() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?temp
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?temp }
+        }
+    "#;
+
+    let mut rsp_engine = RSPEngine::new(query.to_string());
+    rsp_engine.initialize().unwrap();
+    rsp_engine.set_evaluation_threads(1);
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let alerts = rsp_engine.start_processing_alerts(vec![
+        rsp_rs::AlertRule::greater_than("overheating", "temp", 30.0)
+            .with_debounce(Duration::from_millis(2000)),
+    ]);
+
+    let reading = |sensor: &str, temp: f64| {
+        vec![Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", sensor)).unwrap(),
+            NamedNode::new("https://rsp.rs/temperature").unwrap(),
+            Literal::from(temp),
+            GraphName::DefaultGraph,
+        )]
+    };
+    stream.add_quads(reading("s1", 35.0), 100).unwrap();
+    stream.add_quads(reading("s2", 20.0), 200).unwrap();
+    // Within the debounce interval of the first alert
+    stream.add_quads(reading("s1", 36.0), 1100).unwrap();
+    stream.add_quads(reading("s1", 40.0), 3100).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 10000)
+        .unwrap();
+
+    let first = alerts.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(first.rule, "overheating");
+    assert_eq!(first.binding.number("temp"), Some(35.0));
+    let second = alerts.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(second.binding.number("temp"), Some(40.0));
+    assert!(second.window.1 - first.window.1 >= 2000);
+    assert!(alerts.recv_timeout(Duration::from_millis(300)).is_err());
+}
+
+#[test]
+fn test_recent_results_are_retained() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <https://rsp.rs/output> AS
+        SELECT ?s
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:predicate ?o }
+        }
+    "#;
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .retain_results(2)
+        .evaluation_threads(1)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let results = rsp_engine.start_processing();
+
+    for (i, timestamp) in [100, 1100, 2100, 3100].into_iter().enumerate() {
+        let event = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/s{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            NamedNode::new("https://rsp.rs/object").unwrap(),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![event], timestamp).unwrap();
+    }
+    for _ in 0..3 {
+        results.recv_timeout(Duration::from_secs(5)).unwrap();
+    }
+
+    // A consumer attaching now still sees the last two results
+    let recent = rsp_engine
+        .recent_results("https://rsp.rs/output", 0)
+        .unwrap();
+    assert_eq!(recent.len(), 2);
+    assert!(recent[0].bindings.contains("s1"));
+    assert!(recent[1].bindings.contains("s2"));
+    let latest = rsp_engine
+        .recent_results("https://rsp.rs/output", recent[1].timestamp_to)
+        .unwrap();
+    assert_eq!(latest.len(), 1);
+    assert!(
+        rsp_engine
+            .recent_results("https://rsp.rs/other", 0)
+            .is_none()
+    );
+}
+
+#[test]
+fn test_synchronous_mode_needs_no_waiting() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?s
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:predicate ?o }
+        }
+    "#;
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let event = |subject: &str| {
+        vec![Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", subject)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            NamedNode::new("https://rsp.rs/object").unwrap(),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    stream.add_quads(event("s1"), 100).unwrap();
+    stream.add_quads(event("s2"), 500).unwrap();
+    assert!(rsp_engine.poll_results().is_empty());
+
+    // The event closing the window has been fully processed when add_quads returns
+    stream.add_quads(event("s3"), 1100).unwrap();
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 2);
+    assert!(
+        results
+            .iter()
+            .all(|r| r.timestamp_to - r.timestamp_from == 1000)
+    );
+    assert!(rsp_engine.poll_results().is_empty());
+
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].bindings.contains("s3"));
+}
+
+#[test]
+fn test_eviction_policy_keeps_latest_reading_per_sensor() {
+    use rsp_rs::LatestPerSubject;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    rsp_engine
+        .get_window("https://rsp.rs/w1")
+        .unwrap()
+        .lock()
+        .unwrap()
+        .add_eviction_policy(LatestPerSubject::new());
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let reading = |sensor: &str, value: i64| {
+        vec![Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", sensor)).unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    stream.add_quads(reading("sensor1", 11), 100).unwrap();
+    stream.add_quads(reading("sensor2", 20), 200).unwrap();
+    stream.add_quads(reading("sensor1", 12), 400).unwrap();
+    stream.add_quads(reading("sensor1", 13), 700).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 1100)
+        .unwrap();
+
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().any(|r| r.bindings.contains("\"13\"")));
+    assert!(results.iter().any(|r| r.bindings.contains("\"20\"")));
+}
+
+#[test]
+fn test_window_emits_empty_windows() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        1000,
+        500,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+    window.set_emit_empty_windows(true);
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send((content.len(), content.last_timestamp_changed))
+            .unwrap();
+    });
+
+    for (i, timestamp) in [100, 1200, 3700].into_iter().enumerate() {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i as i64),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, timestamp);
+    }
+
+    // Every closed instance reports, and the instances of the gap between 1200 and
+    // 3700 report empty, stamped with their open time
+    let reports: Vec<_> = rx.try_iter().collect();
+    assert_eq!(
+        reports,
+        vec![
+            (1, 100),
+            (1, 100),
+            (1, 1200),
+            (1, 1200),
+            (0, 1500),
+            (0, 2000),
+            (0, 2500)
+        ]
+    );
+    assert!(window.emits_empty_windows());
+}
+
+#[test]
+fn test_window_delay_includes_late_events() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let run = |delay: i64| {
+        let mut window = CSPARQLWindow::new(
+            "https://rsp.rs/w1".to_string(),
+            1000,
+            1000,
+            ReportStrategy::OnWindowClose,
+            Tick::TimeDriven,
+            0,
+        );
+        window.set_origin(Some(0));
+        window.set_delay(delay);
+        let (tx, rx) = mpsc::channel();
+        window.subscribe(StreamType::RStream, move |content| {
+            tx.send((content.len(), content.last_timestamp_changed))
+                .unwrap();
+        });
+
+        // 950 arrives after 1100, within the delay of the [0, 1000) instance
+        let mut reports = Vec::new();
+        for (i, timestamp) in [100, 900, 1100, 950, 1300].into_iter().enumerate() {
+            let quad = Quad::new(
+                NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+                NamedNode::new("https://rsp.rs/predicate").unwrap(),
+                Literal::from(i as i64),
+                GraphName::DefaultGraph,
+            );
+            window.add(quad, timestamp);
+            reports.push(rx.try_iter().collect::<Vec<_>>());
+        }
+        reports
+    };
+
+    // Without a delay, 1100 closes the instance and the late event is dropped
+    let reports = run(0);
+    assert_eq!(reports[2], vec![(2, 900)]);
+    assert!(reports[3..].iter().all(|r| r.is_empty()));
+
+    // With a delay, the instance waits until 1300 and includes the late event
+    let reports = run(300);
+    assert!(reports[..4].iter().all(|r| r.is_empty()));
+    assert_eq!(reports[4], vec![(3, 950)]);
+}
+
+#[test]
+fn test_window_advance_to_closes_idle_instances() {
+    use rsp_rs::{CSPARQLWindow, ReportStrategy, StreamType, Tick};
+
+    let mut window = CSPARQLWindow::new(
+        "https://rsp.rs/w1".to_string(),
+        2000,
+        1000,
+        ReportStrategy::OnWindowClose,
+        Tick::TimeDriven,
+        0,
+    );
+    window.set_origin(Some(0));
+    let (tx, rx) = mpsc::channel();
+    window.subscribe(StreamType::RStream, move |content| {
+        tx.send((content.len(), content.window_bounds)).unwrap();
+    });
+    for (i, timestamp) in [100, 900].into_iter().enumerate() {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/subject{}", i)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            Literal::from(i as i64),
+            GraphName::DefaultGraph,
+        );
+        window.add(quad, timestamp);
+    }
+
+    // Time before the latest event changes nothing, and instances only close after
+    // their close time, as with events
+    window.advance_to(600);
+    window.advance_to(1000);
+    assert!(rx.try_iter().next().is_none());
+
+    // Without any further event, processing time closes the instances it is past
+    window.advance_to(1001);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![(2, Some((-1000, 1000)))]
+    );
+    window.advance_to(2001);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![(2, Some((0, 2000)))]
+    );
+    assert_eq!(window.get_active_window_count(), 0);
+}
+
+#[test]
+fn test_manual_clock_drives_processing_time() {
+    use rsp_rs::{ManualClock, TimestampPolicy};
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let clock = ManualClock::new(0);
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .clock(clock.clone())
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine
+        .get_stream("https://rsp.rs/stream1")
+        .unwrap()
+        .with_timestamp_policy(TimestampPolicy::WallClock);
+
+    // Events are timestamped with the clock's time on arrival
+    for time in [100, 600] {
+        clock.set(time);
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(time),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], 0).unwrap();
+    }
+    assert_eq!(rsp_engine.advance_to_clock(), Some(600));
+    assert!(rsp_engine.poll_results().is_empty());
+
+    // The stream stays idle, the clock alone closes its window
+    clock.advance(900);
+    assert_eq!(rsp_engine.advance_to_clock(), Some(1500));
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 2);
+    assert!(
+        results
+            .iter()
+            .all(|r| (r.timestamp_from, r.timestamp_to) == (0, 1000))
+    );
+
+    // Event-time engines ignore the clock
+    let rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    assert_eq!(rsp_engine.advance_to_clock(), None);
+}
+
+#[test]
+fn test_idle_stream_timeout_closes_windows() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .stream_idle_timeout("https://rsp.rs/stream1", Duration::from_millis(50))
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [100, 500] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    assert!(rsp_engine.poll_results().is_empty());
+
+    // The producer stops without closing the stream
+    let mut results = Vec::new();
+    for _ in 0..100 {
+        results.extend(rsp_engine.poll_results());
+        if !results.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(results.len(), 2);
+    assert!(
+        results
+            .iter()
+            .all(|r| (r.timestamp_from, r.timestamp_to) == (0, 1000))
+    );
+
+    // Later events still open new instances
+    let quad = Quad::new(
+        NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+        NamedNode::new("https://rsp.rs/value").unwrap(),
+        Literal::from(1500),
+        GraphName::DefaultGraph,
+    );
+    stream.add_quads(vec![quad], 1500).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+    assert!(!rsp_engine.poll_results().is_empty());
+}
+
+#[test]
+fn test_idle_stream_timeout_waits_for_all_streams_of_window() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1, ex:stream2 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .stream_idle_timeout("https://rsp.rs/stream1", Duration::from_millis(200))
+        .build();
+    rsp_engine.initialize().unwrap();
+    let quad = |timestamp: i64| {
+        Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        )
+    };
+    let idle = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    idle.add_quads(vec![quad(100)], 100).unwrap();
+
+    // The second stream keeps feeding the window while the first one is silent
+    let busy = rsp_engine.get_stream("https://rsp.rs/stream2").unwrap();
+    for timestamp in 101..161 {
+        busy.add_quads(vec![quad(timestamp)], timestamp).unwrap();
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(rsp_engine.poll_results().is_empty());
+
+    // Closed once both are silent, the second one for the timeout of the first
+    let mut results = Vec::new();
+    for _ in 0..100 {
+        results.extend(rsp_engine.poll_results());
+        if !results.is_empty() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(results.len(), 61);
+}
+
+#[test]
+fn test_processing_handle_before_initialize() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    let results = rsp_engine.start_processing();
+    rsp_engine.initialize().unwrap();
+    let (tx, rx) = mpsc::channel();
+    results.add_subscriber(move |result| tx.send(result.timestamp_from).unwrap());
+    assert!(results.is_running());
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [100, 1100, 2100] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+        if timestamp == 1100 {
+            results.stop();
+        }
+    }
+    assert!(!results.is_running());
+    // Only the report before stopping reached the channel and the subscriber
+    let received: Vec<_> = results.iter().map(|r| r.timestamp_from).collect();
+    assert_eq!(received, vec![0]);
+    assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![0]);
+    // Other consumers are independent
+    assert_eq!(rsp_engine.poll_results().len(), 2);
+}
+
+#[test]
+fn test_result_callbacks_with_unsubscribe() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <https://rsp.rs/output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    let (tx, rx) = mpsc::channel();
+    let first_tx = tx.clone();
+    let first = rsp_engine
+        .on_result("https://rsp.rs/output", move |result| {
+            first_tx.send(("first", result.timestamp_from)).unwrap()
+        })
+        .unwrap();
+    rsp_engine.initialize().unwrap();
+    rsp_engine
+        .on_result("https://rsp.rs/output", move |result| {
+            tx.send(("second", result.timestamp_from)).unwrap()
+        })
+        .unwrap();
+    assert!(
+        rsp_engine
+            .on_result("https://rsp.rs/other", |_| {})
+            .is_err()
+    );
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [100, 1100, 2100] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+        if timestamp == 1100 {
+            assert!(rsp_engine.unsubscribe(first));
+        }
+    }
+    assert!(!rsp_engine.unsubscribe(first));
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        vec![("first", 0), ("second", 0), ("second", 1000)]
+    );
+}
+
+#[test]
+fn test_static_patterns_are_cached() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <https://rsp.rs/output> AS
+        SELECT ?room ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000]
+        WHERE {
+            ?sensor ex:room ?room .
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let located = |sensor: &str, room: &str| {
+        Quad::new(
+            node(sensor),
+            node("room"),
+            node(room),
+            GraphName::DefaultGraph,
+        )
+    };
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .cache_static_patterns(1000)
+        .synchronous(true)
+        .build();
+    rsp_engine.add_static_data(located("sensor1", "kitchen"));
+    rsp_engine.add_static_data(located("sensor2", "hall"));
+    rsp_engine.initialize().unwrap();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let reading = |sensor: &str, value: i64| {
+        Quad::new(
+            node(sensor),
+            node("value"),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )
+    };
+    stream.add_quads(vec![reading("sensor1", 21)], 100).unwrap();
+    stream.add_quads(vec![reading("sensor3", 18)], 200).unwrap();
+    // Closes the first window, before sensor3 is located
+    stream
+        .add_quads(vec![reading("sensor3", 19)], 1100)
+        .unwrap();
+    rsp_engine.add_static_data(located("sensor3", "attic"));
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+
+    let rooms: Vec<_> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| result.solution.get("room").cloned())
+        .collect();
+    assert_eq!(
+        rooms,
+        vec![Some(node("kitchen").into()), Some(node("attic").into())]
+    );
+}
+
+#[test]
+fn test_overlapping_windows_dedup_results() {
+    use rsp_rs::ResultDedup;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <https://rsp.rs/output> AS
+        SELECT ?sensor
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 250]
+        WHERE { WINDOW ex:w1 { ?sensor ex:value ?value } }
+    "#;
+    let sensors = |mode: ResultDedup| {
+        let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+            .result_dedup(mode)
+            .synchronous(true)
+            .build();
+        rsp_engine.initialize().unwrap();
+        let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+        let reading = |sensor: &str| {
+            Quad::new(
+                NamedNode::new(format!("https://rsp.rs/{}", sensor)).unwrap(),
+                NamedNode::new("https://rsp.rs/value").unwrap(),
+                Literal::from(21),
+                GraphName::DefaultGraph,
+            )
+        };
+        stream.add_quads(vec![reading("sensor1")], 100).unwrap();
+        stream.add_quads(vec![reading("sensor2")], 600).unwrap();
+        rsp_engine
+            .close_stream("https://rsp.rs/stream1", 5000)
+            .unwrap();
+        rsp_engine
+            .poll_results()
+            .iter()
+            .map(|result| result.solution.get("sensor").unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Every instance covering an event reports it
+    assert!(sensors(ResultDedup::None).len() > 2);
+    assert_eq!(
+        sensors(ResultDedup::NewBindings),
+        vec!["<https://rsp.rs/sensor1>", "<https://rsp.rs/sensor2>"]
+    );
+}
+
+#[test]
+fn test_results_of_windows_are_merged_by_close_time() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <https://rsp.rs/output> AS
+        SELECT ?sensor
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000]
+        FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 1000 STEP 1000]
+        WHERE {
+            { WINDOW ex:w1 { ?sensor ex:value ?value } }
+            UNION
+            { WINDOW ex:w2 { ?sensor ex:value ?value } }
+        }
+    "#;
+    let close_times = |merge: bool| {
+        let mut builder = rsp_rs::RSPEngineBuilder::new(query.to_string()).synchronous(true);
+        if merge {
+            builder = builder.merge_window_results(Duration::from_secs(60));
+        }
+        let mut rsp_engine = builder.build();
+        rsp_engine.initialize().unwrap();
+        let reading = |sensor: &str| {
+            Quad::new(
+                NamedNode::new(format!("https://rsp.rs/{}", sensor)).unwrap(),
+                NamedNode::new("https://rsp.rs/value").unwrap(),
+                Literal::from(21),
+                GraphName::DefaultGraph,
+            )
+        };
+        let stream1 = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+        let stream2 = rsp_engine.get_stream("https://rsp.rs/stream2").unwrap();
+        // The first stream runs ahead of the second one
+        for timestamp in [100, 1100, 2100, 3100] {
+            stream1
+                .add_quads(vec![reading("sensor1")], timestamp)
+                .unwrap();
+        }
+        for timestamp in [200, 1200, 2200] {
+            stream2
+                .add_quads(vec![reading("sensor2")], timestamp)
+                .unwrap();
+        }
+        let mut close_times: Vec<i64> = rsp_engine
+            .poll_results()
+            .iter()
+            .map(|result| result.timestamp_to)
+            .collect();
+        rsp_engine
+            .close_stream("https://rsp.rs/stream1", 10_000)
+            .unwrap();
+        rsp_engine
+            .close_stream("https://rsp.rs/stream2", 10_000)
+            .unwrap();
+        close_times.extend(rsp_engine.poll_results().iter().map(|r| r.timestamp_to));
+        close_times
+    };
+
+    let unmerged = close_times(false);
+    assert!(unmerged.windows(2).any(|pair| pair[0] > pair[1]));
+    let merged = close_times(true);
+    assert!(merged.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(merged.len(), unmerged.len());
+}
+
+#[test]
+fn test_window_quads_keep_their_stream() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+        PREFIX rsp: <https://rsp.rs/ns#>
+        REGISTER RStream <output> AS
+        SELECT ?stream (COUNT(?value) AS ?readings)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1, ex:stream2 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 {
+                ?sensor ex:value ?value .
+                ?statement rdf:subject ?sensor ; rdf:object ?value ; rsp:stream ?stream
+            }
+        }
+        GROUP BY ?stream
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .annotate_streams(true)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+
+    let readings = [
+        ("stream1", 1, 100),
+        ("stream2", 2, 200),
+        ("stream2", 3, 300),
+    ];
+    for (stream, value, timestamp) in readings {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/sensor{}", value)).unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        let stream = rsp_engine
+            .get_stream(&format!("https://rsp.rs/{}", stream))
+            .unwrap();
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 1500)
+        .unwrap();
+
+    let mut rows: Vec<(String, f64)> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| {
+            let stream = result.solution.get("stream").unwrap().to_string();
+            (stream, result.solution.number("readings").unwrap())
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        rows,
+        vec![
+            ("<https://rsp.rs/stream1>".to_string(), 1.0),
+            ("<https://rsp.rs/stream2>".to_string(), 2.0)
+        ]
+    );
+}
+
+/// Run an aggregation query over readings of two sensors in three windows, returning
+/// the (window start, sensor, average) results
+fn averages_per_window(
+    query: &str,
+    filter: Option<rsp_rs::ResultFilter>,
+) -> Vec<(i64, String, f64)> {
+    let mut builder = rsp_rs::RSPEngineBuilder::new(query.to_string()).synchronous(true);
+    if let Some(filter) = filter {
+        builder = builder.filter_results(filter);
+    }
+    let mut rsp_engine = builder.build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let readings = [
+        ("sensor1", 30, 100),
+        ("sensor1", 20, 200),
+        ("sensor2", 10, 300),
+        ("sensor1", 15, 1100),
+        ("sensor2", 40, 2100),
+    ];
+    for (sensor, value, timestamp) in readings {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", sensor)).unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 3000)
+        .unwrap();
+
+    let mut rows: Vec<(i64, String, f64)> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| {
+            let sensor = result.solution.get("sensor").unwrap().to_string();
+            let avg = result.solution.number("avg").unwrap();
+            (result.timestamp_from, sensor, avg)
+        })
+        .collect();
+    rows.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    rows
+}
+
+#[test]
+fn test_having_applies_per_window() {
+    let query = |having: &str| {
+        format!(
+            r#"
+            PREFIX ex: <https://rsp.rs/>
+            REGISTER RStream <output> AS
+            SELECT ?sensor (AVG(?value) AS ?avg)
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+            WHERE {{
+                WINDOW ex:w1 {{ ?sensor ex:value ?value }}
+            }}
+            GROUP BY ?sensor
+            {}
+        "#,
+            having
+        )
+    };
+    let expected = vec![
+        (0, "<https://rsp.rs/sensor1>".to_string(), 25.0),
+        (2000, "<https://rsp.rs/sensor2>".to_string(), 40.0),
+    ];
+
+    // [1000, 2000) has no group above the threshold, so it emits nothing
+    let having = averages_per_window(&query("HAVING (AVG(?value) > 20)"), None);
+    assert_eq!(having, expected);
+
+    // The engine filter drops the same results without a HAVING clause
+    let filter = rsp_rs::ResultFilter::greater_than("avg", 20.0);
+    assert_eq!(averages_per_window(&query(""), Some(filter)), expected);
+    assert_eq!(averages_per_window(&query(""), None).len(), 4);
+}
+
+#[test]
+fn test_query_replacement_keeps_window_content() {
+    let query = |projection: &str, having: &str| {
+        format!(
+            r#"
+            PREFIX ex: <https://rsp.rs/>
+            REGISTER RStream <output> AS
+            SELECT {}
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+            WHERE {{
+                WINDOW ex:w1 {{ ?sensor ex:value ?value }}
+            }}
+            {}
+        "#,
+            projection, having
+        )
+    };
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query("(COUNT(?value) AS ?n)", ""))
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    assert!(rsp_engine.explain().unwrap().windows[0].incremental);
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let add = |value: i64, timestamp: i64| {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    };
+    let totals = |engine: &RSPEngine| -> Vec<f64> {
+        engine
+            .poll_results()
+            .iter()
+            .map(|result| result.solution.number("n").unwrap())
+            .collect()
+    };
+
+    add(10, 100);
+    add(20, 200);
+    // The sum covers the events added before the replacement
+    rsp_engine
+        .replace_query(&query("(SUM(?value) AS ?n)", ""))
+        .unwrap();
+    assert!(rsp_engine.explain().unwrap().windows[0].incremental);
+    add(30, 1100);
+    assert_eq!(totals(&rsp_engine), vec![30.0]);
+
+    // Queries that can't be maintained incrementally are evaluated with SPARQL
+    let having = "HAVING (SUM(?value) > 50)";
+    rsp_engine
+        .replace_query(&query("(SUM(?value) AS ?n)", having))
+        .unwrap();
+    assert!(!rsp_engine.explain().unwrap().windows[0].incremental);
+    add(40, 1200);
+    add(5, 2100);
+    assert_eq!(totals(&rsp_engine), vec![70.0]);
+
+    // Other windows or invalid SPARQL are rejected, keeping the query
+    let other_window = query("?value", "").replace("RANGE 1000", "RANGE 2000");
+    assert!(rsp_engine.replace_query(&other_window).is_err());
+    assert!(rsp_engine.replace_query(&query("?value", "GROUP")).is_err());
+    assert!(rsp_engine.parsed_query().sparql_query.contains("HAVING"));
+}
+
+#[test]
+fn test_replace_query_rederives_window_settings() {
+    let query = |pattern: &str| {
+        format!(
+            r#"
+            PREFIX ex: <https://rsp.rs/>
+            REGISTER RStream <output> AS
+            SELECT *
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 PARTITION BY ?sensor]
+            WHERE {{ {} }}
+        "#,
+            pattern
+        )
+    };
+    let mut rsp_engine = RSPEngine::new(query("WINDOW ex:w1 { ?sensor ex:value ?v }"));
+    rsp_engine.initialize().unwrap();
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let value = NamedNode::new("https://rsp.rs/value").unwrap();
+    let heartbeat = NamedNode::new("https://rsp.rs/heartbeat").unwrap();
+    let settings = || {
+        let window = window.lock().unwrap();
+        (
+            window.emits_empty_windows(),
+            window.partition_key().cloned(),
+        )
+    };
+    assert_eq!(
+        settings(),
+        (false, Some(rsp_rs::PartitionKey::subject_of(Some(value))))
+    );
+
+    // Negating the window and moving the key are not changes of the window
+    let negated = "?sensor a ex:Sensor . MINUS { WINDOW ex:w1 { ?beat ex:heartbeat ?sensor } }";
+    rsp_engine.replace_query(&query(negated)).unwrap();
+    assert!(rsp_engine.parsed_query().s2r[0].emit_empty_windows);
+    assert_eq!(
+        settings(),
+        (true, Some(rsp_rs::PartitionKey::object_of(Some(heartbeat))))
+    );
+
+    // A key missing from the new query is rejected, keeping the query
+    let unkeyed = query("WINDOW ex:w1 { ?s ex:value ?v }");
+    assert!(rsp_engine.replace_query(&unkeyed).is_err());
+    assert!(rsp_engine.parsed_query().sparql_query.contains("MINUS"));
+    assert!(settings().0);
+}
+
+#[test]
+fn test_side_input_stream_updates_static_data() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?room
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+            ?sensor ex:room ?room .
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .side_input_stream("https://rsp.rs/devices")
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let devices = rsp_engine.get_stream("https://rsp.rs/devices").unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let room = |room: &str| {
+        Quad::new(
+            node("sensor1"),
+            node("room"),
+            node(room),
+            GraphName::DefaultGraph,
+        )
+    };
+    let reading = Quad::new(
+        node("sensor1"),
+        node("value"),
+        Literal::from(20),
+        GraphName::DefaultGraph,
+    );
+    let rooms = |engine: &RSPEngine| -> Vec<String> {
+        engine
+            .poll_results()
+            .iter()
+            .map(|result| result.solution.get("room").unwrap().to_string())
+            .collect()
+    };
+
+    devices.add_quads(vec![room("kitchen")], 0).unwrap();
+    stream.add_quads(vec![reading.clone()], 100).unwrap();
+    stream.add_quads(vec![reading.clone()], 1100).unwrap();
+    assert_eq!(rooms(&rsp_engine), vec!["<https://rsp.rs/kitchen>"]);
+
+    // The sensor moved: its new room replaces the old one
+    devices.add_quads(vec![room("attic")], 1200).unwrap();
+    stream.add_quads(vec![reading.clone()], 2100).unwrap();
+    assert_eq!(rooms(&rsp_engine), vec!["<https://rsp.rs/attic>"]);
+    assert_eq!(rsp_engine.static_data_version(), 3);
+
+    // The sensor was decommissioned
+    let deleted = Quad::new(
+        node("sensor1"),
+        NamedNode::new(rsp_rs::r2r::DELETED).unwrap(),
+        Literal::from(true),
+        GraphName::DefaultGraph,
+    );
+    devices.add_quads(vec![deleted], 2200).unwrap();
+    stream.add_quads(vec![reading], 3100).unwrap();
+    assert!(rooms(&rsp_engine).is_empty());
+    let stats = rsp_engine.stream_stats("https://rsp.rs/devices").unwrap();
+    assert_eq!(stats.events, 3);
+
+    // Streams feeding a window can't be side inputs
+    let mut invalid = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .side_input_stream("https://rsp.rs/stream1")
+        .build();
+    assert!(invalid.initialize().is_err());
+}
+
+#[test]
+fn test_approximate_aggregates_per_window() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        PREFIX rsp: <https://rsp.rs/ns#>
+        REGISTER RStream <output> AS
+        SELECT (rsp:approxCountDistinct(?sensor) AS ?sensors) (rsp:approxPercentile95(?value) AS ?p95)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    // Maintained per window instance rather than re-evaluated
+    assert!(rsp_engine.explain().unwrap().windows[0].incremental);
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for value in 1..=20 {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/sensor{}", value % 5)).unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], value * 10).unwrap();
+    }
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 1100)
+        .unwrap();
+
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].solution.number("sensors"), Some(5.0));
+    assert_eq!(results[0].solution.number("p95"), Some(19.5));
+}
+
+#[test]
+fn test_quads_are_filtered_at_ingestion() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let temperature = NamedNode::new("https://rsp.rs/temperature").unwrap();
+    let humidity = NamedNode::new("https://rsp.rs/humidity").unwrap();
+    let reading = |predicate: &NamedNode, value: i64| {
+        Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            predicate.clone(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )
+    };
+    let builder = || rsp_rs::RSPEngineBuilder::new(query.to_string()).synchronous(true);
+    let pattern = rsp_rs::QuadFilter::pattern("?s <https://rsp.rs/temperature> ?t").unwrap();
+    let engines = [
+        builder().filter_window(
+            "https://rsp.rs/w1",
+            rsp_rs::QuadFilter::predicates([temperature.clone()]),
+        ),
+        builder().filter_stream("https://rsp.rs/stream1", pattern),
+    ];
+
+    for builder in engines {
+        let mut rsp_engine = builder.build();
+        rsp_engine.initialize().unwrap();
+        let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+        let event = vec![reading(&temperature, 20), reading(&humidity, 50)];
+        stream.add_quads(event, 100).unwrap();
+        let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+        assert_eq!(window.lock().unwrap().get_content(100).unwrap().len(), 1);
+
+        // Events without any matching quad still advance time
+        let idle = vec![reading(&humidity, 55)];
+        stream.add_quads(idle, 1100).unwrap();
+        let results = rsp_engine.poll_results();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].solution.number("value"), Some(20.0));
+    }
+}
+
+#[test]
+fn test_feed_is_routed_to_windows_by_content() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:temperatures ON STREAM ex:temperatureStream [RANGE 1000 STEP 1000 ALIGN 0]
+        FROM NAMED WINDOW ex:doors ON STREAM ex:doorStream [RANGE 5000 STEP 5000 ALIGN 0]
+        WHERE {
+            WINDOW ex:temperatures { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let router = rsp_rs::StreamRouter::new()
+        .route(
+            "https://rsp.rs/temperatures",
+            rsp_rs::QuadFilter::predicates([node("temperature")]),
+        )
+        .route(
+            "https://rsp.rs/doors",
+            rsp_rs::QuadFilter::pattern("?door <https://rsp.rs/open> ?open").unwrap(),
+        );
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .route_stream("https://rsp.rs/feed", router)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let feed = rsp_engine.get_stream("https://rsp.rs/feed").unwrap();
+    let event = vec![
+        Quad::new(
+            node("sensor1"),
+            node("temperature"),
+            Literal::from(20),
+            GraphName::DefaultGraph,
+        ),
+        Quad::new(
+            node("door1"),
+            node("open"),
+            Literal::from(true),
+            GraphName::DefaultGraph,
+        ),
+        Quad::new(
+            node("sensor1"),
+            node("battery"),
+            Literal::from(80),
+            GraphName::DefaultGraph,
+        ),
+    ];
+    feed.add_quads(event, 100).unwrap();
+
+    let content = |window: &str| {
+        let window = rsp_engine.get_window(window).unwrap();
+        let window = window.lock().unwrap();
+        let content = window.get_content(100).unwrap();
+        content
+            .elements
+            .iter()
+            .map(|quad| quad.predicate.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        content("https://rsp.rs/temperatures"),
+        vec![node("temperature")]
+    );
+    assert_eq!(content("https://rsp.rs/doors"), vec![node("open")]);
+
+    // Events without quads for a window still advance its time
+    feed.add_quads(Vec::new(), 1100).unwrap();
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].solution.number("value"), Some(20.0));
+
+    let unknown = rsp_rs::StreamRouter::new()
+        .route("https://rsp.rs/unknown", rsp_rs::QuadFilter::new(|_| true));
+    let mut invalid = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .route_stream("https://rsp.rs/feed", unknown)
+        .build();
+    assert!(invalid.initialize().is_err());
+}
+
+#[test]
+fn test_window_runtime_statistics() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 500 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let reading = |sensor: &str, predicate: &str, value: i64| {
+        Quad::new(
+            node(sensor),
+            node(predicate),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )
+    };
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .filter_window(
+            "https://rsp.rs/w1",
+            rsp_rs::QuadFilter::predicates([node("temperature")]),
+        )
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let events = [
+        (
+            vec![
+                reading("sensor1", "temperature", 20),
+                reading("sensor1", "battery", 80),
+                reading("sensor2", "temperature", 21),
+            ],
+            100,
+        ),
+        (vec![reading("sensor1", "temperature", 22)], 600),
+        (vec![reading("sensor3", "temperature", 23)], 1100),
+    ];
+    for (quads, timestamp) in events {
+        stream.add_quads(quads, timestamp).unwrap();
+    }
+    assert!(!rsp_engine.poll_results().is_empty());
+
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let window = window.lock().unwrap();
+    let stats = window.stats();
+    assert_eq!((stats.events, stats.quads, stats.filtered_quads), (3, 4, 1));
+    let ranges: Vec<(i64, i64)> = stats
+        .quads_per_window
+        .iter()
+        .map(|&(open, close, _)| (open, close))
+        .collect();
+    assert_eq!(ranges, window.get_active_window_ranges());
+    assert_eq!(stats.quads_per_window.last(), Some(&(1000, 2000, 1)));
+    assert_eq!(stats.distinct_subjects, 2);
+    // The quads of the events at 600 and 1100, over the last second
+    assert_eq!(stats.arrival_rate, 2.0);
+    assert!(stats.evaluations >= 1);
+}
+
+#[test]
+fn test_load_shedding_when_overloaded() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let reading = |value: i64| {
+        Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/temperature").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )
+    };
+    // Thresholds of 0 are always reached, so the engine is always overloaded
+    let engine = |max_queue_depth, max_evaluation_backlog, strategy| {
+        let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+            .load_shedding(rsp_rs::LoadSheddingPolicy {
+                max_queue_depth,
+                max_evaluation_backlog,
+                strategy,
+            })
+            .synchronous(true)
+            .build();
+        rsp_engine.initialize().unwrap();
+        let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+        for timestamp in [100, 1100, 2100, 3100, 4100] {
+            stream
+                .add_quads(vec![reading(timestamp)], timestamp)
+                .unwrap();
+        }
+        rsp_engine
+    };
+
+    let thinned = engine(None, Some(0), rsp_rs::SheddingStrategy::EveryNthReport(2));
+    let closes: Vec<i64> = thinned
+        .poll_results()
+        .iter()
+        .map(|result| result.timestamp_to)
+        .collect();
+    assert_eq!(closes, vec![1000, 3000]);
+    assert_eq!(thinned.metrics().reports_shed, 2);
+
+    let skipped = engine(Some(0), None, rsp_rs::SheddingStrategy::SkipEvaluations);
+    assert!(skipped.poll_results().is_empty());
+    assert_eq!(skipped.metrics().reports_shed, 4);
+
+    let sampled = engine(
+        Some(0),
+        None,
+        rsp_rs::SheddingStrategy::SampleInput { drop_percent: 100 },
+    );
+    assert!(sampled.poll_results().is_empty());
+    let metrics = sampled.metrics();
+    assert_eq!((metrics.events_shed, metrics.events_ingested), (5, 0));
+
+    // Below the thresholds nothing is shed
+    let normal = engine(
+        Some(100),
+        Some(100),
+        rsp_rs::SheddingStrategy::SkipEvaluations,
+    );
+    assert_eq!(normal.poll_results().len(), 4);
+    assert_eq!(normal.metrics().reports_shed, 0);
+}
+
+#[test]
+fn test_landmark_window_compacts_raw_quads() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT (COUNT(?value) AS ?n) (SUM(?value) AS ?total)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE UNBOUNDED STEP 1000 RETAIN 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    assert!(rsp_engine.explain().unwrap().windows[0].incremental);
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for (value, timestamp) in [(1, 100), (2, 600), (3, 1100), (4, 2100), (5, 3100)] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    // Every step reports the aggregates since the landmark, stamped with the step
+    let results: Vec<(i64, i64, f64, f64)> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| {
+            let n = result.solution.number("n").unwrap();
+            let total = result.solution.number("total").unwrap();
+            (result.timestamp_from, result.timestamp_to, n, total)
+        })
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            (0, 1000, 2.0, 3.0),
+            (1000, 2000, 3.0, 6.0),
+            (2000, 3000, 4.0, 10.0)
+        ]
+    );
+
+    // Only the quads of the last second before the last report are kept
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let window = window.lock().unwrap();
+    assert!(window.is_landmark());
+    assert_eq!(window.retention(), Some(1000));
+    assert_eq!(window.stats().quads_per_window, vec![(0, i64::MAX, 2)]);
+}
+
+#[test]
+fn test_window_callback_panics_are_recovered() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ?p ?o }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .thread_name_prefix("sensors")
+        .build();
+    rsp_engine.initialize().unwrap();
+    let errors = rsp_engine.errors();
+
+    // The first report panics, the next ones report the thread they run on
+    let (tx, rx) = mpsc::channel();
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let reports = std::sync::atomic::AtomicUsize::new(0);
+    window
+        .lock()
+        .unwrap()
+        .subscribe(rsp_rs::StreamType::RStream, move |_| {
+            if reports.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 0 {
+                panic!("broken callback");
+            }
+            let _ = tx.send(thread::current().name().map(str::to_string));
+        });
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let add = |timestamp: i64| {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    };
+    add(100);
+    add(1100);
+    let error = errors.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(error.thread, "sensors-window-https://rsp.rs/w1");
+    assert_eq!(error.message, "broken callback");
+
+    // The ingestion loop was restarted and the window is still usable
+    add(2100);
+    let thread = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(thread.as_deref(), Some("sensors-window-https://rsp.rs/w1"));
+    assert!(window.lock().is_ok());
+}
+
+#[test]
+fn test_engine_events() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ?p ?o }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .max_lateness(0)
+        .build();
+    let events = rsp_engine.subscribe_events();
+    rsp_engine.initialize().unwrap();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let add = |timestamp: i64| {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    };
+    add(100);
+    add(1100);
+    // Older than the report at 1100
+    add(500);
+
+    let events: Vec<_> = events.try_iter().collect();
+    let window = "https://rsp.rs/w1".to_string();
+    assert!(events.contains(&rsp_rs::EngineEvent::WatermarkAdvanced {
+        window: window.clone(),
+        watermark: 1100,
+    }));
+    assert!(events.contains(&rsp_rs::EngineEvent::WindowEvicted {
+        window: window.clone(),
+        open: 0,
+        close: 1000,
+    }));
+    assert!(events.contains(&rsp_rs::EngineEvent::LateEventDropped {
+        window,
+        stream: "https://rsp.rs/stream1".to_string(),
+        timestamp: 500,
+    }));
+    // The query parses, so nothing is reported about it
+    assert!(
+        !events
+            .iter()
+            .any(|event| matches!(event, rsp_rs::EngineEvent::ParseWarning(_)))
+    );
+}
+
+#[test]
+fn test_invalid_events_are_quarantined() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:value ?value }
+        }
+    "#;
+    let shapes = rsp_rs::ShaclShapes::parse(
+        r#"
+        PREFIX sh: <http://www.w3.org/ns/shacl#>
+        PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+        PREFIX ex: <https://rsp.rs/>
+        ex:ValueShape sh:targetSubjectsOf ex:value ;
+            sh:property [ sh:path ex:value ; sh:datatype xsd:integer ; sh:maxInclusive 100 ] .
+        "#,
+        oxigraph::io::RdfFormat::Turtle,
+    )
+    .unwrap();
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .validate_stream(
+            "https://rsp.rs/stream1",
+            shapes,
+            rsp_rs::ValidationPolicy::Quarantine,
+        )
+        .build();
+    rsp_engine.initialize().unwrap();
+    let dead_letters = rsp_engine.dead_letters();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let add = |value: i64, timestamp: i64| {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    };
+    add(20, 100);
+    add(999, 200);
+    add(30, 1100);
+
+    let letter = dead_letters.try_recv().unwrap();
+    assert_eq!(letter.stream, "https://rsp.rs/stream1");
+    assert_eq!(letter.timestamp, Some(200));
+    assert_eq!(letter.reason.code(), "invalid");
+    let rsp_rs::DropReason::Invalid(violations) = &letter.reason else {
+        panic!("Expected an invalid event, got {:?}", letter.reason);
+    };
+    assert_eq!(violations.len(), 1);
+    assert!(dead_letters.try_recv().is_err());
+
+    // Only the valid reading of the first window reaches the query
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert!(results[0].bindings.contains("20"));
+}
+
+#[test]
+fn test_dropped_events_are_dead_lettered() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ?p ?o }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .max_lateness(0)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let dead_letters = rsp_engine.dead_letters();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let event = "<https://rsp.rs/sensor1> <https://rsp.rs/value> 1 .";
+    let format = oxigraph::io::RdfFormat::NTriples;
+    stream.add_rdf(event, format, 100).unwrap();
+    stream.add_rdf(event, format, 1100).unwrap();
+    // Older than the report at 1100
+    stream.add_rdf(event, format, 500).unwrap();
+    let invalid = "<https://rsp.rs/sensor1> .";
+    assert!(stream.add_rdf(invalid, format, 1200).is_err());
+
+    let late = dead_letters.try_recv().unwrap();
+    assert_eq!(late.timestamp, Some(500));
+    assert_eq!(late.quads.len(), 1);
+    assert_eq!(
+        late.reason,
+        rsp_rs::DropReason::Late {
+            window: "https://rsp.rs/w1".to_string(),
+            watermark: 1100,
+        }
+    );
+    let unparsable = dead_letters.try_recv().unwrap();
+    assert_eq!(unparsable.reason.code(), "unparsable");
+    assert_eq!(unparsable.document.as_deref(), Some(invalid));
+    assert!(unparsable.quads.is_empty());
+    assert!(dead_letters.try_recv().is_err());
+}
+
+#[test]
+fn test_replay_recorded_input() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT (SUM(?value) AS ?total)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 500 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:value ?value }
+        }
+    "#;
+    let log = std::env::temp_dir().join(format!("rsp-rs-replay-{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log);
+    let run = |record: bool| {
+        let mut builder = rsp_rs::RSPEngineBuilder::new(query.to_string()).synchronous(true);
+        if record {
+            builder = builder.record_input(&log);
+        }
+        let mut rsp_engine = builder.build();
+        rsp_engine.initialize().unwrap();
+        rsp_engine
+    };
+    let results = |rsp_engine: &rsp_rs::RSPEngine| {
+        let results = rsp_engine.poll_results().into_iter();
+        results
+            .map(|result| (result.bindings, result.timestamp_from, result.timestamp_to))
+            .collect::<Vec<_>>()
+    };
+
+    let recorded = run(true);
+    let stream = recorded.get_stream("https://rsp.rs/stream1").unwrap();
+    for (value, timestamp) in [(1, 100), (2, 700), (4, 300), (8, 1200), (16, 2600)] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    let expected = results(&recorded);
+    assert!(!expected.is_empty());
+
+    let input = rsp_rs::InputLog::open(&log).unwrap();
+    assert_eq!(input.events.len(), 5);
+    assert_eq!(input.events[2].timestamp, 300);
+    let replayed = run(false);
+    assert_eq!(replayed.replay(&input), Ok(5));
+    assert_eq!(results(&replayed), expected);
+    std::fs::remove_file(&log).unwrap();
+}
+
+#[test]
+fn test_window_enriched_from_static_lookup() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?room ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value ; ex:room ?room }
+        }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let fact =
+        |s: &str, p: &str, o: &str| Quad::new(node(s), node(p), node(o), GraphName::DefaultGraph);
+    let reading = |sensor: &str, value: i64| {
+        let value = Literal::from(value);
+        Quad::new(node(sensor), node("value"), value, GraphName::DefaultGraph)
+    };
+    let rooms = rsp_rs::StaticLookup::predicates([node("room")]);
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .enrich_window("https://rsp.rs/w1", rooms)
+        .build();
+    rsp_engine.add_static_data(fact("sensor1", "room", "kitchen"));
+    rsp_engine.add_static_data(fact("sensor1", "owner", "alice"));
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    stream.add_quads(vec![reading("sensor1", 20)], 100).unwrap();
+    // The table follows changes of the static data
+    rsp_engine.add_static_data(fact("sensor2", "room", "hall"));
+    stream.add_quads(vec![reading("sensor2", 5)], 200).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 1100)
+        .unwrap();
+
+    let mut rooms: Vec<Term> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| result.solution.get("room").unwrap().clone())
+        .collect();
+    rooms.sort_by_key(Term::to_string);
+    assert_eq!(rooms, vec![node("hall").into(), node("kitchen").into()]);
+}
+
+#[test]
+fn test_queries_share_windows_and_patterns() {
+    let query = |output: &str, filter: &str, range: i64| {
+        format!(
+            r#"
+            PREFIX ex: <https://rsp.rs/>
+            REGISTER RStream <{}> AS
+            SELECT ?sensor ?value
+            FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE {} STEP 1000 ALIGN 0]
+            WHERE {{
+                WINDOW ex:w1 {{ ?sensor ex:value ?value }}
+                {}
+            }}
+            "#,
+            output, range, filter
+        )
+    };
+    let sharing = rsp_rs::PlanSharing::new();
+    let engine = |query: String| {
+        let mut engine = rsp_rs::RSPEngineBuilder::new(query)
+            .synchronous(true)
+            .share_plans(&sharing)
+            .build();
+        engine.initialize().unwrap();
+        engine
+    };
+    let all = engine(query("all", "", 1000));
+    let high = engine(query("high", "FILTER(?value > 10)", 1000));
+    let longer = engine(query("longer", "", 2000));
+
+    let window = |engine: &RSPEngine| engine.get_window("https://rsp.rs/w1").unwrap();
+    assert!(std::sync::Arc::ptr_eq(&window(&all), &window(&high)));
+    assert!(!std::sync::Arc::ptr_eq(&window(&all), &window(&longer)));
+    assert_eq!(sharing.window_count(), 2);
+
+    // Events added once reach every query over the shared window
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let stream = all.get_stream("https://rsp.rs/stream1").unwrap();
+    for (sensor, value, timestamp) in [("s1", 20, 100), ("s2", 5, 200)] {
+        let quad = Quad::new(
+            node(sensor),
+            node("value"),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    all.close_stream("https://rsp.rs/stream1", 1100).unwrap();
+
+    let sensors = |engine: &RSPEngine| {
+        let mut sensors: Vec<String> = engine
+            .poll_results()
+            .iter()
+            .map(|result| result.solution.get("sensor").unwrap().to_string())
+            .collect();
+        sensors.sort();
+        sensors
+    };
+    let expected = ["<https://rsp.rs/s1>", "<https://rsp.rs/s2>"];
+    assert_eq!(sensors(&all), expected);
+    assert_eq!(sensors(&high), ["<https://rsp.rs/s1>"]);
+    assert!(sensors(&longer).is_empty());
+
+    drop(longer);
+    assert_eq!(sharing.window_count(), 1);
+}
+
+#[test]
+fn test_results_carry_evaluation_and_ingestion_latency() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?s
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ex:predicate ?o }
+        }
+    "#;
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let event = |subject: &str| {
+        vec![Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", subject)).unwrap(),
+            NamedNode::new("https://rsp.rs/predicate").unwrap(),
+            NamedNode::new("https://rsp.rs/object").unwrap(),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    stream.add_quads(event("s1"), 100).unwrap();
+    stream.add_quads(event("s2"), 1100).unwrap();
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    let evaluation = results[0].evaluation_latency.unwrap();
+    let ingestion = results[0].ingestion_latency.unwrap();
+    // The closing event was received before its window instance reported
+    assert!(ingestion >= evaluation);
+}
+
+#[test]
+fn test_multiset_window_counts_duplicate_events() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT (COUNT(?value) AS ?n) (AVG(?value) AS ?avg)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+    let aggregates = |multiset: bool, incremental: bool| {
+        let mut builder = rsp_rs::RSPEngineBuilder::new(query.to_string())
+            .synchronous(true)
+            .incremental_aggregation(incremental);
+        if multiset {
+            builder = builder.window_multiset("https://rsp.rs/w1");
+        }
+        let mut rsp_engine = builder.build();
+        rsp_engine.initialize().unwrap();
+        let stream_uri = "https://rsp.rs/stream1";
+        let stream = rsp_engine.get_stream(stream_uri).unwrap();
+        // The same reading is observed twice
+        for (value, timestamp) in [(20, 100), (20, 300), (26, 500)] {
+            let quad = Quad::new(
+                NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+                NamedNode::new("https://rsp.rs/value").unwrap(),
+                Literal::from(value),
+                GraphName::DefaultGraph,
+            );
+            stream.add_quads(vec![quad], timestamp).unwrap();
+        }
+        rsp_engine.close_stream(stream_uri, 1100).unwrap();
+        let results = rsp_engine.poll_results();
+        assert_eq!(results.len(), 1);
+        let n = results[0].solution.number("n").unwrap();
+        let avg = results[0].solution.number("avg").unwrap();
+        (n, avg)
+    };
+
+    for incremental in [true, false] {
+        assert_eq!(aggregates(false, incremental), (2.0, 23.0));
+        assert_eq!(aggregates(true, incremental), (3.0, 22.0));
+    }
+}
+
+#[test]
+fn test_window_ttl_expires_stale_observations() {
+    use rsp_rs::QuadTtl;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?value ?room
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value ; ex:room ?room }
+        }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let ttl = QuadTtl::new().with_predicate_ttl(node("value"), 600);
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .window_ttl("https://rsp.rs/w1", ttl)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream_uri = "https://rsp.rs/stream1";
+    let stream = rsp_engine.get_stream(stream_uri).unwrap();
+    let fact = |predicate: &str, object: Term| {
+        Quad::new(
+            node("sensor1"),
+            node(predicate),
+            object,
+            GraphName::DefaultGraph,
+        )
+    };
+
+    // The room of the sensor is context, kept for the whole window
+    let room = fact("room", node("kitchen").into());
+    let value = |value: i64| fact("value", Literal::from(value).into());
+    stream.add_quads(vec![room, value(1)], 100).unwrap();
+    stream.add_quads(vec![value(2)], 600).unwrap();
+    rsp_engine.close_stream(stream_uri, 1100).unwrap();
+
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].solution.number("value"), Some(2.0));
+    let kitchen = Term::from(node("kitchen"));
+    assert_eq!(results[0].solution.get("room"), Some(&kitchen));
+}
+
+#[test]
+fn test_temporal_join_of_two_streams() {
+    use rsp_rs::TemporalJoin;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:gps, ex:accelerometer [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let join = |right: &str| {
+        TemporalJoin::new(100)
+            .with_prefix("ex", "https://rsp.rs/")
+            .left("https://rsp.rs/gps", "?vehicle ex:position ?position")
+            .unwrap()
+            .right(right, "?vehicle ex:acceleration ?acceleration")
+            .unwrap()
+    };
+    let window = "https://rsp.rs/w1";
+    let matches = rsp_engine
+        .start_processing_joins(window, join("https://rsp.rs/accelerometer"))
+        .unwrap();
+    let unknown = join("https://rsp.rs/stream3");
+    assert!(rsp_engine.start_processing_joins(window, unknown).is_err());
+
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let readings = [
+        ("gps", "car1", "position", 100),
+        ("accelerometer", "car1", "acceleration", 150),
+        ("accelerometer", "car1", "acceleration", 400),
+        ("gps", "car2", "position", 600),
+        ("accelerometer", "car1", "acceleration", 650),
+        ("gps", "car1", "position", 1100),
+    ];
+    for (stream, vehicle, predicate, timestamp) in readings {
+        let quad = Quad::new(
+            node(vehicle),
+            node(predicate),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        let stream = rsp_engine
+            .get_stream(&format!("https://rsp.rs/{}", stream))
+            .unwrap();
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    // The readings of car1 at 400 and 650 have no position of car1 within 100 ms
+    let matches: Vec<_> = matches.try_iter().collect();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].timestamps, (100, 150));
+    let vehicle = matches[0].binding.get("vehicle");
+    assert_eq!(vehicle, Some(&node("car1").into()));
+}
+
+#[test]
+fn test_stream_rate_limit_drops_excess_events() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .stream_rate_limit(
+            "https://rsp.rs/stream1",
+            rsp_rs::RateLimit {
+                max_quads_per_second: None,
+                max_events_per_second: Some(2),
+                action: rsp_rs::RateLimitAction::Drop,
+            },
+        )
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+
+    // A runaway producer adds five events at once, only a burst of two passes
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [100, 200, 300, 400, 500] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/temperature").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 1500)
+        .unwrap();
+
+    let values: Vec<f64> = rsp_engine
+        .poll_results()
+        .iter()
+        .filter_map(|result| result.solution.number("value"))
+        .collect();
+    assert_eq!(values.len(), 2);
+    assert_eq!(rsp_engine.metrics().events_rate_limited, 3);
+}
+
+#[test]
+fn test_rate_limited_events_are_dead_lettered() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:temperature ?value }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .stream_rate_limit(
+            "https://rsp.rs/stream1",
+            rsp_rs::RateLimit {
+                max_quads_per_second: None,
+                max_events_per_second: Some(1),
+                action: rsp_rs::RateLimitAction::Drop,
+            },
+        )
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let dead_letters = rsp_engine.dead_letters();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [100, 200] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/temperature").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    let letter = dead_letters.try_recv().unwrap();
+    assert_eq!(letter.reason, rsp_rs::DropReason::RateLimited);
+    assert_eq!(letter.reason.code(), "rate_limited");
+    assert_eq!(letter.timestamp, Some(200));
+    assert_eq!(letter.quads.len(), 1);
+    assert!(dead_letters.try_recv().is_err());
+}
+
+#[test]
+fn test_close_all_streams_without_sentinel_quad() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?s ?p ?o
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1, ex:stream2 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?s ?p ?o }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .close_sentinel(None)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+
+    for (stream, timestamp) in [("stream1", 100), ("stream2", 200)] {
+        let quad = Quad::new(
+            NamedNode::new(format!("https://rsp.rs/{}", stream)).unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        let stream = rsp_engine
+            .get_stream(&format!("https://rsp.rs/{}", stream))
+            .unwrap();
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+    rsp_engine.close_all_streams(1500).unwrap();
+
+    let mut subjects: Vec<String> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|result| result.solution.get("s").unwrap().to_string())
+        .collect();
+    subjects.sort();
+    assert_eq!(
+        subjects,
+        vec!["<https://rsp.rs/stream1>", "<https://rsp.rs/stream2>"]
+    );
+
+    // Without a sentinel quad, the instance closed next is empty and doesn't report
+    rsp_engine.close_all_streams(2500).unwrap();
+    assert!(rsp_engine.poll_results().is_empty());
+}
+
+#[test]
+fn test_daily_windows_aligned_on_local_midnight() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?s ?p ?o
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 86400000 STEP 86400000 ALIGN CALENDAR "CET-1CEST,M3.5.0,M10.5.0/3"]
+        WHERE {
+            WINDOW ex:w1 { ?s ?p ?o }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let (tx, rx) = mpsc::channel();
+    window
+        .lock()
+        .unwrap()
+        .subscribe(rsp_rs::StreamType::RStream, move |content| {
+            tx.send(content.window_bounds.unwrap()).unwrap();
+        });
+
+    // Hours since midnight UTC on 27 October 2024, when daylight saving time ends
+    let hour = |hours: i64| 1_729_987_200_000 + hours * 3_600_000;
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [hour(-12), hour(12), hour(36)] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    // Days start at midnight in Paris, and 27 October lasts 25 hours
+    let bounds: Vec<(i64, i64)> = rx.try_iter().collect();
+    assert_eq!(bounds, vec![(hour(-26), hour(-2)), (hour(-2), hour(23))]);
+
+    let invalid = query.replace("CET-1CEST,", "CET-1CEST;");
+    let mut rsp_engine = rsp_rs::RSPEngine::new(invalid);
+    assert!(rsp_engine.initialize().is_err());
+    let iana = query.replace("CET-1CEST,M3.5.0,M10.5.0/3", "Europe/Paris");
+    let error = rsp_rs::RSPEngine::new(iana).initialize().unwrap_err();
+    assert!(error.contains("only POSIX TZ strings"), "{}", error);
+    for not_tumbling in [
+        query.replace("STEP 86400000", "STEP 3600000"),
+        query.replace("RANGE 86400000", "RANGE UNBOUNDED"),
+        query.replace("ALIGN", "REPORT NON_EMPTY_CONTENT ALIGN"),
+    ] {
+        let error = rsp_rs::RSPEngine::new(not_tumbling)
+            .initialize()
+            .unwrap_err();
+        assert!(error.contains("Cannot align window"), "{}", error);
+    }
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .window_calendar("https://rsp.rs/w1", rsp_rs::TimeZone::utc())
+        .build();
+    rsp_engine.initialize().unwrap();
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    let calendar = window.lock().unwrap().calendar().cloned();
+    assert_eq!(calendar, Some(rsp_rs::TimeZone::utc()));
+}
+
+#[test]
+fn test_absence_detection_with_not_exists() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:heartbeats [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            ?sensor a ex:Sensor .
+            FILTER NOT EXISTS { WINDOW ex:w1 { ?sensor ex:heartbeat ?t } }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    assert!(rsp_engine.parsed_query().s2r[0].emit_empty_windows);
+
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    for sensor in ["s1", "s2"] {
+        rsp_engine.add_static_data(Quad::new(
+            node(sensor),
+            vocab::rdf::TYPE,
+            node("Sensor"),
+            GraphName::DefaultGraph,
+        ));
+    }
+    let stream = rsp_engine.get_stream("https://rsp.rs/heartbeats").unwrap();
+    let heartbeat = |sensor: &str, timestamp: i64| {
+        vec![Quad::new(
+            node(sensor),
+            node("heartbeat"),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    stream.add_quads(heartbeat("s1", 100), 100).unwrap();
+    stream.add_quads(heartbeat("s2", 200), 200).unwrap();
+    stream.add_quads(heartbeat("s1", 1100), 1100).unwrap();
+    // Both sensors sent a heartbeat in [0, 1000)
+    assert!(rsp_engine.poll_results().is_empty());
+
+    stream.add_quads(heartbeat("s1", 3500), 3500).unwrap();
+    let results = rsp_engine.poll_results();
+    // s2 is missing from [1000, 2000), and no heartbeat arrived in [2000, 3000)
+    let missing: Vec<_> = results
+        .iter()
+        .map(|r| (r.timestamp_from, r.bindings.contains("s1")))
+        .collect();
+    assert_eq!(results.len(), 3);
+    assert!(missing.contains(&(1100, false)));
+    assert!(missing.contains(&(2000, true)));
+    assert!(missing.contains(&(2000, false)));
+}
+
+#[test]
+fn test_empty_windows_report_zero_counts() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT (COUNT(?value) AS ?readings)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value }
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .window_emit_empty_windows("https://rsp.rs/w1", true)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let reading = |value: i64| {
+        vec![Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    stream.add_quads(reading(1), 100).unwrap();
+    stream.add_quads(reading(2), 3500).unwrap();
+
+    // [0, 1000) has one reading, [1000, 2000) and [2000, 3000) none
+    let results = rsp_engine.poll_results();
+    let windows: Vec<_> = results
+        .iter()
+        .map(|r| (r.timestamp_from, r.bindings.contains("\"0\"")))
+        .collect();
+    assert_eq!(windows, vec![(100, false), (1000, true), (2000, true)]);
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .emit_empty_windows(true)
+        .window_emit_empty_windows("https://rsp.rs/w1", false)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let window = rsp_engine.get_window("https://rsp.rs/w1").unwrap();
+    assert!(!window.lock().unwrap().emits_empty_windows());
+}
+
+#[test]
+fn test_window_bound_functions() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        PREFIX rsp: <https://rsp.rs/ns#>
+        REGISTER RStream <output> AS
+        SELECT ?start ?end ?age
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 500 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:time ?t }
+            BIND(rsp:windowStart() AS ?start)
+            BIND(rsp:windowEnd() AS ?end)
+            BIND(?end - ?t AS ?age)
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    for timestamp in [100, 700, 1200] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/time").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    // 1200 closes [0, 1000), holding the readings at 100 and 700
+    let mut rows: Vec<_> = rsp_engine
+        .poll_results()
+        .iter()
+        .map(|r| {
+            let number = |variable| r.solution.number(variable).unwrap() as i64;
+            (number("start"), number("end"), number("age"))
+        })
+        .collect();
+    rows.sort();
+    assert_eq!(rows, vec![(0, 1000, 300), (0, 1000, 900)]);
+}
+
+#[test]
+fn test_latest_observation_with_annotated_timestamps() {
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+        PREFIX rsp: <https://rsp.rs/ns#>
+        REGISTER RStream <output> AS
+        SELECT ?value ?t
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 {
+                ?sensor ex:value ?value .
+                ?statement rdf:subject ?sensor ; rdf:object ?value ; rsp:timestamp ?t
+            }
+        }
+        ORDER BY DESC(?t) LIMIT 1
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .annotate_timestamps(true)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    // The readings are added out of their value order
+    for (value, timestamp) in [(7, 100), (3, 400), (5, 1200)] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(value),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].solution.number("value"), Some(3.0));
+    assert_eq!(results[0].solution.number("t"), Some(400.0));
+}
+
+#[cfg(feature = "rdf-star")]
+#[test]
+fn test_annotated_observations_with_rdf_star() {
+    use oxigraph::io::RdfFormat;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT ?sensor ?value ?confidence
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000 ALIGN 0]
+        WHERE {
+            WINDOW ex:w1 { ?sensor ex:value ?value {| ex:confidence ?confidence |} }
+            FILTER(?confidence > 0.5)
+        }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+
+    let events = [
+        ("ex:sensor1 ex:value 21 {| ex:confidence 0.9 |} .", 100),
+        ("ex:sensor2 ex:value 35 {| ex:confidence 0.2 |} .", 400),
+        ("ex:sensor1 ex:value 22 {| ex:confidence 0.8 |} .", 1200),
+    ];
+    for (document, timestamp) in events {
+        let document = format!("PREFIX ex: <https://rsp.rs/>\n{}", document);
+        stream
+            .add_rdf(&document, RdfFormat::Turtle, timestamp)
+            .unwrap();
+    }
+
+    // [0, 1000) holds both annotated readings, only sensor1 is confident enough
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].solution.number("value"), Some(21.0));
+    assert_eq!(results[0].solution.number("confidence"), Some(0.9));
+}
+
+#[test]
+fn test_sequence_pattern_over_sliding_windows() {
+    use rsp_rs::SequencePattern;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 500 ALIGN 0]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let pattern = SequencePattern::new(500)
+        .with_prefix("ex", "https://rsp.rs/")
+        .then("?door ex:state ex:open ; ex:room ?room")
+        .unwrap()
+        .then("?alarm ex:room ?room")
+        .unwrap();
+    let matches = rsp_engine
+        .start_processing_sequences("https://rsp.rs/w1", pattern.clone())
+        .unwrap();
+    assert!(
+        rsp_engine
+            .start_processing_sequences("https://rsp.rs/w2", pattern)
+            .is_err()
+    );
+
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let triple =
+        |s: &str, p: &str, o: &str| Quad::new(node(s), node(p), node(o), GraphName::DefaultGraph);
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let events = vec![
+        (
+            vec![
+                triple("door1", "state", "open"),
+                triple("door1", "room", "kitchen"),
+            ],
+            100,
+        ),
+        (vec![triple("alarm1", "room", "kitchen")], 400),
+        (vec![triple("alarm2", "room", "hall")], 600),
+        (vec![triple("sensor1", "state", "idle")], 1100),
+        (vec![triple("sensor1", "state", "idle")], 1600),
+    ];
+    for (quads, timestamp) in events {
+        stream.add_quads(quads, timestamp).unwrap();
+    }
+
+    // [-500, 500) and [0, 1000) both hold the sequence, which is sent once
+    let matches: Vec<_> = matches.try_iter().collect();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].timestamps, vec![100, 400]);
+    assert_eq!(
+        matches[0].binding.get("alarm"),
+        Some(&node("alarm1").into())
+    );
+}
+
+#[test]
+fn test_stream_gap_monitor() {
+    use rsp_rs::{GapThreshold, StreamGap};
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let gaps = rsp_engine
+        .get_stream("https://rsp.rs/stream1")
+        .unwrap()
+        .monitor_gaps(GapThreshold::EventTime(2000));
+
+    // Another clone of the stream is monitored too
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    for timestamp in [0, 1000, 2500, 6000, 7000] {
+        let quad = Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/heartbeat").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        );
+        stream.add_quads(vec![quad], timestamp).unwrap();
+    }
+
+    let gaps: Vec<_> = gaps.try_iter().collect();
+    assert_eq!(
+        gaps,
+        vec![StreamGap::EventTime {
+            stream: "https://rsp.rs/stream1".to_string(),
+            last: 2500,
+            next: 6000,
+        }]
+    );
+}
+
+#[test]
+fn test_pause_and_resume_engine() {
+    use rsp_rs::PausePolicy;
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER RStream <output> AS
+        SELECT *
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 1000]
+        WHERE { WINDOW ex:w1 { ?s ?p ?o } }
+    "#;
+    let reading = |timestamp: i64| {
+        vec![Quad::new(
+            NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+            NamedNode::new("https://rsp.rs/value").unwrap(),
+            Literal::from(timestamp),
+            GraphName::DefaultGraph,
+        )]
+    };
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    stream.add_quads(reading(100), 100).unwrap();
+
+    // Events are buffered, so the window neither closes nor loses its content
+    rsp_engine.pause();
+    assert!(rsp_engine.is_paused());
+    stream.add_quads(reading(500), 500).unwrap();
+    stream.add_quads(reading(1200), 1200).unwrap();
+    assert!(rsp_engine.poll_results().is_empty());
+
+    rsp_engine.resume().unwrap();
+    assert!(!rsp_engine.is_paused());
+    assert_eq!(rsp_engine.poll_results().len(), 2);
+
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .synchronous(true)
+        .pause_policy(PausePolicy::Reject)
+        .build();
+    rsp_engine.initialize().unwrap();
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    rsp_engine.pause();
+    assert!(stream.add_quads(reading(100), 100).is_err());
+    rsp_engine.resume().unwrap();
+    stream.add_quads(reading(200), 200).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+    assert_eq!(rsp_engine.poll_results().len(), 1);
+}
+
+#[test]
+fn test_explain_compiled_pipeline() {
+    use rsp_rs::{Operator, ReportStrategy};
+
+    let query = r#"
+        PREFIX ex: <https://rsp.rs/>
+        REGISTER IStream <output> AS
+        SELECT ?s (COUNT(?o) AS ?count)
+        FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 1000 STEP 500 DELAY 100]
+        WHERE { WINDOW ex:w1 { ?s ex:value ?o } }
+        GROUP BY ?s
+    "#;
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .window_report_strategy("https://rsp.rs/w1", ReportStrategy::NonEmptyContent)
+        .build();
+    assert!(rsp_engine.explain().is_err());
+    rsp_engine.initialize().unwrap();
+
+    let plan = rsp_engine.explain().unwrap();
+    assert!(matches!(plan.operator, Operator::IStream));
+    assert_eq!(plan.output, "output");
+    assert_eq!(plan.windows.len(), 1);
+    let window = &plan.windows[0];
+    assert_eq!(window.name, "https://rsp.rs/w1");
+    assert_eq!(window.streams, vec!["https://rsp.rs/stream1".to_string()]);
+    assert_eq!((window.width, window.slide, window.delay), (1000, 500, 100));
+    assert_eq!(window.report, ReportStrategy::NonEmptyContent);
+    assert!(plan.sparql_query.contains("GRAPH"));
+    assert!(plan.evaluation_plan.is_some());
+
+    let text = plan.to_string();
+    assert!(text.contains("window <https://rsp.rs/w1> [RANGE 1000 STEP 500]"));
+    assert!(text.contains("stream <https://rsp.rs/stream1>"));
+}
+
+#[test]
+fn test_csparql_query_joins_stream_and_static_data() {
+    use rsp_rs::QueryDialect;
+
+    let query = r#"
+        REGISTER QUERY rooms AS
+        PREFIX ex: <https://rsp.rs/>
+        SELECT ?room ?value
+        FROM STREAM ex:stream1 [RANGE 1s STEP 1s]
+        WHERE { ?sensor ex:value ?value ; ex:room ?room }
+    "#;
+    let node = |name: &str| NamedNode::new(format!("https://rsp.rs/{}", name)).unwrap();
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::new(query.to_string())
+        .dialect(QueryDialect::CSparql)
+        .synchronous(true)
+        .build();
+    rsp_engine.add_static_data(Quad::new(
+        node("sensor1"),
+        node("room"),
+        node("kitchen"),
+        GraphName::DefaultGraph,
+    ));
+    rsp_engine.initialize().unwrap();
+
+    // The window is matched in the default graph, together with the static data
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let reading = Quad::new(
+        node("sensor1"),
+        node("value"),
+        Literal::from(21),
+        GraphName::DefaultGraph,
+    );
+    stream.add_quads(vec![reading], 100).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+
+    let results = rsp_engine.poll_results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(
+        results[0].solution.get("room"),
+        Some(&node("kitchen").into())
+    );
+}
+
+#[test]
+fn test_engine_from_built_query() {
+    use rsp_rs::{Operator, ParsedQuery, WindowDefinition};
+
+    let query = ParsedQuery::builder()
+        .register(Operator::RStream, "https://rsp.rs/output")
+        .window(WindowDefinition::new(
+            "https://rsp.rs/w1",
+            "https://rsp.rs/stream1",
+            1000,
+            1000,
+        ))
+        .sparql("SELECT ?s WHERE { GRAPH <https://rsp.rs/w1> { ?s ?p ?o } }")
+        .build()
+        .unwrap();
+    let mut rsp_engine = rsp_rs::RSPEngineBuilder::from_parsed_query(query)
+        .synchronous(true)
+        .build();
+    rsp_engine.initialize().unwrap();
+
+    let stream = rsp_engine.get_stream("https://rsp.rs/stream1").unwrap();
+    let quad = Quad::new(
+        NamedNode::new("https://rsp.rs/sensor1").unwrap(),
+        NamedNode::new("https://rsp.rs/value").unwrap(),
+        Literal::from(1),
+        GraphName::DefaultGraph,
+    );
+    stream.add_quads(vec![quad], 100).unwrap();
+    rsp_engine
+        .close_stream("https://rsp.rs/stream1", 5000)
+        .unwrap();
+    assert_eq!(rsp_engine.poll_results().len(), 1);
+}